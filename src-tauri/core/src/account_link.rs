@@ -0,0 +1,280 @@
+//! 同一账号的凭证识别
+//!
+//! 同一个人可能用同一账号添加了多个凭证（多个组织、重复登录、OAuth 与
+//! API Key 混用）。按 `owner_email`（不区分大小写）或 `user_id` 把凭证归为
+//! 同一账号，列表与用量统计按账号分组；同一账号的凭证又属于同一组织时
+//! 消耗的是同一份额度，实际容量并没有增加，此时发出一次
+//! `account_shared_quota` 提醒。
+
+use crate::credentials::DroidCredentials;
+use crate::events;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// 同一账号的凭证
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountGroup {
+    /// 账号标识（邮箱优先，否则为 user_id）
+    pub account: String,
+    pub owner_emails: Vec<String>,
+    pub user_ids: Vec<String>,
+    pub credential_ids: Vec<String>,
+    pub organization_ids: Vec<String>,
+    /// 该账号有两个及以上凭证的组织（这些凭证共享额度）
+    pub shared_organizations: Vec<String>,
+    pub usage_count: u64,
+    pub error_count: u64,
+}
+
+impl AccountGroup {
+    /// 是否关联了两个及以上凭证
+    pub fn is_linked(&self) -> bool {
+        self.credential_ids.len() > 1
+    }
+}
+
+lazy_static::lazy_static! {
+    /// 已提醒过的 账号 / 组织
+    static ref WARNED: Mutex<HashSet<(String, String)>> = Mutex::new(HashSet::new());
+}
+
+/// 凭证的账号标识（邮箱、user_id）
+fn identities(credential: &DroidCredentials) -> BTreeSet<String> {
+    let email = credential
+        .owner_email
+        .as_deref()
+        .map(|e| e.trim().to_lowercase())
+        .filter(|e| !e.is_empty())
+        .map(|e| format!("email:{}", e));
+    let user = credential
+        .user_id
+        .as_deref()
+        .filter(|u| !u.is_empty())
+        .map(|u| format!("user:{}", u));
+    email.into_iter().chain(user).collect()
+}
+
+/// 按账号分组（含只有一个凭证的账号；没有邮箱和 user_id 的凭证不归组）
+pub fn groups(credentials: &HashMap<String, DroidCredentials>) -> Vec<AccountGroup> {
+    let mut ids: Vec<&String> = credentials.keys().collect();
+    ids.sort();
+
+    // 邮箱或 user_id 任一相同即为同一账号，传递合并
+    let mut merged: Vec<(BTreeSet<String>, Vec<&String>)> = Vec::new();
+    for id in ids {
+        let keys = identities(&credentials[id]);
+        if keys.is_empty() {
+            continue;
+        }
+        let (overlapping, rest): (Vec<_>, Vec<_>) = merged
+            .into_iter()
+            .partition(|(existing, _)| !existing.is_disjoint(&keys));
+        let mut group = (keys, vec![id]);
+        for (keys, members) in overlapping {
+            group.0.extend(keys);
+            group.1.extend(members);
+        }
+        merged = rest;
+        merged.push(group);
+    }
+
+    let mut groups: Vec<AccountGroup> = merged
+        .into_iter()
+        .map(|(keys, mut members)| {
+            members.sort();
+            let strip = |prefix: &str| -> Vec<String> {
+                keys.iter()
+                    .filter_map(|k| k.strip_prefix(prefix))
+                    .map(str::to_string)
+                    .collect()
+            };
+            let mut orgs: BTreeMap<&str, usize> = BTreeMap::new();
+            let mut group = AccountGroup {
+                owner_emails: strip("email:"),
+                user_ids: strip("user:"),
+                ..Default::default()
+            };
+            for id in members {
+                let credential = &credentials[id];
+                group.credential_ids.push(id.clone());
+                group.usage_count += credential.usage_count;
+                group.error_count += credential.error_count;
+                if let Some(org) = credential.organization_id.as_deref() {
+                    *orgs.entry(org).or_default() += 1;
+                }
+            }
+            group.account = group
+                .owner_emails
+                .first()
+                .or(group.user_ids.first())
+                .cloned()
+                .unwrap_or_default();
+            group.organization_ids = orgs.keys().map(|o| o.to_string()).collect();
+            group.shared_organizations = orgs
+                .iter()
+                .filter(|(_, count)| **count > 1)
+                .map(|(org, _)| org.to_string())
+                .collect();
+            group
+        })
+        .collect();
+    groups.sort_by(|a, b| a.account.cmp(&b.account));
+    groups
+}
+
+/// 凭证 ID → 所属账号（只含关联了多个凭证的账号）
+pub fn linked_accounts(groups: &[AccountGroup]) -> HashMap<String, String> {
+    groups
+        .iter()
+        .filter(|g| g.is_linked())
+        .flat_map(|g| {
+            g.credential_ids
+                .iter()
+                .map(|id| (id.clone(), g.account.clone()))
+        })
+        .collect()
+}
+
+/// 凭证 ID → 账号（用量统计用；没有账号信息的凭证不在其中）
+pub fn account_map(groups: &[AccountGroup]) -> HashMap<String, String> {
+    groups
+        .iter()
+        .flat_map(|g| {
+            g.credential_ids
+                .iter()
+                .map(|id| (id.clone(), g.account.clone()))
+        })
+        .collect()
+}
+
+/// 同一账号在同一组织下有多个凭证时提醒一次，返回本次新提醒的 账号 / 组织
+///
+/// 凭证池每次修改后调用；不再共享额度的 账号 / 组织（凭证已删除）会清除
+/// 提醒记录，再次出现时重新提醒。
+pub fn check(credentials: &HashMap<String, DroidCredentials>) -> Vec<(String, String)> {
+    check_with(&mut WARNED.lock().unwrap(), credentials)
+}
+
+fn check_with(
+    warned: &mut HashSet<(String, String)>,
+    credentials: &HashMap<String, DroidCredentials>,
+) -> Vec<(String, String)> {
+    let groups = groups(credentials);
+    warned.retain(|(account, org)| {
+        groups
+            .iter()
+            .any(|g| &g.account == account && g.shared_organizations.contains(org))
+    });
+    let mut new = Vec::new();
+    for group in groups {
+        for org in &group.shared_organizations {
+            if !warned.insert((group.account.clone(), org.clone())) {
+                continue;
+            }
+            let ids: Vec<&String> = group
+                .credential_ids
+                .iter()
+                .filter(|id| credentials[*id].organization_id.as_deref() == Some(org))
+                .collect();
+            warn!(
+                "账号 {} 在组织 {} 下有 {} 个凭证，共享同一份额度",
+                group.account,
+                org,
+                ids.len()
+            );
+            events::emit(
+                "account_shared_quota",
+                format!(
+                    "{} 个凭证属于同一账号 {} 且在同一组织下，共享额度，不会增加实际容量",
+                    ids.len(),
+                    group.account
+                ),
+                serde_json::json!({
+                    "account": group.account,
+                    "organization_id": org,
+                    "credential_ids": ids,
+                }),
+            );
+            new.push((group.account.clone(), org.clone()));
+        }
+    }
+    new
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credential(email: Option<&str>, user: Option<&str>, org: Option<&str>) -> DroidCredentials {
+        DroidCredentials {
+            owner_email: email.map(str::to_string),
+            user_id: user.map(str::to_string),
+            organization_id: org.map(str::to_string),
+            usage_count: 1,
+            ..Default::default()
+        }
+    }
+
+    fn pool() -> HashMap<String, DroidCredentials> {
+        [
+            (
+                "a",
+                credential(Some("Dev@Example.com"), Some("user_1"), Some("org_1")),
+            ),
+            (
+                "b",
+                credential(Some("dev@example.com"), None, Some("org_2")),
+            ),
+            // 只有 user_id，经 a 与邮箱关联
+            ("c", credential(None, Some("user_1"), Some("org_1"))),
+            (
+                "d",
+                credential(Some("other@example.com"), None, Some("org_1")),
+            ),
+            ("e", credential(None, None, Some("org_1"))),
+        ]
+        .into_iter()
+        .map(|(id, c)| (format!("link-{}", id), c))
+        .collect()
+    }
+
+    #[test]
+    fn test_groups() {
+        let groups = groups(&pool());
+        assert_eq!(groups.len(), 2);
+        let linked = &groups[0];
+        assert_eq!(linked.account, "dev@example.com");
+        assert_eq!(linked.credential_ids, vec!["link-a", "link-b", "link-c"]);
+        assert_eq!(linked.user_ids, vec!["user_1"]);
+        assert_eq!(linked.organization_ids, vec!["org_1", "org_2"]);
+        assert_eq!(linked.shared_organizations, vec!["org_1"]);
+        assert_eq!(linked.usage_count, 3);
+        assert!(!groups[1].is_linked());
+
+        let accounts = linked_accounts(&groups);
+        assert_eq!(accounts.len(), 3);
+        let accounts = account_map(&groups);
+        assert_eq!(
+            accounts.get("link-d").map(String::as_str),
+            Some("other@example.com")
+        );
+    }
+
+    #[test]
+    fn test_check_warns_once() {
+        let mut warned = HashSet::new();
+        let mut pool = pool();
+        let expected = vec![("dev@example.com".to_string(), "org_1".to_string())];
+        assert_eq!(check_with(&mut warned, &pool), expected);
+        assert!(check_with(&mut warned, &pool).is_empty());
+
+        // 删除后不再共享，重新添加时再次提醒
+        let removed = pool.remove("link-c").unwrap();
+        assert!(check_with(&mut warned, &pool).is_empty());
+        assert!(warned.is_empty());
+        pool.insert("link-c".to_string(), removed);
+        assert_eq!(check_with(&mut warned, &pool), expected);
+    }
+}
@@ -0,0 +1,377 @@
+//! 应用锁
+//!
+//! 供多人共用一台电脑的用户使用：设置口令后，启动时或闲置超过
+//! `idle_timeout_secs` 后进入锁定状态，查看密钥、修改凭证等操作需要先用口令
+//! 解锁（代理请求照常转发，不受影响）。口令同时保护主密钥：存储中只保留用
+//! 口令派生密钥包装后的主密钥，启动后第一次解锁前无法解密任何机密。
+//! 口令本身不保存，只保存随机盐与校验值。
+
+use crate::auth::encryption::{decrypt_sensitive_data, encrypt_sensitive_data};
+use crate::auth::master_key;
+use crate::events;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use pbkdf2::pbkdf2_hmac_array;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+use tracing::{info, warn};
+
+/// 口令派生（PBKDF2-HMAC-SHA256）的迭代次数
+const KDF_ITERATIONS: u32 = 600_000;
+
+/// 连续失败多少次后开始退避
+const FREE_ATTEMPTS: u32 = 3;
+
+/// 失败退避的上限（秒）
+const MAX_BACKOFF_SECS: u64 = 300;
+
+/// 口令最短长度
+pub const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// 应用锁配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppLockConfig {
+    pub enabled: bool,
+    /// 闲置多久后自动锁定（秒），0 表示不自动锁定
+    pub idle_timeout_secs: u64,
+    /// 启动时处于锁定状态（主密钥受口令保护时始终如此）
+    pub lock_on_launch: bool,
+}
+
+impl Default for AppLockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_timeout_secs: 900,
+            lock_on_launch: true,
+        }
+    }
+}
+
+/// 保存在密钥存储中的口令记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockRecord {
+    pub salt: String,
+    pub verifier: String,
+    /// 用口令派生密钥加密的主密钥
+    pub wrapped_key: String,
+    /// PBKDF2 迭代次数
+    pub iterations: u32,
+}
+
+fn verifier(derived: &[u8; 32]) -> String {
+    hex::encode(
+        Sha256::new()
+            .chain_update(b"app-lock-verify:")
+            .chain_update(derived)
+            .finalize(),
+    )
+}
+
+impl LockRecord {
+    /// 用口令包装主密钥
+    pub fn create(passphrase: &str, master_key: &str) -> Result<Self> {
+        Self::create_with_iterations(passphrase, master_key, KDF_ITERATIONS)
+    }
+
+    fn create_with_iterations(passphrase: &str, master_key: &str, iterations: u32) -> Result<Self> {
+        let salt = hex::encode(rand::thread_rng().gen::<[u8; 16]>());
+        let derived =
+            pbkdf2_hmac_array::<Sha256, 32>(passphrase.as_bytes(), salt.as_bytes(), iterations);
+        Ok(Self {
+            verifier: verifier(&derived),
+            wrapped_key: encrypt_sensitive_data(master_key, &hex::encode(derived))?,
+            salt,
+            iterations,
+        })
+    }
+
+    fn derive(&self, passphrase: &str) -> Result<[u8; 32]> {
+        if self.iterations == 0 {
+            anyhow::bail!("口令记录缺少迭代次数");
+        }
+        Ok(pbkdf2_hmac_array::<Sha256, 32>(
+            passphrase.as_bytes(),
+            self.salt.as_bytes(),
+            self.iterations,
+        ))
+    }
+
+    /// 校验口令并解开主密钥
+    pub fn open(&self, passphrase: &str) -> Result<String> {
+        let derived = self.derive(passphrase)?;
+        let matches: bool = verifier(&derived)
+            .as_bytes()
+            .ct_eq(self.verifier.as_bytes())
+            .into();
+        if !matches {
+            anyhow::bail!("口令错误");
+        }
+        decrypt_sensitive_data(&self.wrapped_key, &hex::encode(derived))
+    }
+}
+
+fn load_record() -> Result<Option<LockRecord>> {
+    match master_key::read_wrapped_key()? {
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+        None => Ok(None),
+    }
+}
+
+/// 锁定状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppLockStatus {
+    pub enabled: bool,
+    pub has_passphrase: bool,
+    pub locked: bool,
+    /// 主密钥尚未解开（启动后未解锁过）
+    pub master_key_locked: bool,
+    pub idle_timeout_secs: u64,
+    #[serde(default)]
+    pub locked_at: Option<String>,
+}
+
+struct LockState {
+    locked_at: Option<DateTime<Utc>>,
+    last_activity: Instant,
+}
+
+/// 连续输错口令的次数与最近一次失败的时间
+#[derive(Default)]
+struct Failures {
+    count: u32,
+    last: Option<Instant>,
+}
+
+impl Failures {
+    /// 下次尝试前需要等待的时长
+    fn backoff(&self) -> Duration {
+        if self.count < FREE_ATTEMPTS {
+            return Duration::ZERO;
+        }
+        let secs = 1u64
+            .checked_shl(self.count - FREE_ATTEMPTS)
+            .unwrap_or(u64::MAX)
+            .min(MAX_BACKOFF_SECS);
+        Duration::from_secs(secs)
+    }
+
+    fn remaining(&self) -> Duration {
+        match self.last {
+            Some(last) => self.backoff().saturating_sub(last.elapsed()),
+            None => Duration::ZERO,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref STATE: Mutex<Option<LockState>> = Mutex::new(None);
+    static ref FAILURES: Mutex<Failures> = Mutex::new(Failures::default());
+}
+
+/// 校验口令；连续输错后按指数退避拒绝尝试
+fn open_record(record: &LockRecord, passphrase: &str) -> Result<String> {
+    let remaining = FAILURES.lock().unwrap().remaining();
+    if !remaining.is_zero() {
+        anyhow::bail!(
+            "口令错误次数过多，请 {} 秒后再试",
+            remaining.as_secs().max(1)
+        );
+    }
+    match record.open(passphrase) {
+        Ok(key) => {
+            *FAILURES.lock().unwrap() = Failures::default();
+            Ok(key)
+        }
+        Err(e) => {
+            let mut failures = FAILURES.lock().unwrap();
+            failures.count = failures.count.saturating_add(1);
+            failures.last = Some(Instant::now());
+            warn!("应用锁口令校验失败（连续 {} 次）", failures.count);
+            Err(e)
+        }
+    }
+}
+
+fn initial_state(config: &AppLockConfig) -> LockState {
+    let locked = (config.enabled && config.lock_on_launch) || master_key::is_locked();
+    LockState {
+        locked_at: locked.then(Utc::now),
+        last_activity: Instant::now(),
+    }
+}
+
+fn has_passphrase() -> bool {
+    matches!(load_record(), Ok(Some(_)))
+}
+
+/// 闲置超时则锁定
+fn check_idle(config: &AppLockConfig, state: &mut LockState) {
+    let timeout = Duration::from_secs(config.idle_timeout_secs);
+    if state.locked_at.is_none()
+        && config.idle_timeout_secs > 0
+        && state.last_activity.elapsed() >= timeout
+    {
+        state.locked_at = Some(Utc::now());
+        info!("闲置超过 {} 秒，应用已锁定", config.idle_timeout_secs);
+    }
+}
+
+/// 受保护的操作前调用：已锁定时返回错误，否则记录一次活动
+pub fn ensure_unlocked() -> Result<()> {
+    if master_key::is_locked() {
+        anyhow::bail!("应用已锁定，请先输入口令解锁");
+    }
+    let config = crate::config::get_config().app_lock;
+    if !config.enabled || !has_passphrase() {
+        return Ok(());
+    }
+    let mut guard = STATE.lock().unwrap();
+    let state = guard.get_or_insert_with(|| initial_state(&config));
+    check_idle(&config, state);
+    if state.locked_at.is_some() {
+        anyhow::bail!("应用已锁定，请先输入口令解锁");
+    }
+    state.last_activity = Instant::now();
+    Ok(())
+}
+
+/// 用口令解锁（同时解开受保护的主密钥）
+pub fn unlock(passphrase: &str) -> Result<()> {
+    let record = load_record()?.ok_or_else(|| anyhow::anyhow!("尚未设置应用锁口令"))?;
+    let key = open_record(&record, passphrase)?;
+    if master_key::is_locked() {
+        master_key::unlock(&key);
+    }
+    let config = crate::config::get_config().app_lock;
+    let mut guard = STATE.lock().unwrap();
+    let state = guard.get_or_insert_with(|| initial_state(&config));
+    state.locked_at = None;
+    state.last_activity = Instant::now();
+    info!("应用已解锁");
+    events::emit("app_unlocked", "应用已解锁", serde_json::json!({}));
+    Ok(())
+}
+
+/// 立即锁定（主密钥仍保留在内存中，代理请求不受影响）
+pub fn lock() {
+    let config = crate::config::get_config().app_lock;
+    let mut guard = STATE.lock().unwrap();
+    let state = guard.get_or_insert_with(|| initial_state(&config));
+    state.locked_at.get_or_insert_with(Utc::now);
+    info!("应用已锁定");
+}
+
+/// 设置或修改口令；已有口令时需提供当前口令
+pub fn set_passphrase(passphrase: &str, current: Option<&str>) -> Result<()> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        anyhow::bail!("口令至少需要 {} 个字符", MIN_PASSPHRASE_LEN);
+    }
+    let master = match load_record()? {
+        Some(record) => {
+            let current = current.ok_or_else(|| anyhow::anyhow!("修改口令需要提供当前口令"))?;
+            open_record(&record, current)?
+        }
+        None => master_key::encryption_key()?,
+    };
+    let record = LockRecord::create(passphrase, &master)?;
+    master_key::store_wrapped_key(&serde_json::to_string(&record)?)?;
+    info!("应用锁口令已设置");
+    Ok(())
+}
+
+/// 取消口令（主密钥恢复为明文保存）
+pub fn remove_passphrase(current: &str) -> Result<()> {
+    let record = load_record()?.ok_or_else(|| anyhow::anyhow!("尚未设置应用锁口令"))?;
+    let master = open_record(&record, current)?;
+    master_key::store_unwrapped_key(&master)?;
+    if master_key::is_locked() {
+        master_key::unlock(&master);
+    }
+    *STATE.lock().unwrap() = None;
+    info!("应用锁口令已取消");
+    Ok(())
+}
+
+/// 当前状态
+pub fn status() -> AppLockStatus {
+    let config = crate::config::get_config().app_lock;
+    let has_passphrase = has_passphrase();
+    let master_key_locked = master_key::is_locked();
+    let locked_at = if config.enabled && has_passphrase {
+        let mut guard = STATE.lock().unwrap();
+        let state = guard.get_or_insert_with(|| initial_state(&config));
+        check_idle(&config, state);
+        state.locked_at
+    } else {
+        None
+    };
+    AppLockStatus {
+        enabled: config.enabled,
+        has_passphrase,
+        locked: master_key_locked || locked_at.is_some(),
+        master_key_locked,
+        idle_timeout_secs: config.idle_timeout_secs,
+        locked_at: locked_at.map(|t| t.to_rfc3339()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_round_trip() {
+        let record =
+            LockRecord::create_with_iterations("correct horse", "master-key-hex", 1_000).unwrap();
+        assert_eq!(record.open("correct horse").unwrap(), "master-key-hex");
+        assert!(record.open("wrong horse").is_err());
+        assert!(!record.wrapped_key.contains("master-key-hex"));
+
+        // 同一口令每次使用不同的盐
+        let other =
+            LockRecord::create_with_iterations("correct horse", "master-key-hex", 1_000).unwrap();
+        assert_ne!(record.verifier, other.verifier);
+    }
+
+    #[test]
+    fn test_failure_backoff() {
+        let mut failures = Failures::default();
+        for _ in 0..FREE_ATTEMPTS {
+            assert!(failures.backoff().is_zero());
+            failures.count += 1;
+        }
+        assert_eq!(failures.backoff(), Duration::from_secs(1));
+        failures.count += 3;
+        assert_eq!(failures.backoff(), Duration::from_secs(8));
+        failures.count = 200;
+        assert_eq!(failures.backoff(), Duration::from_secs(MAX_BACKOFF_SECS));
+
+        failures.last = Some(Instant::now());
+        assert!(!failures.remaining().is_zero());
+        failures.last = Some(Instant::now() - Duration::from_secs(MAX_BACKOFF_SECS));
+        assert!(failures.remaining().is_zero());
+    }
+
+    #[test]
+    fn test_idle_lock() {
+        let config = AppLockConfig {
+            enabled: true,
+            idle_timeout_secs: 60,
+            lock_on_launch: false,
+        };
+        let mut state = initial_state(&config);
+        assert!(state.locked_at.is_none());
+        check_idle(&config, &mut state);
+        assert!(state.locked_at.is_none());
+
+        state.last_activity = Instant::now() - Duration::from_secs(61);
+        check_idle(&config, &mut state);
+        assert!(state.locked_at.is_some());
+    }
+}
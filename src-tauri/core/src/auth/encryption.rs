@@ -60,8 +60,7 @@ pub fn decrypt_sensitive_data(encrypted_text: &str, encryption_key: &str) -> Res
     }
 
     let iv = hex::decode(parts[0]).map_err(|e| anyhow::anyhow!("IV 解码失败: {}", e))?;
-    let ciphertext =
-        hex::decode(parts[1]).map_err(|e| anyhow::anyhow!("密文解码失败: {}", e))?;
+    let ciphertext = hex::decode(parts[1]).map_err(|e| anyhow::anyhow!("密文解码失败: {}", e))?;
 
     if iv.len() != 16 {
         anyhow::bail!("IV 长度无效");
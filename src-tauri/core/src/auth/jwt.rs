@@ -0,0 +1,82 @@
+//! Access Token (JWT) 解析
+//!
+//! WorkOS 签发的 access_token 是 JWT。这里只解码载荷（不校验签名），
+//! 用于读取真实的过期时间、组织 ID 和会话 ID。
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+/// JWT 载荷中关心的字段
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JwtClaims {
+    /// 过期时间（Unix 秒）
+    #[serde(default)]
+    pub exp: Option<i64>,
+    /// 签发时间（Unix 秒）
+    #[serde(default)]
+    pub iat: Option<i64>,
+    /// 用户 ID
+    #[serde(default)]
+    pub sub: Option<String>,
+    /// 组织 ID
+    #[serde(default)]
+    pub org_id: Option<String>,
+    /// 会话 ID
+    #[serde(default)]
+    pub sid: Option<String>,
+}
+
+impl JwtClaims {
+    /// 过期时间
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.exp.and_then(|exp| Utc.timestamp_opt(exp, 0).single())
+    }
+}
+
+/// 解码 JWT 载荷，不是 JWT 时返回 None
+pub fn decode_claims(token: &str) -> Option<JwtClaims> {
+    let mut parts = token.split('.');
+    let (_header, payload, _signature) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_token(payload: serde_json::Value) -> String {
+        format!(
+            "{}.{}.signature",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256"}"#),
+            URL_SAFE_NO_PAD.encode(payload.to_string())
+        )
+    }
+
+    #[test]
+    fn test_decode_claims() {
+        let token = make_token(serde_json::json!({
+            "exp": 1_900_000_000,
+            "org_id": "org_01H",
+            "sid": "session_01H",
+            "sub": "user_01H"
+        }));
+
+        let claims = decode_claims(&token).unwrap();
+        assert_eq!(claims.org_id.as_deref(), Some("org_01H"));
+        assert_eq!(claims.sid.as_deref(), Some("session_01H"));
+        assert_eq!(claims.expires_at().unwrap().timestamp(), 1_900_000_000);
+    }
+
+    #[test]
+    fn test_decode_non_jwt() {
+        assert!(decode_claims("opaque-token").is_none());
+        assert!(decode_claims("a.b.c").is_none());
+    }
+}
@@ -0,0 +1,231 @@
+//! 多密钥加密
+//!
+//! 密文格式为 `key_id$iv_hex:ciphertext_hex`，key_id 由密钥派生，因此同一份
+//! 存储中可以并存不同主密钥加密的条目（主密钥轮换、合并其他设备的配置时）。
+//! 新数据始终用当前主密钥加密；旧密钥加密的条目在下次写入凭证时重新加密。
+//! 不带 key_id 的旧格式密文依次尝试环中的全部密钥。
+//!
+//! 旧密钥用当前主密钥加密后保存在密钥存储中（`retired-keys`），重启后仍能
+//! 解密尚未迁移的条目。
+
+use super::encryption::{decrypt_sensitive_data, encrypt_sensitive_data};
+use super::master_key::encryption_key;
+use super::secret_store;
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tracing::warn;
+
+/// key_id 与密文之间的分隔符（不会出现在 hex 中）
+const KEY_ID_SEPARATOR: char = '$';
+
+/// 旧密钥列表在密钥存储中的名称
+pub const RETIRED_KEYS_SECRET: &str = "retired-keys";
+
+lazy_static::lazy_static! {
+    /// 除当前主密钥外仍可用于解密的旧密钥（首次使用时从密钥存储加载）
+    static ref RETIRED_KEYS: RwLock<Option<Vec<String>>> = RwLock::new(None);
+}
+
+/// 由密钥派生 key_id（不泄露密钥本身）
+pub fn key_id(key: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"droid-key-id:");
+    hasher.update(key.as_bytes());
+    hex::encode(&hasher.finalize()[..4])
+}
+
+/// 拆分密文中的 key_id，旧格式返回 None
+fn split_key_id(ciphertext: &str) -> (Option<&str>, &str) {
+    match ciphertext.split_once(KEY_ID_SEPARATOR) {
+        Some((id, rest)) => (Some(id), rest),
+        None => (None, ciphertext),
+    }
+}
+
+/// 密钥环
+#[derive(Debug, Clone)]
+pub struct KeyRing {
+    primary: String,
+    keys: HashMap<String, String>,
+}
+
+impl KeyRing {
+    /// 以主密钥创建
+    pub fn new(primary: &str) -> Self {
+        let id = key_id(primary);
+        Self {
+            primary: id.clone(),
+            keys: HashMap::from([(id, primary.to_string())]),
+        }
+    }
+
+    /// 添加仅用于解密的密钥，返回其 key_id
+    pub fn add(&mut self, key: &str) -> String {
+        let id = key_id(key);
+        self.keys
+            .entry(id.clone())
+            .or_insert_with(|| key.to_string());
+        id
+    }
+
+    /// 当前主密钥的 key_id
+    pub fn primary_id(&self) -> &str {
+        &self.primary
+    }
+
+    /// 用主密钥加密
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let encrypted = encrypt_sensitive_data(plaintext, &self.keys[&self.primary])?;
+        if encrypted.is_empty() {
+            return Ok(encrypted);
+        }
+        Ok(format!("{}{}{}", self.primary, KEY_ID_SEPARATOR, encrypted))
+    }
+
+    /// 解密任一密钥加密的数据
+    pub fn decrypt(&self, ciphertext: &str) -> Result<String> {
+        if ciphertext.is_empty() {
+            return Ok(String::new());
+        }
+        match split_key_id(ciphertext) {
+            (Some(id), encrypted) => {
+                let key = self
+                    .keys
+                    .get(id)
+                    .ok_or_else(|| anyhow::anyhow!("缺少密钥 {}，无法解密", id))?;
+                decrypt_sensitive_data(encrypted, key)
+            }
+            // 旧格式：主密钥优先，其余密钥依次尝试
+            (None, encrypted) => std::iter::once(&self.keys[&self.primary])
+                .chain(
+                    self.keys
+                        .iter()
+                        .filter(|(id, _)| **id != self.primary)
+                        .map(|(_, k)| k),
+                )
+                .find_map(|key| decrypt_sensitive_data(encrypted, key).ok())
+                .ok_or_else(|| anyhow::anyhow!("没有可解密该数据的密钥")),
+        }
+    }
+
+    /// 是否已由主密钥加密（空值视为无需处理）
+    pub fn is_current(&self, ciphertext: &str) -> bool {
+        ciphertext.is_empty() || split_key_id(ciphertext).0 == Some(self.primary.as_str())
+    }
+
+    /// 非主密钥加密的数据重新用主密钥加密，已是最新时返回 None
+    pub fn reencrypt(&self, ciphertext: &str) -> Result<Option<String>> {
+        if self.is_current(ciphertext) {
+            return Ok(None);
+        }
+        Ok(Some(self.encrypt(&self.decrypt(ciphertext)?)?))
+    }
+}
+
+/// 从密钥存储读取旧密钥列表（用主密钥解密）
+fn load_retired(primary: &str) -> Result<Vec<String>> {
+    match secret_store::active()?.get(RETIRED_KEYS_SECRET)? {
+        Some(encrypted) => Ok(serde_json::from_str(&decrypt_sensitive_data(
+            &encrypted, primary,
+        )?)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// 已登记的旧密钥
+fn retired_keys(primary: &str) -> Vec<String> {
+    if let Some(keys) = RETIRED_KEYS.read().unwrap().clone() {
+        return keys;
+    }
+    let keys = load_retired(primary).unwrap_or_else(|e| {
+        warn!("旧密钥列表读取失败，已忽略: {:#}", e);
+        Vec::new()
+    });
+    RETIRED_KEYS.write().unwrap().get_or_insert(keys).clone()
+}
+
+/// 当前密钥环（主密钥 + 已登记的旧密钥），主密钥未解锁时返回错误
+pub fn current() -> Result<KeyRing> {
+    let primary = encryption_key()?;
+    let mut ring = KeyRing::new(&primary);
+    for key in retired_keys(&primary) {
+        ring.add(&key);
+    }
+    Ok(ring)
+}
+
+/// 登记一把旧密钥用于解密并保存到密钥存储，返回其 key_id
+pub fn add_retired_key(key: &str) -> Result<String> {
+    let primary = encryption_key()?;
+    let mut retired = retired_keys(&primary);
+    if key != primary && !retired.iter().any(|k| k == key) {
+        retired.push(key.to_string());
+        let encrypted = encrypt_sensitive_data(&serde_json::to_string(&retired)?, &primary)?;
+        secret_store::active()?.set(RETIRED_KEYS_SECRET, &encrypted)?;
+        *RETIRED_KEYS.write().unwrap() = Some(retired);
+    }
+    Ok(key_id(key))
+}
+
+/// 主密钥更换后，旧密钥列表改用新主密钥加密保存，并加入被替换的主密钥
+pub fn rekey(previous: &str, next: &str) -> Result<()> {
+    let mut retired = retired_keys(previous);
+    if !retired.iter().any(|k| k == previous) {
+        retired.push(previous.to_string());
+    }
+    retired.retain(|k| k != next);
+    let encrypted = encrypt_sensitive_data(&serde_json::to_string(&retired)?, next)?;
+    secret_store::active()?.set(RETIRED_KEYS_SECRET, &encrypted)?;
+    *RETIRED_KEYS.write().unwrap() = Some(retired);
+    Ok(())
+}
+
+/// 用当前主密钥加密
+pub fn encrypt(plaintext: &str) -> Result<String> {
+    current()?.encrypt(plaintext)
+}
+
+/// 解密任一已知密钥加密的数据
+pub fn decrypt(ciphertext: &str) -> Result<String> {
+    current()?.decrypt(ciphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_under_different_keys() {
+        let old = KeyRing::new("old-key");
+        let old_secret = old.encrypt("secret-a").unwrap();
+        assert!(old_secret.starts_with(&format!("{}$", key_id("old-key"))));
+
+        let mut ring = KeyRing::new("new-key");
+        assert!(ring.decrypt(&old_secret).is_err());
+        ring.add("old-key");
+
+        let new_secret = ring.encrypt("secret-b").unwrap();
+        assert_eq!(ring.decrypt(&old_secret).unwrap(), "secret-a");
+        assert_eq!(ring.decrypt(&new_secret).unwrap(), "secret-b");
+        assert!(!ring.is_current(&old_secret));
+        assert!(ring.is_current(&new_secret));
+
+        let migrated = ring.reencrypt(&old_secret).unwrap().unwrap();
+        assert!(ring.is_current(&migrated));
+        assert_eq!(ring.decrypt(&migrated).unwrap(), "secret-a");
+        assert!(ring.reencrypt(&migrated).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_legacy_ciphertext() {
+        let legacy = encrypt_sensitive_data("secret", "old-key").unwrap();
+        let mut ring = KeyRing::new("new-key");
+        ring.add("old-key");
+
+        assert_eq!(ring.decrypt(&legacy).unwrap(), "secret");
+        assert!(!ring.is_current(&legacy));
+        assert!(ring.is_current(""));
+    }
+}
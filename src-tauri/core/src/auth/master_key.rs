@@ -0,0 +1,227 @@
+//! 主密钥管理
+//!
+//! 首次运行时生成随机主密钥，保存到配置选择的密钥存储后端
+//! （见 `secret_store`，默认 macOS / Windows 为系统钥匙串，其余平台为加密文件）。
+//! 同时生成 24 词的 BIP39 恢复短语，重装系统后可用其恢复主密钥，
+//! 避免已加密的 API Key 无法解密。
+//!
+//! 设置了 `DROID_ENCRYPTION_KEY` 环境变量时直接使用该值。主密钥无法加载时
+//! 所有加解密操作直接失败，不会退回任何内置密钥。
+//! 设置了应用锁口令时，存储中只保留口令包装后的主密钥（见 `app_lock`），
+//! 启动后需先解锁才能加解密。
+
+use super::secret_store::{self, SecretBackend};
+use crate::events;
+use anyhow::Result;
+use bip39::Mnemonic;
+use rand::RngCore;
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+/// 主密钥在密钥存储中的名称
+pub const MASTER_KEY_SECRET: &str = "master-key";
+/// 应用锁口令包装后的主密钥在密钥存储中的名称
+pub const WRAPPED_MASTER_KEY_SECRET: &str = "master-key-wrapped";
+
+/// 主密钥由应用锁口令保护且尚未解锁
+#[derive(Debug, thiserror::Error)]
+#[error("主密钥受应用锁保护，请先输入口令解锁")]
+pub struct MasterKeyLocked;
+
+lazy_static::lazy_static! {
+    static ref MASTER_KEY: RwLock<Option<String>> = RwLock::new(initial_key());
+    /// 已确认主密钥被包装保护（避免每次都读取密钥存储）
+    static ref LOCKED: RwLock<bool> = RwLock::new(false);
+}
+
+/// 测试进程使用固定密钥，不读写密钥存储
+#[cfg(test)]
+fn initial_key() -> Option<String> {
+    Some("test-encryption-key".to_string())
+}
+
+#[cfg(not(test))]
+fn initial_key() -> Option<String> {
+    None
+}
+
+/// 获取当前加密密钥，主密钥受应用锁保护且未解锁时返回 `MasterKeyLocked`，
+/// 无法加载时返回加载错误
+pub fn encryption_key() -> Result<String> {
+    if let Some(key) = MASTER_KEY.read().unwrap().clone() {
+        return Ok(key);
+    }
+    if *LOCKED.read().unwrap() {
+        return Err(MasterKeyLocked.into());
+    }
+
+    let key = match std::env::var("DROID_ENCRYPTION_KEY") {
+        Ok(key) => key,
+        Err(_) => match load_or_create() {
+            Ok(key) => key,
+            Err(e) if e.is::<MasterKeyLocked>() => {
+                *LOCKED.write().unwrap() = true;
+                return Err(e);
+            }
+            Err(e) => {
+                warn!("主密钥加载失败: {:#}", e);
+                return Err(e.context("主密钥不可用"));
+            }
+        },
+    };
+    *MASTER_KEY.write().unwrap() = Some(key.clone());
+    Ok(key)
+}
+
+/// 主密钥是否受应用锁保护且尚未解锁
+pub fn is_locked() -> bool {
+    matches!(encryption_key(), Err(e) if e.is::<MasterKeyLocked>())
+}
+
+/// 用口令解开的主密钥解锁（嵌入方也可直接注入主密钥）
+pub fn unlock(key: &str) {
+    *MASTER_KEY.write().unwrap() = Some(key.to_string());
+    *LOCKED.write().unwrap() = false;
+}
+
+/// 主密钥加载失败的原因（受应用锁保护不算失败）
+pub fn load_error() -> Option<String> {
+    match encryption_key() {
+        Err(e) if !e.is::<MasterKeyLocked>() => Some(format!("{:#}", e)),
+        _ => None,
+    }
+}
+
+/// 加载主密钥，不存在时生成
+fn load_or_create() -> Result<String> {
+    if let Some(key) = read_stored_key()? {
+        return Ok(key);
+    }
+    if read_wrapped_key()?.is_some() {
+        return Err(MasterKeyLocked.into());
+    }
+    let store = secret_store::active()?;
+    if !store.writable() {
+        anyhow::bail!(
+            "{} 后端中没有主密钥，请设置环境变量 {}",
+            store.name(),
+            secret_store::EnvStore::var_name(MASTER_KEY_SECRET)
+        );
+    }
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let key = hex::encode(bytes);
+    write_stored_key(&key)?;
+
+    // 恢复短语不进入事件队列，由宿主调用 get_recovery_phrase 取得
+    info!("已生成新的主密钥");
+    events::emit(
+        "master_key_created",
+        "已生成新的主密钥，请立即通过 get_recovery_phrase 记录恢复短语",
+        serde_json::json!({}),
+    );
+    Ok(key)
+}
+
+/// 由主密钥生成恢复短语
+fn recovery_phrase_for(key: &str) -> Result<String> {
+    let bytes = hex::decode(key)
+        .map_err(|_| anyhow::anyhow!("主密钥不是由本程序生成，无法导出恢复短语"))?;
+    Ok(Mnemonic::from_entropy(&bytes)?.to_string())
+}
+
+/// 获取当前主密钥的恢复短语
+pub fn get_recovery_phrase() -> Result<String> {
+    recovery_phrase_for(&encryption_key()?)
+}
+
+/// 通过恢复短语恢复主密钥
+pub fn recover_master_key(phrase: &str) -> Result<()> {
+    if read_wrapped_key()?.is_some() {
+        anyhow::bail!("主密钥受应用锁口令保护，请先取消口令再恢复");
+    }
+    let mnemonic = Mnemonic::parse_normalized(phrase.trim())
+        .map_err(|e| anyhow::anyhow!("恢复短语无效: {}", e))?;
+    let key = hex::encode(mnemonic.to_entropy());
+
+    // 旧主密钥加密的数据仍需可解密，下次写入时迁移到新密钥
+    if let Ok(previous) = encryption_key() {
+        super::key_ring::rekey(&previous, &key)?;
+    }
+    write_stored_key(&key)?;
+    *MASTER_KEY.write().unwrap() = Some(key);
+    info!("主密钥已通过恢复短语恢复");
+    Ok(())
+}
+
+/// 从当前后端读取主密钥
+fn read_stored_key() -> Result<Option<String>> {
+    secret_store::active()?.get(MASTER_KEY_SECRET)
+}
+
+fn write_stored_key(key: &str) -> Result<()> {
+    secret_store::active()?.set(MASTER_KEY_SECRET, key)
+}
+
+/// 读取口令包装后的主密钥
+pub fn read_wrapped_key() -> Result<Option<String>> {
+    secret_store::active()?.get(WRAPPED_MASTER_KEY_SECRET)
+}
+
+/// 只保存包装后的主密钥（删除明文主密钥）
+pub fn store_wrapped_key(wrapped: &str) -> Result<()> {
+    let store = secret_store::active()?;
+    store.set(WRAPPED_MASTER_KEY_SECRET, wrapped)?;
+    store.delete(MASTER_KEY_SECRET)?;
+    info!("主密钥已改为由应用锁口令保护");
+    Ok(())
+}
+
+/// 恢复保存明文主密钥（取消应用锁口令时）
+pub fn store_unwrapped_key(key: &str) -> Result<()> {
+    let store = secret_store::active()?;
+    store.set(MASTER_KEY_SECRET, key)?;
+    store.delete(WRAPPED_MASTER_KEY_SECRET)?;
+    info!("主密钥已取消口令保护");
+    Ok(())
+}
+
+/// 切换密钥存储后端时迁移主密钥（明文或口令包装后的）
+pub fn migrate_backend(from: SecretBackend, to: SecretBackend) -> Result<()> {
+    if from == to {
+        return Ok(());
+    }
+    let source = secret_store::open(from)?;
+    let target = secret_store::open(to)?;
+    secret_store::migrate(
+        source.as_ref(),
+        target.as_ref(),
+        &[
+            MASTER_KEY_SECRET,
+            WRAPPED_MASTER_KEY_SECRET,
+            super::key_ring::RETIRED_KEYS_SECRET,
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_phrase_round_trip() {
+        let key = hex::encode([7u8; 32]);
+        let phrase = recovery_phrase_for(&key).unwrap();
+        assert_eq!(phrase.split_whitespace().count(), 24);
+
+        let mnemonic = Mnemonic::parse_normalized(&phrase).unwrap();
+        assert_eq!(hex::encode(mnemonic.to_entropy()), key);
+    }
+
+    #[test]
+    fn test_non_generated_key_has_no_phrase() {
+        assert!(recovery_phrase_for("my-own-encryption-key").is_err());
+    }
+}
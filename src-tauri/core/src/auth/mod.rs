@@ -2,5 +2,10 @@
 //!
 //! 支持 WorkOS OAuth 和 API Key 两种认证方式
 
-pub mod workos;
 pub mod encryption;
+pub mod jwt;
+pub mod key_ring;
+pub mod master_key;
+pub mod secret_store;
+pub mod token_seal;
+pub mod workos;
@@ -0,0 +1,334 @@
+//! 密钥存储后端
+//!
+//! 主密钥等机密通过 `SecretStore` 读写，后端可在配置中选择：
+//! - `keychain`：系统钥匙串（macOS / Windows）
+//! - `encrypted_file`：数据目录下加密的 `secrets.json`，适合没有钥匙串的
+//!   无界面 Linux 服务器。文件密钥取自 `DROID_SECRETS_PASSPHRASE`，未设置时
+//!   拒绝使用
+//! - `env`：只读，从 `DROID_SECRET_<NAME>` 环境变量注入，适合容器部署
+//!
+//! 默认 `auto`：macOS / Windows 使用钥匙串，其余平台使用加密文件。
+//! 切换后端时已保存的机密由 `migrate` 搬到新后端。
+
+use super::encryption::{decrypt_sensitive_data, encrypt_sensitive_data};
+use crate::config::{data_dir, get_config};
+use crate::store;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// 钥匙串服务名
+pub const KEYCHAIN_SERVICE: &str = "droid-provider";
+/// 加密文件名
+pub const SECRETS_FILE: &str = "secrets.json";
+/// 加密文件口令环境变量
+pub const PASSPHRASE_ENV: &str = "DROID_SECRETS_PASSPHRASE";
+/// 环境变量后端的变量名前缀
+pub const ENV_PREFIX: &str = "DROID_SECRET_";
+
+/// 存储后端
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretBackend {
+    /// 按平台自动选择
+    #[default]
+    Auto,
+    Keychain,
+    EncryptedFile,
+    Env,
+}
+
+/// 密钥存储配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecretStoreConfig {
+    pub backend: SecretBackend,
+}
+
+/// 机密读写接口
+pub trait SecretStore: Send + Sync {
+    /// 后端名称
+    fn name(&self) -> &'static str;
+
+    fn get(&self, name: &str) -> Result<Option<String>>;
+
+    fn set(&self, name: &str, value: &str) -> Result<()>;
+
+    fn delete(&self, name: &str) -> Result<()>;
+
+    /// 是否可写入（只读后端无法保存新生成的机密）
+    fn writable(&self) -> bool {
+        true
+    }
+}
+
+/// 系统钥匙串
+pub struct KeychainStore;
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+impl SecretStore for KeychainStore {
+    fn name(&self) -> &'static str {
+        "keychain"
+    }
+
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        match keyring::Entry::new(KEYCHAIN_SERVICE, name)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<()> {
+        keyring::Entry::new(KEYCHAIN_SERVICE, name)?.set_password(value)?;
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> Result<()> {
+        match keyring::Entry::new(KEYCHAIN_SERVICE, name)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+impl SecretStore for KeychainStore {
+    fn name(&self) -> &'static str {
+        "keychain"
+    }
+
+    fn get(&self, _name: &str) -> Result<Option<String>> {
+        anyhow::bail!("当前平台不支持系统钥匙串，请使用 encrypted_file 或 env 后端")
+    }
+
+    fn set(&self, _name: &str, _value: &str) -> Result<()> {
+        anyhow::bail!("当前平台不支持系统钥匙串，请使用 encrypted_file 或 env 后端")
+    }
+
+    fn delete(&self, _name: &str) -> Result<()> {
+        anyhow::bail!("当前平台不支持系统钥匙串，请使用 encrypted_file 或 env 后端")
+    }
+}
+
+/// 加密文件
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    file_key: String,
+}
+
+impl EncryptedFileStore {
+    pub fn new(path: PathBuf, file_key: String) -> Self {
+        Self { path, file_key }
+    }
+
+    /// 数据目录下的默认文件，密钥取自口令环境变量，未设置时返回错误
+    pub fn default_location() -> Result<Self> {
+        let file_key = std::env::var(PASSPHRASE_ENV)
+            .ok()
+            .filter(|p| !p.is_empty())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "encrypted_file 后端需要通过 {} 设置口令（或改用 keychain / env 后端）",
+                    PASSPHRASE_ENV
+                )
+            })?;
+        Ok(Self::new(data_dir().join(SECRETS_FILE), file_key))
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>> {
+        Ok(store::read_json(&self.path)?.unwrap_or_default())
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, String>) -> Result<()> {
+        store::write_json(&self.path, secrets)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for path in [self.path.clone(), store::backup_path(&self.path)] {
+                if path.exists() {
+                    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "encrypted_file"
+    }
+
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        let secrets = self.load()?;
+        let Some(encrypted) = secrets.get(name) else {
+            return Ok(None);
+        };
+        decrypt_sensitive_data(encrypted, &self.file_key)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("无法解密 {}（口令已变化？）: {}", name, e))
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<()> {
+        let mut secrets = self.load()?;
+        secrets.insert(
+            name.to_string(),
+            encrypt_sensitive_data(value, &self.file_key)?,
+        );
+        self.save(&secrets)
+    }
+
+    fn delete(&self, name: &str) -> Result<()> {
+        let mut secrets = self.load()?;
+        if secrets.remove(name).is_some() {
+            self.save(&secrets)?;
+        }
+        Ok(())
+    }
+}
+
+/// 环境变量注入（只读）
+pub struct EnvStore;
+
+impl EnvStore {
+    /// 机密对应的环境变量名，如 `master-key` → `DROID_SECRET_MASTER_KEY`
+    pub fn var_name(name: &str) -> String {
+        format!(
+            "{}{}",
+            ENV_PREFIX,
+            name.to_ascii_uppercase().replace(['-', '.'], "_")
+        )
+    }
+}
+
+impl SecretStore for EnvStore {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(std::env::var(Self::var_name(name))
+            .ok()
+            .filter(|v| !v.is_empty()))
+    }
+
+    fn set(&self, name: &str, _value: &str) -> Result<()> {
+        anyhow::bail!(
+            "env 后端为只读，请通过环境变量 {} 注入",
+            Self::var_name(name)
+        )
+    }
+
+    fn delete(&self, name: &str) -> Result<()> {
+        anyhow::bail!("env 后端为只读，请移除环境变量 {}", Self::var_name(name))
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+}
+
+/// 按配置构造后端
+pub fn open(backend: SecretBackend) -> Result<Box<dyn SecretStore>> {
+    Ok(match backend {
+        SecretBackend::Auto if cfg!(any(target_os = "macos", target_os = "windows")) => {
+            Box::new(KeychainStore)
+        }
+        SecretBackend::Auto | SecretBackend::EncryptedFile => {
+            Box::new(EncryptedFileStore::default_location()?)
+        }
+        SecretBackend::Keychain => Box::new(KeychainStore),
+        SecretBackend::Env => Box::new(EnvStore),
+    })
+}
+
+/// 当前配置选择的后端
+pub fn active() -> Result<Box<dyn SecretStore>> {
+    open(get_config().secret_store.backend)
+}
+
+/// 把机密从旧后端搬到新后端，返回搬运的数量
+///
+/// 新后端只读（env）时不写入，只要求其中已有相同的值；全部写入成功后才
+/// 从旧后端删除。
+pub fn migrate(from: &dyn SecretStore, to: &dyn SecretStore, names: &[&str]) -> Result<usize> {
+    let mut moved = Vec::new();
+    for name in names {
+        let Some(value) = from.get(name)? else {
+            continue;
+        };
+        if to.writable() {
+            to.set(name, &value)?;
+        } else if to.get(name)?.as_deref() != Some(value.as_str()) {
+            anyhow::bail!(
+                "{} 后端为只读，请先设置环境变量 {} 后再切换",
+                to.name(),
+                EnvStore::var_name(name)
+            );
+        }
+        moved.push(*name);
+    }
+    for name in &moved {
+        if from.writable() {
+            if let Err(e) = from.delete(name) {
+                warn!("已迁移的 {} 无法从 {} 后端删除: {}", name, from.name(), e);
+            }
+        }
+    }
+    if !moved.is_empty() {
+        info!(
+            "{} 个机密已从 {} 后端迁移到 {} 后端",
+            moved.len(),
+            from.name(),
+            to.name()
+        );
+    }
+    Ok(moved.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("secret-store-{}", uuid::Uuid::new_v4()));
+        let path = dir.join(SECRETS_FILE);
+        let store = EncryptedFileStore::new(path.clone(), "passphrase".to_string());
+
+        assert!(store.get("master-key").unwrap().is_none());
+        store.set("master-key", "abc123").unwrap();
+        assert_eq!(store.get("master-key").unwrap().as_deref(), Some("abc123"));
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("abc123"));
+
+        store.delete("master-key").unwrap();
+        assert!(store.get("master-key").unwrap().is_none());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_migrate_between_backends() {
+        let dir = std::env::temp_dir().join(format!("secret-store-{}", uuid::Uuid::new_v4()));
+        let from = EncryptedFileStore::new(dir.join("a.json"), "one".to_string());
+        let to = EncryptedFileStore::new(dir.join("b.json"), "two".to_string());
+        from.set("master-key", "abc123").unwrap();
+
+        assert_eq!(migrate(&from, &to, &["master-key", "other"]).unwrap(), 1);
+        assert_eq!(to.get("master-key").unwrap().as_deref(), Some("abc123"));
+        assert!(from.get("master-key").unwrap().is_none());
+
+        // 只读后端中没有对应的值时拒绝切换
+        assert!(migrate(&to, &EnvStore, &["master-key"]).is_err());
+        assert!(to.get("master-key").unwrap().is_some());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_env_store_is_read_only() {
+        assert_eq!(EnvStore::var_name("master-key"), "DROID_SECRET_MASTER_KEY");
+        assert!(EnvStore.set("master-key", "x").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! OAuth Token 落盘加密
+//!
+//! API Key 在内存中就以密文保存，Access Token / Refresh Token 则在每次请求
+//! 与刷新时都要使用，内存中保持明文，只在写入文件时用主密钥加密
+//! （`sealed:` 前缀 + 密钥环密文），读取时透明解密。宿主传入的凭证配置
+//! 同样可以是加密后的值。泄露的凭证文件不再能直接拿来登录。
+
+use super::key_ring;
+use crate::credentials::DroidCredentials;
+use anyhow::{Context, Result};
+
+/// 加密 Token 的前缀
+pub const SEALED_PREFIX: &str = "sealed:";
+
+/// 是否已加密
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with(SEALED_PREFIX)
+}
+
+/// 加密单个 Token（已加密的原样返回）
+pub fn seal_value(value: &str) -> Result<String> {
+    if is_sealed(value) {
+        return Ok(value.to_string());
+    }
+    Ok(format!("{}{}", SEALED_PREFIX, key_ring::encrypt(value)?))
+}
+
+/// 解密单个 Token（明文原样返回）
+pub fn open_value(value: &str) -> Result<String> {
+    match value.strip_prefix(SEALED_PREFIX) {
+        Some(ciphertext) => key_ring::decrypt(ciphertext),
+        None => Ok(value.to_string()),
+    }
+}
+
+fn tokens(credential: &mut DroidCredentials) -> [(&'static str, &mut Option<String>); 2] {
+    [
+        ("access_token", &mut credential.access_token),
+        ("refresh_token", &mut credential.refresh_token),
+    ]
+}
+
+/// 写入文件前加密凭证中的 Token
+pub fn seal(credential: &mut DroidCredentials) -> Result<()> {
+    for (name, token) in tokens(credential) {
+        if let Some(value) = token.as_mut() {
+            *value = seal_value(value).with_context(|| format!("加密 {} 失败", name))?;
+        }
+    }
+    Ok(())
+}
+
+/// 读取后解密凭证中的 Token
+pub fn open(credential: &mut DroidCredentials) -> Result<()> {
+    for (name, token) in tokens(credential) {
+        if let Some(value) = token.as_mut() {
+            *value = open_value(value).with_context(|| format!("解密 {} 失败", name))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let mut credential = DroidCredentials {
+            access_token: Some("eyJhbGciOi.payload.sig".to_string()),
+            refresh_token: Some("rt_live".to_string()),
+            ..Default::default()
+        };
+        seal(&mut credential).unwrap();
+        let sealed = credential.refresh_token.clone().unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("rt_live"));
+
+        // 重复加密不会套两层
+        seal(&mut credential).unwrap();
+        assert_eq!(credential.refresh_token.as_deref(), Some(sealed.as_str()));
+
+        open(&mut credential).unwrap();
+        assert_eq!(
+            credential.access_token.as_deref(),
+            Some("eyJhbGciOi.payload.sig")
+        );
+        assert_eq!(credential.refresh_token.as_deref(), Some("rt_live"));
+
+        // 明文直接通过
+        assert_eq!(open_value("plain").unwrap(), "plain");
+        assert!(open_value("sealed:not-a-ciphertext").is_err());
+    }
+}
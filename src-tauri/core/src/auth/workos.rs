@@ -0,0 +1,372 @@
+//! WorkOS OAuth 认证模块
+//!
+//! 实现 Factory.ai Droid 的 WorkOS OAuth 认证流程
+
+#![allow(dead_code)]
+
+use crate::auth::jwt::decode_claims;
+use crate::credentials::{TokenRefreshResult, WorkOSTokenResponse};
+use crate::refresh_failure::RefreshFailure;
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+/// WorkOS OAuth 配置
+pub const WORKOS_CLIENT_ID: &str = "client_01HNM792M5G5G1A2THWPXKFMXB";
+pub const WORKOS_TOKEN_URL: &str = "https://api.workos.com/user_management/authenticate";
+pub const FACTORY_CLI_ORG_URL: &str = "https://app.factory.ai/api/cli/org";
+/// 内置默认 User-Agent，实际使用的值见 `user_agent::resolve`
+pub const FACTORY_USER_AGENT: &str = "factory-cli/0.32.1";
+
+/// WorkOS 组织（组织选择挑战中返回）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkOSOrganization {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Token 刷新结果（含 WorkOS 挑战）
+///
+/// WorkOS 在需要用户交互时不会直接返回 Token，而是返回挑战信息，
+/// UI 需引导用户完成后重新登录。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RefreshOutcome {
+    /// 刷新成功
+    Success(TokenRefreshResult),
+    /// 需要完成 MFA 验证
+    MfaRequired {
+        pending_authentication_token: Option<String>,
+        #[serde(default)]
+        authentication_factors: Vec<serde_json::Value>,
+    },
+    /// 需要验证邮箱
+    EmailVerificationRequired {
+        pending_authentication_token: Option<String>,
+        #[serde(default)]
+        email: Option<String>,
+    },
+    /// 需要选择组织
+    OrganizationSelectionRequired {
+        pending_authentication_token: Option<String>,
+        #[serde(default)]
+        organizations: Vec<WorkOSOrganization>,
+    },
+}
+
+impl RefreshOutcome {
+    /// 面向用户的提示信息
+    pub fn message(&self) -> &'static str {
+        match self {
+            RefreshOutcome::Success(_) => "Token 刷新成功",
+            RefreshOutcome::MfaRequired { .. } => "需要完成多因素认证 (MFA) 后重新登录",
+            RefreshOutcome::EmailVerificationRequired { .. } => "需要验证邮箱后重新登录",
+            RefreshOutcome::OrganizationSelectionRequired { .. } => "需要选择组织后重新登录",
+        }
+    }
+}
+
+/// 解析 WorkOS 挑战响应
+pub fn parse_challenge(body: &serde_json::Value) -> Option<RefreshOutcome> {
+    let code = body.get("code").and_then(|c| c.as_str())?;
+    let pending_authentication_token = body
+        .get("pending_authentication_token")
+        .and_then(|t| t.as_str())
+        .map(String::from);
+
+    match code {
+        "mfa_challenge" | "mfa_enrollment" => Some(RefreshOutcome::MfaRequired {
+            pending_authentication_token,
+            authentication_factors: body
+                .get("authentication_factors")
+                .and_then(|f| f.as_array())
+                .cloned()
+                .unwrap_or_default(),
+        }),
+        "email_verification_required" => Some(RefreshOutcome::EmailVerificationRequired {
+            pending_authentication_token,
+            email: body.get("email").and_then(|e| e.as_str()).map(String::from),
+        }),
+        "organization_selection_required" => Some(RefreshOutcome::OrganizationSelectionRequired {
+            pending_authentication_token,
+            organizations: body
+                .get("organizations")
+                .cloned()
+                .and_then(|o| serde_json::from_value(o).ok())
+                .unwrap_or_default(),
+        }),
+        _ => None,
+    }
+}
+
+/// WorkOS 刷新请求的原始响应
+#[derive(Debug, Clone)]
+pub struct RawRefreshResponse {
+    pub status: reqwest::StatusCode,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// 构建刷新请求的表单
+pub fn refresh_form(
+    refresh_token: &str,
+    organization_id: Option<&str>,
+) -> Vec<(&'static str, String)> {
+    let mut form = vec![
+        ("grant_type", "refresh_token".to_string()),
+        ("refresh_token", refresh_token.to_string()),
+        ("client_id", WORKOS_CLIENT_ID.to_string()),
+    ];
+    if let Some(org_id) = organization_id {
+        form.push(("organization_id", org_id.to_string()));
+    }
+    form
+}
+
+/// 构建授权码换取 Token 的表单（PKCE）
+pub fn code_exchange_form(code: &str, code_verifier: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("grant_type", "authorization_code".to_string()),
+        ("code", code.to_string()),
+        ("code_verifier", code_verifier.to_string()),
+        ("client_id", WORKOS_CLIENT_ID.to_string()),
+    ]
+}
+
+/// 向 WorkOS Token 端点提交表单，返回未经解析的响应
+async fn send_token_form(form: &[(&'static str, String)]) -> Result<RawRefreshResponse> {
+    let timeouts = crate::config::get_config().timeouts.for_control();
+    let client = timeouts.client()?;
+
+    let request = client
+        .post(WORKOS_TOKEN_URL)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .form(form);
+    let response = timeouts.send(request).await?;
+
+    let status = response.status();
+    let headers = response
+        .headers()
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_str().unwrap_or("").to_string()))
+        .collect();
+    let body = timeouts.read_text(response).await.unwrap_or_default();
+    Ok(RawRefreshResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// 发送刷新请求，返回未经解析的响应
+pub async fn send_refresh_request(
+    refresh_token: &str,
+    organization_id: Option<&str>,
+) -> Result<RawRefreshResponse> {
+    debug!("刷新 WorkOS Token");
+    send_token_form(&refresh_form(refresh_token, organization_id)).await
+}
+
+/// 用登录回调中的授权码换取 Token（重新登录）
+pub async fn exchange_code(code: &str, code_verifier: &str) -> Result<RefreshOutcome> {
+    debug!("用授权码换取 WorkOS Token");
+    let response = send_token_form(&code_exchange_form(code, code_verifier)).await?;
+    parse_refresh_response(response.status, &response.body)
+}
+
+/// 使用 Refresh Token 刷新 Access Token
+pub async fn refresh_workos_token(
+    refresh_token: &str,
+    organization_id: Option<&str>,
+) -> Result<RefreshOutcome> {
+    let response = send_refresh_request(refresh_token, organization_id).await?;
+    parse_refresh_response(response.status, &response.body).map_err(|e| {
+        match e.downcast::<RefreshFailure>() {
+            Ok(failure) => failure.with_retry_after(&response.headers).into(),
+            Err(e) => e,
+        }
+    })
+}
+
+/// 解析刷新响应（挑战、错误状态码或新 Token）
+pub fn parse_refresh_response(status: reqwest::StatusCode, body: &str) -> Result<RefreshOutcome> {
+    let json: Option<serde_json::Value> = serde_json::from_str(body).ok();
+
+    if let Some(challenge) = json.as_ref().and_then(parse_challenge) {
+        info!("WorkOS 返回挑战: {}", challenge.message());
+        return Ok(challenge);
+    }
+
+    if !status.is_success() {
+        return Err(RefreshFailure::from_response(status.as_u16(), body).into());
+    }
+
+    let token_response: WorkOSTokenResponse = json
+        .ok_or_else(|| anyhow::anyhow!("WorkOS 响应不是有效的 JSON"))
+        .and_then(|v| {
+            serde_json::from_value(v).map_err(|e| anyhow::anyhow!("WorkOS 响应缺少必要字段: {}", e))
+        })?;
+
+    // 计算过期时间：优先使用 JWT 中的 exp
+    let claims = decode_claims(&token_response.access_token);
+    let expires_at = if let Some(exp) = claims.as_ref().and_then(|c| c.expires_at()) {
+        Some(exp)
+    } else if let Some(expires_at_str) = &token_response.expires_at {
+        chrono::DateTime::parse_from_rfc3339(expires_at_str)
+            .ok()
+            .map(|dt| dt.with_timezone(&Utc))
+    } else if let Some(expires_in) = token_response.expires_in {
+        Some(Utc::now() + Duration::seconds(expires_in))
+    } else {
+        // 默认 8 小时
+        Some(Utc::now() + Duration::hours(8))
+    };
+
+    info!("WorkOS Token 刷新成功");
+
+    Ok(RefreshOutcome::Success(TokenRefreshResult {
+        access_token: token_response.access_token,
+        refresh_token: token_response.refresh_token,
+        expires_at,
+        organization_id: token_response
+            .organization_id
+            .or_else(|| claims.as_ref().and_then(|c| c.org_id.clone())),
+        user_id: token_response.user.as_ref().and_then(|u| u.id.clone()),
+        owner_email: token_response.user.as_ref().and_then(|u| u.email.clone()),
+    }))
+}
+
+/// 获取 Factory 组织 ID 列表
+pub async fn fetch_factory_org_ids(access_token: &str) -> Result<Vec<String>> {
+    Ok(fetch_factory_orgs(access_token)
+        .await?
+        .into_iter()
+        .map(|org| org.id)
+        .collect())
+}
+
+/// 获取 Factory 组织列表（接口返回名称时一并带上）
+pub async fn fetch_factory_orgs(access_token: &str) -> Result<Vec<WorkOSOrganization>> {
+    let timeouts = crate::config::get_config().timeouts.for_control();
+    let client = timeouts.client()?;
+
+    debug!("获取 Factory 组织信息");
+
+    let request = client
+        .get(FACTORY_CLI_ORG_URL)
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Content-Type", "application/json")
+        .header("Accept", "application/json")
+        .header("x-factory-client", "cli")
+        .header("User-Agent", crate::user_agent::resolve(None));
+    let response = timeouts.send(request).await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = timeouts.read_text(response).await.unwrap_or_default();
+        anyhow::bail!("获取 Factory 组织信息失败: {} - {}", status, body);
+    }
+
+    Ok(parse_factory_orgs(&timeouts.read_json(response).await?))
+}
+
+/// 解析 Factory 组织响应：`workosOrgIds` 只有 ID，`organizations` 可能带名称
+pub fn parse_factory_orgs(body: &serde_json::Value) -> Vec<WorkOSOrganization> {
+    #[derive(Deserialize)]
+    struct FactoryOrg {
+        #[serde(alias = "workosOrgId")]
+        id: String,
+        #[serde(default, alias = "displayName")]
+        name: Option<String>,
+    }
+
+    let mut orgs: Vec<WorkOSOrganization> = body
+        .get("organizations")
+        .cloned()
+        .and_then(|o| serde_json::from_value::<Vec<FactoryOrg>>(o).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|org| WorkOSOrganization {
+            id: org.id,
+            name: org.name.filter(|n| !n.trim().is_empty()),
+        })
+        .collect();
+    let ids = body.get("workosOrgIds").and_then(|ids| ids.as_array());
+    for id in ids.into_iter().flatten().filter_map(|id| id.as_str()) {
+        if !orgs.iter().any(|org| org.id == id) {
+            orgs.push(WorkOSOrganization {
+                id: id.to_string(),
+                name: None,
+            });
+        }
+    }
+    orgs
+}
+
+/// 验证 Access Token 是否有效
+pub async fn validate_access_token(access_token: &str) -> Result<bool> {
+    // 尝试获取组织信息来验证 Token
+    match fetch_factory_org_ids(access_token).await {
+        Ok(_) => Ok(true),
+        Err(_) => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constants() {
+        assert!(!WORKOS_CLIENT_ID.is_empty());
+        assert!(WORKOS_TOKEN_URL.starts_with("https://"));
+        assert!(FACTORY_CLI_ORG_URL.starts_with("https://"));
+    }
+
+    #[test]
+    fn test_parse_challenge() {
+        let mfa = serde_json::json!({
+            "code": "mfa_challenge",
+            "pending_authentication_token": "pat_123",
+            "authentication_factors": [{ "id": "auth_factor_1", "type": "totp" }]
+        });
+        match parse_challenge(&mfa) {
+            Some(RefreshOutcome::MfaRequired {
+                pending_authentication_token,
+                authentication_factors,
+            }) => {
+                assert_eq!(pending_authentication_token.as_deref(), Some("pat_123"));
+                assert_eq!(authentication_factors.len(), 1);
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+
+        let org = serde_json::json!({
+            "code": "organization_selection_required",
+            "organizations": [{ "id": "org_1", "name": "Acme" }]
+        });
+        match parse_challenge(&org) {
+            Some(RefreshOutcome::OrganizationSelectionRequired { organizations, .. }) => {
+                assert_eq!(organizations[0].name.as_deref(), Some("Acme"));
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+
+        assert!(parse_challenge(&serde_json::json!({ "error": "invalid_grant" })).is_none());
+    }
+
+    #[test]
+    fn test_parse_factory_orgs() {
+        let body = serde_json::json!({
+            "workosOrgIds": ["org_1", "org_2"],
+            "organizations": [{ "workosOrgId": "org_1", "displayName": "Acme" }]
+        });
+        let orgs = parse_factory_orgs(&body);
+        assert_eq!(orgs.len(), 2);
+        assert_eq!(orgs[0].name.as_deref(), Some("Acme"));
+        assert_eq!(orgs[1].id, "org_2");
+        assert!(orgs[1].name.is_none());
+    }
+}
@@ -0,0 +1,303 @@
+//! 开机自启动
+//!
+//! 编辑器依赖本地网关时，希望它像后台服务一样随登录启动并最小化到托盘。
+//! 宿主传入要启动的程序（绝对路径）与参数，按平台写入：
+//! macOS 的 LaunchAgent plist、Linux 的 XDG autostart `.desktop` 文件、
+//! Windows 注册表 `HKCU\...\Run` 项。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::info;
+
+/// 自启动项名称（LaunchAgent label / desktop 文件名 / 注册表值名）
+pub const ENTRY_NAME: &str = "ai.proxycast.droid-provider";
+
+/// 最小化到托盘启动的参数
+pub const MINIMIZED_ARG: &str = "--minimized";
+
+/// Windows 注册表 Run 键
+const WINDOWS_RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+
+/// 要注册的启动命令
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutostartEntry {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl AutostartEntry {
+    /// 按需追加最小化参数
+    pub fn new(program: String, mut args: Vec<String>, minimized: bool) -> Self {
+        if minimized && !args.iter().any(|a| a == MINIMIZED_ARG) {
+            args.push(MINIMIZED_ARG.to_string());
+        }
+        Self { program, args }
+    }
+
+    /// 程序必须是绝对路径，程序与参数都不能含控制字符（换行会破坏
+    /// desktop 文件与注册表值）
+    pub fn validate(&self) -> Result<()> {
+        if self.program.trim().is_empty() {
+            anyhow::bail!("program 不能为空");
+        }
+        if !std::path::Path::new(&self.program).is_absolute() {
+            anyhow::bail!("program 必须是绝对路径: {}", self.program);
+        }
+        if let Some(bad) = std::iter::once(&self.program)
+            .chain(&self.args)
+            .find(|arg| arg.chars().any(char::is_control))
+        {
+            anyhow::bail!("启动命令含有控制字符: {:?}", bad);
+        }
+        Ok(())
+    }
+}
+
+/// 自启动状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutostartStatus {
+    pub enabled: bool,
+    pub platform: String,
+    /// 自启动项所在位置（文件路径或注册表键）
+    pub location: String,
+    #[serde(default)]
+    pub minimized: bool,
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// macOS LaunchAgent plist
+pub fn render_launch_agent(entry: &AutostartEntry) -> String {
+    let arguments: String = std::iter::once(&entry.program)
+        .chain(&entry.args)
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(arg)))
+        .collect();
+    format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" ",
+            "\"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+            "<plist version=\"1.0\">\n",
+            "<dict>\n",
+            "    <key>Label</key>\n",
+            "    <string>{}</string>\n",
+            "    <key>ProgramArguments</key>\n",
+            "    <array>\n",
+            "{}",
+            "    </array>\n",
+            "    <key>RunAtLoad</key>\n",
+            "    <true/>\n",
+            "</dict>\n",
+            "</plist>\n"
+        ),
+        ENTRY_NAME, arguments
+    )
+}
+
+/// 含空白或引号的参数加引号
+fn quote_arg(arg: &str) -> String {
+    if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '"') {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    } else {
+        arg.to_string()
+    }
+}
+
+/// 命令行（Windows 注册表值与 desktop 文件的 Exec 共用）
+pub fn render_command_line(entry: &AutostartEntry) -> String {
+    std::iter::once(&entry.program)
+        .chain(&entry.args)
+        .map(|arg| quote_arg(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Linux XDG autostart 文件
+pub fn render_desktop_entry(entry: &AutostartEntry) -> String {
+    // Exec 中的 % 需要转义
+    let exec = render_command_line(entry).replace('%', "%%");
+    format!(
+        "[Desktop Entry]\nType=Application\nName=Droid Provider\nExec={}\n\
+         X-GNOME-Autostart-enabled=true\nNoDisplay=true\n",
+        exec
+    )
+}
+
+fn home_dir() -> Result<PathBuf> {
+    dirs::home_dir().ok_or_else(|| anyhow::anyhow!("无法确定用户主目录"))
+}
+
+/// 自启动文件路径（Windows 使用注册表，返回 None）
+fn entry_path() -> Result<Option<PathBuf>> {
+    if cfg!(target_os = "macos") {
+        let dir = home_dir()?.join("Library").join("LaunchAgents");
+        Ok(Some(dir.join(format!("{}.plist", ENTRY_NAME))))
+    } else if cfg!(windows) {
+        Ok(None)
+    } else {
+        let dir = dirs::config_dir().ok_or_else(|| anyhow::anyhow!("无法确定配置目录"))?;
+        Ok(Some(
+            dir.join("autostart")
+                .join(format!("{}.desktop", ENTRY_NAME)),
+        ))
+    }
+}
+
+fn run_reg(args: &[&str]) -> Result<std::process::Output> {
+    std::process::Command::new("reg")
+        .args(args)
+        .output()
+        .context("无法执行 reg 命令")
+}
+
+/// 注册自启动
+pub fn enable(entry: &AutostartEntry) -> Result<AutostartStatus> {
+    entry.validate()?;
+    match entry_path()? {
+        Some(path) => {
+            let content = if cfg!(target_os = "macos") {
+                render_launch_agent(entry)
+            } else {
+                render_desktop_entry(entry)
+            };
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, content)
+                .with_context(|| format!("写入自启动文件失败: {}", path.display()))?;
+        }
+        None => {
+            let command = render_command_line(entry);
+            let output = run_reg(&[
+                "add",
+                WINDOWS_RUN_KEY,
+                "/v",
+                ENTRY_NAME,
+                "/t",
+                "REG_SZ",
+                "/d",
+                &command,
+                "/f",
+            ])?;
+            if !output.status.success() {
+                anyhow::bail!(
+                    "写入注册表失败: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+        }
+    }
+    info!("已注册开机自启动: {}", render_command_line(entry));
+    status()
+}
+
+/// 取消自启动
+pub fn disable() -> Result<AutostartStatus> {
+    match entry_path()? {
+        Some(path) => {
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        None => {
+            // 值不存在时 reg 返回失败，忽略
+            run_reg(&["delete", WINDOWS_RUN_KEY, "/v", ENTRY_NAME, "/f"])?;
+        }
+    }
+    info!("已取消开机自启动");
+    status()
+}
+
+/// 当前自启动状态
+pub fn status() -> Result<AutostartStatus> {
+    let (enabled, location, content) = match entry_path()? {
+        Some(path) => {
+            let content = std::fs::read_to_string(&path).ok();
+            (content.is_some(), path.display().to_string(), content)
+        }
+        None => {
+            let output = run_reg(&["query", WINDOWS_RUN_KEY, "/v", ENTRY_NAME])?;
+            let content = String::from_utf8_lossy(&output.stdout).into_owned();
+            (
+                output.status.success(),
+                WINDOWS_RUN_KEY.to_string(),
+                Some(content),
+            )
+        }
+    };
+    Ok(AutostartStatus {
+        enabled,
+        platform: std::env::consts::OS.to_string(),
+        location,
+        minimized: enabled && content.is_some_and(|c| c.contains(MINIMIZED_ARG)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> AutostartEntry {
+        AutostartEntry::new(
+            "/Applications/Proxy Cast.app/Contents/MacOS/proxycast".to_string(),
+            vec!["--profile=a&b".to_string()],
+            true,
+        )
+    }
+
+    #[test]
+    fn test_minimized_arg_added_once() {
+        let entry = AutostartEntry::new("app".to_string(), vec![MINIMIZED_ARG.to_string()], true);
+        assert_eq!(entry.args, vec![MINIMIZED_ARG]);
+        assert!(AutostartEntry::new("app".to_string(), Vec::new(), false)
+            .args
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(entry().validate().is_ok());
+        let relative = AutostartEntry::new("proxycast".to_string(), Vec::new(), false);
+        assert!(relative.validate().is_err());
+        let newline = AutostartEntry::new(
+            "/usr/bin/proxycast".to_string(),
+            vec!["a\nExec=evil".to_string()],
+            false,
+        );
+        assert!(newline.validate().is_err());
+    }
+
+    #[test]
+    fn test_render_launch_agent() {
+        let plist = render_launch_agent(&entry());
+        assert!(plist.contains(&format!("<string>{}</string>", ENTRY_NAME)));
+        assert!(plist.contains("<string>/Applications/Proxy Cast.app/Contents/MacOS/proxycast"));
+        assert!(plist.contains("<string>--profile=a&amp;b</string>"));
+        assert!(plist.contains("<string>--minimized</string>"));
+        assert!(plist.contains("<key>RunAtLoad</key>"));
+    }
+
+    #[test]
+    fn test_render_command_line_and_desktop_entry() {
+        assert_eq!(
+            render_command_line(&entry()),
+            "\"/Applications/Proxy Cast.app/Contents/MacOS/proxycast\" --profile=a&b --minimized"
+        );
+        let entry = AutostartEntry::new(
+            "/usr/bin/proxycast".to_string(),
+            vec!["50%".to_string()],
+            false,
+        );
+        let desktop = render_desktop_entry(&entry);
+        assert!(desktop.contains("Exec=/usr/bin/proxycast 50%%\n"));
+        assert!(desktop.starts_with("[Desktop Entry]\n"));
+    }
+}
@@ -0,0 +1,111 @@
+//! 模型可用性
+//!
+//! `list_models` 为每个模型附带当前凭证池的可用情况：能路由到该模型的凭证数、
+//! 其中健康且可立即使用的数量、冷却中的凭证最早何时恢复，以及可用凭证中
+//! Access Token 最早的过期时间。客户端 UI 可据此把必然失败的模型置灰。
+
+use crate::credentials::{AuthType, DroidCredentials};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 单个模型的可用情况
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelAvailability {
+    /// 能路由到该模型的凭证数
+    pub credentials: usize,
+    /// 其中健康且不在冷却中的凭证数
+    pub available: usize,
+    /// 冷却中的凭证数
+    pub cooling_down: usize,
+    /// 冷却中的凭证最早恢复时间（暂无可用凭证时可据此提示）
+    #[serde(default)]
+    pub next_available_at: Option<String>,
+    /// 可用的 OAuth 凭证中最早的 Access Token 过期时间
+    #[serde(default)]
+    pub earliest_token_expiry: Option<String>,
+}
+
+impl ModelAvailability {
+    /// 是否有可立即使用的凭证
+    pub fn is_available(&self) -> bool {
+        self.available > 0
+    }
+}
+
+fn parse_time(value: Option<&str>) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value?)
+        .ok()
+        .map(|ts| ts.with_timezone(&Utc))
+}
+
+/// 汇总能服务某个模型的凭证
+pub fn summarize<'a>(
+    credentials: impl IntoIterator<Item = &'a DroidCredentials>,
+    now: DateTime<Utc>,
+) -> ModelAvailability {
+    let mut summary = ModelAvailability::default();
+    let mut next_available: Option<DateTime<Utc>> = None;
+    let mut earliest_expiry: Option<DateTime<Utc>> = None;
+
+    for credential in credentials.into_iter().filter(|c| !c.read_only) {
+        summary.credentials += 1;
+        if !credential.is_healthy() {
+            continue;
+        }
+        let cooldown = parse_time(credential.cooldown_until.as_deref()).filter(|t| *t > now);
+        if let Some(until) = cooldown {
+            summary.cooling_down += 1;
+            next_available = Some(next_available.map_or(until, |t| t.min(until)));
+            continue;
+        }
+        summary.available += 1;
+        if credential.auth_type == AuthType::OAuth {
+            if let Some(expiry) = parse_time(credential.expires_at.as_deref()) {
+                earliest_expiry = Some(earliest_expiry.map_or(expiry, |t| t.min(expiry)));
+            }
+        }
+    }
+
+    summary.next_available_at = next_available.map(|t| t.to_rfc3339());
+    summary.earliest_token_expiry = earliest_expiry.map(|t| t.to_rfc3339());
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_summarize() {
+        let now = Utc::now();
+        let cooling = |minutes: i64| DroidCredentials {
+            cooldown_until: Some((now + Duration::minutes(minutes)).to_rfc3339()),
+            ..Default::default()
+        };
+        let healthy = DroidCredentials {
+            expires_at: Some((now + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let credentials = [cooling(10), cooling(3), healthy];
+
+        let summary = summarize(&credentials, now);
+        assert_eq!(summary.credentials, 3);
+        assert_eq!(summary.available, 1);
+        assert_eq!(summary.cooling_down, 2);
+        assert_eq!(
+            summary.next_available_at,
+            Some((now + Duration::minutes(3)).to_rfc3339())
+        );
+        assert!(summary.earliest_token_expiry.is_some());
+
+        let unavailable = summarize(&credentials[..2], now);
+        assert!(!unavailable.is_available());
+
+        let read_only = DroidCredentials {
+            read_only: true,
+            ..Default::default()
+        };
+        assert_eq!(summarize([&read_only], now).credentials, 0);
+    }
+}
@@ -0,0 +1,310 @@
+//! 冷却与熔断状态持久化
+//!
+//! 429 冷却期间应用重启时，凭证会被立即重新打满。冷却截止时间、各端点连续
+//! 5xx 计数和“不健康”标记在变化时记录下来，随凭证文件一起由后台写入
+//! `backoff_state.json`（不在凭证池的锁内写盘），宿主重新创建凭证时按账号身份
+//! （OAuth 的用户与组织，API Key 的首个 Key 哈希）恢复。凭证 ID 每次创建都会
+//! 重新生成，因此不能作为键。5xx 计数与“不健康”标记超过 `STATE_TTL_HOURS`
+//! 未更新即失效；凭证删除时清除对应记录。
+
+use crate::config::data_dir;
+use crate::credentials::{AuthType, DroidCredentials, EndpointType};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// 状态文件名
+pub const BACKOFF_STATE_FILE: &str = "backoff_state.json";
+
+/// 5xx 计数与“不健康”标记的有效期（小时）
+pub const STATE_TTL_HOURS: i64 = 24;
+
+/// 单个凭证需要跨重启保留的状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackoffState {
+    #[serde(default)]
+    pub cooldown_until: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub endpoint_failures: HashMap<EndpointType, u32>,
+    #[serde(default)]
+    pub marked_unhealthy: bool,
+    /// 最后更新时间
+    pub updated_at: String,
+}
+
+impl BackoffState {
+    /// 提取凭证当前的状态，没有需要保留的内容时返回 None
+    pub fn capture(credential: &DroidCredentials, now: DateTime<Utc>) -> Option<Self> {
+        let state = Self {
+            cooldown_until: credential
+                .cooldown_until
+                .clone()
+                .filter(|until| !is_expired(until, now)),
+            endpoint_failures: credential.endpoint_failures.clone(),
+            marked_unhealthy: credential.health.marked_unhealthy,
+            updated_at: now.to_rfc3339(),
+        };
+        (!state.is_empty()).then_some(state)
+    }
+
+    /// 没有需要保留的内容
+    fn is_empty(&self) -> bool {
+        self.cooldown_until.is_none() && self.endpoint_failures.is_empty() && !self.marked_unhealthy
+    }
+
+    /// 除更新时间外是否相同
+    fn same_as(&self, other: &Self) -> bool {
+        self.cooldown_until == other.cooldown_until
+            && self.endpoint_failures == other.endpoint_failures
+            && self.marked_unhealthy == other.marked_unhealthy
+    }
+
+    /// 5xx 计数与“不健康”标记是否已过期
+    fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        DateTime::parse_from_rfc3339(&self.updated_at).map_or(true, |t| {
+            now - t.with_timezone(&Utc) > Duration::hours(STATE_TTL_HOURS)
+        })
+    }
+
+    /// 去掉过期的部分，全部过期时返回 None
+    pub fn prune(mut self, now: DateTime<Utc>) -> Option<Self> {
+        if self
+            .cooldown_until
+            .as_deref()
+            .is_some_and(|u| is_expired(u, now))
+        {
+            self.cooldown_until = None;
+        }
+        if self.is_stale(now) {
+            self.endpoint_failures.clear();
+            self.marked_unhealthy = false;
+        }
+        (!self.is_empty()).then_some(self)
+    }
+
+    /// 写回凭证（已过期的部分不恢复）
+    pub fn apply(&self, credential: &mut DroidCredentials, now: DateTime<Utc>) {
+        let Some(state) = self.clone().prune(now) else {
+            return;
+        };
+        if let Some(until) = state.cooldown_until {
+            credential.cooldown_until = Some(until);
+        }
+        for (endpoint, failures) in &state.endpoint_failures {
+            credential.endpoint_failures.insert(*endpoint, *failures);
+        }
+        credential.health.marked_unhealthy |= state.marked_unhealthy;
+        credential.update_health_score();
+    }
+}
+
+fn is_expired(until: &str, now: DateTime<Utc>) -> bool {
+    DateTime::parse_from_rfc3339(until).map_or(true, |t| t <= now)
+}
+
+/// 凭证的稳定身份，无法识别时返回 None
+pub fn state_key(credential: &DroidCredentials) -> Option<String> {
+    match credential.auth_type {
+        AuthType::OAuth => credential.user_id.as_ref().map(|user| {
+            format!(
+                "oauth:{}:{}",
+                user,
+                credential.organization_id.as_deref().unwrap_or("")
+            )
+        }),
+        AuthType::ApiKey => credential
+            .api_keys
+            .first()
+            .map(|entry| format!("api_key:{}", entry.hash)),
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref STATES: Mutex<Option<BTreeMap<String, BackoffState>>> = Mutex::new(None);
+}
+
+/// 有尚未写盘的变化
+static DIRTY: AtomicBool = AtomicBool::new(false);
+
+fn state_path() -> PathBuf {
+    data_dir().join(BACKOFF_STATE_FILE)
+}
+
+fn load_from_disk() -> BTreeMap<String, BackoffState> {
+    let states: BTreeMap<String, BackoffState> = match crate::store::read_json(&state_path()) {
+        Ok(states) => states.unwrap_or_default(),
+        Err(e) => {
+            warn!("冷却状态文件读取失败，已忽略: {}", e);
+            BTreeMap::new()
+        }
+    };
+    let now = Utc::now();
+    states
+        .into_iter()
+        .filter_map(|(key, state)| Some((key, state.prune(now)?)))
+        .collect()
+}
+
+/// 写入有变化的状态（随凭证文件一起在后台调用）
+pub fn save() -> Result<()> {
+    if !DIRTY.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let states = STATES.lock().unwrap().clone().unwrap_or_default();
+    crate::store::write_json(&state_path(), &states).inspect_err(|_| {
+        DIRTY.store(true, Ordering::SeqCst);
+    })
+}
+
+/// 记录凭证的最新状态，有变化时安排写盘
+pub fn record(credential: &DroidCredentials) {
+    let Some(key) = state_key(credential) else {
+        return;
+    };
+    let state = BackoffState::capture(credential, Utc::now());
+
+    let mut guard = STATES.lock().unwrap();
+    let states = guard.get_or_insert_with(load_from_disk);
+    match (states.get(&key), &state) {
+        (None, None) => return,
+        (Some(existing), Some(state)) if existing.same_as(state) => return,
+        _ => {}
+    }
+    match state {
+        Some(state) => states.insert(key, state),
+        None => states.remove(&key),
+    };
+    DIRTY.store(true, Ordering::SeqCst);
+}
+
+/// 凭证删除后清除其记录
+pub fn forget(credential: &DroidCredentials) {
+    let Some(key) = state_key(credential) else {
+        return;
+    };
+    let mut guard = STATES.lock().unwrap();
+    let states = guard.get_or_insert_with(load_from_disk);
+    if states.remove(&key).is_some() {
+        DIRTY.store(true, Ordering::SeqCst);
+    }
+}
+
+/// 为新创建的凭证恢复上次保存的状态
+pub fn restore(credential: &mut DroidCredentials) {
+    let Some(key) = state_key(credential) else {
+        return;
+    };
+    let mut guard = STATES.lock().unwrap();
+    let states = guard.get_or_insert_with(load_from_disk);
+    if let Some(state) = states.get(&key) {
+        state.apply(credential, Utc::now());
+        info!("已恢复凭证 {} 的冷却与熔断状态", key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oauth_credential() -> DroidCredentials {
+        DroidCredentials {
+            user_id: Some("user_01".to_string()),
+            organization_id: Some("org_01".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_capture_and_apply() {
+        let now = Utc::now();
+        let mut credential = oauth_credential();
+        assert!(BackoffState::capture(&credential, now).is_none());
+
+        credential.cooldown_until = Some((now + Duration::minutes(5)).to_rfc3339());
+        credential
+            .endpoint_failures
+            .insert(EndpointType::Anthropic, 3);
+        credential.health.marked_unhealthy = true;
+        let state = BackoffState::capture(&credential, now).unwrap();
+
+        let mut restored = oauth_credential();
+        state.apply(&mut restored, now);
+        assert!(restored.in_cooldown());
+        assert_eq!(restored.endpoint_failures[&EndpointType::Anthropic], 3);
+        assert_eq!(restored.health_score, 0);
+
+        // 重启时冷却已结束则不再恢复
+        let mut late = oauth_credential();
+        state.apply(&mut late, now + Duration::minutes(10));
+        assert!(late.cooldown_until.is_none());
+        assert!(late.health.marked_unhealthy);
+
+        // 超过有效期的 5xx 计数与不健康标记不再恢复
+        let mut stale = oauth_credential();
+        state.apply(&mut stale, now + Duration::hours(STATE_TTL_HOURS + 1));
+        assert!(stale.endpoint_failures.is_empty());
+        assert!(!stale.health.marked_unhealthy);
+        assert!(state
+            .prune(now + Duration::hours(STATE_TTL_HOURS + 1))
+            .is_none());
+    }
+
+    #[test]
+    fn test_state_key() {
+        assert_eq!(
+            state_key(&oauth_credential()).as_deref(),
+            Some("oauth:user_01:org_01")
+        );
+        assert!(state_key(&DroidCredentials::default()).is_none());
+    }
+
+    #[test]
+    fn test_record_and_restore() {
+        let mut credential = DroidCredentials {
+            user_id: Some("user_record".to_string()),
+            ..Default::default()
+        };
+        credential.cooldown_until = Some((Utc::now() + Duration::minutes(5)).to_rfc3339());
+        record(&credential);
+
+        let mut recreated = DroidCredentials {
+            user_id: Some("user_record".to_string()),
+            ..Default::default()
+        };
+        restore(&mut recreated);
+        assert!(recreated.in_cooldown());
+
+        // 冷却结束后清除记录
+        recreated.cooldown_until = None;
+        record(&recreated);
+        let mut again = DroidCredentials {
+            user_id: Some("user_record".to_string()),
+            ..Default::default()
+        };
+        restore(&mut again);
+        assert!(again.cooldown_until.is_none());
+    }
+
+    #[test]
+    fn test_forget() {
+        let mut credential = DroidCredentials {
+            user_id: Some("user_forget".to_string()),
+            ..Default::default()
+        };
+        credential.health.marked_unhealthy = true;
+        record(&credential);
+        forget(&credential);
+
+        let mut recreated = DroidCredentials {
+            user_id: Some("user_forget".to_string()),
+            ..Default::default()
+        };
+        restore(&mut recreated);
+        assert!(!recreated.health.marked_unhealthy);
+    }
+}
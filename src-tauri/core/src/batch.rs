@@ -0,0 +1,563 @@
+//! Anthropic 消息批处理
+//!
+//! 通过 Factory 的 Anthropic 路径提交 Message Batches，轮询批次状态并获取结果。
+//! 上游要求 custom_id 满足 `^[a-zA-Z0-9_-]{1,64}$`，因此提交时统一生成
+//! `item-<序号>`，并在结果中映射回调用方提供的 id。启用租户时提交需要虚拟
+//! 密钥，受租户的凭证子集与配额限制，租户只能查看自己提交的批次。
+//!
+//! 每次访问上游都经 `acquire_pinned` 占用租约，与普通请求一样遵守暂停、维护
+//! 退避与冷却，结束后释放并上报结果；首次取回结果时把各请求的 Token 用量
+//! 计入统计。凭证只发送给 Factory：上游返回的 `results_url` 不在 Factory 地址
+//! 下时改用批次的 `/results` 路径。
+
+use crate::config::get_config;
+use crate::credentials::{EndpointType, ErrorDetail, ReleaseReport, ReleaseStatus, UsageInfo};
+use crate::http::ordered_headers;
+use crate::profiles;
+use crate::provider::{self, AcquireOptions, FACTORY_API_BASE_URL};
+use crate::tenants;
+use crate::timeouts::Timeouts;
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::RwLock;
+use tracing::{debug, info};
+
+/// 批处理端点路径
+pub const ENDPOINT_ANTHROPIC_BATCHES: &str = "/a/v1/messages/batches";
+
+/// Anthropic API 版本
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// 单个批处理请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchItem {
+    /// 调用方提供的 id
+    pub id: String,
+    /// Messages API 请求体
+    pub params: serde_json::Value,
+}
+
+/// 批次处理状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    #[default]
+    InProgress,
+    Canceling,
+    Ended,
+}
+
+/// 批次内各状态的请求数
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchRequestCounts {
+    #[serde(default)]
+    pub processing: u64,
+    #[serde(default)]
+    pub succeeded: u64,
+    #[serde(default)]
+    pub errored: u64,
+    #[serde(default)]
+    pub canceled: u64,
+    #[serde(default)]
+    pub expired: u64,
+}
+
+/// 批次信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJob {
+    /// 上游批次 ID
+    pub id: String,
+    pub credential_id: String,
+    pub status: BatchStatus,
+    pub request_counts: BatchRequestCounts,
+    pub created_at: String,
+    /// 占用租约时使用的模型（批次中等级要求最高的模型）
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub results_url: Option<String>,
+    /// 结果的 Token 用量是否已计入统计
+    #[serde(default)]
+    pub usage_recorded: bool,
+    /// 提交批次的租户
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// custom_id -> 调用方 id
+    #[serde(default)]
+    pub id_map: BTreeMap<String, String>,
+}
+
+/// 单个请求的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchItemResult {
+    /// 调用方提供的 id
+    pub id: String,
+    pub custom_id: String,
+    /// succeeded / errored / canceled / expired
+    pub result_type: String,
+    #[serde(default)]
+    pub message: Option<serde_json::Value>,
+    #[serde(default)]
+    pub error: Option<serde_json::Value>,
+}
+
+/// 上游批次响应中关心的字段
+#[derive(Debug, Deserialize)]
+struct UpstreamBatch {
+    id: String,
+    #[serde(default)]
+    processing_status: BatchStatus,
+    #[serde(default)]
+    request_counts: BatchRequestCounts,
+    #[serde(default)]
+    results_url: Option<String>,
+}
+
+lazy_static::lazy_static! {
+    static ref BATCHES: Arc<RwLock<HashMap<String, BatchJob>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+}
+
+/// 构造上游请求体，返回请求体和 custom_id 映射
+pub fn build_batch_body(
+    items: Vec<BatchItem>,
+) -> Result<(serde_json::Value, BTreeMap<String, String>)> {
+    if items.is_empty() {
+        anyhow::bail!("批处理请求不能为空");
+    }
+
+    let mut id_map = BTreeMap::new();
+    let mut requests = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        if id_map.values().any(|id| id == &item.id) {
+            anyhow::bail!("批处理请求 id 重复: {}", item.id);
+        }
+        let custom_id = format!("item-{}", index);
+        requests.push(serde_json::json!({
+            "custom_id": custom_id,
+            "params": item.params,
+        }));
+        id_map.insert(custom_id, item.id);
+    }
+
+    Ok((serde_json::json!({ "requests": requests }), id_map))
+}
+
+/// 解析 JSONL 结果并映射回调用方 id
+pub fn parse_results(content: &str, id_map: &BTreeMap<String, String>) -> Vec<BatchItemResult> {
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter_map(|entry| {
+            let custom_id = entry["custom_id"].as_str()?.to_string();
+            let result = &entry["result"];
+            Some(BatchItemResult {
+                id: id_map
+                    .get(&custom_id)
+                    .cloned()
+                    .unwrap_or_else(|| custom_id.clone()),
+                custom_id,
+                result_type: result["type"].as_str().unwrap_or("unknown").to_string(),
+                message: result.get("message").cloned(),
+                error: result.get("error").cloned(),
+            })
+        })
+        .collect()
+}
+
+/// 上游返回的错误状态
+#[derive(Debug, thiserror::Error)]
+#[error("批处理请求失败: {status} - {body}")]
+struct UpstreamError {
+    status: u16,
+    body: String,
+}
+
+/// 是否为 Factory 地址（只向 Factory 发送凭证）
+fn is_factory_url(url: &str) -> bool {
+    url.strip_prefix(FACTORY_API_BASE_URL)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// 取回结果的地址：上游给出的 `results_url` 不在 Factory 下时改用 `/results`
+fn results_url(job: &BatchJob) -> String {
+    job.results_url
+        .clone()
+        .filter(|url| is_factory_url(url))
+        .unwrap_or_else(|| format!("{}/results", batch_url(&job.id)))
+}
+
+/// 经 `acquire_pinned` 占用凭证的租约，返回租约 ID
+async fn lease(
+    credential_id: &str,
+    model: &str,
+    tenant_key: Option<&str>,
+) -> Result<Option<String>> {
+    let options = AcquireOptions {
+        tenant_key: tenant_key.map(str::to_string),
+        ..Default::default()
+    };
+    let (endpoint_type, acquired) =
+        provider::acquire_pinned(credential_id, model, &options).await?;
+    let lease_id = acquired.metadata["lease_id"].as_str().map(str::to_string);
+    if endpoint_type != EndpointType::Anthropic {
+        let report = ReleaseReport {
+            lease_id,
+            status: ReleaseStatus::Cancelled,
+            ..Default::default()
+        };
+        provider::release_credential(credential_id, report).await?;
+        anyhow::bail!("批处理只支持走 Anthropic 端点的模型: {}", model);
+    }
+    Ok(lease_id)
+}
+
+/// 释放租约并上报本次访问的结果
+async fn release<T>(
+    credential_id: &str,
+    lease_id: Option<String>,
+    started: Instant,
+    outcome: &Result<T>,
+    usage: Option<UsageInfo>,
+) -> Result<()> {
+    let mut report = ReleaseReport {
+        lease_id,
+        latency_ms: Some(started.elapsed().as_millis() as u64),
+        usage,
+        ..Default::default()
+    };
+    if let Err(e) = outcome {
+        report.status = ReleaseStatus::Error;
+        report.error = Some(ErrorDetail {
+            status_code: e.downcast_ref::<UpstreamError>().map(|e| e.status),
+            message: Some(e.to_string()),
+            ..Default::default()
+        });
+    }
+    provider::release_credential(credential_id, report).await
+}
+
+/// 占用租约访问上游并读取响应体
+async fn request(
+    job: &BatchJob,
+    tenant_key: Option<&str>,
+    method: reqwest::Method,
+    url: &str,
+    body: Option<&serde_json::Value>,
+) -> Result<String> {
+    let lease_id = lease(&job.credential_id, &job.model, tenant_key).await?;
+    let started = Instant::now();
+    let outcome = match send(&job.credential_id, method, url, body).await {
+        Ok(response) => batch_timeouts().read_text(response).await,
+        Err(e) => Err(e),
+    };
+    release(&job.credential_id, lease_id, started, &outcome, None).await?;
+    outcome
+}
+
+/// 发送带凭证的请求（只发往 Factory）
+async fn send(
+    credential_id: &str,
+    method: reqwest::Method,
+    url: &str,
+    body: Option<&serde_json::Value>,
+) -> Result<reqwest::Response> {
+    if !is_factory_url(url) {
+        anyhow::bail!("拒绝向非 Factory 地址发送凭证: {}", url);
+    }
+    let authorized = provider::authorize_credential(credential_id, EndpointType::Anthropic).await?;
+
+    let client = batch_timeouts().client()?;
+    let mut headers = authorized.headers;
+    headers.insert(
+        "anthropic-version".to_string(),
+        ANTHROPIC_VERSION.to_string(),
+    );
+    let mut request = client.request(method, url);
+    for (name, value) in ordered_headers(&headers, &get_config().http.header_order) {
+        request = request.header(name, value);
+    }
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+
+    let response = batch_timeouts().send(request).await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = batch_timeouts()
+            .read_text(response)
+            .await
+            .unwrap_or_default();
+        return Err(UpstreamError {
+            status: status.as_u16(),
+            body: text,
+        }
+        .into());
+    }
+    Ok(response)
+}
+
+/// 批处理请求走 Anthropic 端点的超时
+fn batch_timeouts() -> Timeouts {
+    get_config().timeouts.for_endpoint(EndpointType::Anthropic)
+}
+
+fn batch_url(batch_id: &str) -> String {
+    format!(
+        "{}{}/{}",
+        FACTORY_API_BASE_URL, ENDPOINT_ANTHROPIC_BATCHES, batch_id
+    )
+}
+
+/// 查找批次；启用租户时只能访问本租户提交的批次
+async fn find_job(batch_id: &str, tenant_key: Option<&str>) -> Result<BatchJob> {
+    let config = get_config();
+    let tenant = tenants::resolve(&config.tenants, tenant_key)?;
+    BATCHES
+        .read()
+        .await
+        .get(batch_id)
+        .filter(|job| tenant.is_none_or(|t| job.tenant.as_deref() == Some(t.id.as_str())))
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("批次不存在: {}", batch_id))
+}
+
+/// 提交批处理
+///
+/// `tenant_key` 为客户端的虚拟密钥，启用租户时必填。
+pub async fn submit_batch(
+    credential_id: &str,
+    items: Vec<BatchItem>,
+    tenant_key: Option<&str>,
+) -> Result<BatchJob> {
+    let config = get_config();
+    let tenant = tenants::resolve(&config.tenants, tenant_key)?;
+
+    // 按租户绑定的配置档转换
+    let profile = profiles::select(&config, None, tenant_key, None)?;
+    let mut transformed = Vec::with_capacity(items.len());
+    for item in items {
+        transformed.push(BatchItem {
+            params: provider::transform_request(item.params, profile.as_deref()).await?,
+            id: item.id,
+        });
+    }
+    // 租约按等级要求最高的模型检查，避免混入昂贵模型绕过等级限制
+    let model = transformed
+        .iter()
+        .filter_map(|item| item.params["model"].as_str())
+        .max_by_key(|model| config.model_tiers.required_tier(model))
+        .unwrap_or_default()
+        .to_string();
+    let (body, id_map) = build_batch_body(transformed)?;
+
+    let mut job = BatchJob {
+        id: String::new(),
+        credential_id: credential_id.to_string(),
+        status: BatchStatus::default(),
+        request_counts: BatchRequestCounts::default(),
+        created_at: Utc::now().to_rfc3339(),
+        model,
+        results_url: None,
+        usage_recorded: false,
+        tenant: tenant.map(|t| t.id.clone()),
+        id_map,
+    };
+    let url = format!("{}{}", FACTORY_API_BASE_URL, ENDPOINT_ANTHROPIC_BATCHES);
+    let text = request(&job, tenant_key, reqwest::Method::POST, &url, Some(&body)).await?;
+    let upstream: UpstreamBatch = crate::body_text::parse_json(text.as_bytes())?;
+    job.id = upstream.id;
+    job.status = upstream.processing_status;
+    job.request_counts = upstream.request_counts;
+    job.results_url = upstream.results_url;
+    info!("已提交批处理 {}（{} 个请求）", job.id, job.id_map.len());
+    BATCHES.write().await.insert(job.id.clone(), job.clone());
+    Ok(job)
+}
+
+/// 查询批次状态（从上游拉取最新状态）
+pub async fn get_batch(batch_id: &str, tenant_key: Option<&str>) -> Result<BatchJob> {
+    let job = find_job(batch_id, tenant_key).await?;
+    if job.status == BatchStatus::Ended {
+        return Ok(job);
+    }
+
+    let text = request(
+        &job,
+        tenant_key,
+        reqwest::Method::GET,
+        &batch_url(batch_id),
+        None,
+    )
+    .await?;
+    let upstream: UpstreamBatch = crate::body_text::parse_json(text.as_bytes())?;
+    debug!("批次 {} 状态: {:?}", batch_id, upstream.processing_status);
+
+    let mut batches = BATCHES.write().await;
+    let job = batches
+        .get_mut(batch_id)
+        .ok_or_else(|| anyhow::anyhow!("批次不存在: {}", batch_id))?;
+    job.status = upstream.processing_status;
+    job.request_counts = upstream.request_counts;
+    job.results_url = upstream.results_url.or(job.results_url.take());
+    Ok(job.clone())
+}
+
+/// 列出已提交的批次（启用租户时只列出本租户的批次）
+pub async fn list_batches(tenant_key: Option<&str>) -> Result<Vec<BatchJob>> {
+    let config = get_config();
+    let tenant = tenants::resolve(&config.tenants, tenant_key)?;
+    let mut jobs: Vec<_> = BATCHES
+        .read()
+        .await
+        .values()
+        .filter(|job| tenant.is_none_or(|t| job.tenant.as_deref() == Some(t.id.as_str())))
+        .cloned()
+        .collect();
+    jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(jobs)
+}
+
+/// 取消批次
+pub async fn cancel_batch(batch_id: &str, tenant_key: Option<&str>) -> Result<BatchJob> {
+    let job = find_job(batch_id, tenant_key).await?;
+
+    let url = format!("{}/cancel", batch_url(batch_id));
+    request(&job, tenant_key, reqwest::Method::POST, &url, None).await?;
+    info!("已请求取消批次 {}", batch_id);
+    get_batch(batch_id, tenant_key).await
+}
+
+/// 获取批次结果（批次结束后可用）
+pub async fn get_batch_results(
+    batch_id: &str,
+    tenant_key: Option<&str>,
+) -> Result<Vec<BatchItemResult>> {
+    let job = get_batch(batch_id, tenant_key).await?;
+    if job.status != BatchStatus::Ended {
+        anyhow::bail!("批次尚未结束: {}", batch_id);
+    }
+
+    let lease_id = lease(&job.credential_id, &job.model, tenant_key).await?;
+    let started = Instant::now();
+    let outcome = match send(
+        &job.credential_id,
+        reqwest::Method::GET,
+        &results_url(&job),
+        None,
+    )
+    .await
+    {
+        Ok(response) => batch_timeouts().read_text(response).await,
+        Err(e) => Err(e),
+    };
+    let results = outcome
+        .as_ref()
+        .map(|content| parse_results(content, &job.id_map))
+        .unwrap_or_default();
+
+    // 各请求的用量只在首次取回结果时计入
+    let record_usage = outcome.is_ok()
+        && BATCHES
+            .write()
+            .await
+            .get_mut(batch_id)
+            .is_some_and(|job| !std::mem::replace(&mut job.usage_recorded, true));
+    let usage = record_usage.then(|| total_usage(&results));
+    release(&job.credential_id, lease_id, started, &outcome, usage).await?;
+    outcome?;
+    Ok(results)
+}
+
+/// 成功请求的 Token 用量合计
+fn total_usage(results: &[BatchItemResult]) -> UsageInfo {
+    results
+        .iter()
+        .filter_map(|r| r.message.as_ref())
+        .map(UsageInfo::from_response)
+        .fold(UsageInfo::default(), |total, usage| UsageInfo {
+            input_tokens: total.input_tokens + usage.input_tokens,
+            output_tokens: total.output_tokens + usage.output_tokens,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str) -> BatchItem {
+        BatchItem {
+            id: id.to_string(),
+            params: serde_json::json!({ "model": "claude-sonnet-4-20250514", "max_tokens": 16 }),
+        }
+    }
+
+    #[test]
+    fn test_build_batch_body() {
+        let (body, id_map) = build_batch_body(vec![item("doc/1"), item("doc/2")]).unwrap();
+        assert_eq!(body["requests"][1]["custom_id"], "item-1");
+        assert_eq!(id_map["item-0"], "doc/1");
+
+        assert!(build_batch_body(vec![item("a"), item("a")]).is_err());
+        assert!(build_batch_body(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_results_maps_ids() {
+        let (_, id_map) = build_batch_body(vec![item("doc/1"), item("doc/2")]).unwrap();
+        let content = concat!(
+            r#"{"custom_id":"item-1","result":{"type":"errored","error":{"type":"invalid_request_error"}}}"#,
+            "\n",
+            r#"{"custom_id":"item-0","result":{"type":"succeeded","message":{"id":"msg_1"}}}"#,
+        );
+
+        let results = parse_results(content, &id_map);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].id, "doc/2");
+        assert_eq!(results[0].result_type, "errored");
+        assert_eq!(results[1].id, "doc/1");
+        assert_eq!(results[1].message.as_ref().unwrap()["id"], "msg_1");
+    }
+
+    #[test]
+    fn test_results_url_stays_on_factory() {
+        let mut job = BatchJob {
+            id: "msgbatch_1".to_string(),
+            credential_id: "cred".to_string(),
+            status: BatchStatus::Ended,
+            request_counts: BatchRequestCounts::default(),
+            created_at: String::new(),
+            model: String::new(),
+            results_url: Some("https://attacker.example/results".to_string()),
+            usage_recorded: false,
+            tenant: None,
+            id_map: BTreeMap::new(),
+        };
+        assert_eq!(
+            results_url(&job),
+            format!("{}/results", batch_url("msgbatch_1"))
+        );
+
+        let factory = format!("{}/results?page=2", batch_url("msgbatch_1"));
+        job.results_url = Some(factory.clone());
+        assert_eq!(results_url(&job), factory);
+        assert!(!is_factory_url("https://api.factory.ai/api/llm.evil.com/x"));
+    }
+
+    #[test]
+    fn test_total_usage() {
+        let content = concat!(
+            r#"{"custom_id":"item-0","result":{"type":"succeeded","message":{"usage":{"input_tokens":10,"output_tokens":5}}}}"#,
+            "\n",
+            r#"{"custom_id":"item-1","result":{"type":"succeeded","message":{"usage":{"input_tokens":3,"output_tokens":2}}}}"#,
+            "\n",
+            r#"{"custom_id":"item-2","result":{"type":"errored","error":{}}}"#,
+        );
+        let usage = total_usage(&parse_results(content, &BTreeMap::new()));
+        assert_eq!((usage.input_tokens, usage.output_tokens), (13, 7));
+    }
+}
@@ -0,0 +1,55 @@
+//! 上游响应体解码
+//!
+//! 上游（Factory、WorkOS、代理网关）偶尔返回带 BOM 或夹杂非 UTF-8 字节的
+//! 响应体，尤其是错误页。直接 `from_slice` / `Response::json` 会在解析阶段
+//! 失败，连错误本身都无法处理。所有响应体统一按 UTF-8 解码（非法字节替换为
+//! U+FFFD）并去掉开头的 BOM，再交给 JSON 解析。
+
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// 去掉开头的 BOM
+pub fn strip_bom(text: &str) -> &str {
+    text.strip_prefix('\u{FEFF}').unwrap_or(text)
+}
+
+/// 解码为文本：去掉 BOM，非法 UTF-8 字节替换为 U+FFFD
+pub fn decode(bytes: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes))
+}
+
+/// 解码后解析 JSON
+pub fn parse_json<T: DeserializeOwned>(bytes: &[u8]) -> serde_json::Result<T> {
+    serde_json::from_str(&decode(bytes))
+}
+
+/// 读取上游响应体为文本
+pub async fn read_text(response: reqwest::Response) -> reqwest::Result<String> {
+    Ok(decode(&response.bytes().await?).into_owned())
+}
+
+/// 读取上游响应体并解析 JSON
+pub async fn read_json<T: DeserializeOwned>(response: reqwest::Response) -> anyhow::Result<T> {
+    let bytes = response.bytes().await?;
+    Ok(parse_json(&bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"\xEF\xBB\xBF{\"a\":1}"), "{\"a\":1}");
+        assert_eq!(decode(b"bad \xFF gateway"), "bad \u{FFFD} gateway");
+        assert_eq!(strip_bom("\u{FEFF}ok"), "ok");
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let value: serde_json::Value = parse_json(b"\xEF\xBB\xBF{\"error\":\"x\xC3\"}").unwrap();
+        assert_eq!(value["error"], "x\u{FFFD}");
+    }
+}
@@ -0,0 +1,251 @@
+//! 多凭证广播评估
+//!
+//! 评估模式下同一请求并发发往多个（凭证，模型）组合，全部响应按来源标记后
+//! 一起返回，便于比较不同账号的行为或不同模型的输出。广播请求一律非流式，
+//! 请求体格式需与目标端点一致。
+//!
+//! 每个目标与正常请求一样经过凭证可用性检查（暂停、租户、只读、等级、
+//! 启用时段、加密锁定、冷却、健康状态）并占用租约，结束后计入用量与健康
+//! 状态；不可用的目标单独报告错误，不影响其他目标。
+
+use crate::config::get_config;
+use crate::credentials::{
+    AcquiredCredential, EndpointType, ErrorDetail, ReleaseReport, ReleaseStatus, UsageInfo,
+};
+use crate::http::ordered_headers;
+use crate::provider::{self, AcquireOptions};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tracing::info;
+
+/// 广播配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BroadcastConfig {
+    pub enabled: bool,
+    /// 单次广播的目标数上限
+    pub max_targets: usize,
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_targets: 8,
+        }
+    }
+}
+
+/// 广播目标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BroadcastTarget {
+    pub credential_id: String,
+    /// 覆盖请求中的模型，为空时沿用请求本身的模型
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// 单个目标的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BroadcastResult {
+    pub credential_id: String,
+    #[serde(default)]
+    pub credential_name: Option<String>,
+    pub model: String,
+    #[serde(default)]
+    pub endpoint_type: Option<EndpointType>,
+    /// 上游状态码（未发出请求时为空）
+    #[serde(default)]
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    /// 上游响应体（非 JSON 时为字符串）
+    #[serde(default)]
+    pub response: Option<serde_json::Value>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// 校验目标列表
+pub fn validate_targets(targets: &[BroadcastTarget], config: &BroadcastConfig) -> Result<()> {
+    if !config.enabled {
+        anyhow::bail!("广播评估未启用");
+    }
+    if targets.is_empty() {
+        anyhow::bail!("至少需要一个广播目标");
+    }
+    if targets.len() > config.max_targets {
+        anyhow::bail!(
+            "广播目标过多: {}（上限 {}）",
+            targets.len(),
+            config.max_targets
+        );
+    }
+    Ok(())
+}
+
+/// 为单个目标生成请求体（替换模型并关闭流式）
+pub fn target_request(request: &serde_json::Value, model: &str) -> serde_json::Value {
+    let mut request = request.clone();
+    if let Some(object) = request.as_object_mut() {
+        object.insert("model".to_string(), serde_json::json!(model));
+        object.insert("stream".to_string(), serde_json::json!(false));
+    }
+    request
+}
+
+async fn send_to(
+    request: serde_json::Value,
+    target: BroadcastTarget,
+    options: AcquireOptions,
+) -> BroadcastResult {
+    let model = target
+        .model
+        .clone()
+        .or_else(|| request["model"].as_str().map(str::to_string))
+        .unwrap_or_default();
+    let mut result = BroadcastResult {
+        credential_id: target.credential_id.clone(),
+        model: model.clone(),
+        ..Default::default()
+    };
+    let started = Instant::now();
+    if let Err(e) = send_inner(&request, &target, &model, &options, &mut result).await {
+        result.error = Some(e.to_string());
+    }
+    result.latency_ms = started.elapsed().as_millis() as u64;
+    result
+}
+
+async fn send_inner(
+    request: &serde_json::Value,
+    target: &BroadcastTarget,
+    model: &str,
+    options: &AcquireOptions,
+    result: &mut BroadcastResult,
+) -> Result<()> {
+    let body = provider::transform_request(target_request(request, model), None).await?;
+    let (endpoint_type, acquired) =
+        provider::acquire_pinned(&target.credential_id, model, options).await?;
+    result.credential_name = acquired.name.clone();
+    result.endpoint_type = Some(endpoint_type);
+    let lease_id = acquired.metadata["lease_id"].as_str().map(str::to_string);
+
+    let started = Instant::now();
+    let sent = send_request(&body, endpoint_type, &acquired, result).await;
+    let mut report = ReleaseReport {
+        lease_id,
+        latency_ms: Some(started.elapsed().as_millis() as u64),
+        ..Default::default()
+    };
+    let success = sent.is_ok() && result.status.is_some_and(|s| (200..300).contains(&s));
+    if success {
+        report.usage = result.response.as_ref().map(UsageInfo::from_response);
+    } else {
+        report.status = ReleaseStatus::Error;
+        report.error = Some(ErrorDetail {
+            status_code: result.status,
+            message: sent.as_ref().err().map(|e| e.to_string()),
+            ..Default::default()
+        });
+    }
+    provider::release_credential(&target.credential_id, report).await?;
+    sent
+}
+
+async fn send_request(
+    body: &serde_json::Value,
+    endpoint_type: EndpointType,
+    authorized: &AcquiredCredential,
+    result: &mut BroadcastResult,
+) -> Result<()> {
+    let timeouts = get_config().timeouts.for_endpoint(endpoint_type);
+    let client = timeouts.client()?;
+    let url = authorized
+        .base_url
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("凭证缺少请求地址"))?;
+    let mut builder = client.post(url).json(body);
+    for (name, value) in ordered_headers(&authorized.headers, &get_config().http.header_order) {
+        builder = builder.header(name, value);
+    }
+
+    let response = timeouts.send(builder).await?;
+    result.status = Some(response.status().as_u16());
+    let text = timeouts.read_text(response).await?;
+    result.response = Some(serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text)));
+    Ok(())
+}
+
+/// 并发广播请求，结果顺序与目标一致
+///
+/// `tenant_key` 为客户端的虚拟密钥，启用租户时必填，只能广播到该租户可用的凭证。
+pub async fn broadcast(
+    request: serde_json::Value,
+    targets: Vec<BroadcastTarget>,
+    tenant_key: Option<String>,
+) -> Result<Vec<BroadcastResult>> {
+    validate_targets(&targets, &get_config().broadcast)?;
+    info!("广播评估请求到 {} 个目标", targets.len());
+
+    let options = AcquireOptions {
+        tenant_key,
+        client_name: Some("broadcast".to_string()),
+        ..Default::default()
+    };
+    let handles: Vec<_> = targets
+        .into_iter()
+        .map(|target| tokio::spawn(send_to(request.clone(), target, options.clone())))
+        .collect();
+    let mut results = Vec::with_capacity(handles.len());
+    for handle in handles {
+        results.push(handle.await?);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(id: &str) -> BroadcastTarget {
+        BroadcastTarget {
+            credential_id: id.to_string(),
+            model: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_targets() {
+        let mut config = BroadcastConfig::default();
+        assert!(validate_targets(&[target("a")], &config).is_err());
+
+        config.enabled = true;
+        config.max_targets = 2;
+        assert!(validate_targets(&[], &config).is_err());
+        assert!(validate_targets(&[target("a"), target("b")], &config).is_ok());
+        assert!(validate_targets(&[target("a"), target("b"), target("c")], &config).is_err());
+    }
+
+    #[test]
+    fn test_target_request() {
+        let request = serde_json::json!({
+            "model": "claude-sonnet-4-20250514",
+            "stream": true,
+            "messages": [{ "role": "user", "content": "hi" }],
+        });
+        let body = target_request(&request, "claude-opus-4-1-20250805");
+        assert_eq!(body["model"], "claude-opus-4-1-20250805");
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["messages"], request["messages"]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_credential_is_reported_per_target() {
+        let request = serde_json::json!({ "model": "claude-sonnet-4-20250514" });
+        let result = send_to(request, target("missing"), AcquireOptions::default()).await;
+        assert_eq!(result.model, "claude-sonnet-4-20250514");
+        assert!(result.status.is_none());
+        assert!(result.error.unwrap().contains("missing"));
+    }
+}
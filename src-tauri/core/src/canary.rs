@@ -0,0 +1,190 @@
+//! 新凭证的金丝雀放量
+//!
+//! 新加入凭证池的凭证先进入观察期，只分到一小部分流量；观察期结束且错误率
+//! 未超过阈值时转为正常凭证，否则标记为不健康，避免一个坏 Key 同时拖垮
+//! 大量请求。
+
+use crate::credentials::DroidCredentials;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 金丝雀配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CanaryConfig {
+    pub enabled: bool,
+    /// 分给观察期凭证的流量比例（百分比）
+    pub traffic_percent: f64,
+    /// 观察期时长（分钟）
+    pub probation_minutes: u64,
+    /// 判定错误率所需的最少请求数
+    pub min_requests: u64,
+    /// 允许的最大错误率 (0-1)
+    pub max_error_rate: f64,
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            traffic_percent: 5.0,
+            probation_minutes: 60,
+            min_requests: 20,
+            max_error_rate: 0.2,
+        }
+    }
+}
+
+/// 观察期状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryState {
+    /// 观察期开始时间 (RFC3339 格式)
+    pub started_at: String,
+    #[serde(default)]
+    pub requests: u64,
+    #[serde(default)]
+    pub errors: u64,
+}
+
+impl CanaryState {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            started_at: now.to_rfc3339(),
+            requests: 0,
+            errors: 0,
+        }
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.requests as f64
+        }
+    }
+}
+
+/// 记录一次请求后的观察结论
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanaryVerdict {
+    /// 仍在观察
+    Probation,
+    /// 通过观察，转为正常凭证
+    Promoted,
+    /// 错误率超标
+    Failed,
+}
+
+/// 新凭证进入观察期（未启用时不处理）
+pub fn start(config: &CanaryConfig, credential: &mut DroidCredentials) {
+    if config.enabled {
+        credential.canary = Some(CanaryState::new(Utc::now()));
+    }
+}
+
+/// 本次请求是否分给观察期凭证
+pub fn route_to_canary(config: &CanaryConfig) -> bool {
+    rand::random::<f64>() * 100.0 < config.traffic_percent
+}
+
+/// 记录观察期凭证的请求结果
+pub fn record(
+    config: &CanaryConfig,
+    credential: &mut DroidCredentials,
+    success: bool,
+    now: DateTime<Utc>,
+) -> CanaryVerdict {
+    let Some(state) = credential.canary.as_mut() else {
+        return CanaryVerdict::Promoted;
+    };
+    state.requests += 1;
+    if !success {
+        state.errors += 1;
+    }
+
+    let enough = state.requests >= config.min_requests;
+    if enough && state.error_rate() > config.max_error_rate {
+        // 重新开始观察，恢复健康后仍需再次通过
+        credential.canary = Some(CanaryState::new(now));
+        credential.health.marked_unhealthy = true;
+        return CanaryVerdict::Failed;
+    }
+
+    let elapsed = DateTime::parse_from_rfc3339(&state.started_at)
+        .map(|started| now - started.with_timezone(&Utc))
+        .unwrap_or_else(|_| Duration::zero());
+    if enough && elapsed >= Duration::minutes(config.probation_minutes as i64) {
+        credential.canary = None;
+        return CanaryVerdict::Promoted;
+    }
+    CanaryVerdict::Probation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CanaryConfig {
+        CanaryConfig {
+            enabled: true,
+            min_requests: 4,
+            max_error_rate: 0.25,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_failed_canary_is_marked_unhealthy() {
+        let config = config();
+        let mut credential = DroidCredentials::default();
+        start(&config, &mut credential);
+
+        let now = Utc::now();
+        assert_eq!(
+            record(&config, &mut credential, false, now),
+            CanaryVerdict::Probation
+        );
+        assert_eq!(
+            record(&config, &mut credential, true, now),
+            CanaryVerdict::Probation
+        );
+        assert_eq!(
+            record(&config, &mut credential, false, now),
+            CanaryVerdict::Probation
+        );
+        assert_eq!(
+            record(&config, &mut credential, true, now),
+            CanaryVerdict::Failed
+        );
+        assert!(credential.health.marked_unhealthy);
+        assert_eq!(credential.canary.as_ref().unwrap().requests, 0);
+    }
+
+    #[test]
+    fn test_promoted_after_probation() {
+        let config = config();
+        let mut credential = DroidCredentials::default();
+        start(&config, &mut credential);
+
+        let now = Utc::now();
+        for _ in 0..4 {
+            assert_eq!(
+                record(&config, &mut credential, true, now),
+                CanaryVerdict::Probation
+            );
+        }
+        let later = now + Duration::minutes(config.probation_minutes as i64);
+        assert_eq!(
+            record(&config, &mut credential, true, later),
+            CanaryVerdict::Promoted
+        );
+        assert!(credential.canary.is_none());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut credential = DroidCredentials::default();
+        start(&CanaryConfig::default(), &mut credential);
+        assert!(credential.canary.is_none());
+    }
+}
@@ -0,0 +1,189 @@
+//! 能力协商
+//!
+//! 前端按 `get_provider_capabilities` 的结果调整界面，而不是假定后端支持
+//! 某个功能：支持的认证方式、端点、凭证选择策略与约束、可用中间件、
+//! 各可选功能当前是否开启，以及各持久化结构的版本号。
+//! 新增可选功能时在 `features` 中补上一项；结构有不兼容变化时
+//! `CAPABILITIES_VERSION` 加一。
+
+use crate::config::ProviderConfig;
+use crate::credentials::{AuthType, EndpointType};
+use crate::hooks;
+use crate::middleware;
+use crate::migrations;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 能力描述结构版本
+pub const CAPABILITIES_VERSION: u32 = 1;
+
+/// 凭证选择策略
+pub const SELECTION_STRATEGIES: &[&str] = &["health_weighted"];
+
+/// 选择时生效的约束（按执行顺序）
+pub const SELECTION_CONSTRAINTS: &[&str] = &[
+    "tenant",
+    "read_only",
+    "health",
+    "cooldown",
+    "active_hours",
+    "model_tier",
+    "model_access",
+    "endpoint_support",
+    "concurrency",
+    "canary",
+    "quota_link",
+    "hook",
+];
+
+/// 中间件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MiddlewareCapability {
+    pub name: String,
+    pub builtin: bool,
+    /// 是否在当前配置的执行链中
+    pub enabled: bool,
+}
+
+/// 凭证选择
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectionCapabilities {
+    pub strategies: Vec<String>,
+    pub default_strategy: String,
+    pub constraints: Vec<String>,
+}
+
+/// 结构版本号
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaVersions {
+    pub capabilities: u32,
+    /// 凭证文件（`credentials.json`）
+    pub credentials: u32,
+    pub json_rpc: String,
+}
+
+/// 后端能力
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderCapabilities {
+    pub version: String,
+    /// 与 `create_credential` 的 `auth_type` 参数一致
+    pub auth_types: Vec<String>,
+    pub endpoint_types: Vec<String>,
+    pub selection: SelectionCapabilities,
+    pub middleware: Vec<MiddlewareCapability>,
+    /// 可选功能 → 当前是否开启
+    pub features: BTreeMap<String, bool>,
+    pub schema_versions: SchemaVersions,
+}
+
+fn features(config: &ProviderConfig) -> BTreeMap<String, bool> {
+    [
+        ("app_lock", config.app_lock.enabled),
+        ("broadcast", config.broadcast.enabled),
+        ("canary", config.canary.enabled),
+        ("chaos", config.chaos.enabled),
+        ("chat_normalize", config.chat_normalize.enabled),
+        ("compression", config.compression.enabled),
+        ("context_trim", config.context_trim.enabled),
+        ("dead_credentials", config.dead_credentials.enabled),
+        ("dedup", config.dedup.enabled),
+        ("digest", config.digest.enabled),
+        ("failover", config.failover.enabled),
+        ("heartbeat", config.heartbeat.enabled),
+        ("hooks", config.hooks.enabled),
+        ("keepalive", config.keepalive.enabled),
+        ("maintenance", config.maintenance.enabled),
+        ("mock", config.mock.enabled),
+        ("model_tiers", !config.model_tiers.rules.is_empty()),
+        ("quota_link", config.quota_link.enabled),
+        ("reassembly", config.reassembly.enabled),
+        ("relogin", config.relogin.enabled),
+        ("response_repair", config.response_repair.enabled),
+        ("retry_budget", config.retry_budget.enabled),
+        ("salvage", config.salvage.enabled),
+        ("spool", config.spool.enabled),
+        ("tenants", config.tenants.enabled),
+        ("wake", config.wake.enabled),
+    ]
+    .into_iter()
+    .map(|(name, enabled)| (name.to_string(), enabled))
+    .collect()
+}
+
+/// 按当前配置汇总能力
+pub fn describe(config: &ProviderConfig) -> ProviderCapabilities {
+    let to_strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+    ProviderCapabilities {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        auth_types: [AuthType::OAuth, AuthType::ApiKey]
+            .iter()
+            .map(|t| t.to_string())
+            .collect(),
+        endpoint_types: [
+            EndpointType::Anthropic,
+            EndpointType::OpenAI,
+            EndpointType::Comm,
+        ]
+        .iter()
+        .map(|e| e.to_string())
+        .collect(),
+        selection: SelectionCapabilities {
+            strategies: to_strings(SELECTION_STRATEGIES),
+            default_strategy: SELECTION_STRATEGIES[0].to_string(),
+            constraints: to_strings(SELECTION_CONSTRAINTS),
+        },
+        middleware: middleware::available()
+            .into_iter()
+            .chain(
+                config
+                    .hooks
+                    .modules
+                    .iter()
+                    .map(|m| format!("{}{}", hooks::MIDDLEWARE_PREFIX, m.name)),
+            )
+            .map(|name| MiddlewareCapability {
+                builtin: middleware::BUILTIN.contains(&name.as_str()),
+                enabled: config.middleware.0.contains(&name),
+                name,
+            })
+            .collect(),
+        features: features(config),
+        schema_versions: SchemaVersions {
+            capabilities: CAPABILITIES_VERSION,
+            credentials: migrations::CURRENT_VERSION,
+            json_rpc: "2.0".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let mut config = ProviderConfig::default();
+        config.middleware.0.retain(|name| name != "content_filter");
+        let capabilities = describe(&config);
+
+        let value = serde_json::to_value(&capabilities).unwrap();
+        assert_eq!(value["auth_types"], serde_json::json!(["oauth", "api_key"]));
+        assert_eq!(
+            value["endpoint_types"],
+            serde_json::json!(["anthropic", "openai", "comm"])
+        );
+        assert_eq!(
+            capabilities.schema_versions.credentials,
+            migrations::CURRENT_VERSION
+        );
+
+        let filter = capabilities
+            .middleware
+            .iter()
+            .find(|m| m.name == "content_filter")
+            .unwrap();
+        assert!(filter.builtin && !filter.enabled);
+        assert!(!capabilities.features["model_tiers"]);
+        assert!(capabilities.features.contains_key("dedup"));
+    }
+}
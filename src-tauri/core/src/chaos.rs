@@ -0,0 +1,313 @@
+//! 故障注入（混沌模式）
+//!
+//! 开发者用于验证故障转移、冷却与重试逻辑：开启后按配置的概率为每个租约
+//! 抽取一种故障——429、5xx、慢响应或流中途断开，不必真的把账号打到限流。
+//! 故障在 `acquire_credential` 时决定并写入元数据 `chaos_fault`：
+//! 错误类故障由宿主直接以该状态码应答（不访问上游），同时 `transform_response`
+//! 也会把响应替换为错误体；慢响应与断流在响应转换阶段生效。慢响应只在
+//! 转换结果中返回 `delay_ms`，由宿主等待，不占用 JSON-RPC 主循环。
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::info;
+
+/// 故障注入配置（各概率取 0.0 ~ 1.0，依次抽取，总和不应超过 1）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChaosConfig {
+    pub enabled: bool,
+    /// 返回 429 的概率
+    pub rate_limit_rate: f64,
+    /// 429 附带的 Retry-After（秒）
+    pub retry_after_seconds: u64,
+    /// 返回 5xx 的概率
+    pub server_error_rate: f64,
+    /// 5xx 状态码（随机选取）
+    pub server_error_statuses: Vec<u16>,
+    /// 慢响应的概率
+    pub slow_rate: f64,
+    /// 慢响应额外延迟（毫秒）
+    pub slow_delay_ms: u64,
+    /// 流中途断开的概率
+    pub stream_drop_rate: f64,
+    /// 断流前放行的分块数
+    pub stream_drop_after_chunks: u32,
+    /// 只对这些凭证注入（为空时对全部凭证）
+    pub credential_ids: Vec<String>,
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rate_limit_rate: 0.0,
+            retry_after_seconds: 30,
+            server_error_rate: 0.0,
+            server_error_statuses: vec![500, 502, 503, 529],
+            slow_rate: 0.0,
+            slow_delay_ms: 5_000,
+            stream_drop_rate: 0.0,
+            stream_drop_after_chunks: 5,
+            credential_ids: Vec::new(),
+        }
+    }
+}
+
+/// 注入的故障
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ChaosFault {
+    /// 以指定状态码应答
+    Status {
+        status_code: u16,
+        #[serde(default)]
+        retry_after_seconds: Option<u64>,
+    },
+    /// 响应延迟
+    Slow { delay_ms: u64 },
+    /// 流在若干分块后断开
+    StreamDrop { after_chunks: u32 },
+}
+
+impl ChaosFault {
+    /// 错误类故障替换后的响应（响应体、状态码与响应头）
+    pub fn injected_response(&self) -> Option<serde_json::Value> {
+        let ChaosFault::Status {
+            status_code,
+            retry_after_seconds,
+        } = self
+        else {
+            return None;
+        };
+        let error_type = match status_code {
+            429 => "rate_limit_error",
+            529 => "overloaded_error",
+            _ => "api_error",
+        };
+        let body = serde_json::json!({
+            "type": "error",
+            "error": {
+                "type": error_type,
+                "message": format!("Chaos: injected {}", status_code),
+            }
+        });
+        let headers = match retry_after_seconds {
+            Some(seconds) => serde_json::json!({ "retry-after": seconds.to_string() }),
+            None => serde_json::json!({}),
+        };
+        Some(serde_json::json!({
+            "response": body,
+            "status_code": status_code,
+            "headers": headers,
+            "chaos": true,
+        }))
+    }
+}
+
+/// 按随机数抽取故障（`roll` 取 [0, 1)，`pick` 用于选择 5xx 状态码）
+pub fn roll(config: &ChaosConfig, roll: f64, pick: usize) -> Option<ChaosFault> {
+    let mut threshold = config.rate_limit_rate;
+    if roll < threshold {
+        return Some(ChaosFault::Status {
+            status_code: 429,
+            retry_after_seconds: Some(config.retry_after_seconds),
+        });
+    }
+    threshold += config.server_error_rate;
+    if roll < threshold && !config.server_error_statuses.is_empty() {
+        let statuses = &config.server_error_statuses;
+        return Some(ChaosFault::Status {
+            status_code: statuses[pick % statuses.len()],
+            retry_after_seconds: None,
+        });
+    }
+    threshold += config.slow_rate;
+    if roll < threshold {
+        return Some(ChaosFault::Slow {
+            delay_ms: config.slow_delay_ms,
+        });
+    }
+    threshold += config.stream_drop_rate;
+    if roll < threshold {
+        return Some(ChaosFault::StreamDrop {
+            after_chunks: config.stream_drop_after_chunks,
+        });
+    }
+    None
+}
+
+/// 租约上的故障与已放行的分块数
+struct LeaseFault {
+    fault: ChaosFault,
+    chunks: u32,
+    delayed: bool,
+}
+
+lazy_static::lazy_static! {
+    static ref FAULTS: Mutex<HashMap<String, LeaseFault>> = Mutex::new(HashMap::new());
+}
+
+/// 为新租约抽取故障
+pub fn assign(config: &ChaosConfig, lease_id: &str, credential_id: &str) -> Option<ChaosFault> {
+    if !config.enabled {
+        return None;
+    }
+    let targets = &config.credential_ids;
+    if !targets.is_empty() && !targets.iter().any(|id| id == credential_id) {
+        return None;
+    }
+    let mut rng = rand::thread_rng();
+    let fault = roll(config, rng.gen(), rng.gen())?;
+    info!("混沌模式：租约 {} 注入故障 {:?}", lease_id, fault);
+    FAULTS.lock().unwrap().insert(
+        lease_id.to_string(),
+        LeaseFault {
+            fault: fault.clone(),
+            chunks: 0,
+            delayed: false,
+        },
+    );
+    Some(fault)
+}
+
+/// 租约结束时清理
+pub fn finish(lease_id: &str) {
+    FAULTS.lock().unwrap().remove(lease_id);
+}
+
+/// 租约上的错误类故障（替换非流式响应）
+pub fn status_fault(lease_id: &str) -> Option<ChaosFault> {
+    FAULTS
+        .lock()
+        .unwrap()
+        .get(lease_id)
+        .map(|f| f.fault.clone())
+        .filter(|fault| matches!(fault, ChaosFault::Status { .. }))
+}
+
+/// 慢响应：返回宿主应额外等待的时长，每个租约只延迟一次（流式响应在
+/// 首个分块前延迟）
+pub fn take_delay(lease_id: &str) -> std::time::Duration {
+    let mut faults = FAULTS.lock().unwrap();
+    match faults.get_mut(lease_id) {
+        Some(entry) if !entry.delayed => match entry.fault {
+            ChaosFault::Slow { delay_ms } => {
+                entry.delayed = true;
+                std::time::Duration::from_millis(delay_ms)
+            }
+            _ => std::time::Duration::ZERO,
+        },
+        _ => std::time::Duration::ZERO,
+    }
+}
+
+/// 记录一个流分块，到达断流点后返回错误
+pub fn check_stream(lease_id: &str) -> anyhow::Result<()> {
+    let mut faults = FAULTS.lock().unwrap();
+    let Some(entry) = faults.get_mut(lease_id) else {
+        return Ok(());
+    };
+    if let ChaosFault::StreamDrop { after_chunks } = entry.fault {
+        if entry.chunks >= after_chunks {
+            anyhow::bail!("Chaos: 流在第 {} 个分块后被中断", after_chunks);
+        }
+        entry.chunks += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roll() {
+        let config = ChaosConfig {
+            enabled: true,
+            rate_limit_rate: 0.1,
+            server_error_rate: 0.2,
+            slow_rate: 0.1,
+            stream_drop_rate: 0.1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            roll(&config, 0.05, 0),
+            Some(ChaosFault::Status {
+                status_code: 429,
+                ..
+            })
+        ));
+        assert!(matches!(
+            roll(&config, 0.2, 2),
+            Some(ChaosFault::Status {
+                status_code: 503,
+                ..
+            })
+        ));
+        assert_eq!(
+            roll(&config, 0.35, 0),
+            Some(ChaosFault::Slow { delay_ms: 5_000 })
+        );
+        assert_eq!(
+            roll(&config, 0.45, 0),
+            Some(ChaosFault::StreamDrop { after_chunks: 5 })
+        );
+        assert_eq!(roll(&config, 0.6, 0), None);
+    }
+
+    #[test]
+    fn test_stream_drop() {
+        let config = ChaosConfig {
+            enabled: true,
+            stream_drop_rate: 1.0,
+            stream_drop_after_chunks: 2,
+            ..Default::default()
+        };
+        let fault = assign(&config, "chaos-lease", "cred").unwrap();
+        assert_eq!(fault, ChaosFault::StreamDrop { after_chunks: 2 });
+        assert!(status_fault("chaos-lease").is_none());
+        assert!(check_stream("chaos-lease").is_ok());
+        assert!(check_stream("chaos-lease").is_ok());
+        assert!(check_stream("chaos-lease").is_err());
+
+        finish("chaos-lease");
+        assert!(check_stream("chaos-lease").is_ok());
+    }
+
+    #[test]
+    fn test_slow_delay_taken_once() {
+        let config = ChaosConfig {
+            enabled: true,
+            slow_rate: 1.0,
+            slow_delay_ms: 250,
+            ..Default::default()
+        };
+        assign(&config, "slow-lease", "cred").unwrap();
+        assert_eq!(
+            take_delay("slow-lease"),
+            std::time::Duration::from_millis(250)
+        );
+        assert!(take_delay("slow-lease").is_zero());
+        assert!(take_delay("unknown-lease").is_zero());
+        finish("slow-lease");
+    }
+
+    #[test]
+    fn test_credential_filter() {
+        let config = ChaosConfig {
+            enabled: true,
+            rate_limit_rate: 1.0,
+            credential_ids: vec!["target".to_string()],
+            ..Default::default()
+        };
+        assert!(assign(&config, "chaos-other", "other").is_none());
+        let fault = assign(&config, "chaos-target", "target").unwrap();
+        let response = fault.injected_response().unwrap();
+        assert_eq!(response["status_code"], 429);
+        assert_eq!(response["response"]["error"]["type"], "rate_limit_error");
+        assert_eq!(response["headers"]["retry-after"], "30");
+        finish("chaos-target");
+    }
+}
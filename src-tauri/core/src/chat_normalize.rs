@@ -0,0 +1,280 @@
+//! Chat Completions 工具调用规范化
+//!
+//! Comm 端点（`/o/v1/chat/completions`）返回的工具调用与 OpenAI 官方格式有
+//! 几处出入：`function.arguments` 偶尔是对象而不是字符串、缺少 `type` / `id`、
+//! 流式增量缺少 `index` 或在每个增量里重复 `id` 与函数名（客户端会把函数名
+//! 拼接成 `read_fileread_file`）、产生工具调用后 `finish_reason` 仍为 `stop`，
+//! 以及旧式的 `function_call`。这里按官方格式统一，下游客户端看到的
+//! content、tool_calls、finish_reason 与其他路径一致。只处理 Chat Completions
+//! 形态的响应，其他格式原样通过。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 流状态保留时长，超时未结束的流视为已中断
+const STREAM_STATE_TTL: Duration = Duration::from_secs(600);
+
+/// 规范化配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatNormalizeConfig {
+    pub enabled: bool,
+}
+
+impl Default for ChatNormalizeConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// 参数统一为 JSON 字符串
+fn normalize_arguments(function: &mut serde_json::Value) {
+    let arguments = &mut function["arguments"];
+    match arguments {
+        serde_json::Value::String(_) => {}
+        serde_json::Value::Null => *arguments = serde_json::json!("{}"),
+        other => *other = serde_json::json!(other.to_string()),
+    }
+}
+
+fn is_tool_finish(reason: &serde_json::Value) -> bool {
+    matches!(reason.as_str(), None | Some("stop") | Some("function_call"))
+}
+
+/// 非流式响应
+pub fn normalize_response(response: &mut serde_json::Value) {
+    let Some(choices) = response.get_mut("choices").and_then(|c| c.as_array_mut()) else {
+        return;
+    };
+    for (choice_index, choice) in choices.iter_mut().enumerate() {
+        let Some(message) = choice.get_mut("message").filter(|m| m.is_object()) else {
+            continue;
+        };
+        // 旧式 function_call 转为 tool_calls
+        if let Some(function_call) = message
+            .as_object_mut()
+            .and_then(|m| m.remove("function_call"))
+        {
+            if message["tool_calls"].is_null() && !function_call.is_null() {
+                message["tool_calls"] = serde_json::json!([{ "function": function_call }]);
+            }
+        }
+        let calls = message.get_mut("tool_calls").and_then(|c| c.as_array_mut());
+        let has_calls = calls.as_ref().is_some_and(|c| !c.is_empty());
+        for (index, call) in calls.into_iter().flatten().enumerate() {
+            if call["id"].as_str().is_none_or(str::is_empty) {
+                call["id"] = serde_json::json!(format!("call_{}_{}", choice_index, index));
+            }
+            call["type"] = serde_json::json!("function");
+            normalize_arguments(&mut call["function"]);
+        }
+        if !has_calls {
+            if let Some(message) = message.as_object_mut() {
+                message.remove("tool_calls");
+            }
+            continue;
+        }
+        // 只有工具调用时 content 为 null
+        if message["content"] == "" {
+            message["content"] = serde_json::Value::Null;
+        }
+        if is_tool_finish(&choice["finish_reason"]) {
+            choice["finish_reason"] = serde_json::json!("tool_calls");
+        }
+    }
+}
+
+/// 单个 choice 的流状态
+#[derive(Debug, Clone, Default)]
+struct ChoiceState {
+    /// 已开始的工具调用数
+    calls: u64,
+}
+
+/// 单个流的规范化状态
+#[derive(Debug, Default)]
+pub struct ChatStreamNormalizer {
+    choices: HashMap<u64, ChoiceState>,
+}
+
+impl ChatStreamNormalizer {
+    /// 规范化一个流式块，返回该流是否已结束
+    pub fn normalize(&mut self, chunk: &mut serde_json::Value) -> bool {
+        let Some(choices) = chunk.get_mut("choices").and_then(|c| c.as_array_mut()) else {
+            return false;
+        };
+        let mut finished = false;
+        for (position, choice) in choices.iter_mut().enumerate() {
+            let choice_index = choice["index"].as_u64().unwrap_or(position as u64);
+            let state = self.choices.entry(choice_index).or_default();
+            let calls = choice
+                .pointer_mut("/delta/tool_calls")
+                .and_then(|c| c.as_array_mut());
+            for call in calls.into_iter().flatten() {
+                normalize_delta(state, choice_index, call);
+            }
+            if !choice["finish_reason"].is_null() {
+                finished = true;
+                if state.calls > 0 && is_tool_finish(&choice["finish_reason"]) {
+                    choice["finish_reason"] = serde_json::json!("tool_calls");
+                }
+            }
+        }
+        finished
+    }
+}
+
+/// 规范化一个工具调用增量：新调用补全 `index` / `id` / `type`，
+/// 后续增量去掉重复的 `id`、`type` 与函数名
+fn normalize_delta(state: &mut ChoiceState, choice_index: u64, call: &mut serde_json::Value) {
+    let has_id = call["id"].as_str().is_some_and(|id| !id.is_empty());
+    let index = match call["index"].as_u64() {
+        Some(index) => index,
+        // 没有 index 时：带 id 的是新调用，否则续接上一个调用
+        None if has_id || state.calls == 0 => state.calls,
+        None => state.calls - 1,
+    };
+    call["index"] = serde_json::json!(index);
+
+    if index >= state.calls {
+        state.calls = index + 1;
+        if !has_id {
+            call["id"] = serde_json::json!(format!("call_{}_{}", choice_index, index));
+        }
+        call["type"] = serde_json::json!("function");
+    } else if let Some(call) = call.as_object_mut() {
+        call.remove("id");
+        call.remove("type");
+        if let Some(function) = call.get_mut("function").and_then(|f| f.as_object_mut()) {
+            function.remove("name");
+        }
+    }
+    let arguments = &mut call["function"]["arguments"];
+    if !arguments.is_string() && !arguments.is_null() {
+        *arguments = serde_json::json!(arguments.to_string());
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref STREAMS: Mutex<HashMap<String, (Instant, ChatStreamNormalizer)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// 规范化流式块（按块中的 `id` 区分不同的流）
+pub fn normalize_chunk(chunk: &mut serde_json::Value) {
+    if chunk["choices"].is_null() {
+        return;
+    }
+    // 没有 id 的块无法关联到流，只做单块内的规范化
+    let Some(key) = chunk["id"].as_str().map(str::to_string) else {
+        ChatStreamNormalizer::default().normalize(chunk);
+        return;
+    };
+    let mut streams = STREAMS.lock().unwrap();
+    streams.retain(|_, (touched, _)| touched.elapsed() < STREAM_STATE_TTL);
+    let (touched, normalizer) = streams
+        .entry(key.clone())
+        .or_insert_with(|| (Instant::now(), ChatStreamNormalizer::default()));
+    *touched = Instant::now();
+    if normalizer.normalize(chunk) {
+        streams.remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_response() {
+        let mut response = serde_json::json!({
+            "object": "chat.completion",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": "",
+                    "tool_calls": [{ "function": { "name": "read", "arguments": { "path": "a" } } }]
+                },
+                "finish_reason": "stop"
+            }, {
+                "index": 1,
+                "message": { "role": "assistant", "content": "hi", "tool_calls": [] },
+                "finish_reason": "stop"
+            }]
+        });
+        normalize_response(&mut response);
+
+        let choice = &response["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert!(choice["message"]["content"].is_null());
+        let call = &choice["message"]["tool_calls"][0];
+        assert_eq!(call["type"], "function");
+        assert_eq!(call["id"], "call_0_0");
+        assert_eq!(call["function"]["arguments"], "{\"path\":\"a\"}");
+
+        let plain = &response["choices"][1];
+        assert_eq!(plain["finish_reason"], "stop");
+        assert!(plain["message"].get("tool_calls").is_none());
+    }
+
+    #[test]
+    fn test_legacy_function_call() {
+        let mut response = serde_json::json!({
+            "choices": [{
+                "message": { "content": null, "function_call": { "name": "f", "arguments": "{}" } },
+                "finish_reason": "function_call"
+            }]
+        });
+        normalize_response(&mut response);
+        let choice = &response["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert_eq!(choice["message"]["tool_calls"][0]["function"]["name"], "f");
+        assert!(choice["message"].get("function_call").is_none());
+    }
+
+    #[test]
+    fn test_stream_deltas() {
+        let mut normalizer = ChatStreamNormalizer::default();
+        let mut chunks = [
+            serde_json::json!({ "choices": [{ "delta": { "tool_calls": [
+                { "id": "call_a", "function": { "name": "read", "arguments": "" } }
+            ] } }] }),
+            // 重复 id 与函数名，没有 index
+            serde_json::json!({ "choices": [{ "delta": { "tool_calls": [
+                { "function": { "name": "read", "arguments": "{\"path\"" } }
+            ] } }] }),
+            serde_json::json!({ "choices": [{ "delta": { "tool_calls": [
+                { "id": "call_b", "function": { "name": "list", "arguments": { "dir": "." } } }
+            ] } }] }),
+            serde_json::json!({ "choices": [{ "delta": {}, "finish_reason": "stop" }] }),
+        ];
+        let finished: Vec<bool> = chunks.iter_mut().map(|c| normalizer.normalize(c)).collect();
+        assert_eq!(finished, vec![false, false, false, true]);
+
+        let first = &chunks[0]["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(first["index"], 0);
+        assert_eq!(first["type"], "function");
+        let repeat = &chunks[1]["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(repeat["index"], 0);
+        assert!(repeat.get("id").is_none());
+        assert!(repeat["function"].get("name").is_none());
+        let second = &chunks[2]["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(second["index"], 1);
+        assert_eq!(second["function"]["arguments"], "{\"dir\":\".\"}");
+        assert_eq!(chunks[3]["choices"][0]["finish_reason"], "tool_calls");
+    }
+
+    #[test]
+    fn test_text_stream_unchanged() {
+        let mut chunk = serde_json::json!({
+            "id": "chatcmpl-text",
+            "choices": [{ "index": 0, "delta": { "content": "hi" }, "finish_reason": "stop" }]
+        });
+        let original = chunk.clone();
+        normalize_chunk(&mut chunk);
+        assert_eq!(chunk, original);
+    }
+}
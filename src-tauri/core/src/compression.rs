@@ -0,0 +1,392 @@
+//! 响应压缩
+//!
+//! 插件自身的 HTTP 客户端由 reqwest 自动协商并解压 gzip / brotli。
+//! 启用后，宿主转发请求时 acquire 返回的请求头带上 `Accept-Encoding`；
+//! 收到压缩的上游响应后先解压再交给 `transform_response`，
+//! 返回给本地客户端时按其 `Accept-Encoding` 重新压缩。压缩的流式响应
+//! 按租约逐块交给 `decode_stream_chunk` 增量解压，得到的文本再按 SSE 解析。
+//! 解压后的大小有上限，防止压缩炸弹。
+
+use crate::body_text;
+use anyhow::Result;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::Mutex;
+
+/// 压缩配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    /// 向上游声明的 Accept-Encoding
+    pub accept_encoding: String,
+    /// 小于该字节数的响应不重新压缩
+    pub min_compress_bytes: usize,
+    /// 单个响应（含整个流）解压后的大小上限（字节）
+    pub max_decompressed_bytes: u64,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            accept_encoding: "br, gzip".to_string(),
+            min_compress_bytes: 1024,
+            max_decompressed_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// 解压后超过大小上限
+#[derive(Debug, thiserror::Error)]
+#[error("解压后的响应超过 {0} 字节上限")]
+pub struct DecompressedTooLarge(pub u64);
+
+/// 内容编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    Identity,
+    Gzip,
+    #[serde(rename = "br")]
+    Brotli,
+}
+
+impl Encoding {
+    /// 解析 Content-Encoding 头，不支持的编码返回错误
+    pub fn parse(header: Option<&str>) -> Result<Self> {
+        match header.map(|h| h.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") | Some("identity") => Ok(Encoding::Identity),
+            Some("gzip") | Some("x-gzip") => Ok(Encoding::Gzip),
+            Some("br") => Ok(Encoding::Brotli),
+            Some(other) => anyhow::bail!("不支持的 Content-Encoding: {}", other),
+        }
+    }
+
+    /// Content-Encoding 头的值，不压缩时为 None
+    pub fn header_value(self) -> Option<&'static str> {
+        match self {
+            Encoding::Identity => None,
+            Encoding::Gzip => Some("gzip"),
+            Encoding::Brotli => Some("br"),
+        }
+    }
+}
+
+/// 按客户端的 Accept-Encoding 选择编码（brotli 优先，忽略 q=0）
+pub fn negotiate(accept_encoding: Option<&str>) -> Encoding {
+    let accepted: Vec<(String, f32)> = accept_encoding
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let name = parts.next()?.trim().to_ascii_lowercase();
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q=").and_then(|q| q.parse().ok()))
+                .unwrap_or(1.0);
+            (!name.is_empty()).then_some((name, q))
+        })
+        .collect();
+    let quality = |name: &str| {
+        accepted
+            .iter()
+            .find(|(n, _)| n == name || n == "*")
+            .map(|(_, q)| *q)
+            .unwrap_or(0.0)
+    };
+
+    match (quality("br"), quality("gzip")) {
+        (br, gzip) if br > 0.0 && br >= gzip => Encoding::Brotli,
+        (_, gzip) if gzip > 0.0 => Encoding::Gzip,
+        _ => Encoding::Identity,
+    }
+}
+
+/// 解压，解压后超过 `max_bytes` 时返回错误
+pub fn decompress(body: &[u8], encoding: Encoding, max_bytes: u64) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    // 多读一个字节，据此判断是否超限
+    let limit = max_bytes.saturating_add(1);
+    match encoding {
+        Encoding::Identity => output.extend_from_slice(body),
+        Encoding::Gzip => {
+            flate2::read::MultiGzDecoder::new(body)
+                .take(limit)
+                .read_to_end(&mut output)?;
+        }
+        Encoding::Brotli => {
+            brotli::Decompressor::new(body, 4096)
+                .take(limit)
+                .read_to_end(&mut output)?;
+        }
+    }
+    if output.len() as u64 > max_bytes {
+        return Err(DecompressedTooLarge(max_bytes).into());
+    }
+    Ok(output)
+}
+
+/// 压缩
+pub fn compress(body: &[u8], encoding: Encoding) -> Result<Vec<u8>> {
+    match encoding {
+        Encoding::Identity => Ok(body.to_vec()),
+        Encoding::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(body)?;
+            Ok(encoder.finish()?)
+        }
+        Encoding::Brotli => {
+            let mut output = Vec::new();
+            {
+                let mut writer = brotli::CompressorWriter::new(&mut output, 4096, 5, 22);
+                writer.write_all(body)?;
+            }
+            Ok(output)
+        }
+    }
+}
+
+/// 解码宿主传来的 base64 响应体并解压
+fn decode_body(body_base64: &str, content_encoding: Option<&str>) -> Result<Vec<u8>> {
+    let body = base64::engine::general_purpose::STANDARD.decode(body_base64)?;
+    let max_bytes = crate::config::get_config()
+        .compression
+        .max_decompressed_bytes;
+    decompress(&body, Encoding::parse(content_encoding)?, max_bytes)
+}
+
+/// 解码宿主传来的 base64 响应体并解压为 JSON
+pub fn decode_json(body_base64: &str, content_encoding: Option<&str>) -> Result<serde_json::Value> {
+    let decoded = decode_body(body_base64, content_encoding)?;
+    Ok(body_text::parse_json(&decoded)?)
+}
+
+/// 解码宿主传来的 base64 响应体并解压为文本（宽松 UTF-8）
+pub fn decode_text(body_base64: &str, content_encoding: Option<&str>) -> Result<String> {
+    let decoded = decode_body(body_base64, content_encoding)?;
+    Ok(body_text::decode(&decoded).into_owned())
+}
+
+/// 按客户端 Accept-Encoding 压缩 JSON，返回 base64 响应体与所用编码
+pub fn encode_json(
+    value: &serde_json::Value,
+    accept_encoding: Option<&str>,
+    config: &CompressionConfig,
+) -> Result<(String, Encoding)> {
+    let body = serde_json::to_vec(value)?;
+    let encoding = if config.enabled && body.len() >= config.min_compress_bytes {
+        negotiate(accept_encoding)
+    } else {
+        Encoding::Identity
+    };
+    let encoded = compress(&body, encoding)?;
+    Ok((
+        base64::engine::general_purpose::STANDARD.encode(encoded),
+        encoding,
+    ))
+}
+
+/// 增量解压器：压缩数据逐块写入，解压结果写入内部缓冲
+enum Inflater {
+    Gzip(Box<flate2::write::MultiGzDecoder<Vec<u8>>>),
+    Brotli(Box<brotli::DecompressorWriter<Vec<u8>>>),
+}
+
+impl Inflater {
+    fn write(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        match self {
+            Inflater::Gzip(decoder) => decoder.write_all(chunk),
+            Inflater::Brotli(decoder) => decoder.write_all(chunk),
+        }
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        match self {
+            Inflater::Gzip(decoder) => decoder.try_finish(),
+            Inflater::Brotli(decoder) => decoder.flush(),
+        }
+    }
+
+    fn output(&mut self) -> &mut Vec<u8> {
+        match self {
+            Inflater::Gzip(decoder) => decoder.get_mut(),
+            Inflater::Brotli(decoder) => decoder.get_mut(),
+        }
+    }
+}
+
+/// 单个流的解压状态
+pub struct StreamDecoder {
+    inflater: Option<Inflater>,
+    /// 尚未凑成完整 UTF-8 字符的尾部字节
+    pending: Vec<u8>,
+    total: u64,
+    max_bytes: u64,
+}
+
+impl StreamDecoder {
+    pub fn new(encoding: Encoding, max_bytes: u64) -> Self {
+        let inflater = match encoding {
+            Encoding::Identity => None,
+            Encoding::Gzip => Some(Inflater::Gzip(Box::new(
+                flate2::write::MultiGzDecoder::new(Vec::new()),
+            ))),
+            Encoding::Brotli => Some(Inflater::Brotli(Box::new(brotli::DecompressorWriter::new(
+                Vec::new(),
+                4096,
+            )))),
+        };
+        Self {
+            inflater,
+            pending: Vec::new(),
+            total: 0,
+            max_bytes,
+        }
+    }
+
+    /// 写入一块压缩数据，返回目前可以确定的文本
+    pub fn push(&mut self, chunk: &[u8], done: bool) -> Result<String> {
+        let decoded = match self.inflater.as_mut() {
+            Some(inflater) => {
+                inflater.write(chunk)?;
+                if done {
+                    inflater.finish()?;
+                }
+                std::mem::take(inflater.output())
+            }
+            None => chunk.to_vec(),
+        };
+        self.total += decoded.len() as u64;
+        if self.total > self.max_bytes {
+            return Err(DecompressedTooLarge(self.max_bytes).into());
+        }
+        self.pending.extend_from_slice(&decoded);
+        Ok(self.take_text(done))
+    }
+
+    /// 取出完整的 UTF-8 文本，被截断的多字节字符留到下一块
+    fn take_text(&mut self, done: bool) -> String {
+        let complete = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(e) if e.error_len().is_none() && !done => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        let rest = self.pending.split_off(complete);
+        let text = body_text::decode(&self.pending).into_owned();
+        self.pending = rest;
+        text
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref STREAMS: Mutex<HashMap<String, StreamDecoder>> = Mutex::new(HashMap::new());
+}
+
+/// 按租约增量解压流式响应，返回解压出的文本（可能为空）
+pub fn decode_stream_chunk(
+    stream_id: &str,
+    chunk_base64: &str,
+    content_encoding: Option<&str>,
+    done: bool,
+) -> Result<String> {
+    let chunk = base64::engine::general_purpose::STANDARD.decode(chunk_base64)?;
+    let mut streams = STREAMS.lock().unwrap();
+    let decoder = match streams.entry(stream_id.to_string()) {
+        std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+        std::collections::hash_map::Entry::Vacant(entry) => {
+            let encoding = Encoding::parse(content_encoding)?;
+            let max_bytes = crate::config::get_config()
+                .compression
+                .max_decompressed_bytes;
+            entry.insert(StreamDecoder::new(encoding, max_bytes))
+        }
+    };
+    let result = decoder.push(&chunk, done);
+    if done || result.is_err() {
+        streams.remove(stream_id);
+    }
+    result
+}
+
+/// 流结束（租约释放）时清理解压状态
+pub fn finish_stream(stream_id: &str) {
+    STREAMS.lock().unwrap().remove(stream_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(Some("gzip, deflate, br")), Encoding::Brotli);
+        assert_eq!(negotiate(Some("gzip;q=1.0, br;q=0.5")), Encoding::Gzip);
+        assert_eq!(negotiate(Some("br;q=0, gzip")), Encoding::Gzip);
+        assert_eq!(negotiate(Some("*")), Encoding::Brotli);
+        assert_eq!(negotiate(None), Encoding::Identity);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let body = "x".repeat(4096);
+        for encoding in [Encoding::Identity, Encoding::Gzip, Encoding::Brotli] {
+            let compressed = compress(body.as_bytes(), encoding).unwrap();
+            if encoding != Encoding::Identity {
+                assert!(compressed.len() < body.len());
+            }
+            assert_eq!(
+                decompress(&compressed, encoding, 1 << 20).unwrap(),
+                body.as_bytes()
+            );
+            // 解压后超过上限
+            let err = decompress(&compressed, encoding, 1024).unwrap_err();
+            assert!(err.is::<DecompressedTooLarge>());
+        }
+        assert!(Encoding::parse(Some("zstd")).is_err());
+    }
+
+    #[test]
+    fn test_stream_chunks() {
+        let events = "data: {\"text\":\"你好\"}\n\n".repeat(200);
+        for encoding in [Encoding::Gzip, Encoding::Brotli] {
+            let compressed = compress(events.as_bytes(), encoding).unwrap();
+            let stream_id = format!("compression-{:?}", encoding);
+            let chunks: Vec<_> = compressed.chunks(7).collect();
+            let mut text = String::new();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let chunk = base64::engine::general_purpose::STANDARD.encode(chunk);
+                let done = i + 1 == chunks.len();
+                text += &decode_stream_chunk(&stream_id, &chunk, encoding.header_value(), done)
+                    .unwrap();
+            }
+            // 多字节字符跨块时不会被替换为 U+FFFD
+            assert_eq!(text, events);
+            assert!(!STREAMS.lock().unwrap().contains_key(&stream_id));
+        }
+
+        let compressed = compress(events.as_bytes(), Encoding::Gzip).unwrap();
+        let mut decoder = StreamDecoder::new(Encoding::Gzip, 1024);
+        assert!(decoder.push(&compressed, true).is_err());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let value = serde_json::json!({ "text": "hello ".repeat(500) });
+        let config = CompressionConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let (body, encoding) = encode_json(&value, Some("gzip"), &config).unwrap();
+        assert_eq!(encoding, Encoding::Gzip);
+        assert_eq!(decode_json(&body, encoding.header_value()).unwrap(), value);
+
+        let page = b"\xEF\xBB\xBF<h1>Bad \xFF Gateway</h1>";
+        let error_page = compress(page, Encoding::Gzip).unwrap();
+        let error_page = base64::engine::general_purpose::STANDARD.encode(error_page);
+        let text = decode_text(&error_page, Some("gzip")).unwrap();
+        assert_eq!(text, "<h1>Bad \u{FFFD} Gateway</h1>");
+    }
+}
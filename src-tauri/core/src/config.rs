@@ -0,0 +1,288 @@
+//! 运行时配置
+//!
+//! 对应 `plugin/config.json` 中的 `settings`，由宿主通过 `update_config` 下发。
+//! 未提供的字段使用默认值。
+
+use crate::app_lock::AppLockConfig;
+use crate::auth::secret_store::SecretStoreConfig;
+use crate::broadcast::BroadcastConfig;
+use crate::canary::CanaryConfig;
+use crate::chaos::ChaosConfig;
+use crate::chat_normalize::ChatNormalizeConfig;
+use crate::compression::CompressionConfig;
+use crate::config_check::{self, Severity, ValidationReport};
+use crate::context_trim::ContextTrimConfig;
+use crate::control::PauseConfig;
+use crate::dead_credentials::DeadCredentialConfig;
+use crate::dedup::DedupConfig;
+use crate::digest::DigestConfig;
+use crate::documents::DocumentLimitConfig;
+use crate::env_import::EnvImportConfig;
+use crate::failover::FailoverConfig;
+use crate::filter::ContentFilterConfig;
+use crate::heartbeat::HeartbeatConfig;
+use crate::hooks::HooksConfig;
+use crate::http::HttpClientConfig;
+use crate::keepalive::KeepAliveConfig;
+use crate::lease::LeaseConfig;
+use crate::limits::SizeLimitConfig;
+use crate::logging::LoggingConfig;
+use crate::maintenance::MaintenanceConfig;
+use crate::middleware::MiddlewareOrder;
+use crate::mock::MockConfig;
+use crate::model_tiers::ModelTierConfig;
+use crate::param_policy::ParamPolicyConfig;
+use crate::params::GenerationDefaults;
+use crate::passthrough::PassthroughConfig;
+use crate::pricing::PricingConfig;
+use crate::profiles::ProfilesConfig;
+use crate::quota_link::QuotaLinkConfig;
+use crate::reassembly::ReassemblyConfig;
+use crate::refresh_limiter::RefreshLimitConfig;
+use crate::relogin::ReloginConfig;
+use crate::response_meta::ResponseMetaConfig;
+use crate::response_repair::ResponseRepairConfig;
+use crate::retention::RetentionConfig;
+use crate::retry_budget::RetryBudgetConfig;
+use crate::reveal::RevealConfig;
+use crate::salvage::SalvageConfig;
+use crate::spool::SpoolConfig;
+use crate::startup::StartupQueueConfig;
+use crate::stats::StatsConfig;
+use crate::stream_progress::ProgressConfig;
+use crate::tenants::TenantsConfig;
+use crate::throttle::ThrottleConfig;
+use crate::timeouts::TimeoutConfig;
+use crate::token_age::TokenAgeConfig;
+use crate::tray::TrayConfig;
+use crate::user_agent::FactoryConfig;
+use crate::wake::WakeConfig;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use tracing::warn;
+
+/// Provider 配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderConfig {
+    /// 响应内容过滤
+    pub content_filter: ContentFilterConfig,
+    /// 弃用模型的继任模型映射
+    pub model_successors: HashMap<String, String>,
+    /// 按模型家族 (opus / sonnet / gpt) 的默认生成参数
+    pub generation_defaults: HashMap<String, GenerationDefaults>,
+    /// 使用统计持久化
+    pub stats: StatsConfig,
+    /// 进行中请求去重
+    pub dedup: DedupConfig,
+    /// 全局暂停行为
+    pub pause: PauseConfig,
+    /// 流式响应限速
+    pub throttle: ThrottleConfig,
+    /// 上游 HTTP 客户端（HTTP 版本 / TLS / 请求头顺序）
+    pub http: HttpClientConfig,
+    /// Factory 客户端（User-Agent）
+    pub factory: FactoryConfig,
+    /// 每日摘要与异常告警
+    pub digest: DigestConfig,
+    /// 数据保留天数
+    pub retention: RetentionConfig,
+    /// 请求 / 响应中间件执行顺序
+    pub middleware: MiddlewareOrder,
+    /// 模拟模式（前端开发用）
+    pub mock: MockConfig,
+    /// Refresh Token 会话寿命提醒
+    pub token_age: TokenAgeConfig,
+    /// 流中断时保留部分响应
+    pub salvage: SalvageConfig,
+    /// 响应压缩协商
+    pub compression: CompressionConfig,
+    /// 端点间故障转移
+    pub failover: FailoverConfig,
+    /// 密钥存储后端
+    pub secret_store: SecretStoreConfig,
+    /// Token 刷新限流
+    pub refresh_limit: RefreshLimitConfig,
+    /// 休眠唤醒检测
+    pub wake: WakeConfig,
+    /// 流式响应进度事件
+    pub progress: ProgressConfig,
+    /// 上游流式、下游非流式的响应重组
+    pub reassembly: ReassemblyConfig,
+    /// 跳过所有改写的原样透传
+    pub passthrough: PassthroughConfig,
+    /// 请求大小与连接数限制
+    pub limits: SizeLimitConfig,
+    /// 重新登录提醒
+    pub relogin: ReloginConfig,
+    /// 按端点区分的超时
+    pub timeouts: TimeoutConfig,
+    /// 本地租户（虚拟密钥、凭证子集、配额）
+    pub tenants: TenantsConfig,
+    /// 凭证池就绪前的启动请求队列
+    pub startup_queue: StartupQueueConfig,
+    /// 日志级别与输出目标
+    pub logging: LoggingConfig,
+    /// 查看明文密钥的确认流程
+    pub reveal: RevealConfig,
+    /// 未知请求参数的处理策略
+    pub param_policy: ParamPolicyConfig,
+    /// 新凭证的金丝雀放量
+    pub canary: CanaryConfig,
+    /// 标明处理请求的凭证的响应元数据
+    pub response_meta: ResponseMetaConfig,
+    /// 从环境变量和 .env 文件导入 API Key
+    pub env_import: EnvImportConfig,
+    /// 文档与图片内容块的大小、页数限制
+    pub documents: DocumentLimitConfig,
+    /// 多凭证广播评估
+    pub broadcast: BroadcastConfig,
+    /// 关联凭证的共享额度
+    pub quota_link: QuotaLinkConfig,
+    /// 远程价格表
+    pub pricing: PricingConfig,
+    /// 闲置凭证保活
+    pub keepalive: KeepAliveConfig,
+    /// 按虚拟密钥或应用选择的转换配置档
+    pub profiles: ProfilesConfig,
+    /// Factory 维护窗口检测
+    pub maintenance: MaintenanceConfig,
+    /// 故障注入（混沌模式，仅用于测试）
+    pub chaos: ChaosConfig,
+    /// 超长对话的上下文裁剪
+    pub context_trim: ContextTrimConfig,
+    /// 单个逻辑请求的重试预算
+    pub retry_budget: RetryBudgetConfig,
+    /// 每个凭证端点的并发上限与租约超时
+    pub leases: LeaseConfig,
+    /// 流式响应心跳（上游静默时写给本地客户端）
+    pub heartbeat: HeartbeatConfig,
+    /// 上游响应校验与 JSON 修复
+    pub response_repair: ResponseRepairConfig,
+    /// 应用锁（口令解锁后才能查看密钥、修改凭证）
+    pub app_lock: AppLockConfig,
+    /// 托盘菜单
+    pub tray: TrayConfig,
+    /// 按模型限制凭证等级
+    pub model_tiers: ModelTierConfig,
+    /// 长期失效凭证清理
+    pub dead_credentials: DeadCredentialConfig,
+    /// Chat Completions 工具调用规范化
+    pub chat_normalize: ChatNormalizeConfig,
+    /// 大响应落盘
+    pub spool: SpoolConfig,
+    /// 用户钩子（实验性，WASM 模块）
+    pub hooks: HooksConfig,
+}
+
+lazy_static::lazy_static! {
+    static ref CONFIG: RwLock<ProviderConfig> = RwLock::new(ProviderConfig::default());
+    /// 配置版本，每次更新配置加一（用于缓存由配置派生的对象）
+    static ref GENERATION: AtomicU64 = AtomicU64::new(0);
+    /// 嵌入方指定的数据目录
+    static ref DATA_DIR: RwLock<Option<PathBuf>> = RwLock::new(initial_data_dir());
+}
+
+/// 测试进程使用独立的临时目录，不读写用户数据
+#[cfg(test)]
+fn initial_data_dir() -> Option<PathBuf> {
+    Some(std::env::temp_dir().join(format!("droid-provider-test-{}", std::process::id())))
+}
+
+#[cfg(not(test))]
+fn initial_data_dir() -> Option<PathBuf> {
+    None
+}
+
+/// 指定数据目录（优先于 `DROID_DATA_DIR`）
+pub fn set_data_dir(dir: impl Into<PathBuf>) {
+    *DATA_DIR.write().unwrap() = Some(dir.into());
+}
+
+/// 数据目录，可通过 `set_data_dir` 或 `DROID_DATA_DIR` 覆盖
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = DATA_DIR.read().unwrap().clone() {
+        return dir;
+    }
+    if let Ok(dir) = std::env::var("DROID_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+    dirs::data_dir()
+        .map(|d| d.join("droid-provider"))
+        .unwrap_or_else(|| PathBuf::from(".droid-provider"))
+}
+
+/// 获取当前配置快照
+pub fn get_config() -> ProviderConfig {
+    CONFIG.read().unwrap().clone()
+}
+
+/// 获取当前配置快照及其版本
+pub fn get_config_with_generation() -> (u64, ProviderConfig) {
+    let config = CONFIG.read().unwrap();
+    (GENERATION.load(Ordering::SeqCst), config.clone())
+}
+
+/// 传入的 JSON 与当前配置按顶层字段合并，未出现的字段保持不变
+fn merge_settings(
+    current: &ProviderConfig,
+    settings: &serde_json::Value,
+) -> Result<ProviderConfig> {
+    let mut merged = serde_json::to_value(current)?;
+    if let (Some(merged), Some(updates)) = (merged.as_object_mut(), settings.as_object()) {
+        for (key, value) in updates {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(serde_json::from_value(merged)?)
+}
+
+/// 校验配置但不应用；未传入设置时校验当前配置
+pub fn validate_config(settings: Option<&serde_json::Value>) -> Result<ValidationReport> {
+    let current = get_config();
+    let next = match settings {
+        Some(settings) => merge_settings(&current, settings)?,
+        None => current.clone(),
+    };
+    Ok(config_check::validate_config(&current, &next))
+}
+
+/// 更新配置，配置本身存在 error 级别的问题时拒绝（环境问题只在校验报告中列出）
+pub fn update_config(settings: serde_json::Value) -> Result<ProviderConfig> {
+    let current = get_config();
+    let config = merge_settings(&current, &settings)?;
+
+    let report = ValidationReport {
+        changed: config_check::changed_fields(&current, &config),
+        findings: config_check::check_config(&config),
+    };
+    if report.has_errors() {
+        anyhow::bail!("配置无效: {}", report.error_summary());
+    }
+    for finding in report
+        .findings
+        .iter()
+        .filter(|f| f.severity == Severity::Warning)
+    {
+        warn!("配置提示 {}: {}", finding.field, finding.message);
+    }
+
+    // 切换密钥存储后端时先迁移主密钥，失败则保持原配置
+    crate::auth::master_key::migrate_backend(
+        current.secret_store.backend,
+        config.secret_store.backend,
+    )?;
+
+    if let Err(e) = crate::logging::apply(&config.logging) {
+        warn!("日志配置应用失败: {}", e);
+    }
+    let mut current = CONFIG.write().unwrap();
+    *current = config.clone();
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    drop(current);
+    Ok(config)
+}
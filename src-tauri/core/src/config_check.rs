@@ -0,0 +1,675 @@
+//! 配置校验
+//!
+//! 启动与 `update_config` 时检查配置，返回结构化的问题列表，而不是直接
+//! panic 或悄悄改用默认值。`error` 级别的问题会拒绝本次更新；`warning`
+//! 只提示。报告同时列出相对当前配置发生变化的字段，便于 UI 定位。
+
+use crate::auth::master_key;
+use crate::auth::secret_store::{EnvStore, SecretBackend, PASSPHRASE_ENV};
+use crate::compression::Encoding;
+use crate::config::ProviderConfig;
+use crate::context_trim::TrimStrategy;
+use crate::control::PauseBehavior;
+use crate::credentials::EndpointType;
+use crate::filter::ContentFilter;
+use crate::hooks::{self, MIDDLEWARE_PREFIX};
+use crate::http::{IpFamily, TlsBackend};
+use crate::model_registry::is_builtin_family;
+use crate::tls_trust;
+use serde::{Deserialize, Serialize};
+
+/// 上游代理环境变量（reqwest 会自动读取）
+pub const PROXY_ENV_VARS: &[&str] = &[
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+
+/// 问题级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// 单条校验结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    pub severity: Severity,
+    /// 配置路径，如 `digest.hour`
+    pub field: String,
+    pub message: String,
+    /// 修复建议
+    #[serde(default)]
+    pub suggestion: Option<String>,
+}
+
+/// 校验报告
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// 相对当前配置发生变化的顶层字段
+    pub changed: Vec<String>,
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    /// 是否有 error 级别的问题
+    pub fn has_errors(&self) -> bool {
+        self.findings.iter().any(|f| f.severity == Severity::Error)
+    }
+
+    /// 汇总 error 级别的问题
+    pub fn error_summary(&self) -> String {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == Severity::Error)
+            .map(|f| format!("{}: {}", f.field, f.message))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+struct Findings(Vec<Finding>);
+
+impl Findings {
+    fn push(&mut self, severity: Severity, field: &str, message: String, suggestion: &str) {
+        self.0.push(Finding {
+            severity,
+            field: field.to_string(),
+            message,
+            suggestion: (!suggestion.is_empty()).then(|| suggestion.to_string()),
+        });
+    }
+
+    fn error(&mut self, field: &str, message: String, suggestion: &str) {
+        self.push(Severity::Error, field, message, suggestion);
+    }
+
+    fn warning(&mut self, field: &str, message: String, suggestion: &str) {
+        self.push(Severity::Warning, field, message, suggestion);
+    }
+}
+
+/// 相对旧配置发生变化的顶层字段
+pub fn changed_fields(previous: &ProviderConfig, next: &ProviderConfig) -> Vec<String> {
+    let (Ok(previous), Ok(next)) = (serde_json::to_value(previous), serde_json::to_value(next))
+    else {
+        return Vec::new();
+    };
+    let mut changed: Vec<String> = next
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(key, value)| previous.get(key.as_str()) != Some(*value))
+        .map(|(key, _)| key.clone())
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// 校验配置本身（不含环境）
+pub fn check_config(config: &ProviderConfig) -> Vec<Finding> {
+    let mut findings = Findings(Vec::new());
+
+    if let Err(e) = crate::middleware::build_chain(config) {
+        findings.error("middleware", e.to_string(), "检查中间件名称拼写");
+    }
+    let hooked = config
+        .middleware
+        .0
+        .iter()
+        .any(|m| m.starts_with(MIDDLEWARE_PREFIX));
+    if hooked && !config.hooks.enabled {
+        findings.warning(
+            "middleware",
+            "引用了钩子但 hooks 未启用，钩子不会执行".to_string(),
+            "",
+        );
+    }
+    if config.hooks.enabled {
+        for hook in hooks::status(&config.hooks) {
+            if let Some(e) = hook.load_error {
+                let field = format!("hooks.modules.{}", hook.name);
+                findings.error(&field, e, "确认 .wasm 文件路径与内容");
+            }
+        }
+    }
+    if let Err(e) = ContentFilter::compile(&config.content_filter) {
+        findings.error("content_filter.rules", e.to_string(), "修正正则表达式");
+    }
+
+    for (model, successor) in &config.model_successors {
+        let field = format!("model_successors.{}", model);
+        if model == successor {
+            findings.error(&field, "继任模型不能是自身".to_string(), "");
+        } else if config.model_successors.contains_key(successor) {
+            findings.warning(
+                &field,
+                format!("继任模型 {} 自身也配置了继任，不会被继续改写", successor),
+                "直接指向最终模型",
+            );
+        } else if !is_builtin_family(successor) {
+            findings.warning(
+                &field,
+                format!("继任模型 {} 不属于内置模型家族", successor),
+                "确认该模型已由凭证或模型覆盖提供",
+            );
+        }
+    }
+
+    if let Some(rate) = config.throttle.tokens_per_second {
+        if rate <= 0.0 {
+            findings.error(
+                "throttle.tokens_per_second",
+                format!("速率必须大于 0，当前 {}", rate),
+                "不限速请删除该字段",
+            );
+        }
+    }
+    for (client, rate) in &config.throttle.per_client {
+        if *rate <= 0.0 {
+            findings.error(
+                &format!("throttle.per_client.{}", client),
+                format!("速率必须大于 0，当前 {}", rate),
+                "",
+            );
+        }
+    }
+    if config.throttle.burst_tokens == 0 {
+        findings.warning(
+            "throttle.burst_tokens",
+            "突发额度为 0，限速时每个事件都会等待".to_string(),
+            "",
+        );
+    }
+
+    if config.pause.behavior == PauseBehavior::Queue && config.pause.queue_timeout_ms == 0 {
+        findings.warning(
+            "pause.queue_timeout_ms",
+            "排队超时为 0，等同于直接拒绝".to_string(),
+            "",
+        );
+    }
+    if config.dedup.enabled && config.dedup.window_ms == 0 {
+        findings.warning(
+            "dedup.window_ms",
+            "去重窗口为 0，去重不会生效".to_string(),
+            "",
+        );
+    }
+    if config.stats.persist && config.stats.flush_interval_ms == 0 {
+        findings.error(
+            "stats.flush_interval_ms",
+            "刷新间隔必须大于 0".to_string(),
+            "",
+        );
+    }
+
+    if config.digest.hour > 23 {
+        findings.error(
+            "digest.hour",
+            format!("小时必须在 0-23 之间，当前 {}", config.digest.hour),
+            "",
+        );
+    }
+    if config.digest.error_spike_factor <= 1.0 {
+        findings.warning(
+            "digest.error_spike_factor",
+            format!(
+                "倍数 {} 不大于 1，错误率持平也会告警",
+                config.digest.error_spike_factor
+            ),
+            "建议不小于 2",
+        );
+    }
+
+    if config.token_age.absolute_lifetime_days == 0 {
+        findings.error(
+            "token_age.absolute_lifetime_days",
+            "会话寿命必须大于 0".to_string(),
+            "",
+        );
+    } else if config.token_age.warn_days >= config.token_age.absolute_lifetime_days {
+        findings.warning(
+            "token_age.warn_days",
+            "提醒天数不小于会话寿命，登录后会立即提醒".to_string(),
+            "",
+        );
+    }
+
+    for (index, item) in config.compression.accept_encoding.split(',').enumerate() {
+        let name = item.split(';').next().unwrap_or("").trim();
+        if !name.is_empty() && name != "*" && Encoding::parse(Some(name)).is_err() {
+            findings.error(
+                "compression.accept_encoding",
+                format!("第 {} 项 {} 不受支持", index + 1, name),
+                "只能使用 gzip、br、identity",
+            );
+        }
+    }
+
+    if config.failover.enabled && config.failover.failure_threshold == 0 {
+        findings.error(
+            "failover.failure_threshold",
+            "阈值为 0 时所有 Anthropic 请求都会改走 Chat Completions".to_string(),
+            "建议不小于 3",
+        );
+    }
+
+    if config.refresh_limit.max_concurrent == 0 {
+        findings.warning(
+            "refresh_limit.max_concurrent",
+            "并发上限为 0，按 1 处理".to_string(),
+            "",
+        );
+    }
+
+    let has_keychain = cfg!(any(target_os = "macos", target_os = "windows"));
+    if config.secret_store.backend == SecretBackend::Keychain && !has_keychain {
+        findings.error(
+            "secret_store.backend",
+            "当前平台不支持系统钥匙串".to_string(),
+            "改用 encrypted_file 或 env",
+        );
+    }
+
+    let endpoint_timeouts = [
+        EndpointType::Anthropic,
+        EndpointType::OpenAI,
+        EndpointType::Comm,
+    ]
+    .map(|endpoint| (endpoint.to_string(), config.timeouts.for_endpoint(endpoint)));
+    let control = ("control".to_string(), config.timeouts.for_control());
+    for (scope, timeouts) in endpoint_timeouts.into_iter().chain([control]) {
+        let total = timeouts.total_secs;
+        let longer = [
+            ("read_secs", timeouts.read_secs),
+            ("stream_idle_secs", timeouts.stream_idle_secs),
+        ]
+        .into_iter()
+        .find(|(_, secs)| total > 0 && *secs > total);
+        if let Some((name, secs)) = longer {
+            findings.warning(
+                &format!("timeouts.{}", scope),
+                format!("{} ({} 秒) 大于总超时 {} 秒，不会生效", name, secs, total),
+                "调大 total_secs 或设为 0 不限制",
+            );
+        }
+    }
+
+    let tenants = &config.tenants;
+    if tenants.enabled && tenants.tenants.is_empty() {
+        findings.error(
+            "tenants.tenants",
+            "已启用租户但没有定义任何租户，所有请求都会被拒绝".to_string(),
+            "添加租户或关闭 tenants.enabled",
+        );
+    }
+    let mut seen_ids = std::collections::HashSet::new();
+    let mut seen_keys = std::collections::HashSet::new();
+    for tenant in &tenants.tenants {
+        let field = format!("tenants.{}", tenant.id);
+        if tenant.id.is_empty() || !seen_ids.insert(tenant.id.as_str()) {
+            findings.error(&field, "租户 ID 为空或重复".to_string(), "");
+        }
+        if tenant
+            .key_hashes
+            .iter()
+            .any(|hash| !seen_keys.insert(hash.as_str()))
+        {
+            findings.error(
+                &field,
+                "虚拟密钥与其他租户重复".to_string(),
+                "为每个租户单独生成密钥",
+            );
+        }
+        if tenants.enabled && tenant.key_hashes.is_empty() {
+            findings.warning(&field, "租户没有虚拟密钥，无法使用".to_string(), "");
+        }
+    }
+
+    let canary = &config.canary;
+    if !(0.0..=100.0).contains(&canary.traffic_percent) {
+        findings.error(
+            "canary.traffic_percent",
+            format!("流量比例 {} 不在 0-100 之间", canary.traffic_percent),
+            "",
+        );
+    }
+    if !(0.0..=1.0).contains(&canary.max_error_rate) {
+        findings.error(
+            "canary.max_error_rate",
+            format!("错误率阈值 {} 不在 0-1 之间", canary.max_error_rate),
+            "例如 0.2 表示 20%",
+        );
+    }
+
+    let logging = &config.logging;
+    if let Err(e) = crate::logging::build_filter(logging) {
+        findings.error(
+            "logging.filters",
+            format!("日志过滤规则无效: {}", e),
+            "格式为 `级别` 或 `模块路径=级别`",
+        );
+    }
+    if !logging.stderr && !logging.file.enabled && logging.ring_buffer_lines == 0 {
+        findings.warning(
+            "logging",
+            "所有日志输出目标均已关闭，日志将被丢弃".to_string(),
+            "至少开启 stderr、file 或 ring_buffer_lines 其中之一",
+        );
+    }
+
+    let max_body = config.limits.max_body_bytes;
+    if max_body > 0 && max_body < 1024 * 1024 {
+        findings.warning(
+            "limits.max_body_bytes",
+            format!(
+                "请求体上限仅 {} 字节，长对话或带图片的请求会被拒绝",
+                max_body
+            ),
+            "建议不小于 1 MiB",
+        );
+    }
+
+    let http = &config.http;
+    if let Some(addr) = http.local_address {
+        let conflict = match http.ip_family {
+            IpFamily::Ipv4Only => addr.is_ipv6(),
+            IpFamily::Ipv6Only => addr.is_ipv4(),
+            _ => false,
+        };
+        if conflict {
+            findings.error(
+                "http.ip_family",
+                format!("绑定地址 {} 与协议限制 {:?} 冲突", addr, http.ip_family),
+                "调整 local_address 或 ip_family",
+            );
+        }
+        if http.interface.is_some() {
+            findings.warning(
+                "http.interface",
+                "同时配置了 local_address，网卡设置不生效".to_string(),
+                "删除其中一项",
+            );
+        }
+    }
+
+    for pin in &http.trust.pinned_sha256 {
+        if let Err(e) = tls_trust::parse_pin(pin) {
+            findings.error(
+                "http.trust.pinned_sha256",
+                e.to_string(),
+                "填写证书 SHA-256 指纹",
+            );
+        }
+    }
+    if !http.trust.pinned_sha256.is_empty() && http.tls_backend == TlsBackend::Native {
+        findings.warning(
+            "http.trust.pinned_sha256",
+            "证书固定仅支持 rustls，固定指纹时将忽略 native-tls 设置".to_string(),
+            "将 tls_backend 改为 rustls",
+        );
+    }
+    if let Some(path) = &http.trust.ca_bundle {
+        if let Err(e) = tls_trust::load_ca_bundle(path) {
+            findings.error(
+                "http.trust.ca_bundle",
+                e.to_string(),
+                "确认 PEM 文件路径与内容",
+            );
+        }
+    } else if !http.trust.builtin_roots {
+        findings.error(
+            "http.trust.builtin_roots",
+            "未信任任何根证书，所有 HTTPS 请求都会失败".to_string(),
+            "配置 ca_bundle 或启用 builtin_roots",
+        );
+    }
+
+    for (name, profile) in &config.profiles.profiles {
+        let effective = crate::profiles::effective_config(config, profile);
+        if let Err(e) = crate::middleware::build_chain(&effective) {
+            findings.error(
+                &format!("profiles.profiles.{}.middleware", name),
+                e.to_string(),
+                "",
+            );
+        }
+    }
+    let bound = config
+        .profiles
+        .clients
+        .iter()
+        .map(|(client, profile)| (format!("profiles.clients.{}", client), profile))
+        .chain(config.tenants.tenants.iter().filter_map(|t| {
+            let profile = t.profile.as_ref()?;
+            Some((format!("tenants.{}.profile", t.id), profile))
+        }));
+    for (field, profile) in bound {
+        if !config.profiles.profiles.contains_key(profile) {
+            findings.error(
+                &field,
+                format!("转换配置档 {} 不存在", profile),
+                "检查配置档名称",
+            );
+        }
+    }
+
+    if config.pricing.remote_url.is_some() && config.pricing.public_key.is_none() {
+        findings.error(
+            "pricing.public_key",
+            "配置了远程价格表但没有签名公钥，价格表不会被使用".to_string(),
+            "填写价格表发布方的 Ed25519 公钥（base64）",
+        );
+    }
+
+    let maintenance = &config.maintenance;
+    if maintenance.enabled && maintenance.status_codes.iter().any(|code| *code < 500) {
+        findings.warning(
+            "maintenance.status_codes",
+            "维护检测包含非 5xx 状态码，普通请求错误也可能触发全局退避".to_string(),
+            "只保留 502、503 等表示上游整体故障的状态码",
+        );
+    }
+
+    let trim = &config.context_trim;
+    if trim.enabled
+        && trim.strategies.contains(&TrimStrategy::Summarize)
+        && !trim.summary_model.starts_with("claude-")
+    {
+        findings.error(
+            "context_trim.summary_model",
+            format!("摘要模型 {} 不是 Claude 模型", trim.summary_model),
+            "使用 claude- 开头的模型",
+        );
+    }
+    if trim.chars_per_token == 0 {
+        findings.error(
+            "context_trim.chars_per_token",
+            "必须大于 0".to_string(),
+            "建议为 4",
+        );
+    }
+
+    let heartbeat = &config.heartbeat;
+    if heartbeat.enabled && heartbeat.interval_ms < 1000 {
+        findings.warning(
+            "heartbeat.interval_ms",
+            format!(
+                "心跳间隔 {}ms 过短，会向客户端写入大量无用数据",
+                heartbeat.interval_ms
+            ),
+            "建议为 10000 ~ 30000",
+        );
+    }
+
+    let budget = &config.retry_budget;
+    if budget.enabled && budget.max_attempts == 0 {
+        findings.error(
+            "retry_budget.max_attempts",
+            "尝试次数包含首次请求，不能为 0".to_string(),
+            "至少为 1，建议为 5",
+        );
+    }
+
+    let dead = &config.dead_credentials;
+    if dead.enabled && dead.unhealthy_days == 0 {
+        findings.error(
+            "dead_credentials.unhealthy_days",
+            "为 0 时刚失败的凭证就会被判定为失效".to_string(),
+            "建议为 14 ~ 30",
+        );
+    }
+
+    let chaos = &config.chaos;
+    if chaos.enabled {
+        let rates = [
+            chaos.rate_limit_rate,
+            chaos.server_error_rate,
+            chaos.slow_rate,
+            chaos.stream_drop_rate,
+        ];
+        if rates.iter().any(|rate| !(0.0..=1.0).contains(rate)) || rates.iter().sum::<f64>() > 1.0 {
+            findings.error(
+                "chaos",
+                "故障注入概率须在 0 ~ 1 之间，且总和不超过 1".to_string(),
+                "调小各项概率",
+            );
+        }
+        findings.warning(
+            "chaos.enabled",
+            "故障注入已开启，部分请求会收到人为制造的错误".to_string(),
+            "测试完成后关闭 chaos.enabled",
+        );
+    }
+
+    if config.broadcast.enabled && config.broadcast.max_targets == 0 {
+        findings.error(
+            "broadcast.max_targets",
+            "广播目标上限为 0，广播请求都会被拒绝".to_string(),
+            "设置为至少 2",
+        );
+    }
+
+    if config.factory.user_agent.as_deref() == Some("") {
+        findings.warning(
+            "factory.user_agent",
+            "User-Agent 为空字符串，将使用自动检测的值".to_string(),
+            "删除该字段",
+        );
+    }
+
+    findings.0
+}
+
+/// 校验运行环境（代理环境变量、主密钥）
+pub fn check_environment() -> Vec<Finding> {
+    let mut findings = Findings(Vec::new());
+
+    for var in PROXY_ENV_VARS {
+        if let Ok(url) = std::env::var(var) {
+            if !url.is_empty() && reqwest::Proxy::all(&url).is_err() {
+                findings.error(
+                    &format!("env.{}", var),
+                    format!("代理地址无法解析: {}", url),
+                    "格式如 http://127.0.0.1:7890",
+                );
+            }
+        }
+    }
+
+    let injected = |var: &str| std::env::var(var).is_ok_and(|v| !v.is_empty());
+    let master_key_var = EnvStore::var_name(master_key::MASTER_KEY_SECRET);
+    if crate::config::get_config().secret_store.backend == SecretBackend::Env
+        && !injected(&master_key_var)
+        && !injected("DROID_ENCRYPTION_KEY")
+    {
+        findings.error(
+            "secret_store.backend",
+            format!("env 后端未注入主密钥，环境变量 {} 未设置", master_key_var),
+            "在容器中注入该变量，或改用 encrypted_file",
+        );
+    }
+    let uses_file = match crate::config::get_config().secret_store.backend {
+        SecretBackend::EncryptedFile => true,
+        SecretBackend::Auto => !cfg!(any(target_os = "macos", target_os = "windows")),
+        _ => false,
+    };
+    if uses_file && !injected(PASSPHRASE_ENV) && !injected("DROID_ENCRYPTION_KEY") {
+        findings.error(
+            "secret_store.backend",
+            format!(
+                "encrypted_file 后端需要口令，环境变量 {} 未设置",
+                PASSPHRASE_ENV
+            ),
+            "设置该变量，或改用 keychain / env 后端",
+        );
+    }
+
+    if let Err(e) = crate::http::resolve_bind_address(&crate::config::get_config().http) {
+        findings.error(
+            "http.interface",
+            e.to_string(),
+            "检查网卡名称（如 en0、eth0）或改用 local_address",
+        );
+    }
+
+    if let Some(error) = master_key::load_error() {
+        findings.error(
+            "encryption_key",
+            format!("主密钥加载失败，加解密操作不可用: {}", error),
+            "设置 DROID_ENCRYPTION_KEY 或用恢复短语恢复主密钥",
+        );
+    }
+
+    findings.0
+}
+
+/// 完整校验：配置、环境以及相对当前配置的变化
+pub fn validate_config(previous: &ProviderConfig, next: &ProviderConfig) -> ValidationReport {
+    let mut findings = check_config(next);
+    findings.extend(check_environment());
+    ValidationReport {
+        changed: changed_fields(previous, next),
+        findings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_clean() {
+        assert!(check_config(&ProviderConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_findings() {
+        let mut config = ProviderConfig::default();
+        config.digest.hour = 24;
+        config.compression.accept_encoding = "br, zstd".to_string();
+        config
+            .model_successors
+            .insert("claude-old".to_string(), "claude-old".to_string());
+
+        let findings = check_config(&config);
+        let fields: Vec<_> = findings.iter().map(|f| f.field.as_str()).collect();
+        assert!(fields.contains(&"digest.hour"));
+        assert!(fields.contains(&"compression.accept_encoding"));
+        assert!(fields.contains(&"model_successors.claude-old"));
+        assert!(findings.iter().all(|f| f.severity == Severity::Error));
+
+        let report = validate_config(&ProviderConfig::default(), &config);
+        assert!(report.has_errors());
+        assert_eq!(
+            report.changed,
+            ["compression", "digest", "model_successors"]
+        );
+    }
+}
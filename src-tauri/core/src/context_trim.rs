@@ -0,0 +1,699 @@
+//! 超长对话的上下文裁剪
+//!
+//! 请求估算超出模型上下文时，按配置的策略依次裁剪，直到放得下为止，
+//! 而不是原样转发后被上游拒绝：截断过长的工具输出、丢弃最早的轮次、
+//! 或用便宜的模型把早期对话压缩成摘要。最近的若干条消息与系统提示词
+//! 始终保留。Token 数按字符数粗略估算，图片与 PDF 按块单独估算（不按
+//! base64 字符数）；裁剪结果通过 `transform_request` 的返回值与
+//! `x-droid-context-trim` 响应头告知宿主。
+//!
+//! 摘要请求固定走 Anthropic 端点（不参与故障转移改道），按原请求的租户
+//! 占用凭证并计入用量；`transform_request` 在后台并发处理，等待摘要时不会
+//! 阻塞其他 RPC。
+
+use crate::config::get_config;
+use crate::credentials::{
+    AcquiredCredential, EndpointType, ErrorDetail, ReleaseReport, ReleaseStatus, UsageInfo,
+};
+use crate::documents;
+use crate::http::ordered_headers;
+use crate::provider::{self, AcquireOptions};
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// 摘要请求的 Anthropic API 版本
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// 裁剪策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrimStrategy {
+    /// 截断过长的工具输出
+    TruncateToolOutputs,
+    /// 丢弃最早的消息
+    DropOldest,
+    /// 用便宜的模型把早期消息压缩为摘要
+    Summarize,
+}
+
+/// 上下文裁剪配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextTrimConfig {
+    pub enabled: bool,
+    /// 依次尝试的策略
+    pub strategies: Vec<TrimStrategy>,
+    /// 始终保留的最近消息数
+    pub keep_recent_messages: usize,
+    /// 工具输出保留的最大字符数
+    pub tool_output_max_chars: usize,
+    /// 生成摘要的模型（须为 Claude 模型）
+    pub summary_model: String,
+    /// 摘要的最大输出 Token 数
+    pub summary_max_tokens: u64,
+    /// 估算用的每 Token 字符数
+    pub chars_per_token: u64,
+    /// 预留的安全余量（Token）
+    pub margin_tokens: u64,
+    /// 每张图片估算的 Token 数
+    pub image_tokens: u64,
+    /// PDF 每页估算的 Token 数
+    pub document_page_tokens: u64,
+}
+
+impl Default for ContextTrimConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strategies: vec![TrimStrategy::TruncateToolOutputs, TrimStrategy::DropOldest],
+            keep_recent_messages: 6,
+            tool_output_max_chars: 4_000,
+            summary_model: "claude-sonnet-4-20250514".to_string(),
+            summary_max_tokens: 1_024,
+            chars_per_token: 4,
+            margin_tokens: 1_000,
+            image_tokens: 1_600,
+            document_page_tokens: 2_000,
+        }
+    }
+}
+
+/// 裁剪结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrimReport {
+    /// 可用于输入的 Token 预算
+    pub budget_tokens: u64,
+    pub original_tokens: u64,
+    pub final_tokens: u64,
+    /// 实际生效的策略
+    pub applied: Vec<TrimStrategy>,
+    pub truncated_outputs: usize,
+    pub dropped_messages: usize,
+    pub summarized_messages: usize,
+    /// 裁剪后是否已在预算内
+    pub fits: bool,
+}
+
+impl TrimReport {
+    /// 响应头（未裁剪时为空）
+    pub fn headers(&self) -> BTreeMap<String, String> {
+        let mut headers = BTreeMap::new();
+        if !self.applied.is_empty() {
+            let applied: Vec<String> = self
+                .applied
+                .iter()
+                .filter_map(|s| serde_json::to_value(s).ok())
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect();
+            headers.insert("x-droid-context-trim".to_string(), applied.join(","));
+        }
+        headers
+    }
+}
+
+/// 文档块中 base64 编码的 PDF
+fn pdf_data(block: &serde_json::Value) -> Option<&str> {
+    match block["type"].as_str()? {
+        "document" if block["source"]["type"] == "base64" => block["source"]["data"].as_str(),
+        "input_file" | "file" => {
+            let data = block["file_data"]
+                .as_str()
+                .or(block["file"]["file_data"].as_str())?;
+            Some(data.split_once("base64,").map_or(data, |(_, data)| data))
+        }
+        _ => None,
+    }
+}
+
+/// 图片与 PDF 块的估算 Token 数，其他内容返回 None
+fn media_tokens(block: &serde_json::Value, config: &ContextTrimConfig) -> Option<u64> {
+    if matches!(
+        block["type"].as_str(),
+        Some("image") | Some("image_url") | Some("input_image")
+    ) {
+        return Some(config.image_tokens);
+    }
+    let data = pdf_data(block)?;
+    let pages = STANDARD
+        .decode(data)
+        .map(|pdf| documents::count_pdf_pages(&pdf))
+        .unwrap_or(0);
+    // 页数无法识别时按大小估算（约 50 KB 一页）
+    let pages = match pages {
+        0 => (documents::decoded_len(data) / 50_000).max(1),
+        pages => pages,
+    };
+    Some(pages * config.document_page_tokens)
+}
+
+/// 去掉图片与 PDF 块后的副本，媒体块的 Token 数累加到 `media`
+fn strip_media(
+    value: &serde_json::Value,
+    config: &ContextTrimConfig,
+    media: &mut u64,
+) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => match media_tokens(value, config) {
+            Some(tokens) => {
+                *media += tokens;
+                serde_json::Value::Null
+            }
+            None => map
+                .iter()
+                .map(|(key, value)| (key.clone(), strip_media(value, config, media)))
+                .collect(),
+        },
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(|item| strip_media(item, config, media))
+            .collect(),
+        other => other.clone(),
+    }
+}
+
+/// 估算 JSON 值的 Token 数
+pub fn estimate_tokens(value: &serde_json::Value, config: &ContextTrimConfig) -> u64 {
+    let mut media = 0;
+    let text = strip_media(value, config, &mut media).to_string();
+    let chars = text.chars().count() as u64;
+    chars.div_ceil(config.chars_per_token.max(1)) + media
+}
+
+/// 输入 Token 预算：上下文长度减去输出上限与安全余量
+pub fn input_budget(config: &ContextTrimConfig, request: &serde_json::Value, context: u64) -> u64 {
+    let max_output = ["max_tokens", "max_output_tokens", "max_completion_tokens"]
+        .iter()
+        .find_map(|key| request[*key].as_u64())
+        .unwrap_or(0);
+    context.saturating_sub(max_output + config.margin_tokens)
+}
+
+/// 消息数组所在字段（Anthropic / Chat 为 `messages`，Responses 为 `input`）
+fn items_key(request: &serde_json::Value) -> Option<&'static str> {
+    if request["messages"].is_array() {
+        Some("messages")
+    } else if request["input"].is_array() {
+        Some("input")
+    } else {
+        None
+    }
+}
+
+fn is_system(item: &serde_json::Value) -> bool {
+    matches!(item["role"].as_str(), Some("system") | Some("developer"))
+}
+
+/// 是否为工具结果（不能脱离对应的工具调用单独出现）
+fn is_tool_result(item: &serde_json::Value) -> bool {
+    item["role"] == "tool"
+        || item["type"] == "function_call_output"
+        || item["content"]
+            .as_array()
+            .is_some_and(|blocks| blocks.iter().any(|b| b["type"] == "tool_result"))
+}
+
+/// 裁剪后的对话能否从该消息开始
+fn is_clean_start(item: &serde_json::Value) -> bool {
+    item["role"] == "user" && !is_tool_result(item)
+}
+
+fn truncate_text(text: &str, max_chars: usize) -> Option<String> {
+    let total = text.chars().count();
+    if total <= max_chars {
+        return None;
+    }
+    let kept: String = text.chars().take(max_chars).collect();
+    Some(format!(
+        "{}\n…[truncated {} chars]",
+        kept,
+        total - max_chars
+    ))
+}
+
+/// 截断工具输出，返回截断的数量
+pub fn truncate_tool_outputs(request: &mut serde_json::Value, max_chars: usize) -> usize {
+    let Some(key) = items_key(request) else {
+        return 0;
+    };
+    let mut truncated = 0;
+    let mut truncate = |value: &mut serde_json::Value| {
+        if let Some(text) = value.as_str().and_then(|t| truncate_text(t, max_chars)) {
+            *value = serde_json::json!(text);
+            truncated += 1;
+        }
+    };
+    for item in request[key].as_array_mut().into_iter().flatten() {
+        if item["role"] == "tool" {
+            truncate(&mut item["content"]);
+        } else if item["type"] == "function_call_output" {
+            truncate(&mut item["output"]);
+        } else if let Some(blocks) = item["content"].as_array_mut() {
+            for block in blocks.iter_mut().filter(|b| b["type"] == "tool_result") {
+                match block["content"].as_array_mut() {
+                    Some(parts) => parts
+                        .iter_mut()
+                        .filter(|p| p["type"] == "text")
+                        .for_each(|p| truncate(&mut p["text"])),
+                    None => truncate(&mut block["content"]),
+                }
+            }
+        }
+    }
+    truncated
+}
+
+/// 可被裁剪的最早一段消息的下标（不含系统消息与最近的消息）
+fn trimmable(items: &[serde_json::Value], keep_recent: usize) -> Vec<usize> {
+    let conversation: Vec<usize> = (0..items.len())
+        .filter(|i| !is_system(&items[*i]))
+        .collect();
+    let keep = keep_recent.max(1).min(conversation.len());
+    conversation[..conversation.len() - keep].to_vec()
+}
+
+/// 把要移除的范围向后延伸到干净的起点，避免拆开工具调用与结果
+fn extend_to_clean_start(items: &[serde_json::Value], mut removed: Vec<usize>) -> Vec<usize> {
+    let last = items.len().saturating_sub(1);
+    let mut next = removed.last().map_or(0, |i| i + 1);
+    while next < last && (is_system(&items[next]) || !is_clean_start(&items[next])) {
+        if !is_system(&items[next]) {
+            removed.push(next);
+        }
+        next += 1;
+    }
+    removed
+}
+
+fn remove_indices(items: &mut Vec<serde_json::Value>, removed: &[usize]) {
+    let mut index = 0;
+    items.retain(|_| {
+        index += 1;
+        !removed.contains(&(index - 1))
+    });
+}
+
+/// 从最早的消息开始丢弃，直到放得下，返回丢弃的数量
+pub fn drop_oldest(
+    request: &mut serde_json::Value,
+    config: &ContextTrimConfig,
+    budget: u64,
+) -> usize {
+    let Some(key) = items_key(request) else {
+        return 0;
+    };
+    let mut excess = estimate_tokens(request, config).saturating_sub(budget);
+    if excess == 0 {
+        return 0;
+    }
+    let Some(items) = request[key].as_array_mut() else {
+        return 0;
+    };
+    let mut removed = Vec::new();
+    for index in trimmable(items, config.keep_recent_messages) {
+        if excess == 0 {
+            break;
+        }
+        excess = excess.saturating_sub(estimate_tokens(&items[index], config));
+        removed.push(index);
+    }
+    if removed.is_empty() {
+        return 0;
+    }
+    let removed = extend_to_clean_start(items, removed);
+    remove_indices(items, &removed);
+    removed.len()
+}
+
+/// 把消息渲染为摘要用的文本
+fn transcript(items: &[&serde_json::Value], max_chars: usize) -> String {
+    items
+        .iter()
+        .map(|item| {
+            let role = item["role"]
+                .as_str()
+                .or(item["type"].as_str())
+                .unwrap_or("item");
+            let text = match &item["content"] {
+                serde_json::Value::String(text) => text.clone(),
+                serde_json::Value::Null => item.to_string(),
+                content => content
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|block| match block["text"].as_str() {
+                        Some(text) => text.to_string(),
+                        None => block.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+            let text = truncate_text(&text, max_chars).unwrap_or(text);
+            format!("{}: {}", role, text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// 请求摘要模型，租约按正常请求释放（计入用量与健康状态）
+async fn request_summary(
+    config: &ContextTrimConfig,
+    transcript: String,
+    tenant_key: Option<&str>,
+) -> Result<String> {
+    if !config.summary_model.starts_with("claude-") {
+        anyhow::bail!("摘要模型须为 Claude 模型: {}", config.summary_model);
+    }
+    let body = serde_json::json!({
+        "model": config.summary_model,
+        "max_tokens": config.summary_max_tokens,
+        "system": "Summarize the earlier part of this conversation so it can continue without \
+                   it. Keep decisions, facts, file names, code identifiers and open tasks. \
+                   Reply with the summary only.",
+        "messages": [{ "role": "user", "content": transcript }],
+    });
+
+    // 摘要请求体是 Anthropic 格式：不参与故障转移改道，并显式使用 Anthropic 端点
+    let options = AcquireOptions {
+        raw: true,
+        tenant_key: tenant_key.map(str::to_string),
+        client_name: Some("context-trim".to_string()),
+        ..Default::default()
+    };
+    let acquired = provider::acquire_credential(&config.summary_model, &options).await?;
+    let lease_id = acquired
+        .metadata
+        .get("lease_id")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    let started = Instant::now();
+    let result = match provider::authorize_credential(&acquired.id, EndpointType::Anthropic).await {
+        Ok(direct) => send_summary(&direct, &body).await,
+        Err(e) => Err((None, e)),
+    };
+
+    let mut report = ReleaseReport {
+        lease_id,
+        latency_ms: Some(started.elapsed().as_millis() as u64),
+        ..Default::default()
+    };
+    match &result {
+        Ok((_, usage)) => report.usage = Some(usage.clone()),
+        Err((status_code, e)) => {
+            report.status = ReleaseStatus::Error;
+            report.error = Some(ErrorDetail {
+                status_code: *status_code,
+                message: Some(e.to_string()),
+                ..Default::default()
+            });
+        }
+    }
+    provider::release_credential(&acquired.id, report).await?;
+    result.map(|(summary, _)| summary).map_err(|(_, e)| e)
+}
+
+async fn send_summary(
+    acquired: &AcquiredCredential,
+    body: &serde_json::Value,
+) -> std::result::Result<(String, UsageInfo), (Option<u16>, anyhow::Error)> {
+    let config = get_config();
+    let timeouts = config.timeouts.for_endpoint(EndpointType::Anthropic);
+    let client = timeouts.client().map_err(|e| (None, e))?;
+    let url = acquired.base_url.clone();
+    let url = url.ok_or_else(|| (None, anyhow::anyhow!("凭证缺少请求地址")))?;
+    let mut headers = acquired.headers.clone();
+    headers.insert(
+        "anthropic-version".to_string(),
+        ANTHROPIC_VERSION.to_string(),
+    );
+    let mut builder = client.post(url).json(body);
+    for (name, value) in ordered_headers(&headers, &config.http.header_order) {
+        builder = builder.header(name, value);
+    }
+
+    let response = timeouts.send(builder).await.map_err(|e| (None, e))?;
+    let status = response.status();
+    let response: serde_json::Value = timeouts.read_json(response).await.map_err(|e| (None, e))?;
+    if !status.is_success() {
+        let message = response["error"]["message"]
+            .as_str()
+            .unwrap_or("未知错误")
+            .to_string();
+        return Err((
+            Some(status.as_u16()),
+            anyhow::anyhow!("摘要请求失败: {}", message),
+        ));
+    }
+    let summary: String = response["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|block| block["text"].as_str())
+        .collect();
+    let usage = serde_json::from_value(response["usage"].clone()).unwrap_or_default();
+    Ok((summary, usage))
+}
+
+/// 把最早的消息替换为摘要，返回被替换的数量
+async fn summarize(
+    request: &mut serde_json::Value,
+    config: &ContextTrimConfig,
+    tenant_key: Option<&str>,
+) -> Result<usize> {
+    let Some(key) = items_key(request) else {
+        return Ok(0);
+    };
+    let Some(items) = request[key].as_array() else {
+        return Ok(0);
+    };
+    let removed = trimmable(items, config.keep_recent_messages);
+    if removed.is_empty() {
+        return Ok(0);
+    }
+    let removed = extend_to_clean_start(items, removed);
+    let selected: Vec<&serde_json::Value> = removed.iter().map(|i| &items[*i]).collect();
+    let text = transcript(&selected, config.tool_output_max_chars);
+    let summary = request_summary(config, text, tenant_key).await?;
+
+    let Some(items) = request[key].as_array_mut() else {
+        return Ok(0);
+    };
+    let position = removed[0];
+    remove_indices(items, &removed);
+    items.insert(
+        position,
+        serde_json::json!({
+            "role": "user",
+            "content": format!("[Summary of the earlier conversation]\n{}", summary),
+        }),
+    );
+    Ok(removed.len())
+}
+
+/// 超出上下文时按策略裁剪；未超出或未开启时返回 None
+///
+/// `tenant_key` 为原请求的虚拟密钥，摘要请求按同一租户占用凭证。
+pub async fn trim(
+    config: &ContextTrimConfig,
+    request: &mut serde_json::Value,
+    context_length: u64,
+    tenant_key: Option<&str>,
+) -> Result<Option<TrimReport>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    let budget = input_budget(config, request, context_length);
+    let original = estimate_tokens(request, config);
+    if original <= budget {
+        return Ok(None);
+    }
+
+    let mut report = TrimReport {
+        budget_tokens: budget,
+        original_tokens: original,
+        ..Default::default()
+    };
+    for strategy in &config.strategies {
+        if estimate_tokens(request, config) <= budget {
+            break;
+        }
+        let changed = match strategy {
+            TrimStrategy::TruncateToolOutputs => {
+                let max_chars = config.tool_output_max_chars;
+                report.truncated_outputs += truncate_tool_outputs(request, max_chars);
+                report.truncated_outputs > 0
+            }
+            TrimStrategy::DropOldest => {
+                report.dropped_messages += drop_oldest(request, config, budget);
+                report.dropped_messages > 0
+            }
+            TrimStrategy::Summarize => match summarize(request, config, tenant_key).await {
+                Ok(count) => {
+                    report.summarized_messages += count;
+                    count > 0
+                }
+                // 摘要失败时继续尝试后续策略
+                Err(e) => {
+                    warn!("生成对话摘要失败: {}", e);
+                    false
+                }
+            },
+        };
+        if changed {
+            report.applied.push(*strategy);
+        }
+    }
+
+    report.final_tokens = estimate_tokens(request, config);
+    report.fits = report.final_tokens <= budget;
+    info!(
+        "请求超出上下文（约 {} > {} Token），裁剪后约 {} Token: {:?}",
+        original, budget, report.final_tokens, report.applied
+    );
+    Ok(Some(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ContextTrimConfig {
+        ContextTrimConfig {
+            enabled: true,
+            keep_recent_messages: 2,
+            tool_output_max_chars: 10,
+            chars_per_token: 1,
+            margin_tokens: 0,
+            ..Default::default()
+        }
+    }
+
+    fn turn(role: &str, text: &str) -> serde_json::Value {
+        serde_json::json!({ "role": role, "content": text })
+    }
+
+    #[test]
+    fn test_estimate_media_separately() {
+        let config = config();
+        let image = serde_json::json!({
+            "type": "image",
+            "source": { "type": "base64", "media_type": "image/png", "data": "A".repeat(10_000) },
+        });
+        let request = serde_json::json!({
+            "messages": [{ "role": "user", "content": [image, { "type": "text", "text": "hi" }] }]
+        });
+        let text_only = serde_json::json!({
+            "messages": [{ "role": "user", "content": [null, { "type": "text", "text": "hi" }] }]
+        });
+        assert_eq!(
+            estimate_tokens(&request, &config),
+            estimate_tokens(&text_only, &config) + config.image_tokens
+        );
+
+        let pdf = STANDARD.encode(b"%PDF-1.4 /Type /Page\n/Type /Page\n/Type /Pages");
+        let document = serde_json::json!({
+            "type": "document",
+            "source": { "type": "base64", "media_type": "application/pdf", "data": pdf },
+        });
+        assert_eq!(
+            media_tokens(&document, &config),
+            Some(2 * config.document_page_tokens)
+        );
+        assert_eq!(
+            media_tokens(&serde_json::json!({ "type": "text" }), &config),
+            None
+        );
+    }
+
+    #[test]
+    fn test_truncate_tool_outputs() {
+        let mut request = serde_json::json!({
+            "messages": [
+                { "role": "user", "content": [
+                    { "type": "tool_result", "tool_use_id": "t1", "content": "0123456789abcdef" },
+                ]},
+                { "role": "tool", "tool_call_id": "c1", "content": "short" },
+            ]
+        });
+        assert_eq!(truncate_tool_outputs(&mut request, 10), 1);
+        let content = request["messages"][0]["content"][0]["content"]
+            .as_str()
+            .unwrap();
+        assert!(content.starts_with("0123456789\n…[truncated 6 chars]"));
+        assert_eq!(request["messages"][1]["content"], "short");
+    }
+
+    #[test]
+    fn test_drop_oldest_keeps_system_and_recent() {
+        let long = "x".repeat(200);
+        let mut request = serde_json::json!({
+            "max_tokens": 10,
+            "messages": [
+                turn("system", "rules"),
+                turn("user", &long),
+                turn("assistant", &long),
+                turn("user", "latest question"),
+                turn("assistant", "ok"),
+            ]
+        });
+        let config = config();
+        let budget = input_budget(&config, &request, 300);
+        assert_eq!(budget, 290);
+        assert_eq!(drop_oldest(&mut request, &config, budget), 2);
+        let messages = request["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[1]["content"], "latest question");
+    }
+
+    #[test]
+    fn test_drop_oldest_does_not_orphan_tool_results() {
+        let long = "x".repeat(100);
+        let mut request = serde_json::json!({
+            "messages": [
+                turn("user", &long),
+                { "role": "assistant", "content": [{ "type": "tool_use", "id": "t1" }] },
+                { "role": "user", "content": [{ "type": "tool_result", "tool_use_id": "t1" }] },
+                turn("assistant", "done"),
+                turn("user", "next"),
+            ]
+        });
+        let config = ContextTrimConfig {
+            keep_recent_messages: 3,
+            ..config()
+        };
+        assert_eq!(drop_oldest(&mut request, &config, 150), 4);
+        let messages = request["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["content"], "next");
+    }
+
+    #[tokio::test]
+    async fn test_trim_reports_strategies() {
+        let mut request = serde_json::json!({
+            "messages": [
+                turn("user", &"a".repeat(100)),
+                turn("assistant", &"b".repeat(100)),
+                turn("user", "hi"),
+            ]
+        });
+        let report = trim(&config(), &mut request, 150, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.applied, vec![TrimStrategy::DropOldest]);
+        assert_eq!(report.dropped_messages, 2);
+        assert!(report.fits);
+        assert_eq!(report.headers()["x-droid-context-trim"], "drop_oldest");
+
+        let mut small = serde_json::json!({ "messages": [turn("user", "hi")] });
+        assert!(trim(&config(), &mut small, 150, None)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
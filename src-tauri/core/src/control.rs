@@ -0,0 +1,109 @@
+//! 全局暂停 / 恢复
+//!
+//! 怀疑上游风控时可立即停止所有流量：暂停期间 `acquire_credential`
+//! 按配置拒绝或排队等待，后台刷新也会跳过。
+//!
+//! 排队等待发生在 `acquire_credential` 内部。JSON-RPC 主循环把该方法放到
+//! 后台并发处理（`main.rs` 的 `CONCURRENT_METHODS`），排队期间 `resume`
+//! 等其他请求照常处理，不会被等待中的请求阻塞。
+
+use crate::events;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::info;
+
+/// 暂停期间获取凭证的行为
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseBehavior {
+    /// 直接拒绝
+    #[default]
+    Reject,
+    /// 排队等待恢复（超时后拒绝）；等待不阻塞 JSON-RPC 主循环
+    Queue,
+}
+
+/// 暂停配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PauseConfig {
+    #[serde(default)]
+    pub behavior: PauseBehavior,
+    /// 排队等待的最长时间（毫秒）
+    #[serde(default = "default_queue_timeout_ms")]
+    pub queue_timeout_ms: u64,
+}
+
+fn default_queue_timeout_ms() -> u64 {
+    30_000
+}
+
+impl Default for PauseConfig {
+    fn default() -> Self {
+        Self {
+            behavior: PauseBehavior::Reject,
+            queue_timeout_ms: default_queue_timeout_ms(),
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref PAUSED: watch::Sender<bool> = watch::channel(false).0;
+}
+
+/// 暂停 Provider
+pub fn pause(reason: Option<&str>) {
+    if PAUSED.send_replace(true) {
+        return;
+    }
+    info!("Provider 已暂停: {}", reason.unwrap_or("手动暂停"));
+    events::emit(
+        "provider_paused",
+        "Provider 已暂停，所有请求将被拦截",
+        serde_json::json!({ "reason": reason }),
+    );
+}
+
+/// 恢复 Provider
+pub fn resume() {
+    if !PAUSED.send_replace(false) {
+        return;
+    }
+    info!("Provider 已恢复");
+    events::emit(
+        "provider_resumed",
+        "Provider 已恢复",
+        serde_json::Value::Null,
+    );
+}
+
+/// 是否处于暂停状态
+pub fn is_paused() -> bool {
+    *PAUSED.borrow()
+}
+
+/// 等待恢复，超时返回 false
+pub async fn wait_until_resumed(timeout: Duration) -> bool {
+    let mut receiver = PAUSED.subscribe();
+    tokio::time::timeout(timeout, receiver.wait_for(|paused| !*paused))
+        .await
+        .map(|result| result.is_ok())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        pause(Some("test"));
+        assert!(is_paused());
+        assert!(!wait_until_resumed(Duration::from_millis(10)).await);
+
+        let waiter = tokio::spawn(wait_until_resumed(Duration::from_secs(5)));
+        resume();
+        assert!(waiter.await.unwrap());
+        assert!(!is_paused());
+    }
+}
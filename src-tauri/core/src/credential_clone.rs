@@ -0,0 +1,160 @@
+//! 凭证克隆
+//!
+//! 复制已有凭证并修改部分配置（端点类型、组织等），用于测试另一种配置而无需
+//! 重新输入密钥。加密字段用当前主密钥重新加密；OAuth 凭证与源凭证加入同一
+//! 刷新组，共享的 Refresh Token 轮换后会同步给对方。更换组织时清空
+//! Access Token，需按新组织重新刷新获取。
+
+use crate::auth::key_ring::KeyRing;
+use crate::credentials::{AuthType, DroidCredentials, EndpointType};
+use crate::health::HealthStats;
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// 克隆时覆盖的字段，未提供的沿用源凭证
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloneOverrides {
+    pub name: Option<String>,
+    pub endpoint_type: Option<EndpointType>,
+    pub supported_endpoints: Option<Vec<EndpointType>>,
+    pub organization_id: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// 生成克隆凭证（`group` 为 OAuth 凭证共用的刷新组）
+pub fn clone_credential(
+    source: &DroidCredentials,
+    overrides: CloneOverrides,
+    group: Option<&str>,
+    ring: &KeyRing,
+) -> Result<DroidCredentials> {
+    let mut cloned = DroidCredentials {
+        health_score: 100,
+        health: HealthStats::default(),
+        usage_count: 0,
+        error_count: 0,
+        recent_errors: VecDeque::new(),
+        cooldown_until: None,
+        endpoint_failures: HashMap::new(),
+        refresh_group: group.map(String::from),
+        ..source.clone()
+    };
+
+    let source_name = source.name.as_deref().unwrap_or("凭证");
+    cloned.name = Some(
+        overrides
+            .name
+            .unwrap_or_else(|| format!("{} (副本)", source_name)),
+    );
+    if let Some(endpoint_type) = overrides.endpoint_type {
+        cloned.endpoint_type = endpoint_type;
+    }
+    if let Some(supported) = overrides.supported_endpoints {
+        cloned.supported_endpoints = supported;
+    }
+    if let Some(user_agent) = overrides.user_agent {
+        cloned.user_agent = Some(user_agent).filter(|ua| !ua.is_empty());
+    }
+    let supported = &cloned.supported_endpoints;
+    if !supported.is_empty() && !supported.contains(&cloned.endpoint_type) {
+        anyhow::bail!("默认端点 {} 不在支持的端点列表中", cloned.endpoint_type);
+    }
+
+    if let Some(org_id) = overrides.organization_id {
+        if cloned.organization_id.as_deref() != Some(org_id.as_str()) {
+            // Access Token 绑定组织，需重新刷新
+            if cloned.auth_type == AuthType::OAuth {
+                cloned.access_token = None;
+                cloned.expires_at = None;
+            }
+            cloned.organization_id = Some(org_id);
+        }
+    }
+
+    // 用当前主密钥重新加密（新的 IV），不与源凭证共用密文
+    let now = Utc::now().to_rfc3339();
+    for entry in &mut cloned.api_keys {
+        entry.encrypted_key = ring.encrypt(&ring.decrypt(&entry.encrypted_key)?)?;
+        entry.id = uuid::Uuid::new_v4().to_string();
+        entry.created_at = now.clone();
+        entry.last_used_at = None;
+        entry.usage_count = 0;
+    }
+    if let Some(previous) = cloned.previous_refresh_token.as_mut() {
+        *previous = ring.encrypt(&ring.decrypt(previous)?)?;
+    }
+
+    Ok(cloned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credentials::ApiKeyEntry;
+
+    #[test]
+    fn test_clone_reencrypts_and_overrides() {
+        let ring = KeyRing::new("test-key");
+        let encrypted = ring.encrypt("fk-secret").unwrap();
+        let source = DroidCredentials {
+            name: Some("Work".to_string()),
+            auth_type: AuthType::ApiKey,
+            api_keys: vec![ApiKeyEntry {
+                id: "k1".to_string(),
+                hash: "h".to_string(),
+                encrypted_key: encrypted.clone(),
+                created_at: "2025-01-01T00:00:00Z".to_string(),
+                last_used_at: None,
+                usage_count: 12,
+                status: "active".to_string(),
+                error_message: None,
+            }],
+            error_count: 3,
+            ..Default::default()
+        };
+
+        let overrides = CloneOverrides {
+            endpoint_type: Some(EndpointType::Comm),
+            ..Default::default()
+        };
+        let cloned = clone_credential(&source, overrides, None, &ring).unwrap();
+        assert_eq!(cloned.name.as_deref(), Some("Work (副本)"));
+        assert_eq!(cloned.endpoint_type, EndpointType::Comm);
+        assert_eq!(cloned.error_count, 0);
+
+        let entry = &cloned.api_keys[0];
+        assert_ne!(entry.encrypted_key, encrypted);
+        assert_ne!(entry.id, "k1");
+        assert_eq!(entry.usage_count, 0);
+        assert_eq!(ring.decrypt(&entry.encrypted_key).unwrap(), "fk-secret");
+    }
+
+    #[test]
+    fn test_clone_with_other_org_drops_access_token() {
+        let source = DroidCredentials {
+            access_token: Some("at".to_string()),
+            refresh_token: Some("rt".to_string()),
+            organization_id: Some("org_1".to_string()),
+            ..Default::default()
+        };
+        let overrides = CloneOverrides {
+            organization_id: Some("org_2".to_string()),
+            ..Default::default()
+        };
+        let ring = KeyRing::new("test-key");
+        let cloned = clone_credential(&source, overrides, Some("cred-1"), &ring).unwrap();
+        assert!(cloned.access_token.is_none());
+        assert_eq!(cloned.refresh_token.as_deref(), Some("rt"));
+        assert_eq!(cloned.refresh_group.as_deref(), Some("cred-1"));
+
+        let invalid = CloneOverrides {
+            endpoint_type: Some(EndpointType::OpenAI),
+            supported_endpoints: Some(vec![EndpointType::Anthropic]),
+            ..Default::default()
+        };
+        assert!(clone_credential(&source, invalid, None, &ring).is_err());
+    }
+}
@@ -0,0 +1,626 @@
+//! 凭证数据结构
+
+use crate::canary::CanaryState;
+use crate::health::{HealthStats, MIN_HEALTH_SCORE};
+use crate::schedule::ActiveSchedule;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// 每个凭证保留的最近错误条数
+pub const ERROR_HISTORY_SIZE: usize = 20;
+
+/// 认证类型
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthType {
+    /// WorkOS OAuth 认证
+    #[default]
+    OAuth,
+    /// API Key 认证
+    ApiKey,
+}
+
+impl std::fmt::Display for AuthType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthType::OAuth => write!(f, "oauth"),
+            AuthType::ApiKey => write!(f, "api_key"),
+        }
+    }
+}
+
+/// 端点类型
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointType {
+    /// Anthropic Messages API
+    #[default]
+    Anthropic,
+    /// OpenAI Responses API
+    OpenAI,
+    /// OpenAI Chat Completions API
+    Comm,
+}
+
+impl std::fmt::Display for EndpointType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EndpointType::Anthropic => write!(f, "anthropic"),
+            EndpointType::OpenAI => write!(f, "openai"),
+            EndpointType::Comm => write!(f, "comm"),
+        }
+    }
+}
+
+/// API Key 条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyEntry {
+    /// 条目 ID
+    pub id: String,
+    /// API Key 哈希（用于去重）
+    pub hash: String,
+    /// 加密的 API Key
+    pub encrypted_key: String,
+    /// 创建时间
+    pub created_at: String,
+    /// 最后使用时间
+    #[serde(default)]
+    pub last_used_at: Option<String>,
+    /// 使用次数
+    #[serde(default)]
+    pub usage_count: u64,
+    /// 状态 (active/error)
+    #[serde(default = "default_status")]
+    pub status: String,
+    /// 错误信息
+    #[serde(default)]
+    pub error_message: Option<String>,
+}
+
+fn default_status() -> String {
+    "active".to_string()
+}
+
+/// Droid 凭证
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct DroidCredentials {
+    /// 凭证名称
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 认证类型
+    #[serde(default)]
+    pub auth_type: AuthType,
+    /// 端点类型
+    #[serde(default)]
+    pub endpoint_type: EndpointType,
+    /// 探测到的可用端点（为空表示未探测）
+    #[serde(default)]
+    pub supported_endpoints: Vec<EndpointType>,
+
+    // OAuth 字段
+    /// Access Token
+    pub access_token: Option<String>,
+    /// Refresh Token
+    pub refresh_token: Option<String>,
+    /// 轮换前的 Refresh Token（加密存储，仅在宽限期内用于回滚）
+    #[serde(default)]
+    pub previous_refresh_token: Option<String>,
+    /// 旧 Refresh Token 宽限期截止时间 (RFC3339 格式)
+    #[serde(default)]
+    pub previous_refresh_token_expires_at: Option<String>,
+    /// 登录会话首个 Refresh Token 的签发时间 (RFC3339 格式)，轮换时保持不变
+    #[serde(default)]
+    pub refresh_token_issued_at: Option<String>,
+    /// 共享 Refresh Token 的刷新组（多组织凭证），轮换后的 Token 在组内同步
+    #[serde(default)]
+    pub refresh_group: Option<String>,
+    /// 过期时间 (RFC3339 格式)
+    pub expires_at: Option<String>,
+    /// 组织 ID
+    pub organization_id: Option<String>,
+    /// 用户 ID
+    pub user_id: Option<String>,
+    /// 所有者邮箱
+    pub owner_email: Option<String>,
+    /// 所有者名称
+    pub owner_name: Option<String>,
+    /// Token 类型
+    #[serde(default = "default_token_type")]
+    pub token_type: String,
+
+    // API Key 字段
+    /// API Key 条目列表
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyEntry>,
+
+    // 通用字段
+    /// 最后刷新时间
+    pub last_refresh: Option<String>,
+    /// 健康分数 (0-100)
+    #[serde(default = "default_health_score")]
+    pub health_score: u8,
+    /// 计算健康分数用的统计
+    #[serde(default)]
+    pub health: HealthStats,
+    /// 使用次数
+    #[serde(default)]
+    pub usage_count: u64,
+    /// 错误次数
+    #[serde(default)]
+    pub error_count: u64,
+    /// 最近的错误记录（最多 ERROR_HISTORY_SIZE 条，旧的在前）
+    #[serde(default)]
+    pub recent_errors: VecDeque<CredentialError>,
+    /// 冷却截止时间 (RFC3339 格式)，期间不参与选择
+    #[serde(default)]
+    pub cooldown_until: Option<String>,
+    /// 各端点连续 5xx 次数，用于端点间故障转移
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub endpoint_failures: HashMap<EndpointType, u32>,
+    /// 单独设置的 User-Agent，覆盖全局配置
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// 额外的模型条目（企业组织自定义模型）
+    #[serde(default)]
+    pub extra_models: Vec<CustomModel>,
+    /// 备注（如“试用账号，3 月到期”）
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// 自定义键值元数据
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    /// 金丝雀观察期状态，观察期内只分到少量流量
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canary: Option<CanaryState>,
+    /// 只读：照常验证、刷新和展示，但从不分配流量（如保持备用账号活跃）
+    #[serde(default)]
+    pub read_only: bool,
+    /// 允许使用的模型（支持 `claude-*` 这样的前缀通配），为空表示不限制
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_models: Vec<String>,
+    /// 禁止使用的模型，优先于 allowed_models
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocked_models: Vec<String>,
+    /// 共享额度的预算组，为空时按组织 ID 关联
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota_group: Option<String>,
+    /// 启用时段（本地时间），时段外不分配流量；为空表示不限制
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_hours: Option<ActiveSchedule>,
+    /// 凭证等级，用于按模型限制可用凭证（见 `model_tiers`），默认为 0
+    #[serde(default)]
+    pub tier: u8,
+}
+
+/// 模型名是否匹配（支持 `claude-*` 这样的前缀通配）
+pub fn matches_model_pattern(pattern: &str, model: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => model.starts_with(prefix),
+        None => pattern == model,
+    }
+}
+
+/// 凭证的一次错误记录
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CredentialError {
+    /// 发生时间 (RFC3339 格式)
+    pub timestamp: String,
+    /// 上游 HTTP 状态码
+    #[serde(default)]
+    pub status_code: Option<u16>,
+    #[serde(default)]
+    pub error_type: Option<String>,
+    /// 上游返回的错误信息
+    #[serde(default)]
+    pub message: Option<String>,
+    /// 上游请求 ID
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
+/// 凭证声明的自定义模型
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomModel {
+    pub id: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// 该模型使用的端点
+    #[serde(default)]
+    pub endpoint_type: EndpointType,
+    #[serde(default)]
+    pub context_length: Option<u32>,
+}
+
+impl DroidCredentials {
+    /// 是否处于冷却期
+    pub fn in_cooldown(&self) -> bool {
+        self.cooldown_until
+            .as_deref()
+            .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+            .map(|until| until > chrono::Utc::now())
+            .unwrap_or(false)
+    }
+
+    /// 当前是否在启用时段内（未设置时段时始终为真）
+    pub fn in_active_hours(&self) -> bool {
+        self.active_hours.as_ref().is_none_or(|s| s.is_active())
+    }
+
+    /// 记录一次错误，超出保留条数时丢弃最旧的
+    pub fn record_error(&mut self, error: CredentialError) {
+        if self.recent_errors.len() >= ERROR_HISTORY_SIZE {
+            self.recent_errors.pop_front();
+        }
+        self.recent_errors.push_back(error);
+    }
+
+    /// 该凭证的套餐是否允许使用某个模型
+    pub fn allows_model(&self, model: &str) -> bool {
+        let matches = |pattern: &String| matches_model_pattern(pattern, model);
+        !self.blocked_models.iter().any(matches)
+            && (self.allowed_models.is_empty() || self.allowed_models.iter().any(matches))
+    }
+
+    /// 是否健康（分数不低于阈值）
+    pub fn is_healthy(&self) -> bool {
+        self.health_score >= MIN_HEALTH_SCORE
+    }
+
+    /// 按最新统计重新计算健康分数
+    pub fn update_health_score(&mut self) {
+        self.health_score = self.health.score(chrono::Utc::now());
+    }
+}
+
+fn default_token_type() -> String {
+    "Bearer".to_string()
+}
+
+fn default_health_score() -> u8 {
+    100
+}
+
+impl Default for DroidCredentials {
+    fn default() -> Self {
+        Self {
+            name: None,
+            auth_type: AuthType::OAuth,
+            endpoint_type: EndpointType::Anthropic,
+            supported_endpoints: Vec::new(),
+            access_token: None,
+            refresh_token: None,
+            previous_refresh_token: None,
+            previous_refresh_token_expires_at: None,
+            refresh_token_issued_at: None,
+            refresh_group: None,
+            expires_at: None,
+            organization_id: None,
+            user_id: None,
+            owner_email: None,
+            owner_name: None,
+            token_type: default_token_type(),
+            api_keys: Vec::new(),
+            last_refresh: None,
+            health_score: default_health_score(),
+            health: HealthStats::default(),
+            usage_count: 0,
+            error_count: 0,
+            recent_errors: VecDeque::new(),
+            cooldown_until: None,
+            endpoint_failures: HashMap::new(),
+            user_agent: None,
+            extra_models: Vec::new(),
+            notes: None,
+            metadata: HashMap::new(),
+            canary: None,
+            read_only: false,
+            allowed_models: Vec::new(),
+            blocked_models: Vec::new(),
+            quota_group: None,
+            active_hours: None,
+            tier: 0,
+        }
+    }
+}
+
+/// 获取的凭证
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcquiredCredential {
+    /// 凭证 ID
+    pub id: String,
+    /// 凭证名称
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 认证方式
+    pub auth_type: String,
+    /// Base URL
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// 请求头
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// 额外元数据
+    #[serde(default)]
+    pub metadata: HashMap<String, serde_json::Value>,
+}
+
+/// 凭证验证结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationResult {
+    /// 是否有效
+    pub valid: bool,
+    /// 消息
+    #[serde(default)]
+    pub message: Option<String>,
+    /// 额外信息
+    #[serde(default)]
+    pub details: HashMap<String, serde_json::Value>,
+}
+
+/// Token 刷新结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenRefreshResult {
+    /// 新的 access_token
+    pub access_token: String,
+    /// 新的 refresh_token（如果更新了）
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// 过期时间
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// 组织 ID
+    #[serde(default)]
+    pub organization_id: Option<String>,
+    /// 用户 ID
+    #[serde(default)]
+    pub user_id: Option<String>,
+    /// 所有者邮箱
+    #[serde(default)]
+    pub owner_email: Option<String>,
+}
+
+/// WorkOS Token 响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkOSTokenResponse {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<String>,
+    pub expires_in: Option<i64>,
+    pub token_type: Option<String>,
+    pub organization_id: Option<String>,
+    pub user: Option<WorkOSUser>,
+    pub authentication_method: Option<String>,
+}
+
+/// WorkOS 用户信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkOSUser {
+    pub id: Option<String>,
+    pub email: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+}
+
+/// 请求结果状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseStatus {
+    #[default]
+    Success,
+    Error,
+    /// 客户端取消，不影响健康状态
+    Cancelled,
+}
+
+/// Token 用量
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageInfo {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+}
+
+impl UsageInfo {
+    /// 从上游响应的 `usage` 读取（Anthropic 与 Chat Completions 字段均可）
+    pub fn from_response(response: &serde_json::Value) -> Self {
+        let usage = &response["usage"];
+        let tokens = |keys: [&str; 2]| keys.iter().find_map(|k| usage[*k].as_u64()).unwrap_or(0);
+        Self {
+            input_tokens: tokens(["input_tokens", "prompt_tokens"]),
+            output_tokens: tokens(["output_tokens", "completion_tokens"]),
+        }
+    }
+}
+
+/// 请求错误详情
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ErrorDetail {
+    /// 上游 HTTP 状态码
+    #[serde(default)]
+    pub status_code: Option<u16>,
+    /// 错误类型（与 parse_error 返回的 error_type 一致）
+    #[serde(default)]
+    pub error_type: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+    /// 上游请求 ID（如 `request-id` 响应头）
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// 冷却时长，未提供时按状态码推断
+    #[serde(default)]
+    pub cooldown_seconds: Option<u64>,
+}
+
+/// 释放凭证时上报的请求结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReleaseReport {
+    /// acquire_credential 返回的租约 ID
+    #[serde(default)]
+    pub lease_id: Option<String>,
+    #[serde(default)]
+    pub status: ReleaseStatus,
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    #[serde(default)]
+    pub usage: Option<UsageInfo>,
+    #[serde(default)]
+    pub error: Option<ErrorDetail>,
+    /// 是否直接标记为不健康
+    #[serde(default)]
+    pub mark_unhealthy: bool,
+}
+
+impl ReleaseReport {
+    /// 解析上报结果，兼容旧格式
+    ///
+    /// 旧格式没有 `status` 字段，错误信息位于 `error.message`，
+    /// `mark_unhealthy` 位于 `error` 内部。兼容期结束后将只接受新格式。
+    pub fn from_value(value: serde_json::Value) -> anyhow::Result<Self> {
+        if value.get("status").is_some() {
+            return Ok(serde_json::from_value(value)?);
+        }
+
+        tracing::debug!("release_credential 收到旧格式结果，建议迁移到 ReleaseReport");
+        let error = value.get("error").filter(|e| !e.is_null());
+        Ok(Self {
+            lease_id: value
+                .get("lease_id")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+            status: if error.is_some() {
+                ReleaseStatus::Error
+            } else {
+                ReleaseStatus::Success
+            },
+            latency_ms: value.get("latency_ms").and_then(|v| v.as_u64()),
+            usage: value
+                .get("usage")
+                .cloned()
+                .and_then(|u| serde_json::from_value(u).ok()),
+            error: error.map(|e| ErrorDetail {
+                status_code: e
+                    .get("status_code")
+                    .and_then(|v| v.as_u64())
+                    .map(|v| v as u16),
+                error_type: e
+                    .get("error_type")
+                    .and_then(|v| v.as_str())
+                    .map(String::from),
+                message: e.get("message").and_then(|v| v.as_str()).map(String::from),
+                request_id: e
+                    .get("request_id")
+                    .and_then(|v| v.as_str())
+                    .map(String::from),
+                cooldown_seconds: e.get("cooldown_seconds").and_then(|v| v.as_u64()),
+            }),
+            mark_unhealthy: error
+                .and_then(|e| e.get("mark_unhealthy"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_report_legacy_format() {
+        let report = ReleaseReport::from_value(serde_json::json!({
+            "error": { "message": "boom", "mark_unhealthy": true }
+        }))
+        .unwrap();
+        assert_eq!(report.status, ReleaseStatus::Error);
+        assert!(report.mark_unhealthy);
+        assert_eq!(
+            report.error.and_then(|e| e.message).as_deref(),
+            Some("boom")
+        );
+
+        let success = ReleaseReport::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(success.status, ReleaseStatus::Success);
+        assert!(success.error.is_none());
+    }
+
+    #[test]
+    fn test_release_report_typed_format() {
+        let report = ReleaseReport::from_value(serde_json::json!({
+            "status": "error",
+            "latency_ms": 1200,
+            "usage": { "input_tokens": 10, "output_tokens": 5 },
+            "error": { "status_code": 429 }
+        }))
+        .unwrap();
+        assert_eq!(report.status, ReleaseStatus::Error);
+        assert_eq!(report.error.and_then(|e| e.status_code), Some(429));
+        assert_eq!(report.usage.map(|u| u.output_tokens), Some(5));
+    }
+
+    #[test]
+    fn test_notes_and_metadata_default() {
+        let credential: DroidCredentials = serde_json::from_value(serde_json::json!({
+            "access_token": null,
+            "refresh_token": null,
+            "expires_at": null,
+            "organization_id": null,
+            "user_id": null,
+            "owner_email": null,
+            "owner_name": null,
+            "last_refresh": null
+        }))
+        .unwrap();
+        assert!(credential.notes.is_none());
+        assert!(credential.metadata.is_empty());
+
+        let value = serde_json::to_value(DroidCredentials {
+            notes: Some("试用账号，3 月到期".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(value["notes"], "试用账号，3 月到期");
+        assert!(value.get("metadata").is_none());
+    }
+
+    #[test]
+    fn test_allows_model() {
+        let mut credential = DroidCredentials {
+            allowed_models: vec!["claude-*".to_string()],
+            ..Default::default()
+        };
+        assert!(credential.allows_model("claude-sonnet-4-20250514"));
+        assert!(!credential.allows_model("gpt-5-2025-08-07"));
+
+        credential.blocked_models = vec!["claude-opus-4-1-20250805".to_string()];
+        assert!(!credential.allows_model("claude-opus-4-1-20250805"));
+
+        assert!(DroidCredentials::default().allows_model("gpt-5-2025-08-07"));
+    }
+
+    #[test]
+    fn test_in_cooldown() {
+        let mut credential = DroidCredentials::default();
+        assert!(!credential.in_cooldown());
+        credential.cooldown_until =
+            Some((chrono::Utc::now() + chrono::Duration::seconds(60)).to_rfc3339());
+        assert!(credential.in_cooldown());
+    }
+
+    #[test]
+    fn test_error_history_is_bounded() {
+        let mut credential = DroidCredentials::default();
+        for i in 0..ERROR_HISTORY_SIZE + 5 {
+            credential.record_error(CredentialError {
+                message: Some(format!("error {}", i)),
+                ..Default::default()
+            });
+        }
+        assert_eq!(credential.recent_errors.len(), ERROR_HISTORY_SIZE);
+        assert_eq!(
+            credential.recent_errors[0].message.as_deref(),
+            Some("error 5")
+        );
+    }
+}
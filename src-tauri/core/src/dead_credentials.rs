@@ -0,0 +1,307 @@
+//! 长期失效凭证清理
+//!
+//! 大量导入的凭证池中，早已失效的账号会拖慢选择并干扰列表展示。
+//! 按账号身份（同 `backoff_state`）记录凭证开始不健康的时间与最近一次成功
+//! 请求，连续 `unhealthy_days` 天不健康且期间没有成功请求的凭证标记为失效，
+//! 发出 `dead_credentials_found` 事件；用户确认后一键归档或删除。
+//! 归档会把凭证完整写入 `archived_credentials.json`（Token 加密）后移出凭证池。
+
+use crate::auth::token_seal;
+use crate::backoff_state::state_key;
+use crate::config::data_dir;
+use crate::credentials::DroidCredentials;
+use crate::events;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// 状态文件名
+pub const LIVENESS_FILE: &str = "liveness.json";
+
+/// 归档文件名
+pub const ARCHIVE_FILE: &str = "archived_credentials.json";
+
+/// 后台检查间隔（秒）
+const CHECK_INTERVAL_SECS: u64 = 3600;
+
+/// 失效凭证配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeadCredentialConfig {
+    pub enabled: bool,
+    /// 连续不健康多少天后视为失效
+    pub unhealthy_days: u32,
+}
+
+impl Default for DeadCredentialConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            unhealthy_days: 14,
+        }
+    }
+}
+
+/// 清理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupAction {
+    /// 写入归档文件后移出凭证池
+    Archive,
+    /// 直接移出凭证池
+    Delete,
+}
+
+/// 单个账号的存活记录
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Liveness {
+    /// 开始不健康的时间，恢复健康后清除
+    #[serde(default)]
+    pub unhealthy_since: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_success: Option<DateTime<Utc>>,
+    /// 已发出过失效通知
+    #[serde(default)]
+    pub flagged: bool,
+}
+
+impl Liveness {
+    /// 按当前健康状态更新
+    fn observe(&mut self, healthy: bool, now: DateTime<Utc>) {
+        if healthy {
+            self.unhealthy_since = None;
+            self.flagged = false;
+        } else {
+            self.unhealthy_since.get_or_insert(now);
+        }
+    }
+
+    /// 连续不健康超过指定天数，且期间没有成功请求
+    pub fn is_dead(&self, days: u32, now: DateTime<Utc>) -> bool {
+        self.unhealthy_since.is_some_and(|since| {
+            now - since >= Duration::days(days as i64)
+                && self.last_success.is_none_or(|success| success < since)
+        })
+    }
+}
+
+/// 失效凭证
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadCredential {
+    pub credential_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub unhealthy_since: DateTime<Utc>,
+    #[serde(default)]
+    pub last_success: Option<DateTime<Utc>>,
+    pub health_score: u8,
+    /// 本次检查新发现（尚未通知过）
+    #[serde(default)]
+    pub newly_flagged: bool,
+}
+
+/// 清理结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub action: Option<CleanupAction>,
+    pub removed: Vec<String>,
+    /// 未失效或仍有进行中请求而跳过的凭证
+    pub skipped: Vec<String>,
+    #[serde(default)]
+    pub archive_path: Option<String>,
+}
+
+/// 归档条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedCredential {
+    pub archived_at: DateTime<Utc>,
+    pub credential: DroidCredentials,
+}
+
+lazy_static::lazy_static! {
+    static ref STATES: Mutex<Option<BTreeMap<String, Liveness>>> = Mutex::new(None);
+}
+
+fn liveness_path() -> PathBuf {
+    data_dir().join(LIVENESS_FILE)
+}
+
+/// 归档文件路径
+pub fn archive_path() -> PathBuf {
+    data_dir().join(ARCHIVE_FILE)
+}
+
+fn load_from_disk() -> BTreeMap<String, Liveness> {
+    match crate::store::read_json(&liveness_path()) {
+        Ok(states) => states.unwrap_or_default(),
+        Err(e) => {
+            warn!("凭证存活记录读取失败，已忽略: {}", e);
+            BTreeMap::new()
+        }
+    }
+}
+
+fn save_to_disk(states: &BTreeMap<String, Liveness>) {
+    if let Err(e) = crate::store::write_json(&liveness_path(), states) {
+        warn!("保存凭证存活记录失败: {}", e);
+    }
+}
+
+/// 记录一次成功请求
+pub fn record_success(credential: &DroidCredentials) {
+    let Some(key) = state_key(credential) else {
+        return;
+    };
+    let mut guard = STATES.lock().unwrap();
+    let states = guard.get_or_insert_with(load_from_disk);
+    let now = Utc::now();
+    let liveness = states.entry(key).or_default();
+    // 精确到小时即可判断，避免每个请求都写盘
+    if liveness
+        .last_success
+        .is_some_and(|t| now - t < Duration::hours(1))
+    {
+        return;
+    }
+    liveness.last_success = Some(now);
+    save_to_disk(states);
+}
+
+/// 更新全部凭证的存活记录，返回已失效的凭证
+pub fn scan<'a>(
+    config: &DeadCredentialConfig,
+    credentials: impl IntoIterator<Item = (&'a String, &'a DroidCredentials)>,
+    now: DateTime<Utc>,
+) -> Vec<DeadCredential> {
+    let mut guard = STATES.lock().unwrap();
+    let states = guard.get_or_insert_with(load_from_disk);
+    let mut changed = false;
+    let mut dead = Vec::new();
+    for (id, credential) in credentials {
+        let Some(key) = state_key(credential) else {
+            continue;
+        };
+        let liveness = states.entry(key).or_default();
+        let before = liveness.clone();
+        liveness.observe(credential.is_healthy(), now);
+        if config.enabled && liveness.is_dead(config.unhealthy_days, now) {
+            dead.push(DeadCredential {
+                credential_id: id.clone(),
+                name: credential.name.clone(),
+                unhealthy_since: liveness.unhealthy_since.unwrap_or(now),
+                last_success: liveness.last_success,
+                health_score: credential.health_score,
+                newly_flagged: !liveness.flagged,
+            });
+            liveness.flagged = true;
+        }
+        changed |= *liveness != before;
+    }
+    if changed {
+        save_to_disk(states);
+    }
+    dead.sort_by_key(|d| d.unhealthy_since);
+    dead
+}
+
+/// 删除凭证的存活记录
+pub fn forget(credential: &DroidCredentials) {
+    let Some(key) = state_key(credential) else {
+        return;
+    };
+    let mut guard = STATES.lock().unwrap();
+    let states = guard.get_or_insert_with(load_from_disk);
+    if states.remove(&key).is_some() {
+        save_to_disk(states);
+    }
+}
+
+/// 把凭证追加到归档文件
+pub fn archive(credentials: &HashMap<String, DroidCredentials>) -> Result<PathBuf> {
+    let path = archive_path();
+    let mut archived: BTreeMap<String, ArchivedCredential> =
+        crate::store::read_json(&path)?.unwrap_or_default();
+    let now = Utc::now();
+    for (id, credential) in credentials {
+        let mut credential = credential.clone();
+        token_seal::seal(&mut credential)?;
+        let entry = ArchivedCredential {
+            archived_at: now,
+            credential,
+        };
+        archived.insert(id.clone(), entry);
+    }
+    crate::store::write_json(&path, &archived)?;
+    Ok(path)
+}
+
+/// 后台任务：定期检查并通知新发现的失效凭证
+pub async fn run_monitor() {
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+        let dead = crate::provider::dead_credentials().await;
+        let new: Vec<_> = dead.iter().filter(|d| d.newly_flagged).collect();
+        if new.is_empty() {
+            continue;
+        }
+        info!("发现 {} 个长期失效的凭证", new.len());
+        events::emit(
+            "dead_credentials_found",
+            format!("{} 个凭证已长期不可用，可归档或删除", new.len()),
+            serde_json::json!({ "credentials": new, "total": dead.len() }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_liveness() {
+        let now = Utc::now();
+        let mut liveness = Liveness::default();
+        liveness.observe(false, now - Duration::days(15));
+        liveness.observe(false, now);
+        assert!(liveness.is_dead(14, now));
+        assert!(!liveness.is_dead(30, now));
+
+        // 不健康期间有过成功请求
+        liveness.last_success = Some(now - Duration::days(3));
+        assert!(!liveness.is_dead(14, now));
+
+        liveness.observe(true, now);
+        assert!(liveness.unhealthy_since.is_none());
+        assert!(!liveness.is_dead(0, now));
+    }
+
+    #[test]
+    fn test_scan_flags_once() {
+        let config = DeadCredentialConfig {
+            enabled: true,
+            unhealthy_days: 1,
+        };
+        let mut credential = DroidCredentials {
+            user_id: Some("user_dead_scan".to_string()),
+            ..Default::default()
+        };
+        credential.health.marked_unhealthy = true;
+        credential.update_health_score();
+        let credentials = HashMap::from([("cred-1".to_string(), credential)]);
+
+        let start = Utc::now();
+        assert!(scan(&config, &credentials, start).is_empty());
+        let dead = scan(&config, &credentials, start + Duration::days(2));
+        assert_eq!(dead.len(), 1);
+        assert!(dead[0].newly_flagged);
+        let dead = scan(&config, &credentials, start + Duration::days(3));
+        assert!(!dead[0].newly_flagged);
+
+        forget(&credentials["cred-1"]);
+        assert!(scan(&config, &credentials, start + Duration::days(3)).is_empty());
+    }
+}
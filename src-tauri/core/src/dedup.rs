@@ -0,0 +1,242 @@
+//! 进行中请求去重
+//!
+//! 客户端超时后重发完全相同的非流式请求时，如果原请求仍在进行，重发者
+//! 不再拿凭证请求上游，而是等待原请求的响应：原请求经 `transform_response`
+//! 转换后发布响应，跟随者通过 `await_shared_response` 取得同一份结果。
+//! 跟随者不访问上游，因此不占用租约、不计入用量；原请求失败或未发布响应
+//! 就释放时，跟随者收到错误，应重新申请凭证。
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::watch;
+
+/// 去重配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 去重窗口（毫秒），超过该时长的原请求不再复用
+    #[serde(default = "default_window_ms")]
+    pub window_ms: u64,
+    /// 跟随者等待原请求响应的最长时间（毫秒）
+    #[serde(default = "default_max_wait_ms")]
+    pub max_wait_ms: u64,
+}
+
+fn default_window_ms() -> u64 {
+    30_000
+}
+
+fn default_max_wait_ms() -> u64 {
+    300_000
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_ms: default_window_ms(),
+            max_wait_ms: default_max_wait_ms(),
+        }
+    }
+}
+
+/// 原请求发布的响应（None 表示尚未返回）
+type Shared = Option<serde_json::Value>;
+
+/// 进行中的原请求
+#[derive(Debug, Clone)]
+pub struct InFlight {
+    pub lease_id: String,
+    pub credential_id: String,
+    pub started_at: DateTime<Utc>,
+}
+
+struct Entry {
+    in_flight: InFlight,
+    response: watch::Sender<Shared>,
+}
+
+#[derive(Default)]
+struct DedupTable {
+    by_hash: HashMap<String, Entry>,
+    /// 跟随者租约 → 原请求的响应
+    followers: HashMap<String, watch::Receiver<Shared>>,
+}
+
+lazy_static::lazy_static! {
+    static ref TABLE: Mutex<DedupTable> = Mutex::new(DedupTable::default());
+}
+
+/// 计算请求指纹（对 JSON 规范化后取 SHA256）
+///
+/// 流式请求无法共享响应，返回 None；租户不同的请求不会互相复用。
+pub fn request_fingerprint(request: &serde_json::Value, tenant: Option<&str>) -> Option<String> {
+    if request["stream"] == true {
+        return None;
+    }
+    // serde_json 的 Map 默认按键排序，序列化结果稳定
+    let canonical = serde_json::to_string(request).unwrap_or_default();
+    let mut hasher = Sha256::new();
+    hasher.update(tenant.unwrap_or_default().as_bytes());
+    hasher.update([0]);
+    hasher.update(canonical.as_bytes());
+    Some(hex::encode(hasher.finalize()))
+}
+
+/// 查找窗口内相同指纹的进行中请求
+pub fn find_in_flight(hash: &str, window_ms: u64) -> Option<InFlight> {
+    let mut table = TABLE.lock().unwrap();
+    let deadline = Utc::now() - Duration::milliseconds(window_ms as i64);
+    match table.by_hash.get(hash) {
+        Some(entry) if entry.in_flight.started_at > deadline => Some(entry.in_flight.clone()),
+        Some(_) => {
+            table.by_hash.remove(hash);
+            None
+        }
+        None => None,
+    }
+}
+
+/// 登记原请求
+pub fn register(hash: &str, lease_id: &str, credential_id: &str) {
+    let (response, _) = watch::channel(None);
+    TABLE.lock().unwrap().by_hash.insert(
+        hash.to_string(),
+        Entry {
+            in_flight: InFlight {
+                lease_id: lease_id.to_string(),
+                credential_id: credential_id.to_string(),
+                started_at: Utc::now(),
+            },
+            response,
+        },
+    );
+}
+
+/// 登记跟随者，返回其租约 ID（原请求已结束时返回 None）
+pub fn attach_follower(original_lease_id: &str) -> Option<String> {
+    let mut table = TABLE.lock().unwrap();
+    let response = table
+        .by_hash
+        .values()
+        .find(|e| e.in_flight.lease_id == original_lease_id)?
+        .response
+        .subscribe();
+    let follower_id = uuid::Uuid::new_v4().to_string();
+    table.followers.insert(follower_id.clone(), response);
+    Some(follower_id)
+}
+
+/// 原请求发布转换后的响应，并不再接受新的跟随者
+pub fn publish(lease_id: &str, response: &serde_json::Value) {
+    let mut table = TABLE.lock().unwrap();
+    let Some(hash) = table
+        .by_hash
+        .iter()
+        .find(|(_, e)| e.in_flight.lease_id == lease_id)
+        .map(|(hash, _)| hash.clone())
+    else {
+        return;
+    };
+    if let Some(entry) = table.by_hash.remove(&hash) {
+        entry.response.send_replace(Some(response.clone()));
+    }
+}
+
+/// 是否为跟随者租约
+pub fn is_follower(lease_id: &str) -> bool {
+    TABLE.lock().unwrap().followers.contains_key(lease_id)
+}
+
+/// 跟随者等待原请求的响应
+pub async fn wait(follower_id: &str, max_wait_ms: u64) -> Result<serde_json::Value> {
+    let mut response = TABLE
+        .lock()
+        .unwrap()
+        .followers
+        .get(follower_id)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("跟随者租约不存在: {}", follower_id))?;
+    let timeout = std::time::Duration::from_millis(max_wait_ms);
+    let waited = tokio::time::timeout(timeout, response.wait_for(Option::is_some))
+        .await
+        .map(|shared| shared.map(|s| s.clone()));
+    match waited {
+        Ok(Ok(shared)) => Ok(shared.unwrap_or_default()),
+        // 原请求已释放：发布过则取已发布的响应
+        Ok(Err(_)) => response
+            .borrow()
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("原请求未返回响应，请重新申请凭证")),
+        Err(_) => anyhow::bail!("等待原请求响应超时（{} 毫秒）", max_wait_ms),
+    }
+}
+
+/// 释放租约：跟随者返回 true（调用方应跳过统计），原请求则从表中移除
+pub fn release(lease_id: &str) -> bool {
+    let mut table = TABLE.lock().unwrap();
+    if table.followers.remove(lease_id).is_some() {
+        return true;
+    }
+    table
+        .by_hash
+        .retain(|_, entry| entry.in_flight.lease_id != lease_id);
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_key_order_independent() {
+        let a = serde_json::json!({ "model": "m", "messages": [], "stream": false });
+        let b = serde_json::json!({ "stream": false, "messages": [], "model": "m" });
+        assert_eq!(request_fingerprint(&a, None), request_fingerprint(&b, None));
+        assert_ne!(
+            request_fingerprint(&a, None),
+            request_fingerprint(&a, Some("team"))
+        );
+        let stream = serde_json::json!({ "model": "m", "stream": true });
+        assert!(request_fingerprint(&stream, None).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_follower_shares_response() {
+        let hash = request_fingerprint(&serde_json::json!({ "test": "dedup" }), None).unwrap();
+        register(&hash, "lease-dedup", "cred");
+        assert_eq!(
+            find_in_flight(&hash, 30_000).map(|f| f.lease_id),
+            Some("lease-dedup".to_string())
+        );
+
+        let follower = attach_follower("lease-dedup").unwrap();
+        assert!(is_follower(&follower));
+        let waiting = tokio::spawn({
+            let follower = follower.clone();
+            async move { wait(&follower, 5_000).await }
+        });
+        let response = serde_json::json!({ "content": "shared" });
+        publish("lease-dedup", &response);
+        assert!(find_in_flight(&hash, 30_000).is_none());
+        assert!(!release("lease-dedup"));
+        assert_eq!(waiting.await.unwrap().unwrap(), response);
+        assert!(release(&follower));
+    }
+
+    #[tokio::test]
+    async fn test_follower_fails_when_original_released() {
+        let hash = request_fingerprint(&serde_json::json!({ "test": "dedup-fail" }), None).unwrap();
+        register(&hash, "lease-dedup-fail", "cred");
+        let follower = attach_follower("lease-dedup-fail").unwrap();
+        assert!(!release("lease-dedup-fail"));
+        assert!(wait(&follower, 5_000).await.is_err());
+        assert!(release(&follower));
+        assert!(attach_follower("lease-dedup-fail").is_none());
+    }
+}
@@ -0,0 +1,146 @@
+//! 模型弃用检测
+//!
+//! 上游返回 "model not found / deprecated" 时记录到弃用表，
+//! 不再在 `list_models` 中展示该模型，并按配置将其改写为继任模型。
+
+use crate::config::get_config;
+use crate::events;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tracing::warn;
+
+/// 弃用模型记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeprecatedModel {
+    pub model: String,
+    /// 检测时间 (RFC3339 格式)
+    pub detected_at: String,
+    /// 上游状态码
+    pub status_code: u16,
+    /// 上游错误信息
+    pub message: String,
+    /// 配置的继任模型
+    #[serde(default)]
+    pub successor: Option<String>,
+}
+
+lazy_static::lazy_static! {
+    static ref DEPRECATED_MODELS: RwLock<HashMap<String, DeprecatedModel>> =
+        RwLock::new(HashMap::new());
+}
+
+/// 判断上游错误是否表示模型不存在或已弃用
+pub fn is_model_unavailable_error(status: u16, body: &str) -> bool {
+    if status != 400 && status != 404 && status != 410 {
+        return false;
+    }
+
+    let body = body.to_lowercase();
+    body.contains("model")
+        && [
+            "not found",
+            "not_found",
+            "deprecated",
+            "does not exist",
+            "no longer",
+            "retired",
+        ]
+        .iter()
+        .any(|marker| body.contains(marker))
+}
+
+/// 记录模型弃用
+pub fn record_deprecation(model: &str, status: u16, body: &str) {
+    let successor = get_config().model_successors.get(model).cloned();
+
+    let mut table = DEPRECATED_MODELS.write().unwrap();
+    if table.contains_key(model) {
+        return;
+    }
+
+    warn!("检测到模型已弃用: {} (继任: {:?})", model, successor);
+    table.insert(
+        model.to_string(),
+        DeprecatedModel {
+            model: model.to_string(),
+            detected_at: Utc::now().to_rfc3339(),
+            status_code: status,
+            message: body.chars().take(500).collect(),
+            successor: successor.clone(),
+        },
+    );
+
+    events::emit(
+        "model_deprecated",
+        match &successor {
+            Some(s) => format!("模型 {} 已不可用，后续请求将改写为 {}", model, s),
+            None => format!("模型 {} 已不可用", model),
+        },
+        serde_json::json!({ "model": model, "successor": successor }),
+    );
+}
+
+/// 模型是否已被标记为弃用
+pub fn is_deprecated(model: &str) -> bool {
+    DEPRECATED_MODELS.read().unwrap().contains_key(model)
+}
+
+/// 将弃用模型改写为继任模型；未弃用或无继任时原样返回
+pub fn resolve_model(model: &str) -> String {
+    if !is_deprecated(model) {
+        return model.to_string();
+    }
+    get_config()
+        .model_successors
+        .get(model)
+        .cloned()
+        .unwrap_or_else(|| model.to_string())
+}
+
+/// 列出所有弃用模型
+pub fn list_deprecated_models() -> Vec<DeprecatedModel> {
+    DEPRECATED_MODELS
+        .read()
+        .unwrap()
+        .values()
+        .cloned()
+        .collect()
+}
+
+/// 清除弃用标记（上游恢复或误判时使用）
+pub fn clear_deprecation(model: &str) -> bool {
+    DEPRECATED_MODELS.write().unwrap().remove(model).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_model_unavailable_error() {
+        assert!(is_model_unavailable_error(
+            404,
+            r#"{"error":{"message":"Model claude-x not found"}}"#
+        ));
+        assert!(is_model_unavailable_error(
+            400,
+            r#"{"error":{"message":"The model has been deprecated"}}"#
+        ));
+        assert!(!is_model_unavailable_error(500, "model not found"));
+        assert!(!is_model_unavailable_error(404, "route not found"));
+    }
+
+    #[test]
+    fn test_record_and_clear() {
+        record_deprecation("claude-test-deprecated", 404, "model not found");
+        assert!(is_deprecated("claude-test-deprecated"));
+        assert_eq!(
+            resolve_model("claude-test-deprecated"),
+            "claude-test-deprecated"
+        );
+        assert!(clear_deprecation("claude-test-deprecated"));
+        assert!(!is_deprecated("claude-test-deprecated"));
+    }
+}
@@ -0,0 +1,295 @@
+//! 每日摘要与异常告警
+//!
+//! 每天在配置的时间发出 `daily_digest` 事件，汇总前一天的请求数、Token、
+//! 估算费用以及过期 / 出错的凭证；错误率在一小时内较基线暴涨时立即发出
+//! `error_rate_spike` 事件。宿主收到事件后转为系统通知。
+
+use crate::events;
+use crate::pricing::estimate_cost;
+use crate::provider;
+use crate::stats::UsageRecord;
+use crate::usage::load_records;
+use chrono::{DateTime, Duration, Local, NaiveDate, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use tracing::{debug, warn};
+
+/// 后台检查间隔（秒）
+const CHECK_INTERVAL_SECS: u64 = 300;
+/// 基线错误率下限，避免基线为 0 时任何错误都触发告警
+const MIN_BASELINE_ERROR_RATE: f64 = 0.01;
+
+/// 摘要配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DigestConfig {
+    pub enabled: bool,
+    /// 发送摘要的本地时间（小时）
+    pub hour: u32,
+    /// 最近一小时错误率达到基线的多少倍时告警
+    pub error_spike_factor: f64,
+    /// 最近一小时请求数少于该值时不判断暴涨
+    pub spike_min_requests: u64,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hour: 9,
+            error_spike_factor: 10.0,
+            spike_min_requests: 20,
+        }
+    }
+}
+
+/// 每日摘要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyDigest {
+    pub date: NaiveDate,
+    pub requests: u64,
+    pub failed_requests: u64,
+    /// 端点故障转移的请求数
+    #[serde(default)]
+    pub failover_requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub estimated_cost_usd: f64,
+    /// 当天有失败请求的凭证
+    pub errored_credentials: Vec<String>,
+    /// Token 已过期的凭证
+    pub expired_credentials: Vec<String>,
+}
+
+/// 错误率暴涨
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorSpike {
+    pub recent_requests: u64,
+    pub recent_error_rate: f64,
+    pub baseline_error_rate: f64,
+}
+
+fn timestamp(record: &UsageRecord) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&record.timestamp)
+        .ok()
+        .map(|ts| ts.with_timezone(&Utc))
+}
+
+/// 汇总某一天（UTC）的摘要
+pub fn build_digest(
+    records: &[UsageRecord],
+    date: NaiveDate,
+    expired_credentials: Vec<String>,
+) -> DailyDigest {
+    let mut digest = DailyDigest {
+        date,
+        requests: 0,
+        failed_requests: 0,
+        failover_requests: 0,
+        input_tokens: 0,
+        output_tokens: 0,
+        estimated_cost_usd: 0.0,
+        errored_credentials: Vec::new(),
+        expired_credentials,
+    };
+    let mut errored = BTreeSet::new();
+
+    for record in records {
+        if timestamp(record).map(|ts| ts.date_naive()) != Some(date) {
+            continue;
+        }
+        digest.requests += 1;
+        digest.input_tokens += record.input_tokens;
+        digest.output_tokens += record.output_tokens;
+        if let Some(ref model) = record.model {
+            digest.estimated_cost_usd +=
+                estimate_cost(model, record.input_tokens, record.output_tokens);
+        }
+        if record.failover_from.is_some() {
+            digest.failover_requests += 1;
+        }
+        if !record.success {
+            digest.failed_requests += 1;
+            errored.insert(record.credential_id.clone());
+        }
+    }
+
+    digest.errored_credentials = errored.into_iter().collect();
+    digest
+}
+
+/// 检查最近一小时的错误率是否较前 24 小时暴涨
+pub fn detect_error_spike(
+    records: &[UsageRecord],
+    now: DateTime<Utc>,
+    config: &DigestConfig,
+) -> Option<ErrorSpike> {
+    let recent_start = now - Duration::hours(1);
+    let baseline_start = recent_start - Duration::hours(24);
+
+    let (mut recent, mut recent_failed) = (0u64, 0u64);
+    let (mut baseline, mut baseline_failed) = (0u64, 0u64);
+    for record in records {
+        let Some(ts) = timestamp(record) else {
+            continue;
+        };
+        if ts > recent_start && ts <= now {
+            recent += 1;
+            recent_failed += u64::from(!record.success);
+        } else if ts > baseline_start && ts <= recent_start {
+            baseline += 1;
+            baseline_failed += u64::from(!record.success);
+        }
+    }
+
+    if recent < config.spike_min_requests {
+        return None;
+    }
+    let recent_error_rate = recent_failed as f64 / recent as f64;
+    let baseline_error_rate = if baseline == 0 {
+        0.0
+    } else {
+        baseline_failed as f64 / baseline as f64
+    };
+
+    let threshold = baseline_error_rate.max(MIN_BASELINE_ERROR_RATE) * config.error_spike_factor;
+    (recent_error_rate >= threshold).then_some(ErrorSpike {
+        recent_requests: recent,
+        recent_error_rate,
+        baseline_error_rate,
+    })
+}
+
+/// 生成某一天的摘要，未指定日期时为前一天
+pub async fn daily_digest(date: Option<NaiveDate>) -> anyhow::Result<DailyDigest> {
+    let date = date.unwrap_or_else(|| (Utc::now() - Duration::days(1)).date_naive());
+    let records = load_records()?;
+    Ok(build_digest(
+        &records,
+        date,
+        provider::expired_credentials().await,
+    ))
+}
+
+/// 后台任务：按时发送每日摘要并检查错误率暴涨
+pub async fn run_scheduler() {
+    let mut last_digest: Option<NaiveDate> = None;
+    let mut last_spike_alert: Option<DateTime<Utc>> = None;
+
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+        let config = crate::config::get_config().digest;
+        if !config.enabled {
+            continue;
+        }
+        crate::stats::flush().await;
+
+        let records = match load_records() {
+            Ok(records) => records,
+            Err(e) => {
+                warn!("读取使用记录失败，跳过摘要: {}", e);
+                continue;
+            }
+        };
+
+        let now = Utc::now();
+        let local = Local::now();
+        if local.hour() >= config.hour && last_digest != Some(local.date_naive()) {
+            last_digest = Some(local.date_naive());
+            let yesterday = (now - Duration::days(1)).date_naive();
+            let expired = provider::expired_credentials().await;
+            let digest = build_digest(&records, yesterday, expired);
+            events::emit(
+                "daily_digest",
+                format!(
+                    "{} 共 {} 次请求，失败 {} 次，估算费用 ${:.2}",
+                    digest.date, digest.requests, digest.failed_requests, digest.estimated_cost_usd
+                ),
+                serde_json::to_value(&digest).unwrap_or_default(),
+            );
+        }
+
+        let recently_alerted = last_spike_alert
+            .map(|at| now - at < Duration::hours(1))
+            .unwrap_or(false);
+        if recently_alerted {
+            continue;
+        }
+        if let Some(spike) = detect_error_spike(&records, now, &config) {
+            last_spike_alert = Some(now);
+            events::emit(
+                "error_rate_spike",
+                format!(
+                    "最近一小时错误率 {:.1}%，基线 {:.1}%",
+                    spike.recent_error_rate * 100.0,
+                    spike.baseline_error_rate * 100.0
+                ),
+                serde_json::to_value(&spike).unwrap_or_default(),
+            );
+        } else {
+            debug!("错误率正常");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: DateTime<Utc>, success: bool) -> UsageRecord {
+        UsageRecord {
+            timestamp: timestamp.to_rfc3339(),
+            credential_id: "cred".to_string(),
+            model: Some("claude-sonnet-4-20250514".to_string()),
+            endpoint_type: None,
+            client_name: None,
+            input_tokens: 1_000_000,
+            output_tokens: 0,
+            latency_ms: None,
+            failover_from: None,
+            tenant: None,
+            tags: Default::default(),
+            success,
+        }
+    }
+
+    #[test]
+    fn test_build_digest() {
+        let now = Utc::now();
+        let records = vec![
+            record(now, true),
+            record(now, false),
+            record(now - Duration::days(2), true),
+        ];
+
+        let digest = build_digest(&records, now.date_naive(), vec!["expired".to_string()]);
+        assert_eq!(digest.requests, 2);
+        assert_eq!(digest.failed_requests, 1);
+        assert_eq!(digest.errored_credentials, ["cred"]);
+        assert!((digest.estimated_cost_usd - 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_detect_error_spike() {
+        let now = Utc::now();
+        let config = DigestConfig {
+            spike_min_requests: 5,
+            ..Default::default()
+        };
+
+        // 基线：100 次请求 1 次失败；最近一小时 10 次中 5 次失败
+        let mut records: Vec<_> = (0..100)
+            .map(|i| record(now - Duration::hours(2), i != 0))
+            .collect();
+        records.extend((0..10).map(|i| record(now - Duration::minutes(10), i % 2 == 0)));
+        let spike = detect_error_spike(&records, now, &config).unwrap();
+        assert_eq!(spike.recent_requests, 10);
+
+        // 最近一小时错误率与基线持平
+        let steady: Vec<_> = (0..10)
+            .map(|_| record(now - Duration::minutes(10), true))
+            .collect();
+        assert!(detect_error_spike(&steady, now, &config).is_none());
+    }
+}
@@ -0,0 +1,380 @@
+//! 自检（doctor）
+//!
+//! 依次检查主密钥、凭证文件、WorkOS / Factory 端点连通性、本机时钟偏差、
+//! 代理设置和每个凭证的有效性，返回结构化报告，由 UI 或 CLI 渲染为检查清单。
+//! 凭证检查并发进行，单个凭证有超时，OAuth 凭证会刷新过期 Token 并发送
+//! 一次最小请求，确认 Token 确实被上游接受。
+
+use crate::auth::key_ring;
+use crate::auth::master_key;
+use crate::auth::workos::WORKOS_TOKEN_URL;
+use crate::config_check::{self, PROXY_ENV_VARS};
+use crate::credentials::{AuthType, ValidationResult};
+use crate::probe;
+use crate::provider::{self, CredentialSummary, FACTORY_API_BASE_URL};
+use crate::setup::StepStatus;
+use crate::store;
+use crate::store_lock::{self, StoreMode};
+use crate::tls_trust::{self, CertificatePinMismatch};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 连通性检查的超时
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(10);
+/// 单个凭证检查的超时（含刷新与测试请求）
+const CREDENTIAL_TIMEOUT: Duration = Duration::from_secs(45);
+/// 同时检查的凭证数
+const CREDENTIAL_CONCURRENCY: usize = 4;
+/// 时钟偏差告警 / 失败阈值（秒）
+const CLOCK_SKEW_WARN_SECS: i64 = 60;
+const CLOCK_SKEW_FAIL_SECS: i64 = 300;
+
+/// 单项检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoctorCheck {
+    /// 检查项，如 `encryption_key`、`network.workos`、`credential.<id>`
+    pub name: String,
+    pub status: StepStatus,
+    pub message: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+impl DoctorCheck {
+    fn new(name: impl Into<String>, status: StepStatus, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            message: message.into(),
+            data: serde_json::Value::Null,
+        }
+    }
+
+    fn with_data(mut self, data: serde_json::Value) -> Self {
+        self.data = data;
+        self
+    }
+}
+
+/// 自检报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoctorReport {
+    pub checks: Vec<DoctorCheck>,
+    /// 没有失败项
+    pub healthy: bool,
+    pub generated_at: String,
+}
+
+/// 主密钥可用且能完成加解密
+fn check_encryption_key() -> DoctorCheck {
+    if master_key::is_locked() {
+        return DoctorCheck::new(
+            "encryption_key",
+            StepStatus::Warning,
+            "主密钥受应用锁保护，解锁后才能加解密",
+        );
+    }
+    if let Some(error) = master_key::load_error() {
+        return DoctorCheck::new(
+            "encryption_key",
+            StepStatus::Failed,
+            format!("主密钥加载失败: {}", error),
+        );
+    }
+    let roundtrip = key_ring::encrypt("doctor").and_then(|c| key_ring::decrypt(&c));
+    match roundtrip {
+        Ok(plain) if plain == "doctor" => {
+            DoctorCheck::new("encryption_key", StepStatus::Ok, "主密钥可用")
+        }
+        Ok(_) => DoctorCheck::new("encryption_key", StepStatus::Failed, "加解密结果不一致"),
+        Err(e) => DoctorCheck::new("encryption_key", StepStatus::Failed, e.to_string()),
+    }
+}
+
+/// 凭证文件可读
+fn check_store() -> DoctorCheck {
+    let path = store::credentials_path();
+    let data = serde_json::json!({ "path": path.display().to_string() });
+    let check = match store::read_json::<serde_json::Value>(&path) {
+        Ok(Some(_)) => DoctorCheck::new("store", StepStatus::Ok, "凭证文件可读"),
+        Ok(None) => DoctorCheck::new("store", StepStatus::Warning, "凭证文件不存在"),
+        Err(e) => DoctorCheck::new(
+            "store",
+            StepStatus::Failed,
+            format!("凭证文件读取失败: {}", e),
+        ),
+    };
+    check.with_data(data)
+}
+
+/// 当前实例持有凭证存储锁
+fn check_store_lock() -> DoctorCheck {
+    let status = store_lock::status();
+    let data = serde_json::to_value(&status).unwrap_or_default();
+    let check = match status.mode {
+        StoreMode::Owner => DoctorCheck::new("store_lock", StepStatus::Ok, "已持有凭证存储锁"),
+        StoreMode::Unlocked => {
+            DoctorCheck::new("store_lock", StepStatus::Ok, "当前实例不写入凭证存储")
+        }
+        StoreMode::ReadOnly => DoctorCheck::new(
+            "store_lock",
+            StepStatus::Warning,
+            "凭证存储被其他实例占用，当前为只读模式",
+        ),
+    };
+    check.with_data(data)
+}
+
+/// 请求端点（任何 HTTP 响应都算可达），返回检查结果和服务端时间
+async fn check_endpoint(name: &str, url: &str) -> (DoctorCheck, Option<DateTime<Utc>>) {
+    let client = match crate::http::client_builder().and_then(|b| Ok(b.build()?)) {
+        Ok(client) => client,
+        Err(e) => {
+            return (
+                DoctorCheck::new(name, StepStatus::Failed, e.to_string()),
+                None,
+            )
+        }
+    };
+    let started = Instant::now();
+    match client.get(url).timeout(NETWORK_TIMEOUT).send().await {
+        Ok(response) => {
+            let latency_ms = started.elapsed().as_millis() as u64;
+            let server_time = response
+                .headers()
+                .get(reqwest::header::DATE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+                .map(|t| t.with_timezone(&Utc));
+            let check =
+                DoctorCheck::new(name, StepStatus::Ok, format!("可达（{} ms）", latency_ms))
+                    .with_data(serde_json::json!({
+                        "url": url,
+                        "status_code": response.status().as_u16(),
+                        "latency_ms": latency_ms,
+                    }));
+            (check, server_time)
+        }
+        Err(e) => {
+            let message = match tls_trust::send_error(e) {
+                e if e.is::<CertificatePinMismatch>() => e.to_string(),
+                e => format!("无法连接: {}", e),
+            };
+            let check = DoctorCheck::new(name, StepStatus::Failed, message)
+                .with_data(serde_json::json!({ "url": url }));
+            (check, None)
+        }
+    }
+}
+
+/// 按服务端 Date 头估算本机时钟偏差
+pub fn check_clock_skew(server_time: Option<DateTime<Utc>>, now: DateTime<Utc>) -> DoctorCheck {
+    let Some(server_time) = server_time else {
+        return DoctorCheck::new("clock_skew", StepStatus::Warning, "无法获取服务端时间");
+    };
+    let skew = (now - server_time).num_seconds();
+    let status = match skew.abs() {
+        s if s >= CLOCK_SKEW_FAIL_SECS => StepStatus::Failed,
+        s if s >= CLOCK_SKEW_WARN_SECS => StepStatus::Warning,
+        _ => StepStatus::Ok,
+    };
+    let message = match status {
+        StepStatus::Ok => "本机时间正常".to_string(),
+        _ => format!("本机时间与服务端相差 {} 秒，Token 过期判断可能出错", skew),
+    };
+    DoctorCheck::new("clock_skew", status, message)
+        .with_data(serde_json::json!({ "skew_secs": skew }))
+}
+
+/// 代理环境变量
+fn check_proxy() -> DoctorCheck {
+    let configured: Vec<_> = PROXY_ENV_VARS
+        .iter()
+        .filter_map(|var| {
+            std::env::var(var)
+                .ok()
+                .filter(|v| !v.is_empty())
+                .map(|v| (var.to_string(), v))
+        })
+        .collect();
+    let invalid: Vec<_> = config_check::check_environment()
+        .into_iter()
+        .filter(|f| f.field.starts_with("env."))
+        .map(|f| f.message)
+        .collect();
+
+    let variables: Vec<_> = configured.iter().map(|(var, _)| var).collect();
+    let data = serde_json::json!({ "variables": variables });
+    let check = if !invalid.is_empty() {
+        DoctorCheck::new("proxy", StepStatus::Failed, invalid.join("; "))
+    } else if configured.is_empty() {
+        DoctorCheck::new("proxy", StepStatus::Ok, "未配置代理，直接连接")
+    } else {
+        DoctorCheck::new(
+            "proxy",
+            StepStatus::Ok,
+            format!("使用代理 {}", configured[0].1),
+        )
+    };
+    check.with_data(data)
+}
+
+/// 把凭证验证结果转为检查项
+fn validation_check(name: &str, result: ValidationResult) -> DoctorCheck {
+    let status = if result.valid {
+        StepStatus::Ok
+    } else {
+        StepStatus::Failed
+    };
+    DoctorCheck::new(name, status, result.message.unwrap_or_default())
+        .with_data(serde_json::to_value(result.details).unwrap_or_default())
+}
+
+/// OAuth 凭证：过期则先刷新，再发送一次最小请求确认 Access Token 被接受
+async fn verify_oauth(summary: &CredentialSummary, needs_refresh: bool) -> DoctorCheck {
+    let name = format!("credential.{}", summary.id);
+    if needs_refresh {
+        if let Err(e) = provider::refresh_token(&summary.id).await {
+            return DoctorCheck::new(name, StepStatus::Failed, format!("Token 刷新失败: {}", e));
+        }
+    }
+    let acquired = match provider::authorize_credential(&summary.id, summary.endpoint_type).await {
+        Ok(acquired) => acquired,
+        Err(e) => return DoctorCheck::new(name, StepStatus::Failed, e.to_string()),
+    };
+    match probe::send_test_request(&acquired, summary.endpoint_type).await {
+        Ok((status, latency_ms)) => {
+            let data = serde_json::json!({
+                "status_code": status.as_u16(),
+                "latency_ms": latency_ms,
+                "refreshed": needs_refresh,
+            });
+            let (step_status, message) = if probe::status_indicates_support(status) {
+                (StepStatus::Ok, "Access Token 有效".to_string())
+            } else if status == reqwest::StatusCode::UNAUTHORIZED
+                || status == reqwest::StatusCode::FORBIDDEN
+            {
+                (
+                    StepStatus::Failed,
+                    format!("Access Token 被拒绝: {}", status),
+                )
+            } else {
+                (StepStatus::Warning, format!("测试请求返回 {}", status))
+            };
+            DoctorCheck::new(name, step_status, message).with_data(data)
+        }
+        Err(e) => DoctorCheck::new(name, StepStatus::Failed, format!("测试请求失败: {}", e)),
+    }
+}
+
+/// 验证单个凭证
+async fn check_credential(summary: CredentialSummary, needs_refresh: bool) -> DoctorCheck {
+    let name = format!("credential.{}", summary.id);
+    let check = match provider::validate_credential(&summary.id).await {
+        Ok(result) => validation_check(&name, result),
+        Err(e) => return DoctorCheck::new(name, StepStatus::Failed, e.to_string()),
+    };
+    if summary.auth_type != AuthType::OAuth || check.status == StepStatus::Failed {
+        return check;
+    }
+    verify_oauth(&summary, needs_refresh).await
+}
+
+/// 并发验证凭证（限制并发数，单个凭证超时记为失败）
+async fn check_credentials() -> Vec<DoctorCheck> {
+    let summaries = provider::list_credentials().await;
+    if summaries.is_empty() {
+        return vec![DoctorCheck::new(
+            "credentials",
+            StepStatus::Warning,
+            "没有任何凭证",
+        )];
+    }
+
+    let needs_refresh: HashSet<String> = provider::credentials_needing_refresh()
+        .await
+        .into_iter()
+        .collect();
+    let permits = Arc::new(tokio::sync::Semaphore::new(CREDENTIAL_CONCURRENCY));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (index, summary) in summaries.into_iter().enumerate() {
+        let permits = permits.clone();
+        let refresh = needs_refresh.contains(&summary.id);
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let name = format!("credential.{}", summary.id);
+            let check =
+                tokio::time::timeout(CREDENTIAL_TIMEOUT, check_credential(summary, refresh))
+                    .await
+                    .unwrap_or_else(|_| {
+                        DoctorCheck::new(
+                            name,
+                            StepStatus::Failed,
+                            format!("检查超时（{} 秒）", CREDENTIAL_TIMEOUT.as_secs()),
+                        )
+                    });
+            (index, check)
+        });
+    }
+
+    let mut checks = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok(check) => checks.push(check),
+            Err(e) => tracing::warn!("凭证检查任务异常: {}", e),
+        }
+    }
+    checks.sort_by_key(|(index, _)| *index);
+    checks.into_iter().map(|(_, check)| check).collect()
+}
+
+/// 运行全部检查
+pub async fn run() -> DoctorReport {
+    let mut checks = vec![
+        check_encryption_key(),
+        check_store(),
+        check_store_lock(),
+        check_proxy(),
+    ];
+
+    let ((workos, workos_time), (factory, factory_time)) = tokio::join!(
+        check_endpoint("network.workos", WORKOS_TOKEN_URL),
+        check_endpoint("network.factory", FACTORY_API_BASE_URL)
+    );
+    checks.push(workos);
+    checks.push(factory);
+    checks.push(check_clock_skew(factory_time.or(workos_time), Utc::now()));
+
+    checks.extend(check_credentials().await);
+
+    DoctorReport {
+        healthy: checks.iter().all(|c| c.status != StepStatus::Failed),
+        checks,
+        generated_at: Utc::now().to_rfc3339(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_clock_skew() {
+        let now = Utc::now();
+        assert_eq!(check_clock_skew(Some(now), now).status, StepStatus::Ok);
+
+        let check = check_clock_skew(Some(now - chrono::Duration::seconds(90)), now);
+        assert_eq!(check.status, StepStatus::Warning);
+        assert_eq!(check.data["skew_secs"], 90);
+
+        let ahead = now + chrono::Duration::minutes(10);
+        assert_eq!(
+            check_clock_skew(Some(ahead), now).status,
+            StepStatus::Failed
+        );
+        assert_eq!(check_clock_skew(None, now).status, StepStatus::Warning);
+    }
+}
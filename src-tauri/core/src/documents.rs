@@ -0,0 +1,394 @@
+//! 文档与图片内容块校验
+//!
+//! 在 `transform_request` 中检查 Anthropic 的 `document` / `image` 内容块：
+//! base64 数据的解码后大小、PDF 页数。超限时直接返回带修改建议的错误，
+//! 而不是等上游拒绝后再浪费一次重试。启用 `image-downscale` 特性并开启
+//! `downscale_images` 时，超大的 PNG / JPEG 会先在本地缩小再转发。
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+/// 解压对象流时最多读取的字节数（防止压缩炸弹）
+const MAX_OBJECT_STREAM_BYTES: u64 = 16 * 1024 * 1024;
+
+/// 文档限制配置（各项为 0 表示不限制）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocumentLimitConfig {
+    pub enabled: bool,
+    /// 单个 PDF 解码后大小上限（字节）
+    pub max_pdf_bytes: u64,
+    /// 单个 PDF 的页数上限
+    pub max_pdf_pages: u64,
+    /// 单张图片解码后大小上限（字节）
+    pub max_image_bytes: u64,
+    /// 超限图片在本地缩小（需要 `image-downscale` 特性）
+    pub downscale_images: bool,
+    /// 缩小后的最长边（像素）
+    pub downscale_max_dimension: u32,
+}
+
+impl Default for DocumentLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_pdf_bytes: 32 * 1024 * 1024,
+            max_pdf_pages: 100,
+            max_image_bytes: 5 * 1024 * 1024,
+            downscale_images: false,
+            downscale_max_dimension: 1568,
+        }
+    }
+}
+
+/// 校验失败
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentViolation {
+    /// 内容块位置，如 `messages[0].content[1]`
+    pub path: String,
+    /// pdf_too_large / pdf_too_many_pages / image_too_large / invalid_base64
+    pub kind: String,
+    pub limit: u64,
+    pub actual: u64,
+    pub message: String,
+    pub suggestion: String,
+}
+
+impl std::fmt::Display for DocumentViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for DocumentViolation {}
+
+/// base64 解码后的字节数（不实际解码）
+pub fn decoded_len(data: &str) -> u64 {
+    let trimmed = data.trim_end_matches('=');
+    (trimmed.len() as u64 * 3) / 4
+}
+
+lazy_static::lazy_static! {
+    static ref PAGE: regex::bytes::Regex =
+        regex::bytes::Regex::new(r"/Type\s*/Page[^s]").unwrap();
+    /// 不含嵌套或只有一层嵌套的字典
+    static ref DICT: regex::bytes::Regex =
+        regex::bytes::Regex::new(r"(?-u)<<(?:[^<>]|<<[^<>]*>>)*>>").unwrap();
+    static ref PAGES_TYPE: regex::bytes::Regex =
+        regex::bytes::Regex::new(r"/Type\s*/Pages\b").unwrap();
+    static ref COUNT: regex::bytes::Regex =
+        regex::bytes::Regex::new(r"/Count\s+(\d+)").unwrap();
+    /// 对象流：`obj <<.../Type /ObjStm...>> stream ... endstream`
+    static ref STREAM: regex::bytes::Regex =
+        regex::bytes::Regex::new(r"(?s-u)\bobj\b(.*?)\bstream\r?\n(.*?)\bendstream").unwrap();
+}
+
+/// 解压 PDF 中的对象流（PDF 1.5 起页树等对象可以放在压缩的对象流里）
+fn object_streams(pdf: &[u8]) -> Vec<Vec<u8>> {
+    STREAM
+        .captures_iter(pdf)
+        .filter(|caps| {
+            let dict = &caps[1];
+            dict.windows(7).any(|w| w == b"/ObjStm")
+                && dict.windows(12).any(|w| w == b"/FlateDecode")
+        })
+        .filter_map(|caps| {
+            let mut decoded = Vec::new();
+            flate2::read::ZlibDecoder::new(&caps[2])
+                .take(MAX_OBJECT_STREAM_BYTES)
+                .read_to_end(&mut decoded)
+                .ok()?;
+            Some(decoded)
+        })
+        .collect()
+}
+
+/// 页树根节点的 `/Count`（各 `/Type /Pages` 节点中最大的一个）
+fn page_tree_count(data: &[u8]) -> Option<u64> {
+    DICT.find_iter(data)
+        .map(|dict| dict.as_bytes())
+        .filter(|dict| PAGES_TYPE.is_match(dict))
+        .filter_map(|dict| {
+            let count = COUNT.captures(dict)?;
+            std::str::from_utf8(&count[1]).ok()?.parse().ok()
+        })
+        .max()
+}
+
+/// 统计 PDF 页数
+///
+/// 优先读取页树根节点的 `/Count`（包括压缩对象流中的页树）；找不到页树时
+/// 退回统计 `/Type /Page` 对象的个数，无法识别时为 0。
+pub fn count_pdf_pages(pdf: &[u8]) -> u64 {
+    let streams = object_streams(pdf);
+    let sections = || std::iter::once(pdf).chain(streams.iter().map(Vec::as_slice));
+    if let Some(count) = sections().filter_map(page_tree_count).max() {
+        return count;
+    }
+    sections()
+        .map(|data| PAGE.find_iter(data).count() as u64)
+        .sum()
+}
+
+fn violation(
+    path: &str,
+    kind: &str,
+    limit: u64,
+    actual: u64,
+    message: String,
+    suggestion: &str,
+) -> DocumentViolation {
+    DocumentViolation {
+        path: path.to_string(),
+        kind: kind.to_string(),
+        limit,
+        actual,
+        message,
+        suggestion: suggestion.to_string(),
+    }
+}
+
+/// 校验单个内容块，必要时缩小图片
+fn check_block(
+    config: &DocumentLimitConfig,
+    path: &str,
+    block: &mut serde_json::Value,
+) -> Result<(), DocumentViolation> {
+    let kind = block["type"].as_str().unwrap_or_default().to_string();
+    if block["source"]["type"] != "base64" || !matches!(kind.as_str(), "document" | "image") {
+        return Ok(());
+    }
+    let media_type = block["source"]["media_type"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let data = block["source"]["data"].as_str().unwrap_or_default();
+    let size = decoded_len(data);
+
+    if kind == "document" && media_type == "application/pdf" {
+        if config.max_pdf_bytes > 0 && size > config.max_pdf_bytes {
+            return Err(violation(
+                path,
+                "pdf_too_large",
+                config.max_pdf_bytes,
+                size,
+                format!(
+                    "PDF 大小 {} 字节超过上限 {} 字节",
+                    size, config.max_pdf_bytes
+                ),
+                "拆分为多个较小的 PDF，或只上传需要的页面",
+            ));
+        }
+        if config.max_pdf_pages > 0 {
+            let pdf = STANDARD.decode(data).map_err(|e| {
+                violation(
+                    path,
+                    "invalid_base64",
+                    0,
+                    0,
+                    format!("base64 无效: {}", e),
+                    "",
+                )
+            })?;
+            let pages = count_pdf_pages(&pdf);
+            if pages > config.max_pdf_pages {
+                return Err(violation(
+                    path,
+                    "pdf_too_many_pages",
+                    config.max_pdf_pages,
+                    pages,
+                    format!("PDF 共 {} 页，超过上限 {} 页", pages, config.max_pdf_pages),
+                    "按章节拆分 PDF 后分多次发送",
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    if kind == "image" && config.max_image_bytes > 0 && size > config.max_image_bytes {
+        if config.downscale_images {
+            if let Ok(smaller) = downscale(data, &media_type, config.downscale_max_dimension) {
+                if decoded_len(&smaller) <= config.max_image_bytes {
+                    block["source"]["data"] = serde_json::json!(smaller);
+                    return Ok(());
+                }
+            }
+        }
+        return Err(violation(
+            path,
+            "image_too_large",
+            config.max_image_bytes,
+            size,
+            format!(
+                "图片大小 {} 字节超过上限 {} 字节",
+                size, config.max_image_bytes
+            ),
+            "压缩或缩小图片后重试（长边 1568 像素以内即可）",
+        ));
+    }
+    Ok(())
+}
+
+/// 缩小 base64 图片，保持原格式
+#[cfg(feature = "image-downscale")]
+fn downscale(data: &str, media_type: &str, max_dimension: u32) -> anyhow::Result<String> {
+    let format = match media_type {
+        "image/png" => image::ImageFormat::Png,
+        "image/jpeg" => image::ImageFormat::Jpeg,
+        other => anyhow::bail!("不支持缩小 {} 格式", other),
+    };
+    let decoded = image::load_from_memory_with_format(&STANDARD.decode(data)?, format)?;
+    let resized = decoded.thumbnail(max_dimension, max_dimension);
+    let mut output = std::io::Cursor::new(Vec::new());
+    match format {
+        // JPEG 不支持透明通道
+        image::ImageFormat::Jpeg => {
+            image::DynamicImage::ImageRgb8(resized.to_rgb8()).write_to(&mut output, format)?
+        }
+        _ => resized.write_to(&mut output, format)?,
+    }
+    Ok(STANDARD.encode(output.into_inner()))
+}
+
+#[cfg(not(feature = "image-downscale"))]
+fn downscale(_data: &str, _media_type: &str, _max_dimension: u32) -> anyhow::Result<String> {
+    tracing::warn!("未启用 image-downscale 特性，无法缩小图片");
+    anyhow::bail!("未启用 image-downscale 特性")
+}
+
+/// 校验请求中所有消息（含 tool_result 内嵌）的文档与图片
+pub fn check_request(
+    config: &DocumentLimitConfig,
+    request: &mut serde_json::Value,
+) -> Result<(), DocumentViolation> {
+    if !config.enabled {
+        return Ok(());
+    }
+    let Some(messages) = request["messages"].as_array_mut() else {
+        return Ok(());
+    };
+    for (i, message) in messages.iter_mut().enumerate() {
+        let Some(blocks) = message["content"].as_array_mut() else {
+            continue;
+        };
+        for (j, block) in blocks.iter_mut().enumerate() {
+            let path = format!("messages[{}].content[{}]", i, j);
+            check_block(config, &path, block)?;
+            if let Some(nested) = block["content"].as_array_mut() {
+                for (k, inner) in nested.iter_mut().enumerate() {
+                    check_block(config, &format!("{}.content[{}]", path, k), inner)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(block: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({ "messages": [{ "role": "user", "content": [block] }] })
+    }
+
+    fn pdf_block(pages: usize) -> serde_json::Value {
+        let body: String = (0..pages)
+            .map(|i| format!("{} 0 obj << /Type /Page >>\n", i))
+            .collect();
+        let pdf = format!("%PDF-1.4\n1 0 obj << /Type /Pages >>\n{}%%EOF", body);
+        serde_json::json!({
+            "type": "document",
+            "source": {
+                "type": "base64",
+                "media_type": "application/pdf",
+                "data": STANDARD.encode(pdf),
+            },
+        })
+    }
+
+    #[test]
+    fn test_count_pdf_pages() {
+        // 页树根节点的 /Count 优先于逐个统计页对象
+        let plain = b"%PDF-1.4\n1 0 obj << /Type /Pages /Kids [2 0 R 3 0 R] /Count 12 >>\nendobj\n\
+            2 0 obj << /Type /Pages /Parent 1 0 R /Count 5 >>\nendobj\n\
+            4 0 obj << /Type /Page /Parent 2 0 R >>\nendobj\n%%EOF";
+        assert_eq!(count_pdf_pages(plain), 12);
+
+        // 页树在压缩对象流中
+        let objects = b"1 0 2 40 << /Type /Catalog /Pages 2 0 R >> \
+            << /Type /Pages /Kids [3 0 R] /Count 7 >> << /Type /Page /Parent 2 0 R >>";
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, objects).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut pdf = format!(
+            "%PDF-1.5\n5 0 obj << /Type /ObjStm /N 3 /First 8 /Filter /FlateDecode /Length {} >>\nstream\n",
+            compressed.len()
+        )
+        .into_bytes();
+        pdf.extend_from_slice(&compressed);
+        pdf.extend_from_slice(b"\nendstream\nendobj\n%%EOF");
+        assert_eq!(count_pdf_pages(&pdf), 7);
+
+        assert_eq!(count_pdf_pages(b"not a pdf"), 0);
+    }
+
+    #[test]
+    fn test_pdf_page_limit() {
+        let config = DocumentLimitConfig {
+            max_pdf_pages: 3,
+            ..Default::default()
+        };
+        assert!(check_request(&config, &mut request(pdf_block(3))).is_ok());
+
+        let err = check_request(&config, &mut request(pdf_block(4))).unwrap_err();
+        assert_eq!(err.kind, "pdf_too_many_pages");
+        assert_eq!(err.actual, 4);
+        assert_eq!(err.path, "messages[0].content[0]");
+    }
+
+    #[test]
+    fn test_image_size_limit_in_tool_result() {
+        let config = DocumentLimitConfig {
+            max_image_bytes: 16,
+            ..Default::default()
+        };
+        let image = serde_json::json!({
+            "type": "image",
+            "source": {
+                "type": "base64",
+                "media_type": "image/gif",
+                "data": STANDARD.encode([0u8; 64]),
+            },
+        });
+        let mut body = request(serde_json::json!({
+            "type": "tool_result",
+            "tool_use_id": "toolu_1",
+            "content": [image],
+        }));
+        let err = check_request(&config, &mut body).unwrap_err();
+        assert_eq!(err.kind, "image_too_large");
+        assert_eq!(err.path, "messages[0].content[0].content[0]");
+    }
+
+    #[cfg(feature = "image-downscale")]
+    #[test]
+    fn test_downscale_png() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::new_rgb8(200, 100)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let smaller = downscale(&STANDARD.encode(png.into_inner()), "image/png", 50).unwrap();
+        let decoded = image::load_from_memory(&STANDARD.decode(smaller).unwrap()).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (50, 25));
+    }
+
+    #[test]
+    fn test_decoded_len() {
+        assert_eq!(decoded_len(&STANDARD.encode([0u8; 10])), 10);
+        assert_eq!(decoded_len(&STANDARD.encode([0u8; 12])), 12);
+    }
+}
@@ -0,0 +1,168 @@
+//! 从环境变量和 .env 文件导入 API Key
+//!
+//! 扫描配置的环境变量以及 `.env` 文件（变量名在列表中、或值以 Factory Key
+//! 前缀开头的条目），按哈希与已保存的 Key 去重后新建或追加到 API Key 凭证。
+
+use crate::auth::encryption::hash_api_key;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// 导入配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvImportConfig {
+    /// 读取的环境变量名
+    pub variables: Vec<String>,
+    /// `.env` 文件路径
+    pub dotenv_path: Option<String>,
+    /// Factory API Key 前缀，`.env` 中值带此前缀的条目也会导入
+    pub key_prefix: String,
+}
+
+impl Default for EnvImportConfig {
+    fn default() -> Self {
+        Self {
+            variables: vec!["FACTORY_API_KEY".to_string(), "DROID_API_KEY".to_string()],
+            dotenv_path: None,
+            key_prefix: "fk-".to_string(),
+        }
+    }
+}
+
+/// 找到的 Key
+#[derive(Debug, Clone)]
+pub struct FoundKey {
+    /// 来源，如 `env:FACTORY_API_KEY`、`dotenv:FACTORY_API_KEY`
+    pub source: String,
+    pub key: String,
+}
+
+/// 导入结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvImportResult {
+    /// 写入的凭证，没有新 Key 时为空
+    pub credential_id: Option<String>,
+    /// 是否新建了凭证
+    pub created: bool,
+    /// 新增 Key 的来源
+    pub imported: Vec<String>,
+    /// 已存在而跳过的 Key 的来源
+    pub duplicates: Vec<String>,
+}
+
+/// 解析 `.env` 内容（支持注释、`export` 前缀和引号）
+pub fn parse_dotenv(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+                // 未加引号时去掉行尾注释
+                _ => value.split(" #").next().unwrap_or_default().trim(),
+            };
+            Some((name.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// 扫描环境变量和 `.env` 文件，同一个 Key 只保留首次出现
+pub fn scan(config: &EnvImportConfig, dotenv_path: Option<&Path>) -> Result<Vec<FoundKey>> {
+    let mut found = Vec::new();
+    for var in &config.variables {
+        if let Ok(key) = std::env::var(var) {
+            found.push(FoundKey {
+                source: format!("env:{}", var),
+                key,
+            });
+        }
+    }
+
+    let dotenv_path = dotenv_path.or(config.dotenv_path.as_deref().map(Path::new));
+    if let Some(path) = dotenv_path {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("读取 {} 失败: {}", path.display(), e))?;
+        for (name, value) in parse_dotenv(&content) {
+            let prefixed = !config.key_prefix.is_empty() && value.starts_with(&config.key_prefix);
+            if config.variables.contains(&name) || prefixed {
+                found.push(FoundKey {
+                    source: format!("dotenv:{}", name),
+                    key: value,
+                });
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    found.retain(|k| !k.key.trim().is_empty() && seen.insert(k.key.trim().to_string()));
+    for key in &mut found {
+        key.key = key.key.trim().to_string();
+    }
+    Ok(found)
+}
+
+/// 按已保存的哈希拆分为新 Key 和重复 Key
+pub fn partition_new(
+    found: Vec<FoundKey>,
+    stored_hashes: &HashSet<String>,
+) -> (Vec<FoundKey>, Vec<FoundKey>) {
+    found
+        .into_iter()
+        .partition(|k| !stored_hashes.contains(&hash_api_key(&k.key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dotenv() {
+        let content = r#"
+# Factory keys
+FACTORY_API_KEY="fk-one"
+export OTHER_KEY='fk-two'
+PLAIN=fk-three # 行尾注释
+EMPTY=
+"#;
+        let entries = parse_dotenv(content);
+        assert_eq!(
+            entries[0],
+            ("FACTORY_API_KEY".to_string(), "fk-one".to_string())
+        );
+        assert_eq!(entries[1], ("OTHER_KEY".to_string(), "fk-two".to_string()));
+        assert_eq!(entries[2], ("PLAIN".to_string(), "fk-three".to_string()));
+        assert_eq!(entries[3], ("EMPTY".to_string(), String::new()));
+    }
+
+    #[test]
+    fn test_scan_and_dedup() {
+        let dir = std::env::temp_dir().join(format!("droid-env-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".env");
+        std::fs::write(
+            &path,
+            "DROID_API_KEY=fk-one\nA=fk-two\nB=fk-one\nC=sk-other\n",
+        )
+        .unwrap();
+
+        let config = EnvImportConfig {
+            variables: vec!["DROID_API_KEY".to_string()],
+            ..Default::default()
+        };
+        let found = scan(&config, Some(&path)).unwrap();
+        let keys: Vec<_> = found.iter().map(|k| k.key.as_str()).collect();
+        assert_eq!(keys, vec!["fk-one", "fk-two"]);
+
+        let stored = HashSet::from([hash_api_key("fk-two")]);
+        let (new, duplicates) = partition_new(found, &stored);
+        assert_eq!(new[0].source, "dotenv:DROID_API_KEY");
+        assert_eq!(duplicates[0].key, "fk-two");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,53 @@
+//! Provider 事件队列
+//!
+//! 后端产生的通知（模型弃用、凭证状态变化等）先进入有界队列，
+//! 由宿主通过 `drain_events` 拉取后转发给 UI。
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tracing::debug;
+
+/// 队列最多保留的事件数
+const MAX_PENDING_EVENTS: usize = 256;
+
+/// Provider 事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderEvent {
+    /// 事件类型，如 `model_deprecated`
+    pub kind: String,
+    /// 人类可读的消息
+    pub message: String,
+    /// 附加数据
+    #[serde(default)]
+    pub data: serde_json::Value,
+    /// 事件时间 (RFC3339 格式)
+    pub timestamp: String,
+}
+
+lazy_static::lazy_static! {
+    static ref EVENTS: Mutex<VecDeque<ProviderEvent>> = Mutex::new(VecDeque::new());
+}
+
+/// 发出事件
+pub fn emit(kind: &str, message: impl Into<String>, data: serde_json::Value) {
+    let event = ProviderEvent {
+        kind: kind.to_string(),
+        message: message.into(),
+        data,
+        timestamp: Utc::now().to_rfc3339(),
+    };
+    debug!("事件: {} - {}", event.kind, event.message);
+
+    let mut events = EVENTS.lock().unwrap();
+    if events.len() >= MAX_PENDING_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(event);
+}
+
+/// 取出所有待处理事件
+pub fn drain_events() -> Vec<ProviderEvent> {
+    EVENTS.lock().unwrap().drain(..).collect()
+}
@@ -0,0 +1,666 @@
+//! 端点类型间的故障转移
+//!
+//! 同一凭证的 Anthropic 路径连续返回 5xx、而 Chat Completions 路径正常时，
+//! 路由改走 `/o/v1/chat/completions`。acquire 返回的 metadata 中带
+//! `failover_from`，宿主据此调用 `translate_request_to_chat` 转换请求格式，
+//! 并用 `translate_response_from_chat`（流式为 `translate_stream_chunk_from_chat`）
+//! 把响应转换回 Anthropic 格式。故障转移的请求在使用记录中单独标记，并发出
+//! `endpoint_failover` 事件。
+//!
+//! 切换后每隔 `probe_interval_secs` 放行一个请求回到 Anthropic 路径试探
+//! （半开）：成功则清零恢复，失败则继续改道到下一个间隔。
+
+use crate::credentials::{DroidCredentials, EndpointType};
+use crate::stop_sequences::{self, RequestFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 故障转移配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailoverConfig {
+    pub enabled: bool,
+    /// 连续多少次 5xx 后切换端点
+    pub failure_threshold: u32,
+    /// 切换后每隔多少秒放行一个请求试探原端点
+    pub probe_interval_secs: u64,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            failure_threshold: 3,
+            probe_interval_secs: 60,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    /// 凭证 ID → 下次允许试探 Anthropic 路径的时间（重启后立即可试探）
+    static ref NEXT_PROBE: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
+    /// 租约 ID → 流式响应转换状态
+    static ref STREAMS: Mutex<HashMap<String, ChatStreamTranslator>> = Mutex::new(HashMap::new());
+}
+
+/// 记录某个端点的请求结果（只统计 5xx，成功后清零）
+pub fn record_result(
+    credential_id: &str,
+    credential: &mut DroidCredentials,
+    endpoint: EndpointType,
+    success: bool,
+    status_code: Option<u16>,
+    config: &FailoverConfig,
+) {
+    if success {
+        credential.endpoint_failures.remove(&endpoint);
+        if endpoint == EndpointType::Anthropic {
+            NEXT_PROBE.lock().unwrap().remove(credential_id);
+        }
+    } else if status_code.is_some_and(|status| status >= 500) {
+        *credential.endpoint_failures.entry(endpoint).or_default() += 1;
+        if endpoint == EndpointType::Anthropic {
+            delay_probe(credential_id, config);
+        }
+    }
+}
+
+fn delay_probe(credential_id: &str, config: &FailoverConfig) {
+    let next = Instant::now() + Duration::from_secs(config.probe_interval_secs);
+    NEXT_PROBE
+        .lock()
+        .unwrap()
+        .insert(credential_id.to_string(), next);
+}
+
+/// Anthropic 路径是否已连续失败达到阈值
+fn tripped(credential: &DroidCredentials, config: &FailoverConfig) -> bool {
+    let failures = |endpoint| {
+        credential
+            .endpoint_failures
+            .get(&endpoint)
+            .copied()
+            .unwrap_or(0)
+    };
+    let supported = &credential.supported_endpoints;
+    let comm_available = supported.is_empty() || supported.contains(&EndpointType::Comm);
+
+    failures(EndpointType::Anthropic) >= config.failure_threshold
+        && failures(EndpointType::Comm) < config.failure_threshold
+        && comm_available
+}
+
+/// 需要故障转移时返回替代端点（到了试探时间则放行原端点）
+pub fn reroute(
+    credential_id: &str,
+    credential: &DroidCredentials,
+    endpoint: EndpointType,
+    config: &FailoverConfig,
+) -> Option<EndpointType> {
+    if !config.enabled || endpoint != EndpointType::Anthropic || !tripped(credential, config) {
+        return None;
+    }
+    let probe_due = NEXT_PROBE
+        .lock()
+        .unwrap()
+        .get(credential_id)
+        .is_none_or(|next| Instant::now() >= *next);
+    (!probe_due).then_some(EndpointType::Comm)
+}
+
+/// 已切换的凭证被分到原端点时占用本轮试探，间隔内的其他请求继续改道
+pub fn start_probe(
+    credential_id: &str,
+    credential: &DroidCredentials,
+    endpoint: EndpointType,
+    config: &FailoverConfig,
+) -> bool {
+    let probing =
+        config.enabled && endpoint == EndpointType::Anthropic && tripped(credential, config);
+    if probing {
+        delay_probe(credential_id, config);
+    }
+    probing
+}
+
+/// Anthropic 内容块转为 Chat Completions 文本 / 多模态片段
+fn chat_content(content: &serde_json::Value) -> serde_json::Value {
+    let Some(blocks) = content.as_array() else {
+        return content.clone();
+    };
+    let parts: Vec<serde_json::Value> = blocks
+        .iter()
+        .filter_map(|block| match block["type"].as_str() {
+            Some("text") => Some(serde_json::json!({ "type": "text", "text": block["text"] })),
+            Some("image") => {
+                let source = &block["source"];
+                let url = match source["type"].as_str() {
+                    Some("base64") => format!(
+                        "data:{};base64,{}",
+                        source["media_type"].as_str().unwrap_or("image/png"),
+                        source["data"].as_str().unwrap_or("")
+                    ),
+                    _ => source["url"].as_str().unwrap_or("").to_string(),
+                };
+                Some(serde_json::json!({ "type": "image_url", "image_url": { "url": url } }))
+            }
+            _ => None,
+        })
+        .collect();
+
+    // 纯文本合并为字符串，兼容只接受字符串的实现
+    if parts.iter().all(|p| p["type"] == "text") {
+        let text: Vec<&str> = parts.iter().filter_map(|p| p["text"].as_str()).collect();
+        return serde_json::json!(text.join("\n"));
+    }
+    serde_json::json!(parts)
+}
+
+/// 工具结果内容转为字符串
+fn tool_result_text(content: &serde_json::Value) -> String {
+    match content {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|b| b["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        other => other.to_string(),
+    }
+}
+
+/// Anthropic Messages 请求转换为 Chat Completions 请求
+pub fn anthropic_to_chat(request: &serde_json::Value) -> serde_json::Value {
+    let mut messages = Vec::new();
+
+    match &request["system"] {
+        serde_json::Value::Null => {}
+        system => messages.push(serde_json::json!({
+            "role": "system",
+            "content": chat_content(system),
+        })),
+    }
+
+    for message in request["messages"].as_array().into_iter().flatten() {
+        let role = message["role"].as_str().unwrap_or("user");
+        let blocks = message["content"].as_array();
+
+        // 工具结果在 Chat Completions 中是独立的 tool 消息
+        for block in blocks
+            .into_iter()
+            .flatten()
+            .filter(|b| b["type"] == "tool_result")
+        {
+            messages.push(serde_json::json!({
+                "role": "tool",
+                "tool_call_id": block["tool_use_id"],
+                "content": tool_result_text(&block["content"]),
+            }));
+        }
+
+        let tool_calls: Vec<serde_json::Value> = blocks
+            .into_iter()
+            .flatten()
+            .filter(|b| b["type"] == "tool_use")
+            .map(|block| {
+                serde_json::json!({
+                    "id": block["id"],
+                    "type": "function",
+                    "function": {
+                        "name": block["name"],
+                        "arguments": block["input"].to_string(),
+                    }
+                })
+            })
+            .collect();
+
+        let content = chat_content(&message["content"]);
+        let has_content = match &content {
+            serde_json::Value::String(text) => !text.is_empty(),
+            serde_json::Value::Array(parts) => !parts.is_empty(),
+            _ => false,
+        };
+        if !has_content && tool_calls.is_empty() {
+            continue;
+        }
+
+        let mut translated = serde_json::json!({ "role": role, "content": content });
+        if !tool_calls.is_empty() {
+            translated["tool_calls"] = serde_json::json!(tool_calls);
+        }
+        messages.push(translated);
+    }
+
+    let mut chat = serde_json::json!({
+        "model": request["model"],
+        "messages": messages,
+    });
+    for field in ["max_tokens", "temperature", "top_p", "stream"] {
+        if !request[field].is_null() {
+            chat[field] = request[field].clone();
+        }
+    }
+    if !request["stop_sequences"].is_null() {
+        chat["stop_sequences"] = request["stop_sequences"].clone();
+        stop_sequences::normalize(&mut chat, RequestFormat::Chat);
+    }
+    if request["stream"] == true {
+        chat["stream_options"] = serde_json::json!({ "include_usage": true });
+    }
+    if let Some(tools) = request["tools"].as_array() {
+        chat["tools"] = tools
+            .iter()
+            .map(|tool| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool["name"],
+                        "description": tool["description"],
+                        "parameters": tool["input_schema"],
+                    }
+                })
+            })
+            .collect();
+    }
+    chat
+}
+
+/// Chat Completions 结束原因转为 Anthropic stop_reason
+fn stop_reason(finish_reason: &serde_json::Value) -> serde_json::Value {
+    match finish_reason.as_str() {
+        Some("stop") => serde_json::json!("end_turn"),
+        Some("length") => serde_json::json!("max_tokens"),
+        Some("tool_calls") | Some("function_call") => serde_json::json!("tool_use"),
+        Some(other) => serde_json::json!(other),
+        None => serde_json::Value::Null,
+    }
+}
+
+/// Chat Completions 响应转换为 Anthropic Messages 响应
+pub fn chat_to_anthropic(response: &serde_json::Value) -> serde_json::Value {
+    let choice = &response["choices"][0];
+    let message = &choice["message"];
+
+    let mut content = Vec::new();
+    if let Some(text) = message["content"].as_str().filter(|t| !t.is_empty()) {
+        content.push(serde_json::json!({ "type": "text", "text": text }));
+    }
+    for call in message["tool_calls"].as_array().into_iter().flatten() {
+        let arguments = call["function"]["arguments"].as_str().unwrap_or("{}");
+        content.push(serde_json::json!({
+            "type": "tool_use",
+            "id": call["id"],
+            "name": call["function"]["name"],
+            "input": serde_json::from_str::<serde_json::Value>(arguments)
+                .unwrap_or_else(|_| serde_json::json!({})),
+        }));
+    }
+
+    serde_json::json!({
+        "id": response["id"],
+        "type": "message",
+        "role": "assistant",
+        "model": response["model"],
+        "content": content,
+        "stop_reason": stop_reason(&choice["finish_reason"]),
+        "usage": {
+            "input_tokens": response["usage"]["prompt_tokens"].as_u64().unwrap_or(0),
+            "output_tokens": response["usage"]["completion_tokens"].as_u64().unwrap_or(0),
+        }
+    })
+}
+
+/// 当前打开的内容块
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OpenBlock {
+    Text,
+    /// Chat Completions 中 tool_calls 的 index
+    Tool(u64),
+}
+
+/// Chat Completions 流式事件转换为 Anthropic Messages 流式事件
+#[derive(Debug, Default)]
+pub struct ChatStreamTranslator {
+    started: bool,
+    open: Option<OpenBlock>,
+    /// 下一个 Anthropic 内容块索引
+    next_index: usize,
+    stop_reason: Option<serde_json::Value>,
+    input_tokens: u64,
+    output_tokens: u64,
+    finished: bool,
+}
+
+impl ChatStreamTranslator {
+    /// 转换一个 Chat Completions 事件，返回对应的 Anthropic 事件（可能为空或多个）
+    pub fn translate(&mut self, chunk: &serde_json::Value) -> Vec<serde_json::Value> {
+        let mut events = Vec::new();
+        if self.finished {
+            return events;
+        }
+        if !self.started {
+            self.started = true;
+            events.push(serde_json::json!({
+                "type": "message_start",
+                "message": {
+                    "id": chunk["id"],
+                    "type": "message",
+                    "role": "assistant",
+                    "model": chunk["model"],
+                    "content": [],
+                    "stop_reason": null,
+                    "stop_sequence": null,
+                    "usage": { "input_tokens": 0, "output_tokens": 0 },
+                }
+            }));
+        }
+
+        let choice = &chunk["choices"][0];
+        let delta = &choice["delta"];
+        if let Some(text) = delta["content"].as_str().filter(|t| !t.is_empty()) {
+            self.open_block(
+                OpenBlock::Text,
+                &mut events,
+                || serde_json::json!({ "type": "text", "text": "" }),
+            );
+            events.push(self.delta(serde_json::json!({ "type": "text_delta", "text": text })));
+        }
+        for call in delta["tool_calls"].as_array().into_iter().flatten() {
+            let index = call["index"].as_u64().unwrap_or(0);
+            self.open_block(OpenBlock::Tool(index), &mut events, || {
+                serde_json::json!({
+                    "type": "tool_use",
+                    "id": call["id"],
+                    "name": call["function"]["name"],
+                    "input": {},
+                })
+            });
+            if let Some(arguments) = call["function"]["arguments"]
+                .as_str()
+                .filter(|a| !a.is_empty())
+            {
+                events.push(self.delta(serde_json::json!({
+                    "type": "input_json_delta",
+                    "partial_json": arguments,
+                })));
+            }
+        }
+        if !choice["finish_reason"].is_null() {
+            self.close_block(&mut events);
+            self.stop_reason = Some(stop_reason(&choice["finish_reason"]));
+        }
+        // include_usage 时用量在结束原因之后单独一个事件
+        if let Some(usage) = chunk["usage"].as_object() {
+            self.input_tokens = usage["prompt_tokens"].as_u64().unwrap_or(0);
+            self.output_tokens = usage["completion_tokens"].as_u64().unwrap_or(0);
+            if self.stop_reason.is_some() {
+                events.extend(self.finish());
+            }
+        }
+        events
+    }
+
+    /// 上游流结束（`[DONE]`）：补齐未发出的结束事件
+    pub fn finish(&mut self) -> Vec<serde_json::Value> {
+        let mut events = Vec::new();
+        if self.finished || !self.started {
+            return events;
+        }
+        self.finished = true;
+        self.close_block(&mut events);
+        events.push(serde_json::json!({
+            "type": "message_delta",
+            "delta": {
+                "stop_reason": self.stop_reason.clone().unwrap_or(serde_json::json!("end_turn")),
+                "stop_sequence": null,
+            },
+            "usage": {
+                "input_tokens": self.input_tokens,
+                "output_tokens": self.output_tokens,
+            }
+        }));
+        events.push(serde_json::json!({ "type": "message_stop" }));
+        events
+    }
+
+    fn open_block(
+        &mut self,
+        block: OpenBlock,
+        events: &mut Vec<serde_json::Value>,
+        content_block: impl FnOnce() -> serde_json::Value,
+    ) {
+        if self.open == Some(block) {
+            return;
+        }
+        self.close_block(events);
+        self.open = Some(block);
+        events.push(serde_json::json!({
+            "type": "content_block_start",
+            "index": self.next_index,
+            "content_block": content_block(),
+        }));
+    }
+
+    fn close_block(&mut self, events: &mut Vec<serde_json::Value>) {
+        if self.open.take().is_some() {
+            events.push(serde_json::json!({
+                "type": "content_block_stop",
+                "index": self.next_index,
+            }));
+            self.next_index += 1;
+        }
+    }
+
+    fn delta(&self, delta: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "type": "content_block_delta",
+            "index": self.next_index,
+            "delta": delta,
+        })
+    }
+}
+
+/// 按流（租约）转换 Chat Completions 流式事件；`done` 表示上游流已结束
+pub fn translate_stream_chunk(
+    stream_id: &str,
+    chunk: Option<&serde_json::Value>,
+    done: bool,
+) -> Vec<serde_json::Value> {
+    let mut streams = STREAMS.lock().unwrap();
+    let translator = streams.entry(stream_id.to_string()).or_default();
+    let mut events = chunk.map(|c| translator.translate(c)).unwrap_or_default();
+    if done {
+        events.extend(translator.finish());
+    }
+    if done || translator.finished {
+        streams.remove(stream_id);
+    }
+    events
+}
+
+/// 丢弃未结束的流转换状态（租约释放时调用）
+pub fn finish_stream(stream_id: &str) {
+    STREAMS.lock().unwrap().remove(stream_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reroute_after_threshold() {
+        let config = FailoverConfig {
+            enabled: true,
+            failure_threshold: 2,
+            probe_interval_secs: 60,
+        };
+        let id = "failover-threshold";
+        let mut credential = DroidCredentials::default();
+        let record = |credential: &mut DroidCredentials, success, status| {
+            record_result(
+                id,
+                credential,
+                EndpointType::Anthropic,
+                success,
+                status,
+                &config,
+            )
+        };
+        record(&mut credential, false, Some(502));
+        record(&mut credential, false, Some(429));
+        assert!(reroute(id, &credential, EndpointType::Anthropic, &config).is_none());
+
+        record(&mut credential, false, Some(503));
+        assert_eq!(
+            reroute(id, &credential, EndpointType::Anthropic, &config),
+            Some(EndpointType::Comm)
+        );
+
+        credential.supported_endpoints = vec![EndpointType::Anthropic];
+        assert!(reroute(id, &credential, EndpointType::Anthropic, &config).is_none());
+
+        record(&mut credential, true, None);
+        assert!(credential.endpoint_failures.is_empty());
+    }
+
+    #[test]
+    fn test_half_open_probe() {
+        let config = FailoverConfig {
+            enabled: true,
+            failure_threshold: 1,
+            probe_interval_secs: 0,
+        };
+        let id = "failover-probe";
+        let anthropic = EndpointType::Anthropic;
+        let mut credential = DroidCredentials::default();
+        credential.endpoint_failures.insert(anthropic, 1);
+
+        // 重启后没有记录：立即放行一次试探，之后的请求继续改道
+        let config_wait = FailoverConfig {
+            probe_interval_secs: 60,
+            ..config.clone()
+        };
+        assert!(reroute(id, &credential, anthropic, &config_wait).is_none());
+        assert!(start_probe(id, &credential, anthropic, &config_wait));
+        assert_eq!(
+            reroute(id, &credential, anthropic, &config_wait),
+            Some(EndpointType::Comm)
+        );
+
+        // 间隔到期后再次试探，试探成功即恢复
+        delay_probe(id, &config);
+        assert!(reroute(id, &credential, anthropic, &config).is_none());
+        record_result(id, &mut credential, anthropic, true, None, &config);
+        assert!(!start_probe(id, &credential, anthropic, &config));
+    }
+
+    #[test]
+    fn test_translate_stream() {
+        let chunks = [
+            serde_json::json!({ "id": "c1", "model": "m", "choices": [{ "delta": { "role": "assistant", "content": "Hi" } }] }),
+            serde_json::json!({ "choices": [{ "delta": { "content": " there" } }] }),
+            serde_json::json!({ "choices": [{ "delta": { "tool_calls": [
+                { "index": 0, "id": "t1", "function": { "name": "lookup", "arguments": "" } }
+            ] } }] }),
+            serde_json::json!({ "choices": [{ "delta": { "tool_calls": [
+                { "index": 0, "function": { "arguments": "{\"q\":1}" } }
+            ] } }] }),
+            serde_json::json!({ "choices": [{ "delta": {}, "finish_reason": "tool_calls" }] }),
+            serde_json::json!({ "choices": [], "usage": { "prompt_tokens": 3, "completion_tokens": 4 } }),
+        ];
+        let events: Vec<serde_json::Value> = chunks
+            .iter()
+            .flat_map(|c| translate_stream_chunk("failover-stream", Some(c), false))
+            .collect();
+        let types: Vec<&str> = events.iter().filter_map(|e| e["type"].as_str()).collect();
+        assert_eq!(
+            types,
+            [
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_delta",
+                "content_block_stop",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop",
+            ]
+        );
+        assert_eq!(events[0]["message"]["id"], "c1");
+        assert_eq!(events[5]["index"], 1);
+        assert_eq!(events[5]["content_block"]["name"], "lookup");
+        assert_eq!(events[6]["delta"]["partial_json"], "{\"q\":1}");
+        assert_eq!(events[8]["delta"]["stop_reason"], "tool_use");
+        assert_eq!(events[8]["usage"]["output_tokens"], 4);
+        assert!(!STREAMS.lock().unwrap().contains_key("failover-stream"));
+
+        // 没有用量事件时由 [DONE] 补齐结束事件
+        let chunk = serde_json::json!({ "choices": [{ "delta": { "content": "ok" }, "finish_reason": "stop" }] });
+        let events = translate_stream_chunk("failover-done", Some(&chunk), false);
+        assert_eq!(events.last().unwrap()["type"], "content_block_stop");
+        let events = translate_stream_chunk("failover-done", None, true);
+        assert_eq!(events[0]["delta"]["stop_reason"], "end_turn");
+        assert_eq!(events[1]["type"], "message_stop");
+    }
+
+    #[test]
+    fn test_translate_request() {
+        let request = serde_json::json!({
+            "model": "claude-sonnet-4-20250514",
+            "system": "be brief",
+            "max_tokens": 100,
+            "stop_sequences": ["END"],
+            "messages": [
+                { "role": "user", "content": "hi" },
+                { "role": "assistant", "content": [
+                    { "type": "tool_use", "id": "t1", "name": "lookup", "input": { "q": 1 } }
+                ]},
+                { "role": "user", "content": [
+                    { "type": "tool_result", "tool_use_id": "t1", "content": "42" }
+                ]}
+            ],
+            "tools": [{ "name": "lookup", "input_schema": { "type": "object" } }]
+        });
+        let chat = anthropic_to_chat(&request);
+        let messages = chat["messages"].as_array().unwrap();
+
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[1]["content"], "hi");
+        assert_eq!(
+            messages[2]["tool_calls"][0]["function"]["arguments"],
+            r#"{"q":1}"#
+        );
+        assert_eq!(messages[3]["role"], "tool");
+        assert_eq!(messages.len(), 4);
+        assert_eq!(chat["stop"][0], "END");
+        assert_eq!(chat["tools"][0]["function"]["name"], "lookup");
+    }
+
+    #[test]
+    fn test_translate_response() {
+        let response = serde_json::json!({
+            "id": "chatcmpl-1",
+            "model": "claude-sonnet-4-20250514",
+            "choices": [{
+                "message": {
+                    "content": "done",
+                    "tool_calls": [{
+                        "id": "t2",
+                        "function": { "name": "lookup", "arguments": "{\"q\":2}" }
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": { "prompt_tokens": 5, "completion_tokens": 7 }
+        });
+        let message = chat_to_anthropic(&response);
+        assert_eq!(message["content"][0]["text"], "done");
+        assert_eq!(message["content"][1]["input"]["q"], 2);
+        assert_eq!(message["stop_reason"], "tool_use");
+        assert_eq!(message["usage"]["output_tokens"], 7);
+    }
+}
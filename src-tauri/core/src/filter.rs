@@ -0,0 +1,199 @@
+//! 响应内容过滤
+//!
+//! 基于正则 / 关键词的后置过滤管线，在响应返回给下游工具之前执行，
+//! 便于自托管用户实施本地内容策略。
+
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// 过滤动作
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    /// 替换匹配内容
+    #[default]
+    Redact,
+    /// 拦截整个响应
+    Block,
+}
+
+/// 过滤规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterRule {
+    /// 正则表达式（关键词可直接作为字面量写入）
+    pub pattern: String,
+    /// 过滤动作
+    #[serde(default)]
+    pub action: FilterAction,
+    /// 替换文本（仅 redact 时使用）
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+}
+
+fn default_replacement() -> String {
+    "[FILTERED]".to_string()
+}
+
+/// 内容过滤配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContentFilterConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub rules: Vec<FilterRule>,
+}
+
+/// 编译后的过滤器
+///
+/// 正则只在构建中间件链时编译一次，链按配置缓存，配置变化时才重新编译。
+pub struct ContentFilter {
+    rules: Vec<(Regex, FilterRule)>,
+}
+
+/// 会被过滤的文本字段
+///
+/// 不包含工具参数的 `partial_json`：它是 JSON 片段，替换后下游无法拼出合法的参数。
+const TEXT_FIELDS: &[&str] = &["text", "content", "output_text"];
+
+impl ContentFilter {
+    /// 编译配置中的规则
+    pub fn compile(config: &ContentFilterConfig) -> Result<Self> {
+        let mut rules = Vec::new();
+        if config.enabled {
+            for rule in &config.rules {
+                let regex = Regex::new(&rule.pattern)
+                    .map_err(|e| anyhow::anyhow!("过滤规则无效 '{}': {}", rule.pattern, e))?;
+                rules.push((regex, rule.clone()));
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 过滤单段文本
+    pub fn apply_text(&self, text: &str) -> Result<String> {
+        let mut output = text.to_string();
+        for (regex, rule) in &self.rules {
+            if !regex.is_match(&output) {
+                continue;
+            }
+            match rule.action {
+                FilterAction::Block => {
+                    anyhow::bail!("响应被内容过滤规则拦截: {}", rule.pattern)
+                }
+                FilterAction::Redact => {
+                    output = regex
+                        .replace_all(&output, rule.replacement.as_str())
+                        .into_owned();
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// 过滤响应 JSON（完整响应或单个流式事件）
+    ///
+    /// 流式场景下规则只作用于单个事件内的文本，跨事件的匹配无法识别。
+    pub fn apply_json(&self, value: &mut serde_json::Value) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, child) in map.iter_mut() {
+                    match child {
+                        serde_json::Value::String(text) if TEXT_FIELDS.contains(&key.as_str()) => {
+                            *text = self.apply_text(text)?;
+                        }
+                        _ => self.apply_json(child)?,
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.apply_json(item)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(rules: Vec<FilterRule>) -> ContentFilter {
+        ContentFilter::compile(&ContentFilterConfig {
+            enabled: true,
+            rules,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_redact_nested_text() {
+        let filter = filter(vec![FilterRule {
+            pattern: r"sk-[a-z0-9]+".to_string(),
+            action: FilterAction::Redact,
+            replacement: default_replacement(),
+        }]);
+
+        let mut response = serde_json::json!({
+            "content": [{ "type": "text", "text": "key is sk-abc123" }],
+            "model": "sk-model"
+        });
+        filter.apply_json(&mut response).unwrap();
+        assert_eq!(response["content"][0]["text"], "key is [FILTERED]");
+        assert_eq!(response["model"], "sk-model");
+    }
+
+    #[test]
+    fn test_block_stream_delta() {
+        let filter = filter(vec![FilterRule {
+            pattern: "forbidden".to_string(),
+            action: FilterAction::Block,
+            replacement: default_replacement(),
+        }]);
+
+        let mut chunk = serde_json::json!({
+            "choices": [{ "delta": { "content": "a forbidden word" } }]
+        });
+        assert!(filter.apply_json(&mut chunk).is_err());
+    }
+
+    #[test]
+    fn test_skip_partial_json() {
+        let filter = filter(vec![FilterRule {
+            pattern: "secret".to_string(),
+            action: FilterAction::Redact,
+            replacement: default_replacement(),
+        }]);
+
+        let mut chunk = serde_json::json!({
+            "type": "content_block_delta",
+            "delta": { "type": "input_json_delta", "partial_json": "{\"q\": \"secret" }
+        });
+        filter.apply_json(&mut chunk).unwrap();
+        assert_eq!(chunk["delta"]["partial_json"], "{\"q\": \"secret");
+    }
+
+    #[test]
+    fn test_disabled_filter_is_empty() {
+        let config = ContentFilterConfig {
+            enabled: false,
+            rules: vec![FilterRule {
+                pattern: "(".to_string(),
+                action: FilterAction::Block,
+                replacement: default_replacement(),
+            }],
+        };
+        assert!(ContentFilter::compile(&config).unwrap().is_empty());
+    }
+}
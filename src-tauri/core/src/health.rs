@@ -0,0 +1,178 @@
+//! 凭证健康评分
+//!
+//! 以 0-100 的分数代替原先的 is_healthy 布尔值，综合最近请求的成功率、
+//! 平均延迟、Token 刷新可靠性和最近一小时的冷却次数计算。
+//! 选择凭证时按分数加权，UI 可据此显示渐变色而不只是红 / 绿。
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// 低于该分数的凭证不参与选择
+pub const MIN_HEALTH_SCORE: u8 = 20;
+
+/// 保留的最近请求数
+const REQUEST_WINDOW: usize = 50;
+/// 保留的最近刷新次数
+const REFRESH_WINDOW: usize = 10;
+/// 延迟满分 / 零分阈值（毫秒）
+const LATENCY_GOOD_MS: f64 = 2_000.0;
+const LATENCY_BAD_MS: f64 = 30_000.0;
+
+/// 单次请求样本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestSample {
+    pub success: bool,
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+}
+
+/// 健康统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HealthStats {
+    #[serde(default)]
+    pub recent_requests: VecDeque<RequestSample>,
+    #[serde(default)]
+    pub recent_refreshes: VecDeque<bool>,
+    /// 进入冷却的时间 (RFC3339 格式)
+    #[serde(default)]
+    pub cooldowns: VecDeque<String>,
+    /// 被调用方显式标记为不健康，成功请求或刷新后清除
+    #[serde(default)]
+    pub marked_unhealthy: bool,
+}
+
+impl HealthStats {
+    /// 记录请求结果
+    pub fn record_request(&mut self, success: bool, latency_ms: Option<u64>) {
+        if self.recent_requests.len() >= REQUEST_WINDOW {
+            self.recent_requests.pop_front();
+        }
+        self.recent_requests.push_back(RequestSample {
+            success,
+            latency_ms,
+        });
+        if success {
+            self.marked_unhealthy = false;
+        }
+    }
+
+    /// 记录 Token 刷新结果
+    pub fn record_refresh(&mut self, success: bool) {
+        if self.recent_refreshes.len() >= REFRESH_WINDOW {
+            self.recent_refreshes.pop_front();
+        }
+        self.recent_refreshes.push_back(success);
+        if success {
+            self.marked_unhealthy = false;
+        }
+    }
+
+    /// 记录一次冷却，并清理一小时前的记录
+    pub fn record_cooldown(&mut self, now: DateTime<Utc>) {
+        self.cooldowns.push_back(now.to_rfc3339());
+        let cutoff = now - Duration::hours(1);
+        self.cooldowns.retain(|ts| {
+            DateTime::parse_from_rfc3339(ts)
+                .map(|ts| ts > cutoff)
+                .unwrap_or(false)
+        });
+    }
+
+    /// 计算健康分数
+    ///
+    /// 成功率 50 分，延迟 20 分，刷新可靠性 15 分，冷却频率 15 分；
+    /// 没有样本的维度按满分计。
+    pub fn score(&self, now: DateTime<Utc>) -> u8 {
+        if self.marked_unhealthy {
+            return 0;
+        }
+
+        let ratio = |total: usize, ok: usize| {
+            if total == 0 {
+                1.0
+            } else {
+                ok as f64 / total as f64
+            }
+        };
+
+        let success = ratio(
+            self.recent_requests.len(),
+            self.recent_requests.iter().filter(|s| s.success).count(),
+        );
+
+        let latencies: Vec<f64> = self
+            .recent_requests
+            .iter()
+            .filter_map(|s| s.latency_ms)
+            .map(|ms| ms as f64)
+            .collect();
+        let latency = if latencies.is_empty() {
+            1.0
+        } else {
+            let avg = latencies.iter().sum::<f64>() / latencies.len() as f64;
+            ((LATENCY_BAD_MS - avg) / (LATENCY_BAD_MS - LATENCY_GOOD_MS)).clamp(0.0, 1.0)
+        };
+
+        let refresh = ratio(
+            self.recent_refreshes.len(),
+            self.recent_refreshes.iter().filter(|ok| **ok).count(),
+        );
+
+        let cutoff = now - Duration::hours(1);
+        let recent_cooldowns = self
+            .cooldowns
+            .iter()
+            .filter_map(|ts| DateTime::parse_from_rfc3339(ts).ok())
+            .filter(|ts| *ts > cutoff)
+            .count();
+        let cooldown = (1.0 - recent_cooldowns as f64 / 3.0).max(0.0);
+
+        (success * 50.0 + latency * 20.0 + refresh * 15.0 + cooldown * 15.0).round() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fresh_credential_scores_full() {
+        assert_eq!(HealthStats::default().score(Utc::now()), 100);
+    }
+
+    #[test]
+    fn test_score_components() {
+        let now = Utc::now();
+        let mut stats = HealthStats::default();
+        stats.record_request(true, Some(1_000));
+        stats.record_request(false, Some(1_000));
+        // 成功率 50% -> 25 分，其余满分
+        assert_eq!(stats.score(now), 75);
+
+        stats.record_refresh(false);
+        stats.record_cooldown(now);
+        assert_eq!(stats.score(now), 25 + 20 + 10);
+    }
+
+    #[test]
+    fn test_marked_unhealthy_until_success() {
+        let mut stats = HealthStats {
+            marked_unhealthy: true,
+            ..Default::default()
+        };
+        assert_eq!(stats.score(Utc::now()), 0);
+
+        stats.record_refresh(true);
+        assert_eq!(stats.score(Utc::now()), 100);
+    }
+
+    #[test]
+    fn test_window_is_bounded() {
+        let mut stats = HealthStats::default();
+        for _ in 0..(REQUEST_WINDOW + 10) {
+            stats.record_request(true, None);
+        }
+        assert_eq!(stats.recent_requests.len(), REQUEST_WINDOW);
+    }
+}
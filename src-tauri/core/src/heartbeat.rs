@@ -0,0 +1,123 @@
+//! 流式响应心跳
+//!
+//! 上游长时间“思考”时可能几十秒不输出任何字节，部分本地客户端会因读取超时
+//! 断开。开启后 acquire 在 metadata 的 `sse_heartbeat` 中返回心跳间隔与要写入的
+//! 原始字节，宿主在上游静默超过间隔时把它写给本地客户端（不经过
+//! `transform_stream_chunk`，也不重置上游的流式空闲超时）。
+//! 租户可单独覆盖全局设置，例如只为某个慢客户端的虚拟密钥开启。
+
+use crate::credentials::EndpointType;
+use crate::tenants::Tenant;
+use serde::{Deserialize, Serialize};
+
+/// 心跳形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeartbeatStyle {
+    /// SSE 注释行（`: keep-alive`），符合规范的解析器会忽略
+    #[default]
+    Comment,
+    /// Anthropic 的 `ping` 事件（只对 Anthropic 端点生效，其他端点退回注释行）
+    Ping,
+}
+
+/// 心跳配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    pub enabled: bool,
+    /// 上游静默多久后发送一次心跳（毫秒）
+    pub interval_ms: u64,
+    pub style: HeartbeatStyle,
+    /// 注释行内容
+    pub comment: String,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: 15_000,
+            style: HeartbeatStyle::Comment,
+            comment: "keep-alive".to_string(),
+        }
+    }
+}
+
+/// 交给宿主执行的心跳设置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeartbeatPlan {
+    pub interval_ms: u64,
+    /// 每次写给客户端的原始字节
+    pub chunk: String,
+}
+
+impl HeartbeatConfig {
+    /// 按端点生成心跳设置，未开启时返回 None
+    pub fn plan(&self, endpoint_type: EndpointType) -> Option<HeartbeatPlan> {
+        if !self.enabled || self.interval_ms == 0 {
+            return None;
+        }
+        let chunk = match (self.style, endpoint_type) {
+            (HeartbeatStyle::Ping, EndpointType::Anthropic) => {
+                "event: ping\ndata: {\"type\": \"ping\"}\n\n".to_string()
+            }
+            // 注释内容不能含换行，否则后续行会被当作字段解析
+            _ => format!(": {}\n\n", self.comment.replace(['\r', '\n'], " ")),
+        };
+        Some(HeartbeatPlan {
+            interval_ms: self.interval_ms,
+            chunk,
+        })
+    }
+}
+
+/// 租户设置优先于全局设置
+pub fn resolve<'a>(config: &'a HeartbeatConfig, tenant: Option<&'a Tenant>) -> &'a HeartbeatConfig {
+    tenant.and_then(|t| t.heartbeat.as_ref()).unwrap_or(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        let mut config = HeartbeatConfig::default();
+        assert!(config.plan(EndpointType::Anthropic).is_none());
+
+        config.enabled = true;
+        config.comment = "still\nthinking".to_string();
+        let plan = config.plan(EndpointType::OpenAI).unwrap();
+        assert_eq!(plan.chunk, ": still thinking\n\n");
+        assert_eq!(plan.interval_ms, 15_000);
+
+        config.style = HeartbeatStyle::Ping;
+        assert!(config
+            .plan(EndpointType::Anthropic)
+            .unwrap()
+            .chunk
+            .starts_with("event: ping"));
+        assert!(config
+            .plan(EndpointType::Comm)
+            .unwrap()
+            .chunk
+            .starts_with(": "));
+    }
+
+    #[test]
+    fn test_tenant_override() {
+        let global = HeartbeatConfig::default();
+        let tenant = Tenant {
+            id: "slow-client".to_string(),
+            heartbeat: Some(HeartbeatConfig {
+                enabled: true,
+                interval_ms: 5_000,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(resolve(&global, Some(&tenant)).interval_ms, 5_000);
+        assert!(!resolve(&global, None).enabled);
+    }
+}
@@ -0,0 +1,598 @@
+//! 用户钩子（实验性）
+//!
+//! 内置策略表达不了的规则（按标签挑凭证、按内容改写请求等）可以写成
+//! WebAssembly 模块，修改配置即可生效，不必重新编译插件。模块在沙箱中运行：
+//! 不提供 WASI 等任何系统接口，执行步数受燃料（`fuel`）限制，线性内存受
+//! `max_memory_bytes` 限制，每次调用都在新实例中执行，不保留状态。
+//!
+//! 模块约定（输入输出均为 UTF-8 JSON）：
+//! - 导出 `memory` 与 `alloc(len: i32) -> i32`，插件分配后写入输入
+//! - 钩子函数签名为 `(ptr: i32, len: i32) -> i64`，返回 0 表示不修改，
+//!   否则高 32 位为输出地址、低 32 位为输出长度
+//! - `on_request` / `on_response` / `on_stream_chunk` 返回替换后的请求或响应，
+//!   在 `middleware` 中以 `hook:<名称>` 引用；没有 `on_stream_chunk` 时流事件
+//!   交给 `on_response`
+//! - `select` 收到模型与候选凭证，返回 `{"credential_id": ...}` 指定凭证，
+//!   或 `{"weights": {"<凭证 ID>": 权重}}` 调整权重（0 表示排除）。`select`
+//!   在后台线程中执行，不持有凭证池与租约的锁，超过 `timeout_ms` 视为失败
+//! - 可导入 `env.log(ptr: i32, len: i32)` 输出调试日志
+
+use crate::body_text;
+use crate::config::ProviderConfig;
+use crate::middleware::RequestMiddleware;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, warn};
+use wasmi::{Caller, Engine, Extern, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+/// 中间件列表中引用钩子的前缀
+pub const MIDDLEWARE_PREFIX: &str = "hook:";
+
+/// 模块可导出的钩子函数
+pub const HOOK_EXPORTS: &[&str] = &["on_request", "on_response", "on_stream_chunk", "select"];
+
+/// 钩子配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// 实验性功能，默认关闭；关闭时中间件中引用的钩子不执行
+    pub enabled: bool,
+    /// 钩子模块，`select` 按列表顺序执行，第一个给出决定的生效
+    pub modules: Vec<HookModule>,
+    /// 每次调用的燃料上限（约等于执行的指令数）
+    pub fuel: u64,
+    /// 每个实例的线性内存上限（字节）
+    pub max_memory_bytes: u64,
+    /// `select` 的等待上限（毫秒）；超时后不再等待结果，钩子仍会在燃料耗尽前结束
+    pub timeout_ms: u64,
+    /// 钩子失败（燃料耗尽、内存超限、输出无效）时跳过钩子继续处理，关闭时请求失败
+    pub fail_open: bool,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            modules: Vec::new(),
+            fuel: 100_000_000,
+            max_memory_bytes: 16 * 1024 * 1024,
+            timeout_ms: 1000,
+            fail_open: true,
+        }
+    }
+}
+
+/// 钩子模块
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookModule {
+    pub name: String,
+    /// `.wasm` 文件路径，文件修改后下次调用自动重新加载
+    pub path: String,
+}
+
+/// 钩子执行统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HookStats {
+    pub calls: u64,
+    pub failures: u64,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// 最近一次成功调用消耗的燃料
+    pub last_fuel_used: u64,
+}
+
+/// 钩子状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookStatus {
+    pub name: String,
+    pub path: String,
+    /// 模块导出的钩子函数，加载失败时为空
+    pub exports: Vec<String>,
+    #[serde(default)]
+    pub load_error: Option<String>,
+    pub stats: HookStats,
+}
+
+/// 两次检查模块文件是否修改的最小间隔
+const MODULE_RECHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 缓存的模块
+struct CachedModule {
+    /// 编译时的文件修改时间
+    modified: Option<SystemTime>,
+    /// 最近一次检查文件修改时间的时刻
+    checked_at: Instant,
+    module: Arc<Module>,
+}
+
+lazy_static::lazy_static! {
+    static ref ENGINE: Engine = {
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(true);
+        Engine::new(&config)
+    };
+    /// 已编译的模块（按路径，文件修改时间变化后重新编译）
+    static ref MODULES: Mutex<HashMap<String, CachedModule>> = Mutex::new(HashMap::new());
+    static ref STATS: Mutex<BTreeMap<String, HookStats>> = Mutex::new(BTreeMap::new());
+}
+
+/// 编译模块
+fn compile(wasm: &[u8]) -> Result<Module> {
+    Module::new(&ENGINE, wasm).map_err(|e| anyhow::anyhow!("钩子模块无效: {}", e))
+}
+
+/// 加载模块（带缓存，每隔 `MODULE_RECHECK_INTERVAL` 才检查一次文件是否修改）
+fn load(path: &str) -> Result<Arc<Module>> {
+    let mut modules = MODULES.lock().unwrap();
+    if let Some(cached) = modules.get_mut(path) {
+        if cached.checked_at.elapsed() < MODULE_RECHECK_INTERVAL {
+            return Ok(cached.module.clone());
+        }
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        if cached.modified == modified {
+            cached.checked_at = Instant::now();
+            return Ok(cached.module.clone());
+        }
+    }
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let wasm = std::fs::read(path).with_context(|| format!("无法读取钩子模块 {}", path))?;
+    let module = Arc::new(compile(&wasm).with_context(|| path.to_string())?);
+    debug!("钩子模块已加载: {}", path);
+    modules.insert(
+        path.to_string(),
+        CachedModule {
+            modified,
+            checked_at: Instant::now(),
+            module: module.clone(),
+        },
+    );
+    Ok(module)
+}
+
+/// 模块导出的钩子函数
+fn exports(module: &Module) -> Vec<String> {
+    HOOK_EXPORTS
+        .iter()
+        .filter(|name| module.exports().any(|e| e.name() == **name))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// 实际调用的导出函数（流事件可回退到 `on_response`）
+fn resolve_export(module: &Module, export: &str) -> Option<&'static str> {
+    let has = |name: &str| module.exports().any(|e| e.name() == name);
+    let fallback = match export {
+        "on_stream_chunk" => Some("on_response"),
+        _ => None,
+    };
+    HOOK_EXPORTS
+        .iter()
+        .copied()
+        .find(|name| *name == export)
+        .filter(|name| has(name))
+        .or_else(|| fallback.filter(|name| has(name)))
+}
+
+/// 读取实例内存中的一段数据
+fn read_memory(memory: &[u8], ptr: u32, len: u32) -> Option<&[u8]> {
+    memory.get(ptr as usize..(ptr as usize).checked_add(len as usize)?)
+}
+
+/// 在新实例中执行一次钩子函数，返回输出与消耗的燃料
+fn invoke(
+    config: &HooksConfig,
+    module: &Module,
+    export: &str,
+    input: &[u8],
+) -> Result<(Option<Vec<u8>>, u64)> {
+    let limits = StoreLimitsBuilder::new()
+        .memory_size(config.max_memory_bytes as usize)
+        .instances(1)
+        .trap_on_grow_failure(true)
+        .build();
+    let mut store = Store::new(&ENGINE, limits);
+    store.limiter(|limits| limits);
+    store
+        .set_fuel(config.fuel)
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    let mut linker = Linker::<StoreLimits>::new(&ENGINE);
+    linker.func_wrap(
+        "env",
+        "log",
+        |caller: Caller<'_, StoreLimits>, ptr: u32, len: u32| {
+            let memory = caller.get_export("memory").and_then(Extern::into_memory);
+            if let Some(text) = memory.and_then(|m| read_memory(m.data(&caller), ptr, len)) {
+                debug!("钩子日志: {}", body_text::decode(text));
+            }
+        },
+    )?;
+    let instance = linker.instantiate(&mut store, module)?.start(&mut store)?;
+    let memory = instance
+        .get_memory(&store, "memory")
+        .context("钩子模块未导出 memory")?;
+    let alloc = instance
+        .get_typed_func::<u32, u32>(&store, "alloc")
+        .context("钩子模块未导出 alloc(len: i32) -> i32")?;
+    let hook = instance.get_typed_func::<(u32, u32), u64>(&store, export)?;
+
+    let len = u32::try_from(input.len()).context("钩子输入过大")?;
+    let ptr = alloc.call(&mut store, len)?;
+    memory
+        .write(&mut store, ptr as usize, input)
+        .map_err(|e| anyhow::anyhow!("写入钩子输入失败: {}", e))?;
+    let packed = hook.call(&mut store, (ptr, len))?;
+    let fuel_used = config.fuel.saturating_sub(store.get_fuel().unwrap_or(0));
+    if packed == 0 {
+        return Ok((None, fuel_used));
+    }
+    let output = read_memory(memory.data(&store), (packed >> 32) as u32, packed as u32)
+        .context("钩子输出越界")?;
+    Ok((Some(output.to_vec()), fuel_used))
+}
+
+/// 执行钩子函数并记录统计；模块没有该导出时返回 None
+fn call(
+    config: &HooksConfig,
+    hook: &HookModule,
+    export: &str,
+    input: &serde_json::Value,
+) -> Result<Option<serde_json::Value>> {
+    let module = load(&hook.path)?;
+    let Some(export) = resolve_export(&module, export) else {
+        return Ok(None);
+    };
+    let result = invoke(config, &module, export, &serde_json::to_vec(input)?).and_then(
+        |(output, fuel_used)| {
+            let output = match output {
+                Some(output) => Some(body_text::parse_json(&output).context("钩子输出不是 JSON")?),
+                None => None,
+            };
+            Ok((output, fuel_used))
+        },
+    );
+
+    let mut stats = STATS.lock().unwrap();
+    let stats = stats.entry(hook.name.clone()).or_default();
+    stats.calls += 1;
+    match result {
+        Ok((output, fuel_used)) => {
+            stats.last_fuel_used = fuel_used;
+            Ok(output)
+        }
+        Err(e) => {
+            stats.failures += 1;
+            stats.last_error = Some(format!("{:#}", e));
+            Err(e)
+        }
+    }
+}
+
+/// 中间件引用的钩子
+struct HookMiddleware {
+    name: String,
+    hook: HookModule,
+    config: HooksConfig,
+}
+
+impl HookMiddleware {
+    fn apply(&self, export: &str, value: &mut serde_json::Value) -> Result<()> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        match call(&self.config, &self.hook, export, value) {
+            Ok(Some(output)) => *value = output,
+            Ok(None) => {}
+            Err(e) if self.config.fail_open => {
+                warn!(
+                    "钩子 {} 的 {} 执行失败，已跳过: {:#}",
+                    self.hook.name, export, e
+                )
+            }
+            Err(e) => return Err(e.context(format!("钩子 {} 执行失败", self.hook.name))),
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RequestMiddleware for HookMiddleware {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn on_request(&self, request: &mut serde_json::Value) -> Result<()> {
+        self.apply("on_request", request)
+    }
+
+    async fn on_response(&self, response: &mut serde_json::Value) -> Result<()> {
+        self.apply("on_response", response)
+    }
+
+    async fn on_stream_chunk(&self, chunk: &mut serde_json::Value) -> Result<()> {
+        self.apply("on_stream_chunk", chunk)
+    }
+}
+
+/// 按 `hook:<名称>` 创建中间件，钩子未配置时返回错误
+pub fn middleware(config: &ProviderConfig, name: &str) -> Result<Arc<dyn RequestMiddleware>> {
+    let hook_name = name.strip_prefix(MIDDLEWARE_PREFIX).unwrap_or(name);
+    let hook = config
+        .hooks
+        .modules
+        .iter()
+        .find(|m| m.name == hook_name)
+        .ok_or_else(|| anyhow::anyhow!("未知的钩子: {}", hook_name))?;
+    Ok(Arc::new(HookMiddleware {
+        name: name.to_string(),
+        hook: hook.clone(),
+        config: config.hooks.clone(),
+    }))
+}
+
+/// 选择钩子的决定
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SelectDecision {
+    /// 钩子名称
+    #[serde(skip)]
+    pub hook: String,
+    pub credential_id: Option<String>,
+    pub weights: HashMap<String, f64>,
+}
+
+/// 是否有可能给出选择决定的钩子
+pub fn has_select(config: &HooksConfig) -> bool {
+    config.enabled && !config.modules.is_empty()
+}
+
+/// 依次执行导出 `select` 的钩子，返回第一个给出决定的钩子的决定
+fn decide_blocking(
+    config: &HooksConfig,
+    ids: &[String],
+    input: &serde_json::Value,
+) -> Result<Option<SelectDecision>> {
+    for hook in &config.modules {
+        let decision = call(config, hook, "select", input).and_then(|output| {
+            output
+                .filter(|o| !o.is_null())
+                .map(|o| serde_json::from_value::<SelectDecision>(o).context("select 输出格式无效"))
+                .transpose()
+        });
+        let mut decision = match decision {
+            Ok(Some(decision)) => decision,
+            Ok(None) => continue,
+            Err(e) if config.fail_open => {
+                warn!("钩子 {} 的 select 执行失败，已跳过: {:#}", hook.name, e);
+                continue;
+            }
+            Err(e) => return Err(e.context(format!("钩子 {} 执行失败", hook.name))),
+        };
+        if let Some(id) = &decision.credential_id {
+            if !ids.contains(id) {
+                warn!("钩子 {} 指定的凭证 {} 不在候选中，已忽略", hook.name, id);
+                decision.credential_id = None;
+            }
+        }
+        if decision.credential_id.is_none() && decision.weights.is_empty() {
+            continue;
+        }
+        decision.hook = hook.name.clone();
+        return Ok(Some(decision));
+    }
+    Ok(None)
+}
+
+/// 在后台线程中执行 `select` 钩子（调用方不应持有凭证池与租约的锁）
+///
+/// `ids` 为候选凭证，`input` 为传给钩子的输入；超过 `timeout_ms` 时按
+/// `fail_open` 跳过或失败。
+pub async fn decide(
+    config: &HooksConfig,
+    ids: Vec<String>,
+    input: serde_json::Value,
+) -> Result<Option<SelectDecision>> {
+    if !has_select(config) {
+        return Ok(None);
+    }
+    let timeout = Duration::from_millis(config.timeout_ms);
+    let worker_config = config.clone();
+    let task = tokio::task::spawn_blocking(move || decide_blocking(&worker_config, &ids, &input));
+    match tokio::time::timeout(timeout, task).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(anyhow::anyhow!("select 钩子异常退出: {}", e)),
+        Err(_) if config.fail_open => {
+            warn!("select 钩子超过 {} 毫秒未返回，已跳过", config.timeout_ms);
+            Ok(None)
+        }
+        Err(_) => anyhow::bail!("select 钩子超过 {} 毫秒未返回", config.timeout_ms),
+    }
+}
+
+/// 把钩子的决定应用到当前候选：指定凭证时返回其在 `ids` 中的下标，调整
+/// 权重时直接修改 `weights`
+///
+/// 钩子执行期间候选可能已变化，指定的凭证不再可用时改按权重选择。
+pub fn apply(
+    decision: &SelectDecision,
+    ids: &[&str],
+    weights: &mut [f64],
+) -> Result<Option<usize>> {
+    if let Some(id) = &decision.credential_id {
+        match ids.iter().position(|candidate| candidate == id) {
+            Some(index) => {
+                debug!("钩子 {} 指定凭证 {}", decision.hook, id);
+                return Ok(Some(index));
+            }
+            None => warn!(
+                "钩子 {} 指定的凭证 {} 已不可用，改按权重选择",
+                decision.hook, id
+            ),
+        }
+    }
+    if decision.weights.is_empty() {
+        return Ok(None);
+    }
+    for (weight, id) in weights.iter_mut().zip(ids) {
+        if let Some(w) = decision.weights.get(*id) {
+            *weight = w.max(0.0);
+        }
+    }
+    if weights.iter().all(|w| *w <= 0.0) {
+        anyhow::bail!("钩子 {} 排除了所有候选凭证", decision.hook);
+    }
+    Ok(None)
+}
+
+/// 各钩子的状态
+pub fn status(config: &HooksConfig) -> Vec<HookStatus> {
+    let stats = STATS.lock().unwrap();
+    config
+        .modules
+        .iter()
+        .map(|hook| {
+            let loaded = load(&hook.path);
+            HookStatus {
+                name: hook.name.clone(),
+                path: hook.path.clone(),
+                exports: loaded.as_ref().map(|m| exports(m)).unwrap_or_default(),
+                load_error: loaded.err().map(|e| format!("{:#}", e)),
+                stats: stats.get(&hook.name).cloned().unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 把输入里的 `"a"` 全部改成 `"b"` 后原样返回；`select` 固定返回 `{"credential_id":"c2"}`
+    const ECHO: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (data (i32.const 0) "{\"credential_id\":\"c2\"}")
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "on_request") (param $ptr i32) (param $len i32) (result i64)
+            (local $i i32)
+            (block $done
+              (loop $next
+                (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+                (if (i32.eq (i32.load8_u (i32.add (local.get $ptr) (local.get $i))) (i32.const 97))
+                  (then (i32.store8 (i32.add (local.get $ptr) (local.get $i)) (i32.const 98))))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $next)))
+            (i64.or
+              (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+              (i64.extend_i32_u (local.get $len))))
+          (func (export "select") (param i32 i32) (result i64) (i64.const 22))
+          (func (export "spin") (param i32 i32) (result i64) (loop $l (br $l)) (i64.const 0))
+          (func (export "grow") (param i32 i32) (result i64)
+            (drop (memory.grow (i32.const 1024))) (i64.const 0)))
+    "#;
+
+    fn echo_module() -> Module {
+        compile(&wat::parse_str(ECHO).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_invoke() {
+        let config = HooksConfig::default();
+        let module = echo_module();
+        let (output, fuel) = invoke(&config, &module, "on_request", br#"{"a":"a"}"#).unwrap();
+        assert_eq!(output.unwrap(), br#"{"b":"b"}"#);
+        assert!(fuel > 0);
+
+        let (output, _) = invoke(&config, &module, "select", b"{}").unwrap();
+        assert_eq!(output.unwrap(), br#"{"credential_id":"c2"}"#);
+
+        assert_eq!(resolve_export(&module, "on_stream_chunk"), None);
+        assert_eq!(exports(&module), vec!["on_request", "select"]);
+    }
+
+    #[test]
+    fn test_sandbox_limits() {
+        let config = HooksConfig {
+            fuel: 100_000,
+            max_memory_bytes: 1024 * 1024,
+            ..Default::default()
+        };
+        let module = echo_module();
+        // 死循环在燃料耗尽时终止
+        assert!(invoke(&config, &module, "spin", b"{}").is_err());
+        // 超出内存上限
+        assert!(invoke(&config, &module, "grow", b"{}").is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        let dir = std::env::temp_dir().join(format!("droid-hooks-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("echo.wasm");
+        std::fs::write(&path, wat::parse_str(ECHO).unwrap()).unwrap();
+        let config = HooksConfig {
+            enabled: true,
+            modules: vec![HookModule {
+                name: "echo".to_string(),
+                path: path.display().to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let input = serde_json::json!({});
+        let mut weights = vec![1.0, 1.0];
+        let decision = decide_blocking(&config, &ids(&["c1", "c2"]), &input)
+            .unwrap()
+            .unwrap();
+        assert_eq!(decision.hook, "echo");
+        assert_eq!(
+            apply(&decision, &["c1", "c2"], &mut weights).unwrap(),
+            Some(1)
+        );
+        // 钩子执行期间凭证不再可用：改按权重选择
+        assert_eq!(apply(&decision, &["c1"], &mut weights).unwrap(), None);
+        // 指定的凭证不在候选中时忽略
+        assert!(decide_blocking(&config, &ids(&["c1"]), &input)
+            .unwrap()
+            .is_none());
+        assert_eq!(status(&config)[0].stats.calls, 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_decide_times_out() {
+        let dir = std::env::temp_dir().join(format!("droid-hooks-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("spin.wasm");
+        // select 死循环，直到燃料耗尽
+        let spin = ECHO.replace(
+            r#"(func (export "select") (param i32 i32) (result i64) (i64.const 22))"#,
+            r#"(func (export "select") (param i32 i32) (result i64) (loop $l (br $l)) (i64.const 0))"#,
+        );
+        std::fs::write(&path, wat::parse_str(&spin).unwrap()).unwrap();
+        let mut config = HooksConfig {
+            enabled: true,
+            modules: vec![HookModule {
+                name: "spin".to_string(),
+                path: path.display().to_string(),
+            }],
+            fuel: 50_000_000,
+            timeout_ms: 1,
+            ..Default::default()
+        };
+        let ids = vec!["c1".to_string()];
+        let input = serde_json::json!({});
+        assert!(decide(&config, ids.clone(), input.clone())
+            .await
+            .unwrap()
+            .is_none());
+        config.fail_open = false;
+        assert!(decide(&config, ids, input).await.is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
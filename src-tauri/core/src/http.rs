@@ -0,0 +1,402 @@
+//! 上游 HTTP 客户端配置
+//!
+//! 部分用户反馈默认的 reqwest 指纹与 factory-cli 的流量被区别对待。
+//! 这里集中配置 HTTP 版本偏好、TLS 后端 / 版本、请求头顺序以及出站网卡 /
+//! IP 协议偏好（多网卡或 VPN 拆分隧道时固定出口），所有访问 Factory / WorkOS
+//! 的客户端都通过 `client_builder` 构建。
+
+use crate::tls_trust::{self, TlsTrustConfig};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+/// HTTP 版本偏好
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpVersionPreference {
+    /// 由 ALPN 协商
+    #[default]
+    Auto,
+    /// 仅使用 HTTP/1.1
+    Http1Only,
+    /// 直接使用 HTTP/2（不协商）
+    Http2PriorKnowledge,
+}
+
+/// TLS 后端
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsBackend {
+    #[default]
+    Rustls,
+    /// 系统 TLS（需启用 `native-tls` 特性编译）
+    Native,
+}
+
+/// 最低 TLS 版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// 出站 IP 协议偏好
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpFamily {
+    /// 按系统解析顺序
+    #[default]
+    Auto,
+    /// IPv4 地址优先，失败后回退 IPv6
+    PreferIpv4,
+    PreferIpv6,
+    /// 只连接 IPv4 地址
+    Ipv4Only,
+    Ipv6Only,
+}
+
+impl IpFamily {
+    fn accepts(self, addr: &IpAddr) -> bool {
+        match self {
+            IpFamily::Ipv4Only => addr.is_ipv4(),
+            IpFamily::Ipv6Only => addr.is_ipv6(),
+            _ => true,
+        }
+    }
+
+    fn rank(self, addr: &IpAddr) -> u8 {
+        match self {
+            IpFamily::PreferIpv4 => u8::from(addr.is_ipv6()),
+            IpFamily::PreferIpv6 => u8::from(addr.is_ipv4()),
+            _ => 0,
+        }
+    }
+}
+
+/// HTTP 客户端配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpClientConfig {
+    pub http_version: HttpVersionPreference,
+    pub tls_backend: TlsBackend,
+    pub min_tls_version: Option<TlsVersion>,
+    /// HTTP/1 请求头使用首字母大写形式（与 Node.js 客户端一致）
+    pub http1_title_case_headers: bool,
+    /// 请求头发送顺序，未列出的请求头按名称排在其后
+    pub header_order: Vec<String>,
+    /// 出站连接绑定的本地 IP
+    pub local_address: Option<IpAddr>,
+    /// 出站连接绑定的网卡名（如 `en0`），取其地址绑定；与 `local_address` 同时
+    /// 配置时以后者为准
+    pub interface: Option<String>,
+    pub ip_family: IpFamily,
+    /// 自定义 CA 与证书固定
+    pub trust: TlsTrustConfig,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            http_version: HttpVersionPreference::Auto,
+            tls_backend: TlsBackend::Rustls,
+            min_tls_version: None,
+            http1_title_case_headers: false,
+            header_order: [
+                "Content-Type",
+                "Authorization",
+                "User-Agent",
+                "x-factory-client",
+                "anthropic-version",
+            ]
+            .iter()
+            .map(|h| h.to_string())
+            .collect(),
+            local_address: None,
+            interface: None,
+            ip_family: IpFamily::Auto,
+            trust: TlsTrustConfig::default(),
+        }
+    }
+}
+
+/// 按配置构建 HTTP 客户端（超时由调用方设置）
+///
+/// 配置的网卡不存在或没有可用地址时返回错误，而不是悄悄走默认路由。
+pub fn client_builder() -> Result<reqwest::ClientBuilder> {
+    let config = crate::config::get_config().http;
+    let mut builder = reqwest::Client::builder();
+
+    builder = match config.http_version {
+        HttpVersionPreference::Auto => builder,
+        HttpVersionPreference::Http1Only => builder.http1_only(),
+        HttpVersionPreference::Http2PriorKnowledge => builder.http2_prior_knowledge(),
+    };
+    if config.http1_title_case_headers {
+        builder = builder.http1_title_case_headers();
+    }
+
+    builder = match config.tls_backend {
+        TlsBackend::Rustls => builder.use_rustls_tls(),
+        #[cfg(feature = "native-tls")]
+        TlsBackend::Native => builder.use_native_tls(),
+        #[cfg(not(feature = "native-tls"))]
+        TlsBackend::Native => {
+            tracing::warn!("未启用 native-tls 特性，继续使用 rustls");
+            builder.use_rustls_tls()
+        }
+    };
+    if let Some(version) = config.min_tls_version {
+        builder = builder.min_tls_version(match version {
+            TlsVersion::Tls12 => reqwest::tls::Version::TLS_1_2,
+            TlsVersion::Tls13 => reqwest::tls::Version::TLS_1_3,
+        });
+    }
+
+    // 固定证书时改用自定义校验器（始终为 rustls），否则按需追加 CA
+    let alpn: &[&[u8]] = match config.http_version {
+        HttpVersionPreference::Auto => &[b"h2", b"http/1.1"],
+        HttpVersionPreference::Http1Only => &[b"http/1.1"],
+        HttpVersionPreference::Http2PriorKnowledge => &[b"h2"],
+    };
+    let tls13_only = config.min_tls_version == Some(TlsVersion::Tls13);
+    if let Some(tls) = tls_trust::pinned_tls_config(&config.trust, alpn, tls13_only)? {
+        builder = builder.use_preconfigured_tls(tls);
+    } else {
+        builder = builder.tls_built_in_root_certs(config.trust.builtin_roots);
+        if let Some(path) = &config.trust.ca_bundle {
+            for der in tls_trust::load_ca_bundle(path)? {
+                builder = builder.add_root_certificate(reqwest::Certificate::from_der(&der)?);
+            }
+        }
+    }
+
+    let bind_address = resolve_bind_address(&config)?;
+    let family = effective_family(config.ip_family, bind_address);
+    if let Some(addr) = bind_address {
+        builder = builder.local_address(addr);
+    }
+    if family != IpFamily::Auto {
+        builder = builder.dns_resolver(Arc::new(FamilyResolver { family }));
+    }
+
+    Ok(builder)
+}
+
+/// 解析出站绑定地址：显式 IP 优先，其次取网卡上与协议偏好匹配的地址
+pub fn resolve_bind_address(config: &HttpClientConfig) -> Result<Option<IpAddr>> {
+    if let Some(addr) = config.local_address {
+        return Ok(Some(addr));
+    }
+    let Some(name) = config.interface.as_deref().filter(|n| !n.is_empty()) else {
+        return Ok(None);
+    };
+
+    let mut addrs: Vec<_> = interface_addresses(name)?
+        .into_iter()
+        .filter(|addr| config.ip_family.accepts(addr) && !is_ipv6_link_local(addr))
+        .collect();
+    // 未指定偏好时优先 IPv4，与多数上游的可达性一致
+    let family = match config.ip_family {
+        IpFamily::Auto => IpFamily::PreferIpv4,
+        family => family,
+    };
+    addrs.sort_by_key(|addr| family.rank(addr));
+    addrs
+        .into_iter()
+        .next()
+        .map(Some)
+        .with_context(|| format!("网卡 {} 没有可用的 {:?} 地址", name, config.ip_family))
+}
+
+/// 绑定了本地地址时只能连接同协议的远端，否则连接会绕过绑定
+fn effective_family(family: IpFamily, bind_address: Option<IpAddr>) -> IpFamily {
+    match bind_address {
+        Some(IpAddr::V4(_)) => IpFamily::Ipv4Only,
+        Some(IpAddr::V6(_)) => IpFamily::Ipv6Only,
+        None => family,
+    }
+}
+
+fn is_ipv6_link_local(addr: &IpAddr) -> bool {
+    matches!(addr, IpAddr::V6(v6) if (v6.segments()[0] & 0xffc0) == 0xfe80)
+}
+
+/// 按协议偏好排序（稳定排序，保留同协议内的解析顺序）并过滤地址
+pub fn order_addresses(addrs: Vec<SocketAddr>, family: IpFamily) -> Vec<SocketAddr> {
+    let mut addrs: Vec<_> = addrs
+        .into_iter()
+        .filter(|addr| family.accepts(&addr.ip()))
+        .collect();
+    addrs.sort_by_key(|addr| family.rank(&addr.ip()));
+    addrs
+}
+
+/// 按协议偏好返回地址的 DNS 解析器
+struct FamilyResolver {
+    family: IpFamily,
+}
+
+impl reqwest::dns::Resolve for FamilyResolver {
+    fn resolve(&self, name: hyper::client::connect::dns::Name) -> reqwest::dns::Resolving {
+        let family = self.family;
+        Box::pin(async move {
+            let resolved = tokio::net::lookup_host((name.as_str(), 0)).await?;
+            let addrs = order_addresses(resolved.collect(), family);
+            if addrs.is_empty() {
+                let message = format!("{} 没有 {:?} 地址", name.as_str(), family);
+                return Err(std::io::Error::new(std::io::ErrorKind::NotFound, message).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// 列出网卡上的 IP 地址
+#[cfg(unix)]
+pub fn interface_addresses(name: &str) -> Result<Vec<IpAddr>> {
+    use std::ffi::CStr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let mut found = false;
+    let mut addrs = Vec::new();
+    let mut ifap: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs 成功后链表在 freeifaddrs 之前一直有效，只读遍历
+    unsafe {
+        if libc::getifaddrs(&mut ifap) != 0 {
+            return Err(std::io::Error::last_os_error()).context("读取网卡列表失败");
+        }
+        let mut cursor = ifap;
+        while let Some(ifa) = cursor.as_ref() {
+            cursor = ifa.ifa_next;
+            if ifa.ifa_name.is_null() || CStr::from_ptr(ifa.ifa_name).to_bytes() != name.as_bytes()
+            {
+                continue;
+            }
+            found = true;
+            let Some(sockaddr) = ifa.ifa_addr.as_ref() else {
+                continue;
+            };
+            match i32::from(sockaddr.sa_family) {
+                libc::AF_INET => {
+                    let sin = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                    addrs.push(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                        sin.sin_addr.s_addr,
+                    ))));
+                }
+                libc::AF_INET6 => {
+                    let sin6 = &*(ifa.ifa_addr as *const libc::sockaddr_in6);
+                    addrs.push(IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr)));
+                }
+                _ => {}
+            }
+        }
+        libc::freeifaddrs(ifap);
+    }
+
+    if !found {
+        anyhow::bail!("找不到网卡 {}", name);
+    }
+    Ok(addrs)
+}
+
+/// 列出网卡上的 IP 地址
+#[cfg(not(unix))]
+pub fn interface_addresses(name: &str) -> Result<Vec<IpAddr>> {
+    anyhow::bail!("当前平台不支持按网卡名 {} 绑定，请改用 local_address", name)
+}
+
+/// 按配置的顺序排列请求头
+pub fn ordered_headers(
+    headers: &HashMap<String, String>,
+    order: &[String],
+) -> Vec<(String, String)> {
+    let rank = |name: &str| {
+        order
+            .iter()
+            .position(|o| o.eq_ignore_ascii_case(name))
+            .unwrap_or(order.len())
+    };
+
+    let mut ordered: Vec<_> = headers
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    ordered.sort_by(|(a, _), (b, _)| rank(a).cmp(&rank(b)).then_with(|| a.cmp(b)));
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordered_headers() {
+        let headers = HashMap::from([
+            ("x-extra".to_string(), "1".to_string()),
+            ("user-agent".to_string(), "ua".to_string()),
+            ("Authorization".to_string(), "Bearer t".to_string()),
+        ]);
+        let order = HttpClientConfig::default().header_order;
+
+        let names: Vec<_> = ordered_headers(&headers, &order)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["Authorization", "user-agent", "x-extra"]);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config: HttpClientConfig = serde_json::from_value(serde_json::json!({
+            "http_version": "http1_only",
+            "min_tls_version": "1.3"
+        }))
+        .unwrap();
+        assert_eq!(config.http_version, HttpVersionPreference::Http1Only);
+        assert_eq!(config.min_tls_version, Some(TlsVersion::Tls13));
+        assert_eq!(config.tls_backend, TlsBackend::Rustls);
+        assert_eq!(config.ip_family, IpFamily::Auto);
+    }
+
+    #[test]
+    fn test_order_addresses() {
+        let v4a: SocketAddr = "1.1.1.1:443".parse().unwrap();
+        let v6: SocketAddr = "[2606:4700::1111]:443".parse().unwrap();
+        let v4b: SocketAddr = "1.0.0.1:443".parse().unwrap();
+        let addrs = vec![v6, v4a, v4b];
+
+        assert_eq!(
+            order_addresses(addrs.clone(), IpFamily::Auto),
+            [v6, v4a, v4b]
+        );
+        assert_eq!(
+            order_addresses(addrs.clone(), IpFamily::PreferIpv4),
+            [v4a, v4b, v6]
+        );
+        assert_eq!(order_addresses(addrs.clone(), IpFamily::Ipv6Only), [v6]);
+        assert!(order_addresses(vec![v4a], IpFamily::Ipv6Only).is_empty());
+    }
+
+    #[test]
+    fn test_bind_address() {
+        let config = HttpClientConfig {
+            local_address: Some("192.168.1.10".parse().unwrap()),
+            interface: Some("no-such-if0".to_string()),
+            ip_family: IpFamily::PreferIpv6,
+            ..Default::default()
+        };
+        let bind = resolve_bind_address(&config).unwrap();
+        assert_eq!(bind, config.local_address);
+        assert_eq!(effective_family(config.ip_family, bind), IpFamily::Ipv4Only);
+
+        let missing = HttpClientConfig {
+            interface: Some("no-such-if0".to_string()),
+            ..Default::default()
+        };
+        assert!(resolve_bind_address(&missing).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! 闲置凭证保活
+//!
+//! OAuth 会话长时间不活动可能被服务端作废，备用账号到故障转移真正需要时
+//! 才发现已经不能用。开启后，闲置超过 `idle_hours` 的凭证会被低频“ping”
+//! 一次：OAuth 凭证刷新一次 Token，API Key 凭证做一次端点探测。
+//! 活跃时间只记录在内存中，重启后从启动时刻重新计时，避免启动时集中请求。
+
+use crate::credentials::AuthType;
+use crate::events;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// 保活配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeepAliveConfig {
+    pub enabled: bool,
+    /// 闲置多久（小时）后发送一次保活请求
+    pub idle_hours: u64,
+    /// 检查间隔（分钟）
+    pub check_interval_minutes: u64,
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_hours: 72,
+            check_interval_minutes: 60,
+        }
+    }
+}
+
+/// 待检查的凭证
+#[derive(Debug, Clone)]
+pub struct KeepAliveCandidate {
+    pub credential_id: String,
+    pub auth_type: AuthType,
+    /// 最近一次 Token 刷新时间
+    pub last_refresh: Option<DateTime<Utc>>,
+}
+
+lazy_static::lazy_static! {
+    static ref LAST_ACTIVE: Mutex<HashMap<String, DateTime<Utc>>> = Mutex::new(HashMap::new());
+}
+
+/// 记录凭证活跃（请求完成或保活成功）
+pub fn touch(credential_id: &str) {
+    LAST_ACTIVE
+        .lock()
+        .unwrap()
+        .insert(credential_id.to_string(), Utc::now());
+}
+
+/// 找出需要保活的凭证（首次见到的凭证从此刻开始计时）
+pub fn idle_credentials(
+    candidates: &[KeepAliveCandidate],
+    config: &KeepAliveConfig,
+    now: DateTime<Utc>,
+) -> Vec<KeepAliveCandidate> {
+    let idle_after = Duration::hours(config.idle_hours.max(1) as i64);
+    let mut last_active = LAST_ACTIVE.lock().unwrap();
+    candidates
+        .iter()
+        .filter(|candidate| {
+            let seen = *last_active
+                .entry(candidate.credential_id.clone())
+                .or_insert(now);
+            let active = candidate
+                .last_refresh
+                .map_or(seen, |refresh| refresh.max(seen));
+            now - active >= idle_after
+        })
+        .cloned()
+        .collect()
+}
+
+async fn ping(candidate: &KeepAliveCandidate) -> anyhow::Result<()> {
+    match candidate.auth_type {
+        AuthType::OAuth => {
+            crate::provider::refresh_token(&candidate.credential_id).await?;
+        }
+        AuthType::ApiKey => {
+            let result = crate::provider::validate_credential(&candidate.credential_id).await?;
+            if !result.valid {
+                anyhow::bail!(result.message.unwrap_or_else(|| "凭证无效".to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 后台任务：定期为闲置凭证发送保活请求
+pub async fn run_pinger() {
+    loop {
+        let config = crate::config::get_config().keepalive;
+        if config.enabled {
+            let candidates = crate::provider::keepalive_candidates().await;
+            for candidate in idle_credentials(&candidates, &config, Utc::now()) {
+                let id = &candidate.credential_id;
+                debug!(
+                    "凭证 {} 闲置超过 {} 小时，发送保活请求",
+                    id, config.idle_hours
+                );
+                match ping(&candidate).await {
+                    Ok(()) => {
+                        touch(id);
+                        info!("凭证 {} 保活成功", id);
+                    }
+                    Err(e) => {
+                        // 失败也记为活跃，避免每个检查周期都重试
+                        touch(id);
+                        warn!("凭证 {} 保活失败: {}", id, e);
+                        events::emit(
+                            "keepalive_failed",
+                            format!("闲置凭证保活失败: {}", e),
+                            serde_json::json!({ "credential_id": id, "error": e.to_string() }),
+                        );
+                    }
+                }
+            }
+        }
+        let minutes = config.check_interval_minutes.max(1);
+        tokio::time::sleep(std::time::Duration::from_secs(minutes * 60)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: &str, last_refresh: Option<DateTime<Utc>>) -> KeepAliveCandidate {
+        KeepAliveCandidate {
+            credential_id: id.to_string(),
+            auth_type: AuthType::OAuth,
+            last_refresh,
+        }
+    }
+
+    #[test]
+    fn test_idle_credentials() {
+        let config = KeepAliveConfig {
+            enabled: true,
+            idle_hours: 24,
+            ..Default::default()
+        };
+        let now = Utc::now();
+        let candidates = [
+            candidate("keepalive-fresh", None),
+            candidate("keepalive-refreshed", Some(now - Duration::hours(1))),
+        ];
+        // 首次见到时开始计时
+        assert!(idle_credentials(&candidates, &config, now).is_empty());
+
+        let later = now + Duration::hours(25);
+        let idle = idle_credentials(&candidates, &config, later);
+        assert_eq!(idle.len(), 2);
+
+        LAST_ACTIVE
+            .lock()
+            .unwrap()
+            .insert("keepalive-fresh".to_string(), later);
+        let idle = idle_credentials(&candidates, &config, later);
+        assert_eq!(idle.len(), 1);
+        assert_eq!(idle[0].credential_id, "keepalive-refreshed");
+    }
+}
@@ -0,0 +1,284 @@
+//! 凭证租约跟踪
+//!
+//! 以 (凭证, 端点) 为粒度记录进行中的请求，避免同一账号上一个长时间的
+//! Anthropic 流式请求占满并发，阻塞走 OpenAI 路径的短请求。每个 (凭证, 端点)
+//! 的并发上限可配置，默认不限制；宿主崩溃或遗漏释放的租约超过 `ttl_secs`
+//! 后自动回收，不会一直占着并发。
+
+use crate::credentials::EndpointType;
+use crate::request_tags::Tags;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 租约配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaseConfig {
+    /// 每个 (凭证, 端点) 允许的最大并发租约数，0 表示不限制
+    pub max_per_endpoint: usize,
+    /// 租约超过该秒数仍未释放时回收，0 表示不回收
+    pub ttl_secs: u64,
+}
+
+impl Default for LeaseConfig {
+    fn default() -> Self {
+        Self {
+            max_per_endpoint: 0,
+            ttl_secs: 3600,
+        }
+    }
+}
+
+/// 单个租约
+#[derive(Debug, Clone)]
+pub struct Lease {
+    pub credential_id: String,
+    pub endpoint_type: EndpointType,
+    pub model: String,
+    /// 调用方应用名（如 Cursor、Cline）
+    pub client_name: Option<String>,
+    pub acquired_at: DateTime<Utc>,
+    /// 是否已做过一次 401 恢复
+    pub recovered: bool,
+    /// 故障转移前的原端点
+    pub failover_from: Option<EndpointType>,
+    /// 发起请求的租户
+    pub tenant: Option<String>,
+    /// 调用方附加的请求标签
+    pub tags: Tags,
+}
+
+/// 租约跟踪器
+#[derive(Debug, Default)]
+pub struct LeaseTracker {
+    leases: HashMap<String, Lease>,
+    /// 0 表示不限制
+    max_per_endpoint: usize,
+}
+
+impl LeaseTracker {
+    pub fn new(max_per_endpoint: usize) -> Self {
+        Self {
+            leases: HashMap::new(),
+            max_per_endpoint,
+        }
+    }
+
+    /// 套用配置中的并发上限
+    pub fn set_max_per_endpoint(&mut self, max_per_endpoint: usize) {
+        self.max_per_endpoint = max_per_endpoint;
+    }
+
+    /// 移除早于 `now - ttl_secs` 创建的租约并返回；`ttl_secs` 为 0 时不回收
+    pub fn expire(&mut self, ttl_secs: u64, now: DateTime<Utc>) -> Vec<(String, Lease)> {
+        if ttl_secs == 0 {
+            return Vec::new();
+        }
+        let deadline = now - Duration::seconds(ttl_secs as i64);
+        let expired: Vec<String> = self
+            .leases
+            .iter()
+            .filter(|(_, l)| l.acquired_at < deadline)
+            .map(|(id, _)| id.clone())
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|id| self.leases.remove(&id).map(|lease| (id, lease)))
+            .collect()
+    }
+
+    /// 某个 (凭证, 端点) 当前的租约数
+    pub fn active(&self, credential_id: &str, endpoint_type: EndpointType) -> usize {
+        self.leases
+            .values()
+            .filter(|l| l.credential_id == credential_id && l.endpoint_type == endpoint_type)
+            .count()
+    }
+
+    /// 凭证是否有进行中的请求
+    pub fn has_leases(&self, credential_id: &str) -> bool {
+        self.leases
+            .values()
+            .any(|l| l.credential_id == credential_id)
+    }
+
+    /// 一组凭证在所有端点上的租约总数
+    pub fn total(&self, credential_ids: &[String]) -> usize {
+        self.leases
+            .values()
+            .filter(|l| credential_ids.contains(&l.credential_id))
+            .count()
+    }
+
+    /// 某个 (凭证, 端点) 是否还有空闲并发
+    pub fn has_capacity(&self, credential_id: &str, endpoint_type: EndpointType) -> bool {
+        self.max_per_endpoint == 0
+            || self.active(credential_id, endpoint_type) < self.max_per_endpoint
+    }
+
+    /// 创建租约，返回租约 ID；已达上限时返回 None
+    pub fn acquire(
+        &mut self,
+        credential_id: &str,
+        endpoint_type: EndpointType,
+        model: &str,
+    ) -> Option<String> {
+        if !self.has_capacity(credential_id, endpoint_type) {
+            return None;
+        }
+
+        let lease_id = uuid::Uuid::new_v4().to_string();
+        self.leases.insert(
+            lease_id.clone(),
+            Lease {
+                credential_id: credential_id.to_string(),
+                endpoint_type,
+                model: model.to_string(),
+                client_name: None,
+                acquired_at: Utc::now(),
+                recovered: false,
+                failover_from: None,
+                tenant: None,
+                tags: Tags::new(),
+            },
+        );
+        Some(lease_id)
+    }
+
+    /// 查询租约
+    pub fn get(&self, lease_id: &str) -> Option<&Lease> {
+        self.leases.get(lease_id)
+    }
+
+    /// 标记租约已做过 401 恢复；已标记过时返回 false
+    pub fn mark_recovered(&mut self, lease_id: &str) -> bool {
+        match self.leases.get_mut(lease_id) {
+            Some(lease) if !lease.recovered => {
+                lease.recovered = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 记录租约的调用方应用名
+    pub fn set_client_name(&mut self, lease_id: &str, client_name: Option<String>) {
+        if let Some(lease) = self.leases.get_mut(lease_id) {
+            lease.client_name = client_name;
+        }
+    }
+
+    /// 记录租约的请求标签
+    pub fn set_tags(&mut self, lease_id: &str, tags: Tags) {
+        if let Some(lease) = self.leases.get_mut(lease_id) {
+            lease.tags = tags;
+        }
+    }
+
+    /// 记录租约所属的租户
+    pub fn set_tenant(&mut self, lease_id: &str, tenant: Option<String>) {
+        if let Some(lease) = self.leases.get_mut(lease_id) {
+            lease.tenant = tenant;
+        }
+    }
+
+    /// 记录租约是由哪个端点故障转移而来
+    pub fn set_failover_from(&mut self, lease_id: &str, endpoint_type: EndpointType) {
+        if let Some(lease) = self.leases.get_mut(lease_id) {
+            lease.failover_from = Some(endpoint_type);
+        }
+    }
+
+    /// 释放租约
+    ///
+    /// 未提供租约 ID 时（旧调用方），释放该凭证最早的一个租约。
+    pub fn release(&mut self, credential_id: &str, lease_id: Option<&str>) -> Option<Lease> {
+        if let Some(lease_id) = lease_id {
+            return self.leases.remove(lease_id);
+        }
+
+        let oldest = self
+            .leases
+            .iter()
+            .filter(|(_, l)| l.credential_id == credential_id)
+            .min_by_key(|(_, l)| l.acquired_at)
+            .map(|(id, _)| id.clone())?;
+        self.leases.remove(&oldest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPUS: &str = "claude-opus-4-1-20250805";
+    const GPT: &str = "gpt-5-2025-08-07";
+
+    #[test]
+    fn test_leases_are_tracked_per_endpoint() {
+        let mut tracker = LeaseTracker::new(1);
+
+        let opus = tracker.acquire("cred", EndpointType::Anthropic, OPUS);
+        assert!(opus.is_some());
+        assert!(tracker
+            .acquire("cred", EndpointType::Anthropic, OPUS)
+            .is_none());
+
+        // 同一凭证的 OpenAI 路径不受影响
+        assert!(tracker.acquire("cred", EndpointType::OpenAI, GPT).is_some());
+
+        tracker.release("cred", opus.as_deref());
+        assert!(tracker.has_capacity("cred", EndpointType::Anthropic));
+    }
+
+    #[test]
+    fn test_recover_only_once() {
+        let mut tracker = LeaseTracker::default();
+        let lease_id = tracker
+            .acquire("cred", EndpointType::Anthropic, OPUS)
+            .unwrap();
+
+        assert!(tracker.mark_recovered(&lease_id));
+        assert!(!tracker.mark_recovered(&lease_id));
+        assert!(!tracker.mark_recovered("unknown"));
+    }
+
+    #[test]
+    fn test_release_without_lease_id() {
+        let mut tracker = LeaseTracker::default();
+        tracker.acquire("cred", EndpointType::Comm, GPT);
+        assert_eq!(tracker.active("cred", EndpointType::Comm), 1);
+
+        assert!(tracker.release("cred", None).is_some());
+        assert_eq!(tracker.active("cred", EndpointType::Comm), 0);
+    }
+
+    #[test]
+    fn test_unlimited_by_default() {
+        let mut tracker = LeaseTracker::default();
+        for _ in 0..16 {
+            assert!(tracker
+                .acquire("cred", EndpointType::Anthropic, OPUS)
+                .is_some());
+        }
+        tracker.set_max_per_endpoint(16);
+        assert!(!tracker.has_capacity("cred", EndpointType::Anthropic));
+    }
+
+    #[test]
+    fn test_expire_stale_leases() {
+        let mut tracker = LeaseTracker::new(1);
+        let stale = tracker
+            .acquire("cred", EndpointType::Anthropic, OPUS)
+            .unwrap();
+        let now = Utc::now();
+        assert!(tracker.expire(0, now + Duration::hours(2)).is_empty());
+        assert!(tracker.expire(3600, now).is_empty());
+
+        let expired = tracker.expire(3600, now + Duration::hours(2));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].0, stale);
+        assert!(tracker.has_capacity("cred", EndpointType::Anthropic));
+    }
+}
@@ -0,0 +1,94 @@
+//! Droid Provider 核心库
+//!
+//! 提供 Factory.ai 凭证池、Token 刷新与请求路由，不依赖 Tauri 或 CLI，
+//! 可直接嵌入其他 Rust 程序。`droid-provider-cli` 只是其上的 JSON-RPC 外壳。
+
+pub mod account_link;
+pub mod app_lock;
+pub mod auth;
+pub mod autostart;
+pub mod availability;
+pub mod backoff_state;
+pub mod batch;
+pub mod body_text;
+pub mod broadcast;
+pub mod canary;
+pub mod capabilities;
+pub mod chaos;
+pub mod chat_normalize;
+pub mod compression;
+pub mod config;
+pub mod config_check;
+pub mod context_trim;
+pub mod control;
+pub mod credential_clone;
+pub mod credentials;
+pub mod dead_credentials;
+pub mod dedup;
+pub mod deprecation;
+pub mod digest;
+pub mod doctor;
+pub mod documents;
+pub mod env_import;
+pub mod events;
+pub mod failover;
+pub mod filter;
+pub mod health;
+pub mod heartbeat;
+pub mod hooks;
+pub mod http;
+pub mod keepalive;
+pub mod lease;
+pub mod limits;
+pub mod logging;
+pub mod maintenance;
+pub mod middleware;
+pub mod migrations;
+pub mod mock;
+pub mod model_overrides;
+pub mod model_registry;
+pub mod model_tiers;
+pub mod org_discovery;
+pub mod org_names;
+pub mod param_policy;
+pub mod params;
+pub mod passthrough;
+pub mod pricing;
+pub mod probe;
+pub mod profiles;
+pub mod provider;
+pub mod quota_link;
+pub mod reassembly;
+pub mod refresh_debug;
+pub mod refresh_failure;
+pub mod refresh_limiter;
+pub mod relogin;
+pub mod request_tags;
+pub mod response_meta;
+pub mod response_repair;
+pub mod retention;
+pub mod retry_budget;
+pub mod reveal;
+pub mod salvage;
+pub mod schedule;
+pub mod secret_lock;
+pub mod setup;
+pub mod sharing;
+pub mod singleflight;
+pub mod spool;
+pub mod startup;
+pub mod stats;
+pub mod stop_sequences;
+pub mod store;
+pub mod store_lock;
+pub mod stream_progress;
+pub mod tenants;
+pub mod throttle;
+pub mod timeouts;
+pub mod tls_trust;
+pub mod token_age;
+pub mod token_refresh;
+pub mod tray;
+pub mod usage;
+pub mod user_agent;
+pub mod wake;
@@ -0,0 +1,147 @@
+//! 请求大小与连接数限制
+//!
+//! 本地监听由宿主负责，宿主在读取请求体之前用 Content-Length、请求头大小和
+//! 当前连接数调用 `check_request_limits`，按返回的状态码（413 / 431 / 503）
+//! 直接拒绝，避免异常客户端用超大请求撑爆内存。`transform_request` 也会按
+//! 实际请求体大小再检查一次。各项为 0 表示不限制。
+
+use crate::events;
+use serde::{Deserialize, Serialize};
+
+/// 大小限制配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SizeLimitConfig {
+    /// 请求体上限（字节）
+    pub max_body_bytes: u64,
+    /// 请求头总大小上限（字节）
+    pub max_header_bytes: u64,
+    /// 本地并发连接数上限
+    pub max_connections: u64,
+}
+
+impl Default for SizeLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 32 * 1024 * 1024,
+            max_header_bytes: 64 * 1024,
+            max_connections: 256,
+        }
+    }
+}
+
+/// 超限结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LimitViolation {
+    /// 宿主应返回的 HTTP 状态码
+    pub status: u16,
+    /// 超限的配置项
+    pub limit_name: String,
+    pub limit: u64,
+    pub actual: u64,
+    pub message: String,
+}
+
+/// 请求的实际尺寸，由宿主在读取请求体前提供
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestSize {
+    pub body_bytes: u64,
+    pub header_bytes: u64,
+    pub active_connections: u64,
+}
+
+/// 检查请求是否超限，按连接数、请求头、请求体的顺序报告第一项
+pub fn check(config: &SizeLimitConfig, size: &RequestSize) -> Option<LimitViolation> {
+    let checks = [
+        (
+            503,
+            "max_connections",
+            config.max_connections,
+            size.active_connections,
+            "连接数过多",
+        ),
+        (
+            431,
+            "max_header_bytes",
+            config.max_header_bytes,
+            size.header_bytes,
+            "请求头过大",
+        ),
+        (
+            413,
+            "max_body_bytes",
+            config.max_body_bytes,
+            size.body_bytes,
+            "请求体过大",
+        ),
+    ];
+    checks
+        .into_iter()
+        .find(|(_, _, limit, actual, _)| *limit > 0 && actual > limit)
+        .map(
+            |(status, limit_name, limit, actual, reason)| LimitViolation {
+                status,
+                limit_name: limit_name.to_string(),
+                limit,
+                actual,
+                message: format!("{}（{} > {}）", reason, actual, limit),
+            },
+        )
+}
+
+/// 按当前配置检查，超限时发出 `request_rejected` 事件
+pub fn check_request(size: &RequestSize) -> Option<LimitViolation> {
+    let violation = check(&crate::config::get_config().limits, size)?;
+    events::emit(
+        "request_rejected",
+        violation.message.clone(),
+        serde_json::to_value(&violation).unwrap_or_default(),
+    );
+    Some(violation)
+}
+
+/// 检查已解析的请求体大小
+pub fn check_body(body: &serde_json::Value) -> Option<LimitViolation> {
+    let body_bytes = serde_json::to_vec(body)
+        .map(|b| b.len() as u64)
+        .unwrap_or(0);
+    check_request(&RequestSize {
+        body_bytes,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_order_and_unlimited() {
+        let config = SizeLimitConfig {
+            max_body_bytes: 100,
+            max_header_bytes: 10,
+            max_connections: 0,
+        };
+        let size = RequestSize {
+            body_bytes: 200,
+            header_bytes: 20,
+            active_connections: 10_000,
+        };
+        let violation = check(&config, &size).unwrap();
+        assert_eq!(violation.status, 431);
+        assert_eq!(violation.limit_name, "max_header_bytes");
+
+        let size = RequestSize {
+            body_bytes: 200,
+            ..Default::default()
+        };
+        assert_eq!(check(&config, &size).unwrap().status, 413);
+
+        let size = RequestSize {
+            body_bytes: 100,
+            ..Default::default()
+        };
+        assert!(check(&config, &size).is_none());
+    }
+}
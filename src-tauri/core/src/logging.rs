@@ -0,0 +1,335 @@
+//! 日志子系统
+//!
+//! 日志级别、按模块的过滤规则和输出目标都可在运行时通过 `update_config`
+//! 修改，无需重启。输出目标：stderr、按大小轮转的日志文件，以及内存环形
+//! 缓冲区（`get_recent_logs` 读取，便于用户在 UI 中直接附到问题反馈里）。
+//! 核心库作为依赖嵌入其他程序时不会自动安装，需要显式调用 `init`。
+
+use crate::config::data_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, RwLock};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+/// 轮转日志文件配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileSinkConfig {
+    pub enabled: bool,
+    /// 日志文件路径，默认为数据目录下的 `logs/droid-provider.log`
+    pub path: Option<String>,
+    /// 单个文件的大小上限（字节）
+    pub max_bytes: u64,
+    /// 保留的历史文件数
+    pub max_files: u32,
+}
+
+impl Default for FileSinkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: None,
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// 日志配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// 默认级别（trace / debug / info / warn / error），设置了 RUST_LOG 时以其为准
+    pub level: String,
+    /// 按模块的过滤规则，如 `droid_provider_core::provider=trace`
+    pub filters: Vec<String>,
+    pub stderr: bool,
+    pub file: FileSinkConfig,
+    /// 内存中保留的最近日志行数，0 表示不保留
+    pub ring_buffer_lines: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            filters: Vec::new(),
+            stderr: true,
+            file: FileSinkConfig::default(),
+            ring_buffer_lines: 1000,
+        }
+    }
+}
+
+/// 按配置构建过滤器
+pub fn build_filter(config: &LoggingConfig) -> anyhow::Result<EnvFilter> {
+    let level = std::env::var("RUST_LOG")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| config.level.clone());
+    let mut filter = EnvFilter::try_new(&level)?;
+    for directive in &config.filters {
+        filter = filter.add_directive(directive.parse()?);
+    }
+    Ok(filter)
+}
+
+/// 按大小轮转的日志文件
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: u32,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_bytes: u64, max_files: u32) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = open_append(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.max_bytes > 0 && self.written + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    /// `a.log` → `a.log.1` → … → `a.log.N`，最旧的删除
+    fn rotate(&mut self) -> io::Result<()> {
+        let numbered = |n: u32| rotated_path(&self.path, n);
+        let _ = fs::remove_file(numbered(self.max_files));
+        for n in (1..self.max_files).rev() {
+            let _ = fs::rename(numbered(n), numbered(n + 1));
+        }
+        if self.max_files > 0 {
+            fs::rename(&self.path, numbered(1))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+        self.file = open_append(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+/// 以追加方式打开日志文件，仅当前用户可读写（已有文件也收紧权限）
+fn open_append(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(file)
+}
+
+fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// 当前生效的输出目标
+struct Sinks {
+    stderr: bool,
+    file: Option<RotatingFile>,
+    ring: VecDeque<String>,
+    ring_capacity: usize,
+}
+
+impl Sinks {
+    fn write(&mut self, line: &[u8]) {
+        if self.stderr {
+            let _ = io::stderr().write_all(line);
+        }
+        if let Some(file) = self.file.as_mut() {
+            let _ = file.write_line(line);
+        }
+        if self.ring_capacity > 0 {
+            while self.ring.len() >= self.ring_capacity {
+                self.ring.pop_front();
+            }
+            self.ring
+                .push_back(String::from_utf8_lossy(line).trim_end().to_string());
+        }
+    }
+}
+
+static SINKS: RwLock<Option<Mutex<Sinks>>> = RwLock::new(None);
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// 每条日志事件的写入缓冲，结束时分发到各输出目标
+struct LineWriter(Vec<u8>);
+
+impl Write for LineWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LineWriter {
+    fn drop(&mut self) {
+        if self.0.is_empty() {
+            return;
+        }
+        if let Some(sinks) = SINKS.read().unwrap().as_ref() {
+            sinks.lock().unwrap().write(&self.0);
+        }
+    }
+}
+
+fn make_writer() -> LineWriter {
+    LineWriter(Vec::new())
+}
+
+fn file_path(config: &FileSinkConfig) -> PathBuf {
+    config
+        .path
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| data_dir().join("logs").join("droid-provider.log"))
+}
+
+fn build_sinks(config: &LoggingConfig, previous: Option<Sinks>) -> anyhow::Result<Sinks> {
+    let file = if config.file.enabled {
+        let path = file_path(&config.file);
+        Some(RotatingFile::open(
+            path,
+            config.file.max_bytes,
+            config.file.max_files,
+        )?)
+    } else {
+        None
+    };
+    // 调整配置时保留已缓存的日志
+    let mut ring = previous.map(|p| p.ring).unwrap_or_default();
+    while ring.len() > config.ring_buffer_lines {
+        ring.pop_front();
+    }
+    Ok(Sinks {
+        stderr: config.stderr,
+        file,
+        ring,
+        ring_capacity: config.ring_buffer_lines,
+    })
+}
+
+/// 安装全局日志订阅者
+pub fn init(config: &LoggingConfig) -> anyhow::Result<()> {
+    let filter = build_filter(config).unwrap_or_else(|_| EnvFilter::new("info"));
+    let (filter, handle) = reload::Layer::new(filter);
+    *SINKS.write().unwrap() = Some(Mutex::new(build_sinks(config, None)?));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_ansi(false).with_writer(make_writer))
+        .try_init()?;
+    let _ = FILTER.set(handle);
+    Ok(())
+}
+
+/// 应用新的日志配置（未调用 `init` 时忽略）
+pub fn apply(config: &LoggingConfig) -> anyhow::Result<()> {
+    let Some(handle) = FILTER.get() else {
+        return Ok(());
+    };
+    handle.reload(build_filter(config)?)?;
+
+    let mut sinks = SINKS.write().unwrap();
+    let previous = sinks.take().map(|s| s.into_inner().unwrap());
+    *sinks = Some(Mutex::new(build_sinks(config, previous)?));
+    Ok(())
+}
+
+/// 最近的日志（旧的在前），可按关键字过滤
+pub fn recent_logs(limit: usize, contains: Option<&str>) -> Vec<String> {
+    let sinks = SINKS.read().unwrap();
+    let Some(sinks) = sinks.as_ref() else {
+        return Vec::new();
+    };
+    let sinks = sinks.lock().unwrap();
+    let matched: Vec<_> = sinks
+        .ring
+        .iter()
+        .filter(|line| contains.is_none_or(|c| line.contains(c)))
+        .collect();
+    matched[matched.len().saturating_sub(limit)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_filter() {
+        let mut config = LoggingConfig::default();
+        assert!(build_filter(&config).is_ok());
+        config
+            .filters
+            .push("droid_provider_core::provider=oops".to_string());
+        assert!(build_filter(&config).is_err());
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let dir = std::env::temp_dir().join(format!("droid-logs-{}", std::process::id()));
+        let path = dir.join("test.log");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut file = RotatingFile::open(path.clone(), 10, 2).unwrap();
+        for line in ["aaaaaa\n", "bbbbbb\n", "cccccc\n", "dddddd\n"] {
+            file.write_line(line.as_bytes()).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "dddddd\n");
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 1)).unwrap(),
+            "cccccc\n"
+        );
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 2)).unwrap(),
+            "bbbbbb\n"
+        );
+        assert!(!rotated_path(&path, 3).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,341 @@
+//! Factory 维护窗口
+//!
+//! Factory 整体维护或故障时，所有凭证会在短时间内一起返回 502 / 503。
+//! 此时逐个把凭证标记为不健康没有意义，恢复后还要等健康分数慢慢回升。
+//! 检测到这种“全体失败”后进入全局退避：暂停分配凭证、不再扣减各凭证的
+//! 健康状态，由后台任务定期探测，连续 `recovery_successes` 次成功后自动退出。
+//!
+//! “全体”只统计参与分配的凭证（排除只读、不在启用时段、加密密钥锁定的
+//! 凭证），否则池中有闲置凭证时永远无法判定。
+
+use crate::events;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// 维护窗口检测配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    pub enabled: bool,
+    /// 视为上游整体故障的状态码
+    pub status_codes: Vec<u16>,
+    /// 统计窗口（秒）
+    pub window_seconds: u64,
+    /// 至少多少个不同凭证失败才判定为整体故障
+    pub min_credentials: usize,
+    /// 退避期间的恢复探测间隔（秒）
+    pub probe_interval_seconds: u64,
+    /// 连续多少次成功（探测或请求）才退出退避
+    pub recovery_successes: u32,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            status_codes: vec![502, 503],
+            window_seconds: 60,
+            min_credentials: 2,
+            probe_interval_seconds: 30,
+            recovery_successes: 2,
+        }
+    }
+}
+
+/// 维护状态（供界面展示）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    pub active: bool,
+    #[serde(default)]
+    pub since: Option<String>,
+    /// 触发退避的凭证
+    #[serde(default)]
+    pub failing_credentials: Vec<String>,
+    #[serde(default)]
+    pub last_probe_at: Option<String>,
+    #[serde(default)]
+    pub last_probe_status: Option<u16>,
+    #[serde(default)]
+    pub last_probe_error: Option<String>,
+    /// 退避期间已连续成功的次数
+    #[serde(default)]
+    pub recovery_successes: u32,
+}
+
+/// 失败记录与当前状态
+#[derive(Debug, Default)]
+pub struct OutageTracker {
+    failures: VecDeque<(DateTime<Utc>, String)>,
+    status: MaintenanceStatus,
+}
+
+impl OutageTracker {
+    /// 记录一次请求结果，返回该失败是否属于整体故障（调用方据此跳过单凭证惩罚）
+    ///
+    /// `total_credentials` 为参与分配的凭证数。
+    pub fn observe(
+        &mut self,
+        config: &MaintenanceConfig,
+        credential_id: &str,
+        status_code: Option<u16>,
+        total_credentials: usize,
+        now: DateTime<Utc>,
+    ) -> bool {
+        if !config.enabled {
+            return false;
+        }
+        let Some(status) = status_code else {
+            self.failures.clear();
+            self.record_success(config, "请求成功", now);
+            return false;
+        };
+        if !config.status_codes.contains(&status) {
+            return false;
+        }
+
+        self.status.recovery_successes = 0;
+        self.failures.push_back((now, credential_id.to_string()));
+        let window = Duration::seconds(config.window_seconds as i64);
+        while self
+            .failures
+            .front()
+            .is_some_and(|(at, _)| now - *at > window)
+        {
+            self.failures.pop_front();
+        }
+        if self.status.active {
+            return true;
+        }
+
+        let failing: HashSet<&str> = self.failures.iter().map(|(_, id)| id.as_str()).collect();
+        let required = config.min_credentials.max(total_credentials).max(1);
+        if failing.len() < required {
+            return false;
+        }
+        let mut failing: Vec<String> = failing.into_iter().map(str::to_string).collect();
+        failing.sort();
+        self.start(failing, now);
+        true
+    }
+
+    fn start(&mut self, failing_credentials: Vec<String>, now: DateTime<Utc>) {
+        warn!(
+            "{} 个凭证同时返回 5xx，判定 Factory 维护中，进入全局退避",
+            failing_credentials.len()
+        );
+        self.status = MaintenanceStatus {
+            active: true,
+            since: Some(now.to_rfc3339()),
+            failing_credentials,
+            ..Default::default()
+        };
+        events::emit(
+            "maintenance_started",
+            "Factory 服务疑似维护中，已暂停分配凭证并定期探测恢复",
+            serde_json::to_value(&self.status).unwrap_or_default(),
+        );
+    }
+
+    /// 退避期间的一次成功，连续次数达到要求后退出
+    fn record_success(&mut self, config: &MaintenanceConfig, reason: &str, now: DateTime<Utc>) {
+        if !self.status.active {
+            return;
+        }
+        self.status.recovery_successes += 1;
+        if self.status.recovery_successes >= config.recovery_successes.max(1) {
+            self.finish(reason, now);
+        }
+    }
+
+    fn finish(&mut self, reason: &str, now: DateTime<Utc>) {
+        let since = self.status.since.clone();
+        info!("Factory 服务已恢复（{}），退出全局退避", reason);
+        self.status = MaintenanceStatus::default();
+        self.failures.clear();
+        events::emit(
+            "maintenance_ended",
+            "Factory 服务已恢复，凭证分配恢复正常",
+            serde_json::json!({ "since": since, "ended_at": now.to_rfc3339(), "reason": reason }),
+        );
+    }
+
+    /// 记录一次恢复探测的结果
+    pub fn record_probe(
+        &mut self,
+        config: &MaintenanceConfig,
+        result: Result<u16, String>,
+        now: DateTime<Utc>,
+    ) {
+        if !self.status.active {
+            return;
+        }
+        self.status.last_probe_at = Some(now.to_rfc3339());
+        match result {
+            // 任何非整体故障的响应都说明请求已到达上游
+            Ok(status) if !config.status_codes.contains(&status) => {
+                self.status.last_probe_status = Some(status);
+                self.status.last_probe_error = None;
+                self.record_success(config, "恢复探测成功", now);
+            }
+            Ok(status) => {
+                self.status.last_probe_status = Some(status);
+                self.status.last_probe_error = None;
+                self.status.recovery_successes = 0;
+            }
+            Err(error) => {
+                self.status.last_probe_status = None;
+                self.status.last_probe_error = Some(error);
+                self.status.recovery_successes = 0;
+            }
+        }
+    }
+
+    pub fn status(&self) -> &MaintenanceStatus {
+        &self.status
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref TRACKER: Mutex<OutageTracker> = Mutex::new(OutageTracker::default());
+}
+
+/// 记录请求结果（成功时 `status_code` 为空），返回该失败是否属于整体故障
+///
+/// `total_credentials` 为参与分配的凭证数。
+pub fn observe(credential_id: &str, status_code: Option<u16>, total_credentials: usize) -> bool {
+    let config = crate::config::get_config().maintenance;
+    TRACKER.lock().unwrap().observe(
+        &config,
+        credential_id,
+        status_code,
+        total_credentials,
+        Utc::now(),
+    )
+}
+
+/// 是否处于全局退避
+pub fn is_active() -> bool {
+    TRACKER.lock().unwrap().status.active
+}
+
+/// 当前维护状态
+pub fn status() -> MaintenanceStatus {
+    TRACKER.lock().unwrap().status.clone()
+}
+
+/// 手动退出全局退避
+pub fn clear() {
+    let mut tracker = TRACKER.lock().unwrap();
+    if tracker.status.active {
+        tracker.finish("手动恢复", Utc::now());
+    }
+}
+
+async fn probe_once() -> Result<u16, String> {
+    let (credential_id, endpoint_type) = crate::provider::recovery_probe_target()
+        .await
+        .ok_or_else(|| "没有可用于探测的凭证".to_string())?;
+    let acquired = crate::provider::authorize_credential(&credential_id, endpoint_type)
+        .await
+        .map_err(|e| e.to_string())?;
+    crate::probe::send_test_request(&acquired, endpoint_type)
+        .await
+        .map(|(status, _)| status.as_u16())
+        .map_err(|e| e.to_string())
+}
+
+/// 后台任务：退避期间定期探测上游是否恢复
+pub async fn run_prober() {
+    loop {
+        let config = crate::config::get_config().maintenance;
+        let seconds = config.probe_interval_seconds.max(5);
+        tokio::time::sleep(std::time::Duration::from_secs(seconds)).await;
+        if !is_active() {
+            continue;
+        }
+        let result = probe_once().await;
+        debug!("维护窗口恢复探测: {:?}", result);
+        TRACKER
+            .lock()
+            .unwrap()
+            .record_probe(&config, result, Utc::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outage_requires_all_credentials() {
+        let config = MaintenanceConfig::default();
+        let mut tracker = OutageTracker::default();
+        let now = Utc::now();
+
+        assert!(!tracker.observe(&config, "a", Some(503), 3, now));
+        assert!(!tracker.observe(&config, "a", Some(502), 3, now));
+        // 其他错误不计入
+        assert!(!tracker.observe(&config, "b", Some(429), 3, now));
+        assert!(!tracker.observe(&config, "b", Some(503), 3, now));
+        assert!(!tracker.status().active);
+
+        assert!(tracker.observe(&config, "c", Some(502), 3, now));
+        assert!(tracker.status().active);
+        assert_eq!(tracker.status().failing_credentials, vec!["a", "b", "c"]);
+
+        // 连续成功两次才结束退避，中间的失败会重新计数
+        assert!(!tracker.observe(&config, "a", None, 3, now));
+        assert!(tracker.status().active);
+        assert!(tracker.observe(&config, "b", Some(503), 3, now));
+        assert!(!tracker.observe(&config, "a", None, 3, now));
+        assert!(tracker.status().active);
+        assert!(!tracker.observe(&config, "a", None, 3, now));
+        assert!(!tracker.status().active);
+    }
+
+    #[test]
+    fn test_failures_outside_window_expire() {
+        let config = MaintenanceConfig::default();
+        let mut tracker = OutageTracker::default();
+        let now = Utc::now();
+
+        tracker.observe(&config, "a", Some(503), 2, now - Duration::seconds(120));
+        assert!(!tracker.observe(&config, "b", Some(503), 2, now));
+
+        // 只有一个凭证时不触发全局退避
+        let mut tracker = OutageTracker::default();
+        assert!(!tracker.observe(&config, "a", Some(503), 1, now));
+        assert!(!tracker.observe(&config, "a", Some(503), 1, now));
+    }
+
+    #[test]
+    fn test_recovery_probe() {
+        let config = MaintenanceConfig::default();
+        let mut tracker = OutageTracker::default();
+        let now = Utc::now();
+        tracker.observe(&config, "a", Some(503), 2, now);
+        tracker.observe(&config, "b", Some(503), 2, now);
+        assert!(tracker.status().active);
+
+        tracker.record_probe(&config, Err("timeout".to_string()), now);
+        assert_eq!(
+            tracker.status().last_probe_error.as_deref(),
+            Some("timeout")
+        );
+        tracker.record_probe(&config, Ok(503), now);
+        assert!(tracker.status().active);
+        assert_eq!(tracker.status().last_probe_status, Some(503));
+
+        tracker.record_probe(&config, Ok(400), now);
+        assert!(tracker.status().active);
+        assert_eq!(tracker.status().recovery_successes, 1);
+        tracker.record_probe(&config, Ok(503), now);
+        assert_eq!(tracker.status().recovery_successes, 0);
+        tracker.record_probe(&config, Ok(400), now);
+        tracker.record_probe(&config, Ok(200), now);
+        assert!(!tracker.status().active);
+    }
+}
@@ -0,0 +1,305 @@
+//! 请求中间件链
+//!
+//! `transform_request` / `transform_response` 依次执行配置中 `middleware`
+//! 列出的中间件：请求按列表顺序执行，响应按相反顺序执行。
+//! 内置中间件为 `model_rewrite`、`generation_defaults`、`content_filter`，
+//! 嵌入本库的程序可通过 `register` 添加自定义中间件，`hook:<名称>` 引用
+//! `hooks` 中配置的 WASM 钩子。
+//!
+//! 中间件链（含编译好的过滤规则与钩子模块）按 profile 缓存，配置更新或
+//! 注册新的中间件后才重新构建，不会在每个请求、响应或流事件上重复构建。
+
+use crate::config::ProviderConfig;
+use crate::deprecation;
+use crate::filter::ContentFilter;
+use crate::hooks;
+use crate::params::apply_generation_defaults;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::debug;
+
+/// 中间件执行顺序（按名称）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MiddlewareOrder(pub Vec<String>);
+
+impl Default for MiddlewareOrder {
+    fn default() -> Self {
+        Self(BUILTIN.iter().map(|name| name.to_string()).collect())
+    }
+}
+
+/// 请求中间件
+#[async_trait]
+pub trait RequestMiddleware: Send + Sync {
+    /// 中间件名称（配置中引用的名字）
+    fn name(&self) -> &str;
+
+    /// 处理发往上游的请求
+    async fn on_request(&self, _request: &mut serde_json::Value) -> Result<()> {
+        Ok(())
+    }
+
+    /// 处理上游返回的完整响应
+    async fn on_response(&self, _response: &mut serde_json::Value) -> Result<()> {
+        Ok(())
+    }
+
+    /// 处理流式响应事件，默认与完整响应相同
+    async fn on_stream_chunk(&self, chunk: &mut serde_json::Value) -> Result<()> {
+        self.on_response(chunk).await
+    }
+}
+
+/// 中间件工厂：根据当前配置创建实例
+pub type MiddlewareFactory =
+    Arc<dyn Fn(&ProviderConfig) -> Result<Arc<dyn RequestMiddleware>> + Send + Sync>;
+
+/// 已构建的中间件链缓存（配置版本、注册表版本一致时有效）
+#[derive(Default)]
+struct ChainCache {
+    config_generation: u64,
+    registry_generation: u64,
+    chains: HashMap<Option<String>, Arc<Chain>>,
+}
+
+lazy_static::lazy_static! {
+    static ref CUSTOM: RwLock<HashMap<String, MiddlewareFactory>> = RwLock::new(HashMap::new());
+    /// 注册表版本，每次注册加一
+    static ref REGISTRY_GENERATION: AtomicU64 = AtomicU64::new(0);
+    static ref CACHE: Mutex<ChainCache> = Mutex::new(ChainCache::default());
+}
+
+/// 注册自定义中间件，之后可在配置的 `middleware` 列表中按名称引用
+pub fn register(name: &str, factory: MiddlewareFactory) {
+    CUSTOM.write().unwrap().insert(name.to_string(), factory);
+    REGISTRY_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// 内置中间件名称
+pub const BUILTIN: &[&str] = &["model_rewrite", "generation_defaults", "content_filter"];
+
+/// 可用的中间件名称（内置在前，自定义按名称排序）
+pub fn available() -> Vec<String> {
+    let mut custom: Vec<String> = CUSTOM.read().unwrap().keys().cloned().collect();
+    custom.sort();
+    BUILTIN
+        .iter()
+        .map(|name| name.to_string())
+        .chain(custom)
+        .collect()
+}
+
+/// 弃用模型改写为继任模型
+struct ModelRewrite;
+
+#[async_trait]
+impl RequestMiddleware for ModelRewrite {
+    fn name(&self) -> &str {
+        "model_rewrite"
+    }
+
+    async fn on_request(&self, request: &mut serde_json::Value) -> Result<()> {
+        if let Some(model) = request.get("model").and_then(|m| m.as_str()) {
+            let resolved = deprecation::resolve_model(model);
+            if resolved != model {
+                debug!("模型改写: {} -> {}", model, resolved);
+                request["model"] = serde_json::json!(resolved);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 按模型家族补充默认生成参数
+struct GenerationDefaults(HashMap<String, crate::params::GenerationDefaults>);
+
+#[async_trait]
+impl RequestMiddleware for GenerationDefaults {
+    fn name(&self) -> &str {
+        "generation_defaults"
+    }
+
+    async fn on_request(&self, request: &mut serde_json::Value) -> Result<()> {
+        apply_generation_defaults(request, &self.0);
+        Ok(())
+    }
+}
+
+/// 响应内容过滤
+struct ContentFilterMiddleware(ContentFilter);
+
+#[async_trait]
+impl RequestMiddleware for ContentFilterMiddleware {
+    fn name(&self) -> &str {
+        "content_filter"
+    }
+
+    async fn on_response(&self, response: &mut serde_json::Value) -> Result<()> {
+        self.0.apply_json(response)
+    }
+}
+
+/// 按配置构建中间件链，名称未知时返回错误
+pub fn build_chain(config: &ProviderConfig) -> Result<Vec<Arc<dyn RequestMiddleware>>> {
+    let custom = CUSTOM.read().unwrap();
+    config
+        .middleware
+        .0
+        .iter()
+        .map(|name| -> Result<Arc<dyn RequestMiddleware>> {
+            Ok(match name.as_str() {
+                "model_rewrite" => Arc::new(ModelRewrite),
+                "generation_defaults" => {
+                    Arc::new(GenerationDefaults(config.generation_defaults.clone()))
+                }
+                "content_filter" => Arc::new(ContentFilterMiddleware(ContentFilter::compile(
+                    &config.content_filter,
+                )?)),
+                other if other.starts_with(hooks::MIDDLEWARE_PREFIX) => {
+                    hooks::middleware(config, other)?
+                }
+                other => match custom.get(other) {
+                    Some(factory) => factory(config)?,
+                    None => anyhow::bail!("未知的中间件: {}", other),
+                },
+            })
+        })
+        .collect()
+}
+
+/// 构建好的中间件链
+pub struct Chain(Vec<Arc<dyn RequestMiddleware>>);
+
+impl Chain {
+    pub fn new(middleware: Vec<Arc<dyn RequestMiddleware>>) -> Self {
+        Self(middleware)
+    }
+
+    /// 按顺序处理请求
+    pub async fn run_request(&self, request: &mut serde_json::Value) -> Result<()> {
+        for middleware in &self.0 {
+            middleware.on_request(request).await?;
+        }
+        Ok(())
+    }
+
+    /// 按相反顺序处理响应
+    pub async fn run_response(&self, response: &mut serde_json::Value) -> Result<()> {
+        for middleware in self.0.iter().rev() {
+            middleware.on_response(response).await?;
+        }
+        Ok(())
+    }
+
+    /// 按相反顺序处理流式响应事件
+    pub async fn run_stream_chunk(&self, chunk: &mut serde_json::Value) -> Result<()> {
+        for middleware in self.0.iter().rev() {
+            middleware.on_stream_chunk(chunk).await?;
+        }
+        Ok(())
+    }
+}
+
+/// 当前配置下某个 profile 的中间件链（缓存，配置或注册表变化后重建）
+pub fn chain(profile: Option<&str>) -> Result<Arc<Chain>> {
+    let (config_generation, config) = crate::config::get_config_with_generation();
+    let registry_generation = REGISTRY_GENERATION.load(Ordering::SeqCst);
+    let key = profile
+        .filter(|name| config.profiles.profiles.contains_key(*name))
+        .map(str::to_string);
+    {
+        let mut cache = CACHE.lock().unwrap();
+        if cache.config_generation != config_generation
+            || cache.registry_generation != registry_generation
+        {
+            *cache = ChainCache {
+                config_generation,
+                registry_generation,
+                chains: HashMap::new(),
+            };
+        }
+        if let Some(chain) = cache.chains.get(&key) {
+            return Ok(chain.clone());
+        }
+    }
+
+    // 构建可能编译钩子模块，不持有缓存锁
+    let effective = match key.as_deref() {
+        Some(name) => crate::profiles::effective_config(&config, &config.profiles.profiles[name]),
+        None => config,
+    };
+    let chain = Arc::new(Chain::new(build_chain(&effective)?));
+    let mut cache = CACHE.lock().unwrap();
+    if cache.config_generation == config_generation
+        && cache.registry_generation == registry_generation
+    {
+        cache.chains.insert(key, chain.clone());
+    }
+    Ok(chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Tag(&'static str);
+
+    #[async_trait]
+    impl RequestMiddleware for Tag {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        async fn on_request(&self, request: &mut serde_json::Value) -> Result<()> {
+            let trail = request["trail"].as_str().unwrap_or("").to_string();
+            request["trail"] = serde_json::json!(format!("{}{}", trail, self.0));
+            Ok(())
+        }
+
+        async fn on_response(&self, response: &mut serde_json::Value) -> Result<()> {
+            self.on_request(response).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chain_order() {
+        register("tag_a", Arc::new(|_| Ok(Arc::new(Tag("a")))));
+        register("tag_b", Arc::new(|_| Ok(Arc::new(Tag("b")))));
+        let config = ProviderConfig {
+            middleware: MiddlewareOrder(vec!["tag_a".to_string(), "tag_b".to_string()]),
+            ..Default::default()
+        };
+
+        let chain = Chain::new(build_chain(&config).unwrap());
+        let mut request = serde_json::json!({});
+        chain.run_request(&mut request).await.unwrap();
+        assert_eq!(request["trail"], "ab");
+
+        let mut response = serde_json::json!({});
+        chain.run_response(&mut response).await.unwrap();
+        assert_eq!(response["trail"], "ba");
+    }
+
+    #[test]
+    fn test_chain_is_cached_until_registry_changes() {
+        let first = chain(None).unwrap();
+        assert!(Arc::ptr_eq(&first, &chain(None).unwrap()));
+        register("tag_cache", Arc::new(|_| Ok(Arc::new(Tag("c")))));
+        assert!(!Arc::ptr_eq(&first, &chain(None).unwrap()));
+    }
+
+    #[test]
+    fn test_unknown_middleware() {
+        let config = ProviderConfig {
+            middleware: MiddlewareOrder(vec!["does_not_exist".to_string()]),
+            ..Default::default()
+        };
+        assert!(build_chain(&config).is_err());
+        assert_eq!(build_chain(&ProviderConfig::default()).unwrap().len(), 3);
+    }
+}
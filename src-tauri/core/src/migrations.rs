@@ -0,0 +1,202 @@
+//! 凭证文件结构迁移
+//!
+//! 凭证文件带 `schema_version`，加载时按版本依次执行迁移函数（v1 → v2 → …），
+//! 再反序列化为当前的 `DroidCredentials`。未加版本号的旧文件（直接是
+//! ID → 凭证的映射）视为 v1。遇到比当前程序更新的版本时拒绝加载并先备份
+//! 原文件，避免旧版本程序按自己的结构写回导致新字段丢失。
+//!
+//! 新增需要转换的字段时：`CURRENT_VERSION` 加一，在 `MIGRATIONS` 末尾追加
+//! 迁移函数并补充测试。只新增带默认值的字段不需要迁移。
+
+use crate::auth::token_seal;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// 当前凭证文件结构版本
+pub const CURRENT_VERSION: u32 = 3;
+
+/// 单个凭证的迁移函数，输入为旧版本的凭证 JSON
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// 按起始版本排列的迁移，第 i 项把 v(i+1) 迁移到 v(i+2)
+const MIGRATIONS: &[Migration] = &[v1_to_v2, v2_to_v3];
+
+const _: () = assert!(MIGRATIONS.len() == CURRENT_VERSION as usize - 1);
+
+/// 带版本号的凭证文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialsFile<T> {
+    pub schema_version: u32,
+    pub credentials: T,
+}
+
+/// 文件版本比程序支持的更新
+#[derive(Debug, thiserror::Error)]
+#[error("凭证文件版本 v{found} 高于当前程序支持的 v{supported}，请升级程序")]
+pub struct FutureVersion {
+    pub found: u32,
+    pub supported: u32,
+}
+
+/// 迁移结果
+#[derive(Debug)]
+pub struct Migrated {
+    /// 迁移前的版本
+    pub from_version: u32,
+    /// 当前版本的凭证映射
+    pub credentials: Map<String, Value>,
+}
+
+impl Migrated {
+    pub fn changed(&self) -> bool {
+        self.from_version != CURRENT_VERSION
+    }
+}
+
+/// 把任意版本的凭证文件迁移到当前版本
+pub fn migrate(document: Value) -> Result<Migrated> {
+    let (version, credentials) = match document {
+        Value::Object(mut map) if map.contains_key("schema_version") => {
+            let version = map["schema_version"]
+                .as_u64()
+                .ok_or_else(|| anyhow::anyhow!("schema_version 不是整数"))?
+                as u32;
+            let credentials = match map.remove("credentials") {
+                Some(Value::Object(credentials)) => credentials,
+                None => Map::new(),
+                Some(_) => anyhow::bail!("credentials 不是对象"),
+            };
+            (version, credentials)
+        }
+        // 未加版本号的旧文件
+        Value::Object(map) => (1, map),
+        _ => anyhow::bail!("凭证文件格式无效"),
+    };
+
+    if version > CURRENT_VERSION {
+        return Err(FutureVersion {
+            found: version,
+            supported: CURRENT_VERSION,
+        }
+        .into());
+    }
+    if version == 0 {
+        anyhow::bail!("无效的 schema_version: 0");
+    }
+
+    let mut credentials = credentials;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        for (id, credential) in credentials.iter_mut() {
+            let Value::Object(credential) = credential else {
+                anyhow::bail!("凭证 {} 格式无效", id);
+            };
+            migration(credential)
+                .map_err(|e| anyhow::anyhow!("凭证 {} 从 v{} 迁移失败: {}", id, index + 1, e))?;
+        }
+    }
+
+    Ok(Migrated {
+        from_version: version,
+        credentials,
+    })
+}
+
+/// v1 → v2：单条 `last_error` 改为 `recent_errors` 错误记录列表
+fn v1_to_v2(credential: &mut Map<String, Value>) -> Result<()> {
+    let Some(last_error) = credential.remove("last_error") else {
+        return Ok(());
+    };
+    let Some(message) = last_error.as_str().filter(|m| !m.is_empty()) else {
+        return Ok(());
+    };
+    // 旧字段没有时间，用最后刷新时间近似
+    let timestamp = credential
+        .get("last_refresh")
+        .and_then(Value::as_str)
+        .map(String::from)
+        .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+
+    let errors = credential
+        .entry("recent_errors")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    if let Value::Array(errors) = errors {
+        if errors.is_empty() {
+            errors.push(serde_json::json!({ "timestamp": timestamp, "message": message }));
+        }
+    }
+    Ok(())
+}
+
+/// v2 → v3：OAuth Token 改为加密保存（见 `auth::token_seal`）
+fn v2_to_v3(credential: &mut Map<String, Value>) -> Result<()> {
+    for field in ["access_token", "refresh_token"] {
+        if let Some(Value::String(token)) = credential.get_mut(field) {
+            *token = token_seal::seal_value(token)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credentials::DroidCredentials;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_v1_to_v2() {
+        let legacy = serde_json::json!({
+            "cred-1": {
+                "access_token": "at",
+                "refresh_token": "rt",
+                "expires_at": null,
+                "organization_id": null,
+                "user_id": null,
+                "owner_email": null,
+                "owner_name": null,
+                "last_refresh": "2025-01-01T00:00:00Z",
+                "last_error": "401 Unauthorized"
+            }
+        });
+
+        let migrated = migrate(legacy).unwrap();
+        assert_eq!(migrated.from_version, 1);
+        assert!(migrated.changed());
+
+        let credentials: HashMap<String, DroidCredentials> =
+            serde_json::from_value(Value::Object(migrated.credentials)).unwrap();
+        let errors = &credentials["cred-1"].recent_errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message.as_deref(), Some("401 Unauthorized"));
+        assert_eq!(errors[0].timestamp, "2025-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_v2_to_v3_seals_tokens() {
+        let document = serde_json::json!({
+            "schema_version": 2,
+            "credentials": {
+                "cred-1": { "access_token": "at", "refresh_token": "rt" }
+            }
+        });
+        let migrated = migrate(document).unwrap();
+        let credential = &migrated.credentials["cred-1"];
+        let sealed = credential["refresh_token"].as_str().unwrap();
+        assert!(token_seal::is_sealed(sealed));
+        assert_eq!(token_seal::open_value(sealed).unwrap(), "rt");
+        assert!(token_seal::is_sealed(
+            credential["access_token"].as_str().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_current_and_future_versions() {
+        let current = serde_json::json!({ "schema_version": CURRENT_VERSION, "credentials": {} });
+        assert!(!migrate(current).unwrap().changed());
+
+        let future = serde_json::json!({ "schema_version": CURRENT_VERSION + 1 });
+        let error = migrate(future).unwrap_err();
+        assert!(error.downcast_ref::<FutureVersion>().is_some());
+    }
+}
@@ -0,0 +1,251 @@
+//! 模拟模式
+//!
+//! 供前端开发使用：开启后 acquire / refresh / 转发不再访问 Factory，
+//! 而是返回固定的假数据（假模型、固定的流式响应、按脚本出现的错误），
+//! 无需真实账号即可调通全部命令。通过配置 `mock.enabled` 或环境变量
+//! `DROID_PROVIDER_MOCK=1` 开启。
+
+use crate::config::get_config;
+use crate::credentials::{AcquiredCredential, TokenRefreshResult};
+use crate::provider::{ModelInfo, ENDPOINT_ANTHROPIC};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 模拟凭证 ID
+pub const MOCK_CREDENTIAL_ID: &str = "mock-credential";
+/// 模拟上游地址
+pub const MOCK_BASE_URL: &str = "mock://factory.ai/api/llm";
+
+static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+static LEASE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 模拟模式配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MockConfig {
+    pub enabled: bool,
+    /// 依次循环返回的状态码，200 表示成功（为空时总是成功）
+    pub script: Vec<u16>,
+    /// 固定的回复内容
+    pub response_text: String,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            script: Vec::new(),
+            response_text: "This is a mock response from droid-provider.".to_string(),
+        }
+    }
+}
+
+/// 模拟转发结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockResponse {
+    pub status_code: u16,
+    /// 非流式响应体或错误体
+    #[serde(default)]
+    pub body: Option<serde_json::Value>,
+    /// 流式响应事件（SSE data）
+    #[serde(default)]
+    pub events: Vec<serde_json::Value>,
+}
+
+/// 是否处于模拟模式
+pub fn is_enabled() -> bool {
+    get_config().mock.enabled
+        || std::env::var("DROID_PROVIDER_MOCK").is_ok_and(|v| v == "1" || v == "true")
+}
+
+/// 假模型列表
+pub fn models() -> Vec<ModelInfo> {
+    [
+        ("mock-claude", "Mock Claude", 200_000),
+        ("mock-gpt", "Mock GPT", 128_000),
+    ]
+    .into_iter()
+    .map(|(id, name, context)| ModelInfo {
+        id: id.to_string(),
+        display_name: name.to_string(),
+        family: Some("mock".to_string()),
+        context_length: Some(context),
+        supports_vision: true,
+        supports_tools: true,
+        pricing: None,
+        availability: None,
+    })
+    .collect()
+}
+
+/// 模拟获取凭证
+pub fn acquire(model: &str) -> AcquiredCredential {
+    let lease = LEASE_COUNTER.fetch_add(1, Ordering::Relaxed) + 1;
+    AcquiredCredential {
+        id: MOCK_CREDENTIAL_ID.to_string(),
+        name: Some("Mock".to_string()),
+        auth_type: "oauth".to_string(),
+        base_url: Some(format!("{}{}", MOCK_BASE_URL, ENDPOINT_ANTHROPIC)),
+        headers: HashMap::from([
+            (
+                "Authorization".to_string(),
+                "Bearer mock-access-token".to_string(),
+            ),
+            ("Content-Type".to_string(), "application/json".to_string()),
+        ]),
+        metadata: HashMap::from([
+            (
+                "lease_id".to_string(),
+                serde_json::json!(format!("mock-lease-{}", lease)),
+            ),
+            ("model".to_string(), serde_json::json!(model)),
+            ("mock".to_string(), serde_json::json!(true)),
+        ]),
+    }
+}
+
+/// 模拟刷新 Token
+pub fn refresh() -> TokenRefreshResult {
+    TokenRefreshResult {
+        access_token: "mock-access-token".to_string(),
+        refresh_token: Some("mock-refresh-token".to_string()),
+        expires_at: Some(chrono::Utc::now() + chrono::Duration::hours(8)),
+        organization_id: Some("org_mock".to_string()),
+        user_id: Some("user_mock".to_string()),
+        owner_email: Some("mock@example.com".to_string()),
+    }
+}
+
+/// 第 n 次请求（从 0 开始）按脚本应返回的状态码
+pub fn scripted_status(script: &[u16], n: u64) -> u16 {
+    if script.is_empty() {
+        return 200;
+    }
+    script[(n % script.len() as u64) as usize]
+}
+
+/// 错误状态码对应的 Anthropic 错误体
+fn error_body(status: u16) -> serde_json::Value {
+    let (error_type, message) = match status {
+        401 => ("authentication_error", "Mock: invalid access token"),
+        403 => ("permission_error", "Mock: forbidden"),
+        429 => ("rate_limit_error", "Mock: rate limited"),
+        529 => ("overloaded_error", "Mock: overloaded"),
+        _ => ("api_error", "Mock: upstream error"),
+    };
+    serde_json::json!({
+        "type": "error",
+        "error": { "type": error_type, "message": message }
+    })
+}
+
+/// 构建固定响应（Anthropic Messages 格式）
+pub fn build_response(
+    request: &serde_json::Value,
+    config: &MockConfig,
+    status: u16,
+    stream: bool,
+) -> MockResponse {
+    if status != 200 {
+        return MockResponse {
+            status_code: status,
+            body: Some(error_body(status)),
+            events: Vec::new(),
+        };
+    }
+
+    let model = request["model"].as_str().unwrap_or("mock-claude");
+    let text = &config.response_text;
+    let output_tokens = text.split_whitespace().count() as u64;
+    let message = serde_json::json!({
+        "id": "msg_mock",
+        "type": "message",
+        "role": "assistant",
+        "model": model,
+        "content": [{ "type": "text", "text": text }],
+        "stop_reason": "end_turn",
+        "usage": { "input_tokens": 10, "output_tokens": output_tokens }
+    });
+    if !stream {
+        return MockResponse {
+            status_code: 200,
+            body: Some(message),
+            events: Vec::new(),
+        };
+    }
+
+    let mut start = message.clone();
+    start["content"] = serde_json::json!([]);
+    start["stop_reason"] = serde_json::Value::Null;
+    let mut events = vec![
+        serde_json::json!({ "type": "message_start", "message": start }),
+        serde_json::json!({
+            "type": "content_block_start",
+            "index": 0,
+            "content_block": { "type": "text", "text": "" }
+        }),
+    ];
+    events.extend(text.split_inclusive(' ').map(|word| {
+        serde_json::json!({
+            "type": "content_block_delta",
+            "index": 0,
+            "delta": { "type": "text_delta", "text": word }
+        })
+    }));
+    events.push(serde_json::json!({ "type": "content_block_stop", "index": 0 }));
+    events.push(serde_json::json!({
+        "type": "message_delta",
+        "delta": { "stop_reason": "end_turn" },
+        "usage": { "output_tokens": output_tokens }
+    }));
+    events.push(serde_json::json!({ "type": "message_stop" }));
+
+    MockResponse {
+        status_code: 200,
+        body: None,
+        events,
+    }
+}
+
+/// 模拟转发请求，按脚本依次返回成功或错误
+pub fn forward(request: &serde_json::Value) -> MockResponse {
+    let config = get_config().mock;
+    let n = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let stream = request["stream"].as_bool().unwrap_or(false);
+    build_response(request, &config, scripted_status(&config.script, n), stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripted_status_cycles() {
+        assert_eq!(scripted_status(&[], 5), 200);
+        let script = [200, 429, 401];
+        let statuses: Vec<_> = (0..4).map(|n| scripted_status(&script, n)).collect();
+        assert_eq!(statuses, [200, 429, 401, 200]);
+    }
+
+    #[test]
+    fn test_canned_stream() {
+        let config = MockConfig {
+            response_text: "hello mock world".to_string(),
+            ..Default::default()
+        };
+        let request = serde_json::json!({ "model": "mock-claude" });
+
+        let response = build_response(&request, &config, 200, true);
+        let text: String = response
+            .events
+            .iter()
+            .filter_map(|e| e["delta"]["text"].as_str())
+            .collect();
+        assert_eq!(text, "hello mock world");
+        assert_eq!(response.events.last().unwrap()["type"], "message_stop");
+
+        let error = build_response(&request, &config, 429, false);
+        assert_eq!(error.body.unwrap()["error"]["type"], "rate_limit_error");
+    }
+}
@@ -0,0 +1,169 @@
+//! 用户自定义模型能力
+//!
+//! 内置的 context_length / supports_tools / 价格会与 Factory 实际限制脱节，
+//! 允许用户覆盖这些字段。覆盖项保存在数据目录的 `model_overrides.json`，
+//! 并在 `list_models` 时合并到内置列表之上。
+
+use crate::config::data_dir;
+use crate::pricing::ModelPricing;
+use crate::provider::ModelInfo;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tracing::warn;
+
+/// 覆盖文件名
+pub const MODEL_OVERRIDES_FILE: &str = "model_overrides.json";
+
+/// 单个模型的覆盖项，未设置的字段沿用内置值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supports_vision: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supports_tools: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<ModelPricing>,
+}
+
+impl ModelOverride {
+    /// 应用到模型信息
+    pub fn apply(&self, model: &mut ModelInfo) {
+        if let Some(ref display_name) = self.display_name {
+            model.display_name = display_name.clone();
+        }
+        if let Some(ref family) = self.family {
+            model.family = Some(family.clone());
+        }
+        if let Some(context_length) = self.context_length {
+            model.context_length = Some(context_length);
+        }
+        if let Some(supports_vision) = self.supports_vision {
+            model.supports_vision = supports_vision;
+        }
+        if let Some(supports_tools) = self.supports_tools {
+            model.supports_tools = supports_tools;
+        }
+        if let Some(pricing) = self.pricing {
+            model.pricing = Some(pricing);
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref OVERRIDES: RwLock<Option<BTreeMap<String, ModelOverride>>> = RwLock::new(None);
+}
+
+fn overrides_path() -> PathBuf {
+    data_dir().join(MODEL_OVERRIDES_FILE)
+}
+
+fn load_from_disk() -> BTreeMap<String, ModelOverride> {
+    match std::fs::read_to_string(overrides_path()) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("模型覆盖文件解析失败，已忽略: {}", e);
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+fn save_to_disk(overrides: &BTreeMap<String, ModelOverride>) -> Result<()> {
+    crate::store::write_json(&overrides_path(), overrides)
+}
+
+/// 获取全部覆盖项
+pub fn get_overrides() -> BTreeMap<String, ModelOverride> {
+    if let Some(overrides) = OVERRIDES.read().unwrap().clone() {
+        return overrides;
+    }
+    let overrides = load_from_disk();
+    *OVERRIDES.write().unwrap() = Some(overrides.clone());
+    overrides
+}
+
+/// 设置某个模型的覆盖项
+pub fn set_override(model: &str, model_override: ModelOverride) -> Result<()> {
+    let mut overrides = get_overrides();
+    overrides.insert(model.to_string(), model_override);
+    save_to_disk(&overrides)?;
+    *OVERRIDES.write().unwrap() = Some(overrides);
+    Ok(())
+}
+
+/// 删除某个模型的覆盖项
+pub fn remove_override(model: &str) -> Result<bool> {
+    let mut overrides = get_overrides();
+    let removed = overrides.remove(model).is_some();
+    save_to_disk(&overrides)?;
+    *OVERRIDES.write().unwrap() = Some(overrides);
+    Ok(removed)
+}
+
+/// 某个模型的覆盖价格
+pub fn pricing_for(model: &str) -> Option<ModelPricing> {
+    get_overrides().get(model).and_then(|o| o.pricing)
+}
+
+/// 将覆盖项合并到模型列表；不在内置列表中的模型作为新条目追加
+pub fn merge(mut models: Vec<ModelInfo>) -> Vec<ModelInfo> {
+    for (id, model_override) in get_overrides() {
+        match models.iter_mut().find(|m| m.id == id) {
+            Some(model) => model_override.apply(model),
+            None => {
+                let mut model = ModelInfo {
+                    id: id.clone(),
+                    display_name: id.clone(),
+                    family: None,
+                    context_length: None,
+                    supports_vision: false,
+                    supports_tools: false,
+                    pricing: None,
+                    availability: None,
+                };
+                model_override.apply(&mut model);
+                models.push(model);
+            }
+        }
+    }
+    models
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_apply() {
+        let mut model = ModelInfo {
+            id: "claude-sonnet-4-20250514".to_string(),
+            display_name: "Claude Sonnet 4".to_string(),
+            family: Some("sonnet".to_string()),
+            context_length: Some(200000),
+            supports_vision: true,
+            supports_tools: true,
+            pricing: None,
+            availability: None,
+        };
+
+        ModelOverride {
+            context_length: Some(1_000_000),
+            supports_tools: Some(false),
+            ..Default::default()
+        }
+        .apply(&mut model);
+
+        assert_eq!(model.context_length, Some(1_000_000));
+        assert!(!model.supports_tools);
+        assert!(model.supports_vision);
+        assert_eq!(model.display_name, "Claude Sonnet 4");
+    }
+}
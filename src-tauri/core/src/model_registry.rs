@@ -0,0 +1,127 @@
+//! 动态模型注册表
+//!
+//! Factory 企业组织会提供不以 `claude-` / `gpt-` 开头的自定义模型 ID。
+//! 凭证可以声明额外的模型条目，同一组织下的凭证共享这些条目；
+//! 没有组织 ID 的凭证只对自身生效。
+
+use crate::credentials::{CustomModel, DroidCredentials};
+use std::collections::HashMap;
+
+/// 内置模型家族前缀
+const BUILTIN_MODEL_PREFIXES: &[&str] = &["claude-", "gpt-"];
+
+/// 是否属于内置模型家族
+pub fn is_builtin_family(model: &str) -> bool {
+    BUILTIN_MODEL_PREFIXES
+        .iter()
+        .any(|prefix| model.starts_with(prefix))
+}
+
+/// 凭证所在的分组（组织 ID，无组织时为凭证 ID）
+fn group_key(credential_id: &str, credential: &DroidCredentials) -> String {
+    credential
+        .organization_id
+        .clone()
+        .unwrap_or_else(|| credential_id.to_string())
+}
+
+/// 按组织汇总的自定义模型
+#[derive(Debug, Default)]
+pub struct ModelRegistry {
+    groups: HashMap<String, Vec<CustomModel>>,
+}
+
+impl ModelRegistry {
+    /// 从当前凭证构建
+    pub fn build<'a>(
+        credentials: impl IntoIterator<Item = (&'a String, &'a DroidCredentials)>,
+    ) -> Self {
+        let mut groups: HashMap<String, Vec<CustomModel>> = HashMap::new();
+        for (id, credential) in credentials {
+            let models = groups.entry(group_key(id, credential)).or_default();
+            for model in &credential.extra_models {
+                if !models.iter().any(|m| m.id == model.id) {
+                    models.push(model.clone());
+                }
+            }
+        }
+        Self { groups }
+    }
+
+    /// 凭证可用的自定义模型条目
+    pub fn custom_model(
+        &self,
+        credential_id: &str,
+        credential: &DroidCredentials,
+        model: &str,
+    ) -> Option<&CustomModel> {
+        self.groups
+            .get(&group_key(credential_id, credential))?
+            .iter()
+            .find(|m| m.id == model)
+    }
+
+    /// 是否有任一凭证提供该模型
+    pub fn contains(&self, model: &str) -> bool {
+        self.groups.values().flatten().any(|m| m.id == model)
+    }
+
+    /// 全部自定义模型（按 ID 去重）
+    pub fn models(&self) -> Vec<CustomModel> {
+        let mut models: Vec<CustomModel> = Vec::new();
+        for model in self.groups.values().flatten() {
+            if !models.iter().any(|m| m.id == model.id) {
+                models.push(model.clone());
+            }
+        }
+        models.sort_by(|a, b| a.id.cmp(&b.id));
+        models
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credentials::EndpointType;
+
+    fn credential(org: Option<&str>, models: &[&str]) -> DroidCredentials {
+        DroidCredentials {
+            organization_id: org.map(|o| o.to_string()),
+            extra_models: models
+                .iter()
+                .map(|id| CustomModel {
+                    id: id.to_string(),
+                    display_name: None,
+                    endpoint_type: EndpointType::OpenAI,
+                    context_length: None,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_models_shared_within_org() {
+        let creds = HashMap::from([
+            ("a".to_string(), credential(Some("org_1"), &["acme-coder"])),
+            ("b".to_string(), credential(Some("org_1"), &[])),
+            ("c".to_string(), credential(None, &[])),
+        ]);
+        let registry = ModelRegistry::build(&creds);
+
+        assert!(registry.contains("acme-coder"));
+        assert!(registry
+            .custom_model("b", &creds["b"], "acme-coder")
+            .is_some());
+        assert!(registry
+            .custom_model("c", &creds["c"], "acme-coder")
+            .is_none());
+        assert_eq!(registry.models().len(), 1);
+    }
+
+    #[test]
+    fn test_builtin_family() {
+        assert!(is_builtin_family("claude-sonnet-4-20250514"));
+        assert!(!is_builtin_family("acme-coder"));
+    }
+}
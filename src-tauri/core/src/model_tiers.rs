@@ -0,0 +1,64 @@
+//! 按模型限制凭证等级
+//!
+//! 为昂贵的模型（如 `claude-opus-*`）设置最低凭证等级，只有等级达到要求的
+//! 凭证才会被选中，避免代理误用额度有限的个人账号跑 Opus。
+//! 凭证等级默认为 0，多条规则同时匹配时取最高要求。
+
+use crate::credentials::matches_model_pattern;
+use serde::{Deserialize, Serialize};
+
+/// 单条规则
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelTierRule {
+    /// 模型名，支持 `claude-opus-*` 这样的前缀通配
+    pub model: String,
+    /// 最低凭证等级
+    pub min_tier: u8,
+}
+
+/// 模型等级配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelTierConfig {
+    pub rules: Vec<ModelTierRule>,
+}
+
+impl ModelTierConfig {
+    /// 模型要求的最低凭证等级，没有规则匹配时为 0
+    pub fn required_tier(&self, model: &str) -> u8 {
+        self.rules
+            .iter()
+            .filter(|rule| matches_model_pattern(&rule.model, model))
+            .map(|rule| rule.min_tier)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_tier() {
+        let config = ModelTierConfig {
+            rules: vec![
+                ModelTierRule {
+                    model: "claude-opus-*".to_string(),
+                    min_tier: 2,
+                },
+                ModelTierRule {
+                    model: "claude-opus-4-1-20250805".to_string(),
+                    min_tier: 3,
+                },
+            ],
+        };
+        assert_eq!(config.required_tier("claude-opus-4-20250514"), 2);
+        assert_eq!(config.required_tier("claude-opus-4-1-20250805"), 3);
+        assert_eq!(config.required_tier("claude-sonnet-4-20250514"), 0);
+        assert_eq!(
+            ModelTierConfig::default().required_tier("claude-opus-4-20250514"),
+            0
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! 组织凭证自动发现
+//!
+//! 一个 Factory 账号可以属于多个组织（工作区）。登录后查询账号所属的组织，
+//! 组织多于一个时提示用户为每个组织创建一个凭证。这些凭证共享同一个
+//! Refresh Token（同属一个刷新组），只是 `organization_id` 不同；
+//! 任一成员刷新导致 Token 轮换时，新 Token 同步给组内其他成员。
+
+use crate::credentials::DroidCredentials;
+use crate::health::HealthStats;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// 发现的组织
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveredOrg {
+    pub organization_id: String,
+    /// 组织名称（已解析时）
+    #[serde(default)]
+    pub organization_name: Option<String>,
+    /// 已为该组织创建的凭证
+    #[serde(default)]
+    pub credential_id: Option<String>,
+}
+
+/// 凭证所在的刷新组（未加入任何组时为自身 ID）
+pub fn refresh_group(credential_id: &str, credential: &DroidCredentials) -> String {
+    credential
+        .refresh_group
+        .clone()
+        .unwrap_or_else(|| credential_id.to_string())
+}
+
+/// 标记组织是否已有同一账号的凭证
+pub fn match_existing(
+    org_ids: Vec<String>,
+    source: &DroidCredentials,
+    credentials: &HashMap<String, DroidCredentials>,
+) -> Vec<DiscoveredOrg> {
+    org_ids
+        .into_iter()
+        .map(|organization_id| {
+            let credential_id = credentials
+                .iter()
+                .find(|(_, c)| {
+                    c.organization_id.as_deref() == Some(organization_id.as_str())
+                        && c.user_id.is_some()
+                        && c.user_id == source.user_id
+                })
+                .map(|(id, _)| id.clone());
+            DiscoveredOrg {
+                organization_name: crate::org_names::lookup(&organization_id),
+                organization_id,
+                credential_id,
+            }
+        })
+        .collect()
+}
+
+/// 由源凭证派生某个组织的凭证（Access Token 需按组织重新刷新获取）
+pub fn org_credential(source: &DroidCredentials, group: &str, org_id: &str) -> DroidCredentials {
+    let name = source.name.as_deref().unwrap_or("Factory");
+    DroidCredentials {
+        name: Some(format!("{} ({})", name, org_id)),
+        organization_id: Some(org_id.to_string()),
+        refresh_group: Some(group.to_string()),
+        access_token: None,
+        expires_at: None,
+        supported_endpoints: Vec::new(),
+        last_refresh: None,
+        health_score: 100,
+        health: HealthStats::default(),
+        usage_count: 0,
+        error_count: 0,
+        recent_errors: VecDeque::new(),
+        cooldown_until: None,
+        endpoint_failures: HashMap::new(),
+        extra_models: Vec::new(),
+        ..source.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_org_credential_shares_refresh_token() {
+        let source = DroidCredentials {
+            name: Some("Work".to_string()),
+            access_token: Some("at".to_string()),
+            refresh_token: Some("rt".to_string()),
+            organization_id: Some("org_1".to_string()),
+            user_id: Some("user_1".to_string()),
+            ..Default::default()
+        };
+        let derived = org_credential(&source, "cred-1", "org_2");
+        assert_eq!(derived.refresh_token.as_deref(), Some("rt"));
+        assert_eq!(derived.organization_id.as_deref(), Some("org_2"));
+        assert_eq!(derived.refresh_group.as_deref(), Some("cred-1"));
+        assert!(derived.access_token.is_none());
+
+        let credentials = HashMap::from([("cred-1".to_string(), source.clone())]);
+        let orgs = match_existing(
+            vec!["org_1".to_string(), "org_2".to_string()],
+            &source,
+            &credentials,
+        );
+        assert_eq!(orgs[0].credential_id.as_deref(), Some("cred-1"));
+        assert!(orgs[1].credential_id.is_none());
+    }
+}
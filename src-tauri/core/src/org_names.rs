@@ -0,0 +1,141 @@
+//! 组织显示名称
+//!
+//! 凭证只记录 `org_01H...` 形式的组织 ID，在界面上没有意义。组织名称来自
+//! Factory 组织接口（返回名称时）和 WorkOS 的组织选择挑战，解析后缓存在
+//! `org_names.json` 中，凭证列表与使用量报表据此附上 `organization_name`。
+//! 查不到名称的组织也记录查询时间，过期前不再重复请求。
+
+use crate::auth::workos::WorkOSOrganization;
+use crate::config::data_dir;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::warn;
+
+/// 缓存文件名
+pub const ORG_NAMES_FILE: &str = "org_names.json";
+
+/// 缓存有效期，过期后重新查询（组织可能改名）
+const CACHE_TTL_DAYS: i64 = 7;
+
+/// 缓存条目
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrgName {
+    /// 组织名称，接口未返回时为空
+    #[serde(default)]
+    pub name: Option<String>,
+    pub resolved_at: String,
+}
+
+impl OrgName {
+    fn is_fresh(&self, now: DateTime<Utc>) -> bool {
+        DateTime::parse_from_rfc3339(&self.resolved_at)
+            .is_ok_and(|t| now - t.with_timezone(&Utc) < Duration::days(CACHE_TTL_DAYS))
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref NAMES: Mutex<Option<BTreeMap<String, OrgName>>> = Mutex::new(None);
+}
+
+fn cache_path() -> PathBuf {
+    data_dir().join(ORG_NAMES_FILE)
+}
+
+fn load_from_disk() -> BTreeMap<String, OrgName> {
+    match crate::store::read_json(&cache_path()) {
+        Ok(names) => names.unwrap_or_default(),
+        Err(e) => {
+            warn!("组织名称缓存读取失败，已忽略: {}", e);
+            BTreeMap::new()
+        }
+    }
+}
+
+fn save_to_disk(names: &BTreeMap<String, OrgName>) -> Result<()> {
+    crate::store::write_json(&cache_path(), names)
+}
+
+/// 合并查询结果（已知名称不会被空名称覆盖），返回是否有变化
+pub fn merge(
+    names: &mut BTreeMap<String, OrgName>,
+    orgs: &[WorkOSOrganization],
+    now: DateTime<Utc>,
+) -> bool {
+    let mut changed = false;
+    for org in orgs {
+        let previous = names.get(&org.id).and_then(|entry| entry.name.clone());
+        let entry = OrgName {
+            name: org.name.clone().or(previous),
+            resolved_at: now.to_rfc3339(),
+        };
+        if names.get(&org.id).map(|e| &e.name) != Some(&entry.name) {
+            changed = true;
+        }
+        names.insert(org.id.clone(), entry);
+    }
+    changed
+}
+
+/// 记录查询到的组织（含没有名称的组织，避免重复查询）
+pub fn remember(orgs: &[WorkOSOrganization]) {
+    if orgs.is_empty() {
+        return;
+    }
+    let mut guard = NAMES.lock().unwrap();
+    let names = guard.get_or_insert_with(load_from_disk);
+    merge(names, orgs, Utc::now());
+    if let Err(e) = save_to_disk(names) {
+        warn!("保存组织名称缓存失败: {}", e);
+    }
+}
+
+/// 组织名称
+pub fn lookup(org_id: &str) -> Option<String> {
+    let mut guard = NAMES.lock().unwrap();
+    let names = guard.get_or_insert_with(load_from_disk);
+    names.get(org_id).and_then(|entry| entry.name.clone())
+}
+
+/// 是否需要（重新）查询该组织
+pub fn needs_resolve(org_id: &str) -> bool {
+    let mut guard = NAMES.lock().unwrap();
+    let names = guard.get_or_insert_with(load_from_disk);
+    names
+        .get(org_id)
+        .is_none_or(|entry| !entry.is_fresh(Utc::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn org(id: &str, name: Option<&str>) -> WorkOSOrganization {
+        WorkOSOrganization {
+            id: id.to_string(),
+            name: name.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_merge_keeps_known_names() {
+        let now = Utc::now();
+        let mut names = BTreeMap::new();
+        assert!(merge(
+            &mut names,
+            &[org("org_1", Some("Acme")), org("org_2", None)],
+            now
+        ));
+        assert_eq!(names["org_1"].name.as_deref(), Some("Acme"));
+        assert!(names["org_2"].name.is_none());
+
+        // Factory 接口只返回 ID 时保留挑战中得到的名称
+        assert!(!merge(&mut names, &[org("org_1", None)], now));
+        assert_eq!(names["org_1"].name.as_deref(), Some("Acme"));
+        assert!(names["org_1"].is_fresh(now));
+        assert!(!names["org_1"].is_fresh(now + Duration::days(CACHE_TTL_DAYS + 1)));
+    }
+}
@@ -0,0 +1,214 @@
+//! 请求参数审查
+//!
+//! 客户端传入上游未知的字段（或较新的参数，如 `metadata`、`service_tier`）时
+//! 的处理策略：原样转发、丢弃并告警，或直接拒绝请求。
+//! 请求含 `input` 时按 OpenAI Responses 校验，否则按 Anthropic Messages 与
+//! OpenAI Chat Completions 的字段并集校验。
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// 未知字段的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamMode {
+    /// 原样转发
+    #[default]
+    Permissive,
+    /// 丢弃未知字段并告警
+    Strict,
+    /// 拒绝含未知字段的请求
+    Reject,
+}
+
+/// 参数审查配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParamPolicyConfig {
+    pub mode: ParamMode,
+    /// 额外允许的字段
+    pub allowed_fields: Vec<String>,
+}
+
+/// Anthropic Messages API 字段
+const ANTHROPIC_MESSAGES_FIELDS: &[&str] = &[
+    "model",
+    "messages",
+    "system",
+    "max_tokens",
+    "metadata",
+    "stop_sequences",
+    "stream",
+    "temperature",
+    "top_p",
+    "top_k",
+    "tools",
+    "tool_choice",
+    "thinking",
+    "service_tier",
+    "container",
+    "mcp_servers",
+    "context_management",
+];
+
+/// OpenAI Chat Completions 字段
+const OPENAI_CHAT_FIELDS: &[&str] = &[
+    "model",
+    "messages",
+    "max_tokens",
+    "max_completion_tokens",
+    "temperature",
+    "top_p",
+    "n",
+    "stream",
+    "stream_options",
+    "stop",
+    "presence_penalty",
+    "frequency_penalty",
+    "logit_bias",
+    "logprobs",
+    "top_logprobs",
+    "user",
+    "tools",
+    "tool_choice",
+    "parallel_tool_calls",
+    "response_format",
+    "seed",
+    "reasoning_effort",
+    "metadata",
+    "service_tier",
+    "store",
+    "modalities",
+    "audio",
+    "prediction",
+    "web_search_options",
+];
+
+/// OpenAI Responses 字段
+const OPENAI_RESPONSES_FIELDS: &[&str] = &[
+    "model",
+    "input",
+    "instructions",
+    "max_output_tokens",
+    "previous_response_id",
+    "reasoning",
+    "text",
+    "truncation",
+    "include",
+    "store",
+    "tools",
+    "tool_choice",
+    "parallel_tool_calls",
+    "temperature",
+    "top_p",
+    "metadata",
+    "stream",
+    "user",
+    "background",
+    "service_tier",
+    "prompt",
+    "prompt_cache_key",
+    "safety_identifier",
+];
+
+fn is_known(request: &serde_json::Value, field: &str) -> bool {
+    if request.get("input").is_some() {
+        OPENAI_RESPONSES_FIELDS.contains(&field)
+    } else {
+        ANTHROPIC_MESSAGES_FIELDS.contains(&field) || OPENAI_CHAT_FIELDS.contains(&field)
+    }
+}
+
+/// 请求中的未知字段（按字段名排序）
+pub fn unknown_fields(config: &ParamPolicyConfig, request: &serde_json::Value) -> Vec<String> {
+    let Some(body) = request.as_object() else {
+        return Vec::new();
+    };
+    let mut unknown: Vec<String> = body
+        .keys()
+        .filter(|key| !is_known(request, key) && !config.allowed_fields.contains(key))
+        .cloned()
+        .collect();
+    unknown.sort();
+    unknown
+}
+
+/// 按策略处理未知字段，返回被丢弃的字段
+pub fn apply(
+    config: &ParamPolicyConfig,
+    request: &mut serde_json::Value,
+) -> anyhow::Result<Vec<String>> {
+    if config.mode == ParamMode::Permissive {
+        return Ok(Vec::new());
+    }
+    let unknown = unknown_fields(config, request);
+    if unknown.is_empty() {
+        return Ok(unknown);
+    }
+    if config.mode == ParamMode::Reject {
+        anyhow::bail!("请求包含不支持的参数: {}", unknown.join(", "));
+    }
+
+    warn!("丢弃不支持的请求参数: {}", unknown.join(", "));
+    if let Some(body) = request.as_object_mut() {
+        for field in &unknown {
+            body.remove(field);
+        }
+    }
+    Ok(unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> serde_json::Value {
+        serde_json::json!({
+            "model": "claude-sonnet-4-20250514",
+            "messages": [],
+            "max_tokens": 16,
+            "service_tier": "auto",
+            "x_debug": true,
+        })
+    }
+
+    #[test]
+    fn test_permissive_forwards_verbatim() {
+        let mut body = request();
+        assert!(apply(&ParamPolicyConfig::default(), &mut body)
+            .unwrap()
+            .is_empty());
+        assert_eq!(body, request());
+    }
+
+    #[test]
+    fn test_strict_drops_unknown() {
+        let config = ParamPolicyConfig {
+            mode: ParamMode::Strict,
+            ..Default::default()
+        };
+        let mut body = request();
+        assert_eq!(apply(&config, &mut body).unwrap(), vec!["x_debug"]);
+        assert!(body.get("x_debug").is_none());
+        assert_eq!(body["service_tier"], "auto");
+    }
+
+    #[test]
+    fn test_reject_and_allowed_fields() {
+        let mut config = ParamPolicyConfig {
+            mode: ParamMode::Reject,
+            ..Default::default()
+        };
+        assert!(apply(&config, &mut request()).is_err());
+
+        config.allowed_fields.push("x_debug".to_string());
+        assert!(apply(&config, &mut request()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_responses_fields() {
+        let body = serde_json::json!({ "model": "gpt-5", "input": "hi", "max_tokens": 16 });
+        let config = ParamPolicyConfig::default();
+        assert_eq!(unknown_fields(&config, &body), vec!["max_tokens"]);
+    }
+}
@@ -0,0 +1,176 @@
+//! 请求生成参数处理
+//!
+//! 按模型家族合并默认生成参数（temperature / top_p 等），
+//! 仅在请求未显式指定时生效，并按请求格式只补充该端点接受的字段。
+
+use crate::stop_sequences::RequestFormat;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 生成参数默认值
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+}
+
+/// 根据模型 ID 推断模型家族
+pub fn model_family(model: &str) -> Option<&'static str> {
+    if model.contains("opus") {
+        Some("opus")
+    } else if model.contains("sonnet") {
+        Some("sonnet")
+    } else if model.contains("haiku") {
+        Some("haiku")
+    } else if model.starts_with("gpt-") {
+        Some("gpt")
+    } else {
+        None
+    }
+}
+
+/// 各请求格式中最大输出 Token 数的字段名
+const MAX_TOKENS_FIELDS: &[&str] = &["max_tokens", "max_completion_tokens", "max_output_tokens"];
+
+/// 将家族默认参数合并到请求中，已存在的字段不覆盖
+///
+/// 按请求格式补充：`temperature` 与 `top_p` 只补其一，请求已指定任一个时都不补；
+/// `top_k` 只用于 Anthropic 格式；`max_tokens` 按格式写入对应字段
+/// （Chat Completions 为 `max_completion_tokens`，Responses 为 `max_output_tokens`）。
+pub fn apply_generation_defaults(
+    request: &mut serde_json::Value,
+    profiles: &HashMap<String, GenerationDefaults>,
+) {
+    let family = match request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .and_then(model_family)
+    {
+        Some(family) => family,
+        None => return,
+    };
+
+    let defaults = match profiles.get(family) {
+        Some(defaults) => defaults,
+        None => return,
+    };
+
+    let format = RequestFormat::detect(request);
+    let body = match request.as_object_mut() {
+        Some(body) => body,
+        None => return,
+    };
+
+    // 部分模型不接受同时指定 temperature 与 top_p
+    if !body.contains_key("temperature") && !body.contains_key("top_p") {
+        if let Some(temperature) = defaults.temperature {
+            body.insert("temperature".to_string(), serde_json::json!(temperature));
+        } else if let Some(top_p) = defaults.top_p {
+            body.insert("top_p".to_string(), serde_json::json!(top_p));
+        }
+    }
+
+    if let (Some(top_k), RequestFormat::Anthropic) = (defaults.top_k, format) {
+        body.entry("top_k")
+            .or_insert_with(|| serde_json::json!(top_k));
+    }
+
+    if let Some(max_tokens) = defaults.max_tokens {
+        if !MAX_TOKENS_FIELDS.iter().any(|f| body.contains_key(*f)) {
+            let field = match format {
+                RequestFormat::Anthropic => "max_tokens",
+                RequestFormat::Chat => "max_completion_tokens",
+                RequestFormat::Responses => "max_output_tokens",
+            };
+            body.insert(field.to_string(), serde_json::json!(max_tokens));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_family() {
+        assert_eq!(model_family("claude-opus-4-1-20250805"), Some("opus"));
+        assert_eq!(model_family("claude-sonnet-4-20250514"), Some("sonnet"));
+        assert_eq!(model_family("gpt-5-2025-08-07"), Some("gpt"));
+        assert_eq!(model_family("custom-model"), None);
+    }
+
+    #[test]
+    fn test_apply_generation_defaults() {
+        let mut profiles = HashMap::new();
+        profiles.insert(
+            "sonnet".to_string(),
+            GenerationDefaults {
+                temperature: Some(0.2),
+                top_p: Some(0.9),
+                ..Default::default()
+            },
+        );
+
+        let mut request = serde_json::json!({
+            "model": "claude-sonnet-4-20250514",
+            "temperature": 1.0
+        });
+        apply_generation_defaults(&mut request, &profiles);
+        assert_eq!(request["temperature"], 1.0);
+        // 已指定 temperature 时不再补 top_p
+        assert!(request.get("top_p").is_none());
+        assert!(request.get("top_k").is_none());
+
+        let mut request = serde_json::json!({ "model": "claude-sonnet-4-20250514" });
+        apply_generation_defaults(&mut request, &profiles);
+        assert_eq!(request["temperature"], 0.2);
+        assert!(request.get("top_p").is_none());
+
+        let mut gpt = serde_json::json!({ "model": "gpt-5-2025-08-07" });
+        apply_generation_defaults(&mut gpt, &profiles);
+        assert!(gpt.get("temperature").is_none());
+    }
+
+    #[test]
+    fn test_defaults_follow_request_format() {
+        let defaults = GenerationDefaults {
+            top_p: Some(0.9),
+            top_k: Some(40),
+            max_tokens: Some(4096),
+            ..Default::default()
+        };
+        let profiles = HashMap::from([
+            ("opus".to_string(), defaults.clone()),
+            ("gpt".to_string(), defaults),
+        ]);
+
+        let mut claude = serde_json::json!({ "model": "claude-opus-4-1-20250805", "messages": [] });
+        apply_generation_defaults(&mut claude, &profiles);
+        assert_eq!(claude["top_p"], 0.9);
+        assert_eq!(claude["top_k"], 40);
+        assert_eq!(claude["max_tokens"], 4096);
+
+        let mut chat = serde_json::json!({ "model": "gpt-5-2025-08-07", "messages": [] });
+        apply_generation_defaults(&mut chat, &profiles);
+        assert!(chat.get("top_k").is_none());
+        assert!(chat.get("max_tokens").is_none());
+        assert_eq!(chat["max_completion_tokens"], 4096);
+
+        let mut responses = serde_json::json!({
+            "model": "gpt-5-2025-08-07",
+            "input": "hi",
+            "temperature": 1.0,
+            "max_output_tokens": 100
+        });
+        apply_generation_defaults(&mut responses, &profiles);
+        assert!(responses.get("top_p").is_none());
+        assert!(responses.get("top_k").is_none());
+        assert_eq!(responses["max_output_tokens"], 100);
+    }
+}
@@ -0,0 +1,36 @@
+//! 原样透传
+//!
+//! 排查上游 400 是否由本插件的改写引起时，可对单个请求（`raw: true`）或指定
+//! 客户端开启透传：不执行中间件链、不做端点故障转移的协议转换、不做流式重组，
+//! 请求与响应按原字节转发。限速与进度统计只读取内容，仍然生效。
+
+use serde::{Deserialize, Serialize};
+
+/// 透传配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PassthroughConfig {
+    /// 总是透传的客户端
+    pub clients: Vec<String>,
+}
+
+/// 该请求是否原样透传
+pub fn is_raw(config: &PassthroughConfig, client_name: Option<&str>, requested: bool) -> bool {
+    requested || client_name.is_some_and(|name| config.clients.iter().any(|c| c == name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_raw() {
+        let config = PassthroughConfig {
+            clients: vec!["curl".to_string()],
+        };
+        assert!(is_raw(&config, None, true));
+        assert!(is_raw(&config, Some("curl"), false));
+        assert!(!is_raw(&config, Some("Cursor"), false));
+        assert!(!is_raw(&PassthroughConfig::default(), None, false));
+    }
+}
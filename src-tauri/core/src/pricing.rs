@@ -0,0 +1,310 @@
+//! 模型价格表
+//!
+//! 用于估算费用，单位为美元 / 百万 Token。价格优先级：用户覆盖 > 远程价格表 >
+//! 内置价格。远程价格表按计划从配置的 URL 拉取，必须带 Ed25519 签名：
+//! `{"payload": "<价格表 JSON 的 base64>", "signature": "<签名的 base64>"}`，
+//! 签名对象是 payload 解码后的原始字节。校验通过的价格表保存到数据目录，
+//! 重启后继续使用；版本号低于当前价格表的更新会被拒绝。
+
+use crate::config::{data_dir, get_config};
+use crate::events;
+use anyhow::{Context, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+/// 远程价格表缓存文件名
+pub const PRICING_FILE: &str = "pricing.json";
+
+/// 远程价格表配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PricingConfig {
+    /// 价格表地址，为空表示只用内置价格
+    pub remote_url: Option<String>,
+    /// 签名公钥（Ed25519，base64）
+    pub public_key: Option<String>,
+    /// 拉取间隔（小时）
+    pub update_interval_hours: u64,
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        Self {
+            remote_url: None,
+            public_key: None,
+            update_interval_hours: 24,
+        }
+    }
+}
+
+/// 价格表（键为模型 ID，或 `claude-opus-*` 这样的前缀通配）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PricingTable {
+    pub version: u64,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    pub models: BTreeMap<String, ModelPricing>,
+}
+
+impl PricingTable {
+    /// 查找价格：精确匹配优先，其次最长的前缀通配
+    pub fn lookup(&self, model: &str) -> Option<ModelPricing> {
+        if let Some(pricing) = self.models.get(model) {
+            return Some(*pricing);
+        }
+        self.models
+            .iter()
+            .filter_map(|(pattern, pricing)| {
+                let prefix = pattern.strip_suffix('*')?;
+                model
+                    .starts_with(prefix)
+                    .then_some((prefix.len(), *pricing))
+            })
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, pricing)| pricing)
+    }
+}
+
+/// 带签名的价格表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedPricingTable {
+    pub payload: String,
+    pub signature: String,
+}
+
+/// 模型价格
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    /// 输入价格（美元 / 百万 Token）
+    pub input_per_mtok: f64,
+    /// 输出价格（美元 / 百万 Token）
+    pub output_per_mtok: f64,
+}
+
+impl ModelPricing {
+    /// 估算费用（美元）
+    pub fn estimate(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input_per_mtok + output_tokens as f64 * self.output_per_mtok)
+            / 1_000_000.0
+    }
+}
+
+/// 内置价格（按模型家族）
+pub fn builtin_pricing(model: &str) -> Option<ModelPricing> {
+    let (input_per_mtok, output_per_mtok) = if model.contains("opus") {
+        (15.0, 75.0)
+    } else if model.contains("sonnet") {
+        (3.0, 15.0)
+    } else if model.contains("haiku") {
+        (0.8, 4.0)
+    } else if model.starts_with("gpt-5") {
+        (1.25, 10.0)
+    } else {
+        return None;
+    };
+    Some(ModelPricing {
+        input_per_mtok,
+        output_per_mtok,
+    })
+}
+
+/// 远程价格表优先，其次内置价格（不含用户覆盖）
+pub fn default_pricing(model: &str) -> Option<ModelPricing> {
+    remote_table()
+        .and_then(|table| table.lookup(model))
+        .or_else(|| builtin_pricing(model))
+}
+
+/// 估算某次请求的费用（用户覆盖价格优先），未知模型返回 0
+pub fn estimate_cost(model: &str, input_tokens: u64, output_tokens: u64) -> f64 {
+    crate::model_overrides::pricing_for(model)
+        .or_else(|| default_pricing(model))
+        .map(|p| p.estimate(input_tokens, output_tokens))
+        .unwrap_or(0.0)
+}
+
+lazy_static::lazy_static! {
+    static ref REMOTE: RwLock<Option<Option<PricingTable>>> = RwLock::new(None);
+}
+
+fn pricing_path() -> PathBuf {
+    data_dir().join(PRICING_FILE)
+}
+
+fn load_from_disk() -> Option<PricingTable> {
+    crate::store::read_json(&pricing_path()).unwrap_or_else(|e| {
+        warn!("价格表缓存读取失败，已忽略: {}", e);
+        None
+    })
+}
+
+/// 当前生效的远程价格表
+pub fn remote_table() -> Option<PricingTable> {
+    if let Some(table) = REMOTE.read().unwrap().clone() {
+        return table;
+    }
+    let table = load_from_disk();
+    *REMOTE.write().unwrap() = Some(table.clone());
+    table
+}
+
+/// 校验签名并解析价格表
+pub fn verify_signed(signed: &SignedPricingTable, public_key: &str) -> Result<PricingTable> {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let public_key = engine
+        .decode(public_key.trim())
+        .context("价格表公钥不是有效的 base64")?;
+    let payload = engine
+        .decode(&signed.payload)
+        .context("价格表 payload 不是有效的 base64")?;
+    let signature = engine
+        .decode(&signed.signature)
+        .context("价格表签名不是有效的 base64")?;
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, public_key)
+        .verify(&payload, &signature)
+        .map_err(|_| anyhow::anyhow!("价格表签名校验失败"))?;
+
+    let table: PricingTable = serde_json::from_slice(&payload).context("价格表格式无效")?;
+    if table.models.is_empty() {
+        anyhow::bail!("价格表为空");
+    }
+    if let Some((model, _)) = table.models.iter().find(|(_, p)| {
+        !(p.input_per_mtok.is_finite() && p.output_per_mtok.is_finite())
+            || p.input_per_mtok < 0.0
+            || p.output_per_mtok < 0.0
+    }) {
+        anyhow::bail!("价格表中 {} 的价格无效", model);
+    }
+    Ok(table)
+}
+
+/// 拉取并应用远程价格表
+pub async fn update_remote() -> Result<PricingTable> {
+    let config = get_config().pricing;
+    let url = config
+        .remote_url
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("未配置远程价格表地址"))?;
+    let public_key = config
+        .public_key
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("未配置价格表签名公钥，拒绝使用未签名的价格表"))?;
+
+    let timeouts = crate::config::get_config().timeouts.for_control();
+    let response = timeouts.send(timeouts.client()?.get(url)).await?;
+    if !response.status().is_success() {
+        anyhow::bail!("拉取价格表失败: {}", response.status());
+    }
+    let signed: SignedPricingTable = timeouts
+        .read_json(response)
+        .await
+        .context("价格表响应格式无效")?;
+    let table = verify_signed(&signed, public_key)?;
+
+    if let Some(current) = remote_table() {
+        if table.version < current.version {
+            anyhow::bail!(
+                "价格表版本 {} 低于当前版本 {}",
+                table.version,
+                current.version
+            );
+        }
+        if table == current {
+            return Ok(table);
+        }
+    }
+    crate::store::write_json(&pricing_path(), &table)?;
+    *REMOTE.write().unwrap() = Some(Some(table.clone()));
+    info!(
+        "价格表已更新到版本 {}（{} 个模型）",
+        table.version,
+        table.models.len()
+    );
+    events::emit(
+        "pricing_updated",
+        format!("价格表已更新到版本 {}", table.version),
+        serde_json::json!({ "version": table.version, "models": table.models.len() }),
+    );
+    Ok(table)
+}
+
+/// 后台任务：按配置的间隔拉取价格表
+pub async fn run_updater() {
+    loop {
+        let config = get_config().pricing;
+        if config.remote_url.is_some() {
+            if let Err(e) = update_remote().await {
+                warn!("更新价格表失败: {}", e);
+            }
+        }
+        let hours = config.update_interval_hours.max(1);
+        tokio::time::sleep(std::time::Duration::from_secs(hours * 3600)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    fn sign(table: &serde_json::Value) -> (SignedPricingTable, String) {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let payload = serde_json::to_vec(table).unwrap();
+        let signed = SignedPricingTable {
+            payload: engine.encode(&payload),
+            signature: engine.encode(key_pair.sign(&payload).as_ref()),
+        };
+        (signed, engine.encode(key_pair.public_key().as_ref()))
+    }
+
+    #[test]
+    fn test_verify_signed() {
+        let table = serde_json::json!({
+            "version": 3,
+            "models": {
+                "claude-opus-*": { "input_per_mtok": 15.0, "output_per_mtok": 75.0 },
+                "claude-opus-4-1-20250805": { "input_per_mtok": 12.0, "output_per_mtok": 60.0 },
+            },
+        });
+        let (signed, public_key) = sign(&table);
+        let verified = verify_signed(&signed, &public_key).unwrap();
+        assert_eq!(verified.version, 3);
+        assert_eq!(
+            verified
+                .lookup("claude-opus-4-1-20250805")
+                .unwrap()
+                .input_per_mtok,
+            12.0
+        );
+        assert_eq!(
+            verified.lookup("claude-opus-5").unwrap().input_per_mtok,
+            15.0
+        );
+        assert!(verified.lookup("gpt-5").is_none());
+
+        // 篡改后的 payload 无法通过校验
+        let (other, _) = sign(&serde_json::json!({ "version": 4, "models": {} }));
+        let tampered = SignedPricingTable {
+            payload: other.payload,
+            signature: signed.signature.clone(),
+        };
+        assert!(verify_signed(&tampered, &public_key).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_prices() {
+        let table = serde_json::json!({
+            "version": 1,
+            "models": { "gpt-5": { "input_per_mtok": -1.0, "output_per_mtok": 10.0 } },
+        });
+        let (signed, public_key) = sign(&table);
+        assert!(verify_signed(&signed, &public_key).is_err());
+    }
+}
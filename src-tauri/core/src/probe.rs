@@ -0,0 +1,131 @@
+//! API Key 端点探测
+//!
+//! 使用最小请求依次尝试 Anthropic / OpenAI / Comm 三个端点，
+//! 记录该 Key 实际可用的端点类型，供路由使用。
+
+use crate::config::get_config;
+use crate::credentials::{AcquiredCredential, EndpointType};
+use crate::provider::{ENDPOINT_ANTHROPIC, ENDPOINT_COMM, ENDPOINT_OPENAI, FACTORY_API_BASE_URL};
+use crate::user_agent;
+use anyhow::Result;
+use reqwest::StatusCode;
+use tracing::{debug, info};
+
+/// 探测时使用的模型
+const PROBE_CLAUDE_MODEL: &str = "claude-sonnet-4-20250514";
+const PROBE_GPT_MODEL: &str = "gpt-5-2025-08-07";
+
+/// 构造端点对应的最小探测请求
+fn probe_request(endpoint_type: EndpointType) -> (&'static str, serde_json::Value) {
+    match endpoint_type {
+        EndpointType::Anthropic => (
+            ENDPOINT_ANTHROPIC,
+            serde_json::json!({
+                "model": PROBE_CLAUDE_MODEL,
+                "max_tokens": 1,
+                "messages": [{ "role": "user", "content": "ping" }]
+            }),
+        ),
+        EndpointType::OpenAI => (
+            ENDPOINT_OPENAI,
+            serde_json::json!({
+                "model": PROBE_GPT_MODEL,
+                "max_output_tokens": 16,
+                "input": "ping"
+            }),
+        ),
+        EndpointType::Comm => (
+            ENDPOINT_COMM,
+            serde_json::json!({
+                "model": PROBE_GPT_MODEL,
+                "max_tokens": 1,
+                "messages": [{ "role": "user", "content": "ping" }]
+            }),
+        ),
+    }
+}
+
+/// 根据状态码判断端点是否可用
+///
+/// 400 / 429 说明请求已到达模型层（参数或频率问题），视为该端点可用；
+/// 401 / 403 / 404 说明该 Key 无权访问此端点。
+pub fn status_indicates_support(status: StatusCode) -> bool {
+    status.is_success()
+        || status == StatusCode::BAD_REQUEST
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// 探测 API Key 支持的端点类型
+pub async fn probe_endpoints(api_key: &str) -> Result<Vec<EndpointType>> {
+    let config = get_config().timeouts;
+    let mut supported = Vec::new();
+    for endpoint_type in [
+        EndpointType::Anthropic,
+        EndpointType::OpenAI,
+        EndpointType::Comm,
+    ] {
+        let timeouts = config.for_endpoint(endpoint_type);
+        let (path, body) = probe_request(endpoint_type);
+        let request = timeouts
+            .client()?
+            .post(format!("{}{}", FACTORY_API_BASE_URL, path))
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .header("User-Agent", user_agent::resolve(None))
+            .header("x-factory-client", "cli")
+            .json(&body);
+        let response = timeouts.send(request).await;
+
+        match response {
+            Ok(response) => {
+                let status = response.status();
+                debug!("端点探测 {}: {}", endpoint_type, status);
+                if status_indicates_support(status) {
+                    supported.push(endpoint_type);
+                }
+            }
+            Err(e) => debug!("端点探测 {} 失败: {}", endpoint_type, e),
+        }
+    }
+
+    info!("API Key 支持的端点: {:?}", supported);
+    Ok(supported)
+}
+
+/// 发送一次最小测试请求，返回状态码与耗时
+pub async fn send_test_request(
+    acquired: &AcquiredCredential,
+    endpoint_type: EndpointType,
+) -> Result<(StatusCode, u64)> {
+    let timeouts = get_config().timeouts.for_endpoint(endpoint_type);
+    let client = timeouts.client()?;
+
+    let (path, body) = probe_request(endpoint_type);
+    let url = acquired
+        .base_url
+        .clone()
+        .unwrap_or_else(|| format!("{}{}", FACTORY_API_BASE_URL, path));
+
+    let mut request = client.post(url).json(&body);
+    for (name, value) in &acquired.headers {
+        request = request.header(name, value);
+    }
+
+    let started = std::time::Instant::now();
+    let status = timeouts.send(request).await?.status();
+    Ok((status, started.elapsed().as_millis() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_indicates_support() {
+        assert!(status_indicates_support(StatusCode::OK));
+        assert!(status_indicates_support(StatusCode::BAD_REQUEST));
+        assert!(status_indicates_support(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!status_indicates_support(StatusCode::UNAUTHORIZED));
+        assert!(!status_indicates_support(StatusCode::NOT_FOUND));
+    }
+}
@@ -0,0 +1,260 @@
+//! 请求转换配置档
+//!
+//! 一个网关实例服务多种客户端时，可定义命名的转换配置档（中间件组合、默认
+//! 生成参数、参数策略、系统提示词策略），再把租户虚拟密钥或调用方应用绑定到
+//! 某个配置档，例如“Cursor”与“批处理任务”各用一套，而不必运行两个网关。
+//! 选择顺序：请求显式指定 > 租户绑定 > 调用方应用绑定 > 全局配置。
+
+use crate::config::ProviderConfig;
+use crate::middleware::MiddlewareOrder;
+use crate::param_policy::ParamPolicyConfig;
+use crate::params::GenerationDefaults;
+use crate::tenants;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 系统提示词的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemPromptMode {
+    /// 保持原样
+    #[default]
+    Keep,
+    /// 在原系统提示词之前插入
+    Prepend,
+    /// 在原系统提示词之后追加
+    Append,
+    /// 替换为配置的文本
+    Replace,
+    /// 删除系统提示词
+    Strip,
+}
+
+/// 系统提示词策略
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystemPromptPolicy {
+    pub mode: SystemPromptMode,
+    pub text: String,
+}
+
+/// 单个配置档，未设置的项沿用全局配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransformProfile {
+    pub middleware: Option<MiddlewareOrder>,
+    pub generation_defaults: Option<HashMap<String, GenerationDefaults>>,
+    pub param_policy: Option<ParamPolicyConfig>,
+    pub system_prompt: SystemPromptPolicy,
+}
+
+/// 配置档设置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfilesConfig {
+    /// 配置档（名称 → 内容）
+    pub profiles: HashMap<String, TransformProfile>,
+    /// 调用方应用绑定的配置档（应用名 → 配置档名）
+    pub clients: HashMap<String, String>,
+}
+
+/// 按请求上下文选出配置档名称
+pub fn select(
+    config: &ProviderConfig,
+    explicit: Option<&str>,
+    tenant_key: Option<&str>,
+    client_name: Option<&str>,
+) -> Result<Option<String>> {
+    let tenant_profile = tenants::resolve(&config.tenants, tenant_key)
+        .ok()
+        .flatten()
+        .and_then(|t| t.profile.clone());
+    let client_profile = client_name.and_then(|c| config.profiles.clients.get(c).cloned());
+    let name = explicit
+        .map(str::to_string)
+        .or(tenant_profile)
+        .or(client_profile);
+    match name {
+        Some(name) if !config.profiles.profiles.contains_key(&name) => {
+            anyhow::bail!("未知的转换配置档: {}", name)
+        }
+        name => Ok(name),
+    }
+}
+
+/// 套用配置档后的有效配置
+pub fn effective_config(config: &ProviderConfig, profile: &TransformProfile) -> ProviderConfig {
+    let mut effective = config.clone();
+    if let Some(middleware) = &profile.middleware {
+        effective.middleware = middleware.clone();
+    }
+    if let Some(defaults) = &profile.generation_defaults {
+        effective.generation_defaults = defaults.clone();
+    }
+    if let Some(param_policy) = &profile.param_policy {
+        effective.param_policy = param_policy.clone();
+    }
+    effective
+}
+
+fn merge_text(mode: SystemPromptMode, original: &str, text: &str) -> String {
+    match mode {
+        SystemPromptMode::Prepend if !original.is_empty() => format!("{}\n\n{}", text, original),
+        SystemPromptMode::Append if !original.is_empty() => format!("{}\n\n{}", original, text),
+        _ => text.to_string(),
+    }
+}
+
+/// 按策略改写系统提示词（Anthropic `system`、Responses `instructions`、
+/// Chat Completions 的 system 消息）
+pub fn apply_system_prompt(policy: &SystemPromptPolicy, request: &mut serde_json::Value) {
+    let Some(body) = request.as_object_mut() else {
+        return;
+    };
+    let mode = policy.mode;
+    if mode == SystemPromptMode::Keep {
+        return;
+    }
+
+    let is_anthropic = body.contains_key("system")
+        || body
+            .get("model")
+            .and_then(|m| m.as_str())
+            .is_some_and(|m| m.starts_with("claude-"));
+    let field = if body.contains_key("input") {
+        Some("instructions")
+    } else if is_anthropic {
+        Some("system")
+    } else {
+        None
+    };
+
+    if let Some(field) = field {
+        if mode == SystemPromptMode::Strip {
+            body.remove(field);
+            return;
+        }
+        let value = match body.get(field) {
+            // Anthropic 的内容块数组：在前后插入文本块
+            Some(serde_json::Value::Array(blocks)) => {
+                let block = serde_json::json!({ "type": "text", "text": policy.text });
+                let mut blocks = blocks.clone();
+                match mode {
+                    SystemPromptMode::Prepend => blocks.insert(0, block),
+                    SystemPromptMode::Append => blocks.push(block),
+                    _ => blocks = vec![block],
+                }
+                serde_json::Value::Array(blocks)
+            }
+            other => {
+                let original = other.and_then(|v| v.as_str()).unwrap_or("");
+                serde_json::json!(merge_text(mode, original, &policy.text))
+            }
+        };
+        body.insert(field.to_string(), value);
+        return;
+    }
+
+    // Chat Completions：处理第一条 system 消息
+    let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+        return;
+    };
+    let position = messages.iter().position(|m| m["role"] == "system");
+    match (mode, position) {
+        (SystemPromptMode::Strip, _) => messages.retain(|m| m["role"] != "system"),
+        (_, Some(index)) => {
+            let original = messages[index]["content"]
+                .as_str()
+                .unwrap_or("")
+                .to_string();
+            let merged = merge_text(mode, &original, &policy.text);
+            messages[index]["content"] = serde_json::json!(merged);
+        }
+        (_, None) => messages.insert(
+            0,
+            serde_json::json!({ "role": "system", "content": policy.text }),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(mode: SystemPromptMode) -> SystemPromptPolicy {
+        SystemPromptPolicy {
+            mode,
+            text: "Be concise.".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_anthropic_system_prompt() {
+        let mut request = serde_json::json!({
+            "model": "claude-sonnet-4-20250514",
+            "system": "You are helpful.",
+        });
+        apply_system_prompt(&policy(SystemPromptMode::Prepend), &mut request);
+        assert_eq!(request["system"], "Be concise.\n\nYou are helpful.");
+
+        let mut request = serde_json::json!({
+            "model": "claude-sonnet-4-20250514",
+            "system": [{ "type": "text", "text": "You are helpful." }],
+        });
+        apply_system_prompt(&policy(SystemPromptMode::Append), &mut request);
+        assert_eq!(request["system"][1]["text"], "Be concise.");
+
+        let mut request = serde_json::json!({ "model": "claude-opus-4-1-20250805" });
+        apply_system_prompt(&policy(SystemPromptMode::Append), &mut request);
+        assert_eq!(request["system"], "Be concise.");
+
+        apply_system_prompt(&policy(SystemPromptMode::Strip), &mut request);
+        assert!(request.get("system").is_none());
+    }
+
+    #[test]
+    fn test_chat_and_responses_system_prompt() {
+        let mut chat = serde_json::json!({
+            "model": "gpt-5-2025-08-07",
+            "messages": [{ "role": "user", "content": "hi" }],
+        });
+        apply_system_prompt(&policy(SystemPromptMode::Replace), &mut chat);
+        assert_eq!(chat["messages"][0]["role"], "system");
+        assert_eq!(chat["messages"][0]["content"], "Be concise.");
+
+        apply_system_prompt(&policy(SystemPromptMode::Strip), &mut chat);
+        assert_eq!(chat["messages"].as_array().unwrap().len(), 1);
+
+        let mut responses = serde_json::json!({ "model": "gpt-5-2025-08-07", "input": "hi" });
+        apply_system_prompt(&policy(SystemPromptMode::Replace), &mut responses);
+        assert_eq!(responses["instructions"], "Be concise.");
+    }
+
+    #[test]
+    fn test_select_and_effective_config() {
+        let mut config = ProviderConfig::default();
+        config.profiles.profiles.insert(
+            "batch".to_string(),
+            TransformProfile {
+                middleware: Some(MiddlewareOrder(vec!["model_rewrite".to_string()])),
+                ..Default::default()
+            },
+        );
+        config
+            .profiles
+            .clients
+            .insert("batch-runner".to_string(), "batch".to_string());
+
+        assert_eq!(select(&config, None, None, Some("Cursor")).unwrap(), None);
+        let name = select(&config, None, None, Some("batch-runner"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, "batch");
+        assert!(select(&config, Some("missing"), None, None).is_err());
+
+        let effective = effective_config(&config, &config.profiles.profiles[&name]);
+        assert_eq!(effective.middleware.0, vec!["model_rewrite".to_string()]);
+        assert_eq!(effective.param_policy.mode, config.param_policy.mode);
+    }
+}
@@ -0,0 +1,2908 @@
+//! Droid Provider 核心实现
+//!
+//! 实现凭证管理、模型支持检查等核心功能。
+
+use crate::account_link;
+use crate::auth::encryption::hash_api_key;
+use crate::auth::jwt::decode_claims;
+use crate::auth::key_ring::{self, KeyRing};
+use crate::auth::token_seal;
+use crate::auth::workos::fetch_factory_orgs;
+use crate::availability::{self, ModelAvailability};
+use crate::backoff_state;
+use crate::canary::{self, CanaryVerdict};
+use crate::chaos;
+use crate::chat_normalize;
+use crate::config::{get_config, ProviderConfig};
+use crate::context_trim::{self, TrimReport};
+use crate::control::{self, PauseBehavior};
+use crate::credential_clone::{self, CloneOverrides};
+use crate::credentials::{
+    AcquiredCredential, ApiKeyEntry, AuthType, CredentialError, CustomModel, DroidCredentials,
+    EndpointType, ReleaseReport, ReleaseStatus, TokenRefreshResult, ValidationResult,
+};
+use crate::dead_credentials::{self, CleanupAction, DeadCredential, PruneReport};
+use crate::dedup;
+use crate::deprecation;
+use crate::documents;
+use crate::env_import::{self, EnvImportResult};
+use crate::events;
+use crate::failover;
+use crate::heartbeat;
+use crate::hooks;
+use crate::http::ordered_headers;
+use crate::keepalive::{self, KeepAliveCandidate};
+use crate::lease::{LeaseConfig, LeaseTracker};
+use crate::maintenance;
+use crate::middleware;
+use crate::mock;
+use crate::model_overrides;
+use crate::model_registry::{is_builtin_family, ModelRegistry};
+use crate::org_discovery::{self, DiscoveredOrg};
+use crate::org_names;
+use crate::param_policy;
+use crate::passthrough;
+use crate::pricing::{self, ModelPricing};
+use crate::probe;
+use crate::profiles::{self, TransformProfile};
+use crate::quota_link;
+use crate::reassembly;
+use crate::refresh_debug::{self, RefreshTrace};
+use crate::refresh_failure::{self, RefreshFailureCategory};
+use crate::refresh_limiter::{self, RefreshPriority};
+use crate::relogin;
+use crate::request_tags::{self, Tags};
+use crate::response_meta::ServingInfo;
+use crate::retry_budget;
+use crate::reveal::{self, RevealChallenge};
+use crate::salvage;
+use crate::schedule::ActiveSchedule;
+use crate::secret_lock;
+use crate::sharing::{self, PairingExport};
+use crate::singleflight;
+use crate::spool;
+use crate::startup;
+use crate::stats::{self, UsageRecord};
+use crate::stop_sequences::{self, RequestFormat};
+use crate::store;
+use crate::stream_progress::{self, StreamProgress};
+use crate::tenants;
+use crate::throttle;
+use crate::token_age::{self, RefreshTokenAge};
+use crate::token_refresh::RefreshChallenge;
+use crate::user_agent;
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Notify, RwLock, RwLockWriteGuard};
+use tracing::{debug, info, warn};
+
+/// Factory.ai API 基础 URL
+pub const FACTORY_API_BASE_URL: &str = "https://api.factory.ai/api/llm";
+
+/// 端点路径
+pub const ENDPOINT_ANTHROPIC: &str = "/a/v1/messages";
+pub const ENDPOINT_OPENAI: &str = "/o/v1/responses";
+pub const ENDPOINT_COMM: &str = "/o/v1/chat/completions";
+
+/// 模型信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub display_name: String,
+    pub family: Option<String>,
+    pub context_length: Option<u32>,
+    pub supports_vision: bool,
+    pub supports_tools: bool,
+    /// 价格（美元 / 百万 Token）
+    #[serde(default)]
+    pub pricing: Option<ModelPricing>,
+    /// 当前凭证池的可用情况
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub availability: Option<ModelAvailability>,
+}
+
+/// Provider 错误
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderError {
+    pub error_type: String,
+    pub message: String,
+    pub status_code: Option<u16>,
+    pub retryable: bool,
+    pub cooldown_seconds: Option<u64>,
+}
+
+lazy_static::lazy_static! {
+    static ref CREDENTIALS: Arc<RwLock<HashMap<String, DroidCredentials>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+    static ref LEASES: Arc<RwLock<LeaseTracker>> = Arc::new(RwLock::new(LeaseTracker::default()));
+    /// 凭证池有尚未写入凭证文件的修改
+    static ref STORE_DIRTY: Notify = Notify::new();
+}
+
+/// 已载入凭证文件（载入失败时不写盘，以免覆盖无法解析的文件）
+static STORE_LOADED: AtomicBool = AtomicBool::new(false);
+
+/// 凭证池修改后延迟写盘，合并这段时间内的修改
+const STORE_WRITE_DELAY: Duration = Duration::from_secs(1);
+
+/// 凭证池写锁，释放时检查同账号凭证并安排写盘
+struct CredentialsWriteGuard(RwLockWriteGuard<'static, HashMap<String, DroidCredentials>>);
+
+impl Deref for CredentialsWriteGuard {
+    type Target = HashMap<String, DroidCredentials>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for CredentialsWriteGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for CredentialsWriteGuard {
+    fn drop(&mut self) {
+        account_link::check(&self.0);
+        STORE_DIRTY.notify_one();
+    }
+}
+
+/// 获取凭证池写锁；所有修改都经由此处，释放后由 `run_store_writer` 写盘
+async fn write_credentials() -> CredentialsWriteGuard {
+    CredentialsWriteGuard(CREDENTIALS.write().await)
+}
+
+/// 载入凭证文件中保存的凭证，返回载入数量
+///
+/// 加密的机密无法解开时以锁定状态载入（见 `secret_lock`）。
+pub async fn load_saved_credentials() -> Result<usize> {
+    let saved = tokio::task::spawn_blocking(store::load_credentials).await??;
+    let mut creds = CREDENTIALS.write().await;
+    let mut loaded = 0;
+    for (id, mut credential) in saved {
+        if creds.contains_key(&id) {
+            continue;
+        }
+        if let Some(reason) = secret_lock::open(&mut credential) {
+            secret_lock::mark(&id, &credential, reason);
+        }
+        creds.insert(id, credential);
+        loaded += 1;
+    }
+    account_link::check(&creds);
+    relogin::restore(&creds);
+    drop(creds);
+    STORE_LOADED.store(true, Ordering::SeqCst);
+    check_pool_ready().await;
+    Ok(loaded)
+}
+
+/// 立即把凭证池写入凭证文件（冷却状态、租户用量一并写入）
+pub async fn save_store() -> Result<()> {
+    let snapshot = if STORE_LOADED.load(Ordering::SeqCst) {
+        Some(CREDENTIALS.read().await.clone())
+    } else {
+        None
+    };
+    tokio::task::spawn_blocking(move || {
+        backoff_state::save()?;
+        tenants::save()?;
+        match snapshot {
+            Some(snapshot) => store::save_credentials(&snapshot),
+            None => Ok(()),
+        }
+    })
+    .await?
+}
+
+/// 后台写盘：凭证池修改后延迟 `STORE_WRITE_DELAY` 合并写入
+pub async fn run_store_writer() {
+    loop {
+        STORE_DIRTY.notified().await;
+        tokio::time::sleep(STORE_WRITE_DELAY).await;
+        if let Err(e) = save_store().await {
+            warn!("凭证文件写入失败: {:#}", e);
+        }
+    }
+}
+
+/// 列出支持的模型（合并凭证自定义模型与用户覆盖项，不含已检测到弃用的模型）
+pub async fn list_models() -> Vec<ModelInfo> {
+    if mock::is_enabled() {
+        return mock::models();
+    }
+    let mut models = builtin_models();
+    let creds = CREDENTIALS.read().await;
+    let registry = ModelRegistry::build(creds.iter());
+    for custom in registry.models() {
+        if models.iter().any(|m| m.id == custom.id) {
+            continue;
+        }
+        models.push(ModelInfo {
+            display_name: custom
+                .display_name
+                .clone()
+                .unwrap_or_else(|| custom.id.clone()),
+            family: None,
+            context_length: custom.context_length,
+            supports_vision: false,
+            supports_tools: true,
+            pricing: pricing::default_pricing(&custom.id),
+            availability: None,
+            id: custom.id,
+        });
+    }
+
+    let now = Utc::now();
+    model_overrides::merge(models)
+        .into_iter()
+        .filter(|m| !deprecation::is_deprecated(&m.id))
+        .map(|mut model| {
+            let serving = creds.iter().filter(|(id, c)| {
+                endpoint_for_model(&model.id, c, registry.custom_model(id, c, &model.id)).is_some()
+            });
+            model.availability = Some(availability::summarize(serving.map(|(_, c)| c), now));
+            model
+        })
+        .collect()
+}
+
+/// 内置模型列表
+fn builtin_models() -> Vec<ModelInfo> {
+    vec![
+        ModelInfo {
+            id: "claude-opus-4-1-20250805".to_string(),
+            display_name: "Claude Opus 4.1".to_string(),
+            family: Some("opus".to_string()),
+            context_length: Some(200000),
+            supports_vision: true,
+            supports_tools: true,
+            pricing: None,
+            availability: None,
+        },
+        ModelInfo {
+            id: "claude-sonnet-4-5-20250929".to_string(),
+            display_name: "Claude Sonnet 4.5".to_string(),
+            family: Some("sonnet".to_string()),
+            context_length: Some(200000),
+            supports_vision: true,
+            supports_tools: true,
+            pricing: None,
+            availability: None,
+        },
+        ModelInfo {
+            id: "claude-sonnet-4-20250514".to_string(),
+            display_name: "Claude Sonnet 4".to_string(),
+            family: Some("sonnet".to_string()),
+            context_length: Some(200000),
+            supports_vision: true,
+            supports_tools: true,
+            pricing: None,
+            availability: None,
+        },
+        ModelInfo {
+            id: "gpt-5-2025-08-07".to_string(),
+            display_name: "GPT-5".to_string(),
+            family: Some("gpt".to_string()),
+            context_length: Some(128000),
+            supports_vision: true,
+            supports_tools: true,
+            pricing: None,
+            availability: None,
+        },
+    ]
+    .into_iter()
+    .map(|mut model| {
+        model.pricing = pricing::default_pricing(&model.id);
+        model
+    })
+    .collect()
+}
+
+/// 检查是否支持某个模型（内置家族、用户添加的模型或凭证声明的自定义模型）
+pub async fn supports_model(model: &str) -> bool {
+    if mock::is_enabled() {
+        return mock::models().iter().any(|m| m.id == model);
+    }
+    if is_builtin_family(model) || model_overrides::get_overrides().contains_key(model) {
+        return true;
+    }
+    ModelRegistry::build(CREDENTIALS.read().await.iter()).contains(model)
+}
+
+/// 获取端点路径
+fn get_endpoint_path(endpoint_type: EndpointType) -> &'static str {
+    match endpoint_type {
+        EndpointType::Anthropic => ENDPOINT_ANTHROPIC,
+        EndpointType::OpenAI => ENDPOINT_OPENAI,
+        EndpointType::Comm => ENDPOINT_COMM,
+    }
+}
+
+/// 根据模型确定凭证实际使用的端点
+///
+/// 自定义模型走其声明的端点；Claude 模型始终走 Anthropic 路径；
+/// GPT 模型走凭证配置的 OpenAI 兼容路径。
+/// 已探测过端点的凭证只会路由到其支持的端点，不支持时返回 None。
+fn endpoint_for_model(
+    model: &str,
+    credential: &DroidCredentials,
+    custom: Option<&CustomModel>,
+) -> Option<EndpointType> {
+    // 套餐不含该模型的凭证直接跳过，避免浪费一次重试
+    if !credential.allows_model(model) {
+        return None;
+    }
+    let supported = &credential.supported_endpoints;
+    let is_supported =
+        |endpoint: EndpointType| supported.is_empty() || supported.contains(&endpoint);
+
+    if let Some(custom) = custom {
+        return is_supported(custom.endpoint_type).then_some(custom.endpoint_type);
+    }
+    if !is_builtin_family(model) && !model_overrides::get_overrides().contains_key(model) {
+        // 其他组织的自定义模型
+        return None;
+    }
+
+    if model.starts_with("claude-") {
+        return is_supported(EndpointType::Anthropic).then_some(EndpointType::Anthropic);
+    }
+
+    let preferred = match credential.endpoint_type {
+        EndpointType::Anthropic => EndpointType::OpenAI,
+        configured => configured,
+    };
+    [preferred, EndpointType::OpenAI, EndpointType::Comm]
+        .into_iter()
+        .find(|endpoint| is_supported(*endpoint))
+}
+
+/// 获取凭证时的附加选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AcquireOptions {
+    /// 原始请求体（用于进行中请求去重）
+    #[serde(default)]
+    pub request: Option<serde_json::Value>,
+    /// 调用方应用名（如 "Cursor"、"Cline"），用于按应用统计用量
+    #[serde(default)]
+    pub client_name: Option<String>,
+    /// 原样透传：不做任何改写（含故障转移的协议转换）
+    #[serde(default)]
+    pub raw: bool,
+    /// 客户端请求中的虚拟密钥（启用租户时必填）
+    #[serde(default)]
+    pub tenant_key: Option<String>,
+    /// 逻辑请求 ID（宿主重试同一请求时保持不变），用于重试预算
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// 请求标签（如项目名、工单号），随使用记录保存
+    #[serde(default)]
+    pub tags: Tags,
+}
+
+/// 获取凭证
+pub async fn acquire_credential(
+    model: &str,
+    options: &AcquireOptions,
+) -> Result<AcquiredCredential> {
+    if !supports_model(model).await {
+        anyhow::bail!("不支持的模型: {}", model);
+    }
+    if mock::is_enabled() {
+        return Ok(mock::acquire(model));
+    }
+    if let Some(request_id) = &options.request_id {
+        retry_budget::check(request_id)?;
+    }
+
+    let config = get_config();
+    if control::is_paused() {
+        match config.pause.behavior {
+            PauseBehavior::Reject => anyhow::bail!("Provider 已暂停"),
+            PauseBehavior::Queue => {
+                let timeout = std::time::Duration::from_millis(config.pause.queue_timeout_ms);
+                if !control::wait_until_resumed(timeout).await {
+                    anyhow::bail!("Provider 已暂停，等待恢复超时");
+                }
+            }
+        }
+    }
+
+    // Factory 整体维护期间全局退避，由后台探测恢复
+    if maintenance::is_active() {
+        let since = maintenance::status().since.unwrap_or_default();
+        anyhow::bail!("Factory 服务疑似维护中（自 {}），已暂停分配凭证", since);
+    }
+
+    // 启动阶段凭证池未就绪时排队等待，避免重试风暴
+    if !startup::is_ready() {
+        check_pool_ready().await;
+        if !startup::wait_ready(&config.startup_queue).await? {
+            debug!("启动队列等待超时，按当前凭证池处理");
+        }
+    }
+
+    let client_name = options.client_name.as_deref();
+    let raw = passthrough::is_raw(&config.passthrough, client_name, options.raw);
+    let tenant = tenants::resolve(&config.tenants, options.tenant_key.as_deref())?;
+    let fingerprint = match (&options.request, config.dedup.enabled) {
+        (Some(request), true) => dedup::request_fingerprint(request, tenant.map(|t| t.id.as_str())),
+        _ => None,
+    };
+    let tenant_allows = |id: &str| tenant.is_none_or(|t| t.allows_credential(id));
+    // 昂贵模型只分给等级达到要求的凭证
+    let required_tier = config.model_tiers.required_tier(model);
+
+    // 选择钩子执行后的决定（外层 None 表示尚未执行）
+    let mut hook_decision: Option<Option<hooks::SelectDecision>> = None;
+    loop {
+        let creds = CREDENTIALS.read().await;
+        let registry = ModelRegistry::build(creds.iter());
+        let preferred_route = |id: &str, credential: &DroidCredentials| {
+            endpoint_for_model(
+                model,
+                credential,
+                registry.custom_model(id, credential, model),
+            )
+        };
+        // Anthropic 路径持续 5xx 时改走 Chat Completions（透传请求无法转换协议，不改道）
+        let route = |id: &str, credential: &DroidCredentials| {
+            preferred_route(id, credential).map(|endpoint| match raw {
+                true => endpoint,
+                false => failover::reroute(id, credential, endpoint, &config.failover)
+                    .unwrap_or(endpoint),
+            })
+        };
+
+        // 相同请求仍在进行中：等待原请求的响应，不访问上游也不占用并发
+        if let Some(hash) = &fingerprint {
+            let in_flight = dedup::find_in_flight(hash, config.dedup.window_ms);
+            let follower =
+                in_flight.and_then(|f| dedup::attach_follower(&f.lease_id).map(|id| (f, id)));
+            if let Some((in_flight, follower_id)) = follower {
+                debug!("重复请求等待进行中的请求: {}", in_flight.lease_id);
+                let credential = creds.get(&in_flight.credential_id);
+                return Ok(AcquiredCredential {
+                    id: in_flight.credential_id,
+                    name: credential.and_then(|c| c.name.clone()),
+                    auth_type: credential
+                        .map(|c| c.auth_type.to_string())
+                        .unwrap_or_default(),
+                    base_url: None,
+                    headers: HashMap::new(),
+                    metadata: HashMap::from([
+                        ("lease_id".to_string(), serde_json::json!(follower_id)),
+                        (
+                            "dedup_of".to_string(),
+                            serde_json::json!(in_flight.lease_id),
+                        ),
+                        ("shared_response".to_string(), serde_json::json!(true)),
+                    ]),
+                });
+            }
+        }
+
+        let mut leases = LEASES.write().await;
+        prepare_leases(&mut leases, &config.leases);
+
+        // 查找健康且该端点仍有空闲并发的凭证（只读凭证不分配流量）
+        let healthy_creds: Vec<_> = creds
+            .iter()
+            .filter(|(_, c)| !c.read_only && c.is_healthy() && !c.in_cooldown())
+            .filter(|(id, _)| tenant_allows(id))
+            .collect();
+        // 加密密钥不可用的凭证已锁定，不参与分配
+        let locked = healthy_creds
+            .iter()
+            .filter(|(id, _)| secret_lock::is_locked(id))
+            .count();
+        let healthy_creds: Vec<_> = healthy_creds
+            .into_iter()
+            .filter(|(id, _)| !secret_lock::is_locked(id))
+            .collect();
+        let scheduled_off = healthy_creds
+            .iter()
+            .filter(|(_, c)| !c.in_active_hours())
+            .count();
+        let healthy_creds: Vec<_> = healthy_creds
+            .into_iter()
+            .filter(|(_, c)| c.in_active_hours())
+            .collect();
+        let below_tier = healthy_creds
+            .iter()
+            .filter(|(_, c)| c.tier < required_tier)
+            .count();
+        let healthy_creds: Vec<_> = healthy_creds
+            .into_iter()
+            .filter(|(_, c)| c.tier >= required_tier)
+            .collect();
+
+        if healthy_creds.is_empty() {
+            if below_tier > 0 {
+                anyhow::bail!(
+                    "模型 {} 需要等级不低于 {} 的凭证（{} 个凭证等级不足）",
+                    model,
+                    required_tier,
+                    below_tier
+                );
+            }
+            if scheduled_off > 0 {
+                anyhow::bail!(
+                    "没有可用的健康凭证（{} 个凭证不在启用时段内）",
+                    scheduled_off
+                );
+            }
+            if locked > 0 {
+                anyhow::bail!(
+                    "没有可用的凭证：{} 个凭证因加密密钥不可用而锁定，请先解锁",
+                    locked
+                );
+            }
+            anyhow::bail!("没有可用的健康凭证");
+        }
+
+        let candidates: Vec<_> = healthy_creds
+            .iter()
+            .filter_map(|(id, c)| route(id, c).map(|e| (*id, *c, e)))
+            .filter(|(id, _, endpoint)| leases.has_capacity(id, *endpoint))
+            .collect();
+
+        // 观察期凭证只分到一小部分流量，没有其他可用凭证时除外
+        let (canaries, regular): (Vec<_>, Vec<_>) = candidates
+            .into_iter()
+            .partition(|(_, c, _)| config.canary.enabled && c.canary.is_some());
+        let use_canary =
+            !canaries.is_empty() && (regular.is_empty() || canary::route_to_canary(&config.canary));
+        let candidates = if use_canary { canaries } else { regular };
+
+        // 按健康分数加权随机选择，租约越多权重越低（共享额度的凭证合并计算）
+        let mut weights: Vec<f64> = candidates
+            .iter()
+            .map(|(id, c, endpoint)| {
+                let linked = leases.total(&quota_link::linked_ids(&config.quota_link, &creds, id));
+                c.health_score as f64 / (1 + leases.active(id, *endpoint) + linked) as f64
+            })
+            .collect();
+        // 用户的选择钩子可以直接指定凭证或调整权重。钩子在锁外执行，
+        // 结束后重新计算候选，再把决定应用到最新的候选上
+        let ids: Vec<&str> = candidates.iter().map(|(id, _, _)| id.as_str()).collect();
+        let hooked = match &hook_decision {
+            Some(Some(decision)) => hooks::apply(decision, &ids, &mut weights)?,
+            None if hooks::has_select(&config.hooks) => {
+                let input = selection_input(model, options, &candidates, &weights, &leases);
+                let ids = ids.iter().map(|id| id.to_string()).collect();
+                drop(leases);
+                drop(creds);
+                hook_decision = Some(hooks::decide(&config.hooks, ids, input).await?);
+                continue;
+            }
+            _ => None,
+        };
+        let (id, credential, endpoint_type) = hooked
+            .or_else(|| weighted_choice(&weights))
+            .map(|index| candidates[index])
+            .ok_or_else(|| anyhow::anyhow!("所有凭证的并发已满"))?;
+
+        let mut acquired = build_acquired_credential(id, credential, endpoint_type)?;
+        // 选定凭证后才计入租户配额，排队、被拒或共享响应的请求不占用额度
+        if let Some(tenant) = tenant {
+            tenants::admit(tenant)?;
+        }
+        if !raw && failover::start_probe(id, credential, endpoint_type, &config.failover) {
+            debug!("凭证 {} 试探已切换的 {} 端点", id, endpoint_type);
+        }
+
+        let lease_id = leases
+            .acquire(id, endpoint_type, model)
+            .ok_or_else(|| anyhow::anyhow!("所有凭证的并发已满"))?;
+        if let Some(hash) = &fingerprint {
+            dedup::register(hash, &lease_id, id);
+        }
+        if let Some(request_id) = &options.request_id {
+            retry_budget::start(request_id, &lease_id, id, endpoint_type);
+        }
+        leases.set_client_name(&lease_id, options.client_name.clone());
+        leases.set_tags(&lease_id, request_tags::sanitize(&options.tags));
+        if let Some(tenant) = tenant {
+            leases.set_tenant(&lease_id, Some(tenant.id.clone()));
+            acquired
+                .metadata
+                .insert("tenant".to_string(), serde_json::json!(tenant.id));
+        }
+        acquired
+            .metadata
+            .insert("lease_id".to_string(), serde_json::json!(lease_id));
+        if let Some(plan) = heartbeat::resolve(&config.heartbeat, tenant).plan(endpoint_type) {
+            acquired
+                .metadata
+                .insert("sse_heartbeat".to_string(), serde_json::to_value(plan)?);
+        }
+        if let Some(fault) = chaos::assign(&config.chaos, &lease_id, id) {
+            acquired
+                .metadata
+                .insert("chaos_fault".to_string(), serde_json::to_value(fault)?);
+        }
+        if let Some(from) = preferred_route(id, credential).filter(|e| *e != endpoint_type) {
+            leases.set_failover_from(&lease_id, from);
+            acquired.metadata.insert(
+                "failover_from".to_string(),
+                serde_json::json!(from.to_string()),
+            );
+            events::emit(
+                "endpoint_failover",
+                format!(
+                    "凭证 {} 的 {} 端点持续失败，{} 改走 {}",
+                    id, from, model, endpoint_type
+                ),
+                serde_json::json!({
+                    "credential_id": id,
+                    "model": model,
+                    "from": from.to_string(),
+                    "to": endpoint_type.to_string(),
+                    "lease_id": lease_id,
+                }),
+            );
+        }
+        if let Some(ref client_name) = options.client_name {
+            acquired
+                .metadata
+                .insert("client_name".to_string(), serde_json::json!(client_name));
+        }
+        if raw {
+            acquired
+                .metadata
+                .insert("raw_passthrough".to_string(), serde_json::json!(true));
+        } else if let Some(request) = &options.request {
+            if reassembly::should_reassemble(&config.reassembly, request, client_name) {
+                acquired
+                    .metadata
+                    .insert("reassemble_stream".to_string(), serde_json::json!(true));
+            }
+        }
+
+        return Ok(acquired);
+    }
+}
+
+/// 凭证不能分配的原因（与 acquire_credential 的筛选条件一致），可分配时返回 None
+fn unavailable_reason(
+    id: &str,
+    credential: &DroidCredentials,
+    required_tier: u8,
+) -> Option<&'static str> {
+    if credential.read_only {
+        Some("只读凭证不分配流量")
+    } else if !credential.is_healthy() {
+        Some("健康分数过低")
+    } else if credential.in_cooldown() {
+        Some("处于冷却期")
+    } else if secret_lock::is_locked(id) {
+        Some("加密密钥不可用，已锁定")
+    } else if !credential.in_active_hours() {
+        Some("不在启用时段内")
+    } else if credential.tier < required_tier {
+        Some("等级不足")
+    } else {
+        None
+    }
+}
+
+/// 占用指定凭证的租约（广播、批处理等由调用方指定凭证的场景）
+///
+/// 与 `acquire_credential` 使用相同的检查：暂停、维护退避、租户、只读、
+/// 等级、启用时段、加密锁定、冷却与健康状态；不排队等待，也不做故障转移
+/// 改道。返回实际使用的端点，调用方结束后须按 metadata 中的 `lease_id`
+/// 调用 `release_credential`，以计入用量与健康状态。
+pub async fn acquire_pinned(
+    credential_id: &str,
+    model: &str,
+    options: &AcquireOptions,
+) -> Result<(EndpointType, AcquiredCredential)> {
+    let config = get_config();
+    if control::is_paused() {
+        anyhow::bail!("Provider 已暂停");
+    }
+    if maintenance::is_active() {
+        anyhow::bail!("Factory 服务疑似维护中，已暂停分配凭证");
+    }
+    let tenant = tenants::resolve(&config.tenants, options.tenant_key.as_deref())?;
+    if let Some(tenant) = tenant.filter(|t| !t.allows_credential(credential_id)) {
+        anyhow::bail!("租户 {} 不能使用凭证 {}", tenant.id, credential_id);
+    }
+
+    let creds = CREDENTIALS.read().await;
+    let credential = creds
+        .get(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    let required_tier = config.model_tiers.required_tier(model);
+    if let Some(reason) = unavailable_reason(credential_id, credential, required_tier) {
+        anyhow::bail!("凭证 {} 当前不可用：{}", credential_id, reason);
+    }
+    let registry = ModelRegistry::build(creds.iter());
+    let custom = registry.custom_model(credential_id, credential, model);
+    let endpoint_type = endpoint_for_model(model, credential, custom)
+        .ok_or_else(|| anyhow::anyhow!("凭证 {} 无法使用模型 {}", credential_id, model))?;
+    let mut acquired = build_acquired_credential(credential_id, credential, endpoint_type)?;
+
+    let mut leases = LEASES.write().await;
+    prepare_leases(&mut leases, &config.leases);
+    if !leases.has_capacity(credential_id, endpoint_type) {
+        anyhow::bail!("凭证 {} 的 {} 端点并发已满", credential_id, endpoint_type);
+    }
+    if let Some(tenant) = tenant {
+        tenants::admit(tenant)?;
+    }
+    let lease_id = leases
+        .acquire(credential_id, endpoint_type, model)
+        .ok_or_else(|| {
+            anyhow::anyhow!("凭证 {} 的 {} 端点并发已满", credential_id, endpoint_type)
+        })?;
+    leases.set_client_name(&lease_id, options.client_name.clone());
+    leases.set_tags(&lease_id, request_tags::sanitize(&options.tags));
+    if let Some(tenant) = tenant {
+        leases.set_tenant(&lease_id, Some(tenant.id.clone()));
+        acquired
+            .metadata
+            .insert("tenant".to_string(), serde_json::json!(tenant.id));
+    }
+    acquired
+        .metadata
+        .insert("lease_id".to_string(), serde_json::json!(lease_id));
+    Ok((endpoint_type, acquired))
+}
+
+/// 有可直接使用的凭证时标记凭证池就绪
+async fn check_pool_ready() {
+    if startup::is_ready() {
+        return;
+    }
+    let usable = CREDENTIALS.read().await.values().any(|c| {
+        let authorized = match c.auth_type {
+            AuthType::ApiKey => !c.api_keys.is_empty(),
+            AuthType::OAuth => {
+                c.access_token.is_some()
+                    && !crate::token_refresh::is_token_expired(c.expires_at.as_deref())
+            }
+        };
+        authorized && !c.read_only && c.is_healthy() && !c.in_cooldown()
+    });
+    if usable {
+        startup::mark_ready("已有可用凭证");
+    }
+}
+
+/// 按权重随机选择下标，权重全为 0 或为空时返回 None
+fn weighted_choice(weights: &[f64]) -> Option<usize> {
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let mut point = rand::random::<f64>() * total;
+    for (index, weight) in weights.iter().enumerate() {
+        if point < *weight {
+            return Some(index);
+        }
+        point -= weight;
+    }
+    weights.iter().rposition(|w| *w > 0.0)
+}
+
+/// 选择钩子的输入：模型、调用方、标签、原始请求与候选凭证
+fn selection_input(
+    model: &str,
+    options: &AcquireOptions,
+    candidates: &[(&String, &DroidCredentials, EndpointType)],
+    weights: &[f64],
+    leases: &LeaseTracker,
+) -> serde_json::Value {
+    let candidates: Vec<_> = candidates
+        .iter()
+        .zip(weights)
+        .map(|((id, c, endpoint), weight)| {
+            serde_json::json!({
+                "credential_id": id,
+                "name": c.name,
+                "organization_id": c.organization_id,
+                "endpoint": endpoint.to_string(),
+                "health_score": c.health_score,
+                "tier": c.tier,
+                "canary": c.canary.is_some(),
+                "active_leases": leases.active(id, *endpoint),
+                "weight": weight,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "model": model,
+        "client_name": options.client_name,
+        "tags": options.tags,
+        "request": options.request,
+        "candidates": candidates,
+    })
+}
+
+/// 构建返回给宿主的凭证（URL 与请求头）
+fn build_acquired_credential(
+    id: &str,
+    credential: &DroidCredentials,
+    endpoint_type: EndpointType,
+) -> Result<AcquiredCredential> {
+    let endpoint_path = get_endpoint_path(endpoint_type);
+    let base_url = format!("{}{}", FACTORY_API_BASE_URL, endpoint_path);
+
+    let mut headers = HashMap::new();
+    headers.insert("Content-Type".to_string(), "application/json".to_string());
+    headers.insert(
+        "User-Agent".to_string(),
+        user_agent::resolve(Some(credential)),
+    );
+    headers.insert("x-factory-client".to_string(), "cli".to_string());
+    let compression = get_config().compression;
+    if compression.enabled {
+        headers.insert("Accept-Encoding".to_string(), compression.accept_encoding);
+    }
+
+    match credential.auth_type {
+        AuthType::OAuth => {
+            let token = credential
+                .access_token
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("凭证没有有效的 access_token"))?;
+
+            headers.insert("Authorization".to_string(), format!("Bearer {}", token));
+        }
+        AuthType::ApiKey => {
+            // 选择一个可用的 API Key
+            let active_keys: Vec<_> = credential
+                .api_keys
+                .iter()
+                .filter(|k| k.status == "active")
+                .collect();
+
+            if active_keys.is_empty() {
+                anyhow::bail!("没有可用的 API Key");
+            }
+
+            // 随机选择一个
+            let selected = &active_keys[rand::random::<usize>() % active_keys.len()];
+            let api_key = key_ring::decrypt(&selected.encrypted_key)?;
+
+            headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
+        }
+    }
+
+    let mut metadata = HashMap::new();
+    metadata.insert(
+        "endpoint_type".to_string(),
+        serde_json::json!(endpoint_type.to_string()),
+    );
+    // 宿主转发时按此顺序发送请求头，以贴近 factory-cli 的流量特征
+    let header_order: Vec<String> = ordered_headers(&headers, &get_config().http.header_order)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    metadata.insert("header_order".to_string(), serde_json::json!(header_order));
+    // 宿主转发时同样固定出口网卡与协议
+    let http_config = get_config().http;
+    match crate::http::resolve_bind_address(&http_config) {
+        Ok(Some(addr)) => {
+            metadata.insert("local_address".to_string(), serde_json::json!(addr));
+        }
+        Ok(None) => {}
+        Err(e) => warn!("解析出站绑定地址失败: {}", e),
+    }
+    if http_config.ip_family != crate::http::IpFamily::Auto {
+        metadata.insert(
+            "ip_family".to_string(),
+            serde_json::json!(http_config.ip_family),
+        );
+    }
+    let timeouts = get_config().timeouts.for_endpoint(endpoint_type);
+    metadata.insert("timeouts".to_string(), serde_json::json!(timeouts));
+
+    Ok(AcquiredCredential {
+        id: id.to_string(),
+        name: credential.name.clone(),
+        auth_type: credential.auth_type.to_string(),
+        base_url: Some(base_url),
+        headers,
+        metadata,
+    })
+}
+
+/// 为指定凭证构建某个端点的请求信息（不占用租约，供批处理等直连场景使用）
+pub async fn authorize_credential(
+    credential_id: &str,
+    endpoint_type: EndpointType,
+) -> Result<AcquiredCredential> {
+    let credentials = CREDENTIALS.read().await;
+    let credential = credentials
+        .get(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    let supported = &credential.supported_endpoints;
+    if !supported.is_empty() && !supported.contains(&endpoint_type) {
+        anyhow::bail!("凭证不支持 {} 端点", endpoint_type);
+    }
+    build_acquired_credential(credential_id, credential, endpoint_type)
+}
+
+/// 清理租约关联的流式状态
+fn finish_lease_state(lease_id: &str) {
+    stream_progress::finish(lease_id);
+    chaos::finish(lease_id);
+    failover::finish_stream(lease_id);
+    crate::compression::finish_stream(lease_id);
+}
+
+/// 套用租约配置，回收超时未释放的租约（宿主崩溃或遗漏了 release）
+fn prepare_leases(leases: &mut LeaseTracker, config: &LeaseConfig) {
+    leases.set_max_per_endpoint(config.max_per_endpoint);
+    for (lease_id, lease) in leases.expire(config.ttl_secs, Utc::now()) {
+        warn!(
+            "租约 {} 超过 {} 秒未释放，已回收（凭证 {}）",
+            lease_id, config.ttl_secs, lease.credential_id
+        );
+        dedup::release(&lease_id);
+        finish_lease_state(&lease_id);
+        retry_budget::finish(&lease_id, false, None, Some("租约超时".to_string()));
+        events::emit(
+            "lease_expired",
+            format!("凭证 {} 的租约超时未释放，已回收", lease.credential_id),
+            serde_json::json!({
+                "lease_id": lease_id,
+                "credential_id": lease.credential_id,
+                "endpoint_type": lease.endpoint_type.to_string(),
+                "model": lease.model,
+                "acquired_at": lease.acquired_at.to_rfc3339(),
+            }),
+        );
+    }
+}
+
+/// 释放凭证
+pub async fn release_credential(credential_id: &str, report: ReleaseReport) -> Result<()> {
+    if credential_id == mock::MOCK_CREDENTIAL_ID && mock::is_enabled() {
+        return Ok(());
+    }
+    let lease_id = report.lease_id.as_deref();
+
+    // 去重跟随者不占用租约，也不重复计入统计
+    if let Some(lease_id) = lease_id {
+        if dedup::release(lease_id) {
+            return Ok(());
+        }
+    }
+
+    let lease = LEASES.write().await.release(credential_id, lease_id);
+    if let Some(lease_id) = lease_id {
+        finish_lease_state(lease_id);
+        let error = report.error.as_ref();
+        retry_budget::finish(
+            lease_id,
+            report.status == ReleaseStatus::Success,
+            error.and_then(|e| e.status_code),
+            match report.status {
+                ReleaseStatus::Cancelled => Some("已取消".to_string()),
+                _ => error.and_then(|e| e.message.clone()),
+            },
+        );
+    }
+
+    let usage = report.usage.clone().unwrap_or_default();
+    let tenant = lease.as_ref().and_then(|l| l.tenant.clone());
+    if let Some(ref tenant) = tenant {
+        tenants::record_usage(tenant, usage.input_tokens, usage.output_tokens);
+    }
+    stats::record(UsageRecord {
+        timestamp: Utc::now().to_rfc3339(),
+        credential_id: credential_id.to_string(),
+        model: lease.as_ref().map(|l| l.model.clone()),
+        endpoint_type: lease.as_ref().map(|l| l.endpoint_type.to_string()),
+        client_name: lease.as_ref().and_then(|l| l.client_name.clone()),
+        input_tokens: usage.input_tokens,
+        output_tokens: usage.output_tokens,
+        latency_ms: report.latency_ms,
+        success: report.status == ReleaseStatus::Success,
+        failover_from: lease
+            .as_ref()
+            .and_then(|l| l.failover_from)
+            .map(|e| e.to_string()),
+        tenant,
+        tags: lease.as_ref().map(|l| l.tags.clone()).unwrap_or_default(),
+    });
+
+    let mut creds = write_credentials().await;
+    let mut shared_cooldown = None;
+    // 参与分配的凭证一起 502 / 503 时属于上游整体故障，不计入单个凭证
+    let total = creds
+        .iter()
+        .filter(|(id, c)| !c.read_only && c.in_active_hours() && !secret_lock::is_locked(id))
+        .count();
+    let outage = match report.status {
+        ReleaseStatus::Success => maintenance::observe(credential_id, None, total),
+        ReleaseStatus::Error => {
+            let status_code = report.error.as_ref().and_then(|e| e.status_code);
+            status_code.is_some_and(|code| maintenance::observe(credential_id, Some(code), total))
+        }
+        ReleaseStatus::Cancelled => false,
+    };
+
+    if let Some(credential) = creds.get_mut(credential_id) {
+        credential.usage_count += 1;
+        if let Ok(ring) = key_ring::current() {
+            reencrypt_stale(credential_id, credential, &ring);
+        }
+
+        match report.status {
+            ReleaseStatus::Success => {
+                credential.health.record_request(true, report.latency_ms);
+                keepalive::touch(credential_id);
+                dead_credentials::record_success(credential);
+                credential.cooldown_until = None;
+                if let Some(ref lease) = lease {
+                    let config = get_config().failover;
+                    let endpoint = lease.endpoint_type;
+                    failover::record_result(
+                        credential_id,
+                        credential,
+                        endpoint,
+                        true,
+                        None,
+                        &config,
+                    );
+                }
+                debug!("凭证使用成功: {}", credential_id);
+            }
+            ReleaseStatus::Error => {
+                let error = report.error.clone().unwrap_or_default();
+                credential.error_count += 1;
+                credential.record_error(CredentialError {
+                    timestamp: Utc::now().to_rfc3339(),
+                    status_code: error.status_code,
+                    error_type: error.error_type.clone(),
+                    message: error.message.clone(),
+                    request_id: error.request_id.clone(),
+                });
+                if outage {
+                    debug!(
+                        "凭证 {} 的失败属于上游整体故障，不扣减健康状态",
+                        credential_id
+                    );
+                } else {
+                    credential.health.record_request(false, report.latency_ms);
+                    if let Some(ref lease) = lease {
+                        failover::record_result(
+                            credential_id,
+                            credential,
+                            lease.endpoint_type,
+                            false,
+                            error.status_code,
+                            &get_config().failover,
+                        );
+                    }
+
+                    // 冷却时长：显式值优先，否则按状态码推断
+                    let cooldown_seconds = error.cooldown_seconds.or_else(|| {
+                        error
+                            .status_code
+                            .and_then(|status| parse_error(status, "", None))
+                            .and_then(|e| e.cooldown_seconds)
+                    });
+                    if let Some(seconds) = cooldown_seconds.filter(|s| *s > 0) {
+                        let until = Utc::now() + chrono::Duration::seconds(seconds as i64);
+                        credential.cooldown_until = Some(until.to_rfc3339());
+                        credential.health.record_cooldown(Utc::now());
+                        debug!("凭证进入冷却 {} 秒: {}", seconds, credential_id);
+                        if error.status_code == Some(429) {
+                            shared_cooldown = credential.cooldown_until.clone();
+                        }
+                    }
+
+                    if report.mark_unhealthy {
+                        credential.health.marked_unhealthy = true;
+                        warn!("凭证标记为不健康: {}", credential_id);
+                    }
+                }
+            }
+            ReleaseStatus::Cancelled => {
+                debug!("请求被取消: {}", credential_id);
+            }
+        }
+        let canary_config = get_config().canary;
+        if canary_config.enabled
+            && credential.canary.is_some()
+            && report.status != ReleaseStatus::Cancelled
+            && !outage
+        {
+            let success = report.status == ReleaseStatus::Success;
+            record_canary(credential_id, credential, &canary_config, success);
+        }
+        credential.update_health_score();
+        backoff_state::record(credential);
+    }
+
+    // 额度耗尽时同组凭证一起冷却
+    let quota_config = get_config().quota_link;
+    if let Some(until) = shared_cooldown.filter(|_| quota_config.share_cooldown) {
+        for linked in quota_link::linked_ids(&quota_config, &creds, credential_id) {
+            if let Some(credential) = creds.get_mut(&linked) {
+                let parse = |t: &str| chrono::DateTime::parse_from_rfc3339(t).ok();
+                let current = credential.cooldown_until.as_deref().and_then(parse);
+                if current < parse(&until) {
+                    credential.cooldown_until = Some(until.clone());
+                    backoff_state::record(credential);
+                    debug!("凭证 {} 与 {} 共享额度，一同冷却", linked, credential_id);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 记录观察期凭证的请求结果，观察结束或失败时发出事件
+fn record_canary(
+    credential_id: &str,
+    credential: &mut DroidCredentials,
+    config: &canary::CanaryConfig,
+    success: bool,
+) {
+    let error_rate = credential.canary.as_ref().map(|c| c.error_rate());
+    match canary::record(config, credential, success, Utc::now()) {
+        CanaryVerdict::Probation => {}
+        CanaryVerdict::Promoted => {
+            info!("凭证 {} 通过金丝雀观察期", credential_id);
+            events::emit(
+                "canary_promoted",
+                format!("凭证 {} 通过观察期，恢复正常流量", credential_id),
+                serde_json::json!({ "credential_id": credential_id }),
+            );
+        }
+        CanaryVerdict::Failed => {
+            warn!(
+                "凭证 {} 金丝雀观察期错误率过高，已标记为不健康",
+                credential_id
+            );
+            events::emit(
+                "canary_failed",
+                format!("凭证 {} 观察期错误率过高，已停止分配流量", credential_id),
+                serde_json::json!({ "credential_id": credential_id, "error_rate": error_rate }),
+            );
+        }
+    }
+}
+
+/// 手动结束观察期
+pub async fn promote_canary(credential_id: &str) -> Result<()> {
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    if credential.canary.take().is_none() {
+        anyhow::bail!("凭证不在观察期: {}", credential_id);
+    }
+    info!("凭证 {} 手动结束观察期", credential_id);
+    Ok(())
+}
+
+/// 将旧密钥加密的字段迁移到当前主密钥，失败时保留原值
+fn reencrypt_stale(credential_id: &str, credential: &mut DroidCredentials, ring: &KeyRing) {
+    let fields = credential
+        .api_keys
+        .iter_mut()
+        .map(|entry| &mut entry.encrypted_key)
+        .chain(credential.previous_refresh_token.as_mut());
+    for field in fields {
+        match ring.reencrypt(field) {
+            Ok(Some(reencrypted)) => {
+                *field = reencrypted;
+                debug!(
+                    "凭证 {} 的加密字段已迁移到主密钥 {}",
+                    credential_id,
+                    ring.primary_id()
+                );
+            }
+            Ok(None) => {}
+            Err(e) => warn!("凭证 {} 重新加密失败: {}", credential_id, e),
+        }
+    }
+}
+
+/// 设置凭证默认使用的端点类型
+pub async fn set_endpoint_type(credential_id: &str, endpoint_type: EndpointType) -> Result<()> {
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    credential.endpoint_type = endpoint_type;
+    info!("凭证 {} 默认端点设置为 {}", credential_id, endpoint_type);
+    Ok(())
+}
+
+/// 设置凭证的自定义模型条目
+pub async fn set_extra_models(credential_id: &str, models: Vec<CustomModel>) -> Result<()> {
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    info!("凭证 {} 设置 {} 个自定义模型", credential_id, models.len());
+    credential.extra_models = models;
+    Ok(())
+}
+
+/// 更新凭证备注与元数据，传入 None 的字段保持不变；元数据整体替换
+pub async fn set_credential_notes(
+    credential_id: &str,
+    notes: Option<String>,
+    metadata: Option<HashMap<String, String>>,
+) -> Result<()> {
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    if let Some(notes) = notes {
+        credential.notes = Some(notes.trim().to_string()).filter(|n| !n.is_empty());
+    }
+    if let Some(metadata) = metadata {
+        credential.metadata = metadata;
+    }
+    Ok(())
+}
+
+/// 凭证列表项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialSummary {
+    pub id: String,
+    pub name: Option<String>,
+    pub auth_type: AuthType,
+    pub endpoint_type: EndpointType,
+    pub owner_email: Option<String>,
+    pub organization_id: Option<String>,
+    /// 组织名称（已解析时）
+    pub organization_name: Option<String>,
+    pub health_score: u8,
+    pub notes: Option<String>,
+    pub metadata: HashMap<String, String>,
+    pub canary: Option<canary::CanaryState>,
+    pub read_only: bool,
+    pub expires_at: Option<String>,
+    pub allowed_models: Vec<String>,
+    pub blocked_models: Vec<String>,
+    /// 共享额度的预算组
+    pub budget_group: Option<String>,
+    pub active_hours: Option<ActiveSchedule>,
+    /// 当前是否在启用时段内
+    pub in_active_hours: bool,
+    pub tier: u8,
+    /// 加密密钥不可用，机密尚未解开
+    pub locked: bool,
+    /// 与其他凭证属于同一账号时为该账号（邮箱或 user_id）
+    pub linked_account: Option<String>,
+}
+
+/// 列出凭证（按名称排序，不含密钥）
+pub async fn list_credentials() -> Vec<CredentialSummary> {
+    let quota_config = get_config().quota_link;
+    let creds = CREDENTIALS.read().await;
+    let accounts = account_link::linked_accounts(&account_link::groups(&creds));
+    let mut summaries: Vec<_> = creds
+        .iter()
+        .map(|(id, c)| CredentialSummary {
+            id: id.clone(),
+            name: c.name.clone(),
+            auth_type: c.auth_type,
+            endpoint_type: c.endpoint_type,
+            owner_email: c.owner_email.clone(),
+            organization_id: c.organization_id.clone(),
+            organization_name: c.organization_id.as_deref().and_then(org_names::lookup),
+            health_score: c.health_score,
+            notes: c.notes.clone(),
+            metadata: c.metadata.clone(),
+            canary: c.canary.clone(),
+            read_only: c.read_only,
+            expires_at: c.expires_at.clone(),
+            allowed_models: c.allowed_models.clone(),
+            blocked_models: c.blocked_models.clone(),
+            budget_group: quota_link::budget_key(&quota_config, c),
+            active_hours: c.active_hours.clone(),
+            in_active_hours: c.in_active_hours(),
+            tier: c.tier,
+            locked: secret_lock::is_locked(id),
+            linked_account: accounts.get(id).cloned(),
+        })
+        .collect();
+    drop(creds);
+    summaries.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+    // 名称未解析的组织在后台查询，下次列出时带上
+    let unresolved = summaries
+        .iter()
+        .filter_map(|s| s.organization_id.as_deref())
+        .any(org_names::needs_resolve);
+    if unresolved {
+        tokio::spawn(resolve_org_names(false));
+    }
+    summaries
+}
+
+/// 设置凭证允许 / 禁止使用的模型
+pub async fn set_model_access(
+    credential_id: &str,
+    allowed_models: Vec<String>,
+    blocked_models: Vec<String>,
+) -> Result<()> {
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    credential.allowed_models = allowed_models;
+    credential.blocked_models = blocked_models;
+    Ok(())
+}
+
+/// 设置凭证是否只读
+pub async fn set_read_only(credential_id: &str, read_only: bool) -> Result<()> {
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    credential.read_only = read_only;
+    info!(
+        "凭证 {} {}",
+        credential_id,
+        if read_only {
+            "设为只读"
+        } else {
+            "恢复分配流量"
+        }
+    );
+    Ok(())
+}
+
+/// 设置凭证等级（按模型限制可用凭证）
+pub async fn set_tier(credential_id: &str, tier: u8) -> Result<()> {
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    credential.tier = tier;
+    info!("凭证 {} 的等级设为 {}", credential_id, tier);
+    Ok(())
+}
+
+/// 设置凭证的共享额度预算组，传入 None 恢复按组织关联
+pub async fn set_quota_group(credential_id: &str, quota_group: Option<String>) -> Result<()> {
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    credential.quota_group = quota_group.filter(|g| !g.is_empty());
+    info!(
+        "凭证 {} 的预算组设为 {:?}",
+        credential_id, credential.quota_group
+    );
+    Ok(())
+}
+
+/// 设置凭证的启用时段，传入 None 取消限制
+pub async fn set_active_hours(
+    credential_id: &str,
+    active_hours: Option<ActiveSchedule>,
+) -> Result<()> {
+    if let Some(schedule) = &active_hours {
+        schedule.validate()?;
+    }
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    credential.active_hours = active_hours;
+    info!(
+        "凭证 {} 的启用时段设为 {:?}",
+        credential_id, credential.active_hours
+    );
+    Ok(())
+}
+
+/// 按预算组汇总共享额度的凭证用量
+pub async fn list_quota_groups() -> Vec<quota_link::QuotaGroup> {
+    quota_link::summarize(&get_config().quota_link, &*CREDENTIALS.read().await)
+}
+
+/// 按账号分组凭证，`linked_only` 时只返回关联了多个凭证的账号
+pub async fn list_account_groups(linked_only: bool) -> Vec<account_link::AccountGroup> {
+    let mut groups = account_link::groups(&*CREDENTIALS.read().await);
+    if linked_only {
+        groups.retain(account_link::AccountGroup::is_linked);
+    }
+    groups
+}
+
+/// 凭证 ID → 账号，用于按账号汇总用量
+pub async fn credential_accounts() -> HashMap<String, String> {
+    account_link::account_map(&account_link::groups(&*CREDENTIALS.read().await))
+}
+
+/// 设置凭证单独使用的 User-Agent，传入 None 恢复全局配置
+pub async fn set_user_agent(credential_id: &str, user_agent: Option<String>) -> Result<()> {
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    credential.user_agent = user_agent.filter(|ua| !ua.is_empty());
+    Ok(())
+}
+
+/// Token 已过期且无法自动刷新的 OAuth 凭证（名称，无名称时为 ID）
+pub async fn expired_credentials() -> Vec<String> {
+    let mut expired: Vec<String> = CREDENTIALS
+        .read()
+        .await
+        .iter()
+        .filter(|(_, c)| c.auth_type == AuthType::OAuth)
+        .filter(|(_, c)| {
+            crate::token_refresh::is_token_expired(c.expires_at.as_deref())
+                && (c.refresh_token.is_none() || !c.is_healthy())
+        })
+        .map(|(id, c)| c.name.clone().unwrap_or_else(|| id.clone()))
+        .collect();
+    expired.sort();
+    expired
+}
+
+/// 可以保活的凭证（OAuth 需带 Refresh Token）
+pub async fn keepalive_candidates() -> Vec<KeepAliveCandidate> {
+    CREDENTIALS
+        .read()
+        .await
+        .iter()
+        .filter(|(_, c)| c.auth_type == AuthType::ApiKey || c.refresh_token.is_some())
+        .map(|(id, c)| KeepAliveCandidate {
+            credential_id: id.clone(),
+            auth_type: c.auth_type,
+            last_refresh: c
+                .last_refresh
+                .as_deref()
+                .and_then(|t| t.parse::<chrono::DateTime<Utc>>().ok()),
+        })
+        .collect()
+}
+
+/// 维护窗口恢复探测使用的凭证：健康分数最高的可用凭证
+pub async fn recovery_probe_target() -> Option<(String, EndpointType)> {
+    CREDENTIALS
+        .read()
+        .await
+        .iter()
+        .filter(|(_, c)| c.access_token.is_some() || !c.api_keys.is_empty())
+        .max_by_key(|(id, c)| (c.health_score, std::cmp::Reverse((*id).clone())))
+        .map(|(id, c)| (id.clone(), c.endpoint_type))
+}
+
+/// Token 已过期或即将过期、可以自动刷新的 OAuth 凭证
+pub async fn credentials_needing_refresh() -> Vec<String> {
+    let mut ids: Vec<String> = CREDENTIALS
+        .read()
+        .await
+        .iter()
+        .filter(|(_, c)| c.auth_type == AuthType::OAuth && c.refresh_token.is_some())
+        .filter(|(_, c)| {
+            let expires_at = c.expires_at.as_deref();
+            crate::token_refresh::is_token_expired(expires_at)
+                || crate::token_refresh::is_token_expiring_soon(expires_at)
+        })
+        .map(|(id, _)| id.clone())
+        .collect();
+    ids.sort();
+    ids
+}
+
+/// 全部 OAuth 凭证的 Refresh Token 寿命（剩余天数少的在前）
+pub async fn refresh_token_ages() -> Vec<RefreshTokenAge> {
+    let config = get_config().token_age;
+    let now = Utc::now();
+    let mut ages: Vec<_> = CREDENTIALS
+        .read()
+        .await
+        .iter()
+        .filter_map(|(id, c)| token_age::age_of(id, c, now, &config))
+        .collect();
+    ages.sort_by_key(|age| age.days_remaining);
+    ages
+}
+
+/// 长期失效的凭证（同时更新存活记录）
+pub async fn dead_credentials() -> Vec<DeadCredential> {
+    let config = get_config().dead_credentials;
+    dead_credentials::scan(&config, CREDENTIALS.read().await.iter(), Utc::now())
+}
+
+/// 删除凭证（宿主移除凭证时调用，之后不再从凭证文件载入）
+pub async fn delete_credential(credential_id: &str) -> Result<()> {
+    let removed = write_credentials()
+        .await
+        .remove(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    forget_credential(credential_id, &removed);
+    info!("凭证已删除: {}", credential_id);
+    Ok(())
+}
+
+/// 清理已移除凭证的附属状态
+fn forget_credential(credential_id: &str, credential: &DroidCredentials) {
+    secret_lock::forget(credential_id);
+    relogin::clear(credential_id);
+    refresh_failure::clear(credential_id);
+    dead_credentials::forget(credential);
+    backoff_state::forget(credential);
+}
+
+/// 归档或删除失效凭证；未指定 ID 时处理全部失效凭证
+pub async fn prune_dead_credentials(
+    credential_ids: Option<Vec<String>>,
+    action: CleanupAction,
+) -> Result<PruneReport> {
+    let dead: Vec<String> = dead_credentials()
+        .await
+        .into_iter()
+        .map(|d| d.credential_id)
+        .collect();
+    let requested = credential_ids.unwrap_or_else(|| dead.clone());
+
+    // 与 acquire 相同的加锁顺序（先凭证池、后租约），文件读写在释放锁之后进行
+    let (removed, skipped) = {
+        let mut creds = write_credentials().await;
+        let leases = LEASES.read().await;
+        let (targets, skipped): (Vec<_>, Vec<_>) = requested
+            .into_iter()
+            .partition(|id| dead.contains(id) && !leases.has_leases(id));
+        let removed: HashMap<String, DroidCredentials> = targets
+            .iter()
+            .filter_map(|id| Some((id.clone(), creds.remove(id)?)))
+            .collect();
+        (removed, skipped)
+    };
+
+    let archive_path = match action {
+        CleanupAction::Archive => match dead_credentials::archive(&removed) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                // 归档失败时放回凭证池，不丢失凭证
+                write_credentials().await.extend(removed);
+                return Err(e);
+            }
+        },
+        CleanupAction::Delete => None,
+    };
+    for (id, credential) in &removed {
+        secret_lock::forget(id);
+        relogin::clear(id);
+        refresh_failure::clear(id);
+        backoff_state::forget(credential);
+        if action == CleanupAction::Delete {
+            dead_credentials::forget(credential);
+        }
+    }
+
+    let mut removed: Vec<String> = removed.into_keys().collect();
+    removed.sort();
+    if !removed.is_empty() {
+        info!("已{} {} 个失效凭证", action_label(action), removed.len());
+        events::emit(
+            "credentials_pruned",
+            format!("已{} {} 个失效凭证", action_label(action), removed.len()),
+            serde_json::json!({ "action": action, "credential_ids": removed }),
+        );
+    }
+    Ok(PruneReport {
+        action: Some(action),
+        removed,
+        skipped,
+        archive_path: archive_path.map(|p| p.display().to_string()),
+    })
+}
+
+fn action_label(action: CleanupAction) -> &'static str {
+    match action {
+        CleanupAction::Archive => "归档",
+        CleanupAction::Delete => "删除",
+    }
+}
+
+/// 各凭证的健康分数
+pub async fn get_health_scores() -> HashMap<String, u8> {
+    CREDENTIALS
+        .read()
+        .await
+        .iter()
+        .map(|(id, c)| (id.clone(), c.health_score))
+        .collect()
+}
+
+/// 获取凭证最近的错误记录（新的在前）
+pub async fn get_credential_errors(credential_id: &str) -> Result<Vec<CredentialError>> {
+    let creds = CREDENTIALS.read().await;
+    let credential = creds
+        .get(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    Ok(credential.recent_errors.iter().rev().cloned().collect())
+}
+
+/// 验证凭证
+pub async fn validate_credential(credential_id: &str) -> Result<ValidationResult> {
+    let creds = CREDENTIALS.read().await;
+
+    let credential = match creds.get(credential_id) {
+        Some(credential) => credential,
+        None => {
+            return Ok(ValidationResult {
+                valid: false,
+                message: Some("凭证不存在".to_string()),
+                details: HashMap::new(),
+            })
+        }
+    };
+
+    let is_valid = match credential.auth_type {
+        AuthType::OAuth => credential.access_token.is_some() || credential.refresh_token.is_some(),
+        AuthType::ApiKey => credential.api_keys.iter().any(|k| k.status == "active"),
+    };
+
+    if !is_valid || credential.auth_type != AuthType::ApiKey {
+        let mut details = HashMap::new();
+        details.insert(
+            "health_score".to_string(),
+            serde_json::json!(credential.health_score),
+        );
+        if let Some(claims) = credential.access_token.as_deref().and_then(decode_claims) {
+            details.insert("token_claims".to_string(), serde_json::to_value(claims)?);
+        }
+
+        return Ok(ValidationResult {
+            valid: is_valid && credential.is_healthy(),
+            message: if is_valid {
+                Some("凭证有效".to_string())
+            } else {
+                Some("凭证配置不完整".to_string())
+            },
+            details,
+        });
+    }
+
+    // API Key：探测可用端点（不持有锁进行网络请求）
+    let encrypted_key = credential
+        .api_keys
+        .iter()
+        .find(|k| k.status == "active")
+        .map(|k| k.encrypted_key.clone())
+        .unwrap_or_default();
+    drop(creds);
+
+    let api_key = key_ring::decrypt(&encrypted_key)?;
+    let supported = probe::probe_endpoints(&api_key).await?;
+
+    let mut creds = write_credentials().await;
+    let (is_healthy, health_score) = match creds.get_mut(credential_id) {
+        Some(credential) => {
+            credential.supported_endpoints = supported.clone();
+            (credential.is_healthy(), credential.health_score)
+        }
+        None => (false, 0),
+    };
+
+    let mut details = HashMap::new();
+    details.insert("health_score".to_string(), serde_json::json!(health_score));
+    details.insert(
+        "supported_endpoints".to_string(),
+        serde_json::to_value(&supported)?,
+    );
+
+    Ok(ValidationResult {
+        valid: !supported.is_empty() && is_healthy,
+        message: if supported.is_empty() {
+            Some("API Key 无法访问任何端点".to_string())
+        } else {
+            Some("凭证有效".to_string())
+        },
+        details,
+    })
+}
+
+/// 刷新 Token
+pub async fn refresh_token(credential_id: &str) -> Result<TokenRefreshResult> {
+    refresh_token_with_priority(credential_id, RefreshPriority::Background).await
+}
+
+/// 按优先级刷新 Token（即将使用的凭证传 Urgent，优先于后台刷新）
+pub async fn refresh_token_with_priority(
+    credential_id: &str,
+    priority: RefreshPriority,
+) -> Result<TokenRefreshResult> {
+    if mock::is_enabled() {
+        return Ok(mock::refresh());
+    }
+    ensure_not_locked(credential_id, "刷新")?;
+    let lock = refresh_lock(credential_id).await;
+    let _guard = lock.lock().await;
+    refresh_token_locked(credential_id, priority).await
+}
+
+/// 锁定的凭证只有加密后的机密，不能刷新或复制给其他凭证
+fn ensure_not_locked(credential_id: &str, action: &str) -> Result<()> {
+    if secret_lock::is_locked(credential_id) {
+        anyhow::bail!(
+            "凭证 {} 已锁定（加密密钥不可用），解锁后才能{}",
+            credential_id,
+            action
+        );
+    }
+    Ok(())
+}
+
+/// 执行一次带完整记录的刷新，仅在成功时写回凭证
+///
+/// 与普通刷新一样受锁定检查、刷新限流和 Token 替换检查约束，只是不重试，
+/// 失败时也不记录到凭证上。刷新本身的失败写在返回的记录中。
+pub async fn debug_refresh(credential_id: &str) -> Result<RefreshTrace> {
+    if mock::is_enabled() {
+        anyhow::bail!("模拟模式不会请求 WorkOS，无法执行刷新诊断");
+    }
+    ensure_not_locked(credential_id, "刷新")?;
+    let lock = refresh_lock(credential_id).await;
+    let _guard = lock.lock().await;
+
+    let mut trace = None;
+    let result = refresh_with_mode(
+        credential_id,
+        RefreshPriority::Background,
+        RefreshMode::Trace(&mut trace),
+    )
+    .await;
+    trace.ok_or_else(|| {
+        result
+            .err()
+            .unwrap_or_else(|| anyhow::anyhow!("刷新诊断未执行: {}", credential_id))
+    })
+}
+
+/// 在凭证副本上执行一次带记录的刷新，成功时把新 Token 写入副本
+async fn trace_refresh_copy(
+    credential_id: &str,
+    credential: &mut DroidCredentials,
+) -> Result<(RefreshTrace, Result<TokenRefreshResult>)> {
+    if credential.auth_type != AuthType::OAuth {
+        anyhow::bail!("API Key 认证不需要刷新 Token");
+    }
+    let refresh_token = credential
+        .refresh_token
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("缺少 refresh_token"))?;
+    let (trace, result) = refresh_debug::trace_refresh(
+        credential_id,
+        &refresh_token,
+        credential.organization_id.as_deref(),
+    )
+    .await;
+    let result = match result {
+        Some(result) => {
+            crate::token_refresh::apply_refreshed(credential, &refresh_token, &result);
+            Ok(result)
+        }
+        None => Err(anyhow::anyhow!(trace
+            .error
+            .clone()
+            .unwrap_or_else(|| "刷新失败".to_string()))),
+    };
+    Ok((trace, result))
+}
+
+/// 完成重新登录：用登录回调的授权码换取新 Token 并写回原凭证
+///
+/// 首次运行向导发起的新账号登录没有原凭证，此时创建新凭证。返回凭证 ID。
+pub async fn complete_relogin(state: &str, code: &str) -> Result<String> {
+    let pending = relogin::take_pending(state)
+        .ok_or_else(|| anyhow::anyhow!("登录链接已失效或已使用，请使用最新的重新登录链接"))?;
+    let result = match crate::auth::workos::exchange_code(code, &pending.code_verifier).await? {
+        crate::auth::workos::RefreshOutcome::Success(result) => result,
+        outcome => anyhow::bail!("{}", outcome.message()),
+    };
+    let refresh_token = result
+        .refresh_token
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("登录响应缺少 refresh_token"))?;
+    // 首次运行向导登录的新账号：创建凭证
+    let Some(credential_id) = pending.credential_id else {
+        let credential_id = create_credential(
+            "oauth",
+            serde_json::json!({
+                "access_token": result.access_token,
+                "refresh_token": refresh_token,
+                "organization_id": result.organization_id,
+                "user_id": result.user_id,
+                "owner_email": result.owner_email,
+            }),
+        )
+        .await?;
+        info!("已通过登录创建凭证 {}", credential_id);
+        return Ok(credential_id);
+    };
+
+    let lock = refresh_lock(&credential_id).await;
+    let _guard = lock.lock().await;
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(&credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证在登录期间被删除: {}", credential_id))?;
+    // 登录了其他账号时不覆盖原凭证
+    if let (Some(expected), Some(actual)) =
+        (credential.user_id.as_deref(), result.user_id.as_deref())
+    {
+        if expected != actual {
+            anyhow::bail!(
+                "登录的账号与凭证 {} 不一致，请使用原账号登录",
+                credential_id
+            );
+        }
+    }
+    crate::token_refresh::apply_refreshed(credential, &refresh_token, &result);
+    credential.health.record_refresh(true);
+    credential.update_health_score();
+    relogin::clear(&credential_id);
+    refresh_failure::clear(&credential_id);
+    let group = credential.refresh_group.clone();
+    if let Some(group) = group {
+        sync_refresh_group(&mut creds, &credential_id, &group);
+    }
+    drop(creds);
+    check_pool_ready().await;
+    info!("凭证 {} 重新登录成功", credential_id);
+    Ok(credential_id)
+}
+
+/// 凭证刷新用的 singleflight 锁（同一刷新组共用一把锁）
+async fn refresh_lock(credential_id: &str) -> Arc<tokio::sync::Mutex<()>> {
+    let group = CREDENTIALS
+        .read()
+        .await
+        .get(credential_id)
+        .map(|c| org_discovery::refresh_group(credential_id, c))
+        .unwrap_or_else(|| credential_id.to_string());
+    singleflight::lock_for(&group)
+}
+
+/// 刷新方式
+enum RefreshMode<'a> {
+    /// 按失败类别重试，并把成功或失败记录到凭证
+    Record,
+    /// 诊断：只请求一次并捕获完整记录，失败时不改动凭证
+    Trace(&'a mut Option<RefreshTrace>),
+}
+
+/// 刷新 Token（调用方已持有该凭证的 singleflight 锁）
+async fn refresh_token_locked(
+    credential_id: &str,
+    priority: RefreshPriority,
+) -> Result<TokenRefreshResult> {
+    refresh_with_mode(credential_id, priority, RefreshMode::Record).await
+}
+
+/// 按指定方式刷新 Token（调用方已持有该凭证的 singleflight 锁）
+async fn refresh_with_mode(
+    credential_id: &str,
+    priority: RefreshPriority,
+    mut mode: RefreshMode<'_>,
+) -> Result<TokenRefreshResult> {
+    // 有进行中请求的凭证视为紧急；先取得限流许可，再复制凭证
+    let in_use = LEASES.read().await.has_leases(credential_id);
+    let priority = if in_use {
+        RefreshPriority::Urgent
+    } else {
+        priority
+    };
+    let tracing = matches!(mode, RefreshMode::Trace(_));
+    // 每次尝试重新取得限流许可并复制凭证
+    let attempt = || async {
+        let _permit = refresh_limiter::acquire(priority).await;
+        ensure_not_locked(credential_id, "刷新")?;
+
+        // 网络请求在副本上进行，不持有全局锁，刷新期间 acquire 不受阻塞
+        let mut refreshed = CREDENTIALS
+            .read()
+            .await
+            .get(credential_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+        let original_refresh_token = refreshed.refresh_token.clone();
+        let (trace, result) = if tracing {
+            let (trace, result) = trace_refresh_copy(credential_id, &mut refreshed).await?;
+            (Some(trace), result)
+        } else {
+            let result = crate::token_refresh::refresh_token(&mut refreshed).await;
+            (None, result)
+        };
+        Ok(((refreshed, original_refresh_token, trace), result))
+    };
+    // 正常刷新按失败类别退避重试，等待期间不占用许可；诊断只请求一次
+    let ((refreshed, original_refresh_token, trace), result) = if tracing {
+        attempt().await?
+    } else {
+        crate::token_refresh::with_retry(priority, attempt).await?
+    };
+    if let RefreshMode::Trace(out) = &mut mode {
+        **out = trace;
+    }
+
+    // 只在写回结果时短暂持有写锁
+    let mut creds = write_credentials().await;
+    let credential = creds
+        .get_mut(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证在刷新期间被删除: {}", credential_id))?;
+    if credential.refresh_token != original_refresh_token {
+        // 刷新期间凭证被重新登录等方式替换了 Token，以新的为准：本次结果
+        // 不记为成功或失败，也不清除重新登录提醒，直接返回当前状态
+        warn!(
+            "凭证 {} 的 Token 在刷新期间已被更新，丢弃本次刷新结果",
+            credential_id
+        );
+        return current_token_state(credential).ok_or_else(|| {
+            anyhow::anyhow!(
+                "凭证 {} 的 Token 在刷新期间被替换，且没有可用的 Access Token",
+                credential_id
+            )
+        });
+    }
+    if result.is_ok() {
+        crate::token_refresh::commit_refreshed(credential, &refreshed);
+        if let RefreshMode::Trace(Some(trace)) = &mut mode {
+            trace.committed = true;
+        }
+    } else if tracing {
+        info!("刷新诊断未成功，凭证保持不变: {}", credential_id);
+        return result;
+    } else {
+        crate::token_refresh::commit_previous_refresh_token(credential, &refreshed);
+    }
+
+    let group = credential.refresh_group.clone();
+    // 需要用户交互的挑战不计为刷新失败
+    match result {
+        Ok(_) => {
+            credential.health.record_refresh(true);
+            relogin::clear(credential_id);
+            refresh_failure::clear(credential_id);
+        }
+        Err(ref e) if e.is::<RefreshChallenge>() => {
+            relogin::mark_required(credential_id, credential, &e.to_string());
+        }
+        Err(ref e) => {
+            let failure = refresh_failure::classify(e);
+            // 本机网络问题与 WorkOS 故障不扣凭证的健康分
+            if failure.category.counts_against_credential() {
+                credential.health.record_refresh(false);
+            }
+            token_age::observe_refresh_failure(credential, &e.to_string());
+            // 会话已过期，只能重新登录
+            if failure.category == RefreshFailureCategory::InvalidGrant {
+                relogin::mark_required(credential_id, credential, "登录会话已过期");
+            }
+            credential.record_error(CredentialError {
+                timestamp: Utc::now().to_rfc3339(),
+                status_code: failure.status,
+                error_type: Some(format!("token_refresh_{}", failure.category)),
+                message: Some(e.to_string()),
+                ..Default::default()
+            });
+            refresh_failure::report(credential_id, credential.name.as_deref(), &failure);
+        }
+    }
+    credential.update_health_score();
+    backoff_state::record(credential);
+    let result = result?;
+    if let Some(group) = group {
+        sync_refresh_group(&mut creds, credential_id, &group);
+    }
+    drop(creds);
+    check_pool_ready().await;
+    info!("Token 刷新成功: {}", credential_id);
+    Ok(result)
+}
+
+/// 凭证当前的 Token 状态（没有 Access Token 时为 None）
+fn current_token_state(credential: &DroidCredentials) -> Option<TokenRefreshResult> {
+    Some(TokenRefreshResult {
+        access_token: credential.access_token.clone()?,
+        refresh_token: credential.refresh_token.clone(),
+        expires_at: credential
+            .expires_at
+            .as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Utc)),
+        organization_id: credential.organization_id.clone(),
+        user_id: credential.user_id.clone(),
+        owner_email: credential.owner_email.clone(),
+    })
+}
+
+/// 将轮换后的 Refresh Token 同步给刷新组内其他凭证
+fn sync_refresh_group(creds: &mut HashMap<String, DroidCredentials>, source_id: &str, group: &str) {
+    let Some(source) = creds.get(source_id).cloned() else {
+        return;
+    };
+    for (id, credential) in creds.iter_mut() {
+        if id == source_id || org_discovery::refresh_group(id, credential) != group {
+            continue;
+        }
+        if credential.refresh_token != source.refresh_token {
+            credential.refresh_token = source.refresh_token.clone();
+            credential.previous_refresh_token = source.previous_refresh_token.clone();
+            credential.previous_refresh_token_expires_at =
+                source.previous_refresh_token_expires_at.clone();
+            debug!("Refresh Token 已同步到同组凭证: {}", id);
+        }
+    }
+}
+
+/// 查询凭证账号所属的组织
+pub async fn discover_organizations(credential_id: &str) -> Result<Vec<DiscoveredOrg>> {
+    let access_token = {
+        let creds = CREDENTIALS.read().await;
+        let credential = creds
+            .get(credential_id)
+            .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+        if credential.auth_type != AuthType::OAuth {
+            anyhow::bail!("只有 OAuth 凭证可以查询组织");
+        }
+        credential
+            .access_token
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("凭证没有 Access Token，请先刷新"))?
+    };
+
+    let orgs = fetch_factory_orgs(&access_token).await?;
+    org_names::remember(&orgs);
+    let org_ids = orgs.into_iter().map(|org| org.id).collect();
+    let creds = CREDENTIALS.read().await;
+    let source = creds
+        .get(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    Ok(org_discovery::match_existing(org_ids, source, &creds))
+}
+
+/// 解析凭证所属组织的名称，`force` 时忽略缓存有效期；返回已知名称的组织数
+pub async fn resolve_org_names(force: bool) -> usize {
+    // 同一账号的凭证查询结果相同，每个账号只查一次
+    let mut tokens: HashMap<String, String> = HashMap::new();
+    let mut org_ids: Vec<String> = Vec::new();
+    for credential in CREDENTIALS.read().await.values() {
+        let (Some(org_id), Some(access_token)) =
+            (&credential.organization_id, &credential.access_token)
+        else {
+            continue;
+        };
+        org_ids.push(org_id.clone());
+        if credential.auth_type != AuthType::OAuth || !(force || org_names::needs_resolve(org_id)) {
+            continue;
+        }
+        let account = credential.user_id.clone().unwrap_or_else(|| org_id.clone());
+        tokens
+            .entry(account)
+            .or_insert_with(|| access_token.clone());
+    }
+
+    for access_token in tokens.values() {
+        match fetch_factory_orgs(access_token).await {
+            Ok(orgs) => org_names::remember(&orgs),
+            Err(e) => debug!("解析组织名称失败: {}", e),
+        }
+    }
+    org_ids.sort();
+    org_ids.dedup();
+    org_ids
+        .iter()
+        .filter(|id| org_names::lookup(id).is_some())
+        .count()
+}
+
+/// 凭证 ID → 组织名称（用于使用量报表）
+pub async fn credential_org_names() -> HashMap<String, String> {
+    CREDENTIALS
+        .read()
+        .await
+        .iter()
+        .filter_map(|(id, c)| {
+            let name = org_names::lookup(c.organization_id.as_deref()?)?;
+            Some((id.clone(), name))
+        })
+        .collect()
+}
+
+/// 登录后检查账号是否属于多个组织，是则通知宿主提示用户
+async fn notify_organizations(credential_id: String) {
+    match discover_organizations(&credential_id).await {
+        Ok(orgs) if orgs.len() > 1 => {
+            events::emit(
+                "organizations_discovered",
+                format!("该账号属于 {} 个组织，可为每个组织创建凭证", orgs.len()),
+                serde_json::json!({ "credential_id": credential_id, "organizations": orgs }),
+            );
+        }
+        Ok(_) => {}
+        Err(e) => debug!("查询组织失败: {}", e),
+    }
+}
+
+/// 为指定组织创建共享 Refresh Token 的凭证，返回新凭证 ID
+pub async fn create_org_credentials(
+    credential_id: &str,
+    organization_ids: &[String],
+) -> Result<Vec<String>> {
+    let lock = refresh_lock(credential_id).await;
+    let _guard = lock.lock().await;
+
+    let mut created = Vec::new();
+    {
+        let mut creds = write_credentials().await;
+        let source = creds
+            .get_mut(credential_id)
+            .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+        ensure_not_locked(credential_id, "派生组织凭证")?;
+        if source.auth_type != AuthType::OAuth || source.refresh_token.is_none() {
+            anyhow::bail!("只有带 Refresh Token 的 OAuth 凭证可以派生组织凭证");
+        }
+        let group = org_discovery::refresh_group(credential_id, source);
+        source.refresh_group = Some(group.clone());
+        let source = source.clone();
+
+        for org_id in organization_ids {
+            if source.organization_id.as_deref() == Some(org_id.as_str()) {
+                continue;
+            }
+            let id = uuid::Uuid::new_v4().to_string();
+            creds.insert(
+                id.clone(),
+                org_discovery::org_credential(&source, &group, org_id),
+            );
+            created.push(id);
+        }
+    }
+
+    // 逐个按组织刷新获取 Access Token，轮换的 Refresh Token 随之同步
+    for id in &created {
+        if let Err(e) = refresh_token_locked(id, RefreshPriority::Urgent).await {
+            warn!("组织凭证 {} 首次刷新失败: {}", id, e);
+        }
+    }
+    info!(
+        "为凭证 {} 创建了 {} 个组织凭证",
+        credential_id,
+        created.len()
+    );
+    Ok(created)
+}
+
+/// 克隆凭证并覆盖部分配置，返回新凭证 ID
+pub async fn clone_credential(credential_id: &str, overrides: CloneOverrides) -> Result<String> {
+    let lock = refresh_lock(credential_id).await;
+    let _guard = lock.lock().await;
+
+    let (id, needs_refresh) = {
+        let mut creds = write_credentials().await;
+        let source = creds
+            .get_mut(credential_id)
+            .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+        ensure_not_locked(credential_id, "克隆")?;
+        // 共用 Refresh Token 的两个凭证必须在同一刷新组，否则轮换后其中一个失效
+        let group = (source.auth_type == AuthType::OAuth && source.refresh_token.is_some())
+            .then(|| org_discovery::refresh_group(credential_id, source));
+        let cloned = credential_clone::clone_credential(
+            source,
+            overrides,
+            group.as_deref(),
+            &key_ring::current()?,
+        )?;
+        if group.is_some() {
+            source.refresh_group = group;
+        }
+
+        let needs_refresh = cloned.auth_type == AuthType::OAuth && cloned.access_token.is_none();
+        let id = uuid::Uuid::new_v4().to_string();
+        creds.insert(id.clone(), cloned);
+        (id, needs_refresh)
+    };
+
+    if needs_refresh {
+        if let Err(e) = refresh_token_locked(&id, RefreshPriority::Urgent).await {
+            warn!("克隆凭证 {} 首次刷新失败: {}", id, e);
+        }
+    }
+    info!("凭证 {} 已克隆为 {}", credential_id, id);
+    Ok(id)
+}
+
+/// 处理上游返回的 401
+///
+/// 刷新一次 OAuth Token 并返回新的请求信息供宿主重试原请求（与 factory-cli 行为一致）。
+/// 并发的 401 共享同一次刷新：拿到锁后若 Token 已不是失败时使用的那个，直接重试。
+/// 每个租约只恢复一次，重试仍失败时由宿主上报错误。
+pub async fn recover_unauthorized(
+    credential_id: &str,
+    lease_id: Option<&str>,
+    failed_access_token: Option<&str>,
+) -> Result<AcquiredCredential> {
+    if let Some(lease_id) = lease_id {
+        if !LEASES.write().await.mark_recovered(lease_id) {
+            anyhow::bail!("该请求已重试过，仍返回 401");
+        }
+    }
+
+    let lock = refresh_lock(credential_id).await;
+    let _guard = lock.lock().await;
+
+    let already_refreshed = {
+        let creds = CREDENTIALS.read().await;
+        let credential = creds
+            .get(credential_id)
+            .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+        if credential.auth_type != AuthType::OAuth {
+            anyhow::bail!("API Key 凭证无法通过刷新恢复 401");
+        }
+        failed_access_token.is_some() && credential.access_token.as_deref() != failed_access_token
+    };
+    if already_refreshed {
+        debug!("Token 已由并发请求刷新，直接重试: {}", credential_id);
+    } else {
+        refresh_token_locked(credential_id, RefreshPriority::Urgent).await?;
+    }
+
+    let endpoint_type = match lease_id {
+        Some(lease_id) => LEASES.read().await.get(lease_id).map(|l| l.endpoint_type),
+        None => None,
+    };
+    let creds = CREDENTIALS.read().await;
+    let credential = creds
+        .get(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    let endpoint_type = endpoint_type.unwrap_or(credential.endpoint_type);
+
+    let mut acquired = build_acquired_credential(credential_id, credential, endpoint_type)?;
+    if let Some(lease_id) = lease_id {
+        acquired
+            .metadata
+            .insert("lease_id".to_string(), serde_json::json!(lease_id));
+    }
+    acquired
+        .metadata
+        .insert("retry".to_string(), serde_json::json!(true));
+    info!("401 恢复完成，重试请求: {}", credential_id);
+    Ok(acquired)
+}
+
+/// 创建凭证
+pub async fn create_credential(auth_type: &str, config: serde_json::Value) -> Result<String> {
+    let auth_type_enum = match auth_type {
+        "oauth" => AuthType::OAuth,
+        "api_key" => AuthType::ApiKey,
+        _ => anyhow::bail!("不支持的认证类型: {}", auth_type),
+    };
+
+    // `api_keys` 为明文（或 seal_credential_tokens 加密后的）字符串，单独处理
+    let mut fields = config.clone();
+    let api_keys = match fields.as_object_mut() {
+        Some(object) if auth_type_enum == AuthType::ApiKey => object.remove("api_keys"),
+        _ => None,
+    };
+    let mut droid_config: DroidCredentials = serde_json::from_value(fields)?;
+    droid_config.auth_type = auth_type_enum;
+
+    // 明文 API Key 先只计算哈希，确认不是已有凭证后再加密
+    let mut plain_keys = Vec::new();
+    for key in api_keys.iter().flat_map(|v| v.as_array()).flatten() {
+        let Some(key_str) = key.as_str().filter(|k| !k.is_empty()) else {
+            continue;
+        };
+        match key_str.strip_prefix(token_seal::SEALED_PREFIX) {
+            Some(ciphertext) => droid_config.api_keys.push(ApiKeyEntry {
+                encrypted_key: ciphertext.to_string(),
+                ..pending_api_key_entry(String::new())
+            }),
+            None => {
+                plain_keys.push((droid_config.api_keys.len(), key_str.to_string()));
+                droid_config
+                    .api_keys
+                    .push(pending_api_key_entry(hash_api_key(key_str)));
+            }
+        }
+    }
+    // 宿主保存的可能是加密后的 Token / API Key；密钥不可用时以锁定状态载入
+    let locked = secret_lock::open(&mut droid_config);
+
+    // 从 JWT 读取真实过期时间与组织
+    if let Some(claims) = droid_config.access_token.as_deref().and_then(decode_claims) {
+        if let Some(exp) = claims.expires_at() {
+            droid_config.expires_at = Some(exp.to_rfc3339());
+        }
+        if droid_config.organization_id.is_none() {
+            droid_config.organization_id = claims.org_id;
+        }
+        if droid_config.user_id.is_none() {
+            droid_config.user_id = claims.sub;
+        }
+    }
+
+    // 验证必要字段
+    match auth_type_enum {
+        AuthType::OAuth => {
+            if droid_config.refresh_token.is_none() && droid_config.access_token.is_none() {
+                anyhow::bail!("OAuth 认证需要 access_token 或 refresh_token");
+            }
+        }
+        AuthType::ApiKey => {
+            if droid_config.api_keys.is_empty() {
+                anyhow::bail!("API Key 认证需要至少一个 API Key");
+            }
+        }
+    }
+
+    token_age::mark_issued(&mut droid_config);
+    canary::start(&get_config().canary, &mut droid_config);
+    backoff_state::restore(&mut droid_config);
+
+    // 宿主可传入之前返回的凭证 ID；凭证已从凭证文件载入时沿用（文件中的状态更新）
+    let requested_id = config
+        .get("credential_id")
+        .and_then(|v| v.as_str())
+        .filter(|id| !id.is_empty());
+    let mut creds = write_credentials().await;
+    if let Some(existing) = find_same_credential(&creds, requested_id, &droid_config) {
+        debug!("凭证已存在，沿用 {}", existing);
+        return Ok(existing);
+    }
+    for (index, key) in plain_keys {
+        droid_config.api_keys[index].encrypted_key = key_ring::encrypt(&key).map_err(|e| {
+            e.context("加密密钥不可用，无法保存 API Key（解锁后重试，或传入 seal_credential_tokens 加密后的值）")
+        })?;
+    }
+    let credential_id = requested_id
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let discover = droid_config.access_token.is_some() && locked.is_none();
+
+    // 存储凭证
+    if let Some(reason) = locked {
+        secret_lock::mark(&credential_id, &droid_config, reason);
+    }
+    creds.insert(credential_id.clone(), droid_config);
+    drop(creds);
+
+    if discover {
+        tokio::spawn(notify_organizations(credential_id.clone()));
+    }
+    check_pool_ready().await;
+
+    info!("创建凭证成功: {} (类型: {})", credential_id, auth_type);
+    Ok(credential_id)
+}
+
+/// 凭证池中与新凭证相同的凭证：指定的 ID、相同的 Refresh Token（含轮换前的）
+/// 或包含全部相同的 API Key
+fn find_same_credential(
+    creds: &HashMap<String, DroidCredentials>,
+    requested_id: Option<&str>,
+    candidate: &DroidCredentials,
+) -> Option<String> {
+    if let Some(id) = requested_id.filter(|id| creds.contains_key(*id)) {
+        return Some(id.to_string());
+    }
+    let ring = key_ring::current().ok();
+    let same = |existing: &DroidCredentials| match candidate.auth_type {
+        AuthType::OAuth => candidate.refresh_token.as_deref().is_some_and(|token| {
+            existing.refresh_token.as_deref() == Some(token)
+                || existing
+                    .previous_refresh_token
+                    .as_deref()
+                    .zip(ring.as_ref())
+                    .and_then(|(previous, ring)| ring.decrypt(previous).ok())
+                    .is_some_and(|previous| previous == token)
+        }),
+        AuthType::ApiKey => {
+            !candidate.api_keys.is_empty()
+                && candidate.api_keys.iter().all(|k| {
+                    existing.api_keys.iter().any(|e| {
+                        (!k.hash.is_empty() && e.hash == k.hash)
+                            || (!k.encrypted_key.is_empty() && e.encrypted_key == k.encrypted_key)
+                    })
+                })
+        }
+    };
+    creds
+        .iter()
+        .find(|(_, existing)| existing.auth_type == candidate.auth_type && same(existing))
+        .map(|(id, _)| id.clone())
+}
+
+/// 解锁后重新解密锁定的凭证，返回已解锁的凭证 ID
+pub async fn retry_locked_credentials() -> Vec<String> {
+    let mut unlocked = Vec::new();
+    let mut creds = write_credentials().await;
+    for id in secret_lock::locked_ids() {
+        match creds.get_mut(&id).map(secret_lock::open) {
+            Some(Some(_)) => {}
+            Some(None) => unlocked.push(id),
+            None => secret_lock::forget(&id),
+        }
+    }
+    drop(creds);
+    secret_lock::unlocked(&unlocked);
+    check_pool_ready().await;
+    unlocked
+}
+
+/// 加密 API Key 并生成条目
+fn new_api_key_entry(key: &str) -> Result<ApiKeyEntry> {
+    Ok(ApiKeyEntry {
+        encrypted_key: key_ring::encrypt(key)?,
+        ..pending_api_key_entry(hash_api_key(key))
+    })
+}
+
+/// 尚未填入密文的 API Key 条目
+fn pending_api_key_entry(hash: String) -> ApiKeyEntry {
+    ApiKeyEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        hash,
+        encrypted_key: String::new(),
+        created_at: Utc::now().to_rfc3339(),
+        last_used_at: None,
+        usage_count: 0,
+        status: "active".to_string(),
+        error_message: None,
+    }
+}
+
+/// 从环境变量和 .env 文件导入 API Key
+///
+/// 指定 `credential_id` 时追加到该 API Key 凭证，否则新建凭证；
+/// 已保存过的 Key（任意凭证中）按哈希跳过。
+pub async fn import_env_api_keys(
+    credential_id: Option<&str>,
+    dotenv_path: Option<&std::path::Path>,
+) -> Result<EnvImportResult> {
+    let config = get_config();
+    let found = env_import::scan(&config.env_import, dotenv_path)?;
+
+    let mut creds = write_credentials().await;
+    let stored: HashSet<String> = creds
+        .values()
+        .flat_map(|c| c.api_keys.iter().map(|k| k.hash.clone()))
+        .collect();
+    let (new_keys, duplicates) = env_import::partition_new(found, &stored);
+    let mut result = EnvImportResult {
+        imported: new_keys.iter().map(|k| k.source.clone()).collect(),
+        duplicates: duplicates.into_iter().map(|k| k.source).collect(),
+        ..Default::default()
+    };
+    if new_keys.is_empty() {
+        return Ok(result);
+    }
+
+    let entries = new_keys
+        .iter()
+        .map(|k| new_api_key_entry(&k.key))
+        .collect::<Result<Vec<_>>>()?;
+    let id = match credential_id {
+        Some(id) => {
+            let credential = creds
+                .get_mut(id)
+                .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", id))?;
+            if credential.auth_type != AuthType::ApiKey {
+                anyhow::bail!("凭证 {} 不是 API Key 凭证", id);
+            }
+            credential.api_keys.extend(entries);
+            id.to_string()
+        }
+        None => {
+            let mut credential = DroidCredentials {
+                name: Some("env-import".to_string()),
+                auth_type: AuthType::ApiKey,
+                api_keys: entries,
+                ..Default::default()
+            };
+            canary::start(&config.canary, &mut credential);
+            let id = uuid::Uuid::new_v4().to_string();
+            creds.insert(id.clone(), credential);
+            result.created = true;
+            id
+        }
+    };
+    drop(creds);
+    check_pool_ready().await;
+
+    info!(
+        "从环境变量导入 {} 个 API Key 到凭证 {}",
+        result.imported.len(),
+        id
+    );
+    result.credential_id = Some(id);
+    Ok(result)
+}
+
+/// 导出凭证为配对载荷
+pub async fn export_credential(credential_id: &str, ttl_minutes: i64) -> Result<PairingExport> {
+    let creds = CREDENTIALS.read().await;
+    let mut credential = creds
+        .get(credential_id)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    drop(creds);
+
+    // 本机加密密钥与接收方不同，API Key 以明文放入加密载荷
+    for entry in &mut credential.api_keys {
+        entry.encrypted_key = key_ring::decrypt(&entry.encrypted_key)?;
+    }
+    if let Some(previous) = credential.previous_refresh_token.take() {
+        credential.previous_refresh_token = Some(key_ring::decrypt(&previous)?);
+    }
+
+    let export = sharing::seal(credential, ttl_minutes)?;
+    info!(
+        "导出凭证配对载荷: {} (过期: {})",
+        credential_id, export.expires_at
+    );
+    Ok(export)
+}
+
+/// 申请查看明文密钥，返回一次性确认令牌
+pub async fn request_secret_reveal(credential_id: &str, key_id: &str) -> Result<RevealChallenge> {
+    let creds = CREDENTIALS.read().await;
+    let credential = creds
+        .get(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    // 提前确认密钥存在，避免用户确认后才报错
+    reveal::extract(credential, key_id)?;
+    reveal::issue(&get_config().reveal, credential_id, key_id)
+}
+
+/// 凭确认令牌取回明文 API Key 或 Refresh Token
+pub async fn reveal_secret(
+    credential_id: &str,
+    key_id: &str,
+    confirmation_token: &str,
+    os_auth_confirmed: bool,
+) -> Result<String> {
+    let config = get_config().reveal;
+    reveal::confirm(
+        &config,
+        credential_id,
+        key_id,
+        confirmation_token,
+        os_auth_confirmed,
+    )?;
+
+    let creds = CREDENTIALS.read().await;
+    let credential = creds
+        .get(credential_id)
+        .ok_or_else(|| anyhow::anyhow!("凭证不存在: {}", credential_id))?;
+    let secret = reveal::extract(credential, key_id)?;
+    warn!("已查看凭证 {} 的明文密钥 {}", credential_id, key_id);
+    events::emit(
+        "secret_revealed",
+        format!("凭证 {} 的密钥 {} 已被查看", credential_id, key_id),
+        serde_json::json!({ "credential_id": credential_id, "key_id": key_id }),
+    );
+    Ok(secret)
+}
+
+/// 从配对载荷导入凭证
+pub async fn import_credential(payload: &str, pairing_code: &str) -> Result<String> {
+    let mut credential = sharing::open(payload, pairing_code)?;
+
+    for entry in &mut credential.api_keys {
+        entry.encrypted_key = key_ring::encrypt(&entry.encrypted_key)?;
+    }
+    if let Some(previous) = credential.previous_refresh_token.take() {
+        credential.previous_refresh_token = Some(key_ring::encrypt(&previous)?);
+    }
+    canary::start(&get_config().canary, &mut credential);
+    backoff_state::restore(&mut credential);
+
+    let credential_id = uuid::Uuid::new_v4().to_string();
+    write_credentials()
+        .await
+        .insert(credential_id.clone(), credential);
+    check_pool_ready().await;
+
+    info!("通过配对载荷导入凭证: {}", credential_id);
+    Ok(credential_id)
+}
+
+/// 流中断时拼出部分响应，并记录截断
+pub async fn salvage_stream(
+    credential_id: Option<&str>,
+    events: &[serde_json::Value],
+    error: &str,
+) -> Option<serde_json::Value> {
+    let (response, output_tokens) = salvage::salvage(events, &get_config().salvage)?;
+
+    if let Some(credential_id) = credential_id {
+        if let Some(credential) = write_credentials().await.get_mut(credential_id) {
+            credential.record_error(CredentialError {
+                timestamp: Utc::now().to_rfc3339(),
+                error_type: Some("stream_truncated".to_string()),
+                message: Some(format!(
+                    "{}（已保留 {} 个输出 Token）",
+                    error, output_tokens
+                )),
+                ..Default::default()
+            });
+        }
+    }
+    warn!(
+        "流式响应中断，返回部分内容 ({} Token): {}",
+        output_tokens, error
+    );
+    events::emit(
+        "stream_truncated",
+        format!("流式响应中断，已保留 {} 个输出 Token", output_tokens),
+        serde_json::json!({
+            "credential_id": credential_id,
+            "output_tokens": output_tokens,
+            "error": error,
+        }),
+    );
+    Some(response)
+}
+
+/// 该请求是否原样透传（请求级 `raw` 或配置的透传客户端）
+pub fn is_raw_passthrough(client_name: Option<&str>, requested: bool) -> bool {
+    passthrough::is_raw(&get_config().passthrough, client_name, requested)
+}
+
+/// 套用转换配置档后的有效配置（未指定或不存在时为全局配置）
+fn profile_config(profile: Option<&str>) -> (ProviderConfig, Option<TransformProfile>) {
+    let config = get_config();
+    match profile
+        .and_then(|name| config.profiles.profiles.get(name))
+        .cloned()
+    {
+        Some(profile) => (profiles::effective_config(&config, &profile), Some(profile)),
+        None => (config, None),
+    }
+}
+
+/// 转换请求（审查参数、校验文档后执行中间件链）
+pub async fn transform_request(
+    mut request: serde_json::Value,
+    profile: Option<&str>,
+) -> Result<serde_json::Value> {
+    let chain = middleware::chain(profile)?;
+    let (config, profile) = profile_config(profile);
+    // 停止序列先按目标格式改写，避免被参数审查当作未知字段
+    let format = RequestFormat::detect(&request);
+    stop_sequences::normalize(&mut request, format);
+    param_policy::apply(&config.param_policy, &mut request)?;
+    documents::check_request(&config.documents, &mut request)?;
+    if let Some(profile) = profile {
+        profiles::apply_system_prompt(&profile.system_prompt, &mut request);
+    }
+    chain.run_request(&mut request).await?;
+    Ok(request)
+}
+
+/// 请求超出模型上下文时按配置裁剪，返回裁剪结果
+///
+/// `tenant_key` 为客户端请求中的虚拟密钥，生成摘要时按该租户占用凭证。
+pub async fn trim_context(
+    request: &mut serde_json::Value,
+    tenant_key: Option<&str>,
+) -> Result<Option<TrimReport>> {
+    let config = get_config().context_trim;
+    if !config.enabled {
+        return Ok(None);
+    }
+    let Some(model) = request["model"].as_str().map(str::to_string) else {
+        return Ok(None);
+    };
+    let context_length = list_models()
+        .await
+        .into_iter()
+        .find(|m| m.id == model)
+        .and_then(|m| m.context_length);
+    match context_length {
+        Some(context_length) => {
+            let context_length = context_length as u64;
+            context_trim::trim(&config, request, context_length, tenant_key).await
+        }
+        None => Ok(None),
+    }
+}
+
+/// 需要重组的客户端请求改为向上游流式请求，返回是否已修改
+pub fn prepare_reassembly(request: &mut serde_json::Value, client_name: Option<&str>) -> bool {
+    let config = get_config().reassembly;
+    let reassemble = reassembly::should_reassemble(&config, request, client_name);
+    if reassemble {
+        reassembly::prepare_request(request);
+    }
+    reassemble
+}
+
+/// 租约对应的凭证信息，用于响应元数据；租约不存在时返回 None
+pub async fn serving_info(lease_id: &str, retries: u32) -> Option<ServingInfo> {
+    let lease = LEASES.read().await.get(lease_id).cloned()?;
+    let credential_name = CREDENTIALS
+        .read()
+        .await
+        .get(&lease.credential_id)
+        .and_then(|c| c.name.clone());
+    let latency_ms = (Utc::now() - lease.acquired_at).num_milliseconds().max(0) as u64;
+    Some(ServingInfo {
+        credential_id: lease.credential_id,
+        credential_name,
+        endpoint_type: lease.endpoint_type,
+        model: lease.model,
+        retries: retries + lease.recovered as u32,
+        latency_ms,
+        failover_from: lease.failover_from,
+    })
+}
+
+/// 转换响应（按相反顺序执行中间件链）
+pub async fn transform_response(
+    mut response: serde_json::Value,
+    profile: Option<&str>,
+) -> Result<serde_json::Value> {
+    let (config, _) = profile_config(profile);
+    if config.chat_normalize.enabled {
+        chat_normalize::normalize_response(&mut response);
+    }
+    middleware::chain(profile)?
+        .run_response(&mut response)
+        .await?;
+    Ok(response)
+}
+
+/// 逐个事件重组
+fn assemble_events(
+    events: impl Iterator<Item = Result<serde_json::Value>>,
+) -> Result<serde_json::Value> {
+    let mut assembler = reassembly::StreamAssembler::default();
+    // 先补全工具调用增量的 index，重组时才能按调用正确合并
+    let mut normalizer = get_config()
+        .chat_normalize
+        .enabled
+        .then(chat_normalize::ChatStreamNormalizer::default);
+    for event in events {
+        let mut event = event?;
+        if let Some(normalizer) = normalizer.as_mut() {
+            normalizer.normalize(&mut event);
+        }
+        assembler.push(&event);
+    }
+    assembler.finish()
+}
+
+/// 把上游完整的流重组为非流式响应，再按非流式响应执行中间件链
+pub async fn reassemble_stream(events: &[serde_json::Value]) -> Result<serde_json::Value> {
+    let response = assemble_events(events.iter().cloned().map(Ok))?;
+    transform_response(response, None).await
+}
+
+/// 从宿主写入的事件文件（JSON Lines 或原始 SSE）逐行重组，不在内存中保留全部事件
+pub async fn reassemble_stream_file(path: &std::path::Path) -> Result<serde_json::Value> {
+    let config = get_config().spool;
+    let response = assemble_events(spool::read_events(&config, path)?)?;
+    transform_response(response, None).await
+}
+
+/// 转换流式响应事件，并按调用方应用限速（透传时只统计与限速，不改写）
+///
+/// 返回转换后的事件与宿主下发该事件前应等待的时长；插件自身不等待。
+pub async fn transform_stream_chunk(
+    mut chunk: serde_json::Value,
+    client_name: Option<&str>,
+    lease_id: Option<&str>,
+    raw: bool,
+    profile: Option<&str>,
+) -> Result<(serde_json::Value, std::time::Duration)> {
+    let chaos_delay = match lease_id {
+        Some(lease_id) => {
+            chaos::check_stream(lease_id)?;
+            chaos::take_delay(lease_id)
+        }
+        None => std::time::Duration::ZERO,
+    };
+    let (config, _) = profile_config(profile);
+    if !raw {
+        if config.chat_normalize.enabled {
+            chat_normalize::normalize_chunk(&mut chunk);
+        }
+        middleware::chain(profile)?
+            .run_stream_chunk(&mut chunk)
+            .await?;
+    }
+
+    if let Some(lease_id) = lease_id {
+        let lease = LEASES.read().await.get(lease_id).cloned();
+        if let Some(lease) = lease {
+            stream_progress::observe(lease_id, &chunk, &config.progress, || {
+                StreamProgress::new(
+                    &lease.credential_id,
+                    &lease.model,
+                    lease.client_name.clone(),
+                    lease.acquired_at,
+                )
+            });
+        }
+    }
+
+    let tokens = throttle::estimate_chunk_tokens(&chunk);
+    let delay = throttle::reserve(&config.throttle, client_name, tokens);
+    Ok((chunk, chaos_delay + delay))
+}
+
+/// 应用风控
+pub async fn apply_risk_control(
+    _request: &mut serde_json::Value,
+    _credential_id: &str,
+) -> Result<()> {
+    // Droid 暂不需要特殊风控
+    Ok(())
+}
+
+/// 解析错误
+pub fn parse_error(status: u16, body: &str, model: Option<&str>) -> Option<ProviderError> {
+    if let Some(model) = model {
+        if deprecation::is_model_unavailable_error(status, body) {
+            deprecation::record_deprecation(model, status, body);
+            return Some(ProviderError {
+                error_type: "model_deprecated".to_string(),
+                message: format!("模型不可用: {}", model),
+                status_code: Some(status),
+                retryable: false,
+                cooldown_seconds: None,
+            });
+        }
+    }
+
+    match status {
+        401 => Some(ProviderError {
+            error_type: "authentication".to_string(),
+            message: "Token 已过期或无效".to_string(),
+            status_code: Some(status),
+            retryable: true,
+            cooldown_seconds: Some(0),
+        }),
+        403 => Some(ProviderError {
+            error_type: "authorization".to_string(),
+            message: "权限不足".to_string(),
+            status_code: Some(status),
+            retryable: false,
+            cooldown_seconds: None,
+        }),
+        429 => Some(ProviderError {
+            error_type: "rate_limit".to_string(),
+            message: "请求过于频繁".to_string(),
+            status_code: Some(status),
+            retryable: true,
+            cooldown_seconds: Some(60),
+        }),
+        500..=599 => Some(ProviderError {
+            error_type: "server_error".to_string(),
+            message: format!("服务器错误: {}", body),
+            status_code: Some(status),
+            retryable: true,
+            cooldown_seconds: Some(10),
+        }),
+        _ => None,
+    }
+}
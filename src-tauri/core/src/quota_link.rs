@@ -0,0 +1,176 @@
+//! 关联凭证的共享额度
+//!
+//! 同一组织的 OAuth 凭证与组织签发的 API Key 消耗的是同一份 Factory 额度。
+//! 凭证按 `quota_group`（显式设置）或组织 ID 归入同一预算组：选择凭证时按
+//! 整组的进行中请求数降权，一个凭证因 429 进入冷却时同组凭证一起冷却，
+//! 用量按组汇总。
+
+use crate::credentials::DroidCredentials;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// 共享额度配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuotaLinkConfig {
+    pub enabled: bool,
+    /// 组织 ID 相同的凭证自动关联（关闭后只按显式 quota_group 关联）
+    pub link_by_organization: bool,
+    /// 429 冷却同步到同组凭证
+    pub share_cooldown: bool,
+}
+
+impl Default for QuotaLinkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            link_by_organization: true,
+            share_cooldown: true,
+        }
+    }
+}
+
+/// 凭证所属的预算组，未关联时返回 None
+pub fn budget_key(config: &QuotaLinkConfig, credential: &DroidCredentials) -> Option<String> {
+    if !config.enabled {
+        return None;
+    }
+    if let Some(group) = credential.quota_group.as_ref().filter(|g| !g.is_empty()) {
+        return Some(format!("group:{}", group));
+    }
+    credential
+        .organization_id
+        .as_ref()
+        .filter(|_| config.link_by_organization)
+        .map(|org| format!("org:{}", org))
+}
+
+/// 与某个凭证共享额度的其他凭证
+pub fn linked_ids(
+    config: &QuotaLinkConfig,
+    credentials: &HashMap<String, DroidCredentials>,
+    credential_id: &str,
+) -> Vec<String> {
+    let Some(key) = credentials
+        .get(credential_id)
+        .and_then(|c| budget_key(config, c))
+    else {
+        return Vec::new();
+    };
+    let mut ids: Vec<String> = credentials
+        .iter()
+        .filter(|(id, _)| id.as_str() != credential_id)
+        .filter(|(_, c)| budget_key(config, c).as_ref() == Some(&key))
+        .map(|(id, _)| id.clone())
+        .collect();
+    ids.sort();
+    ids
+}
+
+/// 预算组用量汇总
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotaGroup {
+    pub key: String,
+    pub credential_ids: Vec<String>,
+    pub usage_count: u64,
+    pub error_count: u64,
+    /// 组内最晚的冷却截止时间
+    #[serde(default)]
+    pub cooldown_until: Option<String>,
+}
+
+/// 按预算组汇总用量（只包含关联了两个及以上凭证的组）
+pub fn summarize(
+    config: &QuotaLinkConfig,
+    credentials: &HashMap<String, DroidCredentials>,
+) -> Vec<QuotaGroup> {
+    let mut groups: BTreeMap<String, QuotaGroup> = BTreeMap::new();
+    for (id, credential) in credentials {
+        let Some(key) = budget_key(config, credential) else {
+            continue;
+        };
+        let group = groups.entry(key.clone()).or_insert_with(|| QuotaGroup {
+            key,
+            ..Default::default()
+        });
+        group.credential_ids.push(id.clone());
+        group.usage_count += credential.usage_count;
+        group.error_count += credential.error_count;
+        if credential.in_cooldown() {
+            let parse = |t: &Option<String>| t.as_deref()?.parse::<DateTime<Utc>>().ok();
+            if parse(&credential.cooldown_until) > parse(&group.cooldown_until) {
+                group.cooldown_until = credential.cooldown_until.clone();
+            }
+        }
+    }
+    groups
+        .into_values()
+        .filter(|g| g.credential_ids.len() > 1)
+        .map(|mut g| {
+            g.credential_ids.sort();
+            g
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credentials::AuthType;
+
+    fn credential(auth_type: AuthType, org: Option<&str>, usage_count: u64) -> DroidCredentials {
+        DroidCredentials {
+            auth_type,
+            organization_id: org.map(str::to_string),
+            usage_count,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_budget_key() {
+        let config = QuotaLinkConfig::default();
+        let mut oauth = credential(AuthType::OAuth, Some("org_1"), 0);
+        assert_eq!(budget_key(&config, &oauth).as_deref(), Some("org:org_1"));
+
+        oauth.quota_group = Some("team".to_string());
+        assert_eq!(budget_key(&config, &oauth).as_deref(), Some("group:team"));
+
+        let unlinked = QuotaLinkConfig {
+            link_by_organization: false,
+            ..Default::default()
+        };
+        let api_key = credential(AuthType::ApiKey, Some("org_1"), 0);
+        assert!(budget_key(&unlinked, &api_key).is_none());
+        assert!(budget_key(&config, &credential(AuthType::ApiKey, None, 0)).is_none());
+    }
+
+    #[test]
+    fn test_linked_ids_and_summary() {
+        let config = QuotaLinkConfig::default();
+        let credentials: HashMap<String, DroidCredentials> = [
+            ("oauth", credential(AuthType::OAuth, Some("org_1"), 10)),
+            ("key", credential(AuthType::ApiKey, Some("org_1"), 5)),
+            ("other", credential(AuthType::OAuth, Some("org_2"), 7)),
+        ]
+        .into_iter()
+        .map(|(id, c)| (id.to_string(), c))
+        .collect();
+
+        assert_eq!(
+            linked_ids(&config, &credentials, "oauth"),
+            vec!["key".to_string()]
+        );
+        assert!(linked_ids(&config, &credentials, "other").is_empty());
+
+        let groups = summarize(&config, &credentials);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].key, "org:org_1");
+        assert_eq!(
+            groups[0].credential_ids,
+            vec!["key".to_string(), "oauth".to_string()]
+        );
+        assert_eq!(groups[0].usage_count, 15);
+    }
+}
@@ -0,0 +1,376 @@
+//! 流式响应重组
+//!
+//! 部分下游工具无法处理流式的 tool_use 块。开启后，对这些客户端的非流式请求
+//! 改为向上游发起流式请求（仍受上游流式空闲超时保护），acquire 返回的
+//! metadata 带 `reassemble_stream: true`；宿主收完整个流后把事件交给
+//! `reassemble_stream`，拼回标准的非流式响应再返回客户端。
+//! 支持 Anthropic Messages、Chat Completions 与 Responses 流。
+
+use crate::throttle::estimate_chunk_tokens;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// 流式重组配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReassemblyConfig {
+    pub enabled: bool,
+    /// 仅对这些客户端生效，为空时对所有非流式请求生效
+    pub clients: Vec<String>,
+}
+
+/// 该请求是否走“上游流式、下游非流式”
+pub fn should_reassemble(
+    config: &ReassemblyConfig,
+    request: &serde_json::Value,
+    client_name: Option<&str>,
+) -> bool {
+    let client_matches = config.clients.is_empty()
+        || client_name.is_some_and(|name| config.clients.iter().any(|c| c == name));
+    config.enabled && client_matches && request["stream"] != true
+}
+
+/// 把非流式请求改为流式请求（Chat Completions 流不带用量时按字符估算）
+pub fn prepare_request(request: &mut serde_json::Value) {
+    request["stream"] = serde_json::json!(true);
+}
+
+/// 流事件累加器
+#[derive(Debug, Default)]
+pub struct StreamAssembler {
+    id: Option<String>,
+    model: Option<String>,
+    /// Anthropic 内容块（按 index）
+    blocks: Vec<serde_json::Value>,
+    /// Anthropic 用量（message_start 为底，message_delta 覆盖）
+    usage: serde_json::Map<String, serde_json::Value>,
+    stop_reason: Option<serde_json::Value>,
+    stop_sequence: Option<serde_json::Value>,
+    /// Chat Completions 文本与工具调用
+    chat_text: String,
+    chat_tool_calls: Vec<serde_json::Value>,
+    finish_reason: Option<serde_json::Value>,
+    is_chat: bool,
+    /// Responses 流结束时携带的完整响应
+    completed_response: Option<serde_json::Value>,
+    /// 上游在流中返回的错误
+    error: Option<serde_json::Value>,
+    input_tokens: u64,
+    output_tokens: Option<u64>,
+    estimated_output_tokens: u64,
+}
+
+impl StreamAssembler {
+    /// 逐个喂入流事件
+    pub fn push(&mut self, event: &serde_json::Value) {
+        self.estimated_output_tokens += estimate_chunk_tokens(event);
+
+        if event.get("choices").is_some() {
+            self.push_chat(event);
+            return;
+        }
+
+        match event["type"].as_str().unwrap_or("") {
+            "message_start" => {
+                let message = &event["message"];
+                self.id = message["id"].as_str().map(String::from);
+                self.model = message["model"].as_str().map(String::from);
+                self.input_tokens = message["usage"]["input_tokens"].as_u64().unwrap_or(0);
+                if let Some(usage) = message["usage"].as_object() {
+                    self.usage = usage.clone();
+                }
+            }
+            "content_block_start" => {
+                let mut block = event["content_block"].clone();
+                if block["type"] == "tool_use" {
+                    block["input"] = serde_json::json!("");
+                }
+                match event["index"].as_u64().map(|i| i as usize) {
+                    Some(index) if index < self.blocks.len() => self.blocks[index] = block,
+                    Some(index) => {
+                        self.blocks.resize(index, serde_json::Value::Null);
+                        self.blocks.push(block);
+                    }
+                    None => self.blocks.push(block),
+                }
+            }
+            "content_block_delta" => {
+                let block = match event["index"].as_u64() {
+                    Some(index) => self.blocks.get_mut(index as usize),
+                    None => self.blocks.last_mut(),
+                };
+                let Some(block) = block else {
+                    return;
+                };
+                let delta = &event["delta"];
+                let (field, text) = match delta["type"].as_str() {
+                    Some("text_delta") => ("text", &delta["text"]),
+                    Some("thinking_delta") => ("thinking", &delta["thinking"]),
+                    Some("signature_delta") => ("signature", &delta["signature"]),
+                    Some("input_json_delta") => ("input", &delta["partial_json"]),
+                    _ => return,
+                };
+                let current = block[field].as_str().unwrap_or("").to_string();
+                block[field] = serde_json::json!(current + text.as_str().unwrap_or(""));
+            }
+            "message_delta" => {
+                let delta = &event["delta"];
+                if !delta["stop_reason"].is_null() {
+                    self.stop_reason = Some(delta["stop_reason"].clone());
+                    self.stop_sequence = Some(delta["stop_sequence"].clone());
+                }
+                if let Some(usage) = event["usage"].as_object() {
+                    for (key, value) in usage {
+                        self.usage.insert(key.clone(), value.clone());
+                    }
+                }
+                if let Some(tokens) = event["usage"]["output_tokens"].as_u64() {
+                    self.output_tokens = Some(tokens);
+                }
+            }
+            "response.completed" => {
+                self.completed_response = Some(event["response"].clone());
+            }
+            "error" | "response.failed" => {
+                self.error = Some(event.clone());
+            }
+            _ => {}
+        }
+    }
+
+    fn push_chat(&mut self, event: &serde_json::Value) {
+        self.is_chat = true;
+        if self.id.is_none() {
+            self.id = event["id"].as_str().map(String::from);
+            self.model = event["model"].as_str().map(String::from);
+        }
+        let choice = &event["choices"][0];
+        let delta = &choice["delta"];
+        if let Some(text) = delta["content"].as_str() {
+            self.chat_text.push_str(text);
+        }
+        for call in delta["tool_calls"].as_array().into_iter().flatten() {
+            let index = call["index"].as_u64().unwrap_or(0) as usize;
+            if self.chat_tool_calls.len() <= index {
+                self.chat_tool_calls.resize(
+                    index + 1,
+                    serde_json::json!({
+                        "type": "function",
+                        "function": { "name": "", "arguments": "" }
+                    }),
+                );
+            }
+            let target = &mut self.chat_tool_calls[index];
+            if let Some(id) = call["id"].as_str() {
+                target["id"] = serde_json::json!(id);
+            }
+            for field in ["name", "arguments"] {
+                if let Some(part) = call["function"][field].as_str() {
+                    let current = target["function"][field].as_str().unwrap_or("").to_string();
+                    target["function"][field] = serde_json::json!(current + part);
+                }
+            }
+        }
+        if !choice["finish_reason"].is_null() {
+            self.finish_reason = Some(choice["finish_reason"].clone());
+        }
+        if let Some(usage) = event.get("usage").filter(|u| !u.is_null()) {
+            self.input_tokens = usage["prompt_tokens"].as_u64().unwrap_or(self.input_tokens);
+            self.output_tokens = usage["completion_tokens"].as_u64().or(self.output_tokens);
+        }
+    }
+
+    /// 已产生的输出 Token（上游未报告时按字符估算）
+    pub fn output_tokens(&self) -> u64 {
+        self.output_tokens.unwrap_or(self.estimated_output_tokens)
+    }
+
+    /// 流是否已正常结束
+    pub fn is_complete(&self) -> bool {
+        self.completed_response.is_some()
+            || self.stop_reason.is_some()
+            || self.finish_reason.is_some()
+    }
+
+    /// 上游在流中返回的错误事件
+    pub fn error(&self) -> Option<&serde_json::Value> {
+        self.error.as_ref()
+    }
+
+    /// 流已正常结束时生成非流式响应，流中出错或未结束时返回错误
+    pub fn finish(self) -> Result<serde_json::Value> {
+        if let Some(error) = self.error() {
+            anyhow::bail!("上游流返回错误: {}", error);
+        }
+        if !self.is_complete() {
+            anyhow::bail!("流未正常结束，无法重组");
+        }
+        Ok(self.into_response(false))
+    }
+
+    /// 生成非流式响应；`incomplete` 为 true 时附加 `incomplete: true` 标记
+    pub fn into_response(self, incomplete: bool) -> serde_json::Value {
+        if let Some(response) = self.completed_response {
+            return response;
+        }
+
+        let output_tokens = self.output_tokens();
+        let mut response = if self.is_chat {
+            let mut message = serde_json::json!({ "role": "assistant", "content": self.chat_text });
+            if !self.chat_tool_calls.is_empty() {
+                message["tool_calls"] = serde_json::json!(self.chat_tool_calls);
+            }
+            serde_json::json!({
+                "id": self.id,
+                "object": "chat.completion",
+                "model": self.model,
+                "choices": [{
+                    "index": 0,
+                    "message": message,
+                    "finish_reason": self.finish_reason
+                }],
+                "usage": {
+                    "prompt_tokens": self.input_tokens,
+                    "completion_tokens": output_tokens,
+                    "total_tokens": self.input_tokens + output_tokens
+                }
+            })
+        } else {
+            // 工具参数可能只收到一半，能解析的还原为对象，否则保留原始字符串
+            let content: Vec<_> = self
+                .blocks
+                .into_iter()
+                .filter(|block| !block.is_null())
+                .map(|mut block| {
+                    if let Some(json) = block["input"].as_str() {
+                        block["input"] = if json.is_empty() {
+                            serde_json::json!({})
+                        } else {
+                            serde_json::from_str(json).unwrap_or(serde_json::json!(json))
+                        };
+                    }
+                    block
+                })
+                .collect();
+            let mut usage = self.usage;
+            usage.insert(
+                "input_tokens".to_string(),
+                serde_json::json!(self.input_tokens),
+            );
+            usage.insert(
+                "output_tokens".to_string(),
+                serde_json::json!(output_tokens),
+            );
+            serde_json::json!({
+                "id": self.id,
+                "type": "message",
+                "role": "assistant",
+                "model": self.model,
+                "content": content,
+                "stop_reason": self.stop_reason,
+                "stop_sequence": self.stop_sequence,
+                "usage": usage
+            })
+        };
+        if incomplete {
+            response["incomplete"] = serde_json::json!(true);
+        }
+        response
+    }
+}
+
+/// 把完整的流拼成非流式响应，流中出错或未结束时返回错误
+pub fn reassemble(events: &[serde_json::Value]) -> Result<serde_json::Value> {
+    let mut assembler = StreamAssembler::default();
+    for event in events {
+        assembler.push(event);
+    }
+    assembler.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reassemble_anthropic_tool_use() {
+        let events = vec![
+            serde_json::json!({
+                "type": "message_start",
+                "message": { "id": "msg_1", "model": "claude", "usage": { "input_tokens": 9 } }
+            }),
+            serde_json::json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": { "type": "text", "text": "" }
+            }),
+            serde_json::json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": { "type": "text_delta", "text": "Looking up" }
+            }),
+            serde_json::json!({
+                "type": "content_block_start",
+                "index": 1,
+                "content_block": { "type": "tool_use", "id": "t1", "name": "lookup", "input": {} }
+            }),
+            serde_json::json!({
+                "type": "content_block_delta",
+                "index": 1,
+                "delta": { "type": "input_json_delta", "partial_json": "{\"q\":" }
+            }),
+            serde_json::json!({
+                "type": "content_block_delta",
+                "index": 1,
+                "delta": { "type": "input_json_delta", "partial_json": "1}" }
+            }),
+            serde_json::json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": "tool_use", "stop_sequence": null },
+                "usage": { "output_tokens": 20 }
+            }),
+            serde_json::json!({ "type": "message_stop" }),
+        ];
+
+        let response = reassemble(&events).unwrap();
+        assert_eq!(response["content"][0]["text"], "Looking up");
+        assert_eq!(response["content"][1]["input"]["q"], 1);
+        assert_eq!(response["stop_reason"], "tool_use");
+        assert_eq!(response["usage"]["output_tokens"], 20);
+        assert!(response.get("incomplete").is_none());
+
+        assert!(reassemble(&events[..5]).is_err());
+    }
+
+    #[test]
+    fn test_reassemble_chat_tool_calls() {
+        let events = vec![
+            serde_json::json!({ "id": "c1", "choices": [{ "delta": { "tool_calls": [
+                { "index": 0, "id": "call_1", "function": { "name": "lookup", "arguments": "" } }
+            ]}}]}),
+            serde_json::json!({ "id": "c1", "choices": [{ "delta": { "tool_calls": [
+                { "index": 0, "function": { "arguments": "{\"q\":2}" } }
+            ]}}]}),
+            serde_json::json!({ "id": "c1", "choices": [{ "delta": {}, "finish_reason": "tool_calls" }] }),
+        ];
+        let response = reassemble(&events).unwrap();
+        let call = &response["choices"][0]["message"]["tool_calls"][0];
+        assert_eq!(call["id"], "call_1");
+        assert_eq!(call["function"]["arguments"], "{\"q\":2}");
+        assert_eq!(response["choices"][0]["finish_reason"], "tool_calls");
+    }
+
+    #[test]
+    fn test_should_reassemble() {
+        let config = ReassemblyConfig {
+            enabled: true,
+            clients: vec!["legacy-tool".to_string()],
+        };
+        let request = serde_json::json!({ "model": "claude", "messages": [] });
+        assert!(should_reassemble(&config, &request, Some("legacy-tool")));
+        assert!(!should_reassemble(&config, &request, Some("Cursor")));
+
+        let streaming = serde_json::json!({ "stream": true });
+        assert!(!should_reassemble(&config, &streaming, Some("legacy-tool")));
+    }
+}
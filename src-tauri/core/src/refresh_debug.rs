@@ -0,0 +1,234 @@
+//! Token 刷新诊断
+//!
+//! `debug_refresh` 执行一次 WorkOS 刷新，并把完整的请求与响应（Token 等敏感
+//! 字段脱敏后）返回给调用方，用于自行排查“刷新失败”。诊断与普通刷新走同一
+//! 条受限流和锁定检查约束的路径，只有刷新成功才写回凭证：WorkOS 会轮换
+//! Refresh Token，成功后不写回会让已保存的 Token 失效。
+
+use crate::auth::workos::{self, RawRefreshResponse, RefreshOutcome, WORKOS_TOKEN_URL};
+use crate::credentials::TokenRefreshResult;
+use crate::tls_trust::CertificatePinMismatch;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// 刷新失败的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshFailureStage {
+    /// 请求未能送达（DNS、代理、超时等）
+    Network,
+    /// 证书与固定的指纹不符
+    CertificatePin,
+    /// WorkOS 返回错误状态码
+    Upstream,
+    /// 需要用户交互（MFA、邮箱验证、组织选择）
+    Challenge,
+    /// 响应无法解析
+    InvalidResponse,
+}
+
+/// 脱敏后的请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TracedRequest {
+    pub method: String,
+    pub url: String,
+    pub form: BTreeMap<String, String>,
+}
+
+/// 脱敏后的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TracedResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    /// JSON 响应体（非 JSON 时为原文）
+    pub body: serde_json::Value,
+}
+
+/// 一次刷新的完整记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshTrace {
+    pub credential_id: String,
+    pub request: TracedRequest,
+    #[serde(default)]
+    pub response: Option<TracedResponse>,
+    pub elapsed_ms: u64,
+    pub success: bool,
+    #[serde(default)]
+    pub failure_stage: Option<RefreshFailureStage>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// 新 Token 是否已写回凭证
+    pub committed: bool,
+}
+
+/// 脱敏：只保留前 6 个字符和长度
+pub fn redact(value: &str) -> String {
+    if value.chars().count() <= 8 {
+        return "***".to_string();
+    }
+    let prefix: String = value.chars().take(6).collect();
+    format!("{}…（{} 字符）", prefix, value.chars().count())
+}
+
+fn is_sensitive(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    ["token", "secret", "password", "cookie", "authorization"]
+        .iter()
+        .any(|word| key.contains(word))
+}
+
+/// 递归脱敏 JSON 中的敏感字段
+pub fn redact_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    serde_json::Value::String(text) if is_sensitive(key) => *text = redact(text),
+                    _ => redact_json(value),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+fn traced_request(refresh_token: &str, organization_id: Option<&str>) -> TracedRequest {
+    TracedRequest {
+        method: "POST".to_string(),
+        url: WORKOS_TOKEN_URL.to_string(),
+        form: workos::refresh_form(refresh_token, organization_id)
+            .into_iter()
+            .map(|(key, value)| {
+                let value = if is_sensitive(key) {
+                    redact(&value)
+                } else {
+                    value
+                };
+                (key.to_string(), value)
+            })
+            .collect(),
+    }
+}
+
+/// 脱敏原始响应
+pub fn traced_response(raw: &RawRefreshResponse) -> TracedResponse {
+    let mut body = serde_json::from_str(&raw.body)
+        .unwrap_or_else(|_| serde_json::Value::String(raw.body.clone()));
+    redact_json(&mut body);
+    TracedResponse {
+        status: raw.status.as_u16(),
+        headers: raw
+            .headers
+            .iter()
+            .filter(|(name, _)| !is_sensitive(name))
+            .cloned()
+            .collect(),
+        body,
+    }
+}
+
+/// 执行一次带记录的刷新，成功时同时返回新 Token
+pub async fn trace_refresh(
+    credential_id: &str,
+    refresh_token: &str,
+    organization_id: Option<&str>,
+) -> (RefreshTrace, Option<TokenRefreshResult>) {
+    let mut trace = RefreshTrace {
+        credential_id: credential_id.to_string(),
+        request: traced_request(refresh_token, organization_id),
+        response: None,
+        elapsed_ms: 0,
+        success: false,
+        failure_stage: None,
+        error: None,
+        committed: false,
+    };
+
+    let started = Instant::now();
+    let raw = workos::send_refresh_request(refresh_token, organization_id).await;
+    trace.elapsed_ms = started.elapsed().as_millis() as u64;
+
+    let raw = match raw {
+        Ok(raw) => raw,
+        Err(e) => {
+            trace.failure_stage = Some(match e.is::<CertificatePinMismatch>() {
+                true => RefreshFailureStage::CertificatePin,
+                false => RefreshFailureStage::Network,
+            });
+            trace.error = Some(format!("{:#}", e));
+            return (trace, None);
+        }
+    };
+    trace.response = Some(traced_response(&raw));
+
+    match workos::parse_refresh_response(raw.status, &raw.body) {
+        Ok(RefreshOutcome::Success(result)) => {
+            trace.success = true;
+            (trace, Some(result))
+        }
+        Ok(challenge) => {
+            trace.failure_stage = Some(RefreshFailureStage::Challenge);
+            trace.error = Some(challenge.message().to_string());
+            (trace, None)
+        }
+        Err(e) => {
+            trace.failure_stage = Some(match raw.status.is_success() {
+                true => RefreshFailureStage::InvalidResponse,
+                false => RefreshFailureStage::Upstream,
+            });
+            // 错误信息中带有原始响应体，改用脱敏后的版本
+            trace.error = Some(match raw.status.is_success() {
+                true => e.to_string(),
+                false => format!("WorkOS 返回 {}", raw.status),
+            });
+            (trace, None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_json() {
+        let mut body = serde_json::json!({
+            "access_token": "eyJhbGciOiJSUzI1NiJ9.payload.signature",
+            "refresh_token": "short",
+            "user": { "id": "user_01", "email": "a@example.com" },
+            "error": "invalid_grant",
+        });
+        redact_json(&mut body);
+        assert_eq!(body["access_token"], "eyJhbG…（38 字符）");
+        assert_eq!(body["refresh_token"], "***");
+        assert_eq!(body["user"]["id"], "user_01");
+        assert_eq!(body["error"], "invalid_grant");
+    }
+
+    #[test]
+    fn test_traced_request_hides_refresh_token() {
+        let request = traced_request("rt_0123456789abcdef", Some("org_01"));
+        assert_eq!(request.form["refresh_token"], "rt_012…（19 字符）");
+        assert_eq!(request.form["organization_id"], "org_01");
+        assert_eq!(request.form["grant_type"], "refresh_token");
+    }
+
+    #[test]
+    fn test_traced_response_drops_cookies() {
+        let raw = RawRefreshResponse {
+            status: reqwest::StatusCode::BAD_REQUEST,
+            headers: vec![
+                ("set-cookie".to_string(), "session=abc".to_string()),
+                ("x-request-id".to_string(), "req_1".to_string()),
+            ],
+            body: r#"{"error":"invalid_grant","error_description":"Session expired"}"#.to_string(),
+        };
+        let traced = traced_response(&raw);
+        assert_eq!(traced.status, 400);
+        assert!(!traced.headers.contains_key("set-cookie"));
+        assert_eq!(traced.headers["x-request-id"], "req_1");
+        assert_eq!(traced.body["error"], "invalid_grant");
+    }
+}
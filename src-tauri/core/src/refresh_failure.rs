@@ -0,0 +1,262 @@
+//! Token 刷新失败分类
+//!
+//! 刷新失败可能是本机网络问题（DNS、连接、超时、证书固定），也可能是 WorkOS
+//! 拒绝了请求（`invalid_grant`、限流、服务端错误）。按类别决定是否重试、
+//! 重试间隔以及是否计入凭证健康分，并在 `token_refresh_failed` 事件中带上
+//! 类别与处理建议，让用户知道该检查网络还是重新登录。
+
+use crate::tls_trust::CertificatePinMismatch;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// 限流时没有 Retry-After 的默认等待（秒）
+const DEFAULT_RATE_LIMIT_SECS: u64 = 30;
+
+/// 重试间隔上限（秒）
+const MAX_RETRY_DELAY_SECS: u64 = 300;
+
+/// 失败类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshFailureCategory {
+    /// DNS、连接被拒、代理错误等
+    Network,
+    /// 连接或读取超时
+    Timeout,
+    /// 证书与固定的指纹不符
+    CertificatePin,
+    /// 登录会话已失效或 Refresh Token 已被撤销
+    InvalidGrant,
+    /// WorkOS 限流（429）
+    RateLimited,
+    /// WorkOS 服务端错误（5xx）
+    ServerError,
+    /// 其他被拒绝的请求（4xx、缺少 Token 等）
+    Rejected,
+    /// 成功状态码但响应无法解析
+    InvalidResponse,
+}
+
+impl RefreshFailureCategory {
+    /// 是否值得自动重试
+    pub fn is_retryable(self) -> bool {
+        !matches!(
+            self,
+            Self::InvalidGrant | Self::Rejected | Self::CertificatePin
+        )
+    }
+
+    /// 是否计入凭证的刷新失败（本机网络问题、WorkOS 故障与限流不归咎于凭证）
+    pub fn counts_against_credential(self) -> bool {
+        matches!(self, Self::InvalidGrant | Self::Rejected)
+    }
+
+    /// 是否值得改用宽限期内的旧 Refresh Token 再试
+    pub fn is_auth_failure(self) -> bool {
+        matches!(self, Self::InvalidGrant | Self::Rejected)
+    }
+
+    /// 第 `attempt` 次（从 0 开始）失败后的重试间隔
+    pub fn retry_delay(self, attempt: u32, retry_after_secs: Option<u64>) -> Duration {
+        let backoff = |base: u64| base.saturating_mul(2_u64.saturating_pow(attempt));
+        let secs = match self {
+            Self::RateLimited => retry_after_secs.unwrap_or(backoff(DEFAULT_RATE_LIMIT_SECS)),
+            Self::ServerError => backoff(2),
+            _ => backoff(1),
+        };
+        Duration::from_secs(secs.min(MAX_RETRY_DELAY_SECS))
+    }
+
+    /// 给用户的处理建议
+    pub fn hint(self) -> &'static str {
+        match self {
+            Self::Network => "无法连接 WorkOS，请检查网络、代理或 DNS 设置",
+            Self::Timeout => "连接 WorkOS 超时，请检查网络或代理",
+            Self::CertificatePin => "WorkOS 证书与固定的指纹不符，请检查代理或证书固定配置",
+            Self::InvalidGrant => "登录会话已失效，请重新登录该账号",
+            Self::RateLimited => "WorkOS 限流，稍后会自动重试",
+            Self::ServerError => "WorkOS 服务异常，稍后会自动重试",
+            Self::Rejected => "WorkOS 拒绝了刷新请求，请检查凭证配置",
+            Self::InvalidResponse => "WorkOS 响应无法解析，稍后会自动重试",
+        }
+    }
+}
+
+impl std::fmt::Display for RefreshFailureCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            Self::Network => "network",
+            Self::Timeout => "timeout",
+            Self::CertificatePin => "certificate_pin",
+            Self::InvalidGrant => "invalid_grant",
+            Self::RateLimited => "rate_limited",
+            Self::ServerError => "server_error",
+            Self::Rejected => "rejected",
+            Self::InvalidResponse => "invalid_response",
+        };
+        f.write_str(label)
+    }
+}
+
+/// 分类后的刷新失败
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+#[error("{message}")]
+pub struct RefreshFailure {
+    pub category: RefreshFailureCategory,
+    /// WorkOS 返回的状态码
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// WorkOS 返回的错误码（如 `invalid_grant`）
+    #[serde(default)]
+    pub error_code: Option<String>,
+    #[serde(default)]
+    pub retry_after_secs: Option<u64>,
+    pub message: String,
+    pub hint: String,
+}
+
+impl RefreshFailure {
+    fn new(category: RefreshFailureCategory, message: String) -> Self {
+        Self {
+            category,
+            status: None,
+            error_code: None,
+            retry_after_secs: None,
+            message,
+            hint: category.hint().to_string(),
+        }
+    }
+
+    /// 由 WorkOS 的错误响应构建
+    pub fn from_response(status: u16, body: &str) -> Self {
+        let error_code = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|json| json["error"].as_str().map(str::to_string));
+        let category = match status {
+            _ if error_code.as_deref() == Some("invalid_grant") => {
+                RefreshFailureCategory::InvalidGrant
+            }
+            429 => RefreshFailureCategory::RateLimited,
+            500..=599 => RefreshFailureCategory::ServerError,
+            _ => RefreshFailureCategory::Rejected,
+        };
+        Self {
+            status: Some(status),
+            error_code,
+            ..Self::new(
+                category,
+                format!("WorkOS Token 刷新失败: {} - {}", status, body),
+            )
+        }
+    }
+
+    /// 附上响应头中的 Retry-After（秒）
+    pub fn with_retry_after(mut self, headers: &[(String, String)]) -> Self {
+        self.retry_after_secs = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("retry-after"))
+            .and_then(|(_, value)| value.trim().parse().ok());
+        self
+    }
+}
+
+/// 对任意刷新错误分类
+pub fn classify(error: &anyhow::Error) -> RefreshFailure {
+    if let Some(failure) = error.downcast_ref::<RefreshFailure>() {
+        return failure.clone();
+    }
+    let message = format!("{:#}", error);
+    if error.is::<CertificatePinMismatch>() {
+        return RefreshFailure::new(RefreshFailureCategory::CertificatePin, message);
+    }
+    let transport = error
+        .chain()
+        .find_map(|e| e.downcast_ref::<reqwest::Error>());
+    let category = match transport {
+        Some(e) if e.is_timeout() => RefreshFailureCategory::Timeout,
+        Some(e) if e.is_decode() || e.is_body() => RefreshFailureCategory::InvalidResponse,
+        Some(_) => RefreshFailureCategory::Network,
+        None => RefreshFailureCategory::Rejected,
+    };
+    RefreshFailure::new(category, message)
+}
+
+lazy_static::lazy_static! {
+    /// 每个凭证最近一次通知过的失败类别
+    static ref REPORTED: Mutex<HashMap<String, RefreshFailureCategory>> =
+        Mutex::new(HashMap::new());
+}
+
+/// 通知刷新失败；同一凭证连续同类失败只通知一次
+pub fn report(credential_id: &str, name: Option<&str>, failure: &RefreshFailure) {
+    let previous = REPORTED
+        .lock()
+        .unwrap()
+        .insert(credential_id.to_string(), failure.category);
+    if previous == Some(failure.category) {
+        return;
+    }
+    crate::events::emit(
+        "token_refresh_failed",
+        format!(
+            "{} 刷新失败：{}",
+            name.unwrap_or(credential_id),
+            failure.hint
+        ),
+        serde_json::json!({ "credential_id": credential_id, "failure": failure }),
+    );
+}
+
+/// 刷新成功后清除通知记录
+pub fn clear(credential_id: &str) {
+    REPORTED.lock().unwrap().remove(credential_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RefreshFailureCategory::*;
+
+    #[test]
+    fn test_from_response() {
+        let expired = RefreshFailure::from_response(400, r#"{"error":"invalid_grant"}"#);
+        assert_eq!(expired.category, InvalidGrant);
+        assert_eq!(expired.error_code.as_deref(), Some("invalid_grant"));
+        assert!(expired.to_string().contains("invalid_grant"));
+
+        let limited = RefreshFailure::from_response(429, "")
+            .with_retry_after(&[("Retry-After".to_string(), "12".to_string())]);
+        assert_eq!(limited.category, RateLimited);
+        assert_eq!(limited.retry_after_secs, Some(12));
+
+        assert_eq!(RefreshFailure::from_response(503, "").category, ServerError);
+        assert_eq!(RefreshFailure::from_response(401, "{}").category, Rejected);
+    }
+
+    #[test]
+    fn test_classify() {
+        let failure: anyhow::Error = RefreshFailure::from_response(502, "bad gateway").into();
+        assert_eq!(classify(&failure.context("刷新")).category, ServerError);
+        let pin: anyhow::Error = CertificatePinMismatch { url: String::new() }.into();
+        assert_eq!(classify(&pin).category, CertificatePin);
+        assert_eq!(
+            classify(&anyhow::anyhow!("缺少 refresh_token")).category,
+            Rejected
+        );
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        assert!(!InvalidGrant.is_retryable());
+        assert!(Timeout.is_retryable() && !Timeout.counts_against_credential());
+        assert!(!RateLimited.counts_against_credential());
+        assert_eq!(RateLimited.retry_delay(0, Some(7)), Duration::from_secs(7));
+        assert_eq!(RateLimited.retry_delay(1, None), Duration::from_secs(60));
+        assert_eq!(
+            Network.retry_delay(20, None),
+            Duration::from_secs(MAX_RETRY_DELAY_SECS)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Token 刷新限流
+//!
+//! 笔记本唤醒后大量凭证同时过期，宿主逐个触发刷新会瞬间打满 WorkOS。
+//! 所有刷新先取得许可：同时进行的刷新数不超过 `max_concurrent`，相邻两次
+//! 刷新的开始时间至少间隔 `min_interval_ms`。紧急刷新（401 恢复、正在使用
+//! 或宿主声明即将使用的凭证）排在后台刷新之前。
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// 刷新限流配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RefreshLimitConfig {
+    /// 同时进行的刷新数上限
+    pub max_concurrent: usize,
+    /// 相邻两次刷新的最小间隔（毫秒）
+    pub min_interval_ms: u64,
+}
+
+impl Default for RefreshLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 2,
+            min_interval_ms: 500,
+        }
+    }
+}
+
+/// 刷新优先级
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshPriority {
+    /// 定时或批量刷新
+    #[default]
+    Background,
+    /// 凭证正在或即将被使用
+    Urgent,
+}
+
+#[derive(Debug, Default)]
+struct LimiterState {
+    active: usize,
+    last_start: Option<Instant>,
+    waiting_urgent: usize,
+}
+
+/// 刷新限流器
+#[derive(Debug, Default)]
+pub struct RefreshLimiter {
+    state: Mutex<LimiterState>,
+    notify: Notify,
+}
+
+/// 刷新许可，释放时唤醒等待者
+pub struct RefreshPermit<'a> {
+    limiter: &'a RefreshLimiter,
+}
+
+impl Drop for RefreshPermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.state.lock().unwrap().active -= 1;
+        self.limiter.notify.notify_waiters();
+    }
+}
+
+/// 紧急刷新的等待登记
+struct UrgentWaiter<'a>(&'a Mutex<LimiterState>);
+
+impl<'a> UrgentWaiter<'a> {
+    fn register(state: &'a Mutex<LimiterState>) -> Self {
+        state.lock().unwrap().waiting_urgent += 1;
+        Self(state)
+    }
+}
+
+impl Drop for UrgentWaiter<'_> {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().waiting_urgent -= 1;
+    }
+}
+
+impl RefreshLimiter {
+    /// 等待刷新许可
+    pub async fn acquire(
+        &self,
+        priority: RefreshPriority,
+        config: &RefreshLimitConfig,
+    ) -> RefreshPermit<'_> {
+        let urgent = priority == RefreshPriority::Urgent;
+        // 取消等待时也要撤销登记，否则后台刷新会一直让路
+        let _waiting = urgent.then(|| UrgentWaiter::register(&self.state));
+        let interval = Duration::from_millis(config.min_interval_ms);
+
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let wait_until = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                let next_start = state.last_start.map(|t| t + interval).filter(|t| *t > now);
+                let yield_to_urgent = !urgent && state.waiting_urgent > 0;
+
+                if state.active < config.max_concurrent.max(1) && !yield_to_urgent {
+                    match next_start {
+                        None => {
+                            state.active += 1;
+                            state.last_start = Some(now);
+                            return RefreshPermit { limiter: self };
+                        }
+                        Some(next_start) => Some(next_start),
+                    }
+                } else {
+                    None
+                }
+            };
+
+            match wait_until {
+                // 间隔未到：等到点或有许可释放后再抢
+                Some(deadline) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(deadline) => {}
+                        _ = notified => {}
+                    }
+                }
+                None => notified.await,
+            }
+        }
+    }
+
+    /// 当前进行中的刷新数
+    pub fn active(&self) -> usize {
+        self.state.lock().unwrap().active
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref LIMITER: RefreshLimiter = RefreshLimiter::default();
+}
+
+/// 按当前配置等待全局刷新许可
+pub async fn acquire(priority: RefreshPriority) -> RefreshPermit<'static> {
+    let config = crate::config::get_config().refresh_limit;
+    LIMITER.acquire(priority, &config).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrency_limit_and_spacing() {
+        let limiter = RefreshLimiter::default();
+        let config = RefreshLimitConfig {
+            max_concurrent: 1,
+            min_interval_ms: 50,
+        };
+
+        let started = Instant::now();
+        let first = limiter.acquire(RefreshPriority::Background, &config).await;
+        assert_eq!(limiter.active(), 1);
+        drop(first);
+        let _second = limiter.acquire(RefreshPriority::Background, &config).await;
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        let blocked = tokio::time::timeout(
+            Duration::from_millis(100),
+            limiter.acquire(RefreshPriority::Urgent, &config),
+        );
+        assert!(blocked.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_urgent_goes_first() {
+        let limiter = std::sync::Arc::new(RefreshLimiter::default());
+        let config = RefreshLimitConfig {
+            max_concurrent: 1,
+            min_interval_ms: 0,
+        };
+        let held = limiter.acquire(RefreshPriority::Background, &config).await;
+
+        let order = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let spawn = |priority: RefreshPriority| {
+            let (limiter, order, config) = (limiter.clone(), order.clone(), config.clone());
+            tokio::spawn(async move {
+                let _permit = limiter.acquire(priority, &config).await;
+                order.lock().unwrap().push(priority);
+            })
+        };
+        let background = spawn(RefreshPriority::Background);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let urgent = spawn(RefreshPriority::Urgent);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        drop(held);
+        background.await.unwrap();
+        urgent.await.unwrap();
+        assert_eq!(
+            *order.lock().unwrap(),
+            [RefreshPriority::Urgent, RefreshPriority::Background]
+        );
+    }
+}
@@ -0,0 +1,420 @@
+//! 重新登录提醒
+//!
+//! Refresh Token 被拒绝（会话过期）或 WorkOS 要求用户交互时，凭证只能重新
+//! 登录才能恢复。这里记录待重新登录的凭证，生成带账号与组织提示的 WorkOS
+//! 登录链接，立即发出 `relogin_required` 事件，之后按间隔发出
+//! `relogin_reminder`，直到刷新成功。用户可暂缓提醒，无人值守的网关在有人
+//! 处理时能尽快恢复健康凭证。
+//!
+//! 登录链接使用 PKCE：`state` 为随机值，对应的 `code_verifier` 只保存在内存中；
+//! 宿主收到 `redirect_uri` 回调后调用 `complete_relogin`，用授权码换取新 Token
+//! 并写回原凭证。首次运行向导也用同一流程登录新账号（`new_login_url`），回调
+//! 完成后创建新凭证。提醒写入 `relogin.json`，重启后恢复并重新生成登录链接。
+
+use crate::auth::workos::WORKOS_CLIENT_ID;
+use crate::config::data_dir;
+use crate::credentials::DroidCredentials;
+use crate::events;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// WorkOS AuthKit 登录入口
+const WORKOS_AUTHORIZE_URL: &str = "https://api.workos.com/user_management/authorize";
+
+/// 提醒持久化文件名
+pub const RELOGIN_FILE: &str = "relogin.json";
+
+/// 后台检查间隔（秒）
+const CHECK_INTERVAL_SECS: u64 = 300;
+
+/// 重新登录提醒配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReloginConfig {
+    pub enabled: bool,
+    /// 两次提醒的间隔（小时）
+    pub remind_interval_hours: u32,
+    /// 登录完成后的回调地址（宿主注册的 URL Scheme）
+    pub redirect_uri: String,
+}
+
+impl Default for ReloginConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            remind_interval_hours: 4,
+            redirect_uri: "droid-provider://auth/callback".to_string(),
+        }
+    }
+}
+
+/// 待重新登录的凭证
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReloginReminder {
+    pub credential_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub reason: String,
+    pub login_url: String,
+    pub since: DateTime<Utc>,
+    #[serde(default)]
+    pub last_reminded: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub snoozed_until: Option<DateTime<Utc>>,
+}
+
+impl ReloginReminder {
+    /// 是否到了再次提醒的时间
+    fn is_due(&self, now: DateTime<Utc>, interval: Duration) -> bool {
+        if self.snoozed_until.is_some_and(|until| now < until) {
+            return false;
+        }
+        self.last_reminded.is_none_or(|last| now - last >= interval)
+    }
+}
+
+/// 等待回调的登录请求
+#[derive(Debug, Clone)]
+pub struct PendingLogin {
+    /// 重新登录的凭证，新账号登录时为 None
+    pub credential_id: Option<String>,
+    pub code_verifier: String,
+}
+
+lazy_static::lazy_static! {
+    static ref REMINDERS: Mutex<HashMap<String, ReloginReminder>> = Mutex::new(HashMap::new());
+    /// state → 登录请求（不落盘，重启后重新生成）
+    static ref PENDING: Mutex<HashMap<String, PendingLogin>> = Mutex::new(HashMap::new());
+}
+
+/// 提醒文件路径
+pub fn relogin_path() -> PathBuf {
+    data_dir().join(RELOGIN_FILE)
+}
+
+fn save_to_disk(reminders: &HashMap<String, ReloginReminder>) {
+    if let Err(e) = crate::store::write_json(&relogin_path(), reminders) {
+        warn!("保存重新登录提醒失败: {}", e);
+    }
+}
+
+fn load_from_disk() -> HashMap<String, ReloginReminder> {
+    match crate::store::read_json(&relogin_path()) {
+        Ok(reminders) => reminders.unwrap_or_default(),
+        Err(e) => {
+            warn!("重新登录提醒读取失败，已忽略: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+/// 随机的 URL 安全字符串（PKCE verifier 与 state）
+fn random_token() -> String {
+    URL_SAFE_NO_PAD.encode(rand::thread_rng().gen::<[u8; 32]>())
+}
+
+/// PKCE S256 challenge
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// 生成重新登录链接并登记 PKCE 参数（同一凭证只保留最新的链接）
+pub fn login_url(
+    credential_id: &str,
+    credential: &DroidCredentials,
+    config: &ReloginConfig,
+) -> String {
+    register_login(Some(credential_id), credential, config)
+}
+
+/// 生成新账号的登录链接（首次运行向导），回调完成后创建新凭证
+pub fn new_login_url(config: &ReloginConfig) -> String {
+    register_login(None, &DroidCredentials::default(), config)
+}
+
+fn register_login(
+    credential_id: Option<&str>,
+    credential: &DroidCredentials,
+    config: &ReloginConfig,
+) -> String {
+    let state = random_token();
+    let code_verifier = random_token();
+    let url = authorize_url(credential, config, &state, &code_challenge(&code_verifier));
+    let mut pending = PENDING.lock().unwrap();
+    pending.retain(|_, p| p.credential_id.as_deref() != credential_id);
+    pending.insert(
+        state,
+        PendingLogin {
+            credential_id: credential_id.map(str::to_string),
+            code_verifier,
+        },
+    );
+    url
+}
+
+/// 取出回调 `state` 对应的登录请求（每个 state 只能使用一次）
+pub fn take_pending(state: &str) -> Option<PendingLogin> {
+    PENDING.lock().unwrap().remove(state)
+}
+
+/// 解析登录回调地址，返回 (code, state)
+pub fn parse_callback(callback_url: &str) -> anyhow::Result<(String, String)> {
+    let url =
+        reqwest::Url::parse(callback_url).map_err(|e| anyhow::anyhow!("回调地址无效: {}", e))?;
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    };
+    if let Some(error) = param("error_description").or_else(|| param("error")) {
+        anyhow::bail!("登录失败: {}", error);
+    }
+    match (param("code"), param("state")) {
+        (Some(code), Some(state)) => Ok((code, state)),
+        _ => anyhow::bail!("回调地址缺少 code 或 state"),
+    }
+}
+
+/// 拼接 WorkOS 授权链接
+fn authorize_url(
+    credential: &DroidCredentials,
+    config: &ReloginConfig,
+    state: &str,
+    code_challenge: &str,
+) -> String {
+    let mut params = vec![
+        ("client_id", WORKOS_CLIENT_ID),
+        ("response_type", "code"),
+        ("provider", "authkit"),
+        ("redirect_uri", config.redirect_uri.as_str()),
+        ("state", state),
+        ("code_challenge", code_challenge),
+        ("code_challenge_method", "S256"),
+    ];
+    if let Some(email) = credential.owner_email.as_deref() {
+        params.push(("login_hint", email));
+    }
+    if let Some(org_id) = credential.organization_id.as_deref() {
+        params.push(("organization_id", org_id));
+    }
+    reqwest::Url::parse_with_params(WORKOS_AUTHORIZE_URL, &params)
+        .map(String::from)
+        .unwrap_or_else(|_| WORKOS_AUTHORIZE_URL.to_string())
+}
+
+/// 标记凭证需要重新登录，首次标记时立即通知
+pub fn mark_required(credential_id: &str, credential: &DroidCredentials, reason: &str) {
+    let config = crate::config::get_config().relogin;
+    if !config.enabled {
+        return;
+    }
+    let mut reminders = REMINDERS.lock().unwrap();
+    if reminders.contains_key(credential_id) {
+        return;
+    }
+    let now = Utc::now();
+    let reminder = ReloginReminder {
+        credential_id: credential_id.to_string(),
+        name: credential.name.clone(),
+        reason: reason.to_string(),
+        login_url: login_url(credential_id, credential, &config),
+        since: now,
+        last_reminded: Some(now),
+        snoozed_until: None,
+    };
+    info!("凭证 {} 需要重新登录: {}", credential_id, reason);
+    emit("relogin_required", &reminder);
+    reminders.insert(credential_id.to_string(), reminder);
+    save_to_disk(&reminders);
+}
+
+/// 启动时恢复上次保存的提醒
+///
+/// 已删除凭证的提醒直接丢弃；PKCE 参数不落盘，登录链接重新生成。
+pub fn restore(credentials: &HashMap<String, DroidCredentials>) {
+    let config = crate::config::get_config().relogin;
+    let mut restored = load_from_disk();
+    restored.retain(|id, reminder| match credentials.get(id) {
+        Some(credential) if config.enabled => {
+            reminder.login_url = login_url(id, credential, &config);
+            true
+        }
+        _ => false,
+    });
+    if !restored.is_empty() {
+        info!("恢复 {} 个重新登录提醒", restored.len());
+    }
+    let mut reminders = REMINDERS.lock().unwrap();
+    for (id, reminder) in restored {
+        reminders.entry(id).or_insert(reminder);
+    }
+    save_to_disk(&reminders);
+}
+
+/// 凭证已恢复（刷新成功或被删除），不再提醒
+pub fn clear(credential_id: &str) {
+    let mut reminders = REMINDERS.lock().unwrap();
+    if reminders.remove(credential_id).is_some() {
+        info!("凭证 {} 已恢复，停止重新登录提醒", credential_id);
+        PENDING
+            .lock()
+            .unwrap()
+            .retain(|_, p| p.credential_id.as_deref() != Some(credential_id));
+        save_to_disk(&reminders);
+    }
+}
+
+/// 暂缓提醒
+pub fn snooze(credential_id: &str, minutes: u32) -> Option<ReloginReminder> {
+    let mut reminders = REMINDERS.lock().unwrap();
+    let reminder = reminders.get_mut(credential_id)?;
+    reminder.snoozed_until = Some(Utc::now() + Duration::minutes(minutes as i64));
+    let reminder = reminder.clone();
+    save_to_disk(&reminders);
+    Some(reminder)
+}
+
+/// 当前待重新登录的凭证
+pub fn list() -> Vec<ReloginReminder> {
+    let mut reminders: Vec<_> = REMINDERS.lock().unwrap().values().cloned().collect();
+    reminders.sort_by_key(|r| r.since);
+    reminders
+}
+
+/// 取出到期的提醒并更新提醒时间
+fn take_due(now: DateTime<Utc>, interval: Duration) -> Vec<ReloginReminder> {
+    let mut reminders = REMINDERS.lock().unwrap();
+    let due: Vec<ReloginReminder> = reminders
+        .values_mut()
+        .filter(|r| r.is_due(now, interval))
+        .map(|r| {
+            r.last_reminded = Some(now);
+            r.snoozed_until = None;
+            r.clone()
+        })
+        .collect();
+    if !due.is_empty() {
+        save_to_disk(&reminders);
+    }
+    due
+}
+
+fn emit(kind: &str, reminder: &ReloginReminder) {
+    let name = reminder.name.as_deref().unwrap_or(&reminder.credential_id);
+    events::emit(
+        kind,
+        format!("凭证 {} 需要重新登录（{}）", name, reminder.reason),
+        serde_json::to_value(reminder).unwrap_or_default(),
+    );
+}
+
+/// 后台任务：按间隔重复提醒
+pub async fn run_reminder() {
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+        let config = crate::config::get_config().relogin;
+        if !config.enabled {
+            continue;
+        }
+        let interval = Duration::hours(config.remind_interval_hours as i64);
+        for reminder in take_due(Utc::now(), interval) {
+            emit("relogin_reminder", &reminder);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login_url() {
+        let credential = DroidCredentials {
+            owner_email: Some("dev@example.com".to_string()),
+            organization_id: Some("org_1".to_string()),
+            ..Default::default()
+        };
+        let url = authorize_url(&credential, &ReloginConfig::default(), "st", "ch");
+        assert!(url.starts_with(WORKOS_AUTHORIZE_URL));
+        assert!(url.contains("state=st"));
+        assert!(url.contains("code_challenge=ch"));
+        assert!(url.contains("code_challenge_method=S256"));
+        assert!(url.contains("login_hint=dev%40example.com"));
+        assert!(url.contains("organization_id=org_1"));
+    }
+
+    #[test]
+    fn test_parse_callback() {
+        assert_eq!(
+            parse_callback("droid-provider://auth/callback?code=abc&state=xyz").unwrap(),
+            ("abc".to_string(), "xyz".to_string())
+        );
+        let denied = parse_callback(
+            "droid-provider://auth/callback?error=access_denied&error_description=cancelled",
+        );
+        assert!(denied.unwrap_err().to_string().contains("cancelled"));
+        assert!(parse_callback("droid-provider://auth/callback?code=abc").is_err());
+    }
+
+    #[test]
+    fn test_code_challenge() {
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mJ92ZD1z6gDlsvEInNs8Gf6yi0bCbA"),
+            "hFb6LupViCUj4vJ6LQOW9v6VfdKjUi6FD7-TkGueBKc"
+        );
+    }
+
+    #[test]
+    fn test_pending_login() {
+        let credential = DroidCredentials::default();
+        let config = ReloginConfig::default();
+        let first = login_url("relogin-pending", &credential, &config);
+        let second = login_url("relogin-pending", &credential, &config);
+        let state_of = |url: &str| {
+            reqwest::Url::parse(url)
+                .unwrap()
+                .query_pairs()
+                .find(|(k, _)| k == "state")
+                .map(|(_, v)| v.into_owned())
+                .unwrap()
+        };
+        // 只有最新的链接有效，且 state 只能使用一次
+        assert!(take_pending(&state_of(&first)).is_none());
+        let pending = take_pending(&state_of(&second)).unwrap();
+        assert_eq!(pending.credential_id.as_deref(), Some("relogin-pending"));
+        assert!(!pending.code_verifier.is_empty());
+        assert!(take_pending(&state_of(&second)).is_none());
+    }
+
+    #[test]
+    fn test_due_and_snooze() {
+        let now = Utc::now();
+        let interval = Duration::hours(4);
+        let mut reminder = ReloginReminder {
+            credential_id: "a".to_string(),
+            name: None,
+            reason: "会话过期".to_string(),
+            login_url: String::new(),
+            since: now,
+            last_reminded: Some(now - Duration::hours(5)),
+            snoozed_until: None,
+        };
+        assert!(reminder.is_due(now, interval));
+
+        reminder.snoozed_until = Some(now + Duration::hours(1));
+        assert!(!reminder.is_due(now, interval));
+        assert!(reminder.is_due(now + Duration::hours(2), interval));
+
+        reminder.last_reminded = Some(now);
+        reminder.snoozed_until = None;
+        assert!(!reminder.is_due(now + Duration::hours(1), interval));
+    }
+}
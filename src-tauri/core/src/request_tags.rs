@@ -0,0 +1,91 @@
+//! 请求标签
+//!
+//! 调用方在 `acquire_credential` 的 `tags` 参数中为请求附加任意标签
+//! （如 `{"project": "billing", "ticket": "OPS-42"}`），标签随租约写入使用记录，
+//! 导出用量时可按标签筛选或分组，用于按项目分摊费用。
+
+use std::collections::BTreeMap;
+use tracing::warn;
+
+/// 标签（键 → 值）
+pub type Tags = BTreeMap<String, String>;
+
+/// 单个请求最多保留的标签数
+pub const MAX_TAGS: usize = 16;
+
+/// 键的最大长度（字符）
+pub const MAX_KEY_LEN: usize = 64;
+
+/// 值的最大长度（字符）
+pub const MAX_VALUE_LEN: usize = 256;
+
+fn truncate(value: &str, max: usize) -> String {
+    value.trim().chars().take(max).collect()
+}
+
+/// 清理调用方传入的标签：去除首尾空白、丢弃空键、截断过长的键值，
+/// 超出数量上限的部分丢弃（按键排序保留前面的）
+pub fn sanitize(tags: &Tags) -> Tags {
+    let mut sanitized = Tags::new();
+    for (key, value) in tags {
+        let key = truncate(key, MAX_KEY_LEN);
+        if key.is_empty() {
+            continue;
+        }
+        if sanitized.len() >= MAX_TAGS {
+            warn!("请求标签超过 {} 个，其余已丢弃", MAX_TAGS);
+            break;
+        }
+        sanitized.insert(key, truncate(value, MAX_VALUE_LEN));
+    }
+    sanitized
+}
+
+/// 标签是否满足筛选条件：每个条件都要满足，条件值为空时只要求存在该键
+pub fn matches(tags: &Tags, filter: &Tags) -> bool {
+    filter.iter().all(|(key, expected)| match tags.get(key) {
+        Some(value) => expected.is_empty() || value == expected,
+        None => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(pairs: &[(&str, &str)]) -> Tags {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let long = "x".repeat(MAX_VALUE_LEN + 10);
+        let input = tags(&[
+            (" project ", " billing "),
+            ("  ", "dropped"),
+            ("note", &long),
+        ]);
+        let sanitized = sanitize(&input);
+        assert_eq!(sanitized.len(), 2);
+        assert_eq!(sanitized["project"], "billing");
+        assert_eq!(sanitized["note"].len(), MAX_VALUE_LEN);
+
+        let many: Tags = (0..MAX_TAGS + 4)
+            .map(|i| (format!("k{:02}", i), String::new()))
+            .collect();
+        assert_eq!(sanitize(&many).len(), MAX_TAGS);
+    }
+
+    #[test]
+    fn test_matches() {
+        let record = tags(&[("project", "billing"), ("ticket", "OPS-42")]);
+        assert!(matches(&record, &Tags::new()));
+        assert!(matches(&record, &tags(&[("project", "billing")])));
+        assert!(matches(&record, &tags(&[("ticket", "")])));
+        assert!(!matches(&record, &tags(&[("project", "search")])));
+        assert!(!matches(&record, &tags(&[("team", "")])));
+    }
+}
@@ -0,0 +1,131 @@
+//! 响应元数据
+//!
+//! 标明实际处理请求的凭证、端点、重试次数和耗时，便于用户把客户端现象与
+//! 凭证池的决策对应起来。默认只以 `x-droid-*` 响应头的形式交给宿主；
+//! 开启 `body_field` 且参数审查为宽松模式时，另在响应体中加入 `_droid` 字段。
+
+use crate::credentials::EndpointType;
+use crate::param_policy::ParamMode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 响应体中的字段名
+pub const BODY_FIELD: &str = "_droid";
+
+/// 响应元数据配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseMetaConfig {
+    /// 返回 `x-droid-*` 响应头
+    pub headers: bool,
+    /// 在响应体中加入 `_droid` 字段
+    pub body_field: bool,
+}
+
+impl Default for ResponseMetaConfig {
+    fn default() -> Self {
+        Self {
+            headers: true,
+            body_field: false,
+        }
+    }
+}
+
+/// 处理请求的凭证信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServingInfo {
+    pub credential_id: String,
+    pub credential_name: Option<String>,
+    pub endpoint_type: EndpointType,
+    pub model: String,
+    /// 重试次数（宿主上报的重试加上 401 恢复）
+    pub retries: u32,
+    /// 从获取凭证到现在的耗时
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failover_from: Option<EndpointType>,
+}
+
+/// 生成响应头
+pub fn headers(config: &ResponseMetaConfig, info: &ServingInfo) -> BTreeMap<String, String> {
+    let mut headers = BTreeMap::new();
+    if !config.headers {
+        return headers;
+    }
+    let name = info
+        .credential_name
+        .as_deref()
+        .unwrap_or(&info.credential_id);
+    headers.insert("x-droid-credential".to_string(), name.to_string());
+    headers.insert(
+        "x-droid-endpoint".to_string(),
+        info.endpoint_type.to_string(),
+    );
+    headers.insert("x-droid-model".to_string(), info.model.clone());
+    headers.insert("x-droid-retries".to_string(), info.retries.to_string());
+    headers.insert(
+        "x-droid-latency-ms".to_string(),
+        info.latency_ms.to_string(),
+    );
+    if let Some(from) = info.failover_from {
+        headers.insert("x-droid-failover-from".to_string(), from.to_string());
+    }
+    headers
+}
+
+/// 在响应体中加入 `_droid` 字段（严格模式下客户端可能拒绝未知字段，不注入）
+pub fn inject(
+    config: &ResponseMetaConfig,
+    mode: ParamMode,
+    response: &mut serde_json::Value,
+    info: &ServingInfo,
+) {
+    if !config.body_field || mode != ParamMode::Permissive {
+        return;
+    }
+    if let Some(body) = response.as_object_mut() {
+        body.insert(
+            BODY_FIELD.to_string(),
+            serde_json::to_value(info).unwrap_or_default(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> ServingInfo {
+        ServingInfo {
+            credential_id: "cred-1".to_string(),
+            credential_name: Some("work".to_string()),
+            endpoint_type: EndpointType::Anthropic,
+            model: "claude-sonnet-4-20250514".to_string(),
+            retries: 1,
+            latency_ms: 830,
+            failover_from: None,
+        }
+    }
+
+    #[test]
+    fn test_headers() {
+        let headers = headers(&ResponseMetaConfig::default(), &info());
+        assert_eq!(headers["x-droid-credential"], "work");
+        assert_eq!(headers["x-droid-retries"], "1");
+        assert!(!headers.contains_key("x-droid-failover-from"));
+    }
+
+    #[test]
+    fn test_inject_only_when_permissive() {
+        let config = ResponseMetaConfig {
+            body_field: true,
+            ..Default::default()
+        };
+        let mut response = serde_json::json!({ "id": "msg_1" });
+        inject(&config, ParamMode::Strict, &mut response, &info());
+        assert!(response.get(BODY_FIELD).is_none());
+
+        inject(&config, ParamMode::Permissive, &mut response, &info());
+        assert_eq!(response[BODY_FIELD]["credential_name"], "work");
+    }
+}
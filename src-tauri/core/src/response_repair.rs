@@ -0,0 +1,340 @@
+//! 上游响应校验与 JSON 修复
+//!
+//! 上游偶尔返回被截断的响应体，或 tool_call 参数不是合法 JSON，客户端解析时
+//! 直接报错。`transform_response` 前先做一遍校验：响应体必须是对象，工具调用
+//! 参数（Chat Completions 的 `function.arguments`、Responses 的 `function_call`、
+//! Anthropic `tool_use.input`）必须能解析为 JSON。开启修复后尽力补全截断的
+//! 字符串与括号、去掉多余逗号和代码块标记；修复与无法修复的位置都记入报告，
+//! 随转换结果返回并在响应头中标记。
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::warn;
+
+/// 校验与修复配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseRepairConfig {
+    /// 校验响应体与工具调用参数
+    pub enabled: bool,
+    /// 尽力修复无效的 JSON（关闭时只报告）
+    pub repair: bool,
+}
+
+impl Default for ResponseRepairConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            repair: false,
+        }
+    }
+}
+
+/// 校验结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepairReport {
+    /// 已修复的位置（如 `choices[0].message.tool_calls[1].function.arguments`）
+    pub repaired: Vec<String>,
+    /// 仍然无效的位置
+    pub invalid: Vec<String>,
+}
+
+impl RepairReport {
+    pub fn is_empty(&self) -> bool {
+        self.repaired.is_empty() && self.invalid.is_empty()
+    }
+
+    /// 响应头
+    pub fn headers(&self) -> BTreeMap<String, String> {
+        let mut headers = BTreeMap::new();
+        if !self.repaired.is_empty() {
+            let count = self.repaired.len().to_string();
+            headers.insert("x-droid-response-repaired".to_string(), count);
+        }
+        if !self.invalid.is_empty() {
+            let count = self.invalid.len().to_string();
+            headers.insert("x-droid-response-invalid".to_string(), count);
+        }
+        headers
+    }
+}
+
+/// 去掉模型偶尔包裹的 Markdown 代码块标记
+fn strip_fences(text: &str) -> &str {
+    let trimmed = text.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+    let rest = rest.trim_start_matches(|c: char| c.is_ascii_alphanumeric());
+    rest.strip_suffix("```").unwrap_or(rest).trim()
+}
+
+/// 补全截断的 JSON：闭合未结束的字符串与括号，去掉对象 / 数组末尾多余的逗号
+fn close_truncated(text: &str) -> String {
+    let mut output = String::with_capacity(text.len() + 8);
+    let mut stack: Vec<char> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if in_string {
+            output.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => stack.push('}'),
+            '[' => stack.push(']'),
+            '}' | ']' => {
+                trim_trailing_comma(&mut output);
+                if stack.last() == Some(&c) {
+                    stack.pop();
+                }
+            }
+            _ => {}
+        }
+        output.push(c);
+    }
+
+    if in_string {
+        if escaped {
+            output.pop();
+        }
+        output.push('"');
+    }
+    while let Some(closer) = stack.pop() {
+        trim_trailing_comma(&mut output);
+        // 截断在键名或冒号之后时补上 null
+        if output.trim_end().ends_with(':') {
+            output.push_str(" null");
+        } else if closer == '}' && dangling_key(&output) {
+            output.push_str(": null");
+        }
+        output.push(closer);
+    }
+    output
+}
+
+fn trim_trailing_comma(output: &mut String) {
+    let trimmed = output.trim_end().len();
+    output.truncate(trimmed);
+    if output.ends_with(',') {
+        output.pop();
+    }
+}
+
+/// 对象中最后一项只有键名（`{"a": 1, "b"`）
+fn dangling_key(output: &str) -> bool {
+    let trimmed = output.trim_end();
+    if !trimmed.ends_with('"') {
+        return false;
+    }
+    let before = trimmed[..trimmed.len() - 1]
+        .rfind('"')
+        .map(|i| trimmed[..i].trim_end());
+    matches!(before.and_then(|b| b.chars().last()), Some('{') | Some(','))
+}
+
+/// 尽力把文本修复为合法 JSON，无法修复时返回 None
+pub fn repair_json(text: &str) -> Option<serde_json::Value> {
+    let stripped = strip_fences(text);
+    if let Ok(value) = serde_json::from_str(stripped) {
+        return Some(value);
+    }
+    serde_json::from_str(&close_truncated(stripped)).ok()
+}
+
+/// 校验（并按配置修复）一个 JSON 字符串字段；`keep_string` 时修复结果仍写回为字符串
+fn check_json_field(
+    field: &mut serde_json::Value,
+    path: String,
+    keep_string: bool,
+    config: &ResponseRepairConfig,
+    report: &mut RepairReport,
+) {
+    let Some(text) = field.as_str() else {
+        return;
+    };
+    if text.trim().is_empty() && keep_string {
+        // 无参数的工具调用可能给出空字符串
+        return;
+    }
+    if serde_json::from_str::<serde_json::Value>(text).is_ok() {
+        if !keep_string {
+            *field = serde_json::from_str(text).unwrap_or_default();
+        }
+        return;
+    }
+    match repair_json(text).filter(|_| config.repair) {
+        Some(value) => {
+            *field = match keep_string {
+                true => serde_json::json!(value.to_string()),
+                false => value,
+            };
+            report.repaired.push(path);
+        }
+        None => report.invalid.push(path),
+    }
+}
+
+/// 校验响应；被截断成字符串的响应体会先尝试解析
+pub fn validate(config: &ResponseRepairConfig, response: &mut serde_json::Value) -> RepairReport {
+    let mut report = RepairReport::default();
+    if !config.enabled {
+        return report;
+    }
+
+    if response.is_string() {
+        check_json_field(response, "body".to_string(), false, config, &mut report);
+    }
+    if !response.is_object() {
+        if report.invalid.is_empty() {
+            report.invalid.push("body".to_string());
+        }
+        warn!("上游响应体不是 JSON 对象");
+        return report;
+    }
+
+    if let Some(choices) = response.get_mut("choices").and_then(|c| c.as_array_mut()) {
+        for (i, choice) in choices.iter_mut().enumerate() {
+            let calls = choice
+                .pointer_mut("/message/tool_calls")
+                .and_then(|c| c.as_array_mut());
+            for (j, call) in calls.into_iter().flatten().enumerate() {
+                if let Some(arguments) = call.pointer_mut("/function/arguments") {
+                    let path = format!(
+                        "choices[{}].message.tool_calls[{}].function.arguments",
+                        i, j
+                    );
+                    check_json_field(arguments, path, true, config, &mut report);
+                }
+            }
+        }
+    }
+
+    if let Some(output) = response.get_mut("output").and_then(|o| o.as_array_mut()) {
+        for (i, item) in output.iter_mut().enumerate() {
+            if item["type"] != "function_call" {
+                continue;
+            }
+            if let Some(arguments) = item.get_mut("arguments") {
+                let path = format!("output[{}].arguments", i);
+                check_json_field(arguments, path, true, config, &mut report);
+            }
+        }
+    }
+
+    if let Some(content) = response.get_mut("content").and_then(|c| c.as_array_mut()) {
+        for (i, block) in content.iter_mut().enumerate() {
+            if block["type"] != "tool_use" {
+                continue;
+            }
+            // Anthropic 的 input 应为对象，个别情况下会以字符串返回
+            if let Some(input) = block.get_mut("input").filter(|input| input.is_string()) {
+                let path = format!("content[{}].input", i);
+                check_json_field(input, path, false, config, &mut report);
+            }
+        }
+    }
+
+    if !report.is_empty() {
+        warn!(
+            "上游响应校验: 已修复 {:?}，仍无效 {:?}",
+            report.repaired, report.invalid
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repairing() -> ResponseRepairConfig {
+        ResponseRepairConfig {
+            enabled: true,
+            repair: true,
+        }
+    }
+
+    #[test]
+    fn test_repair_json() {
+        assert_eq!(
+            repair_json("{\"a\": 1,}").unwrap(),
+            serde_json::json!({ "a": 1 })
+        );
+        assert_eq!(
+            repair_json("{\"path\": \"/tmp/a\", \"lines\": [1, 2").unwrap(),
+            serde_json::json!({ "path": "/tmp/a", "lines": [1, 2] })
+        );
+        assert_eq!(
+            repair_json("{\"text\": \"unfinished \\").unwrap(),
+            serde_json::json!({ "text": "unfinished " })
+        );
+        assert_eq!(
+            repair_json("{\"a\": 1, \"b\"").unwrap(),
+            serde_json::json!({ "a": 1, "b": null })
+        );
+        assert_eq!(
+            repair_json("{\"a\":").unwrap(),
+            serde_json::json!({ "a": null })
+        );
+        assert_eq!(
+            repair_json("```json\n{\"a\": [1]}\n```").unwrap(),
+            serde_json::json!({ "a": [1] })
+        );
+        assert!(repair_json("not json at all").is_none());
+    }
+
+    #[test]
+    fn test_validate_chat_tool_arguments() {
+        let mut response = serde_json::json!({
+            "choices": [{
+                "message": {
+                    "tool_calls": [
+                        { "function": { "name": "ok", "arguments": "{\"a\": 1}" } },
+                        { "function": { "name": "cut", "arguments": "{\"query\": \"rust" } },
+                        { "function": { "name": "none", "arguments": "" } },
+                    ]
+                }
+            }]
+        });
+        let report = validate(&ResponseRepairConfig::default(), &mut response.clone());
+        assert_eq!(
+            report.invalid,
+            vec!["choices[0].message.tool_calls[1].function.arguments"]
+        );
+        assert!(report.repaired.is_empty());
+
+        let report = validate(&repairing(), &mut response);
+        assert_eq!(report.repaired.len(), 1);
+        let call = &response["choices"][0]["message"]["tool_calls"][1];
+        assert_eq!(call["function"]["arguments"], "{\"query\":\"rust\"}");
+        assert_eq!(report.headers()["x-droid-response-repaired"], "1");
+    }
+
+    #[test]
+    fn test_validate_truncated_body_and_anthropic_input() {
+        let mut response = serde_json::json!("{\"content\": [{\"type\": \"text\", \"text\": \"hi");
+        let report = validate(&repairing(), &mut response);
+        assert_eq!(report.repaired, vec!["body"]);
+        assert_eq!(response["content"][0]["text"], "hi");
+
+        let mut response = serde_json::json!({
+            "content": [{ "type": "tool_use", "name": "read", "input": "{\"file\": \"a.rs\"" }]
+        });
+        let report = validate(&repairing(), &mut response);
+        assert_eq!(report.repaired, vec!["content[0].input"]);
+        assert_eq!(response["content"][0]["input"]["file"], "a.rs");
+
+        let mut response = serde_json::json!("garbage");
+        let report = validate(&repairing(), &mut response);
+        assert_eq!(report.invalid, vec!["body"]);
+    }
+}
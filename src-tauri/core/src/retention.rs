@@ -0,0 +1,99 @@
+//! 数据保留与清理
+//!
+//! 目前落盘的数据只有使用统计（`usage.jsonl`）。默认永久保留；设置
+//! `usage_days` 后后台任务定期删除过期记录。`purge_usage` 供用户手动清理，
+//! 清空全部记录必须显式传入 `all`；清理时会重写文件，顺带去除损坏的行。
+
+use crate::config::get_config;
+use crate::stats::{self, PruneResult};
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// 后台清理间隔（秒）
+const PRUNE_INTERVAL_SECS: u64 = 6 * 3600;
+
+/// 保留配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// 使用统计保留天数，0 表示永久保留
+    pub usage_days: u32,
+}
+
+/// 清理时要删除的记录：早于该天数，或全部（`all`）
+fn purge_cutoff(older_than_days: Option<u32>, all: bool) -> Result<Option<chrono::DateTime<Utc>>> {
+    match (older_than_days, all) {
+        (Some(days), false) => Ok(Some(Utc::now() - Duration::days(days as i64))),
+        (None, true) => Ok(None),
+        (Some(_), true) => anyhow::bail!("older_than_days 与 all 不能同时指定"),
+        (None, false) => anyhow::bail!("请指定 older_than_days，或传入 all: true 清空全部使用记录"),
+    }
+}
+
+/// 清理使用统计
+///
+/// 指定天数时删除早于该天数的记录；`all` 为 true 时删除全部记录。
+pub async fn purge_usage(older_than_days: Option<u32>, all: bool) -> Result<PruneResult> {
+    let cutoff = purge_cutoff(older_than_days, all)?;
+    let result = stats::prune(cutoff).await?;
+    info!(
+        "已清理 {} 条使用记录，保留 {} 条",
+        result.removed, result.kept
+    );
+    Ok(result)
+}
+
+/// 后台任务：按保留天数定期清理
+pub async fn run_pruner() {
+    loop {
+        let days = get_config().retention.usage_days;
+        if days > 0 {
+            if let Err(e) = purge_usage(Some(days), false).await {
+                warn!("清理过期使用记录失败: {}", e);
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(PRUNE_INTERVAL_SECS)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::retain_since;
+
+    #[test]
+    fn test_retain_since() {
+        let now = Utc::now();
+        let line = |ts: chrono::DateTime<Utc>| {
+            format!(
+                r#"{{"timestamp":"{}","credential_id":"c","success":true}}"#,
+                ts.to_rfc3339()
+            )
+        };
+        let content = format!(
+            "{}\n{}\nbroken line\n",
+            line(now - Duration::days(100)),
+            line(now)
+        );
+
+        let (kept, result) = retain_since(&content, Some(now - Duration::days(90)));
+        assert_eq!(result.kept, 1);
+        assert_eq!(result.removed, 2);
+        assert_eq!(kept.lines().count(), 1);
+
+        let (kept, result) = retain_since(&content, None);
+        assert!(kept.is_empty());
+        assert_eq!(result.removed, 3);
+    }
+
+    #[test]
+    fn test_full_purge_requires_all() {
+        assert!(purge_cutoff(None, false).is_err());
+        assert!(purge_cutoff(Some(30), true).is_err());
+        assert!(purge_cutoff(None, true).unwrap().is_none());
+        assert!(purge_cutoff(Some(30), false).unwrap().is_some());
+        assert_eq!(RetentionConfig::default().usage_days, 0);
+    }
+}
@@ -0,0 +1,325 @@
+//! 单个逻辑请求的重试预算
+//!
+//! 宿主在多个凭证、端点间重试同一个客户端请求时，`acquire_credential`
+//! 带上同一个 `request_id`。这里记录每次尝试（凭证、端点、状态码、间隔），
+//! 尝试次数或总耗时超出预算后不再分配凭证，并在错误中附上完整的尝试记录，
+//! 让用户看清一个请求为什么用了 90 秒才失败。请求成功后记录即被清除。
+
+use crate::credentials::EndpointType;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// 重试预算配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryBudgetConfig {
+    pub enabled: bool,
+    /// 单个请求的最大尝试次数（含首次）
+    pub max_attempts: u32,
+    /// 单个请求从首次尝试起的最长总耗时（毫秒）
+    pub max_elapsed_ms: u64,
+}
+
+impl Default for RetryBudgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: 5,
+            max_elapsed_ms: 120_000,
+        }
+    }
+}
+
+/// 单次尝试
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attempt {
+    pub credential_id: String,
+    pub endpoint_type: EndpointType,
+    pub started_at: String,
+    /// 距上一次尝试结束的等待时间
+    pub delay_ms: u64,
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    #[serde(default)]
+    pub status_code: Option<u16>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// 预算耗尽
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryBudgetExceeded {
+    pub request_id: String,
+    /// attempts / elapsed
+    pub reason: String,
+    pub elapsed_ms: u64,
+    pub attempts: Vec<Attempt>,
+    pub message: String,
+}
+
+impl std::fmt::Display for RetryBudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RetryBudgetExceeded {}
+
+#[derive(Debug)]
+struct Trail {
+    started_at: DateTime<Utc>,
+    last_finished_at: Option<DateTime<Utc>>,
+    attempts: Vec<Attempt>,
+    /// 尝试对应的租约
+    leases: Vec<String>,
+}
+
+/// 按请求 ID 记录尝试
+#[derive(Debug, Default)]
+pub struct AttemptLog {
+    trails: HashMap<String, Trail>,
+    lease_requests: HashMap<String, String>,
+}
+
+/// 记录的保留时间（宿主放弃请求后不会再上报）
+const TRAIL_TTL_MINUTES: i64 = 30;
+
+impl AttemptLog {
+    /// 检查预算是否还允许一次新尝试
+    pub fn check(
+        &mut self,
+        config: &RetryBudgetConfig,
+        request_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), RetryBudgetExceeded> {
+        self.prune(now);
+        let Some(trail) = self.trails.get(request_id).filter(|_| config.enabled) else {
+            return Ok(());
+        };
+        let elapsed_ms = (now - trail.started_at).num_milliseconds().max(0) as u64;
+        let attempts = trail.attempts.len() as u32;
+        let reason = if attempts >= config.max_attempts {
+            format!("已尝试 {} 次（上限 {}）", attempts, config.max_attempts)
+        } else if elapsed_ms >= config.max_elapsed_ms {
+            format!(
+                "已耗时 {} 毫秒（上限 {}）",
+                elapsed_ms, config.max_elapsed_ms
+            )
+        } else {
+            return Ok(());
+        };
+        let summary: Vec<String> = trail
+            .attempts
+            .iter()
+            .map(|a| {
+                let outcome = match (a.status_code, &a.error) {
+                    (Some(status), _) => status.to_string(),
+                    (None, Some(error)) => error.clone(),
+                    (None, None) => "进行中".to_string(),
+                };
+                let target = format!("{}/{}", a.credential_id, a.endpoint_type);
+                format!("{} → {}（等待 {}ms）", target, outcome, a.delay_ms)
+            })
+            .collect();
+        let kind = if attempts >= config.max_attempts {
+            "attempts"
+        } else {
+            "elapsed"
+        };
+        Err(RetryBudgetExceeded {
+            request_id: request_id.to_string(),
+            reason: kind.to_string(),
+            elapsed_ms,
+            attempts: trail.attempts.clone(),
+            message: format!(
+                "重试预算已用完：{}；尝试记录: {}",
+                reason,
+                summary.join("; ")
+            ),
+        })
+    }
+
+    /// 记录一次新尝试
+    pub fn start(
+        &mut self,
+        request_id: &str,
+        lease_id: &str,
+        credential_id: &str,
+        endpoint_type: EndpointType,
+        now: DateTime<Utc>,
+    ) {
+        let trail = self
+            .trails
+            .entry(request_id.to_string())
+            .or_insert_with(|| Trail {
+                started_at: now,
+                last_finished_at: None,
+                attempts: Vec::new(),
+                leases: Vec::new(),
+            });
+        let delay_ms = trail.last_finished_at.map_or(0, |finished| {
+            (now - finished).num_milliseconds().max(0) as u64
+        });
+        trail.attempts.push(Attempt {
+            credential_id: credential_id.to_string(),
+            endpoint_type,
+            started_at: now.to_rfc3339(),
+            delay_ms,
+            latency_ms: None,
+            status_code: None,
+            error: None,
+        });
+        trail.leases.push(lease_id.to_string());
+        self.lease_requests
+            .insert(lease_id.to_string(), request_id.to_string());
+    }
+
+    /// 记录尝试结果；成功时清除整个请求的记录
+    pub fn finish(
+        &mut self,
+        lease_id: &str,
+        success: bool,
+        status_code: Option<u16>,
+        error: Option<String>,
+        now: DateTime<Utc>,
+    ) {
+        let Some(request_id) = self.lease_requests.remove(lease_id) else {
+            return;
+        };
+        if success {
+            if let Some(trail) = self.trails.remove(&request_id) {
+                for lease in trail.leases {
+                    self.lease_requests.remove(&lease);
+                }
+            }
+            return;
+        }
+        let Some(trail) = self.trails.get_mut(&request_id) else {
+            return;
+        };
+        if let Some(index) = trail.leases.iter().position(|l| l == lease_id) {
+            let attempt = &mut trail.attempts[index];
+            let started = attempt.started_at.parse::<DateTime<Utc>>().unwrap_or(now);
+            attempt.latency_ms = Some((now - started).num_milliseconds().max(0) as u64);
+            attempt.status_code = status_code;
+            attempt.error = error;
+        }
+        trail.last_finished_at = Some(now);
+    }
+
+    /// 请求的尝试记录
+    pub fn attempts(&self, request_id: &str) -> Vec<Attempt> {
+        self.trails
+            .get(request_id)
+            .map(|t| t.attempts.clone())
+            .unwrap_or_default()
+    }
+
+    fn prune(&mut self, now: DateTime<Utc>) {
+        let ttl = Duration::minutes(TRAIL_TTL_MINUTES);
+        let expired: Vec<String> = self
+            .trails
+            .iter()
+            .filter(|(_, t)| now - t.started_at > ttl)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            if let Some(trail) = self.trails.remove(&id) {
+                for lease in trail.leases {
+                    self.lease_requests.remove(&lease);
+                }
+            }
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref LOG: Mutex<AttemptLog> = Mutex::new(AttemptLog::default());
+}
+
+/// 按当前配置检查预算
+pub fn check(request_id: &str) -> Result<(), RetryBudgetExceeded> {
+    let config = crate::config::get_config().retry_budget;
+    LOG.lock().unwrap().check(&config, request_id, Utc::now())
+}
+
+/// 记录一次新尝试
+pub fn start(request_id: &str, lease_id: &str, credential_id: &str, endpoint_type: EndpointType) {
+    LOG.lock().unwrap().start(
+        request_id,
+        lease_id,
+        credential_id,
+        endpoint_type,
+        Utc::now(),
+    );
+}
+
+/// 记录尝试结果
+pub fn finish(lease_id: &str, success: bool, status_code: Option<u16>, error: Option<String>) {
+    LOG.lock()
+        .unwrap()
+        .finish(lease_id, success, status_code, error, Utc::now());
+}
+
+/// 请求的尝试记录
+pub fn attempts(request_id: &str) -> Vec<Attempt> {
+    LOG.lock().unwrap().attempts(request_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attempt_budget() {
+        let config = RetryBudgetConfig {
+            max_attempts: 2,
+            ..Default::default()
+        };
+        let mut log = AttemptLog::default();
+        let now = Utc::now();
+        assert!(log.check(&config, "req", now).is_ok());
+
+        log.start("req", "lease-1", "cred-a", EndpointType::Anthropic, now);
+        log.finish(
+            "lease-1",
+            false,
+            Some(429),
+            None,
+            now + Duration::seconds(1),
+        );
+        let later = now + Duration::seconds(3);
+        assert!(log.check(&config, "req", later).is_ok());
+        log.start("req", "lease-2", "cred-b", EndpointType::Comm, later);
+        log.finish("lease-2", false, Some(503), None, later);
+
+        let exceeded = log.check(&config, "req", later).unwrap_err();
+        assert_eq!(exceeded.reason, "attempts");
+        assert_eq!(exceeded.attempts.len(), 2);
+        assert_eq!(exceeded.attempts[0].latency_ms, Some(1_000));
+        assert_eq!(exceeded.attempts[1].delay_ms, 2_000);
+        assert!(exceeded.message.contains("cred-b/comm → 503"));
+    }
+
+    #[test]
+    fn test_elapsed_budget_and_success_clears() {
+        let config = RetryBudgetConfig {
+            max_elapsed_ms: 10_000,
+            ..Default::default()
+        };
+        let mut log = AttemptLog::default();
+        let now = Utc::now();
+        log.start("req", "lease-1", "cred-a", EndpointType::Anthropic, now);
+        log.finish("lease-1", false, Some(500), None, now);
+        let exceeded = log
+            .check(&config, "req", now + Duration::seconds(11))
+            .unwrap_err();
+        assert_eq!(exceeded.reason, "elapsed");
+
+        log.start("other", "lease-2", "cred-a", EndpointType::Anthropic, now);
+        log.finish("lease-2", true, None, None, now);
+        assert!(log.attempts("other").is_empty());
+    }
+}
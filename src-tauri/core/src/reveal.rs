@@ -0,0 +1,185 @@
+//! 密钥查看
+//!
+//! 用户需要把 API Key 或 Refresh Token 复制出来时使用。分两步：
+//! `request_secret_reveal` 签发一次性确认令牌（短时有效、绑定凭证与密钥），
+//! UI 弹窗确认后再携带令牌调用 `reveal_secret` 取回明文。
+//! 开启 `require_os_auth` 时，宿主需先完成系统身份验证并在调用时声明。
+//!
+//! 插件运行在宿主的子进程中，无法自行弹出或核验系统身份验证：
+//! `os_auth_confirmed` 只是宿主的声明，这一步的安全性完全由宿主保证，
+//! 插件只负责拒绝未声明的调用。明文密钥只出现在 `reveal_secret` 的响应中，
+//! 不写入日志与事件。
+
+use crate::auth::key_ring;
+use crate::credentials::DroidCredentials;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// 表示 Refresh Token 的密钥 ID
+pub const REFRESH_TOKEN_KEY_ID: &str = "refresh_token";
+
+/// 密钥查看配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RevealConfig {
+    pub enabled: bool,
+    /// 确认令牌有效期（秒）
+    pub confirm_ttl_secs: u64,
+    /// 要求宿主先完成系统身份验证（Touch ID / Windows Hello 等）；
+    /// 由宿主执行并声明，插件无法核验
+    pub require_os_auth: bool,
+}
+
+impl Default for RevealConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            confirm_ttl_secs: 60,
+            require_os_auth: false,
+        }
+    }
+}
+
+/// 签发的确认令牌
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevealChallenge {
+    pub confirmation_token: String,
+    pub expires_at: String,
+    /// 宿主是否需要先进行系统身份验证（由宿主执行）
+    pub require_os_auth: bool,
+}
+
+struct PendingReveal {
+    credential_id: String,
+    key_id: String,
+    expires_at: DateTime<Utc>,
+}
+
+lazy_static::lazy_static! {
+    static ref PENDING: Mutex<HashMap<String, PendingReveal>> = Mutex::new(HashMap::new());
+}
+
+/// 签发确认令牌
+pub fn issue(config: &RevealConfig, credential_id: &str, key_id: &str) -> Result<RevealChallenge> {
+    if !config.enabled {
+        anyhow::bail!("密钥查看已关闭");
+    }
+    let token = hex::encode(rand::thread_rng().gen::<[u8; 16]>());
+    let expires_at = Utc::now() + Duration::seconds(config.confirm_ttl_secs as i64);
+
+    let mut pending = PENDING.lock().unwrap();
+    pending.retain(|_, p| p.expires_at > Utc::now());
+    pending.insert(
+        token.clone(),
+        PendingReveal {
+            credential_id: credential_id.to_string(),
+            key_id: key_id.to_string(),
+            expires_at,
+        },
+    );
+    Ok(RevealChallenge {
+        confirmation_token: token,
+        expires_at: expires_at.to_rfc3339(),
+        require_os_auth: config.require_os_auth,
+    })
+}
+
+/// 校验并消耗确认令牌（无论成功与否，令牌都只能使用一次）
+///
+/// `os_auth_confirmed` 由宿主在完成系统身份验证后传入，这里只检查声明。
+pub fn confirm(
+    config: &RevealConfig,
+    credential_id: &str,
+    key_id: &str,
+    token: &str,
+    os_auth_confirmed: bool,
+) -> Result<()> {
+    if !config.enabled {
+        anyhow::bail!("密钥查看已关闭");
+    }
+    let pending = PENDING
+        .lock()
+        .unwrap()
+        .remove(token)
+        .ok_or_else(|| anyhow::anyhow!("确认令牌无效或已使用"))?;
+    if pending.expires_at <= Utc::now() {
+        anyhow::bail!("确认令牌已过期");
+    }
+    if pending.credential_id != credential_id || pending.key_id != key_id {
+        anyhow::bail!("确认令牌与请求的密钥不匹配");
+    }
+    if config.require_os_auth && !os_auth_confirmed {
+        anyhow::bail!("需要先完成系统身份验证");
+    }
+    Ok(())
+}
+
+/// 取出凭证中的明文密钥
+pub fn extract(credential: &DroidCredentials, key_id: &str) -> Result<String> {
+    if key_id == REFRESH_TOKEN_KEY_ID {
+        return credential
+            .refresh_token
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("凭证没有 Refresh Token"));
+    }
+    let entry = credential
+        .api_keys
+        .iter()
+        .find(|k| k.id == key_id)
+        .ok_or_else(|| anyhow::anyhow!("API Key 不存在: {}", key_id))?;
+    key_ring::decrypt(&entry.encrypted_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirmation_token_is_single_use_and_bound() {
+        let config = RevealConfig::default();
+        let challenge = issue(&config, "cred-1", "key-1").unwrap();
+        let token = &challenge.confirmation_token;
+
+        assert!(confirm(&config, "cred-1", "key-2", token, false).is_err());
+        // 不匹配时令牌同样被消耗
+        assert!(confirm(&config, "cred-1", "key-1", token, false).is_err());
+
+        let challenge = issue(&config, "cred-1", "key-1").unwrap();
+        let token = &challenge.confirmation_token;
+        assert!(confirm(&config, "cred-1", "key-1", token, false).is_ok());
+        assert!(confirm(&config, "cred-1", "key-1", token, false).is_err());
+    }
+
+    #[test]
+    fn test_require_os_auth() {
+        let config = RevealConfig {
+            require_os_auth: true,
+            ..Default::default()
+        };
+        let challenge = issue(&config, "cred-1", REFRESH_TOKEN_KEY_ID).unwrap();
+        assert!(challenge.require_os_auth);
+        let token = &challenge.confirmation_token;
+        assert!(confirm(&config, "cred-1", REFRESH_TOKEN_KEY_ID, token, false).is_err());
+
+        let token = issue(&config, "cred-1", REFRESH_TOKEN_KEY_ID)
+            .unwrap()
+            .confirmation_token;
+        assert!(confirm(&config, "cred-1", REFRESH_TOKEN_KEY_ID, &token, true).is_ok());
+    }
+
+    #[test]
+    fn test_expired_token() {
+        let config = RevealConfig {
+            confirm_ttl_secs: 0,
+            ..Default::default()
+        };
+        let token = issue(&config, "cred-1", "key-1")
+            .unwrap()
+            .confirmation_token;
+        assert!(confirm(&config, "cred-1", "key-1", &token, false).is_err());
+    }
+}
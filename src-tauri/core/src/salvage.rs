@@ -0,0 +1,107 @@
+//! 流中断时保留部分响应
+//!
+//! 长输出在接近结束时断流，整段丢弃代价很高。开启后宿主在流出错时把已收到的
+//! 事件交给 `salvage_stream`，输出量达到阈值则拼成一个带 `incomplete: true`
+//! 的完整响应（含已产生的用量），同时把截断记入凭证错误记录并发出
+//! `stream_truncated` 事件。支持 Anthropic Messages 与 Chat Completions 流。
+
+use crate::reassembly::StreamAssembler;
+use serde::{Deserialize, Serialize};
+
+/// 部分响应保留配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SalvageConfig {
+    pub enabled: bool,
+    /// 输出 Token 少于该值时不保留
+    pub min_output_tokens: u64,
+}
+
+impl Default for SalvageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_output_tokens: 200,
+        }
+    }
+}
+
+/// 按配置拼出部分响应，功能关闭或输出量不足时返回 None
+pub fn salvage(
+    events: &[serde_json::Value],
+    config: &SalvageConfig,
+) -> Option<(serde_json::Value, u64)> {
+    if !config.enabled {
+        return None;
+    }
+    let mut partial = StreamAssembler::default();
+    for event in events {
+        partial.push(event);
+    }
+    let output_tokens = partial.output_tokens();
+    (output_tokens >= config.min_output_tokens)
+        .then(|| (partial.into_response(true), output_tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anthropic_events() -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({
+                "type": "message_start",
+                "message": { "id": "msg_1", "model": "claude", "usage": { "input_tokens": 12 } }
+            }),
+            serde_json::json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": { "type": "text", "text": "" }
+            }),
+            serde_json::json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": { "type": "text_delta", "text": "Hello " }
+            }),
+            serde_json::json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": { "type": "text_delta", "text": "world" }
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_salvage_anthropic_stream() {
+        let config = SalvageConfig {
+            enabled: true,
+            min_output_tokens: 1,
+        };
+        let (response, output_tokens) = salvage(&anthropic_events(), &config).unwrap();
+        assert_eq!(response["content"][0]["text"], "Hello world");
+        assert_eq!(response["incomplete"], true);
+        assert_eq!(response["usage"]["input_tokens"], 12);
+        assert!(output_tokens > 0);
+
+        let strict = SalvageConfig {
+            min_output_tokens: 1_000,
+            ..config
+        };
+        assert!(salvage(&anthropic_events(), &strict).is_none());
+    }
+
+    #[test]
+    fn test_salvage_chat_stream() {
+        let events = vec![
+            serde_json::json!({ "id": "c1", "choices": [{ "delta": { "content": "Hi " } }] }),
+            serde_json::json!({ "id": "c1", "choices": [{ "delta": { "content": "there" } }] }),
+        ];
+        let config = SalvageConfig {
+            enabled: true,
+            min_output_tokens: 1,
+        };
+        let (response, _) = salvage(&events, &config).unwrap();
+        assert_eq!(response["choices"][0]["message"]["content"], "Hi there");
+        assert_eq!(response["incomplete"], true);
+    }
+}
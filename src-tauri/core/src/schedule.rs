@@ -0,0 +1,142 @@
+//! 凭证启用时段
+//!
+//! 为凭证设置按本地时间的启用时段（如工作账号只在周一至周五 9:00–18:00 使用），
+//! 时段外选择器跳过该凭证，个人与公司额度按时间清楚分开。
+//! 结束时间早于开始时间表示跨午夜（如 22:00–06:00），星期按开始时所在的日期计算。
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, TimeZone, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// 单个启用时段
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveWindow {
+    /// 生效的星期（如 `["Mon", "Tue"]`），为空表示每天
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+    /// 开始时间（HH:MM，含）
+    pub start: String,
+    /// 结束时间（HH:MM，不含）
+    pub end: String,
+}
+
+/// 凭证的启用时段，满足任一时段即可使用
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveSchedule {
+    pub windows: Vec<ActiveWindow>,
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| anyhow::anyhow!("时间格式应为 HH:MM: {}", value))
+}
+
+impl ActiveWindow {
+    fn contains<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> bool {
+        let (Ok(start), Ok(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            return false;
+        };
+        let time = now.time();
+        let on_day = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        if start <= end {
+            on_day(now.weekday()) && time >= start && time < end
+        } else {
+            // 跨午夜：午夜后的部分属于前一天的时段
+            (on_day(now.weekday()) && time >= start)
+                || (on_day((now.date_naive() - Duration::days(1)).weekday()) && time < end)
+        }
+    }
+}
+
+impl ActiveSchedule {
+    /// 检查时间格式
+    pub fn validate(&self) -> Result<()> {
+        if self.windows.is_empty() {
+            anyhow::bail!("至少需要一个时段，不限制时段请清除设置");
+        }
+        for window in &self.windows {
+            let (start, end) = (parse_time(&window.start)?, parse_time(&window.end)?);
+            if start == end {
+                anyhow::bail!("时段开始与结束时间相同: {}", window.start);
+            }
+        }
+        Ok(())
+    }
+
+    /// 指定时间是否在启用时段内
+    pub fn is_active_at<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> bool {
+        self.windows.iter().any(|window| window.contains(now))
+    }
+
+    /// 当前本地时间是否在启用时段内
+    pub fn is_active(&self) -> bool {
+        self.is_active_at(&Local::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        // 2025-10-06 是周一
+        DateTime::parse_from_rfc3339(&format!("2025-10-{}:00Z", value))
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_workday_window() {
+        let schedule: ActiveSchedule = serde_json::from_value(serde_json::json!({
+            "windows": [{
+                "days": ["Mon", "Tue", "Wed", "Thu", "Fri"],
+                "start": "09:00",
+                "end": "18:00",
+            }]
+        }))
+        .unwrap();
+        assert!(schedule.validate().is_ok());
+        assert!(schedule.is_active_at(&at("06T09:00")));
+        assert!(schedule.is_active_at(&at("06T17:59")));
+        assert!(!schedule.is_active_at(&at("06T18:00")));
+        assert!(!schedule.is_active_at(&at("06T08:30")));
+        // 周六
+        assert!(!schedule.is_active_at(&at("11T10:00")));
+    }
+
+    #[test]
+    fn test_overnight_window() {
+        let schedule = ActiveSchedule {
+            windows: vec![ActiveWindow {
+                days: vec![Weekday::Fri],
+                start: "22:00".to_string(),
+                end: "06:00".to_string(),
+            }],
+        };
+        assert!(schedule.is_active_at(&at("10T23:00")));
+        // 周六凌晨属于周五的时段
+        assert!(schedule.is_active_at(&at("11T05:00")));
+        assert!(!schedule.is_active_at(&at("11T23:00")));
+        assert!(!schedule.is_active_at(&at("10T05:00")));
+    }
+
+    #[test]
+    fn test_validate() {
+        let window = |start: &str, end: &str| ActiveSchedule {
+            windows: vec![ActiveWindow {
+                days: Vec::new(),
+                start: start.to_string(),
+                end: end.to_string(),
+            }],
+        };
+        assert!(window("9:00", "18:00").validate().is_ok());
+        assert!(window("25:00", "18:00").validate().is_err());
+        assert!(window("09:00", "09:00").validate().is_err());
+        assert!(ActiveSchedule {
+            windows: Vec::new()
+        }
+        .validate()
+        .is_err());
+    }
+}
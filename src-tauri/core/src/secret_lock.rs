@@ -0,0 +1,252 @@
+//! 加密密钥缺失时的锁定状态
+//!
+//! 钥匙串不可用、`DROID_ENCRYPTION_KEY` 未设置或主密钥受应用锁保护尚未解锁时，
+//! 加密保存的 Token / API Key 无法解开。这类凭证照常载入（名称、组织、用量等
+//! 元数据可见），但标记为锁定：不参与分配、不刷新，并且只发出一次
+//! `unlock_required` 事件说明如何解锁，而不是每个请求都报解密失败。
+//! 解锁（应用锁口令、恢复短语、补充旧密钥）后由 `provider` 重新解密。
+
+use crate::auth::encryption::hash_api_key;
+use crate::auth::master_key::{self, MasterKeyLocked};
+use crate::auth::{key_ring, token_seal};
+use crate::credentials::DroidCredentials;
+use crate::events;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// 锁定的凭证
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedCredential {
+    pub credential_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub reason: String,
+    pub since: DateTime<Utc>,
+}
+
+/// 解锁方式
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlockAction {
+    /// 对应的 JSON-RPC 方法，需要重启进程的方式为空
+    #[serde(default)]
+    pub method: Option<String>,
+    pub hint: String,
+}
+
+/// 锁定状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlockStatus {
+    pub locked: bool,
+    /// 主密钥受应用锁保护且尚未解锁
+    pub master_key_locked: bool,
+    pub credentials: Vec<LockedCredential>,
+    pub actions: Vec<UnlockAction>,
+}
+
+lazy_static::lazy_static! {
+    static ref LOCKED: Mutex<BTreeMap<String, LockedCredential>> = Mutex::new(BTreeMap::new());
+}
+
+/// 本轮锁定已通知过（全部解锁后重置）
+static NOTIFIED: AtomicBool = AtomicBool::new(false);
+
+fn reason(error: &anyhow::Error) -> String {
+    if error.chain().any(|e| e.is::<MasterKeyLocked>()) {
+        "主密钥受应用锁保护，尚未解锁".to_string()
+    } else {
+        format!("加密密钥不可用: {:#}", error)
+    }
+}
+
+/// 解开凭证中加密的机密，无法解开时返回原因（机密保持加密状态）
+///
+/// 以密文传入的 API Key 在解开后补上哈希。
+pub fn open(credential: &mut DroidCredentials) -> Option<String> {
+    if let Err(e) = token_seal::open(credential) {
+        return Some(reason(&e));
+    }
+    let ring = match key_ring::current() {
+        Ok(ring) => ring,
+        // 没有任何加密内容时不需要密钥
+        Err(_) if credential.api_keys.is_empty() && credential.previous_refresh_token.is_none() => {
+            return None
+        }
+        Err(e) => return Some(reason(&e)),
+    };
+    for entry in credential.api_keys.iter_mut() {
+        match ring.decrypt(&entry.encrypted_key) {
+            Ok(key) if entry.hash.is_empty() && !key.is_empty() => entry.hash = hash_api_key(&key),
+            Ok(_) => {}
+            Err(e) => return Some(reason(&e)),
+        }
+    }
+    if let Some(previous) = credential.previous_refresh_token.as_deref() {
+        if let Err(e) = ring.decrypt(previous) {
+            return Some(reason(&e));
+        }
+    }
+    None
+}
+
+/// 解锁方式（按当前状态）
+pub fn actions() -> Vec<UnlockAction> {
+    let action = |method: Option<&str>, hint: &str| UnlockAction {
+        method: method.map(str::to_string),
+        hint: hint.to_string(),
+    };
+    if master_key::is_locked() {
+        return vec![action(Some("unlock_app"), "输入应用锁口令解锁")];
+    }
+    vec![
+        action(None, "设置 DROID_ENCRYPTION_KEY 为原来的密钥后重启"),
+        action(Some("recover_master_key"), "用 24 词恢复短语恢复主密钥"),
+        action(Some("add_encryption_key"), "补充加密这些凭证时使用的旧密钥"),
+    ]
+}
+
+/// 标记凭证为锁定，本轮第一次锁定时发出 `unlock_required` 事件
+pub fn mark(credential_id: &str, credential: &DroidCredentials, reason: String) {
+    warn!("凭证 {} 的机密无法解密，已锁定: {}", credential_id, reason);
+    let entry = LockedCredential {
+        credential_id: credential_id.to_string(),
+        name: credential.name.clone(),
+        reason: reason.clone(),
+        since: Utc::now(),
+    };
+    LOCKED
+        .lock()
+        .unwrap()
+        .insert(credential_id.to_string(), entry);
+    if NOTIFIED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    events::emit(
+        "unlock_required",
+        format!(
+            "加密密钥不可用，凭证已以锁定状态载入，解锁后才能使用: {}",
+            reason
+        ),
+        serde_json::json!({ "reason": reason, "actions": actions() }),
+    );
+}
+
+/// 凭证是否锁定
+pub fn is_locked(credential_id: &str) -> bool {
+    LOCKED.lock().unwrap().contains_key(credential_id)
+}
+
+/// 当前锁定的凭证 ID
+pub fn locked_ids() -> Vec<String> {
+    LOCKED.lock().unwrap().keys().cloned().collect()
+}
+
+/// 移除锁定记录（凭证已解锁或已删除）
+pub fn forget(credential_id: &str) {
+    let mut locked = LOCKED.lock().unwrap();
+    locked.remove(credential_id);
+    if locked.is_empty() {
+        NOTIFIED.store(false, Ordering::SeqCst);
+    }
+}
+
+/// 记录一批凭证已解锁
+pub fn unlocked(credential_ids: &[String]) {
+    if credential_ids.is_empty() {
+        return;
+    }
+    for id in credential_ids {
+        forget(id);
+    }
+    let remaining = LOCKED.lock().unwrap().len();
+    info!(
+        "{} 个凭证已解锁，仍锁定 {} 个",
+        credential_ids.len(),
+        remaining
+    );
+    events::emit(
+        "credentials_unlocked",
+        format!("{} 个凭证已解锁", credential_ids.len()),
+        serde_json::json!({ "credential_ids": credential_ids, "remaining": remaining }),
+    );
+}
+
+/// 当前锁定状态
+pub fn status() -> UnlockStatus {
+    let credentials: Vec<_> = LOCKED.lock().unwrap().values().cloned().collect();
+    UnlockStatus {
+        locked: !credentials.is_empty(),
+        master_key_locked: master_key::is_locked(),
+        actions: if credentials.is_empty() {
+            Vec::new()
+        } else {
+            actions()
+        },
+        credentials,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_reports_undecryptable_secrets() {
+        let mut credential = DroidCredentials {
+            refresh_token: Some(token_seal::seal_value("rt_live").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(open(&mut credential), None);
+        assert_eq!(credential.refresh_token.as_deref(), Some("rt_live"));
+
+        // 由其他主密钥加密
+        let other = key_ring::KeyRing::new("some-other-master-key");
+        let sealed = format!(
+            "{}{}",
+            token_seal::SEALED_PREFIX,
+            other.encrypt("rt").unwrap()
+        );
+        let mut credential = DroidCredentials {
+            refresh_token: Some(sealed.clone()),
+            ..Default::default()
+        };
+        let reason = open(&mut credential).unwrap();
+        assert!(reason.starts_with("加密密钥不可用"));
+        assert_eq!(credential.refresh_token.as_deref(), Some(sealed.as_str()));
+    }
+
+    #[test]
+    fn test_open_fills_sealed_api_key_hash() {
+        let mut credential = DroidCredentials {
+            api_keys: vec![crate::credentials::ApiKeyEntry {
+                encrypted_key: key_ring::encrypt("fk-test").unwrap(),
+                hash: String::new(),
+                id: "k1".to_string(),
+                created_at: String::new(),
+                last_used_at: None,
+                usage_count: 0,
+                status: "active".to_string(),
+                error_message: None,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(open(&mut credential), None);
+        assert_eq!(credential.api_keys[0].hash, hash_api_key("fk-test"));
+    }
+
+    #[test]
+    fn test_mark_and_forget() {
+        let credential = DroidCredentials::default();
+        mark("locked-cred", &credential, "测试".to_string());
+        assert!(is_locked("locked-cred"));
+        assert!(status()
+            .credentials
+            .iter()
+            .any(|c| c.credential_id == "locked-cred"));
+        unlocked(&["locked-cred".to_string()]);
+        assert!(!is_locked("locked-cred"));
+    }
+}
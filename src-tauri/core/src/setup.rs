@@ -0,0 +1,276 @@
+//! 首次运行向导
+//!
+//! 将引导流程拆为独立步骤：检测 factory-cli、导入凭证或发起 OAuth 登录、验证、
+//! 选择默认端点、发送测试请求。每一步都返回结构化结果，前端据此构建向导界面。
+//!
+//! OAuth 登录与重新登录共用 PKCE 流程：`start_oauth` 返回登录链接，宿主收到
+//! 回调后调用 `complete_relogin`，得到新凭证 ID 后继续验证。
+
+use crate::config::get_config;
+use crate::credentials::EndpointType;
+use crate::probe;
+use crate::provider;
+use crate::relogin;
+use crate::user_agent::{detected_cli_version, FACTORY_CLI_BINARIES};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// factory-cli 配置目录（位于用户主目录下）
+const FACTORY_CLI_DIR: &str = ".factory";
+/// factory-cli 保存登录信息的文件
+const FACTORY_CLI_AUTH_FILE: &str = "auth.json";
+
+/// 向导步骤
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SetupStep {
+    DetectFactoryCli,
+    ImportCredential,
+    #[serde(rename = "start_oauth")]
+    StartOAuth,
+    Validate,
+    SelectEndpoint,
+    TestRequest,
+}
+
+/// 步骤状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Ok,
+    /// 可继续，但需要提示用户
+    Warning,
+    Failed,
+}
+
+/// 步骤结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepResult {
+    pub step: SetupStep,
+    pub status: StepStatus,
+    pub message: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+impl StepResult {
+    fn new(step: SetupStep, status: StepStatus, message: impl Into<String>) -> Self {
+        Self {
+            step,
+            status,
+            message: message.into(),
+            data: serde_json::Value::Null,
+        }
+    }
+
+    fn with_data(mut self, data: serde_json::Value) -> Self {
+        self.data = data;
+        self
+    }
+}
+
+/// 在 PATH 中查找可执行文件
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .flat_map(|dir| {
+            let candidates = [dir.join(name), dir.join(format!("{}.exe", name))];
+            candidates.into_iter()
+        })
+        .find(|path| path.is_file())
+}
+
+fn factory_auth_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(FACTORY_CLI_DIR).join(FACTORY_CLI_AUTH_FILE))
+}
+
+/// 从 factory-cli 登录文件中读取 Token（兼容 snake_case 与 camelCase）
+pub fn parse_factory_auth(content: &str) -> Option<serde_json::Value> {
+    let auth: serde_json::Value = serde_json::from_str(content).ok()?;
+    let field = |snake: &str, camel: &str| {
+        auth.get(snake)
+            .or_else(|| auth.get(camel))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+
+    let access_token = field("access_token", "accessToken");
+    let refresh_token = field("refresh_token", "refreshToken");
+    if access_token.is_none() && refresh_token.is_none() {
+        return None;
+    }
+    Some(serde_json::json!({
+        "name": "factory-cli",
+        "access_token": access_token,
+        "refresh_token": refresh_token,
+        "organization_id": field("organization_id", "organizationId"),
+    }))
+}
+
+/// 步骤 1：检测本机 factory-cli 安装与登录状态
+pub fn detect_factory_cli() -> StepResult {
+    let binary = FACTORY_CLI_BINARIES
+        .iter()
+        .find_map(|name| find_in_path(name));
+    let auth_file = factory_auth_path().filter(|path| path.is_file());
+    let data = serde_json::json!({
+        "binary": binary.as_ref().map(|p| p.display().to_string()),
+        "auth_file": auth_file.as_ref().map(|p| p.display().to_string()),
+        "version": detected_cli_version(),
+    });
+
+    let (status, message) = match (&binary, &auth_file) {
+        (Some(_), Some(_)) => (StepStatus::Ok, "已检测到 factory-cli 及登录信息"),
+        (Some(_), None) => (StepStatus::Warning, "已安装 factory-cli，但尚未登录"),
+        (None, Some(_)) => (StepStatus::Ok, "已检测到 factory-cli 登录信息"),
+        (None, None) => (StepStatus::Warning, "未检测到 factory-cli，可手动输入凭证"),
+    };
+    StepResult::new(SetupStep::DetectFactoryCli, status, message).with_data(data)
+}
+
+/// 步骤 2：导入凭证
+///
+/// 未提供 `config` 时从 factory-cli 登录文件导入 OAuth 凭证，
+/// 否则按 `auth_type` 使用传入的配置创建凭证。
+pub async fn import_credential(
+    auth_type: Option<&str>,
+    config: Option<serde_json::Value>,
+) -> StepResult {
+    let (auth_type, config) = match config {
+        Some(config) => (auth_type.unwrap_or("oauth").to_string(), config),
+        None => {
+            let parsed = factory_auth_path()
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .and_then(|content| parse_factory_auth(&content));
+            match parsed {
+                Some(config) => ("oauth".to_string(), config),
+                None => {
+                    return StepResult::new(
+                        SetupStep::ImportCredential,
+                        StepStatus::Failed,
+                        "未找到 factory-cli 登录信息，请先运行 droid 登录、通过浏览器登录或手动输入凭证",
+                    )
+                }
+            }
+        }
+    };
+
+    match provider::create_credential(&auth_type, config).await {
+        Ok(credential_id) => {
+            StepResult::new(SetupStep::ImportCredential, StepStatus::Ok, "凭证已导入")
+                .with_data(serde_json::json!({ "credential_id": credential_id }))
+        }
+        Err(e) => StepResult::new(
+            SetupStep::ImportCredential,
+            StepStatus::Failed,
+            e.to_string(),
+        ),
+    }
+}
+
+/// 步骤 2（二选一）：在浏览器中 OAuth 登录
+///
+/// 返回登录链接，宿主打开后等待 `redirect_uri` 回调，再以回调地址调用
+/// `complete_relogin` 创建凭证。
+pub fn start_oauth() -> StepResult {
+    let config = get_config().relogin;
+    let login_url = relogin::new_login_url(&config);
+    StepResult::new(
+        SetupStep::StartOAuth,
+        StepStatus::Ok,
+        "请在浏览器中完成登录",
+    )
+    .with_data(serde_json::json!({
+        "login_url": login_url,
+        "redirect_uri": config.redirect_uri,
+    }))
+}
+
+/// 步骤 3：验证凭证
+pub async fn validate(credential_id: &str) -> StepResult {
+    match provider::validate_credential(credential_id).await {
+        Ok(result) => {
+            let status = if result.valid {
+                StepStatus::Ok
+            } else {
+                StepStatus::Failed
+            };
+            StepResult::new(
+                SetupStep::Validate,
+                status,
+                result.message.clone().unwrap_or_default(),
+            )
+            .with_data(serde_json::to_value(result.details).unwrap_or_default())
+        }
+        Err(e) => StepResult::new(SetupStep::Validate, StepStatus::Failed, e.to_string()),
+    }
+}
+
+/// 步骤 4：选择默认端点类型
+pub async fn select_endpoint(credential_id: &str, endpoint_type: EndpointType) -> StepResult {
+    match provider::set_endpoint_type(credential_id, endpoint_type).await {
+        Ok(()) => StepResult::new(
+            SetupStep::SelectEndpoint,
+            StepStatus::Ok,
+            format!("默认端点已设置为 {}", endpoint_type),
+        ),
+        Err(e) => StepResult::new(SetupStep::SelectEndpoint, StepStatus::Failed, e.to_string()),
+    }
+}
+
+/// 步骤 5：发送测试请求
+pub async fn test_request(credential_id: &str, endpoint_type: EndpointType) -> StepResult {
+    let acquired = match provider::authorize_credential(credential_id, endpoint_type).await {
+        Ok(acquired) => acquired,
+        Err(e) => {
+            return StepResult::new(SetupStep::TestRequest, StepStatus::Failed, e.to_string())
+        }
+    };
+
+    match probe::send_test_request(&acquired, endpoint_type).await {
+        Ok((status, latency_ms)) => {
+            let data = serde_json::json!({
+                "status_code": status.as_u16(),
+                "latency_ms": latency_ms,
+            });
+            let (step_status, message) = if status.is_success() {
+                (StepStatus::Ok, "测试请求成功".to_string())
+            } else if probe::status_indicates_support(status) {
+                (StepStatus::Warning, format!("端点可达，但返回 {}", status))
+            } else {
+                (StepStatus::Failed, format!("测试请求失败: {}", status))
+            };
+            StepResult::new(SetupStep::TestRequest, step_status, message).with_data(data)
+        }
+        Err(e) => StepResult::new(SetupStep::TestRequest, StepStatus::Failed, e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_factory_auth() {
+        let auth = parse_factory_auth(r#"{"accessToken":"at","refreshToken":"rt"}"#).unwrap();
+        assert_eq!(auth["access_token"], "at");
+        assert_eq!(auth["refresh_token"], "rt");
+
+        assert!(parse_factory_auth(r#"{"user":"someone"}"#).is_none());
+        assert!(parse_factory_auth("not json").is_none());
+    }
+
+    #[test]
+    fn test_start_oauth_registers_pending_login() {
+        let result = start_oauth();
+        assert_eq!(serde_json::to_value(result.step).unwrap(), "start_oauth");
+        let url = reqwest::Url::parse(result.data["login_url"].as_str().unwrap()).unwrap();
+        let state = url
+            .query_pairs()
+            .find(|(k, _)| k == "state")
+            .map(|(_, v)| v.into_owned())
+            .unwrap();
+        let pending = relogin::take_pending(&state).unwrap();
+        assert!(pending.credential_id.is_none());
+    }
+}
@@ -0,0 +1,128 @@
+//! 凭证分享（配对码）
+//!
+//! 将单个凭证打包为短时有效的加密载荷（可渲染为二维码），
+//! 在另一台实例上凭配对码导入，全程不暴露明文 Token。
+
+use crate::auth::encryption::{decrypt_sensitive_data, encrypt_sensitive_data};
+use crate::credentials::DroidCredentials;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// 载荷前缀，便于识别与版本升级
+pub const PAIRING_PAYLOAD_PREFIX: &str = "droid-pair:v1:";
+
+/// 默认有效期（分钟）
+pub const DEFAULT_PAIRING_TTL_MINUTES: i64 = 10;
+
+/// 配对码字符集（去除易混淆字符）
+const PAIRING_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTVWXYZ23456789";
+
+/// 导出结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairingExport {
+    /// 加密载荷（可直接编码为二维码）
+    pub payload: String,
+    /// 配对码，需通过其他渠道告知接收方
+    pub pairing_code: String,
+    /// 过期时间 (RFC3339 格式)
+    pub expires_at: String,
+}
+
+/// 载荷明文结构
+#[derive(Debug, Serialize, Deserialize)]
+struct PairingEnvelope {
+    expires_at: DateTime<Utc>,
+    /// 凭证（API Key 以明文形式存放在 encrypted_key 中，导入时重新加密）
+    credential: DroidCredentials,
+}
+
+/// 生成配对码，格式 XXXX-XXXX-XXXX
+pub fn generate_pairing_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..3)
+        .map(|_| {
+            (0..4)
+                .map(|_| PAIRING_ALPHABET[rng.gen_range(0..PAIRING_ALPHABET.len())] as char)
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 规范化用户输入的配对码（忽略大小写与空白）
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// 封装凭证为配对载荷
+///
+/// 传入的凭证中 API Key 应已解密为明文。
+pub fn seal(credential: DroidCredentials, ttl_minutes: i64) -> Result<PairingExport> {
+    let expires_at = Utc::now() + Duration::minutes(ttl_minutes.max(1));
+    let envelope = PairingEnvelope {
+        expires_at,
+        credential,
+    };
+
+    let pairing_code = generate_pairing_code();
+    let encrypted = encrypt_sensitive_data(&serde_json::to_string(&envelope)?, &pairing_code)?;
+
+    Ok(PairingExport {
+        payload: format!("{}{}", PAIRING_PAYLOAD_PREFIX, encrypted),
+        pairing_code,
+        expires_at: expires_at.to_rfc3339(),
+    })
+}
+
+/// 用配对码解开载荷
+pub fn open(payload: &str, pairing_code: &str) -> Result<DroidCredentials> {
+    let encrypted = payload
+        .trim()
+        .strip_prefix(PAIRING_PAYLOAD_PREFIX)
+        .ok_or_else(|| anyhow::anyhow!("不是有效的配对载荷"))?;
+
+    let plaintext = decrypt_sensitive_data(encrypted, &normalize_code(pairing_code))
+        .map_err(|_| anyhow::anyhow!("配对码错误或载荷已损坏"))?;
+    let envelope: PairingEnvelope = serde_json::from_str(&plaintext)?;
+
+    if envelope.expires_at <= Utc::now() {
+        anyhow::bail!("配对载荷已过期，请重新导出");
+    }
+
+    Ok(envelope.credential)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let credential = DroidCredentials {
+            refresh_token: Some("refresh-token".to_string()),
+            ..Default::default()
+        };
+
+        let export = seal(credential, 5).unwrap();
+        assert!(export.payload.starts_with(PAIRING_PAYLOAD_PREFIX));
+        assert!(!export.payload.contains("refresh-token"));
+
+        let code = export.pairing_code.to_lowercase();
+        let opened = open(&export.payload, &code).unwrap();
+        assert_eq!(opened.refresh_token.as_deref(), Some("refresh-token"));
+
+        assert!(open(&export.payload, "AAAA-AAAA-AAAA").is_err());
+    }
+
+    #[test]
+    fn test_pairing_code_format() {
+        let code = generate_pairing_code();
+        assert_eq!(code.len(), 14);
+        assert_eq!(code.matches('-').count(), 2);
+    }
+}
@@ -0,0 +1,36 @@
+//! 按 key 合并并发操作
+//!
+//! 多个请求同时遇到 401 时只应刷新一次 Token：调用方先获取该 key 的锁，
+//! 拿到锁后再检查是否已有其他请求完成了同样的工作。
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+lazy_static::lazy_static! {
+    static ref LOCKS: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// 获取某个 key 对应的异步锁
+pub fn lock_for(key: &str) -> Arc<tokio::sync::Mutex<()>> {
+    LOCKS
+        .lock()
+        .unwrap()
+        .entry(key.to_string())
+        .or_default()
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_same_key_shares_lock() {
+        let a = lock_for("cred-a");
+        let _guard = a.lock().await;
+
+        assert!(lock_for("cred-a").try_lock().is_err());
+        assert!(lock_for("cred-b").try_lock().is_ok());
+    }
+}
@@ -0,0 +1,318 @@
+//! 大响应落盘
+//!
+//! 生成长文档或大型结构化输出时，响应经 JSON-RPC 的一行文本往返会在宿主与
+//! 插件两端各复制好几份。宿主可以把上游响应体 / 流事件写入文件，以
+//! `response_path` / `events_path` 传入；转换结果超过 `threshold_bytes`
+//! （或调用方传入 `spool: true`）时直接写入临时文件，只返回路径与大小，
+//! 由宿主从文件转发给本地客户端。落盘的响应不再按 `Accept-Encoding` 压缩。
+//!
+//! 落盘目录默认在插件数据目录下，仅当前用户可访问（目录 0700、文件 0600）；
+//! 按路径传入的文件必须位于该目录内，插件不会替宿主读取任意文件。文件按流
+//! 读写，不在内存中保留原始文本副本。默认关闭。
+
+use crate::body_text;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// 落盘配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpoolConfig {
+    pub enabled: bool,
+    /// 序列化后超过该字节数的响应写入文件（0 表示只在调用方要求时落盘）
+    pub threshold_bytes: u64,
+    /// 临时文件目录，默认为插件数据目录下的 `spool`
+    pub dir: Option<String>,
+    /// 临时文件保留时长（分钟），宿主应在转发后自行删除，超时的由插件清理
+    pub max_age_minutes: u64,
+}
+
+impl Default for SpoolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_bytes: 8 * 1024 * 1024,
+            dir: None,
+            max_age_minutes: 60,
+        }
+    }
+}
+
+/// 已落盘的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpooledResponse {
+    /// 与传入时的 `response_path` 同名，宿主可统一处理
+    pub response_path: String,
+    pub bytes: u64,
+    pub content_type: String,
+}
+
+/// 只计数不保存的写入器
+#[derive(Default)]
+struct CountingWriter(u64);
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 落盘文件名前缀与后缀，清理时只删除这类文件
+const FILE_PREFIX: &str = "response-";
+const FILE_SUFFIX: &str = ".json";
+
+/// 临时文件目录
+pub fn spool_dir(config: &SpoolConfig) -> PathBuf {
+    match &config.dir {
+        Some(dir) => PathBuf::from(dir),
+        None => crate::config::data_dir().join("spool"),
+    }
+}
+
+/// 创建落盘目录并限制为仅当前用户可访问
+fn ensure_dir(config: &SpoolConfig) -> Result<PathBuf> {
+    let dir = spool_dir(config);
+    std::fs::create_dir_all(&dir).with_context(|| format!("无法创建 {}", dir.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(dir)
+}
+
+/// 校验宿主传入的路径：必须启用落盘且文件位于落盘目录内
+pub fn resolve_input(config: &SpoolConfig, path: &Path) -> Result<PathBuf> {
+    if !config.enabled {
+        anyhow::bail!("未启用响应落盘（spool.enabled），不能按路径传入响应");
+    }
+    let dir = ensure_dir(config)?.canonicalize()?;
+    let resolved = path
+        .canonicalize()
+        .with_context(|| format!("无法打开 {}", path.display()))?;
+    if !resolved.starts_with(&dir) || !resolved.is_file() {
+        anyhow::bail!("{} 不在落盘目录 {} 内", path.display(), dir.display());
+    }
+    Ok(resolved)
+}
+
+/// 去掉开头 BOM 的读取器
+fn open_reader(path: &Path) -> Result<impl Read> {
+    let file = File::open(path).with_context(|| format!("无法打开 {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    if reader.fill_buf()?.starts_with(b"\xEF\xBB\xBF") {
+        reader.consume(3);
+    }
+    Ok(reader)
+}
+
+/// 序列化后的字节数（不分配完整字符串）
+pub fn serialized_len(value: &serde_json::Value) -> u64 {
+    let mut counter = CountingWriter::default();
+    match serde_json::to_writer(&mut counter, value) {
+        Ok(()) => counter.0,
+        Err(_) => 0,
+    }
+}
+
+/// 是否应写入文件
+pub fn should_spool(config: &SpoolConfig, value: &serde_json::Value, requested: bool) -> bool {
+    if !config.enabled {
+        return false;
+    }
+    requested || (config.threshold_bytes > 0 && serialized_len(value) > config.threshold_bytes)
+}
+
+/// 从落盘目录内的文件读取 JSON 响应体
+///
+/// 直接从文件流式解析，不先读入完整文本；含非法 UTF-8 字节时退回按字节
+/// 解码后解析。
+pub fn read_json(config: &SpoolConfig, path: &Path) -> Result<serde_json::Value> {
+    let path = resolve_input(config, path)?;
+    match serde_json::from_reader(open_reader(&path)?) {
+        Ok(value) => Ok(value),
+        Err(e) if e.is_io() || e.is_syntax() => {
+            debug!("流式解析 {} 失败，按字节解码后重试: {}", path.display(), e);
+            let bytes = std::fs::read(&path)?;
+            body_text::parse_json(&bytes).with_context(|| format!("解析 {} 失败", path.display()))
+        }
+        Err(e) => Err(e).with_context(|| format!("解析 {} 失败", path.display())),
+    }
+}
+
+/// 解析流事件文件中的一行：支持 JSON Lines 与原始 SSE（`data:` 行），
+/// 空行、`event:` 等其他 SSE 字段与 `[DONE]` 返回 None
+fn parse_event_line(line: &str) -> Result<Option<serde_json::Value>> {
+    let line = body_text::strip_bom(line.trim());
+    let data = line.strip_prefix("data:").map(str::trim).unwrap_or(line);
+    if !data.starts_with('{') {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(data)?))
+}
+
+/// 逐行读取落盘目录内的流事件文件（按字节分行，非 UTF-8 字节不会中断读取）
+pub fn read_events(
+    config: &SpoolConfig,
+    path: &Path,
+) -> Result<impl Iterator<Item = Result<serde_json::Value>>> {
+    let path = resolve_input(config, path)?;
+    let file = File::open(&path).with_context(|| format!("无法打开 {}", path.display()))?;
+    let events = BufReader::new(file)
+        .split(b'\n')
+        .filter_map(|line| match line {
+            Ok(line) => {
+                let line = body_text::decode(&line);
+                parse_event_line(&line)
+                    .context("流事件解析失败")
+                    .transpose()
+            }
+            Err(e) => Some(Err(e.into())),
+        });
+    Ok(events)
+}
+
+/// 删除超过保留时长的落盘响应（只处理插件写入的 `response-*.json`）
+pub fn cleanup(config: &SpoolConfig) {
+    let max_age = Duration::from_secs(config.max_age_minutes * 60);
+    let Ok(entries) = std::fs::read_dir(spool_dir(config)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with(FILE_PREFIX) || !name.ends_with(FILE_SUFFIX) {
+            continue;
+        }
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| modified.elapsed().unwrap_or_default() > max_age);
+        if expired {
+            if let Err(e) = std::fs::remove_file(entry.path()) {
+                warn!("删除过期的落盘响应失败: {}", e);
+            }
+        }
+    }
+}
+
+/// 把响应写入临时文件
+pub fn write(config: &SpoolConfig, value: &serde_json::Value) -> Result<SpooledResponse> {
+    cleanup(config);
+    let dir = ensure_dir(config)?;
+    let name = format!("{}{}{}", FILE_PREFIX, uuid::Uuid::new_v4(), FILE_SUFFIX);
+    let path = dir.join(name);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut writer = BufWriter::new(options.open(&path)?);
+    serde_json::to_writer(&mut writer, value)?;
+    writer.flush()?;
+    let bytes = std::fs::metadata(&path)?.len();
+    debug!("响应已落盘: {} ({} 字节)", path.display(), bytes);
+    Ok(SpooledResponse {
+        response_path: path.display().to_string(),
+        bytes,
+        content_type: "application/json".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spool_round_trip() {
+        let dir = std::env::temp_dir().join(format!("droid-spool-{}", uuid::Uuid::new_v4()));
+        let config = SpoolConfig {
+            enabled: true,
+            threshold_bytes: 16,
+            dir: Some(dir.display().to_string()),
+            ..Default::default()
+        };
+        let small = serde_json::json!({ "a": 1 });
+        let large = serde_json::json!({ "text": "x".repeat(64) });
+        assert_eq!(serialized_len(&small), 7);
+        assert!(!should_spool(&config, &small, false));
+        assert!(should_spool(&config, &small, true));
+        assert!(should_spool(&config, &large, false));
+
+        let spooled = write(&config, &large).unwrap();
+        assert_eq!(spooled.bytes, serialized_len(&large));
+        let path = PathBuf::from(&spooled.response_path);
+        assert_eq!(read_json(&config, &path).unwrap(), large);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&dir), 0o700);
+            assert_eq!(mode(&path), 0o600);
+        }
+
+        // 带 BOM 与非法 UTF-8 字节的输入
+        let input = dir.join("input.json");
+        std::fs::write(&input, b"\xEF\xBB\xBF{\"a\":\"x\xFF\"}").unwrap();
+        assert_eq!(
+            read_json(&config, &input).unwrap(),
+            serde_json::json!({ "a": "x\u{FFFD}" })
+        );
+
+        // 目录外的文件与未启用时拒绝读取
+        let outside =
+            std::env::temp_dir().join(format!("droid-spool-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&outside, b"{}").unwrap();
+        assert!(read_json(&config, &outside).is_err());
+        assert!(read_json(&config, &dir.join("../").join(outside.file_name().unwrap())).is_err());
+        let disabled = SpoolConfig {
+            enabled: false,
+            ..config.clone()
+        };
+        assert!(read_json(&disabled, &path).is_err());
+        std::fs::remove_file(&outside).unwrap();
+
+        // 清理只删除插件写入的落盘文件
+        let expired = SpoolConfig {
+            max_age_minutes: 0,
+            ..config.clone()
+        };
+        std::thread::sleep(Duration::from_millis(10));
+        cleanup(&expired);
+        assert!(!path.exists());
+        assert!(input.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_event_lines() {
+        assert_eq!(
+            parse_event_line("{\"a\":1}").unwrap(),
+            Some(serde_json::json!({ "a": 1 }))
+        );
+        assert_eq!(
+            parse_event_line("data: {\"type\":\"ping\"}").unwrap(),
+            Some(serde_json::json!({ "type": "ping" }))
+        );
+        assert_eq!(parse_event_line("event: message_start").unwrap(), None);
+        assert_eq!(parse_event_line("data: [DONE]").unwrap(), None);
+        assert_eq!(parse_event_line("").unwrap(), None);
+        assert_eq!(
+            parse_event_line("\u{FEFF}data: {\"a\":1}").unwrap(),
+            Some(serde_json::json!({ "a": 1 }))
+        );
+        assert!(parse_event_line("data: {broken").is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! 启动请求队列
+//!
+//! 网关刚启动时宿主还在逐个加载、刷新凭证，此时到达的请求会因“没有可用的
+//! 健康凭证”直接失败，而代理类客户端往往会立即重试，形成一波请求风暴。
+//! 凭证池就绪（出现第一个可用凭证，或宿主调用 `startup_complete`）之前，
+//! acquire 在有界队列中排队等待，超时后按正常流程处理；队列已满时直接拒绝。
+//! 就绪只发生一次，之后凭证全部失效属于正常的错误处理，不再排队。
+
+use crate::events;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::info;
+
+/// 启动队列配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StartupQueueConfig {
+    pub enabled: bool,
+    /// 同时排队的请求上限
+    pub max_queued: usize,
+    /// 单个请求的最长等待时间（毫秒）
+    pub timeout_ms: u64,
+}
+
+impl Default for StartupQueueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_queued: 64,
+            timeout_ms: 30_000,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref READY: watch::Sender<bool> = watch::channel(false).0;
+}
+
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+
+/// 标记凭证池已就绪，放行所有排队的请求
+pub fn mark_ready(reason: &str) {
+    if READY.send_replace(true) {
+        return;
+    }
+    let queued = QUEUED.load(Ordering::SeqCst);
+    info!("凭证池已就绪（{}），放行 {} 个排队请求", reason, queued);
+    events::emit(
+        "pool_ready",
+        format!("凭证池已就绪，放行 {} 个排队请求", queued),
+        serde_json::json!({ "reason": reason, "released": queued }),
+    );
+}
+
+/// 凭证池是否已就绪
+pub fn is_ready() -> bool {
+    *READY.borrow()
+}
+
+/// 当前排队的请求数
+pub fn queued() -> usize {
+    QUEUED.load(Ordering::SeqCst)
+}
+
+/// 排队计数，取消等待时同样递减
+struct QueueSlot;
+
+impl QueueSlot {
+    fn take(max_queued: usize) -> Option<Self> {
+        let try_add = |n: usize| (n < max_queued).then_some(n + 1);
+        QUEUED
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, try_add)
+            .ok()
+            .map(|_| QueueSlot)
+    }
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        QUEUED.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 凭证池就绪前排队等待；返回是否在超时前就绪
+pub async fn wait_ready(config: &StartupQueueConfig) -> anyhow::Result<bool> {
+    if !config.enabled || is_ready() {
+        return Ok(true);
+    }
+    let _slot = QueueSlot::take(config.max_queued)
+        .ok_or_else(|| anyhow::anyhow!("凭证池仍在加载，启动队列已满"))?;
+
+    let mut receiver = READY.subscribe();
+    let timeout = Duration::from_millis(config.timeout_ms);
+    Ok(
+        tokio::time::timeout(timeout, receiver.wait_for(|ready| *ready))
+            .await
+            .is_ok_and(|result| result.is_ok()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queue_until_ready() {
+        let config = StartupQueueConfig {
+            enabled: true,
+            max_queued: 1,
+            timeout_ms: 5_000,
+        };
+        let waiter = tokio::spawn({
+            let config = config.clone();
+            async move { wait_ready(&config).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(queued(), 1);
+        // 队列已满
+        assert!(wait_ready(&config).await.is_err());
+
+        mark_ready("test");
+        assert!(waiter.await.unwrap().unwrap());
+        assert_eq!(queued(), 0);
+        assert!(wait_ready(&config).await.unwrap());
+    }
+}
@@ -0,0 +1,368 @@
+//! 使用统计管线
+//!
+//! acquire/release 热路径只把记录投递到 mpsc 通道，由后台写入任务
+//! 按批次 / 定时追加到 `usage.jsonl`，退出时统一刷新。
+
+use crate::config::{data_dir, get_config};
+use crate::request_tags::Tags;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+/// 通道容量，写入任务跟不上时丢弃新记录而不阻塞请求
+const CHANNEL_CAPACITY: usize = 10_000;
+
+/// 使用统计文件名
+pub const USAGE_FILE: &str = "usage.jsonl";
+
+/// 统计持久化配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsConfig {
+    /// 是否写入磁盘
+    #[serde(default)]
+    pub persist: bool,
+    /// 定时刷新间隔（毫秒）
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// 达到该条数立即刷新
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+}
+
+fn default_flush_interval_ms() -> u64 {
+    5000
+}
+
+fn default_batch_size() -> usize {
+    100
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            persist: false,
+            flush_interval_ms: default_flush_interval_ms(),
+            batch_size: default_batch_size(),
+        }
+    }
+}
+
+/// 单条使用记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// 记录时间 (RFC3339 格式)
+    pub timestamp: String,
+    pub credential_id: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub endpoint_type: Option<String>,
+    /// 调用方应用名
+    #[serde(default)]
+    pub client_name: Option<String>,
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    pub success: bool,
+    /// 故障转移前的原端点
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failover_from: Option<String>,
+    /// 发起请求的租户
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// 调用方附加的请求标签
+    #[serde(default, skip_serializing_if = "Tags::is_empty")]
+    pub tags: Tags,
+}
+
+enum StatsMessage {
+    Record(Box<UsageRecord>),
+    Flush(oneshot::Sender<()>),
+    Prune(Option<DateTime<Utc>>, oneshot::Sender<Result<PruneResult>>),
+}
+
+/// 清理结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PruneResult {
+    pub removed: usize,
+    pub kept: usize,
+}
+
+static SENDER: OnceLock<mpsc::Sender<StatsMessage>> = OnceLock::new();
+
+/// 获取（必要时启动）后台写入任务的发送端
+fn sender() -> &'static mpsc::Sender<StatsMessage> {
+    SENDER.get_or_init(|| {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::spawn(run_writer(rx));
+        tx
+    })
+}
+
+/// 投递一条使用记录（不阻塞）
+pub fn record(record: UsageRecord) {
+    if !get_config().stats.persist {
+        return;
+    }
+    if let Err(e) = sender().try_send(StatsMessage::Record(Box::new(record))) {
+        warn!("使用统计通道已满，丢弃记录: {}", e);
+    }
+}
+
+/// 刷新所有待写入记录，退出前调用
+pub async fn flush() {
+    let tx = match SENDER.get() {
+        Some(tx) => tx,
+        None => return,
+    };
+    let (ack_tx, ack_rx) = oneshot::channel();
+    if tx.send(StatsMessage::Flush(ack_tx)).await.is_ok() {
+        let _ = ack_rx.await;
+    }
+}
+
+/// 删除早于 `cutoff` 的记录（为空时删除全部），并重写文件去除损坏的行
+///
+/// 经由写入任务执行，避免与追加写入交错。
+pub async fn prune(cutoff: Option<DateTime<Utc>>) -> Result<PruneResult> {
+    let tx = match SENDER.get() {
+        Some(tx) => tx,
+        None => return prune_file(&usage_file_path(), cutoff).await,
+    };
+    let (ack_tx, ack_rx) = oneshot::channel();
+    tx.send(StatsMessage::Prune(cutoff, ack_tx))
+        .await
+        .map_err(|_| anyhow::anyhow!("使用统计写入任务已退出"))?;
+    ack_rx.await?
+}
+
+/// 使用统计文件路径
+pub fn usage_file_path() -> PathBuf {
+    data_dir().join(USAGE_FILE)
+}
+
+async fn run_writer(rx: mpsc::Receiver<StatsMessage>) {
+    let interval_ms = get_config().stats.flush_interval_ms.max(100);
+    write_loop(
+        rx,
+        usage_file_path,
+        || get_config().stats.batch_size,
+        Duration::from_millis(interval_ms),
+    )
+    .await
+}
+
+/// 写入循环：攒够 `batch_size` 条或每隔 `interval` 追加一次，通道关闭时写完剩余记录
+async fn write_loop(
+    mut rx: mpsc::Receiver<StatsMessage>,
+    path: impl Fn() -> PathBuf,
+    batch_size: impl Fn() -> usize,
+    interval: Duration,
+) {
+    let mut batch: Vec<UsageRecord> = Vec::new();
+    // 第一次定时刷新在一个间隔之后，而不是立即触发
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+
+    loop {
+        tokio::select! {
+            message = rx.recv() => match message {
+                Some(StatsMessage::Record(record)) => {
+                    batch.push(*record);
+                    if batch.len() >= batch_size() {
+                        write_batch(&path(), &mut batch).await;
+                    }
+                }
+                Some(StatsMessage::Flush(ack)) => {
+                    write_batch(&path(), &mut batch).await;
+                    let _ = ack.send(());
+                }
+                Some(StatsMessage::Prune(cutoff, ack)) => {
+                    let path = path();
+                    write_batch(&path, &mut batch).await;
+                    let _ = ack.send(prune_file(&path, cutoff).await);
+                }
+                None => {
+                    write_batch(&path(), &mut batch).await;
+                    break;
+                }
+            },
+            _ = interval.tick() => write_batch(&path(), &mut batch).await,
+        }
+    }
+}
+
+async fn write_batch(path: &Path, batch: &mut Vec<UsageRecord>) {
+    if batch.is_empty() {
+        return;
+    }
+    match append_records(path, batch).await {
+        Ok(()) => debug!("写入 {} 条使用记录", batch.len()),
+        Err(e) => warn!("写入使用记录失败: {}", e),
+    }
+    batch.clear();
+}
+
+/// 保留 `cutoff` 之后的记录
+pub fn retain_since(content: &str, cutoff: Option<DateTime<Utc>>) -> (String, PruneResult) {
+    let mut kept = String::new();
+    let mut result = PruneResult::default();
+
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let keep = cutoff.is_some_and(|cutoff| {
+            serde_json::from_str::<UsageRecord>(line)
+                .ok()
+                .and_then(|r| DateTime::parse_from_rfc3339(&r.timestamp).ok())
+                .map(|ts| ts >= cutoff)
+                .unwrap_or(false)
+        });
+        if keep {
+            kept.push_str(line);
+            kept.push('\n');
+            result.kept += 1;
+        } else {
+            result.removed += 1;
+        }
+    }
+    (kept, result)
+}
+
+async fn prune_file(path: &Path, cutoff: Option<DateTime<Utc>>) -> Result<PruneResult> {
+    let content = match tokio::fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(PruneResult::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let (kept, result) = retain_since(&content, cutoff);
+    let tmp_path = path.with_extension("jsonl.tmp");
+    tokio::fs::write(&tmp_path, kept).await?;
+    tokio::fs::rename(&tmp_path, path).await?;
+    Ok(result)
+}
+
+async fn append_records(path: &Path, records: &[UsageRecord]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let mut buffer = String::new();
+    for record in records {
+        buffer.push_str(&serde_json::to_string(record)?);
+        buffer.push('\n');
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(buffer.as_bytes()).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage_record(credential_id: &str) -> StatsMessage {
+        StatsMessage::Record(Box::new(UsageRecord {
+            timestamp: Utc::now().to_rfc3339(),
+            credential_id: credential_id.to_string(),
+            model: None,
+            endpoint_type: None,
+            client_name: None,
+            input_tokens: 1,
+            output_tokens: 1,
+            latency_ms: None,
+            success: true,
+            failover_from: None,
+            tenant: None,
+            tags: Tags::default(),
+        }))
+    }
+
+    /// 启动写入循环，返回发送端、文件路径与任务句柄
+    fn start(
+        name: &str,
+        batch_size: usize,
+        interval: Duration,
+    ) -> (
+        mpsc::Sender<StatsMessage>,
+        PathBuf,
+        tokio::task::JoinHandle<()>,
+    ) {
+        let path = data_dir().join(format!("stats-test-{}.jsonl", name));
+        let _ = std::fs::remove_file(&path);
+        let (tx, rx) = mpsc::channel(16);
+        let file = path.clone();
+        let handle = tokio::spawn(write_loop(
+            rx,
+            move || file.clone(),
+            move || batch_size,
+            interval,
+        ));
+        (tx, path, handle)
+    }
+
+    fn line_count(path: &Path) -> usize {
+        std::fs::read_to_string(path)
+            .map(|content| content.lines().count())
+            .unwrap_or(0)
+    }
+
+    /// 等待文件达到指定行数（最多 5 秒）
+    async fn wait_for_lines(path: &Path, lines: usize) -> usize {
+        for _ in 0..500 {
+            if line_count(path) >= lines {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        line_count(path)
+    }
+
+    #[tokio::test]
+    async fn test_writes_when_batch_is_full() {
+        let (tx, path, _handle) = start("batch", 3, Duration::from_secs(3600));
+        tx.send(usage_record("a")).await.unwrap();
+        tx.send(usage_record("b")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(line_count(&path), 0);
+
+        tx.send(usage_record("c")).await.unwrap();
+        assert_eq!(wait_for_lines(&path, 3).await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_writes_on_flush_interval() {
+        let (tx, path, _handle) = start("interval", 100, Duration::from_millis(50));
+        tx.send(usage_record("a")).await.unwrap();
+        assert_eq!(wait_for_lines(&path, 1).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_flushes_on_shutdown() {
+        let (tx, path, handle) = start("shutdown", 100, Duration::from_secs(3600));
+        tx.send(usage_record("a")).await.unwrap();
+        let (ack_tx, ack_rx) = oneshot::channel();
+        tx.send(StatsMessage::Flush(ack_tx)).await.unwrap();
+        ack_rx.await.unwrap();
+        assert_eq!(line_count(&path), 1);
+
+        // 通道关闭时写完剩余记录再退出
+        tx.send(usage_record("b")).await.unwrap();
+        drop(tx);
+        handle.await.unwrap();
+        assert_eq!(line_count(&path), 2);
+    }
+}
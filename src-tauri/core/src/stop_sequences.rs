@@ -0,0 +1,160 @@
+//! 停止序列的格式归一
+//!
+//! OpenAI Chat Completions 的 `stop` 可以是字符串或最多 4 项的数组；
+//! Anthropic Messages 的 `stop_sequences` 只能是数组，且每项必须含非空白
+//! 字符；OpenAI Responses 不支持停止序列。客户端按一种格式书写、请求却发往
+//! 另一种端点时，上游会直接返回 400。这里按目标格式改写字段名与取值，
+//! 超出限制或目标不支持的项丢弃并告警。
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Chat Completions `stop` 的最大项数
+pub const CHAT_MAX_STOP: usize = 4;
+
+/// 请求格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestFormat {
+    Anthropic,
+    Chat,
+    Responses,
+}
+
+impl RequestFormat {
+    /// 按请求体判断格式（含 `input` 为 Responses，Claude 模型为 Anthropic）
+    pub fn detect(request: &serde_json::Value) -> Self {
+        if request.get("input").is_some() {
+            RequestFormat::Responses
+        } else if request.get("system").is_some()
+            || request["model"]
+                .as_str()
+                .is_some_and(|m| m.starts_with("claude-"))
+        {
+            RequestFormat::Anthropic
+        } else {
+            RequestFormat::Chat
+        }
+    }
+}
+
+/// 取出请求中的停止序列（兼容两种字段名与字符串 / 数组两种写法）
+fn take(request: &mut serde_json::Value) -> Option<Vec<String>> {
+    let object = request.as_object_mut()?;
+    let values: Vec<serde_json::Value> = ["stop_sequences", "stop"]
+        .iter()
+        .filter_map(|key| object.remove(*key))
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    let mut sequences: Vec<String> = Vec::new();
+    for value in values {
+        let items = match value {
+            serde_json::Value::String(text) => vec![text],
+            serde_json::Value::Array(items) => items
+                .into_iter()
+                .filter_map(|item| item.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
+        for item in items {
+            if !sequences.contains(&item) {
+                sequences.push(item);
+            }
+        }
+    }
+    Some(sequences)
+}
+
+/// 按目标格式改写停止序列，返回被丢弃的项
+pub fn normalize(request: &mut serde_json::Value, format: RequestFormat) -> Vec<String> {
+    let Some(mut sequences) = take(request) else {
+        return Vec::new();
+    };
+    let mut dropped = Vec::new();
+    match format {
+        RequestFormat::Responses => dropped = sequences,
+        RequestFormat::Anthropic => {
+            let (kept, blank): (Vec<String>, Vec<String>) =
+                sequences.into_iter().partition(|s| !s.trim().is_empty());
+            dropped.extend(blank);
+            if !kept.is_empty() {
+                request["stop_sequences"] = serde_json::json!(kept);
+            }
+        }
+        RequestFormat::Chat => {
+            sequences.retain(|s| {
+                let keep = !s.is_empty();
+                if !keep {
+                    dropped.push(s.clone());
+                }
+                keep
+            });
+            if sequences.len() > CHAT_MAX_STOP {
+                dropped.extend(sequences.split_off(CHAT_MAX_STOP));
+            }
+            if !sequences.is_empty() {
+                request["stop"] = serde_json::json!(sequences);
+            }
+        }
+    }
+    if !dropped.is_empty() {
+        warn!(
+            "目标格式 {:?} 不接受以下停止序列，已丢弃: {:?}",
+            format, dropped
+        );
+    }
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_stop_to_anthropic() {
+        let mut request = serde_json::json!({
+            "model": "claude-sonnet-4-20250514",
+            "messages": [],
+            "stop": "END",
+        });
+        assert_eq!(RequestFormat::detect(&request), RequestFormat::Anthropic);
+        assert!(normalize(&mut request, RequestFormat::Anthropic).is_empty());
+        assert_eq!(request["stop_sequences"], serde_json::json!(["END"]));
+        assert!(request.get("stop").is_none());
+
+        let mut request = serde_json::json!({ "stop_sequences": ["  ", "\n\nHuman:"] });
+        assert_eq!(
+            normalize(&mut request, RequestFormat::Anthropic),
+            vec!["  "]
+        );
+        assert_eq!(request["stop_sequences"], serde_json::json!(["\n\nHuman:"]));
+    }
+
+    #[test]
+    fn test_anthropic_stop_to_chat_limit() {
+        let mut request = serde_json::json!({
+            "model": "gpt-5-2025-08-07",
+            "stop_sequences": ["a", "b", "c", "d", "e", "a"],
+        });
+        assert_eq!(RequestFormat::detect(&request), RequestFormat::Chat);
+        assert_eq!(normalize(&mut request, RequestFormat::Chat), vec!["e"]);
+        assert_eq!(request["stop"], serde_json::json!(["a", "b", "c", "d"]));
+        assert!(request.get("stop_sequences").is_none());
+    }
+
+    #[test]
+    fn test_responses_drops_stop() {
+        let mut request = serde_json::json!({ "input": "hi", "stop": ["END"] });
+        assert_eq!(RequestFormat::detect(&request), RequestFormat::Responses);
+        assert_eq!(
+            normalize(&mut request, RequestFormat::Responses),
+            vec!["END"]
+        );
+        assert!(request.get("stop").is_none());
+
+        let mut request = serde_json::json!({ "input": "hi" });
+        assert!(normalize(&mut request, RequestFormat::Responses).is_empty());
+    }
+}
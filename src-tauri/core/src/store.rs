@@ -0,0 +1,220 @@
+//! 崩溃安全的文件存储
+//!
+//! 写入流程：写临时文件 → fsync → 将当前文件保留为 `.bak` → 原子 rename →
+//! fsync 目录。任一步骤中断时，磁盘上至少有一个完整版本；读取时主文件
+//! 损坏或缺失则回退到 `.bak`。凭证文件中保存着全部 Refresh Token，必须经由
+//! 此模块写入。只读模式（见 `store_lock`）下拒绝写入。
+
+use crate::auth::token_seal;
+use crate::config::data_dir;
+use crate::credentials::DroidCredentials;
+use crate::migrations::{self, CredentialsFile, FutureVersion};
+use crate::store_lock;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// 凭证文件路径
+pub fn credentials_path() -> PathBuf {
+    data_dir().join("credentials.json")
+}
+
+/// 路径追加后缀（`a.json` → `a.json.bak`）
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// 上一代文件路径
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+/// 原子写入文件，并保留上一代为 `.bak`
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    store_lock::ensure_writable()?;
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty());
+    if let Some(parent) = parent {
+        fs::create_dir_all(parent)?;
+    }
+
+    // 临时文件只在新建时应用权限，先删掉上次中断留下的
+    let tmp_path = with_suffix(path, ".tmp");
+    let _ = fs::remove_file(&tmp_path);
+    {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        // 文件中可能有 Token 等机密，仅当前用户可读写
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options
+            .open(&tmp_path)
+            .with_context(|| format!("无法创建临时文件: {}", tmp_path.display()))?;
+        file.write_all(content)?;
+        file.sync_all()?;
+    }
+
+    if path.exists() {
+        // 硬链接失败（如文件系统不支持）时退化为复制
+        let backup = backup_path(path);
+        let _ = fs::remove_file(&backup);
+        if fs::hard_link(path, &backup).is_err() {
+            fs::copy(path, &backup)?;
+        }
+    }
+    fs::rename(&tmp_path, path)?;
+
+    #[cfg(unix)]
+    if let Some(parent) = parent {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// 原子写入 JSON
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    write_atomic(path, serde_json::to_string_pretty(value)?.as_bytes())
+}
+
+/// 读取 JSON，主文件缺失或损坏时回退到 `.bak`；两者都不存在时返回 None
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    let primary = match fs::read_to_string(path) {
+        Ok(content) => match serde_json::from_str(&content) {
+            Ok(value) => return Ok(Some(value)),
+            Err(e) => anyhow::anyhow!("{} 解析失败: {}", path.display(), e),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            anyhow::anyhow!("{} 不存在", path.display())
+        }
+        Err(e) => e.into(),
+    };
+
+    let backup = backup_path(path);
+    match fs::read_to_string(&backup) {
+        Ok(content) => {
+            let value = serde_json::from_str(&content)
+                .with_context(|| format!("{} 与备份均无法读取", path.display()))?;
+            warn!("{}，已从备份恢复", primary);
+            Ok(Some(value))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if path.exists() {
+                Err(primary)
+            } else {
+                Ok(None)
+            }
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 保存全部凭证（带当前结构版本号）
+///
+/// OAuth Token 加密后写入（见 `auth::token_seal`）。
+pub fn save_credentials(credentials: &HashMap<String, DroidCredentials>) -> Result<()> {
+    let mut sealed = credentials.clone();
+    for credential in sealed.values_mut() {
+        token_seal::seal(credential)?;
+    }
+    write_json(
+        &credentials_path(),
+        &CredentialsFile {
+            schema_version: migrations::CURRENT_VERSION,
+            credentials: &sealed,
+        },
+    )
+}
+
+/// 加载已保存的凭证，旧版本文件迁移后写回（原文件另存为 `.v<版本>.bak`）
+///
+/// Token 保持加密状态，由调用方解开（密钥不可用时以锁定状态载入）。
+pub fn load_credentials() -> Result<HashMap<String, DroidCredentials>> {
+    let path = credentials_path();
+    let Some(document) = read_json::<serde_json::Value>(&path)? else {
+        return Ok(HashMap::new());
+    };
+
+    let migrated = match migrations::migrate(document.clone()) {
+        Ok(migrated) => migrated,
+        Err(e) => {
+            // 无法处理的文件原样留档，之后的保存不会覆盖它
+            let version = e
+                .downcast_ref::<FutureVersion>()
+                .map(|f| f.found)
+                .unwrap_or(0);
+            let backup = with_suffix(&path, &format!(".v{}.bak", version));
+            write_json(&backup, &document)?;
+            return Err(e.context(format!("原文件已备份到 {}", backup.display())));
+        }
+    };
+    let changed = migrated.changed();
+    let from_version = migrated.from_version;
+    let credentials: HashMap<String, DroidCredentials> =
+        serde_json::from_value(serde_json::Value::Object(migrated.credentials))?;
+
+    if changed {
+        let backup = with_suffix(&path, &format!(".v{}.bak", from_version));
+        write_json(&backup, &document)?;
+        save_credentials(&credentials)?;
+        info!(
+            "凭证文件已从 v{} 迁移到 v{}，原文件备份为 {}",
+            from_version,
+            migrations::CURRENT_VERSION,
+            backup.display()
+        );
+    }
+    Ok(credentials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("droid-store-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[test]
+    fn test_write_keeps_previous_generation() {
+        let path = temp_path("generations.json");
+        write_json(&path, &1).unwrap();
+        write_json(&path, &2).unwrap();
+
+        assert_eq!(read_json::<i32>(&path).unwrap(), Some(2));
+        assert_eq!(read_json::<i32>(&backup_path(&path)).unwrap(), Some(1));
+        assert!(!with_suffix(&path, ".tmp").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_written_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = temp_path("private.json");
+        fs::write(&path, "0").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_json(&path, &1).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_corrupt_primary_falls_back_to_backup() {
+        let path = temp_path("corrupt.json");
+        write_json(&path, &"old").unwrap();
+        write_json(&path, &"new").unwrap();
+        fs::write(&path, "{ truncated").unwrap();
+
+        assert_eq!(read_json::<String>(&path).unwrap().as_deref(), Some("old"));
+        assert!(read_json::<String>(&temp_path("missing.json"))
+            .unwrap()
+            .is_none());
+    }
+}
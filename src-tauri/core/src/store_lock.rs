@@ -0,0 +1,190 @@
+//! 凭证存储的单实例锁
+//!
+//! Tauri 应用与无界面实例指向同一数据目录时，并发写入会互相覆盖。启动时
+//! 对 `store.lock` 加跨进程文件锁，持有者信息写入 `store.owner.json`。
+//! 锁已被占用时进入只读模式：照常服务请求，但拒绝写入数据目录，并发出
+//! 警告事件说明是哪个进程持有锁。持有者退出后可调用 `acquire` 重试。
+
+use crate::config::data_dir;
+use crate::events;
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// 锁持有者信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    /// 实例类型（如 `json_rpc`、`tauri`）
+    pub instance: String,
+    pub started_at: String,
+}
+
+/// 当前实例的存储访问模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreMode {
+    /// 尚未尝试加锁（嵌入方未调用 `acquire`）
+    #[default]
+    Unlocked,
+    /// 持有锁，可读写
+    Owner,
+    /// 锁被其他实例持有，只读
+    ReadOnly,
+}
+
+/// 锁状态
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoreLockStatus {
+    pub mode: StoreMode,
+    pub lock_path: String,
+    /// 持有者（只读模式下为其他实例）
+    #[serde(default)]
+    pub owner: Option<LockOwner>,
+}
+
+struct LockState {
+    file: Option<File>,
+    status: StoreLockStatus,
+}
+
+lazy_static::lazy_static! {
+    static ref STATE: Mutex<LockState> = Mutex::new(LockState {
+        file: None,
+        status: StoreLockStatus::default(),
+    });
+}
+
+fn lock_path(dir: &Path) -> PathBuf {
+    dir.join("store.lock")
+}
+
+fn owner_path(dir: &Path) -> PathBuf {
+    dir.join("store.owner.json")
+}
+
+/// 尝试加锁；成功时返回锁文件句柄（句柄存活期间持有锁）
+fn try_lock(dir: &Path, instance: &str) -> Result<(Option<File>, StoreLockStatus)> {
+    fs::create_dir_all(dir)?;
+    let path = lock_path(dir);
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)?;
+    let lock_path = path.display().to_string();
+
+    match file.try_lock() {
+        Ok(()) => {
+            let owner = LockOwner {
+                pid: std::process::id(),
+                instance: instance.to_string(),
+                started_at: Utc::now().to_rfc3339(),
+            };
+            fs::write(owner_path(dir), serde_json::to_vec_pretty(&owner)?)?;
+            let status = StoreLockStatus {
+                mode: StoreMode::Owner,
+                lock_path,
+                owner: Some(owner),
+            };
+            Ok((Some(file), status))
+        }
+        Err(TryLockError::WouldBlock) => {
+            let owner = fs::read(owner_path(dir))
+                .ok()
+                .and_then(|content| serde_json::from_slice(&content).ok());
+            let status = StoreLockStatus {
+                mode: StoreMode::ReadOnly,
+                lock_path,
+                owner,
+            };
+            Ok((None, status))
+        }
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// 对数据目录加锁，锁被占用时切换为只读模式
+pub fn acquire(instance: &str) -> Result<StoreLockStatus> {
+    let mut state = STATE.lock().unwrap();
+    if state.status.mode == StoreMode::Owner {
+        return Ok(state.status.clone());
+    }
+    let (file, status) = try_lock(&data_dir(), instance)?;
+    match status.mode {
+        StoreMode::Owner => info!("已获得凭证存储锁: {}", status.lock_path),
+        _ => {
+            let holder = status
+                .owner
+                .as_ref()
+                .map(|o| format!("{}（pid {}）", o.instance, o.pid))
+                .unwrap_or_else(|| "未知进程".to_string());
+            warn!("凭证存储已被 {} 占用，当前实例以只读模式运行", holder);
+            events::emit(
+                "store_read_only",
+                format!(
+                    "凭证存储已被 {} 占用，当前实例为只读模式，修改不会保存",
+                    holder
+                ),
+                serde_json::to_value(&status).unwrap_or_default(),
+            );
+        }
+    }
+    state.file = file;
+    state.status = status.clone();
+    Ok(status)
+}
+
+/// 当前锁状态
+pub fn status() -> StoreLockStatus {
+    STATE.lock().unwrap().status.clone()
+}
+
+/// 是否为只读模式
+pub fn is_read_only() -> bool {
+    STATE.lock().unwrap().status.mode == StoreMode::ReadOnly
+}
+
+/// 写入数据目录前检查
+pub fn ensure_writable() -> Result<()> {
+    let state = STATE.lock().unwrap();
+    if state.status.mode != StoreMode::ReadOnly {
+        return Ok(());
+    }
+    let pid = state.status.owner.as_ref().map(|o| o.pid);
+    anyhow::bail!(
+        "凭证存储被其他实例占用（pid {}），当前为只读模式",
+        pid.map_or("未知".to_string(), |p| p.to_string())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_lock_is_read_only() {
+        let dir = std::env::temp_dir().join(format!("store-lock-{}", uuid::Uuid::new_v4()));
+        let (first, status) = try_lock(&dir, "tauri").unwrap();
+        assert!(first.is_some());
+        assert_eq!(status.mode, StoreMode::Owner);
+
+        let (second, status) = try_lock(&dir, "json_rpc").unwrap();
+        assert!(second.is_none());
+        assert_eq!(status.mode, StoreMode::ReadOnly);
+        let owner = status.owner.unwrap();
+        assert_eq!(owner.instance, "tauri");
+        assert_eq!(owner.pid, std::process::id());
+
+        // 持有者释放后可重新加锁
+        drop(first);
+        let (third, status) = try_lock(&dir, "json_rpc").unwrap();
+        assert!(third.is_some());
+        assert_eq!(status.owner.unwrap().instance, "json_rpc");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
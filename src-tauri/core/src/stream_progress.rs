@@ -0,0 +1,308 @@
+//! 流式响应进度
+//!
+//! 宿主转发 SSE 响应时逐块调用 `transform_stream_chunk` 并带上 `lease_id`，
+//! 这里从中解析用量事件（上游尚未报告时按字符估算），按间隔生成进度快照
+//! （已生成 Token、耗时、所用凭证），流结束时再生成一次 `done: true`，
+//! 供 UI 显示长输出的实时状态栏。
+//!
+//! 进度不进入通用事件队列（高频的进度会挤掉其他通知），而是每个流只保留
+//! 最新一次快照，由宿主通过 `drain_stream_progress` 单独拉取。默认关闭。
+
+use crate::throttle::estimate_chunk_tokens;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 最多保留的待拉取快照数（每个流一条）
+const MAX_PENDING_SNAPSHOTS: usize = 256;
+
+/// 进度事件配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProgressConfig {
+    pub enabled: bool,
+    /// 同一个流两次进度事件的最小间隔（毫秒）
+    pub interval_ms: u64,
+}
+
+impl Default for ProgressConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: 1000,
+        }
+    }
+}
+
+/// 一次进度快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressSnapshot {
+    pub lease_id: String,
+    pub credential_id: String,
+    pub model: String,
+    #[serde(default)]
+    pub client_name: Option<String>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// 输出 Token 是否为估算值
+    pub estimated: bool,
+    pub elapsed_ms: u64,
+    pub tokens_per_second: f64,
+    pub done: bool,
+}
+
+/// 单个流的累计状态
+#[derive(Debug)]
+pub struct StreamProgress {
+    credential_id: String,
+    model: String,
+    client_name: Option<String>,
+    started_at: DateTime<Utc>,
+    input_tokens: u64,
+    reported_output_tokens: Option<u64>,
+    estimated_output_tokens: u64,
+    done: bool,
+    last_emitted: Option<Instant>,
+}
+
+impl StreamProgress {
+    pub fn new(
+        credential_id: &str,
+        model: &str,
+        client_name: Option<String>,
+        started_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            credential_id: credential_id.to_string(),
+            model: model.to_string(),
+            client_name,
+            started_at,
+            input_tokens: 0,
+            reported_output_tokens: None,
+            estimated_output_tokens: 0,
+            done: false,
+            last_emitted: None,
+        }
+    }
+
+    /// 喂入一个流事件（Anthropic / Chat Completions / Responses）
+    pub fn push(&mut self, chunk: &serde_json::Value) {
+        self.estimated_output_tokens += estimate_chunk_tokens(chunk);
+
+        let usage = match chunk["type"].as_str() {
+            Some("message_start") => &chunk["message"]["usage"],
+            Some("response.completed") => &chunk["response"]["usage"],
+            _ => &chunk["usage"],
+        };
+        let input = ["input_tokens", "prompt_tokens"]
+            .iter()
+            .find_map(|key| usage[key].as_u64());
+        let output = ["output_tokens", "completion_tokens"]
+            .iter()
+            .find_map(|key| usage[key].as_u64());
+        if let Some(input) = input {
+            self.input_tokens = input;
+        }
+        // message_start 中的 output_tokens 只是占位
+        if chunk["type"] != "message_start" {
+            if let Some(output) = output {
+                self.reported_output_tokens = Some(output);
+            }
+        }
+
+        // Chat Completions 的用量块（include_usage）在 finish_reason 之后，是最后一块
+        let chat_finished = chunk.get("choices").is_some() && output.is_some();
+        if chat_finished
+            || matches!(
+                chunk["type"].as_str(),
+                Some("message_stop") | Some("response.completed")
+            )
+        {
+            self.done = true;
+        }
+    }
+
+    pub fn snapshot(&self, lease_id: &str, now: DateTime<Utc>) -> ProgressSnapshot {
+        let output_tokens = self
+            .reported_output_tokens
+            .unwrap_or(self.estimated_output_tokens);
+        let elapsed_ms = (now - self.started_at).num_milliseconds().max(0) as u64;
+        let tokens_per_second = if elapsed_ms > 0 {
+            output_tokens as f64 * 1000.0 / elapsed_ms as f64
+        } else {
+            0.0
+        };
+        ProgressSnapshot {
+            lease_id: lease_id.to_string(),
+            credential_id: self.credential_id.clone(),
+            model: self.model.clone(),
+            client_name: self.client_name.clone(),
+            input_tokens: self.input_tokens,
+            output_tokens,
+            estimated: self.reported_output_tokens.is_none(),
+            elapsed_ms,
+            tokens_per_second,
+            done: self.done,
+        }
+    }
+
+    /// 是否该发进度事件（结束时总是发）
+    fn should_emit(&mut self, now: Instant, interval: Duration) -> bool {
+        let due = self.done || self.last_emitted.is_none_or(|last| now - last >= interval);
+        if due {
+            self.last_emitted = Some(now);
+        }
+        due
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref STREAMS: Mutex<HashMap<String, StreamProgress>> = Mutex::new(HashMap::new());
+    /// 租约 → 尚未被拉取的最新快照
+    static ref PENDING: Mutex<HashMap<String, ProgressSnapshot>> = Mutex::new(HashMap::new());
+}
+
+/// 记录一个流事件，必要时发出进度事件
+///
+/// `start` 只在该租约首次出现时调用，用于创建状态。
+pub fn observe(
+    lease_id: &str,
+    chunk: &serde_json::Value,
+    config: &ProgressConfig,
+    start: impl FnOnce() -> StreamProgress,
+) {
+    if !config.enabled {
+        return;
+    }
+    let mut streams = STREAMS.lock().unwrap();
+    let progress = streams.entry(lease_id.to_string()).or_insert_with(start);
+    progress.push(chunk);
+
+    let interval = Duration::from_millis(config.interval_ms);
+    if !progress.should_emit(Instant::now(), interval) {
+        return;
+    }
+    let snapshot = progress.snapshot(lease_id, Utc::now());
+    if snapshot.done {
+        streams.remove(lease_id);
+    }
+    publish(snapshot);
+}
+
+/// 租约释放时结束跟踪；流没有明确的结束事件时在此补发最终进度
+pub fn finish(lease_id: &str) {
+    let Some(mut progress) = STREAMS.lock().unwrap().remove(lease_id) else {
+        return;
+    };
+    progress.done = true;
+    publish(progress.snapshot(lease_id, Utc::now()));
+}
+
+/// 保存快照，同一个流的旧快照被覆盖
+fn publish(snapshot: ProgressSnapshot) {
+    let mut pending = PENDING.lock().unwrap();
+    if pending.len() >= MAX_PENDING_SNAPSHOTS && !pending.contains_key(&snapshot.lease_id) {
+        // 宿主长时间未拉取：优先丢弃已结束的流
+        let Some(stale) = pending
+            .iter()
+            .find(|(_, s)| s.done)
+            .or_else(|| pending.iter().next())
+            .map(|(lease_id, _)| lease_id.clone())
+        else {
+            return;
+        };
+        pending.remove(&stale);
+    }
+    pending.insert(snapshot.lease_id.clone(), snapshot);
+}
+
+/// 取出所有待拉取的进度快照（按耗时从长到短）
+pub fn drain() -> Vec<ProgressSnapshot> {
+    let mut snapshots: Vec<_> = PENDING.lock().unwrap().drain().map(|(_, s)| s).collect();
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.elapsed_ms));
+    snapshots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anthropic_usage() {
+        let started = Utc::now();
+        let mut progress = StreamProgress::new("cred", "claude", None, started);
+        progress.push(&serde_json::json!({
+            "type": "message_start",
+            "message": { "usage": { "input_tokens": 30, "output_tokens": 1 } }
+        }));
+        progress.push(&serde_json::json!({
+            "type": "content_block_delta",
+            "delta": { "type": "text_delta", "text": "12345678" }
+        }));
+
+        let snapshot = progress.snapshot("lease", started + chrono::Duration::seconds(1));
+        assert_eq!(snapshot.input_tokens, 30);
+        assert_eq!(snapshot.output_tokens, 2);
+        assert!(snapshot.estimated);
+        assert!(!snapshot.done);
+
+        progress.push(&serde_json::json!({
+            "type": "message_delta",
+            "usage": { "output_tokens": 5 }
+        }));
+        progress.push(&serde_json::json!({ "type": "message_stop" }));
+        let snapshot = progress.snapshot("lease", started + chrono::Duration::seconds(1));
+        assert_eq!(snapshot.output_tokens, 5);
+        assert!(!snapshot.estimated);
+        assert!(snapshot.done);
+        assert_eq!(snapshot.tokens_per_second, 5.0);
+    }
+
+    #[test]
+    fn test_emit_interval() {
+        let mut progress = StreamProgress::new("cred", "gpt", None, Utc::now());
+        let now = Instant::now();
+        let interval = Duration::from_secs(1);
+        assert!(progress.should_emit(now, interval));
+        assert!(!progress.should_emit(now + Duration::from_millis(500), interval));
+        assert!(progress.should_emit(now + Duration::from_secs(1), interval));
+
+        progress.push(&serde_json::json!({
+            "choices": [],
+            "usage": { "prompt_tokens": 3, "completion_tokens": 9 }
+        }));
+        assert!(progress.should_emit(now + Duration::from_millis(1100), interval));
+    }
+
+    #[test]
+    fn test_snapshots_coalesce_per_stream() {
+        let config = ProgressConfig {
+            enabled: true,
+            interval_ms: 0,
+        };
+        let start = || StreamProgress::new("cred", "claude", None, Utc::now());
+        let delta = serde_json::json!({
+            "type": "content_block_delta",
+            "delta": { "type": "text_delta", "text": "12345678" }
+        });
+        for _ in 0..500 {
+            observe("lease-progress", &delta, &config, start);
+        }
+        let snapshots: Vec<_> = drain()
+            .into_iter()
+            .filter(|s| s.lease_id == "lease-progress")
+            .collect();
+        // 500 个块只留下最新的一条快照，不会占满通用事件队列
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(
+            snapshots[0].output_tokens,
+            500 * estimate_chunk_tokens(&delta)
+        );
+        finish("lease-progress");
+        assert!(drain()
+            .iter()
+            .any(|s| s.lease_id == "lease-progress" && s.done));
+    }
+}
@@ -0,0 +1,339 @@
+//! 本地租户
+//!
+//! 一个网关实例供家庭或小团队多人共用时，可定义多个租户：每个租户持有自己的
+//! 虚拟密钥（配置中只保存 SHA-256 哈希），只能使用指定的凭证子集，并有独立的
+//! 每日 Token 配额与每分钟请求数限制。宿主把客户端请求中的密钥作为
+//! `tenant_key` 传给 acquire；启用租户后没有有效密钥的请求会被拒绝。
+//! 用量按租户分别统计，使用记录中也带上租户 ID。当日用量随凭证文件一起由
+//! 后台写入 `tenant_usage.json`，重启后配额不会重置；每分钟请求数只在内存中
+//! 统计。
+
+use crate::auth::encryption::hash_api_key;
+use crate::config::data_dir;
+use crate::heartbeat::HeartbeatConfig;
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// 虚拟密钥前缀
+pub const TENANT_KEY_PREFIX: &str = "dpk-";
+
+/// 租户用量文件名
+pub const TENANT_USAGE_FILE: &str = "tenant_usage.json";
+
+/// 单个租户
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tenant {
+    pub id: String,
+    pub name: Option<String>,
+    /// 虚拟密钥的 SHA-256 哈希
+    pub key_hashes: Vec<String>,
+    /// 可使用的凭证 ID，为空表示全部
+    pub credentials: Vec<String>,
+    /// 每日 Token 配额（输入 + 输出，按 UTC 日期），0 表示不限制
+    pub daily_token_quota: u64,
+    /// 每分钟请求数上限，0 表示不限制
+    pub requests_per_minute: u32,
+    /// 绑定的转换配置档
+    pub profile: Option<String>,
+    /// 流式响应心跳，覆盖全局设置
+    pub heartbeat: Option<HeartbeatConfig>,
+}
+
+impl Tenant {
+    /// 是否可以使用该凭证
+    pub fn allows_credential(&self, credential_id: &str) -> bool {
+        self.credentials.is_empty() || self.credentials.iter().any(|c| c == credential_id)
+    }
+}
+
+/// 租户配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TenantsConfig {
+    pub enabled: bool,
+    pub tenants: Vec<Tenant>,
+}
+
+/// 生成新的虚拟密钥，返回 (密钥, 哈希)；密钥只在此时可见
+pub fn generate_key() -> (String, String) {
+    let key = format!(
+        "{}{}",
+        TENANT_KEY_PREFIX,
+        hex::encode(rand::random::<[u8; 24]>())
+    );
+    let hash = hash_api_key(&key);
+    (key, hash)
+}
+
+/// 按虚拟密钥查找租户；未启用租户时返回 None
+pub fn resolve<'a>(
+    config: &'a TenantsConfig,
+    key: Option<&str>,
+) -> anyhow::Result<Option<&'a Tenant>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    let key = key
+        .filter(|k| !k.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("已启用租户，请求需要提供虚拟密钥"))?;
+    let hash = hash_api_key(key);
+    config
+        .tenants
+        .iter()
+        .find(|t| t.key_hashes.contains(&hash))
+        .map(Some)
+        .ok_or_else(|| anyhow::anyhow!("无效的虚拟密钥"))
+}
+
+/// 租户当日用量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantStats {
+    pub tenant_id: String,
+    pub date: NaiveDate,
+    pub requests: u64,
+    pub rejected: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// 剩余 Token 配额，不限制时为 None
+    pub remaining_tokens: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TenantUsage {
+    date: NaiveDate,
+    requests: u64,
+    rejected: u64,
+    input_tokens: u64,
+    output_tokens: u64,
+    /// 最近一分钟内的请求时间（不写盘）
+    #[serde(skip)]
+    recent: VecDeque<Instant>,
+}
+
+impl TenantUsage {
+    fn new(date: NaiveDate) -> Self {
+        Self {
+            date,
+            requests: 0,
+            rejected: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// 跨日时清零当日计数
+    fn roll(&mut self, today: NaiveDate) {
+        if self.date != today {
+            *self = Self {
+                recent: std::mem::take(&mut self.recent),
+                ..Self::new(today)
+            };
+        }
+    }
+
+    /// 检查配额与速率，通过则计入一次请求
+    fn admit(&mut self, tenant: &Tenant, today: NaiveDate, now: Instant) -> anyhow::Result<()> {
+        self.roll(today);
+        let used = self.input_tokens + self.output_tokens;
+        if tenant.daily_token_quota > 0 && used >= tenant.daily_token_quota {
+            self.rejected += 1;
+            anyhow::bail!("租户 {} 今日 Token 配额已用完", tenant.id);
+        }
+
+        let window = Duration::from_secs(60);
+        while self
+            .recent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= window)
+        {
+            self.recent.pop_front();
+        }
+        let rpm = tenant.requests_per_minute as usize;
+        if rpm > 0 && self.recent.len() >= rpm {
+            self.rejected += 1;
+            anyhow::bail!("租户 {} 请求过于频繁（每分钟上限 {}）", tenant.id, rpm);
+        }
+
+        self.recent.push_back(now);
+        self.requests += 1;
+        Ok(())
+    }
+
+    fn stats(&self, tenant: &Tenant) -> TenantStats {
+        let used = self.input_tokens + self.output_tokens;
+        TenantStats {
+            tenant_id: tenant.id.clone(),
+            date: self.date,
+            requests: self.requests,
+            rejected: self.rejected,
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
+            remaining_tokens: (tenant.daily_token_quota > 0)
+                .then(|| tenant.daily_token_quota.saturating_sub(used)),
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    /// 首次使用时从文件载入
+    static ref USAGE: Mutex<Option<HashMap<String, TenantUsage>>> = Mutex::new(None);
+}
+
+/// 有尚未写盘的变化
+static DIRTY: AtomicBool = AtomicBool::new(false);
+
+fn usage_path() -> PathBuf {
+    data_dir().join(TENANT_USAGE_FILE)
+}
+
+fn load_from_disk() -> HashMap<String, TenantUsage> {
+    match crate::store::read_json(&usage_path()) {
+        Ok(usage) => usage.unwrap_or_default(),
+        Err(e) => {
+            warn!("租户用量文件读取失败，已忽略: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+/// 写入有变化的用量（随凭证文件一起在后台调用）
+pub fn save() -> Result<()> {
+    if !DIRTY.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let guard = USAGE.lock().unwrap();
+    let Some(usage) = guard.as_ref() else {
+        return Ok(());
+    };
+    crate::store::write_json(&usage_path(), usage).inspect_err(|_| {
+        DIRTY.store(true, Ordering::SeqCst);
+    })
+}
+
+/// 在租户的当日用量上执行操作（跨日时先清零）
+fn with_usage<T>(tenant_id: &str, f: impl FnOnce(&mut TenantUsage, NaiveDate) -> T) -> T {
+    let today = Utc::now().date_naive();
+    let mut guard = USAGE.lock().unwrap();
+    let entry = guard
+        .get_or_insert_with(load_from_disk)
+        .entry(tenant_id.to_string())
+        .or_insert_with(|| TenantUsage::new(today));
+    entry.roll(today);
+    f(entry, today)
+}
+
+/// 检查租户的配额与速率并计入一次请求
+pub fn admit(tenant: &Tenant) -> anyhow::Result<()> {
+    let result = with_usage(&tenant.id, |usage, today| {
+        usage.admit(tenant, today, Instant::now())
+    });
+    DIRTY.store(true, Ordering::SeqCst);
+    result
+}
+
+/// 请求结束后计入 Token 用量
+pub fn record_usage(tenant_id: &str, input_tokens: u64, output_tokens: u64) {
+    with_usage(tenant_id, |usage, _| {
+        usage.input_tokens += input_tokens;
+        usage.output_tokens += output_tokens;
+    });
+    DIRTY.store(true, Ordering::SeqCst);
+}
+
+/// 各租户当日用量
+pub fn stats(config: &TenantsConfig) -> Vec<TenantStats> {
+    config
+        .tenants
+        .iter()
+        .map(|tenant| with_usage(&tenant.id, |usage, _| usage.stats(tenant)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let (key, hash) = generate_key();
+        assert!(key.starts_with(TENANT_KEY_PREFIX));
+        let config = TenantsConfig {
+            enabled: true,
+            tenants: vec![Tenant {
+                id: "kids".to_string(),
+                key_hashes: vec![hash],
+                credentials: vec!["cred-1".to_string()],
+                ..Default::default()
+            }],
+        };
+
+        let tenant = resolve(&config, Some(&key)).unwrap().unwrap();
+        assert_eq!(tenant.id, "kids");
+        assert!(tenant.allows_credential("cred-1"));
+        assert!(!tenant.allows_credential("cred-2"));
+        assert!(resolve(&config, Some("dpk-wrong")).is_err());
+        assert!(resolve(&config, None).is_err());
+        assert!(resolve(&TenantsConfig::default(), None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_quota_and_rate() {
+        let tenant = Tenant {
+            id: "t".to_string(),
+            daily_token_quota: 100,
+            requests_per_minute: 2,
+            ..Default::default()
+        };
+        let today = Utc::now().date_naive();
+        let now = Instant::now();
+        let mut usage = TenantUsage::new(today);
+
+        assert!(usage.admit(&tenant, today, now).is_ok());
+        assert!(usage.admit(&tenant, today, now).is_ok());
+        assert!(usage.admit(&tenant, today, now).is_err());
+        assert!(usage
+            .admit(&tenant, today, now + Duration::from_secs(61))
+            .is_ok());
+
+        usage.output_tokens = 100;
+        assert!(usage
+            .admit(&tenant, today, now + Duration::from_secs(200))
+            .is_err());
+        assert_eq!(usage.stats(&tenant).remaining_tokens, Some(0));
+        assert_eq!(usage.stats(&tenant).rejected, 2);
+
+        // 跨日后配额恢复
+        let tomorrow = today.succ_opt().unwrap();
+        assert!(usage
+            .admit(&tenant, tomorrow, now + Duration::from_secs(300))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_usage_persists() {
+        let tenant = Tenant {
+            id: "persisted".to_string(),
+            daily_token_quota: 100,
+            ..Default::default()
+        };
+        admit(&tenant).unwrap();
+        record_usage("persisted", 60, 40);
+        save().unwrap();
+
+        // 模拟重启：从文件重新载入后配额仍然用完
+        let mut reloaded = load_from_disk().remove("persisted").unwrap();
+        assert_eq!(reloaded.requests, 1);
+        assert_eq!(reloaded.stats(&tenant).remaining_tokens, Some(0));
+        let today = Utc::now().date_naive();
+        assert!(reloaded.admit(&tenant, today, Instant::now()).is_err());
+    }
+}
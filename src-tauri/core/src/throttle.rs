@@ -0,0 +1,179 @@
+//! 流式响应限速
+//!
+//! 转发流式响应时按调用方应用限制每秒输出的 Token 数，避免后台 Agent
+//! 占满连接或触发 Factory 侧的突发流量检测。采用令牌桶：允许 `burst_tokens`
+//! 的突发，超出部分按速率延迟下发。插件只计算延迟，由宿主在下发该事件前
+//! 等待（`transform_stream_chunk` 响应中的 `delay_ms`），不占用 JSON-RPC 主循环。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 限速配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    /// 默认速率（Token / 秒），为空表示不限速
+    pub tokens_per_second: Option<f64>,
+    /// 按调用方应用覆盖的速率
+    pub per_client: HashMap<String, f64>,
+    /// 允许的突发 Token 数
+    pub burst_tokens: u64,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            tokens_per_second: None,
+            per_client: HashMap::new(),
+            burst_tokens: 200,
+        }
+    }
+}
+
+impl ThrottleConfig {
+    /// 某个调用方的速率
+    pub fn rate_for(&self, client_name: Option<&str>) -> Option<f64> {
+        client_name
+            .and_then(|name| self.per_client.get(name).copied())
+            .or(self.tokens_per_second)
+            .filter(|rate| *rate > 0.0)
+    }
+}
+
+/// 令牌桶
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64, capacity: u64, now: Instant) -> Self {
+        Self {
+            rate,
+            capacity: capacity as f64,
+            tokens: capacity as f64,
+            updated_at: now,
+        }
+    }
+
+    /// 消耗 Token，返回需要等待的时长（余额可透支，透支部分按速率补回）
+    pub fn consume(&mut self, tokens: u64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated_at = now;
+
+        self.tokens -= tokens as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref BUCKETS: Mutex<HashMap<String, TokenBucket>> = Mutex::new(HashMap::new());
+}
+
+/// 估算流式事件中的输出 Token 数（按 4 字符 / Token）
+pub fn estimate_chunk_tokens(chunk: &serde_json::Value) -> u64 {
+    fn text_len(value: &serde_json::Value) -> usize {
+        match value {
+            serde_json::Value::Object(map) => map
+                .iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(text)
+                        if matches!(
+                            key.as_str(),
+                            "text" | "partial_json" | "content" | "delta"
+                        ) =>
+                    {
+                        text.chars().count()
+                    }
+                    _ => text_len(value),
+                })
+                .sum(),
+            serde_json::Value::Array(items) => items.iter().map(text_len).sum(),
+            _ => 0,
+        }
+    }
+
+    text_len(chunk).div_ceil(4) as u64
+}
+
+/// 按调用方限速：扣除 Token，返回下发前需要等待的时长（未配置速率时为 0）
+pub fn reserve(config: &ThrottleConfig, client_name: Option<&str>, tokens: u64) -> Duration {
+    let rate = match config.rate_for(client_name) {
+        Some(rate) if tokens > 0 => rate,
+        _ => return Duration::ZERO,
+    };
+
+    let key = client_name.unwrap_or("").to_string();
+    let now = Instant::now();
+    let mut buckets = BUCKETS.lock().unwrap();
+    let bucket = buckets
+        .entry(key)
+        .or_insert_with(|| TokenBucket::new(rate, config.burst_tokens, now));
+    bucket.rate = rate;
+    bucket.capacity = config.burst_tokens as f64;
+    bucket.consume(tokens, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_burst_then_delays() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(10.0, 20, start);
+
+        assert_eq!(bucket.consume(20, start), Duration::ZERO);
+        assert_eq!(bucket.consume(10, start), Duration::from_secs(1));
+        // 两秒后补回 20 个，扣除透支的 10 个
+        assert_eq!(
+            bucket.consume(10, start + Duration::from_secs(2)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_rate_for_client() {
+        let config = ThrottleConfig {
+            tokens_per_second: Some(50.0),
+            per_client: HashMap::from([("agent".to_string(), 5.0)]),
+            ..Default::default()
+        };
+        assert_eq!(config.rate_for(Some("agent")), Some(5.0));
+        assert_eq!(config.rate_for(Some("Cursor")), Some(50.0));
+        assert_eq!(ThrottleConfig::default().rate_for(None), None);
+    }
+
+    #[test]
+    fn test_reserve_returns_delay_without_sleeping() {
+        let config = ThrottleConfig {
+            per_client: HashMap::from([("reserve-test".to_string(), 10.0)]),
+            burst_tokens: 10,
+            ..Default::default()
+        };
+        let client = Some("reserve-test");
+        assert_eq!(reserve(&config, client, 10), Duration::ZERO);
+        let delay = reserve(&config, client, 10);
+        assert!(delay > Duration::from_millis(900) && delay <= Duration::from_secs(1));
+        assert_eq!(reserve(&config, Some("other"), 1000), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_estimate_chunk_tokens() {
+        let chunk = serde_json::json!({
+            "type": "content_block_delta",
+            "delta": { "type": "text_delta", "text": "12345678" }
+        });
+        assert_eq!(estimate_chunk_tokens(&chunk), 2);
+    }
+}
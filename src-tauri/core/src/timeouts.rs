@@ -0,0 +1,227 @@
+//! 按端点区分的超时
+//!
+//! 各端点的延迟差异很大（推理模型首个 Token 可能要几分钟），统一的客户端
+//! 超时要么误杀长请求，要么让卡住的连接挂很久。这里按端点配置连接、读取、
+//! 总超时以及流式空闲超时（超过 N 秒没有新的 SSE 事件即中止）。acquire 在
+//! metadata 的 `timeouts` 中返回所选端点的生效值，由宿主转发时执行（流式
+//! 响应由宿主读取，空闲超时只能由宿主执行）。插件自己发出的上游请求（批处理、
+//! 广播、上下文摘要、端点探测）通过 `Timeouts::send` / `read_text` 执行连接、
+//! 读取与总超时；WorkOS 刷新、组织查询、价格表等控制面请求使用 `control`。
+//! 0 表示不限制。
+
+use crate::body_text;
+use crate::credentials::EndpointType;
+use crate::tls_trust;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// 读取超时：超过设定时间没有收到任何数据
+#[derive(Debug, thiserror::Error)]
+#[error("上游 {0} 秒内没有返回数据（读取超时）")]
+pub struct ReadTimeout(pub u64);
+
+/// 单个端点的超时覆盖，未设置的项沿用默认值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeoutOverride {
+    pub connect_secs: Option<u64>,
+    pub read_secs: Option<u64>,
+    pub total_secs: Option<u64>,
+    pub stream_idle_secs: Option<u64>,
+}
+
+/// 生效的超时（秒）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timeouts {
+    /// 建立连接（含 TLS 握手）
+    pub connect_secs: u64,
+    /// 两次读到数据之间的最长间隔
+    pub read_secs: u64,
+    /// 整个请求（含流式响应）
+    pub total_secs: u64,
+    /// 流式响应两个事件之间的最长间隔
+    pub stream_idle_secs: u64,
+}
+
+/// 超时配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    pub connect_secs: u64,
+    pub read_secs: u64,
+    pub total_secs: u64,
+    pub stream_idle_secs: u64,
+    pub anthropic: TimeoutOverride,
+    pub openai: TimeoutOverride,
+    pub comm: TimeoutOverride,
+    /// 控制面请求（WorkOS 刷新、组织查询、价格表拉取）
+    pub control: TimeoutOverride,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            connect_secs: 10,
+            read_secs: 300,
+            total_secs: 900,
+            stream_idle_secs: 120,
+            anthropic: TimeoutOverride::default(),
+            openai: TimeoutOverride::default(),
+            comm: TimeoutOverride::default(),
+            control: TimeoutOverride {
+                connect_secs: Some(30),
+                read_secs: Some(60),
+                total_secs: Some(60),
+                stream_idle_secs: Some(0),
+            },
+        }
+    }
+}
+
+impl TimeoutConfig {
+    /// 某端点生效的超时
+    pub fn for_endpoint(&self, endpoint: EndpointType) -> Timeouts {
+        self.resolve(match endpoint {
+            EndpointType::Anthropic => &self.anthropic,
+            EndpointType::OpenAI => &self.openai,
+            EndpointType::Comm => &self.comm,
+        })
+    }
+
+    /// 控制面请求生效的超时
+    pub fn for_control(&self) -> Timeouts {
+        self.resolve(&self.control)
+    }
+
+    fn resolve(&self, overrides: &TimeoutOverride) -> Timeouts {
+        Timeouts {
+            connect_secs: overrides.connect_secs.unwrap_or(self.connect_secs),
+            read_secs: overrides.read_secs.unwrap_or(self.read_secs),
+            total_secs: overrides.total_secs.unwrap_or(self.total_secs),
+            stream_idle_secs: overrides.stream_idle_secs.unwrap_or(self.stream_idle_secs),
+        }
+    }
+}
+
+fn limit(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+impl Timeouts {
+    /// 应用到 reqwest 客户端（连接与总超时；读取超时由 `send` / `read_text` 执行）
+    pub fn apply(&self, mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        if let Some(connect) = limit(self.connect_secs) {
+            builder = builder.connect_timeout(connect);
+        }
+        if let Some(total) = limit(self.total_secs) {
+            builder = builder.timeout(total);
+        }
+        builder
+    }
+
+    /// 按此超时构建客户端
+    pub fn client(&self) -> anyhow::Result<reqwest::Client> {
+        Ok(self.apply(crate::http::client_builder()?).build()?)
+    }
+
+    /// 发送请求，等待响应头的时间受读取超时限制
+    pub async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> anyhow::Result<reqwest::Response> {
+        let sent = request.send();
+        let response = match limit(self.read_secs) {
+            Some(read) => tokio::time::timeout(read, sent)
+                .await
+                .map_err(|_| ReadTimeout(self.read_secs))?,
+            None => sent.await,
+        };
+        response.map_err(tls_trust::send_error)
+    }
+
+    /// 读取响应体为文本，两次收到数据的间隔受读取超时限制
+    pub async fn read_text(&self, mut response: reqwest::Response) -> anyhow::Result<String> {
+        let mut body = Vec::new();
+        loop {
+            let chunk = match limit(self.read_secs) {
+                Some(read) => tokio::time::timeout(read, response.chunk())
+                    .await
+                    .map_err(|_| ReadTimeout(self.read_secs))?,
+                None => response.chunk().await,
+            };
+            match chunk? {
+                Some(chunk) => body.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        Ok(body_text::decode(&body).into_owned())
+    }
+
+    /// 读取响应体并解析 JSON
+    pub async fn read_json<T: DeserializeOwned>(
+        &self,
+        response: reqwest::Response,
+    ) -> anyhow::Result<T> {
+        let text = self.read_text(response).await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_overrides() {
+        let config: TimeoutConfig = serde_json::from_value(serde_json::json!({
+            "stream_idle_secs": 60,
+            "openai": { "read_secs": 600, "stream_idle_secs": 0 }
+        }))
+        .unwrap();
+
+        let anthropic = config.for_endpoint(EndpointType::Anthropic);
+        assert_eq!(anthropic.read_secs, 300);
+        assert_eq!(anthropic.stream_idle_secs, 60);
+
+        let openai = config.for_endpoint(EndpointType::OpenAI);
+        assert_eq!(openai.read_secs, 600);
+        assert_eq!(openai.connect_secs, 10);
+        assert_eq!(openai.stream_idle_secs, 0);
+
+        let control = TimeoutConfig::default().for_control();
+        assert_eq!(control.connect_secs, 30);
+        assert_eq!(control.total_secs, 60);
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // 只返回响应头、不返回响应体的上游
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            let head = "HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\n";
+            socket.write_all(head.as_bytes()).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+
+        let timeouts = Timeouts {
+            connect_secs: 5,
+            read_secs: 1,
+            total_secs: 0,
+            stream_idle_secs: 0,
+        };
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let response = timeouts
+            .send(client.get(format!("http://{}/", addr)))
+            .await
+            .unwrap();
+        let error = timeouts.read_text(response).await.unwrap_err();
+        assert!(error.is::<ReadTimeout>());
+    }
+}
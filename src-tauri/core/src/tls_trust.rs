@@ -0,0 +1,295 @@
+//! TLS 信任设置：自定义 CA 与证书固定
+//!
+//! 处于 TLS 拦截环境（公司代理、杀毒软件）的用户可指定 CA 证书包，或固定
+//! WorkOS / Factory 证书链中某张证书的 SHA-256 指纹。固定指纹时使用自定义
+//! rustls 校验器：先按常规信任链校验，再要求服务器证书本身或其签发链上
+//! 的某张证书与指纹匹配。服务器附带但不在签发链上的证书不算匹配，否则
+//! 拦截方只要把被固定的证书夹带在链中就能通过。
+//! 指纹不匹配会以 `CertificatePinMismatch` 报告，与上游本身的故障区分开。
+
+use anyhow::{Context, Result};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, OwnedTrustAnchor, RootCertStore, ServerName};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// 指纹不匹配时写入 rustls 错误的标记，用于从错误链中识别
+const PIN_MISMATCH_MARKER: &str = "droid-certificate-pin-mismatch";
+
+/// TLS 信任配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsTrustConfig {
+    /// 额外信任的 CA 证书包（PEM 文件路径）
+    pub ca_bundle: Option<String>,
+    /// 是否同时信任内置根证书
+    pub builtin_roots: bool,
+    /// 固定的证书 SHA-256 指纹（十六进制，可带 `sha256:` 前缀与冒号），
+    /// 服务器证书或其签发链（含 `ca_bundle` 中的根证书）上任意一张匹配即通过
+    pub pinned_sha256: Vec<String>,
+}
+
+impl Default for TlsTrustConfig {
+    fn default() -> Self {
+        Self {
+            ca_bundle: None,
+            builtin_roots: true,
+            pinned_sha256: Vec::new(),
+        }
+    }
+}
+
+/// 证书固定校验失败（服务器证书与配置的指纹不符）
+#[derive(Debug, thiserror::Error)]
+#[error("证书固定校验失败：{url} 的证书与配置的指纹不符，网络中可能存在 TLS 拦截")]
+pub struct CertificatePinMismatch {
+    pub url: String,
+}
+
+/// 解析指纹
+pub fn parse_pin(pin: &str) -> Result<[u8; 32]> {
+    let normalized: String = pin
+        .trim()
+        .trim_start_matches("sha256:")
+        .chars()
+        .filter(|c| *c != ':')
+        .collect();
+    let bytes = hex::decode(&normalized).with_context(|| format!("指纹不是十六进制: {}", pin))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("指纹长度应为 32 字节: {}", pin))
+}
+
+/// 读取 PEM 证书包
+pub fn load_ca_bundle(path: &str) -> Result<Vec<Vec<u8>>> {
+    let content = std::fs::read(path).with_context(|| format!("读取 CA 证书包失败: {}", path))?;
+    let certs = rustls_pemfile::certs(&mut content.as_slice())
+        .with_context(|| format!("解析 CA 证书包失败: {}", path))?;
+    if certs.is_empty() {
+        anyhow::bail!("CA 证书包中没有证书: {}", path);
+    }
+    Ok(certs)
+}
+
+fn root_store(config: &TlsTrustConfig) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    if config.builtin_roots {
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+    }
+    if let Some(path) = &config.ca_bundle {
+        for der in load_ca_bundle(path)? {
+            roots.add(&Certificate(der))?;
+        }
+    }
+    Ok(roots)
+}
+
+/// 常规校验后再检查指纹
+struct PinnedVerifier {
+    inner: WebPkiVerifier,
+    pins: Vec<[u8; 32]>,
+    /// `ca_bundle` 中的证书，固定根证书时用于确认它确实签发了服务器证书
+    bundle: Vec<Certificate>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        if chain_matches(&self.pins, end_entity, intermediates, &self.bundle, now) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(PIN_MISMATCH_MARKER.to_string()))
+        }
+    }
+}
+
+/// 与 rustls 默认校验一致的签名算法
+static SIG_ALGS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+/// 服务器证书是否由 `issuer` 签发（可经由服务器提供的中间证书）
+fn issued_by(
+    end_entity: &Certificate,
+    intermediates: &[Certificate],
+    issuer: &Certificate,
+    now: SystemTime,
+) -> bool {
+    let Ok(cert) = webpki::EndEntityCert::try_from(end_entity.0.as_slice()) else {
+        return false;
+    };
+    let Ok(anchor) = webpki::TrustAnchor::try_from_cert_der(&issuer.0) else {
+        return false;
+    };
+    let Ok(time) = webpki::Time::try_from(now) else {
+        return false;
+    };
+    let chain: Vec<&[u8]> = intermediates
+        .iter()
+        .filter(|c| c.0 != issuer.0)
+        .map(|c| c.0.as_slice())
+        .collect();
+    cert.verify_for_usage(
+        SIG_ALGS,
+        &[anchor],
+        &chain,
+        time,
+        webpki::KeyUsage::server_auth(),
+        &[],
+    )
+    .is_ok()
+}
+
+/// 服务器证书或其签发链上是否有证书与指纹匹配
+fn chain_matches(
+    pins: &[[u8; 32]],
+    end_entity: &Certificate,
+    intermediates: &[Certificate],
+    bundle: &[Certificate],
+    now: SystemTime,
+) -> bool {
+    let pinned = |cert: &Certificate| pins.contains(&Sha256::digest(&cert.0).into());
+    if pinned(end_entity) {
+        return true;
+    }
+    intermediates
+        .iter()
+        .chain(bundle)
+        .filter(|cert| pinned(cert))
+        .any(|issuer| issued_by(end_entity, intermediates, issuer, now))
+}
+
+/// 配置了指纹时构建带固定校验的 rustls 配置，否则返回 None
+pub fn pinned_tls_config(
+    config: &TlsTrustConfig,
+    alpn: &[&[u8]],
+    tls13_only: bool,
+) -> Result<Option<rustls::ClientConfig>> {
+    if config.pinned_sha256.is_empty() {
+        return Ok(None);
+    }
+    let pins = config
+        .pinned_sha256
+        .iter()
+        .map(|pin| parse_pin(pin))
+        .collect::<Result<Vec<_>>>()?;
+    let bundle = match &config.ca_bundle {
+        Some(path) => load_ca_bundle(path)?.into_iter().map(Certificate).collect(),
+        None => Vec::new(),
+    };
+    let verifier = PinnedVerifier {
+        inner: WebPkiVerifier::new(root_store(config)?, None),
+        pins,
+        bundle,
+    };
+    let versions: &[&rustls::SupportedProtocolVersion] = if tls13_only {
+        &[&rustls::version::TLS13]
+    } else {
+        rustls::DEFAULT_VERSIONS
+    };
+    let mut tls = rustls::ClientConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(versions)?
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    tls.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+    Ok(Some(tls))
+}
+
+/// 把发送请求的错误转换为 anyhow 错误，证书固定失败时转换为 `CertificatePinMismatch`
+pub fn send_error(error: reqwest::Error) -> anyhow::Error {
+    let pin_failure =
+        std::iter::successors(Some(&error as &(dyn std::error::Error + 'static)), |e| {
+            e.source()
+        })
+        .any(|e| e.to_string().contains(PIN_MISMATCH_MARKER));
+    if pin_failure {
+        let url = error.url().map(|u| u.to_string()).unwrap_or_default();
+        return CertificatePinMismatch { url }.into();
+    }
+    error.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pin() {
+        let hex = "ab".repeat(32);
+        assert_eq!(parse_pin(&hex).unwrap(), [0xab; 32]);
+
+        let colons = vec!["AB"; 32].join(":");
+        assert_eq!(
+            parse_pin(&format!("sha256:{}", colons)).unwrap(),
+            [0xab; 32]
+        );
+
+        assert!(parse_pin("abcd").is_err());
+        assert!(parse_pin("not-hex").is_err());
+    }
+
+    #[test]
+    fn test_chain_matches() {
+        let leaf = Certificate(b"leaf".to_vec());
+        let intermediate = Certificate(b"intermediate".to_vec());
+        let now = SystemTime::now();
+        let leaf_pin: [u8; 32] = Sha256::digest(b"leaf").into();
+        let pin: [u8; 32] = Sha256::digest(b"intermediate").into();
+
+        assert!(chain_matches(&[leaf_pin], &leaf, &[], &[], now));
+        // 夹带在链中、但未签发服务器证书的证书不算匹配
+        let chain = std::slice::from_ref(&intermediate);
+        assert!(!chain_matches(&[pin], &leaf, chain, &[], now));
+        assert!(!chain_matches(&[pin], &leaf, &[], chain, now));
+        assert!(!chain_matches(&[pin], &leaf, &[], &[], now));
+    }
+
+    #[test]
+    fn test_pinned_tls_config() {
+        let mut config = TlsTrustConfig::default();
+        assert!(pinned_tls_config(&config, &[], false).unwrap().is_none());
+
+        config.pinned_sha256 = vec!["ab".repeat(32)];
+        let tls = pinned_tls_config(&config, &[b"h2"], true).unwrap().unwrap();
+        assert_eq!(tls.alpn_protocols, vec![b"h2".to_vec()]);
+
+        config.pinned_sha256 = vec!["bad".to_string()];
+        assert!(pinned_tls_config(&config, &[], false).is_err());
+    }
+}
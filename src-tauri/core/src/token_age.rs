@@ -0,0 +1,197 @@
+//! Refresh Token 寿命跟踪
+//!
+//! WorkOS 的 Refresh Token 会随每次刷新轮换，但整个登录会话有绝对寿命，
+//! 到期后只能重新登录。这里记录会话内第一枚 Refresh Token 的签发时间，
+//! 按配置的寿命估算到期时间；观察到会话因过期被拒绝时，用实际存活时长
+//! 修正估算。到期前 `warn_days` 天发出 `refresh_token_expiring` 事件。
+
+use crate::credentials::{AuthType, DroidCredentials};
+use crate::events;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// 后台检查间隔（秒）
+const CHECK_INTERVAL_SECS: u64 = 6 * 3600;
+
+/// Refresh Token 寿命配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenAgeConfig {
+    /// 会话绝对寿命（天），未观察到实际过期前使用
+    pub absolute_lifetime_days: u32,
+    /// 提前多少天提醒重新登录
+    pub warn_days: u32,
+}
+
+impl Default for TokenAgeConfig {
+    fn default() -> Self {
+        Self {
+            absolute_lifetime_days: 30,
+            warn_days: 3,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    /// 观察到的最短会话寿命
+    static ref OBSERVED_LIFETIME: Mutex<Option<Duration>> = Mutex::new(None);
+    /// 已提醒过的凭证及提醒日期
+    static ref WARNED: Mutex<HashMap<String, chrono::NaiveDate>> = Mutex::new(HashMap::new());
+}
+
+/// Refresh Token 寿命信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshTokenAge {
+    pub credential_id: String,
+    pub issued_at: String,
+    pub age_days: i64,
+    pub estimated_expiry: String,
+    pub days_remaining: i64,
+    /// 估算值是否来自观察到的实际过期
+    pub observed: bool,
+    pub needs_relogin_soon: bool,
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|ts| ts.with_timezone(&Utc))
+}
+
+/// 当前估算的会话寿命
+fn lifetime(config: &TokenAgeConfig) -> (Duration, bool) {
+    let configured = Duration::days(config.absolute_lifetime_days as i64);
+    match *OBSERVED_LIFETIME.lock().unwrap() {
+        Some(observed) if observed < configured => (observed, true),
+        _ => (configured, false),
+    }
+}
+
+/// 记录会话的签发时间（首次获得 Refresh Token 时调用，轮换时保持不变）
+pub fn mark_issued(credential: &mut DroidCredentials) {
+    if credential.refresh_token.is_some() && credential.refresh_token_issued_at.is_none() {
+        credential.refresh_token_issued_at = Some(Utc::now().to_rfc3339());
+    }
+}
+
+/// 刷新失败时检查是否为会话过期，是则记录实际寿命
+pub fn observe_refresh_failure(credential: &DroidCredentials, error: &str) {
+    if !error.contains("invalid_grant") {
+        return;
+    }
+    let Some(issued_at) = credential
+        .refresh_token_issued_at
+        .as_deref()
+        .and_then(parse_time)
+    else {
+        return;
+    };
+    let age = Utc::now() - issued_at;
+    // 刚签发就失败多半不是寿命到期
+    if age < Duration::days(1) {
+        return;
+    }
+    let mut observed = OBSERVED_LIFETIME.lock().unwrap();
+    if observed.map(|current| age < current).unwrap_or(true) {
+        info!("观察到 Refresh Token 会话在 {} 天后过期", age.num_days());
+        *observed = Some(age);
+    }
+}
+
+/// 计算单个凭证的寿命信息
+pub fn age_of(
+    credential_id: &str,
+    credential: &DroidCredentials,
+    now: DateTime<Utc>,
+    config: &TokenAgeConfig,
+) -> Option<RefreshTokenAge> {
+    if credential.auth_type != AuthType::OAuth || credential.refresh_token.is_none() {
+        return None;
+    }
+    let issued_at = credential
+        .refresh_token_issued_at
+        .as_deref()
+        .and_then(parse_time)?;
+    let (lifetime, observed) = lifetime(config);
+    let expiry = issued_at + lifetime;
+    let remaining = expiry - now;
+
+    Some(RefreshTokenAge {
+        credential_id: credential_id.to_string(),
+        issued_at: issued_at.to_rfc3339(),
+        age_days: (now - issued_at).num_days(),
+        estimated_expiry: expiry.to_rfc3339(),
+        days_remaining: remaining.num_days(),
+        observed,
+        needs_relogin_soon: remaining <= Duration::days(config.warn_days as i64),
+    })
+}
+
+/// 后台任务：临近到期时每天提醒一次
+pub async fn run_monitor() {
+    loop {
+        let today = Utc::now().date_naive();
+        for age in crate::provider::refresh_token_ages().await {
+            if !age.needs_relogin_soon {
+                continue;
+            }
+            let mut warned = WARNED.lock().unwrap();
+            if warned.get(&age.credential_id) == Some(&today) {
+                continue;
+            }
+            warned.insert(age.credential_id.clone(), today);
+            warn!(
+                "凭证 {} 的登录会话约 {} 天后过期",
+                age.credential_id, age.days_remaining
+            );
+            events::emit(
+                "refresh_token_expiring",
+                format!(
+                    "凭证登录会话约 {} 天后过期，请重新登录",
+                    age.days_remaining.max(0)
+                ),
+                serde_json::to_value(&age).unwrap_or_default(),
+            );
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_age_estimate() {
+        let now = Utc::now();
+        let credential = DroidCredentials {
+            refresh_token: Some("rt".to_string()),
+            refresh_token_issued_at: Some((now - Duration::days(28)).to_rfc3339()),
+            ..Default::default()
+        };
+        let config = TokenAgeConfig::default();
+
+        let age = age_of("a", &credential, now, &config).unwrap();
+        assert_eq!(age.age_days, 28);
+        assert!(age.needs_relogin_soon);
+
+        let fresh = DroidCredentials {
+            refresh_token_issued_at: Some(now.to_rfc3339()),
+            ..credential.clone()
+        };
+        assert!(
+            !age_of("b", &fresh, now, &config)
+                .unwrap()
+                .needs_relogin_soon
+        );
+
+        let api_key = DroidCredentials {
+            auth_type: AuthType::ApiKey,
+            ..credential
+        };
+        assert!(age_of("c", &api_key, now, &config).is_none());
+    }
+}
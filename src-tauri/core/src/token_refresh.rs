@@ -0,0 +1,437 @@
+//! Token 刷新逻辑
+//!
+//! 支持 WorkOS OAuth Token 刷新，失败按 `refresh_failure` 的类别决定是否重试
+
+use crate::auth::key_ring;
+use crate::auth::workos::{refresh_workos_token, RefreshOutcome};
+use crate::credentials::{AuthType, DroidCredentials, TokenRefreshResult};
+use crate::refresh_failure;
+use crate::refresh_limiter::RefreshPriority;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use tracing::{info, warn};
+
+/// WorkOS 要求用户交互（MFA / 邮箱验证 / 组织选择）时返回的错误
+#[derive(Debug, thiserror::Error)]
+#[error("{}", .0.message())]
+pub struct RefreshChallenge(pub RefreshOutcome);
+
+/// 将刷新结果中的挑战转换为错误
+fn into_refreshed(outcome: RefreshOutcome) -> Result<TokenRefreshResult> {
+    match outcome {
+        RefreshOutcome::Success(result) => Ok(result),
+        challenge => {
+            // 组织选择挑战带有组织名称，顺便缓存
+            if let RefreshOutcome::OrganizationSelectionRequired { organizations, .. } = &challenge
+            {
+                crate::org_names::remember(organizations);
+            }
+            Err(RefreshChallenge(challenge).into())
+        }
+    }
+}
+
+/// 刷新 Token
+pub async fn refresh_token(credential: &mut DroidCredentials) -> Result<TokenRefreshResult> {
+    match credential.auth_type {
+        AuthType::OAuth => refresh_oauth_token(credential).await,
+        AuthType::ApiKey => {
+            // API Key 不需要刷新
+            anyhow::bail!("API Key 认证不需要刷新 Token")
+        }
+    }
+}
+
+/// 旧 Refresh Token 的宽限期（分钟）
+pub const PREVIOUS_REFRESH_TOKEN_GRACE_MINUTES: i64 = 10;
+
+/// 刷新 OAuth Token
+async fn refresh_oauth_token(credential: &mut DroidCredentials) -> Result<TokenRefreshResult> {
+    let refresh_token = credential
+        .refresh_token
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("缺少 refresh_token"))?;
+
+    info!("开始刷新 Droid OAuth Token");
+
+    // 实际用于刷新的 Token：回滚成功时为旧 Token
+    let mut used_token = refresh_token.clone();
+    let result =
+        match refresh_workos_token(&refresh_token, credential.organization_id.as_deref()).await {
+            Ok(outcome) => into_refreshed(outcome)?,
+            Err(e) => {
+                // 当前 Refresh Token 被拒绝时，尝试宽限期内的旧 Token（网络问题换 Token 无济于事）
+                if !refresh_failure::classify(&e).category.is_auth_failure() {
+                    return Err(e);
+                }
+                let previous = match previous_refresh_token(credential) {
+                    Some(previous) => previous,
+                    None => return Err(e),
+                };
+                warn!("当前 Refresh Token 刷新失败，尝试回滚到旧 Token: {}", e);
+                let outcome =
+                    refresh_workos_token(&previous, credential.organization_id.as_deref()).await?;
+                let result = into_refreshed(outcome)?;
+                info!("使用旧 Refresh Token 刷新成功");
+                used_token = previous;
+                result
+            }
+        };
+
+    apply_refreshed(credential, &used_token, &result);
+
+    info!("Droid OAuth Token 刷新成功");
+
+    Ok(result)
+}
+
+/// 把刷新结果写入凭证（`refresh_token` 为本次刷新实际使用的 Token）
+pub fn apply_refreshed(
+    credential: &mut DroidCredentials,
+    refresh_token: &str,
+    result: &TokenRefreshResult,
+) {
+    credential.access_token = Some(result.access_token.clone());
+    match result.refresh_token {
+        Some(ref rt) if rt != refresh_token => {
+            remember_previous_refresh_token(credential, refresh_token);
+            credential.refresh_token = Some(rt.clone());
+        }
+        // 未轮换：继续使用本次刷新用的 Token（回滚时当前 Token 已被拒绝）
+        _ => credential.refresh_token = Some(refresh_token.to_string()),
+    }
+    credential.expires_at = result.expires_at.map(|dt| dt.to_rfc3339());
+    credential.last_refresh = Some(Utc::now().to_rfc3339());
+    // 没有签发时间的旧凭证从首次刷新开始计算
+    crate::token_age::mark_issued(credential);
+
+    if let Some(ref org_id) = result.organization_id {
+        credential.organization_id = Some(org_id.clone());
+    }
+    if let Some(ref user_id) = result.user_id {
+        credential.user_id = Some(user_id.clone());
+    }
+    if let Some(ref email) = result.owner_email {
+        credential.owner_email = Some(email.clone());
+    }
+}
+
+/// 把在凭证副本上完成的刷新写回共享凭证
+///
+/// 刷新在副本上进行，期间共享凭证的统计、健康分等可能已被其他请求更新，
+/// 这里只覆盖刷新会修改的 Token 与账号字段。
+pub fn commit_refreshed(target: &mut DroidCredentials, refreshed: &DroidCredentials) {
+    target.access_token = refreshed.access_token.clone();
+    target.refresh_token = refreshed.refresh_token.clone();
+    target.expires_at = refreshed.expires_at.clone();
+    target.last_refresh = refreshed.last_refresh.clone();
+    target.refresh_token_issued_at = refreshed.refresh_token_issued_at.clone();
+    target.organization_id = refreshed.organization_id.clone();
+    target.user_id = refreshed.user_id.clone();
+    target.owner_email = refreshed.owner_email.clone();
+    commit_previous_refresh_token(target, refreshed);
+}
+
+/// 写回旧 Refresh Token 的状态（刷新失败时宽限期过期的旧 Token 也会被清除）
+pub fn commit_previous_refresh_token(target: &mut DroidCredentials, refreshed: &DroidCredentials) {
+    target.previous_refresh_token = refreshed.previous_refresh_token.clone();
+    target.previous_refresh_token_expires_at = refreshed.previous_refresh_token_expires_at.clone();
+}
+
+/// 记录轮换前的 Refresh Token（加密保存，宽限期后失效）
+pub fn remember_previous_refresh_token(credential: &mut DroidCredentials, refresh_token: &str) {
+    match key_ring::encrypt(refresh_token) {
+        Ok(encrypted) => {
+            credential.previous_refresh_token = Some(encrypted);
+            credential.previous_refresh_token_expires_at = Some(
+                (Utc::now() + Duration::minutes(PREVIOUS_REFRESH_TOKEN_GRACE_MINUTES)).to_rfc3339(),
+            );
+        }
+        Err(e) => {
+            // 原有的旧 Token 比刚用过的还旧，留着回滚只会失败，一并清除
+            warn!("旧 Refresh Token 加密失败，跳过保存: {}", e);
+            credential.previous_refresh_token = None;
+            credential.previous_refresh_token_expires_at = None;
+        }
+    }
+}
+
+/// 获取宽限期内的旧 Refresh Token（已解密），过期则清除
+pub fn previous_refresh_token(credential: &mut DroidCredentials) -> Option<String> {
+    let in_grace = credential
+        .previous_refresh_token_expires_at
+        .as_deref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|expires| expires > Utc::now())
+        .unwrap_or(false);
+
+    if !in_grace {
+        credential.previous_refresh_token = None;
+        credential.previous_refresh_token_expires_at = None;
+        return None;
+    }
+
+    let encrypted = credential.previous_refresh_token.as_deref()?;
+    key_ring::decrypt(encrypted).ok()
+}
+
+/// 检查 Token 是否已过期
+pub fn is_token_expired(expires_at: Option<&str>) -> bool {
+    if let Some(expires_str) = expires_at {
+        if let Ok(expires) = DateTime::parse_from_rfc3339(expires_str) {
+            let now = Utc::now();
+            // 提前 5 分钟判断为过期
+            return expires <= now + Duration::minutes(5);
+        }
+    }
+    // 如果没有过期时间信息，保守地认为可能需要刷新
+    true
+}
+
+/// 检查 Token 是否即将过期（1 小时内）
+pub fn is_token_expiring_soon(expires_at: Option<&str>) -> bool {
+    if let Some(expires_str) = expires_at {
+        if let Ok(expiry) = DateTime::parse_from_rfc3339(expires_str) {
+            let now = Utc::now();
+            let threshold = now + Duration::hours(1);
+            return expiry < threshold;
+        }
+    }
+    false
+}
+
+/// 请求路径上（紧急）的刷新最多尝试的次数
+const URGENT_ATTEMPTS: u32 = 2;
+
+/// 后台刷新最多尝试的次数
+const BACKGROUND_ATTEMPTS: u32 = 4;
+
+/// 紧急刷新最多等待多久再重试；限流要求等待更久时直接失败，由冷却接手
+const URGENT_MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// 第 `attempt` 次（从 0 开始）刷新失败后的重试间隔，不应重试时为 None
+///
+/// 按 `refresh_failure` 的类别决定：会话失效、被拒绝等重试也不会成功的错误
+/// 与需要用户交互的挑战不重试，限流时遵循 Retry-After。
+pub fn retry_delay(
+    error: &anyhow::Error,
+    attempt: u32,
+    priority: RefreshPriority,
+) -> Option<std::time::Duration> {
+    let (max_attempts, max_delay) = match priority {
+        RefreshPriority::Urgent => (URGENT_ATTEMPTS, Some(URGENT_MAX_RETRY_DELAY)),
+        RefreshPriority::Background => (BACKGROUND_ATTEMPTS, None),
+    };
+    if attempt + 1 >= max_attempts || error.is::<RefreshChallenge>() {
+        return None;
+    }
+    let failure = refresh_failure::classify(error);
+    if !failure.category.is_retryable() {
+        return None;
+    }
+    let delay = failure
+        .category
+        .retry_delay(attempt, failure.retry_after_secs);
+    max_delay.is_none_or(|max| delay <= max).then_some(delay)
+}
+
+/// 按失败类别重试刷新
+///
+/// `refresh_once` 完成一次刷新，返回本次使用的上下文（凭证副本等）与刷新
+/// 结果；外层错误（凭证不存在、已锁定等）不重试。两次尝试之间不持有任何锁
+/// 或限流许可。
+pub async fn with_retry<T, R, F, Fut>(
+    priority: RefreshPriority,
+    mut refresh_once: F,
+) -> Result<(T, Result<R>)>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<(T, Result<R>)>>,
+{
+    let mut attempt = 0;
+    loop {
+        let (context, result) = refresh_once().await?;
+        let delay = match &result {
+            Err(e) if !crate::control::is_paused() => retry_delay(e, attempt, priority),
+            _ => None,
+        };
+        let Some(delay) = delay else {
+            return Ok((context, result));
+        };
+        if let Err(e) = &result {
+            warn!(
+                "Token 刷新失败 (第 {} 次), {:?} 后重试: {}",
+                attempt + 1,
+                delay,
+                e
+            );
+        }
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_refreshed_keeps_concurrent_stats() {
+        let mut shared = DroidCredentials {
+            access_token: Some("old-at".to_string()),
+            refresh_token: Some("old-rt".to_string()),
+            ..Default::default()
+        };
+        let mut refreshed = shared.clone();
+        refreshed.access_token = Some("new-at".to_string());
+        refreshed.refresh_token = Some("new-rt".to_string());
+        // 刷新期间其他请求更新了统计
+        shared.usage_count = 7;
+
+        commit_refreshed(&mut shared, &refreshed);
+        assert_eq!(shared.access_token.as_deref(), Some("new-at"));
+        assert_eq!(shared.refresh_token.as_deref(), Some("new-rt"));
+        assert_eq!(shared.usage_count, 7);
+    }
+
+    #[test]
+    fn test_apply_refreshed_after_rollback() {
+        let result = |refresh_token: Option<&str>| TokenRefreshResult {
+            access_token: "new-at".to_string(),
+            refresh_token: refresh_token.map(str::to_string),
+            expires_at: None,
+            organization_id: None,
+            user_id: None,
+            owner_email: None,
+        };
+
+        // 回滚到旧 Token 后轮换：宽限期内保留的是实际用过的旧 Token
+        let mut credential = DroidCredentials {
+            refresh_token: Some("rejected-rt".to_string()),
+            ..Default::default()
+        };
+        apply_refreshed(&mut credential, "previous-rt", &result(Some("new-rt")));
+        assert_eq!(credential.refresh_token.as_deref(), Some("new-rt"));
+        assert_eq!(
+            previous_refresh_token(&mut credential).as_deref(),
+            Some("previous-rt")
+        );
+
+        // 回滚后未轮换：改用旧 Token，而不是保留被拒绝的
+        let mut credential = DroidCredentials {
+            refresh_token: Some("rejected-rt".to_string()),
+            ..Default::default()
+        };
+        apply_refreshed(&mut credential, "previous-rt", &result(None));
+        assert_eq!(credential.refresh_token.as_deref(), Some("previous-rt"));
+        assert_eq!(credential.access_token.as_deref(), Some("new-at"));
+    }
+
+    fn failure(status: u16, retry_after: Option<&str>) -> anyhow::Error {
+        let headers: Vec<(String, String)> = retry_after
+            .map(|v| ("Retry-After".to_string(), v.to_string()))
+            .into_iter()
+            .collect();
+        refresh_failure::RefreshFailure::from_response(status, "{}")
+            .with_retry_after(&headers)
+            .into()
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        use std::time::Duration as StdDuration;
+        let background = RefreshPriority::Background;
+        let urgent = RefreshPriority::Urgent;
+        assert_eq!(
+            retry_delay(&failure(429, Some("3")), 0, background),
+            Some(StdDuration::from_secs(3))
+        );
+        // 紧急刷新不等待过长的 Retry-After
+        assert_eq!(retry_delay(&failure(429, Some("60")), 0, urgent), None);
+        assert!(retry_delay(&failure(400, None), 0, background).is_none());
+        assert!(retry_delay(&failure(503, None), 0, background).is_some());
+        assert!(retry_delay(&failure(503, None), BACKGROUND_ATTEMPTS - 1, background).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_with_retry_follows_category_policy() {
+        // 限流（Retry-After: 0）后重试成功
+        let mut calls = 0;
+        let (context, result) = with_retry(RefreshPriority::Background, || {
+            calls += 1;
+            let attempt = calls;
+            async move {
+                let result = if attempt == 1 {
+                    Err(failure(429, Some("0")))
+                } else {
+                    Ok("refreshed")
+                };
+                Ok((attempt, result))
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(context, 2);
+        assert_eq!(result.unwrap(), "refreshed");
+
+        // 会话失效不重试
+        let mut calls = 0;
+        let (_, result) = with_retry(RefreshPriority::Background, || {
+            calls += 1;
+            async { Ok(((), Err::<(), _>(failure(400, None)))) }
+        })
+        .await
+        .unwrap();
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_is_token_expired() {
+        // 已过期
+        let expired = (Utc::now() - Duration::hours(1)).to_rfc3339();
+        assert!(is_token_expired(Some(&expired)));
+
+        // 即将过期（5分钟内）
+        let expiring = (Utc::now() + Duration::minutes(3)).to_rfc3339();
+        assert!(is_token_expired(Some(&expiring)));
+
+        // 未过期
+        let valid = (Utc::now() + Duration::hours(1)).to_rfc3339();
+        assert!(!is_token_expired(Some(&valid)));
+
+        // 无过期时间
+        assert!(is_token_expired(None));
+    }
+
+    #[test]
+    fn test_is_token_expiring_soon() {
+        // 1小时内过期
+        let expiring = (Utc::now() + Duration::minutes(30)).to_rfc3339();
+        assert!(is_token_expiring_soon(Some(&expiring)));
+
+        // 超过1小时
+        let valid = (Utc::now() + Duration::hours(2)).to_rfc3339();
+        assert!(!is_token_expiring_soon(Some(&valid)));
+    }
+
+    #[test]
+    fn test_previous_refresh_token_grace_window() {
+        let mut credential = DroidCredentials::default();
+        remember_previous_refresh_token(&mut credential, "old-refresh-token");
+        assert_ne!(
+            credential.previous_refresh_token.as_deref(),
+            Some("old-refresh-token")
+        );
+        assert_eq!(
+            previous_refresh_token(&mut credential).as_deref(),
+            Some("old-refresh-token")
+        );
+
+        // 宽限期已过
+        credential.previous_refresh_token_expires_at =
+            Some((Utc::now() - Duration::minutes(1)).to_rfc3339());
+        assert!(previous_refresh_token(&mut credential).is_none());
+        assert!(credential.previous_refresh_token.is_none());
+    }
+}
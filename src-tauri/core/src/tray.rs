@@ -0,0 +1,205 @@
+//! 托盘快捷操作
+//!
+//! 宿主最小化到托盘后，菜单内容与点击后的动作由插件提供：暂停 / 恢复、
+//! 查看状态、复制本地端点地址。`menu` 按当前状态生成菜单项，`run_action`
+//! 执行动作并返回提示文字；复制类动作把要写入剪贴板的文本交给宿主。
+
+use crate::control;
+use serde::{Deserialize, Serialize};
+
+/// 托盘配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrayConfig {
+    /// 编辑器连接的本地端点地址（未设置时由宿主在调用时传入）
+    pub local_endpoint: Option<String>,
+}
+
+/// 托盘动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrayAction {
+    Pause,
+    Resume,
+    TogglePause,
+    Status,
+    CopyEndpoint,
+}
+
+/// 托盘展示的状态摘要
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrayStatus {
+    pub paused: bool,
+    /// 上游维护导致的全局退避
+    pub maintenance: bool,
+    /// 凭证池已就绪
+    pub ready: bool,
+    /// 可用凭证数（健康且在启用时段内）
+    pub usable_credentials: usize,
+    pub total_credentials: usize,
+    pub local_endpoint: Option<String>,
+}
+
+impl TrayStatus {
+    /// 托盘提示文字
+    pub fn tooltip(&self) -> String {
+        let state = if self.paused {
+            "已暂停"
+        } else if self.maintenance {
+            "上游维护中"
+        } else if !self.ready {
+            "启动中"
+        } else if self.usable_credentials == 0 {
+            "无可用凭证"
+        } else {
+            "运行中"
+        };
+        format!(
+            "Droid Provider: {}（可用凭证 {}/{}）",
+            state, self.usable_credentials, self.total_credentials
+        )
+    }
+}
+
+/// 菜单项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrayMenuItem {
+    pub action: TrayAction,
+    pub label: String,
+    pub enabled: bool,
+}
+
+/// 动作执行结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrayActionResult {
+    pub message: String,
+    /// 需要宿主写入剪贴板的文本
+    #[serde(default)]
+    pub clipboard: Option<String>,
+    pub status: TrayStatus,
+}
+
+/// 汇总当前状态；`local_endpoint` 为宿主传入的地址，优先于配置
+pub async fn status(local_endpoint: Option<String>) -> TrayStatus {
+    let credentials = crate::provider::list_credentials().await;
+    TrayStatus {
+        paused: control::is_paused(),
+        maintenance: crate::maintenance::is_active(),
+        ready: crate::startup::is_ready(),
+        usable_credentials: credentials
+            .iter()
+            .filter(|c| c.health_score > 0 && c.in_active_hours)
+            .count(),
+        total_credentials: credentials.len(),
+        local_endpoint: local_endpoint.or(crate::config::get_config().tray.local_endpoint),
+    }
+}
+
+/// 按状态生成菜单
+pub fn menu(status: &TrayStatus) -> Vec<TrayMenuItem> {
+    let toggle = match status.paused {
+        true => "恢复",
+        false => "暂停",
+    };
+    vec![
+        TrayMenuItem {
+            action: TrayAction::Status,
+            label: status.tooltip(),
+            enabled: true,
+        },
+        TrayMenuItem {
+            action: TrayAction::TogglePause,
+            label: toggle.to_string(),
+            enabled: true,
+        },
+        TrayMenuItem {
+            action: TrayAction::CopyEndpoint,
+            label: match &status.local_endpoint {
+                Some(endpoint) => format!("复制本地端点（{}）", endpoint),
+                None => "复制本地端点".to_string(),
+            },
+            enabled: status.local_endpoint.is_some(),
+        },
+    ]
+}
+
+/// 执行托盘动作
+pub async fn run_action(
+    action: TrayAction,
+    local_endpoint: Option<String>,
+) -> anyhow::Result<TrayActionResult> {
+    let action = match action {
+        TrayAction::TogglePause if control::is_paused() => TrayAction::Resume,
+        TrayAction::TogglePause => TrayAction::Pause,
+        other => other,
+    };
+    let mut clipboard = None;
+    let message = match action {
+        TrayAction::Pause => {
+            control::pause(Some("托盘菜单"));
+            "已暂停，所有请求将被拦截".to_string()
+        }
+        TrayAction::Resume => {
+            control::resume();
+            "已恢复".to_string()
+        }
+        TrayAction::CopyEndpoint => {
+            let endpoint = status(local_endpoint.clone())
+                .await
+                .local_endpoint
+                .ok_or_else(|| anyhow::anyhow!("未配置本地端点地址"))?;
+            clipboard = Some(endpoint.clone());
+            format!("已复制 {}", endpoint)
+        }
+        TrayAction::Status | TrayAction::TogglePause => String::new(),
+    };
+    let status = status(local_endpoint).await;
+    Ok(TrayActionResult {
+        message: match message.is_empty() {
+            true => status.tooltip(),
+            false => message,
+        },
+        clipboard,
+        status,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tooltip() {
+        let mut status = TrayStatus {
+            ready: true,
+            usable_credentials: 2,
+            total_credentials: 3,
+            ..Default::default()
+        };
+        assert_eq!(status.tooltip(), "Droid Provider: 运行中（可用凭证 2/3）");
+        status.maintenance = true;
+        assert!(status.tooltip().contains("上游维护中"));
+        status.paused = true;
+        assert!(status.tooltip().contains("已暂停"));
+    }
+
+    #[test]
+    fn test_menu() {
+        let status = TrayStatus {
+            paused: true,
+            ..Default::default()
+        };
+        let items = menu(&status);
+        assert_eq!(items[1].label, "恢复");
+        assert!(!items[2].enabled);
+
+        let status = TrayStatus {
+            local_endpoint: Some("http://127.0.0.1:8999".to_string()),
+            ..Default::default()
+        };
+        let items = menu(&status);
+        assert_eq!(items[1].label, "暂停");
+        assert!(items[2].enabled);
+        assert!(items[2].label.contains("127.0.0.1:8999"));
+    }
+}
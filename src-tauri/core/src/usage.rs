@@ -0,0 +1,490 @@
+//! 使用量报表导出
+//!
+//! 读取 `usage.jsonl`，按 日期 / 模型 / 凭证 汇总 Token 与估算费用，
+//! 导出为 CSV 或 JSON，便于向客户报销 Factory 用量。可按请求标签筛选，
+//! 或按某个标签分组统计，用于按项目分摊费用。
+
+use crate::pricing::estimate_cost;
+use crate::request_tags::{self, Tags};
+use crate::stats::{usage_file_path, UsageRecord};
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+/// 日期范围（含首尾，按 UTC 日期）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageRange {
+    #[serde(default)]
+    pub start: Option<NaiveDate>,
+    #[serde(default)]
+    pub end: Option<NaiveDate>,
+}
+
+impl UsageRange {
+    fn contains(&self, date: NaiveDate) -> bool {
+        self.start.map(|s| date >= s).unwrap_or(true) && self.end.map(|e| date <= e).unwrap_or(true)
+    }
+}
+
+/// 汇总行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRow {
+    pub date: NaiveDate,
+    pub model: String,
+    pub credential_id: String,
+    /// 凭证所属组织的名称（已解析时）
+    #[serde(default)]
+    pub organization_name: Option<String>,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub estimated_cost_usd: f64,
+}
+
+/// 按调用方应用汇总的用量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientUsage {
+    /// 调用方应用名，未标识的请求归入 "unknown"
+    pub client_name: String,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub estimated_cost_usd: f64,
+}
+
+/// 按标签值汇总的用量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagUsage {
+    /// 标签值，没有该标签的请求归入 "untagged"
+    pub value: String,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub estimated_cost_usd: f64,
+}
+
+/// 按账号汇总的用量（同一账号的多个凭证合并）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountUsage {
+    /// 账号（邮箱或 user_id），没有账号信息的凭证按凭证 ID 单列
+    pub account: String,
+    pub credential_ids: Vec<String>,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub estimated_cost_usd: f64,
+}
+
+/// 导出结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageExport {
+    pub rows: usize,
+    /// 写入的文件路径；未指定路径时为空，内容放在 `content` 中
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+/// 读取全部使用记录
+pub fn load_records() -> Result<Vec<UsageRecord>> {
+    let content = match std::fs::read_to_string(usage_file_path()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// 只保留标签满足筛选条件的记录（条件为空时全部保留）
+pub fn filter_by_tags(mut records: Vec<UsageRecord>, filter: &Tags) -> Vec<UsageRecord> {
+    records.retain(|record| request_tags::matches(&record.tags, filter));
+    records
+}
+
+/// 按 日期 / 模型 / 凭证 汇总
+pub fn aggregate(records: &[UsageRecord], range: &UsageRange) -> Vec<UsageRow> {
+    let mut rows: BTreeMap<(NaiveDate, String, String), UsageRow> = BTreeMap::new();
+
+    for record in records {
+        let date = match DateTime::parse_from_rfc3339(&record.timestamp) {
+            Ok(ts) => ts.date_naive(),
+            Err(_) => continue,
+        };
+        if !range.contains(date) {
+            continue;
+        }
+
+        let model = record
+            .model
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string());
+        let row = rows
+            .entry((date, model.clone(), record.credential_id.clone()))
+            .or_insert_with(|| UsageRow {
+                date,
+                model: model.clone(),
+                credential_id: record.credential_id.clone(),
+                organization_name: None,
+                requests: 0,
+                input_tokens: 0,
+                output_tokens: 0,
+                estimated_cost_usd: 0.0,
+            });
+        row.requests += 1;
+        row.input_tokens += record.input_tokens;
+        row.output_tokens += record.output_tokens;
+        row.estimated_cost_usd += estimate_cost(&model, record.input_tokens, record.output_tokens);
+    }
+
+    rows.into_values().collect()
+}
+
+/// 按调用方应用汇总
+pub fn aggregate_by_client(records: &[UsageRecord], range: &UsageRange) -> Vec<ClientUsage> {
+    let mut clients: BTreeMap<String, ClientUsage> = BTreeMap::new();
+
+    for record in records {
+        let in_range = DateTime::parse_from_rfc3339(&record.timestamp)
+            .map(|ts| range.contains(ts.date_naive()))
+            .unwrap_or(false);
+        if !in_range {
+            continue;
+        }
+
+        let client_name = record
+            .client_name
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string());
+        let entry = clients
+            .entry(client_name.clone())
+            .or_insert_with(|| ClientUsage {
+                client_name,
+                requests: 0,
+                input_tokens: 0,
+                output_tokens: 0,
+                estimated_cost_usd: 0.0,
+            });
+        entry.requests += 1;
+        entry.input_tokens += record.input_tokens;
+        entry.output_tokens += record.output_tokens;
+        if let Some(ref model) = record.model {
+            entry.estimated_cost_usd +=
+                estimate_cost(model, record.input_tokens, record.output_tokens);
+        }
+    }
+
+    clients.into_values().collect()
+}
+
+/// 按某个标签的值汇总
+pub fn aggregate_by_tag(records: &[UsageRecord], range: &UsageRange, key: &str) -> Vec<TagUsage> {
+    let mut values: BTreeMap<String, TagUsage> = BTreeMap::new();
+
+    for record in records {
+        let in_range = DateTime::parse_from_rfc3339(&record.timestamp)
+            .map(|ts| range.contains(ts.date_naive()))
+            .unwrap_or(false);
+        if !in_range {
+            continue;
+        }
+
+        let value = record
+            .tags
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| "untagged".to_string());
+        let entry = values.entry(value.clone()).or_insert_with(|| TagUsage {
+            value,
+            requests: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            estimated_cost_usd: 0.0,
+        });
+        entry.requests += 1;
+        entry.input_tokens += record.input_tokens;
+        entry.output_tokens += record.output_tokens;
+        if let Some(ref model) = record.model {
+            entry.estimated_cost_usd +=
+                estimate_cost(model, record.input_tokens, record.output_tokens);
+        }
+    }
+
+    values.into_values().collect()
+}
+
+/// 按账号汇总，`accounts` 为 凭证 ID → 账号
+pub fn aggregate_by_account(
+    records: &[UsageRecord],
+    range: &UsageRange,
+    accounts: &HashMap<String, String>,
+) -> Vec<AccountUsage> {
+    let mut totals: BTreeMap<String, AccountUsage> = BTreeMap::new();
+
+    for record in records {
+        let in_range = DateTime::parse_from_rfc3339(&record.timestamp)
+            .map(|ts| range.contains(ts.date_naive()))
+            .unwrap_or(false);
+        if !in_range {
+            continue;
+        }
+
+        let account = accounts
+            .get(&record.credential_id)
+            .cloned()
+            .unwrap_or_else(|| record.credential_id.clone());
+        let entry = totals
+            .entry(account.clone())
+            .or_insert_with(|| AccountUsage {
+                account,
+                credential_ids: Vec::new(),
+                requests: 0,
+                input_tokens: 0,
+                output_tokens: 0,
+                estimated_cost_usd: 0.0,
+            });
+        if !entry.credential_ids.contains(&record.credential_id) {
+            entry.credential_ids.push(record.credential_id.clone());
+        }
+        entry.requests += 1;
+        entry.input_tokens += record.input_tokens;
+        entry.output_tokens += record.output_tokens;
+        if let Some(ref model) = record.model {
+            entry.estimated_cost_usd +=
+                estimate_cost(model, record.input_tokens, record.output_tokens);
+        }
+    }
+
+    totals
+        .into_values()
+        .map(|mut entry| {
+            entry.credential_ids.sort();
+            entry
+        })
+        .collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 渲染为 CSV
+pub fn to_csv(rows: &[UsageRow]) -> String {
+    let mut output = String::from(
+        "date,model,credential_id,organization_name,requests,input_tokens,output_tokens,\
+         estimated_cost_usd\n",
+    );
+    for row in rows {
+        output.push_str(&format!(
+            "{},{},{},{},{},{},{},{:.6}\n",
+            row.date,
+            csv_field(&row.model),
+            csv_field(&row.credential_id),
+            csv_field(row.organization_name.as_deref().unwrap_or("")),
+            row.requests,
+            row.input_tokens,
+            row.output_tokens,
+            row.estimated_cost_usd
+        ));
+    }
+    output
+}
+
+/// 导出使用量；指定路径时写入文件，否则直接返回内容
+///
+/// `org_names` 为凭证 ID 到组织名称的映射，`tags` 为标签筛选条件
+pub fn export_usage(
+    range: &UsageRange,
+    format: ExportFormat,
+    path: Option<&Path>,
+    org_names: &HashMap<String, String>,
+    tags: &Tags,
+) -> Result<UsageExport> {
+    let records = filter_by_tags(load_records()?, tags);
+    let mut rows = aggregate(&records, range);
+    for row in &mut rows {
+        row.organization_name = org_names.get(&row.credential_id).cloned();
+    }
+    let content = match format {
+        ExportFormat::Csv => to_csv(&rows),
+        ExportFormat::Json => serde_json::to_string_pretty(&rows)?,
+    };
+
+    match path {
+        Some(path) => {
+            std::fs::write(path, content)?;
+            Ok(UsageExport {
+                rows: rows.len(),
+                path: Some(path.display().to_string()),
+                content: None,
+            })
+        }
+        None => Ok(UsageExport {
+            rows: rows.len(),
+            path: None,
+            content: Some(content),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: &str, model: &str, input: u64, output: u64) -> UsageRecord {
+        UsageRecord {
+            timestamp: timestamp.to_string(),
+            credential_id: "cred".to_string(),
+            model: Some(model.to_string()),
+            endpoint_type: None,
+            client_name: None,
+            input_tokens: input,
+            output_tokens: output,
+            latency_ms: None,
+            failover_from: None,
+            tenant: None,
+            tags: Default::default(),
+            success: true,
+        }
+    }
+
+    #[test]
+    fn test_aggregate_by_day_and_model() {
+        let records = vec![
+            record(
+                "2025-10-01T10:00:00Z",
+                "claude-sonnet-4-20250514",
+                1_000_000,
+                0,
+            ),
+            record(
+                "2025-10-01T12:00:00Z",
+                "claude-sonnet-4-20250514",
+                0,
+                1_000_000,
+            ),
+            record("2025-10-02T09:00:00Z", "gpt-5-2025-08-07", 10, 10),
+        ];
+
+        let rows = aggregate(&records, &UsageRange::default());
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].requests, 2);
+        assert!((rows[0].estimated_cost_usd - 18.0).abs() < 1e-9);
+
+        let range = UsageRange {
+            start: NaiveDate::from_ymd_opt(2025, 10, 2),
+            end: None,
+        };
+        assert_eq!(aggregate(&records, &range).len(), 1);
+    }
+
+    #[test]
+    fn test_aggregate_by_client() {
+        let mut cursor = record("2025-10-01T10:00:00Z", "gpt-5-2025-08-07", 100, 50);
+        cursor.client_name = Some("Cursor".to_string());
+        let records = vec![
+            cursor.clone(),
+            cursor,
+            record("2025-10-01T11:00:00Z", "gpt-5-2025-08-07", 1, 1),
+        ];
+
+        let clients = aggregate_by_client(&records, &UsageRange::default());
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients[0].client_name, "Cursor");
+        assert_eq!(clients[0].requests, 2);
+        assert_eq!(clients[0].input_tokens, 200);
+        assert_eq!(clients[1].client_name, "unknown");
+    }
+
+    #[test]
+    fn test_tags() {
+        let mut billing = record("2025-10-01T10:00:00Z", "gpt-5-2025-08-07", 100, 50);
+        billing
+            .tags
+            .insert("project".to_string(), "billing".to_string());
+        let mut search = billing.clone();
+        search
+            .tags
+            .insert("project".to_string(), "search".to_string());
+        let untagged = record("2025-10-01T11:00:00Z", "gpt-5-2025-08-07", 1, 1);
+        let records = vec![billing.clone(), billing, search, untagged];
+
+        let projects = aggregate_by_tag(&records, &UsageRange::default(), "project");
+        let values: Vec<_> = projects
+            .iter()
+            .map(|p| (p.value.as_str(), p.requests))
+            .collect();
+        assert_eq!(values, vec![("billing", 2), ("search", 1), ("untagged", 1)]);
+
+        let filter = Tags::from([("project".to_string(), "billing".to_string())]);
+        let filtered = filter_by_tags(records, &filter);
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(
+            aggregate(&filtered, &UsageRange::default())[0].input_tokens,
+            200
+        );
+    }
+
+    #[test]
+    fn test_aggregate_by_account() {
+        let first = record("2025-10-01T10:00:00Z", "gpt-5-2025-08-07", 100, 50);
+        let second = UsageRecord {
+            credential_id: "cred-2".to_string(),
+            ..first.clone()
+        };
+        let unlinked = UsageRecord {
+            credential_id: "cred-3".to_string(),
+            ..first.clone()
+        };
+        let accounts = HashMap::from([
+            ("cred".to_string(), "dev@example.com".to_string()),
+            ("cred-2".to_string(), "dev@example.com".to_string()),
+        ]);
+
+        let records = [first, second, unlinked];
+        let rows = aggregate_by_account(&records, &UsageRange::default(), &accounts);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].account, "cred-3");
+        assert_eq!(rows[1].account, "dev@example.com");
+        assert_eq!(rows[1].credential_ids, vec!["cred", "cred-2"]);
+        assert_eq!(rows[1].input_tokens, 200);
+    }
+
+    #[test]
+    fn test_csv_organization_name() {
+        let mut rows = aggregate(
+            &[record("2025-10-01T10:00:00Z", "gpt-5-2025-08-07", 1, 1)],
+            &UsageRange::default(),
+        );
+        rows[0].organization_name = Some("Acme, Inc".to_string());
+        let csv = to_csv(&rows);
+        assert!(csv.starts_with("date,model,credential_id,organization_name,requests,"));
+        assert!(csv.contains(",cred,\"Acme, Inc\",1,1,1,"));
+    }
+
+    #[test]
+    fn test_csv_escaping() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,106 @@
+//! User-Agent 管理
+//!
+//! 优先级：凭证单独设置 > 配置 `factory.user_agent` > 本机 factory-cli 版本
+//! （解析 `droid --version` / `factory --version`）> 内置默认值。
+
+use crate::auth::workos::FACTORY_USER_AGENT;
+use crate::config::get_config;
+use crate::credentials::DroidCredentials;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::sync::OnceLock;
+use tracing::debug;
+
+/// factory-cli 可执行文件名
+pub const FACTORY_CLI_BINARIES: &[&str] = &["droid", "factory"];
+
+/// Factory 客户端配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FactoryConfig {
+    /// 手动指定的 User-Agent
+    pub user_agent: Option<String>,
+    /// 是否检测本机 factory-cli 版本
+    pub detect_cli_version: bool,
+}
+
+impl Default for FactoryConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: None,
+            detect_cli_version: true,
+        }
+    }
+}
+
+static DETECTED_VERSION: OnceLock<Option<String>> = OnceLock::new();
+
+/// 从 `--version` 输出中解析版本号
+pub fn parse_version(output: &str) -> Option<String> {
+    let re = regex::Regex::new(r"\d+\.\d+\.\d+(?:-[0-9A-Za-z.]+)?").ok()?;
+    re.find(output).map(|m| m.as_str().to_string())
+}
+
+/// 本机安装的 factory-cli 版本（只检测一次）
+pub fn detected_cli_version() -> Option<String> {
+    DETECTED_VERSION
+        .get_or_init(|| {
+            FACTORY_CLI_BINARIES.iter().find_map(|binary| {
+                let output = Command::new(binary).arg("--version").output().ok()?;
+                if !output.status.success() {
+                    return None;
+                }
+                let version = parse_version(&String::from_utf8_lossy(&output.stdout))?;
+                debug!("检测到 factory-cli 版本: {} ({})", version, binary);
+                Some(version)
+            })
+        })
+        .clone()
+}
+
+/// 解析请求使用的 User-Agent
+pub fn resolve(credential: Option<&DroidCredentials>) -> String {
+    if let Some(user_agent) = credential.and_then(|c| c.user_agent.clone()) {
+        return user_agent;
+    }
+
+    let config = get_config().factory;
+    if let Some(user_agent) = config.user_agent.filter(|ua| !ua.is_empty()) {
+        return user_agent;
+    }
+    if config.detect_cli_version {
+        if let Some(version) = detected_cli_version() {
+            return format!("factory-cli/{}", version);
+        }
+    }
+    FACTORY_USER_AGENT.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("0.40.2\n").as_deref(), Some("0.40.2"));
+        assert_eq!(
+            parse_version("droid v1.2.3-beta.1").as_deref(),
+            Some("1.2.3-beta.1")
+        );
+        assert!(parse_version("unknown").is_none());
+    }
+
+    #[test]
+    fn test_credential_override_wins() {
+        let credential = DroidCredentials {
+            user_agent: Some("factory-cli/9.9.9".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(resolve(Some(&credential)), "factory-cli/9.9.9");
+        // 未安装 factory-cli 时使用内置默认值
+        let expected = detected_cli_version()
+            .map(|v| format!("factory-cli/{}", v))
+            .unwrap_or_else(|| FACTORY_USER_AGENT.to_string());
+        assert_eq!(resolve(None), expected);
+    }
+}
@@ -0,0 +1,143 @@
+//! 系统休眠唤醒检测
+//!
+//! Linux 与 macOS 上休眠期间单调时钟停止而墙上时钟继续走，两者的差值超过
+//! 阈值即视为刚从休眠恢复。Windows 的单调时钟包含休眠时间，两者同步前进，
+//! 只能从检测间隔被拉长来推断：恢复后第一次检测距上次远超检测间隔。后者
+//! 也可能由进程长时间卡住引起，且无法区分休眠与卡顿；Windows 宿主应监听
+//! 系统的电源事件（`WM_POWERBROADCAST` / `PBT_APMRESUMEAUTOMATIC`），并通过
+//! `notify_resume` 转发，检测循环只作兜底。唤醒后
+//! 立即检查所有 OAuth 凭证，过期或即将过期的按刷新限流逐个刷新，并发出
+//! `system_resumed` 事件，宿主据此重建本地监听与上游连接池。
+
+use crate::events;
+use crate::provider;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, warn};
+
+/// 检测间隔（秒）
+const CHECK_INTERVAL_SECS: u64 = 15;
+
+/// 唤醒检测配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WakeConfig {
+    pub enabled: bool,
+    /// 时钟差超过该秒数才视为休眠过
+    pub min_sleep_secs: u64,
+}
+
+impl Default for WakeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_sleep_secs: 60,
+        }
+    }
+}
+
+/// 唤醒处理结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeReport {
+    /// 估算的休眠时长（秒），由宿主通知且未提供时为 None
+    pub slept_seconds: Option<u64>,
+    /// 已安排刷新的凭证
+    pub refreshing: Vec<String>,
+}
+
+/// 推断休眠时长
+///
+/// 取两种迹象中较大者：墙上时钟比单调时钟多走的时间（单调时钟休眠时停止的
+/// 平台），以及单调时钟超出检测间隔的时间（单调时钟包含休眠时间的 Windows）。
+pub fn detect_sleep(
+    wall_elapsed: Duration,
+    monotonic_elapsed: Duration,
+    interval: Duration,
+    min_sleep: Duration,
+) -> Option<Duration> {
+    let clock_gap = wall_elapsed.saturating_sub(monotonic_elapsed);
+    let overrun = monotonic_elapsed.saturating_sub(interval);
+    Some(clock_gap.max(overrun)).filter(|gap| *gap >= min_sleep)
+}
+
+/// 唤醒后检查凭证并通知宿主
+pub async fn handle_resume(slept: Option<Duration>) -> ResumeReport {
+    let refreshing = provider::credentials_needing_refresh().await;
+    for credential_id in &refreshing {
+        // 限流器负责控制并发与间隔，这里只管排队
+        let credential_id = credential_id.clone();
+        tokio::spawn(async move {
+            if let Err(e) = provider::refresh_token(&credential_id).await {
+                warn!("唤醒后刷新凭证 {} 失败: {}", credential_id, e);
+            }
+        });
+    }
+
+    let report = ResumeReport {
+        slept_seconds: slept.map(|d| d.as_secs()),
+        refreshing,
+    };
+    info!(
+        "系统已从休眠恢复（{:?} 秒），{} 个凭证待刷新",
+        report.slept_seconds,
+        report.refreshing.len()
+    );
+    events::emit(
+        "system_resumed",
+        format!(
+            "系统已从休眠恢复，{} 个凭证待刷新，请重建上游连接",
+            report.refreshing.len()
+        ),
+        serde_json::to_value(&report).unwrap_or_default(),
+    );
+    report
+}
+
+/// 后台检测循环
+pub async fn run_detector() {
+    let interval = Duration::from_secs(CHECK_INTERVAL_SECS);
+    let (mut last_wall, mut last_monotonic) = (SystemTime::now(), Instant::now());
+
+    loop {
+        tokio::time::sleep(interval).await;
+        let (wall, monotonic) = (SystemTime::now(), Instant::now());
+        // 墙上时钟被往回调时 duration_since 出错，按未休眠处理
+        let wall_elapsed = wall.duration_since(last_wall).unwrap_or_default();
+        let monotonic_elapsed = monotonic - last_monotonic;
+        (last_wall, last_monotonic) = (wall, monotonic);
+
+        let config = crate::config::get_config().wake;
+        if !config.enabled {
+            continue;
+        }
+        let min_sleep = Duration::from_secs(config.min_sleep_secs);
+        if let Some(slept) = detect_sleep(wall_elapsed, monotonic_elapsed, interval, min_sleep) {
+            handle_resume(Some(slept)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_sleep() {
+        let min = Duration::from_secs(60);
+        let secs = Duration::from_secs;
+        let interval = secs(15);
+        assert_eq!(
+            detect_sleep(secs(615), secs(15), interval, min),
+            Some(secs(600))
+        );
+        assert!(detect_sleep(secs(16), secs(15), interval, min).is_none());
+        // 墙上时钟回拨
+        assert!(detect_sleep(secs(0), secs(15), interval, min).is_none());
+        // 单调时钟包含休眠时间（Windows）：两者同步，检测间隔被拉长
+        assert_eq!(
+            detect_sleep(secs(615), secs(615), interval, min),
+            Some(secs(600))
+        );
+        assert!(detect_sleep(secs(20), secs(20), interval, min).is_none());
+    }
+}
@@ -3,15 +3,22 @@
 //! 这是一个独立的 CLI 工具，通过 JSON-RPC 与 ProxyCast 通信。
 //! 支持 WorkOS OAuth 和 API Key 两种认证方式。
 
-mod auth;
-mod credentials;
-mod provider;
-mod token_refresh;
-
 use clap::{Parser, Subcommand};
+use droid_provider_core::auth::{key_ring, master_key, token_seal};
+use droid_provider_core::credentials::{EndpointType, ReleaseReport};
+use droid_provider_core::token_refresh::RefreshChallenge;
+use droid_provider_core::{
+    app_lock, autostart, batch, body_text, broadcast, capabilities, chaos, compression, config,
+    control, dead_credentials, dedup, deprecation, digest, doctor, documents, events, failover,
+    hooks, keepalive, limits, logging, maintenance, mock, model_overrides, pricing, profiles,
+    provider, refresh_failure, relogin, request_tags, response_meta, response_repair, retention,
+    retry_budget, secret_lock, setup, sharing, spool, startup, stats, store_lock, stream_progress,
+    tenants, token_age, tray, usage, wake,
+};
 use serde::{Deserialize, Serialize};
 use std::io::{self, BufRead, Write};
-use tracing::{debug, info};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info, warn};
 
 /// Droid Provider CLI
 #[derive(Parser)]
@@ -42,7 +49,12 @@ enum Commands {
     Refresh {
         #[arg(long)]
         credential_id: String,
+        /// Print the redacted WorkOS request/response trace
+        #[arg(long)]
+        verbose: bool,
     },
+    /// Run self-diagnostics
+    Doctor,
 }
 
 /// JSON-RPC Request
@@ -83,13 +95,22 @@ impl JsonRpcResponse {
     }
 
     fn error(id: serde_json::Value, code: i32, message: String) -> Self {
+        Self::error_with_data(id, code, message, None)
+    }
+
+    fn error_with_data(
+        id: serde_json::Value,
+        code: i32,
+        message: String,
+        data: Option<serde_json::Value>,
+    ) -> Self {
         Self {
             jsonrpc: "2.0".to_string(),
             result: None,
             error: Some(JsonRpcError {
                 code,
                 message,
-                data: None,
+                data,
             }),
             id,
         }
@@ -98,14 +119,8 @@ impl JsonRpcResponse {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive("droid_provider=debug".parse().unwrap()),
-        )
-        .with_writer(std::io::stderr)
-        .init();
+    // Initialize logging（宿主下发配置后可在运行时调整）
+    logging::init(&config::get_config().logging)?;
 
     let cli = Cli::parse();
 
@@ -118,7 +133,7 @@ async fn main() -> anyhow::Result<()> {
                 println!("{}", serde_json::to_string_pretty(&info)?);
             }
             Commands::Models => {
-                let models = provider::list_models();
+                let models = provider::list_models().await;
                 println!("{}", serde_json::to_string_pretty(&models)?);
             }
             Commands::Validate { credential_id } => {
@@ -128,13 +143,24 @@ async fn main() -> anyhow::Result<()> {
                     Err(e) => eprintln!("Error: {}", e),
                 }
             }
-            Commands::Refresh { credential_id } => {
+            Commands::Refresh {
+                credential_id,
+                verbose: true,
+            } => match provider::debug_refresh(&credential_id).await {
+                Ok(trace) => println!("{}", serde_json::to_string_pretty(&trace)?),
+                Err(e) => eprintln!("Error: {}", e),
+            },
+            Commands::Refresh { credential_id, .. } => {
                 info!("Refreshing token for: {}", credential_id);
                 match provider::refresh_token(&credential_id).await {
                     Ok(result) => println!("{}", serde_json::to_string_pretty(&result)?),
                     Err(e) => eprintln!("Error: {}", e),
                 }
             }
+            Commands::Doctor => {
+                let report = doctor::run().await;
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
         }
     } else {
         // Default: print info
@@ -145,12 +171,49 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// 启动时校验配置与环境，有问题时通知宿主
+fn report_startup_config() {
+    let report = match config::validate_config(None) {
+        Ok(report) => report,
+        Err(e) => return warn!("配置校验失败: {}", e),
+    };
+    if report.findings.is_empty() {
+        return;
+    }
+    for finding in &report.findings {
+        warn!("配置问题 {}: {}", finding.field, finding.message);
+    }
+    events::emit(
+        "config_findings",
+        format!("配置检查发现 {} 个问题", report.findings.len()),
+        serde_json::to_value(&report).unwrap_or_default(),
+    );
+}
+
 /// Run in JSON-RPC mode
 async fn run_json_rpc_mode() -> anyhow::Result<()> {
     info!("Starting Droid Provider in JSON-RPC mode");
+    if let Err(e) = store_lock::acquire("json_rpc") {
+        warn!("凭证存储加锁失败: {}", e);
+    }
+    report_startup_config();
+    match provider::load_saved_credentials().await {
+        Ok(loaded) => info!("已从凭证文件载入 {} 个凭证", loaded),
+        Err(e) => warn!("凭证文件载入失败，本次运行不写入凭证文件: {:#}", e),
+    }
+    tokio::spawn(provider::run_store_writer());
+    tokio::spawn(digest::run_scheduler());
+    tokio::spawn(retention::run_pruner());
+    tokio::spawn(token_age::run_monitor());
+    tokio::spawn(wake::run_detector());
+    tokio::spawn(relogin::run_reminder());
+    tokio::spawn(pricing::run_updater());
+    tokio::spawn(keepalive::run_pinger());
+    tokio::spawn(maintenance::run_prober());
+    tokio::spawn(dead_credentials::run_monitor());
 
     let stdin = io::stdin();
-    let mut stdout = io::stdout();
+    let stdout = Arc::new(Mutex::new(io::stdout()));
 
     for line in stdin.lock().lines() {
         let line = line?;
@@ -158,9 +221,19 @@ async fn run_json_rpc_mode() -> anyhow::Result<()> {
             continue;
         }
 
-        debug!("Received: {}", line);
-
         let response = match serde_json::from_str::<JsonRpcRequest>(&line) {
+            // 可能排队等待的请求放到后台处理，按 id 乱序返回，
+            // 否则等待期间宿主加载凭证的请求也会被阻塞
+            Ok(request) if CONCURRENT_METHODS.contains(&request.method.as_str()) => {
+                let stdout = stdout.clone();
+                tokio::spawn(async move {
+                    let response = handle_request(request).await;
+                    if let Err(e) = write_response(&stdout, &response) {
+                        warn!("写入响应失败: {}", e);
+                    }
+                });
+                continue;
+            }
             Ok(request) => handle_request(request).await,
             Err(e) => JsonRpcResponse::error(
                 serde_json::Value::Null,
@@ -169,51 +242,232 @@ async fn run_json_rpc_mode() -> anyhow::Result<()> {
             ),
         };
 
-        let response_str = serde_json::to_string(&response)?;
-        debug!("Sending: {}", response_str);
+        write_response(&stdout, &response)?;
+    }
+
+    stats::flush().await;
+    if let Err(e) = provider::save_store().await {
+        warn!("凭证文件写入失败: {:#}", e);
+    }
+    Ok(())
+}
+
+/// 可能长时间等待（启动队列、暂停排队）的方法，在后台并发处理
+///
+/// 暂停排队时 `acquire_credential` 会一直等到 `resume`，若在主循环中串行处理，
+/// `resume` 本身也会排在它后面，只能等到超时。
+///
+/// 去重跟随者的 `await_shared_response` 要等原请求的 `transform_response`，
+/// 上下文裁剪的摘要策略会在 `transform_request` 中请求上游，广播评估要等
+/// 所有目标返回，同样不能占住主循环。
+const CONCURRENT_METHODS: &[&str] = &[
+    "acquire_credential",
+    "await_shared_response",
+    "transform_request",
+    "broadcast_request",
+];
+
+/// 应用锁定时需要先解锁的方法（查看密钥与日志、修改凭证、配置与主密钥）
+///
+/// 暂停属于紧急止损，锁定时仍然可用；恢复需要先解锁。
+const LOCKED_METHODS: &[&str] = &[
+    "set_model_override",
+    "remove_model_override",
+    "create_credential",
+    "seal_credential_tokens",
+    "snooze_relogin",
+    "complete_relogin",
+    "update_config",
+    "get_recent_logs",
+    "clear_model_deprecation",
+    "update_pricing",
+    "setup_select_endpoint",
+    "purge_usage",
+    "clear_maintenance",
+    "retry_store_lock",
+    "resume",
+    "set_autostart",
+    "set_credential_models",
+    "set_credential_model_access",
+    "set_credential_read_only",
+    "set_credential_active_hours",
+    "set_credential_tier",
+    "delete_credential",
+    "prune_dead_credentials",
+    "set_credential_quota_group",
+    "set_credential_user_agent",
+    "update_credential_notes",
+    "promote_canary",
+    "create_org_credentials",
+    "clone_credential",
+    "debug_refresh",
+    "import_env_api_keys",
+    "export_credential",
+    "request_secret_reveal",
+    "reveal_secret",
+    "import_credential",
+    "setup_import_credential",
+    "generate_tenant_key",
+    "get_recovery_phrase",
+    "recover_master_key",
+    "add_encryption_key",
+];
 
-        writeln!(stdout, "{}", response_str)?;
-        stdout.flush()?;
+/// 写出一行响应
+fn write_response(stdout: &Mutex<io::Stdout>, response: &JsonRpcResponse) -> anyhow::Result<()> {
+    let response_str = serde_json::to_string(response)?;
+    match &response.error {
+        Some(error) => debug!("Sending: id {} error {}", response.id, error.code),
+        None => debug!("Sending: id {}", response.id),
     }
 
+    let mut stdout = stdout.lock().unwrap();
+    writeln!(stdout, "{}", response_str)?;
+    stdout.flush()?;
     Ok(())
 }
 
+/// 按请求参数（profile / tenant_key / client_name）选出转换配置档
+fn request_profile(params: &serde_json::Value) -> anyhow::Result<Option<String>> {
+    profiles::select(
+        &config::get_config(),
+        params["profile"].as_str(),
+        params["tenant_key"].as_str(),
+        params["client_name"].as_str(),
+    )
+}
+
+/// 可选参数：未传时取默认值，格式不对时返回错误说明（而不是静默按默认处理）
+fn optional_param<T: serde::de::DeserializeOwned + Default>(
+    params: &serde_json::Value,
+    name: &str,
+) -> Result<T, String> {
+    match &params[name] {
+        serde_json::Value::Null => Ok(T::default()),
+        value => serde_json::from_value(value.clone()).map_err(|e| format!("{} 无效: {}", name, e)),
+    }
+}
+
+/// 用量查询的日期范围与标签过滤
+fn usage_query(
+    params: &serde_json::Value,
+) -> Result<(usage::UsageRange, request_tags::Tags), String> {
+    let range: usage::UsageRange = optional_param(params, "range")?;
+    if let (Some(start), Some(end)) = (range.start, range.end) {
+        if start > end {
+            return Err(format!(
+                "range 无效: 开始日期 {} 晚于结束日期 {}",
+                start, end
+            ));
+        }
+    }
+    Ok((range, optional_param(params, "tags")?))
+}
+
 /// Handle a JSON-RPC request
 async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
     let id = request.id.clone();
+    // 参数与结果可能含 Token、密钥与请求正文，只记录方法名
+    debug!("Received: {} (id {})", request.method, id);
+
+    if LOCKED_METHODS.contains(&request.method.as_str()) {
+        if let Err(e) = app_lock::ensure_unlocked() {
+            return JsonRpcResponse::error(id, -32004, e.to_string());
+        }
+    }
 
     match request.method.as_str() {
         "get_info" => {
             let info = get_plugin_info();
             JsonRpcResponse::success(id, serde_json::to_value(info).unwrap())
         }
+        "get_provider_capabilities" => {
+            let capabilities = capabilities::describe(&config::get_config());
+            JsonRpcResponse::success(id, serde_json::to_value(capabilities).unwrap())
+        }
         "list_models" => {
-            let models = provider::list_models();
+            let models = provider::list_models().await;
             JsonRpcResponse::success(id, serde_json::to_value(models).unwrap())
         }
+        "set_model_override" => {
+            let model = request.params["model"].as_str().unwrap_or("");
+            match serde_json::from_value(request.params["override"].clone()) {
+                Ok(model_override) => match model_overrides::set_override(model, model_override) {
+                    Ok(()) => JsonRpcResponse::success(id, serde_json::json!({})),
+                    Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+                },
+                Err(e) => JsonRpcResponse::error(id, -32602, format!("Invalid override: {}", e)),
+            }
+        }
+        "remove_model_override" => {
+            let model = request.params["model"].as_str().unwrap_or("");
+            match model_overrides::remove_override(model) {
+                Ok(removed) => {
+                    JsonRpcResponse::success(id, serde_json::json!({ "removed": removed }))
+                }
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
         "supports_model" => {
             let model = request.params["model"].as_str().unwrap_or("");
-            let supports = provider::supports_model(model);
+            let supports = provider::supports_model(model).await;
             JsonRpcResponse::success(id, serde_json::json!({ "supports": supports }))
         }
         "acquire_credential" => {
             let model = request.params["model"].as_str().unwrap_or("");
-            match provider::acquire_credential(model).await {
+            let options: provider::AcquireOptions =
+                match serde_json::from_value(request.params.clone()) {
+                    Ok(options) => options,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            id,
+                            -32602,
+                            format!("Invalid options: {}", e),
+                        )
+                    }
+                };
+            match provider::acquire_credential(model, &options).await {
                 Ok(credential) => {
                     JsonRpcResponse::success(id, serde_json::to_value(credential).unwrap())
                 }
-                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+                Err(e) => match e.downcast_ref::<retry_budget::RetryBudgetExceeded>() {
+                    Some(exceeded) => JsonRpcResponse::error_with_data(
+                        id,
+                        -32003,
+                        exceeded.to_string(),
+                        serde_json::to_value(exceeded).ok(),
+                    ),
+                    // 重试中途失败也附上已有的尝试记录
+                    None => {
+                        let attempts = options
+                            .request_id
+                            .as_deref()
+                            .map(retry_budget::attempts)
+                            .filter(|attempts| !attempts.is_empty())
+                            .map(|attempts| serde_json::json!({ "attempts": attempts }));
+                        JsonRpcResponse::error_with_data(id, -32000, e.to_string(), attempts)
+                    }
+                },
             }
         }
         "release_credential" => {
             let credential_id = request.params["credential_id"].as_str().unwrap_or("");
-            let result = &request.params["result"];
-            match provider::release_credential(credential_id, result.clone()).await {
+            let report = match ReleaseReport::from_value(request.params["result"].clone()) {
+                Ok(report) => report,
+                Err(e) => {
+                    return JsonRpcResponse::error(id, -32602, format!("Invalid result: {}", e))
+                }
+            };
+            match provider::release_credential(credential_id, report).await {
                 Ok(_) => JsonRpcResponse::success(id, serde_json::json!({})),
                 Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
             }
         }
+        "get_request_attempts" => {
+            let request_id = request.params["request_id"].as_str().unwrap_or("");
+            let attempts = retry_budget::attempts(request_id);
+            JsonRpcResponse::success(id, serde_json::json!({ "attempts": attempts }))
+        }
         "validate_credential" => {
             let credential_id = request.params["credential_id"].as_str().unwrap_or("");
             match provider::validate_credential(credential_id).await {
@@ -221,10 +475,304 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
                 Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
             }
         }
+        "set_credential_models" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let models = match serde_json::from_value(request.params["models"].clone()) {
+                Ok(models) => models,
+                Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+            };
+            match provider::set_extra_models(credential_id, models).await {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "set_credential_model_access" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let parse = |field: &str| {
+                serde_json::from_value::<Option<Vec<String>>>(request.params[field].clone())
+                    .map(Option::unwrap_or_default)
+            };
+            let (allowed, blocked) = match (parse("allowed_models"), parse("blocked_models")) {
+                (Ok(allowed), Ok(blocked)) => (allowed, blocked),
+                (Err(e), _) | (_, Err(e)) => {
+                    return JsonRpcResponse::error(id, -32602, e.to_string())
+                }
+            };
+            match provider::set_model_access(credential_id, allowed, blocked).await {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "set_credential_read_only" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let read_only = request.params["read_only"].as_bool().unwrap_or(true);
+            match provider::set_read_only(credential_id, read_only).await {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "set_credential_tier" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let tier = match request.params["tier"].as_u64().map(u8::try_from) {
+                Some(Ok(tier)) => tier,
+                _ => {
+                    return JsonRpcResponse::error(id, -32602, "tier 应为 0-255 的整数".to_string())
+                }
+            };
+            match provider::set_tier(credential_id, tier).await {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "set_credential_active_hours" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let active_hours = match request.params.get("active_hours") {
+                None | Some(serde_json::Value::Null) => None,
+                Some(value) => match serde_json::from_value(value.clone()) {
+                    Ok(schedule) => Some(schedule),
+                    Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+                },
+            };
+            match provider::set_active_hours(credential_id, active_hours).await {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "set_credential_quota_group" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let quota_group = request.params["quota_group"].as_str().map(str::to_string);
+            match provider::set_quota_group(credential_id, quota_group).await {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "list_quota_groups" => JsonRpcResponse::success(
+            id,
+            serde_json::to_value(provider::list_quota_groups().await).unwrap(),
+        ),
+        "list_account_groups" => {
+            let linked_only = request.params["linked_only"].as_bool().unwrap_or(true);
+            let groups = provider::list_account_groups(linked_only).await;
+            JsonRpcResponse::success(id, serde_json::to_value(groups).unwrap())
+        }
+        "set_credential_user_agent" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let user_agent = request.params["user_agent"].as_str().map(|s| s.to_string());
+            match provider::set_user_agent(credential_id, user_agent).await {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "list_credentials" => {
+            let credentials = provider::list_credentials().await;
+            JsonRpcResponse::success(id, serde_json::to_value(credentials).unwrap())
+        }
+        "update_credential_notes" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let notes = request.params["notes"].as_str().map(|s| s.to_string());
+            let metadata = match request.params.get("metadata").filter(|m| !m.is_null()) {
+                Some(metadata) => match serde_json::from_value(metadata.clone()) {
+                    Ok(metadata) => Some(metadata),
+                    Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+                },
+                None => None,
+            };
+            match provider::set_credential_notes(credential_id, notes, metadata).await {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "promote_canary" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            match provider::promote_canary(credential_id).await {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "get_credential_errors" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            match provider::get_credential_errors(credential_id).await {
+                Ok(errors) => JsonRpcResponse::success(id, serde_json::to_value(errors).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "resolve_organization_names" => {
+            let force = request.params["force"].as_bool().unwrap_or(false);
+            let resolved = provider::resolve_org_names(force).await;
+            JsonRpcResponse::success(id, serde_json::json!({ "resolved": resolved }))
+        }
+        "discover_organizations" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            match provider::discover_organizations(credential_id).await {
+                Ok(orgs) => JsonRpcResponse::success(id, serde_json::to_value(orgs).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "create_org_credentials" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let org_ids: Vec<String> =
+                match serde_json::from_value(request.params["organization_ids"].clone()) {
+                    Ok(org_ids) => org_ids,
+                    Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+                };
+            match provider::create_org_credentials(credential_id, &org_ids).await {
+                Ok(ids) => {
+                    JsonRpcResponse::success(id, serde_json::json!({ "credential_ids": ids }))
+                }
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "clone_credential" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let overrides = match serde_json::from_value(request.params["overrides"].clone()) {
+                Ok(overrides) => overrides,
+                Err(_) if request.params["overrides"].is_null() => Default::default(),
+                Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+            };
+            match provider::clone_credential(credential_id, overrides).await {
+                Ok(new_id) => {
+                    JsonRpcResponse::success(id, serde_json::json!({ "credential_id": new_id }))
+                }
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "get_refresh_token_ages" => {
+            let ages = provider::refresh_token_ages().await;
+            JsonRpcResponse::success(id, serde_json::to_value(ages).unwrap())
+        }
+        "generate_tenant_key" => {
+            // 密钥只返回这一次，配置中保存哈希
+            let (key, hash) = tenants::generate_key();
+            JsonRpcResponse::success(id, serde_json::json!({ "key": key, "key_hash": hash }))
+        }
+        "get_tenant_stats" => {
+            let stats = tenants::stats(&config::get_config().tenants);
+            JsonRpcResponse::success(id, serde_json::to_value(stats).unwrap())
+        }
+        "list_relogin_reminders" => {
+            JsonRpcResponse::success(id, serde_json::to_value(relogin::list()).unwrap())
+        }
+        "snooze_relogin" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let minutes = request.params["minutes"].as_u64().unwrap_or(60) as u32;
+            match relogin::snooze(credential_id, minutes) {
+                Some(reminder) => {
+                    JsonRpcResponse::success(id, serde_json::to_value(reminder).unwrap())
+                }
+                None => JsonRpcResponse::error(
+                    id,
+                    -32000,
+                    format!("凭证 {} 没有待处理的重新登录提醒", credential_id),
+                ),
+            }
+        }
+        "complete_relogin" => {
+            // 宿主可直接转交回调地址，或传入解析好的 code / state
+            let callback = match request.params["callback_url"].as_str() {
+                Some(url) => relogin::parse_callback(url),
+                None => match (
+                    request.params["code"].as_str(),
+                    request.params["state"].as_str(),
+                ) {
+                    (Some(code), Some(state)) => Ok((code.to_string(), state.to_string())),
+                    _ => Err(anyhow::anyhow!("缺少 code 或 state")),
+                },
+            };
+            let (code, state) = match callback {
+                Ok(callback) => callback,
+                Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+            };
+            match provider::complete_relogin(&state, &code).await {
+                Ok(credential_id) => JsonRpcResponse::success(
+                    id,
+                    serde_json::json!({ "credential_id": credential_id }),
+                ),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "list_dead_credentials" => {
+            let dead = provider::dead_credentials().await;
+            JsonRpcResponse::success(id, serde_json::to_value(dead).unwrap())
+        }
+        "delete_credential" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            match provider::delete_credential(credential_id).await {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "prune_dead_credentials" => {
+            let action = match request.params.get("action") {
+                None | Some(serde_json::Value::Null) => dead_credentials::CleanupAction::Archive,
+                Some(value) => match serde_json::from_value(value.clone()) {
+                    Ok(action) => action,
+                    Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+                },
+            };
+            let credential_ids = serde_json::from_value(request.params["credential_ids"].clone())
+                .unwrap_or_default();
+            match provider::prune_dead_credentials(credential_ids, action).await {
+                Ok(report) => JsonRpcResponse::success(id, serde_json::to_value(report).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "get_health_scores" => {
+            let scores = provider::get_health_scores().await;
+            JsonRpcResponse::success(id, serde_json::to_value(scores).unwrap())
+        }
+        "notify_resume" => {
+            // 宿主转发系统唤醒事件；Windows 上单调时钟包含休眠时间，后台检测
+            // 只能兜底，宿主应在收到 PBT_APMRESUMEAUTOMATIC 时调用
+            let slept = request.params["slept_seconds"]
+                .as_u64()
+                .map(std::time::Duration::from_secs);
+            let report = wake::handle_resume(slept).await;
+            JsonRpcResponse::success(id, serde_json::to_value(report).unwrap())
+        }
         "refresh_token" => {
             let credential_id = request.params["credential_id"].as_str().unwrap_or("");
-            match provider::refresh_token(credential_id).await {
+            // 宿主可声明凭证即将被使用，优先于批量刷新
+            let priority =
+                serde_json::from_value(request.params["priority"].clone()).unwrap_or_default();
+            match provider::refresh_token_with_priority(credential_id, priority).await {
                 Ok(result) => JsonRpcResponse::success(id, serde_json::to_value(result).unwrap()),
+                Err(e) => {
+                    // WorkOS 挑战附带结构化数据，供 UI 引导用户
+                    match e.downcast_ref::<RefreshChallenge>() {
+                        Some(challenge) => JsonRpcResponse::error_with_data(
+                            id,
+                            -32001,
+                            e.to_string(),
+                            serde_json::to_value(&challenge.0).ok(),
+                        ),
+                        // 附上失败类别，UI 据此提示检查网络还是重新登录
+                        None => JsonRpcResponse::error_with_data(
+                            id,
+                            -32000,
+                            e.to_string(),
+                            serde_json::to_value(refresh_failure::classify(&e)).ok(),
+                        ),
+                    }
+                }
+            }
+        }
+        "debug_refresh" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            match provider::debug_refresh(credential_id).await {
+                Ok(trace) => JsonRpcResponse::success(id, serde_json::to_value(trace).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "recover_unauthorized" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let lease_id = request.params["lease_id"].as_str();
+            let access_token = request.params["access_token"]
+                .as_str()
+                .map(|t| t.trim_start_matches("Bearer "));
+            match provider::recover_unauthorized(credential_id, lease_id, access_token).await {
+                Ok(acquired) => {
+                    JsonRpcResponse::success(id, serde_json::to_value(acquired).unwrap())
+                }
                 Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
             }
         }
@@ -232,30 +780,434 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
             let auth_type = request.params["auth_type"].as_str().unwrap_or("oauth");
             let config = request.params["config"].clone();
             match provider::create_credential(auth_type, config).await {
-                Ok(credential_id) => {
-                    JsonRpcResponse::success(id, serde_json::json!({ "credential_id": credential_id }))
+                Ok(credential_id) => JsonRpcResponse::success(
+                    id,
+                    serde_json::json!({ "credential_id": credential_id }),
+                ),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "seal_credential_tokens" => {
+            // 宿主保存凭证配置前加密 Token / API Key，之后原样传给 create_credential
+            let mut sealed = serde_json::Map::new();
+            for field in ["access_token", "refresh_token"] {
+                let Some(token) = request.params[field].as_str() else {
+                    continue;
+                };
+                match token_seal::seal_value(token) {
+                    Ok(value) => sealed.insert(field.to_string(), serde_json::json!(value)),
+                    Err(e) => return JsonRpcResponse::error(id, -32000, e.to_string()),
+                };
+            }
+            if let Some(keys) = request.params["api_keys"].as_array() {
+                let keys: Result<Vec<String>, _> = keys
+                    .iter()
+                    .filter_map(|k| k.as_str())
+                    .map(token_seal::seal_value)
+                    .collect();
+                match keys {
+                    Ok(keys) => sealed.insert("api_keys".to_string(), serde_json::json!(keys)),
+                    Err(e) => return JsonRpcResponse::error(id, -32000, e.to_string()),
+                };
+            }
+            JsonRpcResponse::success(id, serde_json::Value::Object(sealed))
+        }
+        "import_env_api_keys" => {
+            let credential_id = request.params["credential_id"].as_str();
+            let dotenv_path = request.params["dotenv_path"]
+                .as_str()
+                .map(std::path::Path::new);
+            match provider::import_env_api_keys(credential_id, dotenv_path).await {
+                Ok(result) => JsonRpcResponse::success(id, serde_json::to_value(result).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "export_credential" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let ttl_minutes = request.params["ttl_minutes"]
+                .as_i64()
+                .unwrap_or(sharing::DEFAULT_PAIRING_TTL_MINUTES);
+            match provider::export_credential(credential_id, ttl_minutes).await {
+                Ok(export) => JsonRpcResponse::success(id, serde_json::to_value(export).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "request_secret_reveal" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let key_id = request.params["key_id"].as_str().unwrap_or("");
+            match provider::request_secret_reveal(credential_id, key_id).await {
+                Ok(challenge) => {
+                    JsonRpcResponse::success(id, serde_json::to_value(challenge).unwrap())
+                }
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "reveal_secret" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let key_id = request.params["key_id"].as_str().unwrap_or("");
+            let token = match request.params["confirmation_token"].as_str() {
+                Some(token) => token,
+                None => {
+                    return JsonRpcResponse::error(
+                        id,
+                        -32602,
+                        "缺少 confirmation_token".to_string(),
+                    )
                 }
+            };
+            let os_auth_confirmed = request.params["os_auth_confirmed"]
+                .as_bool()
+                .unwrap_or(false);
+            match provider::reveal_secret(credential_id, key_id, token, os_auth_confirmed).await {
+                Ok(secret) => JsonRpcResponse::success(id, serde_json::json!({ "secret": secret })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "import_credential" => {
+            let payload = request.params["payload"].as_str().unwrap_or("");
+            let pairing_code = request.params["pairing_code"].as_str().unwrap_or("");
+            match provider::import_credential(payload, pairing_code).await {
+                Ok(credential_id) => JsonRpcResponse::success(
+                    id,
+                    serde_json::json!({ "credential_id": credential_id }),
+                ),
                 Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
             }
         }
+        "mock_forward" => {
+            if !mock::is_enabled() {
+                return JsonRpcResponse::error(id, -32000, "模拟模式未开启".to_string());
+            }
+            let response = mock::forward(&request.params["request"]);
+            JsonRpcResponse::success(id, serde_json::to_value(response).unwrap())
+        }
+        "salvage_stream" => {
+            let events = match request.params["events"].as_array() {
+                Some(events) => events.clone(),
+                None => return JsonRpcResponse::error(id, -32602, "Invalid events".to_string()),
+            };
+            let credential_id = request.params["credential_id"].as_str();
+            let error = request.params["error"].as_str().unwrap_or("stream error");
+            let response = provider::salvage_stream(credential_id, &events, error).await;
+            JsonRpcResponse::success(id, serde_json::json!({ "response": response }))
+        }
+        "translate_request_to_chat" => {
+            let translated = failover::anthropic_to_chat(&request.params["request"]);
+            JsonRpcResponse::success(id, serde_json::json!({ "request": translated }))
+        }
+        "translate_response_from_chat" => {
+            let translated = failover::chat_to_anthropic(&request.params["response"]);
+            JsonRpcResponse::success(id, serde_json::json!({ "response": translated }))
+        }
+        "decode_stream_chunk" => {
+            // 压缩的流式响应：按租约逐块解压，done 表示上游流已结束
+            let Some(stream_id) = request.params["lease_id"].as_str() else {
+                return JsonRpcResponse::error(id, -32602, "缺少 lease_id".to_string());
+            };
+            let chunk = request.params["chunk_base64"].as_str().unwrap_or("");
+            let encoding = request.params["content_encoding"].as_str();
+            let done = request.params["done"] == true;
+            match compression::decode_stream_chunk(stream_id, chunk, encoding, done) {
+                Ok(text) => JsonRpcResponse::success(id, serde_json::json!({ "text": text })),
+                Err(e) => JsonRpcResponse::error(id, -32602, e.to_string()),
+            }
+        }
+        "translate_stream_chunk_from_chat" => {
+            // 故障转移的流式响应：按租约逐个转换，done 表示上游流已结束
+            let Some(stream_id) = request.params["lease_id"].as_str() else {
+                return JsonRpcResponse::error(id, -32602, "缺少 lease_id".to_string());
+            };
+            let chunk = Some(&request.params["chunk"]).filter(|c| c.is_object());
+            let done = request.params["done"] == true;
+            let events = failover::translate_stream_chunk(stream_id, chunk, done);
+            JsonRpcResponse::success(id, serde_json::json!({ "events": events }))
+        }
+        "check_request_limits" => {
+            let size: limits::RequestSize = match serde_json::from_value(request.params.clone()) {
+                Ok(size) => size,
+                Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+            };
+            let result = match limits::check_request(&size) {
+                Some(violation) => serde_json::json!({ "allowed": false, "violation": violation }),
+                None => serde_json::json!({ "allowed": true }),
+            };
+            JsonRpcResponse::success(id, result)
+        }
         "transform_request" => {
             let request_body = request.params["request"].clone();
-            match provider::transform_request(request_body).await {
-                Ok(transformed) => {
-                    JsonRpcResponse::success(id, serde_json::json!({ "request": transformed }))
+            if let Some(violation) = limits::check_body(&request_body) {
+                return JsonRpcResponse::error_with_data(
+                    id,
+                    -32002,
+                    violation.message.clone(),
+                    serde_json::to_value(&violation).ok(),
+                );
+            }
+            let client_name = request.params["client_name"].as_str();
+            if provider::is_raw_passthrough(client_name, request.params["raw"] == true) {
+                let result = serde_json::json!({
+                    "request": request_body,
+                    "reassemble_stream": false,
+                    "raw_passthrough": true,
+                });
+                return JsonRpcResponse::success(id, result);
+            }
+            let profile = match request_profile(&request.params) {
+                Ok(profile) => profile,
+                Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+            };
+            // 超出上下文时先裁剪，结果随响应返回
+            let mut request_body = request_body;
+            let tenant_key = request.params["tenant_key"].as_str();
+            let trim = match provider::trim_context(&mut request_body, tenant_key).await {
+                Ok(trim) => trim,
+                Err(e) => return JsonRpcResponse::error(id, -32000, e.to_string()),
+            };
+            match provider::transform_request(request_body, profile.as_deref()).await {
+                Ok(mut transformed) => {
+                    let reassemble = provider::prepare_reassembly(&mut transformed, client_name);
+                    let mut result = serde_json::json!({
+                        "request": transformed,
+                        "reassemble_stream": reassemble,
+                    });
+                    if let Some(trim) = trim {
+                        result["headers"] = serde_json::json!(trim.headers());
+                        result["context_trim"] = serde_json::to_value(trim).unwrap_or_default();
+                    }
+                    JsonRpcResponse::success(id, result)
                 }
-                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+                Err(e) => match e.downcast_ref::<documents::DocumentViolation>() {
+                    Some(violation) => JsonRpcResponse::error_with_data(
+                        id,
+                        -32002,
+                        violation.to_string(),
+                        serde_json::to_value(violation).ok(),
+                    ),
+                    None => JsonRpcResponse::error(id, -32000, e.to_string()),
+                },
             }
         }
         "transform_response" => {
-            let response_body = request.params["response"].clone();
-            match provider::transform_response(response_body).await {
-                Ok(transformed) => {
-                    JsonRpcResponse::success(id, serde_json::json!({ "response": transformed }))
+            // 透传：原样返回（含压缩的 body_base64）
+            let client_name = request.params["client_name"].as_str();
+            if provider::is_raw_passthrough(client_name, request.params["raw"] == true) {
+                let result = match request.params.get("body_base64") {
+                    Some(body) => serde_json::json!({
+                        "body_base64": body,
+                        "content_encoding": request.params["content_encoding"],
+                    }),
+                    None => match request.params.get("response_path") {
+                        Some(path) => serde_json::json!({ "response_path": path }),
+                        None => serde_json::json!({ "response": request.params["response"] }),
+                    },
+                };
+                return JsonRpcResponse::success(id, result);
+            }
+            // 混沌模式：延迟（由宿主等待）或替换为注入的错误响应
+            let mut chaos_delay = std::time::Duration::ZERO;
+            if let Some(lease_id) = request.params["lease_id"].as_str() {
+                chaos_delay = chaos::take_delay(lease_id);
+                let fault = chaos::status_fault(lease_id);
+                if let Some(injected) = fault.and_then(|f| f.injected_response()) {
+                    return JsonRpcResponse::success(id, injected);
                 }
+            }
+            // 压缩的上游响应以 base64 传入，先解压；大响应由宿主写入文件后按路径传入
+            let response_path = request.params["response_path"].as_str();
+            let response_body = match request.params["body_base64"].as_str() {
+                Some(body) => {
+                    let encoding = request.params["content_encoding"].as_str();
+                    match compression::decode_json(body, encoding) {
+                        Ok(decoded) => decoded,
+                        Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+                    }
+                }
+                None => match response_path {
+                    Some(path) => match spool::read_json(
+                        &config::get_config().spool,
+                        std::path::Path::new(path),
+                    ) {
+                        Ok(body) => body,
+                        Err(e) => return JsonRpcResponse::error(id, -32602, format!("{:#}", e)),
+                    },
+                    None => request.params["response"].clone(),
+                },
+            };
+            let mut response_body = response_body;
+            let settings = config::get_config();
+            let repair = response_repair::validate(&settings.response_repair, &mut response_body);
+            let profile = match request_profile(&request.params) {
+                Ok(profile) => profile,
+                Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+            };
+            let transformed = provider::transform_response(response_body, profile.as_deref());
+            let mut transformed = match transformed.await {
+                Ok(transformed) => transformed,
+                Err(e) => return JsonRpcResponse::error(id, -32000, e.to_string()),
+            };
+            // 标明实际处理请求的凭证
+            let mut headers = repair.headers();
+            if let Some(lease_id) = request.params["lease_id"].as_str() {
+                let retries = request.params["retries"].as_u64().unwrap_or(0) as u32;
+                if let Some(info) = provider::serving_info(lease_id, retries).await {
+                    let meta = &settings.response_meta;
+                    let mode = settings.param_policy.mode;
+                    response_meta::inject(meta, mode, &mut transformed, &info);
+                    headers.extend(response_meta::headers(meta, &info));
+                }
+            }
+            // 等待同一请求的跟随者共享这份响应
+            if let Some(lease_id) = request.params["lease_id"].as_str() {
+                dedup::publish(lease_id, &transformed);
+            }
+            // 大响应写入临时文件，只返回路径
+            let spool = request.params["spool"] == true;
+            if spool::should_spool(&settings.spool, &transformed, spool) {
+                return match spool::write(&settings.spool, &transformed) {
+                    Ok(spooled) => {
+                        let mut result = serde_json::to_value(spooled).unwrap_or_default();
+                        result["headers"] = serde_json::json!(headers);
+                        if !chaos_delay.is_zero() {
+                            result["delay_ms"] = serde_json::json!(chaos_delay.as_millis() as u64);
+                        }
+                        if !repair.is_empty() {
+                            result["response_repair"] =
+                                serde_json::to_value(&repair).unwrap_or_default();
+                        }
+                        JsonRpcResponse::success(id, result)
+                    }
+                    Err(e) => JsonRpcResponse::error(id, -32000, format!("响应落盘失败: {}", e)),
+                };
+            }
+            // 本地客户端声明了 Accept-Encoding 时重新压缩
+            let mut result = match request.params["accept_encoding"].as_str() {
+                Some(accept) => {
+                    let config = &settings.compression;
+                    match compression::encode_json(&transformed, Some(accept), config) {
+                        Ok((body, encoding)) => serde_json::json!({
+                            "body_base64": body,
+                            "content_encoding": encoding.header_value(),
+                            "headers": headers,
+                        }),
+                        Err(e) => return JsonRpcResponse::error(id, -32000, e.to_string()),
+                    }
+                }
+                None => serde_json::json!({ "response": transformed, "headers": headers }),
+            };
+            if !repair.is_empty() {
+                result["response_repair"] = serde_json::to_value(&repair).unwrap_or_default();
+            }
+            if !chaos_delay.is_zero() {
+                result["delay_ms"] = serde_json::json!(chaos_delay.as_millis() as u64);
+            }
+            JsonRpcResponse::success(id, result)
+        }
+        "await_shared_response" => {
+            // 去重跟随者（acquire 返回 shared_response）等待原请求转换后的响应
+            let Some(lease_id) = request.params["lease_id"].as_str() else {
+                return JsonRpcResponse::error(id, -32602, "缺少 lease_id".to_string());
+            };
+            let max_wait_ms = config::get_config().dedup.max_wait_ms;
+            match dedup::wait(lease_id, max_wait_ms).await {
+                Ok(response) => {
+                    JsonRpcResponse::success(id, serde_json::json!({ "response": response }))
+                }
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "get_response_metadata" => {
+            // 流式响应在开始转发前取响应头
+            let lease_id = request.params["lease_id"].as_str().unwrap_or("");
+            let retries = request.params["retries"].as_u64().unwrap_or(0) as u32;
+            match provider::serving_info(lease_id, retries).await {
+                Some(info) => {
+                    let config = config::get_config().response_meta;
+                    let headers = response_meta::headers(&config, &info);
+                    JsonRpcResponse::success(
+                        id,
+                        serde_json::json!({ "serving": info, "headers": headers }),
+                    )
+                }
+                None => JsonRpcResponse::error(id, -32000, format!("租约不存在: {}", lease_id)),
+            }
+        }
+        "reassemble_stream" => {
+            // 长输出的事件由宿主写入文件后按路径传入，逐行重组
+            let reassembled = match request.params["events_path"].as_str() {
+                Some(path) => provider::reassemble_stream_file(std::path::Path::new(path)).await,
+                None => match request.params["events"].as_array() {
+                    Some(events) => provider::reassemble_stream(events).await,
+                    None => {
+                        return JsonRpcResponse::error(id, -32602, "Invalid events".to_string())
+                    }
+                },
+            };
+            let response = match reassembled {
+                Ok(response) => response,
+                Err(e) => return JsonRpcResponse::error(id, -32000, format!("{:#}", e)),
+            };
+            let config = config::get_config().spool;
+            if spool::should_spool(&config, &response, request.params["spool"] == true) {
+                return match spool::write(&config, &response) {
+                    Ok(spooled) => {
+                        JsonRpcResponse::success(id, serde_json::to_value(spooled).unwrap())
+                    }
+                    Err(e) => JsonRpcResponse::error(id, -32000, format!("响应落盘失败: {}", e)),
+                };
+            }
+            JsonRpcResponse::success(id, serde_json::json!({ "response": response }))
+        }
+        "transform_stream_chunk" => {
+            let chunk = request.params["chunk"].clone();
+            let client_name = request.params["client_name"].as_str();
+            let lease_id = request.params["lease_id"].as_str();
+            let raw = provider::is_raw_passthrough(client_name, request.params["raw"] == true);
+            let profile = match request_profile(&request.params) {
+                Ok(profile) => profile,
+                Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+            };
+            let profile = profile.as_deref();
+            let transformed =
+                provider::transform_stream_chunk(chunk, client_name, lease_id, raw, profile);
+            match transformed.await {
+                // 限速等待由宿主在下发前执行，主循环不阻塞
+                Ok((transformed, delay)) => JsonRpcResponse::success(
+                    id,
+                    serde_json::json!({
+                        "chunk": transformed,
+                        "delay_ms": delay.as_millis() as u64,
+                    }),
+                ),
                 Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
             }
         }
+        "get_config" => {
+            JsonRpcResponse::success(id, serde_json::to_value(config::get_config()).unwrap())
+        }
+        "validate_config" => {
+            let settings = Some(&request.params["settings"]).filter(|s| !s.is_null());
+            match config::validate_config(settings) {
+                Ok(report) => JsonRpcResponse::success(id, serde_json::to_value(report).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32602, e.to_string()),
+            }
+        }
+        "update_config" => {
+            let settings = request.params["settings"].clone();
+            match config::update_config(settings) {
+                Ok(updated) => JsonRpcResponse::success(id, serde_json::to_value(updated).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "run_doctor" => {
+            let report = doctor::run().await;
+            JsonRpcResponse::success(id, serde_json::to_value(report).unwrap())
+        }
+        "get_recent_logs" => {
+            let limit = request.params["limit"].as_u64().unwrap_or(200) as usize;
+            let contains = request.params["contains"].as_str();
+            let lines = logging::recent_logs(limit, contains);
+            JsonRpcResponse::success(id, serde_json::json!({ "lines": lines }))
+        }
         "apply_risk_control" => {
             let mut request_body = request.params["request"].clone();
             let credential_id = request.params["credential_id"].as_str().unwrap_or("");
@@ -268,10 +1220,396 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
         }
         "parse_error" => {
             let status = request.params["status"].as_u64().unwrap_or(0) as u16;
-            let body = request.params["body"].as_str().unwrap_or("");
-            let error = provider::parse_error(status, body);
+            // 错误页可能压缩或夹杂非 UTF-8 字节，以 base64 传入时按宽松 UTF-8 解码
+            let body = match request.params["body_base64"].as_str() {
+                Some(body) => {
+                    let encoding = request.params["content_encoding"].as_str();
+                    match compression::decode_text(body, encoding) {
+                        Ok(text) => text,
+                        Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+                    }
+                }
+                None => {
+                    body_text::strip_bom(request.params["body"].as_str().unwrap_or("")).to_string()
+                }
+            };
+            let model = request.params["model"].as_str();
+            let error = provider::parse_error(status, &body, model);
             JsonRpcResponse::success(id, serde_json::to_value(error).unwrap_or_default())
         }
+        "list_deprecated_models" => {
+            let models = deprecation::list_deprecated_models();
+            JsonRpcResponse::success(id, serde_json::to_value(models).unwrap())
+        }
+        "clear_model_deprecation" => {
+            let model = request.params["model"].as_str().unwrap_or("");
+            let cleared = deprecation::clear_deprecation(model);
+            JsonRpcResponse::success(id, serde_json::json!({ "cleared": cleared }))
+        }
+        "get_recovery_phrase" => match master_key::get_recovery_phrase() {
+            Ok(phrase) => JsonRpcResponse::success(id, serde_json::json!({ "phrase": phrase })),
+            Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+        },
+        "get_unlock_status" => {
+            JsonRpcResponse::success(id, serde_json::to_value(secret_lock::status()).unwrap())
+        }
+        "get_hook_status" => {
+            let status = hooks::status(&config::get_config().hooks);
+            JsonRpcResponse::success(id, serde_json::to_value(status).unwrap())
+        }
+        "get_app_lock_status" => {
+            JsonRpcResponse::success(id, serde_json::to_value(app_lock::status()).unwrap())
+        }
+        "unlock_app" => {
+            let passphrase = request.params["passphrase"].as_str().unwrap_or("");
+            match app_lock::unlock(passphrase) {
+                Ok(()) => {
+                    let unlocked = provider::retry_locked_credentials().await;
+                    JsonRpcResponse::success(
+                        id,
+                        serde_json::json!({ "success": true, "unlocked_credentials": unlocked }),
+                    )
+                }
+                Err(e) => JsonRpcResponse::error(id, -32004, e.to_string()),
+            }
+        }
+        "lock_app" => {
+            app_lock::lock();
+            JsonRpcResponse::success(id, serde_json::json!({ "success": true }))
+        }
+        "set_app_lock_passphrase" => {
+            let passphrase = request.params["passphrase"].as_str().unwrap_or("");
+            let current = request.params["current_passphrase"].as_str();
+            match app_lock::set_passphrase(passphrase, current) {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "remove_app_lock_passphrase" => {
+            let current = request.params["current_passphrase"].as_str().unwrap_or("");
+            match app_lock::remove_passphrase(current) {
+                Ok(()) => JsonRpcResponse::success(id, serde_json::json!({ "success": true })),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "recover_master_key" => {
+            let phrase = request.params["phrase"].as_str().unwrap_or("");
+            match master_key::recover_master_key(phrase) {
+                Ok(()) => {
+                    let unlocked = provider::retry_locked_credentials().await;
+                    JsonRpcResponse::success(
+                        id,
+                        serde_json::json!({ "unlocked_credentials": unlocked }),
+                    )
+                }
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "add_encryption_key" => match request.params["key"].as_str() {
+            Some(key) if !key.is_empty() => match key_ring::add_retired_key(key) {
+                Ok(key_id) => {
+                    let unlocked = provider::retry_locked_credentials().await;
+                    JsonRpcResponse::success(
+                        id,
+                        serde_json::json!({ "key_id": key_id, "unlocked_credentials": unlocked }),
+                    )
+                }
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            },
+            _ => JsonRpcResponse::error(id, -32602, "Invalid key".to_string()),
+        },
+        "update_pricing" => match pricing::update_remote().await {
+            Ok(table) => JsonRpcResponse::success(id, serde_json::to_value(table).unwrap()),
+            Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+        },
+        "get_pricing_table" => {
+            JsonRpcResponse::success(id, serde_json::json!({ "remote": pricing::remote_table() }))
+        }
+        "export_usage" => {
+            let query = usage_query(&request.params).and_then(|(range, tags)| {
+                optional_param::<usage::ExportFormat>(&request.params, "format")
+                    .map(|format| (range, tags, format))
+            });
+            let (range, tags, format) = match query {
+                Ok(query) => query,
+                Err(e) => return JsonRpcResponse::error(id, -32602, e),
+            };
+            let path = request.params["path"].as_str().map(std::path::Path::new);
+            stats::flush().await;
+            let org_names = provider::credential_org_names().await;
+            match usage::export_usage(&range, format, path, &org_names, &tags) {
+                Ok(export) => JsonRpcResponse::success(id, serde_json::to_value(export).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "get_account_usage" => {
+            let (range, tags) = match usage_query(&request.params) {
+                Ok(query) => query,
+                Err(e) => return JsonRpcResponse::error(id, -32602, e),
+            };
+            stats::flush().await;
+            let accounts = provider::credential_accounts().await;
+            match usage::load_records() {
+                Ok(records) => {
+                    let records = usage::filter_by_tags(records, &tags);
+                    let rows = usage::aggregate_by_account(&records, &range, &accounts);
+                    JsonRpcResponse::success(id, serde_json::to_value(rows).unwrap())
+                }
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "get_client_usage" => {
+            let (range, tags) = match usage_query(&request.params) {
+                Ok(query) => query,
+                Err(e) => return JsonRpcResponse::error(id, -32602, e),
+            };
+            stats::flush().await;
+            match usage::load_records() {
+                Ok(records) => {
+                    let records = usage::filter_by_tags(records, &tags);
+                    let clients = usage::aggregate_by_client(&records, &range);
+                    JsonRpcResponse::success(id, serde_json::to_value(clients).unwrap())
+                }
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "get_tag_usage" => {
+            let Some(key) = request.params["key"].as_str() else {
+                return JsonRpcResponse::error(id, -32602, "缺少 key".to_string());
+            };
+            let (range, tags) = match usage_query(&request.params) {
+                Ok(query) => query,
+                Err(e) => return JsonRpcResponse::error(id, -32602, e),
+            };
+            stats::flush().await;
+            match usage::load_records() {
+                Ok(records) => {
+                    let records = usage::filter_by_tags(records, &tags);
+                    let values = usage::aggregate_by_tag(&records, &range, key);
+                    JsonRpcResponse::success(id, serde_json::to_value(values).unwrap())
+                }
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "broadcast_request" => {
+            let targets: Vec<broadcast::BroadcastTarget> =
+                match serde_json::from_value(request.params["targets"].clone()) {
+                    Ok(targets) => targets,
+                    Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+                };
+            let tenant_key = request.params["tenant_key"].as_str().map(str::to_string);
+            let body = request.params["request"].clone();
+            match broadcast::broadcast(body, targets, tenant_key).await {
+                Ok(results) => JsonRpcResponse::success(id, serde_json::to_value(results).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "submit_batch" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let items: Vec<batch::BatchItem> =
+                match serde_json::from_value(request.params["requests"].clone()) {
+                    Ok(items) => items,
+                    Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+                };
+            let tenant_key = request.params["tenant_key"].as_str();
+            match batch::submit_batch(credential_id, items, tenant_key).await {
+                Ok(job) => JsonRpcResponse::success(id, serde_json::to_value(job).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "get_batch" => {
+            let batch_id = request.params["batch_id"].as_str().unwrap_or("");
+            let tenant_key = request.params["tenant_key"].as_str();
+            match batch::get_batch(batch_id, tenant_key).await {
+                Ok(job) => JsonRpcResponse::success(id, serde_json::to_value(job).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "list_batches" => {
+            let tenant_key = request.params["tenant_key"].as_str();
+            match batch::list_batches(tenant_key).await {
+                Ok(jobs) => JsonRpcResponse::success(id, serde_json::to_value(jobs).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "cancel_batch" => {
+            let batch_id = request.params["batch_id"].as_str().unwrap_or("");
+            let tenant_key = request.params["tenant_key"].as_str();
+            match batch::cancel_batch(batch_id, tenant_key).await {
+                Ok(job) => JsonRpcResponse::success(id, serde_json::to_value(job).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "get_batch_results" => {
+            let batch_id = request.params["batch_id"].as_str().unwrap_or("");
+            let tenant_key = request.params["tenant_key"].as_str();
+            match batch::get_batch_results(batch_id, tenant_key).await {
+                Ok(results) => JsonRpcResponse::success(id, serde_json::to_value(results).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "setup_detect_factory_cli" => {
+            let result = setup::detect_factory_cli();
+            JsonRpcResponse::success(id, serde_json::to_value(result).unwrap())
+        }
+        "setup_import_credential" => {
+            let auth_type = request.params["auth_type"].as_str();
+            let config = request
+                .params
+                .get("config")
+                .filter(|c| !c.is_null())
+                .cloned();
+            let result = setup::import_credential(auth_type, config).await;
+            JsonRpcResponse::success(id, serde_json::to_value(result).unwrap())
+        }
+        "setup_start_oauth" => {
+            let result = setup::start_oauth();
+            JsonRpcResponse::success(id, serde_json::to_value(result).unwrap())
+        }
+        "setup_validate" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let result = setup::validate(credential_id).await;
+            JsonRpcResponse::success(id, serde_json::to_value(result).unwrap())
+        }
+        "setup_select_endpoint" | "setup_test_request" => {
+            let credential_id = request.params["credential_id"].as_str().unwrap_or("");
+            let endpoint_type: EndpointType =
+                match serde_json::from_value(request.params["endpoint_type"].clone()) {
+                    Ok(endpoint_type) => endpoint_type,
+                    Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+                };
+            let result = if request.method == "setup_select_endpoint" {
+                setup::select_endpoint(credential_id, endpoint_type).await
+            } else {
+                setup::test_request(credential_id, endpoint_type).await
+            };
+            JsonRpcResponse::success(id, serde_json::to_value(result).unwrap())
+        }
+        "purge_usage" => {
+            let older_than_days = request.params["older_than_days"].as_u64().map(|d| d as u32);
+            let all = request.params["all"] == true;
+            match retention::purge_usage(older_than_days, all).await {
+                Ok(result) => JsonRpcResponse::success(id, serde_json::to_value(result).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32602, e.to_string()),
+            }
+        }
+        "get_daily_digest" => {
+            let date = serde_json::from_value(request.params["date"].clone()).ok();
+            stats::flush().await;
+            match digest::daily_digest(date).await {
+                Ok(digest) => JsonRpcResponse::success(id, serde_json::to_value(digest).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "startup_complete" => {
+            // 宿主已加载完所有凭证，不再等待
+            startup::mark_ready("宿主加载完成");
+            JsonRpcResponse::success(id, serde_json::json!({ "success": true }))
+        }
+        "get_startup_status" => JsonRpcResponse::success(
+            id,
+            serde_json::json!({ "ready": startup::is_ready(), "queued": startup::queued() }),
+        ),
+        "get_maintenance_status" => {
+            JsonRpcResponse::success(id, serde_json::to_value(maintenance::status()).unwrap())
+        }
+        "clear_maintenance" => {
+            maintenance::clear();
+            JsonRpcResponse::success(id, serde_json::json!({ "success": true }))
+        }
+        "get_store_lock_status" => {
+            JsonRpcResponse::success(id, serde_json::to_value(store_lock::status()).unwrap())
+        }
+        "retry_store_lock" => match store_lock::acquire("json_rpc") {
+            Ok(status) => JsonRpcResponse::success(id, serde_json::to_value(status).unwrap()),
+            Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+        },
+        "pause" => {
+            control::pause(request.params["reason"].as_str());
+            JsonRpcResponse::success(id, serde_json::json!({ "paused": true }))
+        }
+        "resume" => {
+            control::resume();
+            JsonRpcResponse::success(id, serde_json::json!({ "paused": false }))
+        }
+        "get_autostart_status" => match autostart::status() {
+            Ok(status) => JsonRpcResponse::success(id, serde_json::to_value(status).unwrap()),
+            Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+        },
+        "set_autostart" => {
+            let enabled = request.params["enabled"].as_bool().unwrap_or(true);
+            let minimized = request.params["minimized"].as_bool().unwrap_or(true);
+            let result = if !enabled {
+                autostart::disable()
+            } else {
+                // 宿主必须传入自身的启动命令（本插件只是宿主的子进程）
+                let Some(program) = request.params["program"].as_str() else {
+                    return JsonRpcResponse::error(id, -32602, "缺少 program".to_string());
+                };
+                let args = match &request.params["args"] {
+                    serde_json::Value::Null => Vec::new(),
+                    value => match serde_json::from_value(value.clone()) {
+                        Ok(args) => args,
+                        Err(e) => {
+                            return JsonRpcResponse::error(
+                                id,
+                                -32602,
+                                format!("args 必须是字符串数组: {}", e),
+                            )
+                        }
+                    },
+                };
+                let entry = autostart::AutostartEntry::new(program.to_string(), args, minimized);
+                if let Err(e) = entry.validate() {
+                    return JsonRpcResponse::error(id, -32602, e.to_string());
+                }
+                autostart::enable(&entry)
+            };
+            match result {
+                Ok(status) => JsonRpcResponse::success(id, serde_json::to_value(status).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "get_tray_menu" => {
+            let endpoint = request.params["local_endpoint"].as_str().map(String::from);
+            let status = tray::status(endpoint).await;
+            JsonRpcResponse::success(
+                id,
+                serde_json::json!({
+                    "tooltip": status.tooltip(),
+                    "items": tray::menu(&status),
+                    "status": status,
+                }),
+            )
+        }
+        "tray_action" => {
+            let action: tray::TrayAction =
+                match serde_json::from_value(request.params["action"].clone()) {
+                    Ok(action) => action,
+                    Err(e) => return JsonRpcResponse::error(id, -32602, e.to_string()),
+                };
+            if matches!(
+                action,
+                tray::TrayAction::Resume | tray::TrayAction::TogglePause
+            ) {
+                if let Err(e) = app_lock::ensure_unlocked() {
+                    return JsonRpcResponse::error(id, -32004, e.to_string());
+                }
+            }
+            let endpoint = request.params["local_endpoint"].as_str().map(String::from);
+            match tray::run_action(action, endpoint).await {
+                Ok(result) => JsonRpcResponse::success(id, serde_json::to_value(result).unwrap()),
+                Err(e) => JsonRpcResponse::error(id, -32000, e.to_string()),
+            }
+        }
+        "drain_events" => {
+            let events = events::drain_events();
+            JsonRpcResponse::success(id, serde_json::to_value(events).unwrap())
+        }
+        "drain_stream_progress" => {
+            let snapshots = stream_progress::drain();
+            JsonRpcResponse::success(id, serde_json::to_value(snapshots).unwrap())
+        }
         _ => JsonRpcResponse::error(id, -32601, format!("Method not found: {}", request.method)),
     }
 }
@@ -334,3 +1672,52 @@ fn get_plugin_info() -> serde_json::Value {
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rpc(method: &str, params: serde_json::Value) -> JsonRpcRequest {
+        JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params,
+            id: serde_json::json!(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_malformed_acquire_options_rejected() {
+        let response = handle_request(rpc(
+            "acquire_credential",
+            serde_json::json!({ "model": "claude-sonnet-4", "raw": "yes" }),
+        ))
+        .await;
+        assert_eq!(response.error.map(|e| e.code), Some(-32602));
+    }
+
+    #[tokio::test]
+    async fn test_queued_acquire_completes_after_resume() {
+        // 暂停排队时 acquire_credential 在后台等待，resume 照常处理并放行它
+        config::set_data_dir(
+            std::env::temp_dir().join(format!("droid-provider-rpc-{}", std::process::id())),
+        );
+        config::update_config(serde_json::json!({ "pause": { "behavior": "queue" } })).unwrap();
+        startup::mark_ready("test");
+        handle_request(rpc("pause", serde_json::json!({}))).await;
+
+        let acquire = tokio::spawn(handle_request(rpc(
+            "acquire_credential",
+            serde_json::json!({ "model": "claude-sonnet-4" }),
+        )));
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(!acquire.is_finished());
+
+        let response = handle_request(rpc("resume", serde_json::json!({}))).await;
+        assert!(response.error.is_none());
+        tokio::time::timeout(std::time::Duration::from_secs(5), acquire)
+            .await
+            .expect("恢复后排队的请求应结束")
+            .unwrap();
+    }
+}
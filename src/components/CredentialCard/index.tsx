@@ -26,7 +26,9 @@ import {
   Mail,
   Building,
   User,
+  Users,
 } from "@proxycast/plugin-components";
+import type { CredentialErrorRecord } from "@proxycast/plugin-components";
 import type { CredentialCardProps } from "./types";
 import { AUTH_TYPE_LABELS, AUTH_TYPE_COLORS, ENDPOINT_TYPE_LABELS } from "./types";
 
@@ -63,6 +65,17 @@ function formatDate(dateStr: unknown): string {
   }
 }
 
+/** 卡片上显示的最近错误条数 */
+const VISIBLE_ERROR_COUNT = 3;
+
+/**
+ * 错误记录的简短描述
+ */
+function describeError(error: CredentialErrorRecord): string {
+  const status = error.status_code ? `${error.status_code} ` : "";
+  return `${status}${error.message || error.error_type || "未知错误"}`;
+}
+
 /**
  * 检查是否支持 Token 刷新
  */
@@ -104,6 +117,16 @@ export function CredentialCard({
   const userId = typeof data.user_id === "string" ? data.user_id : "";
   const expiresAt = typeof data.expires_at === "string" ? data.expires_at : null;
   const lastRefresh = typeof data.last_refresh === "string" ? data.last_refresh : null;
+  const linkedAccount =
+    credential.linked_account ||
+    (typeof data.linked_account === "string" ? data.linked_account : "");
+  // 错误记录旧的在前，显示时取最新的几条
+  const recentErrors = (
+    credential.recent_errors ||
+    (Array.isArray(data.recent_errors) ? (data.recent_errors as CredentialErrorRecord[]) : [])
+  )
+    .slice(-VISIBLE_ERROR_COUNT)
+    .reverse();
 
   return (
     <Card className={`${credential.is_disabled ? "opacity-60" : ""}`}>
@@ -177,6 +200,15 @@ export function CredentialCard({
             </div>
           ) : null}
 
+          {/* 同账号的其他凭证 */}
+          {linkedAccount && (
+            <div className="flex items-center gap-2" title="与其他凭证属于同一账号，同一组织下共享额度">
+              <Users className="h-4 w-4 text-muted-foreground" />
+              <span className="text-muted-foreground">同账号:</span>
+              <span className="truncate">{linkedAccount}</span>
+            </div>
+          )}
+
           {/* API Key 数量 (API Key) */}
           {authType === "api_key" && (
             <div className="flex items-center gap-2">
@@ -211,12 +243,26 @@ export function CredentialCard({
         <div className="flex items-center gap-4 text-sm text-muted-foreground">
           <span>使用: {credential.usage_count || 0} 次</span>
           <span>错误: {credential.error_count || 0} 次</span>
-          {credential.last_error && (
-            <span className="text-red-500 truncate">
-              最后错误: {credential.last_error}
-            </span>
-          )}
         </div>
+
+        {/* 最近错误 */}
+        {recentErrors.length > 0 && (
+          <div className="space-y-1 text-sm">
+            <span className="text-muted-foreground">最近错误:</span>
+            {recentErrors.map((error, index) => (
+              <div
+                key={`${error.timestamp}-${index}`}
+                className="flex items-center gap-2 text-red-500"
+                title={error.request_id ? `请求 ID: ${error.request_id}` : undefined}
+              >
+                <span className="shrink-0 text-muted-foreground">
+                  {formatDate(error.timestamp)}
+                </span>
+                <span className="truncate">{describeError(error)}</span>
+              </div>
+            ))}
+          </div>
+        )}
       </CardContent>
 
       <CardFooter className="flex justify-end gap-2">
@@ -104,6 +104,7 @@ declare module "@proxycast/plugin-components" {
   export const Mail: ComponentType<{ className?: string }>;
   export const Building: ComponentType<{ className?: string }>;
   export const User: ComponentType<{ className?: string }>;
+  export const Users: ComponentType<{ className?: string }>;
   export const Trash2: ComponentType<{ className?: string }>;
   export const Edit: ComponentType<{ className?: string }>;
   export const RotateCcw: ComponentType<{ className?: string }>;
@@ -127,6 +128,16 @@ declare module "@proxycast/plugin-components" {
   };
 
   // 类型
+  /** 凭证的一条错误记录 */
+  export interface CredentialErrorRecord {
+    /** 发生时间 (RFC3339) */
+    timestamp: string;
+    status_code?: number | null;
+    error_type?: string | null;
+    message?: string | null;
+    request_id?: string | null;
+  }
+
   export interface CredentialDisplay {
     uuid: string;
     name: string | null;
@@ -135,7 +146,10 @@ declare module "@proxycast/plugin-components" {
     health_status: string;
     usage_count: number;
     error_count: number;
-    last_error: string | null;
+    /** 最近的错误记录（旧的在前） */
+    recent_errors?: CredentialErrorRecord[];
+    /** 与其他凭证属于同一账号时为该账号（邮箱或 user_id） */
+    linked_account?: string | null;
     credential_data: Record<string, unknown>;
     created_at: string;
     updated_at: string;
@@ -104,11 +104,24 @@ declare module "@proxycast/plugin-components" {
     priority?: number;
     tags?: string[];
     source?: CredentialSource;
-    last_error?: string;
+    /** 最近的错误记录（旧的在前） */
+    recent_errors?: CredentialErrorRecord[];
+    /** 与其他凭证属于同一账号时为该账号（邮箱或 user_id） */
+    linked_account?: string | null;
     auth_type?: string;
     credential_data?: Record<string, unknown>;
   }
 
+  /** 凭证的一条错误记录 */
+  export interface CredentialErrorRecord {
+    /** 发生时间 (RFC3339) */
+    timestamp: string;
+    status_code?: number | null;
+    error_type?: string | null;
+    message?: string | null;
+    request_id?: string | null;
+  }
+
   export interface HealthCheckResult { success: boolean; message?: string; }
 
   export const providerPoolApi: {